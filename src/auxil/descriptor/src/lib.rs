@@ -0,0 +1,450 @@
+//! Growable descriptor set allocator.
+//!
+//! Descriptor sets are allocated from a list of descriptor pools, and a new
+//! pool is created whenever the existing ones are exhausted. New pools are
+//! sized after the descriptors of all the sets allocated so far, and hold
+//! more sets than the previous ones, up to a limit.
+
+#[macro_use]
+extern crate log;
+
+use hal::device::{Device as _, OutOfMemory};
+use hal::pso::{
+    AllocationError,
+    DescriptorPool as _,
+    DescriptorPoolCreateFlags,
+    DescriptorRangeDesc,
+    DescriptorSetLayoutBinding,
+    DescriptorType,
+};
+use hal::Backend;
+
+use std::collections::HashMap;
+
+/// Number of descriptors of each type in a set layout.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DescriptorCounts {
+    counts: Vec<(DescriptorType, usize)>,
+}
+
+impl DescriptorCounts {
+    /// Count the descriptors of the bindings of a set layout.
+    pub fn from_bindings(bindings: &[DescriptorSetLayoutBinding]) -> Self {
+        let mut counts = DescriptorCounts::default();
+        for binding in bindings {
+            counts.add(binding.ty, binding.count);
+        }
+        counts
+    }
+
+    /// Add `count` descriptors of type `ty`.
+    pub fn add(&mut self, ty: DescriptorType, count: usize) {
+        match self.counts.iter_mut().find(|&&mut (t, _)| t == ty) {
+            Some(&mut (_, ref mut total)) => *total += count,
+            None => self.counts.push((ty, count)),
+        }
+    }
+
+    /// Get the ranges of a pool holding exactly these descriptors.
+    pub fn ranges<'a>(&'a self) -> impl 'a + Iterator<Item = DescriptorRangeDesc> {
+        self.counts
+            .iter()
+            .map(|&(ty, count)| DescriptorRangeDesc { ty, count })
+    }
+}
+
+/// Configuration of a descriptor allocator.
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    /// Number of sets of the first pool. Every new pool holds twice as many
+    /// sets as the previous one.
+    pub sets_per_pool: usize,
+    /// Limit of the number of sets of a pool.
+    pub max_sets_per_pool: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            sets_per_pool: 64,
+            max_sets_per_pool: 4096,
+        }
+    }
+}
+
+/// Allocation statistics.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Number of descriptor pools.
+    pub pools: usize,
+    /// Number of live descriptor sets.
+    pub sets: usize,
+}
+
+#[derive(Debug)]
+struct Pool<B: Backend> {
+    raw: B::DescriptorPool,
+    max_sets: usize,
+    /// Number of live sets allocated from the pool.
+    sets: usize,
+}
+
+/// Descriptor set given out by a `DescriptorAllocator`.
+#[derive(Debug)]
+pub struct DescriptorSet<B: Backend> {
+    raw: B::DescriptorSet,
+    pool: usize,
+}
+
+impl<B: Backend> DescriptorSet<B> {
+    /// Get the raw descriptor set.
+    pub fn raw(&self) -> &B::DescriptorSet {
+        &self.raw
+    }
+
+    /// Get the raw descriptor set, to update it.
+    pub fn raw_mut(&mut self) -> &mut B::DescriptorSet {
+        &mut self.raw
+    }
+}
+
+/// Allocator of descriptor sets over a growing list of descriptor pools.
+///
+/// The pools are released with `dispose`.
+#[derive(Debug)]
+pub struct DescriptorAllocator<B: Backend> {
+    config: Config,
+    pools: Vec<Pool<B>>,
+    /// Descriptors of all the sets allocated so far, to size the new pools.
+    usage: HashMap<DescriptorType, usize>,
+    /// Number of sets allocated so far.
+    total_sets: usize,
+}
+
+impl<B: Backend> DescriptorAllocator<B> {
+    /// Create an allocator without any pool.
+    pub fn new(config: Config) -> Self {
+        DescriptorAllocator {
+            config,
+            pools: Vec::new(),
+            usage: HashMap::new(),
+            total_sets: 0,
+        }
+    }
+
+    /// Get the allocation statistics.
+    pub fn stats(&self) -> Stats {
+        Stats {
+            pools: self.pools.len(),
+            sets: self.pools.iter().map(|pool| pool.sets).sum(),
+        }
+    }
+
+    /// Allocate `count` sets of a layout, with the descriptors of `counts`,
+    /// into `sets`.
+    ///
+    /// Pools are created when the existing ones are exhausted. On failure,
+    /// the sets allocated by this call are freed.
+    ///
+    /// # Safety
+    ///
+    /// `counts` has to match the bindings of `layout`, and `device` has to be
+    /// the same for all the calls to this allocator.
+    pub unsafe fn allocate(
+        &mut self,
+        device: &B::Device,
+        layout: &B::DescriptorSetLayout,
+        counts: &DescriptorCounts,
+        count: usize,
+        sets: &mut Vec<DescriptorSet<B>>,
+    ) -> Result<(), AllocationError> {
+        self.total_sets += count;
+        for &(ty, descriptors) in &counts.counts {
+            *self.usage.entry(ty).or_insert(0) += descriptors * count;
+        }
+
+        let start = sets.len();
+        for _ in 0 .. count {
+            match self.allocate_one(device, layout, counts) {
+                Ok(set) => sets.push(set),
+                Err(e) => {
+                    let allocated = sets.drain(start ..).collect::<Vec<_>>();
+                    self.free(allocated);
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    unsafe fn allocate_one(
+        &mut self,
+        device: &B::Device,
+        layout: &B::DescriptorSetLayout,
+        counts: &DescriptorCounts,
+    ) -> Result<DescriptorSet<B>, AllocationError> {
+        // the newest pools are the largest ones and the least likely to be full
+        for (index, pool) in self.pools.iter_mut().enumerate().rev() {
+            if pool.sets == pool.max_sets {
+                continue;
+            }
+            match pool.raw.allocate_set(layout) {
+                Ok(raw) => {
+                    pool.sets += 1;
+                    return Ok(DescriptorSet { raw, pool: index });
+                }
+                Err(AllocationError::OutOfPoolMemory)
+                | Err(AllocationError::FragmentedPool)
+                | Err(AllocationError::IncompatibleLayout) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        let index = self.add_pool(device, counts)?;
+        let pool = &mut self.pools[index];
+        let raw = pool.raw.allocate_set(layout)?;
+        pool.sets += 1;
+        Ok(DescriptorSet { raw, pool: index })
+    }
+
+    /// Create a pool sized after the usage so far, able to hold at least one
+    /// set with the descriptors of `counts`.
+    unsafe fn add_pool(
+        &mut self,
+        device: &B::Device,
+        counts: &DescriptorCounts,
+    ) -> Result<usize, AllocationError> {
+        let mut max_sets = self.config.sets_per_pool.max(1);
+        for _ in 0 .. self.pools.len() {
+            if max_sets >= self.config.max_sets_per_pool {
+                break;
+            }
+            max_sets *= 2;
+        }
+        let max_sets = max_sets.min(self.config.max_sets_per_pool);
+        let total_sets = self.total_sets.max(1);
+        let mut ranges = DescriptorCounts::default();
+        for (&ty, &descriptors) in &self.usage {
+            // rounded up, so each type gets at least one descriptor
+            let scaled = descriptors * max_sets;
+            let mut average = scaled / total_sets;
+            if average * total_sets < scaled {
+                average += 1;
+            }
+            let needed = counts
+                .counts
+                .iter()
+                .find(|&&(t, _)| t == ty)
+                .map_or(0, |&(_, count)| count);
+            ranges.add(ty, average.max(needed));
+        }
+        ranges.counts.retain(|&(_, count)| count != 0);
+        ranges.counts.sort_by_key(|&(ty, _)| ty as u32);
+        debug!(
+            "Creating a descriptor pool of {} sets with {:?}",
+            max_sets, ranges.counts
+        );
+
+        let raw = device
+            .create_descriptor_pool(
+                max_sets,
+                ranges.ranges(),
+                DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET,
+            )
+            .map_err(|e| match e {
+                OutOfMemory::Host => AllocationError::Host,
+                OutOfMemory::Device => AllocationError::Device,
+            })?;
+        self.pools.push(Pool {
+            raw,
+            max_sets,
+            sets: 0,
+        });
+        Ok(self.pools.len() - 1)
+    }
+
+    /// Free descriptor sets of this allocator.
+    ///
+    /// Pools left without sets are reset, to recycle their memory without
+    /// fragmentation.
+    ///
+    /// # Safety
+    ///
+    /// The sets must not be in use by the device anymore.
+    pub unsafe fn free<I>(&mut self, sets: I)
+    where
+        I: IntoIterator<Item = DescriptorSet<B>>,
+    {
+        let mut freed = Vec::new();
+        for set in sets {
+            if freed.len() <= set.pool {
+                freed.resize_with(set.pool + 1, Vec::new);
+            }
+            freed[set.pool].push(set.raw);
+        }
+        for (pool, raw_sets) in self.pools.iter_mut().zip(freed) {
+            if raw_sets.is_empty() {
+                continue;
+            }
+            pool.sets -= raw_sets.len();
+            if pool.sets == 0 {
+                drop(raw_sets);
+                pool.raw.reset();
+            } else {
+                pool.raw.free_sets(raw_sets);
+            }
+        }
+    }
+
+    /// Reset all the pools, e.g. at the start of a frame.
+    ///
+    /// # Safety
+    ///
+    /// All the sets of this allocator become invalid, they must not be in
+    /// use by the device anymore and have to be dropped instead of freed.
+    pub unsafe fn reset(&mut self) {
+        for pool in &mut self.pools {
+            pool.raw.reset();
+            pool.sets = 0;
+        }
+    }
+
+    /// Destroy all the pools of the allocator.
+    ///
+    /// # Safety
+    ///
+    /// The sets must not be in use by the device anymore.
+    pub unsafe fn dispose(self, device: &B::Device) {
+        let sets = self.stats().sets;
+        if sets != 0 {
+            warn!("{} descriptor sets are still allocated", sets);
+        }
+        for pool in self.pools {
+            device.destroy_descriptor_pool(pool.raw);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hal::pso::ShaderStageFlags;
+
+    use std::iter;
+
+    type Empty = gfx_backend_empty::Backend;
+
+    const CONFIG: Config = Config {
+        sets_per_pool: 2,
+        max_sets_per_pool: 8,
+    };
+
+    fn binding(binding: u32, ty: DescriptorType, count: usize) -> DescriptorSetLayoutBinding {
+        DescriptorSetLayoutBinding {
+            binding,
+            ty,
+            count,
+            stage_flags: ShaderStageFlags::ALL,
+            immutable_samplers: false,
+        }
+    }
+
+    fn open() -> <Empty as Backend>::Device {
+        let instance = gfx_backend_empty::Instance::create("descriptor", 1).unwrap();
+        gfx_backend_empty::test_utils::open(&instance).1.device
+    }
+
+    #[test]
+    fn test_counts() {
+        let counts = DescriptorCounts::from_bindings(&[
+            binding(0, DescriptorType::UniformBuffer, 1),
+            binding(1, DescriptorType::SampledImage, 4),
+            binding(2, DescriptorType::UniformBuffer, 2),
+        ]);
+        let ranges = counts
+            .ranges()
+            .map(|range| (range.ty, range.count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ranges,
+            vec![
+                (DescriptorType::UniformBuffer, 3),
+                (DescriptorType::SampledImage, 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_growth() {
+        let device = open();
+        let bindings = [binding(0, DescriptorType::UniformBuffer, 1)];
+        let counts = DescriptorCounts::from_bindings(&bindings);
+        let mut allocator = DescriptorAllocator::<Empty>::new(CONFIG);
+        unsafe {
+            let layout = device
+                .create_descriptor_set_layout(
+                    &bindings,
+                    iter::empty::<<Empty as Backend>::Sampler>(),
+                )
+                .unwrap();
+            let mut sets = Vec::new();
+            // pools of 2, 4 and 8 sets
+            allocator
+                .allocate(&device, &layout, &counts, 14, &mut sets)
+                .unwrap();
+            assert_eq!(allocator.stats(), Stats { pools: 3, sets: 14 });
+            // the largest pool is capped
+            allocator
+                .allocate(&device, &layout, &counts, 1, &mut sets)
+                .unwrap();
+            assert_eq!(allocator.stats(), Stats { pools: 4, sets: 15 });
+
+            // freed sets are recycled without new pools
+            let freed = sets.drain(.. 6).collect::<Vec<_>>();
+            allocator.free(freed);
+            allocator
+                .allocate(&device, &layout, &counts, 6, &mut sets)
+                .unwrap();
+            assert_eq!(allocator.stats(), Stats { pools: 4, sets: 15 });
+
+            sets.clear();
+            allocator.reset();
+            assert_eq!(allocator.stats(), Stats { pools: 4, sets: 0 });
+            allocator.dispose(&device);
+        }
+    }
+
+    #[test]
+    fn test_new_descriptor_types() {
+        let device = open();
+        let uniforms = [binding(0, DescriptorType::UniformBuffer, 1)];
+        let images = [binding(0, DescriptorType::SampledImage, 3)];
+        let mut allocator = DescriptorAllocator::<Empty>::new(CONFIG);
+        unsafe {
+            let uniform_layout = device
+                .create_descriptor_set_layout(
+                    &uniforms,
+                    iter::empty::<<Empty as Backend>::Sampler>(),
+                )
+                .unwrap();
+            let image_layout = device
+                .create_descriptor_set_layout(&images, iter::empty::<<Empty as Backend>::Sampler>())
+                .unwrap();
+            let mut sets = Vec::new();
+            let counts = DescriptorCounts::from_bindings(&uniforms);
+            allocator
+                .allocate(&device, &uniform_layout, &counts, 1, &mut sets)
+                .unwrap();
+            // the first pool has no image descriptors, so a second one is needed
+            let counts = DescriptorCounts::from_bindings(&images);
+            allocator
+                .allocate(&device, &image_layout, &counts, 2, &mut sets)
+                .unwrap();
+            assert_eq!(allocator.stats(), Stats { pools: 2, sets: 3 });
+
+            allocator.free(sets);
+            assert_eq!(allocator.stats().sets, 0);
+            allocator.dispose(&device);
+        }
+    }
+}
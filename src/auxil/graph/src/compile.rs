@@ -0,0 +1,556 @@
+use crate::{BufferEntry, BufferId, ImageEntry, ImageId, ImageInfo, ImageRole, PassEntry, PassId};
+use gfx_track::{BufferUse, ImageUse};
+use hal::image;
+use hal::pass::{self, AttachmentLoadOp, AttachmentOps, AttachmentStoreOp};
+
+use std::ops::Range;
+
+/// Error of the graph compilation.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CompileError {
+    /// The pass uses the same image more than once.
+    DuplicateUse { pass: PassId, image: ImageId },
+    /// The pass has more than one depth-stencil attachment.
+    MultipleDepthStencil(PassId),
+    /// The attachments of the pass don't have the same size.
+    ExtentMismatch(PassId),
+    /// The pass reads an image that no previous pass writes.
+    UndefinedImage { pass: PassId, image: ImageId },
+    /// The pass reads a transient buffer that no previous pass writes.
+    UndefinedBuffer { pass: PassId, buffer: BufferId },
+}
+
+/// Render pass of a graphics pass, with a single subpass.
+#[derive(Clone, Debug)]
+pub struct RenderPassDesc {
+    pub attachments: Vec<pass::Attachment>,
+    /// Images bound to the attachments.
+    pub images: Vec<ImageId>,
+    pub colors: Vec<pass::AttachmentRef>,
+    pub depth_stencil: Option<pass::AttachmentRef>,
+    /// Size of the framebuffer.
+    pub extent: image::Extent,
+}
+
+/// Pass of a compiled graph.
+#[derive(Clone, Debug)]
+pub struct CompiledPass {
+    pub id: PassId,
+    pub name: String,
+    /// States the images have to be in during the pass.
+    pub images: Vec<(ImageId, ImageUse)>,
+    /// States the buffers have to be in during the pass.
+    pub buffers: Vec<(BufferId, BufferUse)>,
+    /// Render pass to record the pass in, if it has attachments.
+    pub render_pass: Option<RenderPassDesc>,
+}
+
+/// Graph with its passes in execution order, ready to be turned into frames.
+#[derive(Clone, Debug)]
+pub struct CompiledGraph {
+    pub(crate) images: Vec<ImageEntry>,
+    pub(crate) buffers: Vec<BufferEntry>,
+    passes: Vec<CompiledPass>,
+    culled: Vec<PassId>,
+    image_lifetimes: Vec<Option<Range<usize>>>,
+    image_usage: Vec<image::Usage>,
+    buffer_lifetimes: Vec<Option<Range<usize>>>,
+}
+
+impl CompiledGraph {
+    /// Get the passes to execute, in order.
+    pub fn passes(&self) -> &[CompiledPass] {
+        &self.passes
+    }
+
+    /// Get a pass to execute.
+    pub fn pass(&self, id: PassId) -> Option<&CompiledPass> {
+        self.passes.iter().find(|pass| pass.id == id)
+    }
+
+    /// Get the passes culled because nothing uses their results.
+    pub fn culled(&self) -> &[PassId] {
+        &self.culled
+    }
+
+    /// Get the description of an image.
+    pub fn image_info(&self, id: ImageId) -> &ImageInfo {
+        &self.images[id.0].info
+    }
+
+    /// Get the range of the executed passes using an image, if any.
+    pub fn image_lifetime(&self, id: ImageId) -> Option<Range<usize>> {
+        self.image_lifetimes[id.0].clone()
+    }
+
+    /// Get the usage an image is created with, from all the passes using it.
+    pub fn image_usage(&self, id: ImageId) -> image::Usage {
+        self.image_usage[id.0]
+    }
+
+    /// Get the range of the executed passes using a buffer, if any.
+    pub fn buffer_lifetime(&self, id: BufferId) -> Option<Range<usize>> {
+        self.buffer_lifetimes[id.0].clone()
+    }
+}
+
+fn extend(lifetime: &mut Option<Range<usize>>, index: usize) {
+    match *lifetime {
+        Some(ref mut range) => range.end = index + 1,
+        None => *lifetime = Some(index .. index + 1),
+    }
+}
+
+fn validate(images: &[ImageEntry], id: PassId, pass: &PassEntry) -> Result<(), CompileError> {
+    for (i, &(image, _)) in pass.images.iter().enumerate() {
+        if pass.images[.. i].iter().any(|&(other, _)| other == image) {
+            return Err(CompileError::DuplicateUse { pass: id, image });
+        }
+    }
+    let depth_stencils = pass
+        .images
+        .iter()
+        .filter(|&&(_, role)| role == ImageRole::DepthStencil || role == ImageRole::DepthRead)
+        .count();
+    if depth_stencils > 1 {
+        return Err(CompileError::MultipleDepthStencil(id));
+    }
+    let mut extents = pass
+        .images
+        .iter()
+        .filter(|&&(_, role)| role.is_attachment())
+        .map(|&(image, _)| images[image.0].info.kind.level_extent(0));
+    if let Some(first) = extents.next() {
+        if extents.any(|e| e.width != first.width || e.height != first.height) {
+            return Err(CompileError::ExtentMismatch(id));
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn compile(
+    images: &[ImageEntry],
+    buffers: &[BufferEntry],
+    passes: &[PassEntry],
+) -> Result<CompiledGraph, CompileError> {
+    for (index, pass) in passes.iter().enumerate() {
+        validate(images, PassId(index), pass)?;
+    }
+
+    // Walk the passes backwards from the outputs, keeping the ones
+    // writing a resource read later.
+    let mut needed_images = images
+        .iter()
+        .map(|image| image.import.is_some())
+        .collect::<Vec<_>>();
+    let mut needed_buffers = buffers
+        .iter()
+        .map(|buffer| buffer.imported)
+        .collect::<Vec<_>>();
+    let mut live = vec![false; passes.len()];
+    for (index, pass) in passes.iter().enumerate().rev() {
+        let writes_needed = pass
+            .images
+            .iter()
+            .any(|&(image, role)| role.writes() && needed_images[image.0])
+            || pass
+                .buffers
+                .iter()
+                .any(|&(buffer, state)| state.is_write() && needed_buffers[buffer.0]);
+        if !pass.side_effects && !writes_needed {
+            continue;
+        }
+        live[index] = true;
+        for &(image, role) in &pass.images {
+            if role.reads() {
+                needed_images[image.0] = true;
+            }
+        }
+        for &(buffer, state) in &pass.buffers {
+            if state.is_read() {
+                needed_buffers[buffer.0] = true;
+            }
+        }
+    }
+
+    let order = (0 .. passes.len()).filter(|&i| live[i]).collect::<Vec<_>>();
+    let mut image_lifetimes = vec![None; images.len()];
+    let mut image_usage = vec![image::Usage::empty(); images.len()];
+    let mut buffer_lifetimes = vec![None; buffers.len()];
+    for (index, &pass) in order.iter().enumerate() {
+        for &(image, role) in &passes[pass].images {
+            extend(&mut image_lifetimes[image.0], index);
+            image_usage[image.0] |= role.usage();
+        }
+        for &(buffer, _) in &passes[pass].buffers {
+            extend(&mut buffer_lifetimes[buffer.0], index);
+        }
+    }
+
+    // Contents of the resources defined at this point of the frame.
+    let mut defined_images = images
+        .iter()
+        .map(|image| match image.import {
+            Some(ref layouts) => layouts.start != image::Layout::Undefined,
+            None => false,
+        })
+        .collect::<Vec<_>>();
+    let mut defined_buffers = buffers
+        .iter()
+        .map(|buffer| buffer.imported)
+        .collect::<Vec<_>>();
+
+    let mut compiled = Vec::with_capacity(order.len());
+    for (index, &pass_index) in order.iter().enumerate() {
+        let pass = &passes[pass_index];
+        let id = PassId(pass_index);
+        let mut attachments = Vec::new();
+        let mut attachment_images = Vec::new();
+        let mut colors = Vec::new();
+        let mut depth_stencil = None;
+
+        for &(image, role) in &pass.images {
+            let entry = &images[image.0];
+            let defined = defined_images[image.0];
+            if role.reads() && !role.writes() && !defined {
+                return Err(CompileError::UndefinedImage { pass: id, image });
+            }
+            if !role.is_attachment() {
+                continue;
+            }
+            let layout = role.state().layout;
+            let load = if defined {
+                AttachmentLoadOp::Load
+            } else if entry.info.clear.is_some() {
+                AttachmentLoadOp::Clear
+            } else {
+                AttachmentLoadOp::DontCare
+            };
+            let used_later = match image_lifetimes[image.0] {
+                Some(ref lifetime) => lifetime.end > index + 1,
+                None => false,
+            };
+            let store = if entry.import.is_some() || used_later {
+                AttachmentStoreOp::Store
+            } else {
+                AttachmentStoreOp::DontCare
+            };
+            let ops = AttachmentOps::new(load, store);
+            // The image is already in the layout of the subpass, the
+            // barriers before the render pass transition it.
+            attachments.push(pass::Attachment {
+                format: Some(entry.info.format),
+                samples: entry.info.kind.num_samples(),
+                ops,
+                stencil_ops: if entry.info.format.is_stencil() {
+                    ops
+                } else {
+                    AttachmentOps::DONT_CARE
+                },
+                layouts: layout .. layout,
+            });
+            attachment_images.push(image);
+            let reference = (attachments.len() - 1, layout);
+            if role == ImageRole::Color {
+                colors.push(reference);
+            } else {
+                depth_stencil = Some(reference);
+            }
+        }
+        for &(image, role) in &pass.images {
+            if role.writes() {
+                defined_images[image.0] = true;
+            }
+        }
+
+        for &(buffer, state) in &pass.buffers {
+            if !state.is_write() && !defined_buffers[buffer.0] {
+                return Err(CompileError::UndefinedBuffer { pass: id, buffer });
+            }
+        }
+        for &(buffer, state) in &pass.buffers {
+            if state.is_write() {
+                defined_buffers[buffer.0] = true;
+            }
+        }
+
+        let render_pass = attachment_images.first().cloned().map(|first| {
+            let extent = images[first.0].info.kind.level_extent(0);
+            RenderPassDesc {
+                attachments,
+                images: attachment_images,
+                colors,
+                depth_stencil,
+                extent: image::Extent { depth: 1, ..extent },
+            }
+        });
+        compiled.push(CompiledPass {
+            id,
+            name: pass.name.clone(),
+            images: pass
+                .images
+                .iter()
+                .map(|&(image, role)| (image, role.state()))
+                .collect(),
+            buffers: pass.buffers.clone(),
+            render_pass,
+        });
+    }
+
+    Ok(CompiledGraph {
+        images: images.to_vec(),
+        buffers: buffers.to_vec(),
+        passes: compiled,
+        culled: (0 .. passes.len())
+            .filter(|&i| !live[i])
+            .map(PassId)
+            .collect(),
+        image_lifetimes,
+        image_usage,
+        buffer_lifetimes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GraphBuilder;
+    use hal::buffer;
+    use hal::command::{ClearColor, ClearValue};
+    use hal::format::Format;
+    use hal::pso::PipelineStage;
+
+    fn info(format: Format, clear: bool) -> ImageInfo {
+        ImageInfo {
+            kind: image::Kind::D2(64, 64, 1, 1),
+            levels: 1,
+            format,
+            clear: if clear {
+                Some(ClearValue {
+                    color: ClearColor {
+                        float32: [0.0, 0.0, 0.0, 1.0],
+                    },
+                })
+            } else {
+                None
+            },
+        }
+    }
+
+    fn ops(graph: &CompiledGraph, pass: PassId) -> Vec<AttachmentOps> {
+        graph
+            .pass(pass)
+            .unwrap()
+            .render_pass
+            .as_ref()
+            .unwrap()
+            .attachments
+            .iter()
+            .map(|attachment| attachment.ops)
+            .collect()
+    }
+
+    #[test]
+    fn test_deferred() {
+        let mut builder = GraphBuilder::new();
+        let backbuffer = builder.import_image(
+            info(Format::Bgra8Srgb, false),
+            image::Layout::Undefined .. image::Layout::Present,
+        );
+        let albedo = builder.create_image(info(Format::Rgba8Unorm, true));
+        let depth = builder.create_image(info(Format::D32SfloatS8Uint, true));
+        let gbuffer = builder
+            .pass("gbuffer")
+            .color(albedo)
+            .depth_stencil(depth)
+            .add();
+        let lighting = builder
+            .pass("lighting")
+            .color(backbuffer)
+            .depth_read(depth)
+            .sampled(albedo, PipelineStage::FRAGMENT_SHADER)
+            .add();
+        let graph = builder.compile().unwrap();
+
+        assert!(graph.culled().is_empty());
+        let clear_store = AttachmentOps::new(AttachmentLoadOp::Clear, AttachmentStoreOp::Store);
+        assert_eq!(ops(&graph, gbuffer), vec![clear_store, clear_store]);
+        assert_eq!(
+            ops(&graph, lighting),
+            vec![
+                AttachmentOps::new(AttachmentLoadOp::DontCare, AttachmentStoreOp::Store),
+                AttachmentOps::new(AttachmentLoadOp::Load, AttachmentStoreOp::DontCare),
+            ]
+        );
+
+        let desc = graph.pass(lighting).unwrap().render_pass.clone().unwrap();
+        assert_eq!(desc.images, vec![backbuffer, depth]);
+        assert_eq!(
+            desc.colors,
+            vec![(0, image::Layout::ColorAttachmentOptimal)]
+        );
+        assert_eq!(
+            desc.depth_stencil,
+            Some((1, image::Layout::DepthStencilReadOnlyOptimal))
+        );
+        assert_eq!(desc.attachments[1].stencil_ops, desc.attachments[1].ops);
+        assert_eq!(
+            desc.extent,
+            image::Extent {
+                width: 64,
+                height: 64,
+                depth: 1
+            }
+        );
+
+        assert_eq!(graph.image_lifetime(albedo), Some(0 .. 2));
+        assert_eq!(
+            graph.image_usage(albedo),
+            image::Usage::COLOR_ATTACHMENT | image::Usage::SAMPLED
+        );
+    }
+
+    #[test]
+    fn test_culling() {
+        let mut builder = GraphBuilder::new();
+        let output = builder.import_image(
+            info(Format::Rgba8Unorm, false),
+            image::Layout::Undefined .. image::Layout::TransferSrcOptimal,
+        );
+        let shadow = builder.create_image(info(Format::D32Sfloat, true));
+        let unused = builder.create_image(info(Format::Rgba8Unorm, true));
+        let buffer = builder.create_buffer(256, buffer::Usage::STORAGE);
+
+        let shadows = builder.pass("shadows").depth_stencil(shadow).add();
+        let debug = builder
+            .pass("debug")
+            .color(unused)
+            .sampled(shadow, PipelineStage::FRAGMENT_SHADER)
+            .add();
+        let compute = builder
+            .pass("compute")
+            .write_buffer(
+                buffer,
+                buffer::Access::SHADER_WRITE,
+                PipelineStage::COMPUTE_SHADER,
+            )
+            .add();
+        let main = builder
+            .pass("main")
+            .color(output)
+            .sampled(shadow, PipelineStage::FRAGMENT_SHADER)
+            .add();
+        let readback = builder
+            .pass("readback")
+            .read_buffer(
+                buffer,
+                buffer::Access::SHADER_READ,
+                PipelineStage::COMPUTE_SHADER,
+            )
+            .side_effects()
+            .add();
+        let graph = builder.compile().unwrap();
+
+        assert_eq!(graph.culled(), &[debug]);
+        let executed = graph
+            .passes()
+            .iter()
+            .map(|pass| pass.id)
+            .collect::<Vec<_>>();
+        assert_eq!(executed, vec![shadows, compute, main, readback]);
+        assert_eq!(graph.image_lifetime(unused), None);
+        assert_eq!(graph.image_lifetime(shadow), Some(0 .. 3));
+        assert_eq!(graph.buffer_lifetime(buffer), Some(1 .. 4));
+        // the shadow map is only stored for the main pass
+        assert_eq!(ops(&graph, shadows)[0].store, AttachmentStoreOp::Store);
+    }
+
+    #[test]
+    fn test_accumulation() {
+        let mut builder = GraphBuilder::new();
+        let target = builder.create_image(info(Format::Rgba16Sfloat, false));
+        let output = builder.import_image(
+            info(Format::Rgba8Unorm, false),
+            image::Layout::General .. image::Layout::General,
+        );
+        let first = builder.pass("first").color(target).add();
+        let second = builder.pass("second").color(target).add();
+        let resolve = builder
+            .pass("resolve")
+            .sampled(target, PipelineStage::FRAGMENT_SHADER)
+            .color(output)
+            .add();
+        let graph = builder.compile().unwrap();
+
+        assert!(graph.culled().is_empty());
+        assert_eq!(
+            ops(&graph, first),
+            vec![AttachmentOps::new(
+                AttachmentLoadOp::DontCare,
+                AttachmentStoreOp::Store
+            )]
+        );
+        assert_eq!(ops(&graph, second), vec![AttachmentOps::PRESERVE]);
+        // the contents of an imported image in a defined layout are kept
+        assert_eq!(ops(&graph, resolve), vec![AttachmentOps::PRESERVE]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut builder = GraphBuilder::new();
+        let output = builder.import_image(
+            info(Format::Rgba8Unorm, false),
+            image::Layout::Undefined .. image::Layout::Present,
+        );
+        let texture = builder.create_image(info(Format::Rgba8Unorm, false));
+        let pass = builder
+            .pass("main")
+            .color(output)
+            .sampled(texture, PipelineStage::FRAGMENT_SHADER)
+            .add();
+        assert_eq!(
+            builder.compile().unwrap_err(),
+            CompileError::UndefinedImage {
+                pass,
+                image: texture
+            }
+        );
+
+        let mut builder = GraphBuilder::new();
+        let output = builder.import_image(
+            info(Format::Rgba8Unorm, false),
+            image::Layout::Undefined .. image::Layout::Present,
+        );
+        let pass = builder
+            .pass("main")
+            .color(output)
+            .transfer_dst(output)
+            .add();
+        assert_eq!(
+            builder.compile().unwrap_err(),
+            CompileError::DuplicateUse {
+                pass,
+                image: output
+            }
+        );
+
+        let mut builder = GraphBuilder::new();
+        let output = builder.import_image(
+            info(Format::Rgba8Unorm, false),
+            image::Layout::Undefined .. image::Layout::Present,
+        );
+        let depth = builder.create_image(ImageInfo {
+            kind: image::Kind::D2(32, 32, 1, 1),
+            ..info(Format::D32Sfloat, true)
+        });
+        let pass = builder
+            .pass("main")
+            .color(output)
+            .depth_stencil(depth)
+            .add();
+        assert_eq!(
+            builder.compile().unwrap_err(),
+            CompileError::ExtentMismatch(pass)
+        );
+    }
+}
@@ -0,0 +1,769 @@
+use crate::{BufferId, CompiledGraph, CompiledPass, ImageId, PassId};
+use gfx_track::{BufferUse, ImageUse, Tracker, Transitions};
+use hal::adapter::MemoryProperties;
+use hal::command::{ClearColor, ClearValue, CommandBuffer as _, SubpassContents};
+use hal::device::{self as d, Device as _};
+use hal::pso::{PipelineStage, Rect};
+use hal::{buffer, format, image, memory, pass, Backend, MemoryTypeId};
+
+use std::ops::Range;
+
+/// Error of the creation of a frame.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FrameError {
+    /// An image imported by the graph is missing from the imports.
+    MissingImage(ImageId),
+    /// A buffer imported by the graph is missing from the imports.
+    MissingBuffer(BufferId),
+    /// None of the memory types allowed for a resource is available.
+    NoSuitableMemoryType,
+    /// Out of either host or device memory.
+    OutOfMemory(d::OutOfMemory),
+    /// Cannot create any more memory objects.
+    TooManyObjects,
+    Image(image::CreationError),
+    ImageView(image::ViewError),
+    Buffer(buffer::CreationError),
+    Bind(d::BindError),
+}
+
+impl From<d::OutOfMemory> for FrameError {
+    fn from(error: d::OutOfMemory) -> Self {
+        FrameError::OutOfMemory(error)
+    }
+}
+
+impl From<d::AllocationError> for FrameError {
+    fn from(error: d::AllocationError) -> Self {
+        match error {
+            d::AllocationError::OutOfMemory(oom) => FrameError::OutOfMemory(oom),
+            d::AllocationError::TooManyObjects => FrameError::TooManyObjects,
+        }
+    }
+}
+
+impl From<image::CreationError> for FrameError {
+    fn from(error: image::CreationError) -> Self {
+        FrameError::Image(error)
+    }
+}
+
+impl From<image::ViewError> for FrameError {
+    fn from(error: image::ViewError) -> Self {
+        FrameError::ImageView(error)
+    }
+}
+
+impl From<buffer::CreationError> for FrameError {
+    fn from(error: buffer::CreationError) -> Self {
+        FrameError::Buffer(error)
+    }
+}
+
+impl From<d::BindError> for FrameError {
+    fn from(error: d::BindError) -> Self {
+        FrameError::Bind(error)
+    }
+}
+
+/// Resources owned outside of the graph, used by a frame.
+#[derive(Debug)]
+pub struct Imports<'a, B: Backend> {
+    images: Vec<(ImageId, &'a B::Image)>,
+    buffers: Vec<(BufferId, &'a B::Buffer)>,
+}
+
+impl<'a, B: Backend> Default for Imports<'a, B> {
+    fn default() -> Self {
+        Imports {
+            images: Vec::new(),
+            buffers: Vec::new(),
+        }
+    }
+}
+
+impl<'a, B: Backend> Imports<'a, B> {
+    /// Create an empty set of imports.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bind an image to an imported image of the graph.
+    pub fn image(mut self, id: ImageId, image: &'a B::Image) -> Self {
+        self.images.push((id, image));
+        self
+    }
+
+    /// Bind a buffer to an imported buffer of the graph.
+    pub fn buffer(mut self, id: BufferId, buffer: &'a B::Buffer) -> Self {
+        self.buffers.push((id, buffer));
+        self
+    }
+}
+
+#[derive(Debug)]
+enum Slot<'a, T> {
+    Owned(T),
+    Imported(&'a T),
+}
+
+impl<'a, T> Slot<'a, T> {
+    fn get(&self) -> &T {
+        match *self {
+            Slot::Owned(ref resource) => resource,
+            Slot::Imported(resource) => resource,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Resource {
+    Image(ImageId),
+    Buffer(BufferId),
+}
+
+/// Transient resource to place in memory.
+#[derive(Debug)]
+struct Placement {
+    resource: Resource,
+    memory_type: usize,
+    lifetime: Range<usize>,
+    requirements: memory::Requirements,
+}
+
+impl Placement {
+    fn is_image(&self) -> bool {
+        match self.resource {
+            Resource::Image(_) => true,
+            Resource::Buffer(_) => false,
+        }
+    }
+}
+
+/// Place resources in a single memory object, sharing the memory between
+/// the ones with disjoint lifetimes.
+///
+/// Returns the offsets of the resources and the size of the memory.
+fn pack(items: &[(Range<usize>, memory::Requirements)]) -> (Vec<u64>, u64) {
+    let mut order = (0 .. items.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| !items[i].1.size);
+
+    let mut placed: Vec<(Range<u64>, Range<usize>)> = Vec::new();
+    let mut offsets = vec![0; items.len()];
+    let mut total = 0;
+    for i in order {
+        let (ref lifetime, requirements) = items[i];
+        let mask = requirements.alignment.max(1) - 1;
+        let align = |offset: u64| (offset + mask) & !mask;
+        let mut conflicts = placed
+            .iter()
+            .filter(|(_, other)| other.start < lifetime.end && lifetime.start < other.end)
+            .map(|(memory, _)| memory.clone())
+            .collect::<Vec<_>>();
+        conflicts.sort_by_key(|memory| memory.start);
+
+        let mut offset = 0;
+        for memory in conflicts {
+            if align(offset) + requirements.size <= memory.start {
+                break;
+            }
+            offset = offset.max(memory.end);
+        }
+        let offset = align(offset);
+        offsets[i] = offset;
+        total = total.max(offset + requirements.size);
+        placed.push((offset .. offset + requirements.size, lifetime.clone()));
+    }
+    (offsets, total)
+}
+
+fn memory_type(properties: &MemoryProperties, type_mask: u64) -> Option<usize> {
+    let allowed = properties
+        .memory_types
+        .iter()
+        .enumerate()
+        .filter(|&(id, _)| type_mask & (1 << id) != 0);
+    allowed
+        .clone()
+        .find(|&(_, ty)| ty.properties.contains(memory::Properties::DEVICE_LOCAL))
+        .or_else(|| allowed.clone().next())
+        .map(|(id, _)| id)
+}
+
+fn full_range(info: &crate::ImageInfo) -> image::SubresourceRange {
+    image::SubresourceRange {
+        aspects: info.format.surface_desc().aspects,
+        levels: 0 .. info.levels,
+        layers: 0 .. info.kind.num_layers(),
+    }
+}
+
+/// Device objects to record a compiled graph with.
+///
+/// Transient resources are created once with the frame, which can be
+/// recorded again every time it has finished executing. Synchronizing the
+/// submissions is up to the user.
+#[derive(Debug)]
+pub struct Frame<'a, B: Backend> {
+    graph: CompiledGraph,
+    images: Vec<Option<Slot<'a, B::Image>>>,
+    /// States of the images when the frame starts.
+    image_states: Vec<ImageUse>,
+    views: Vec<Option<B::ImageView>>,
+    buffers: Vec<Option<Slot<'a, B::Buffer>>>,
+    buffer_states: Vec<BufferUse>,
+    memory: Vec<(B::Memory, u64)>,
+    render_passes: Vec<Option<(B::RenderPass, B::Framebuffer)>>,
+}
+
+impl<'a, B: Backend> Frame<'a, B> {
+    /// Create the resources and render passes of a compiled graph.
+    ///
+    /// # Safety
+    ///
+    /// The imported resources have to outlive the frame, and to be created
+    /// with the usage the graph needs.
+    pub unsafe fn new(
+        device: &B::Device,
+        memory_properties: &MemoryProperties,
+        graph: CompiledGraph,
+        imports: &Imports<'a, B>,
+    ) -> Result<Self, FrameError> {
+        let mut frame = Frame {
+            image_states: graph
+                .images
+                .iter()
+                .map(|image| {
+                    let layout = image
+                        .import
+                        .as_ref()
+                        .map_or(image::Layout::Undefined, |l| l.start);
+                    ImageUse::unused(layout)
+                })
+                .collect(),
+            images: Vec::new(),
+            views: Vec::new(),
+            buffer_states: vec![BufferUse::unused(); graph.buffers.len()],
+            buffers: Vec::new(),
+            memory: Vec::new(),
+            render_passes: Vec::new(),
+            graph,
+        };
+        match frame.init(device, memory_properties, imports) {
+            Ok(()) => Ok(frame),
+            Err(error) => {
+                frame.dispose(device);
+                Err(error)
+            }
+        }
+    }
+
+    unsafe fn init(
+        &mut self,
+        device: &B::Device,
+        memory_properties: &MemoryProperties,
+        imports: &Imports<'a, B>,
+    ) -> Result<(), FrameError> {
+        let mut placements = Vec::new();
+
+        for index in 0 .. self.graph.images.len() {
+            let id = ImageId(index);
+            let lifetime = match self.graph.image_lifetime(id) {
+                Some(lifetime) => lifetime,
+                None => {
+                    self.images.push(None);
+                    continue;
+                }
+            };
+            if self.graph.images[index].import.is_some() {
+                let &(_, image) = imports
+                    .images
+                    .iter()
+                    .find(|&&(other, _)| other == id)
+                    .ok_or(FrameError::MissingImage(id))?;
+                self.images.push(Some(Slot::Imported(image)));
+                continue;
+            }
+            let info = self.graph.images[index].info;
+            let image = device.create_image(
+                info.kind,
+                info.levels,
+                info.format,
+                image::Tiling::Optimal,
+                self.graph.image_usage(id),
+                image::ViewCapabilities::empty(),
+            )?;
+            let requirements = device.get_image_requirements(&image);
+            self.images.push(Some(Slot::Owned(image)));
+            placements.push(Placement {
+                resource: Resource::Image(id),
+                memory_type: memory_type(memory_properties, requirements.type_mask)
+                    .ok_or(FrameError::NoSuitableMemoryType)?,
+                lifetime,
+                requirements,
+            });
+        }
+
+        for index in 0 .. self.graph.buffers.len() {
+            let id = BufferId(index);
+            let lifetime = match self.graph.buffer_lifetime(id) {
+                Some(lifetime) => lifetime,
+                None => {
+                    self.buffers.push(None);
+                    continue;
+                }
+            };
+            let entry = &self.graph.buffers[index];
+            if entry.imported {
+                let &(_, buffer) = imports
+                    .buffers
+                    .iter()
+                    .find(|&&(other, _)| other == id)
+                    .ok_or(FrameError::MissingBuffer(id))?;
+                self.buffers.push(Some(Slot::Imported(buffer)));
+                continue;
+            }
+            let buffer = device.create_buffer(entry.size, entry.usage)?;
+            let requirements = device.get_buffer_requirements(&buffer);
+            self.buffers.push(Some(Slot::Owned(buffer)));
+            placements.push(Placement {
+                resource: Resource::Buffer(id),
+                memory_type: memory_type(memory_properties, requirements.type_mask)
+                    .ok_or(FrameError::NoSuitableMemoryType)?,
+                lifetime,
+                requirements,
+            });
+        }
+
+        self.bind_memory(device, &placements)?;
+        self.create_render_passes(device)
+    }
+
+    /// Allocate the memory of the transient resources, one object per
+    /// memory type, keeping buffers and images apart.
+    unsafe fn bind_memory(
+        &mut self,
+        device: &B::Device,
+        placements: &[Placement],
+    ) -> Result<(), FrameError> {
+        let mut groups: Vec<(usize, bool)> = Vec::new();
+        for placement in placements {
+            let key = (placement.memory_type, placement.is_image());
+            if !groups.contains(&key) {
+                groups.push(key);
+            }
+        }
+
+        for (memory_type, is_image) in groups {
+            let group = placements
+                .iter()
+                .filter(|p| p.memory_type == memory_type && p.is_image() == is_image)
+                .collect::<Vec<_>>();
+            let items = group
+                .iter()
+                .map(|p| (p.lifetime.clone(), p.requirements))
+                .collect::<Vec<_>>();
+            let (offsets, size) = pack(&items);
+            let memory = device.allocate_memory(MemoryTypeId(memory_type), size)?;
+
+            for (i, placement) in group.iter().enumerate() {
+                match placement.resource {
+                    Resource::Image(id) => match self.images[id.0] {
+                        Some(Slot::Owned(ref mut image)) => {
+                            device.bind_image_memory(&memory, offsets[i], image)?
+                        }
+                        _ => unreachable!(),
+                    },
+                    Resource::Buffer(id) => match self.buffers[id.0] {
+                        Some(Slot::Owned(ref mut buffer)) => {
+                            device.bind_buffer_memory(&memory, offsets[i], buffer)?
+                        }
+                        _ => unreachable!(),
+                    },
+                }
+
+                // Wait for the previous resources in the same memory.
+                let range = offsets[i] .. offsets[i] + placement.requirements.size;
+                let mut stages = PipelineStage::empty();
+                for (j, other) in group.iter().enumerate() {
+                    let other_range = offsets[j] .. offsets[j] + other.requirements.size;
+                    if other.lifetime.end <= placement.lifetime.start
+                        && other_range.start < range.end
+                        && range.start < other_range.end
+                    {
+                        stages |= self.last_stages(other.resource, other.lifetime.end - 1);
+                    }
+                }
+                if stages.is_empty() {
+                    continue;
+                }
+                match placement.resource {
+                    Resource::Image(id) => {
+                        self.image_states[id.0] = ImageUse {
+                            access: image::Access::MEMORY_WRITE,
+                            layout: image::Layout::Undefined,
+                            stages,
+                        };
+                    }
+                    Resource::Buffer(id) => {
+                        self.buffer_states[id.0] = BufferUse {
+                            access: buffer::Access::MEMORY_WRITE,
+                            stages,
+                        };
+                    }
+                }
+            }
+            self.memory.push((memory, size));
+        }
+        Ok(())
+    }
+
+    fn last_stages(&self, resource: Resource, pass: usize) -> PipelineStage {
+        let pass = &self.graph.passes()[pass];
+        match resource {
+            Resource::Image(id) => pass
+                .images
+                .iter()
+                .filter(|&&(image, _)| image == id)
+                .fold(PipelineStage::empty(), |stages, &(_, state)| {
+                    stages | state.stages
+                }),
+            Resource::Buffer(id) => pass
+                .buffers
+                .iter()
+                .filter(|&&(buffer, _)| buffer == id)
+                .fold(PipelineStage::empty(), |stages, &(_, state)| {
+                    stages | state.stages
+                }),
+        }
+    }
+
+    unsafe fn create_render_passes(&mut self, device: &B::Device) -> Result<(), FrameError> {
+        self.views = (0 .. self.graph.images.len()).map(|_| None).collect();
+        for index in 0 .. self.graph.passes().len() {
+            let desc = match self.graph.passes()[index].render_pass {
+                Some(ref desc) => desc.clone(),
+                None => {
+                    self.render_passes.push(None);
+                    continue;
+                }
+            };
+            for &id in &desc.images {
+                if self.views[id.0].is_none() {
+                    let info = self.graph.image_info(id);
+                    let view = device.create_image_view(
+                        self.image(id),
+                        image::ViewKind::D2,
+                        info.format,
+                        format::Swizzle::NO,
+                        image::SubresourceRange {
+                            levels: 0 .. 1,
+                            layers: 0 .. 1,
+                            ..full_range(info)
+                        },
+                    )?;
+                    self.views[id.0] = Some(view);
+                }
+            }
+
+            let subpass = pass::SubpassDesc {
+                colors: &desc.colors,
+                depth_stencil: desc.depth_stencil.as_ref(),
+                inputs: &[],
+                resolves: &[],
+                preserves: &[],
+            };
+            let render_pass = device.create_render_pass(
+                &desc.attachments,
+                &[subpass],
+                &[] as &[pass::SubpassDependency],
+            )?;
+            let views = desc
+                .images
+                .iter()
+                .map(|id| self.views[id.0].as_ref().unwrap());
+            match device.create_framebuffer(&render_pass, views, desc.extent) {
+                Ok(framebuffer) => self.render_passes.push(Some((render_pass, framebuffer))),
+                Err(error) => {
+                    device.destroy_render_pass(render_pass);
+                    return Err(error.into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the compiled graph of the frame.
+    pub fn graph(&self) -> &CompiledGraph {
+        &self.graph
+    }
+
+    /// Get an image used by the executed passes.
+    pub fn image(&self, id: ImageId) -> &B::Image {
+        self.images[id.0].as_ref().expect("Image is not used").get()
+    }
+
+    /// Get a buffer used by the executed passes.
+    pub fn buffer(&self, id: BufferId) -> &B::Buffer {
+        self.buffers[id.0]
+            .as_ref()
+            .expect("Buffer is not used")
+            .get()
+    }
+
+    /// Get the render pass of an executed graphics pass, to create its
+    /// pipelines with.
+    pub fn render_pass(&self, id: PassId) -> Option<&B::RenderPass> {
+        let index = self.graph.passes().iter().position(|pass| pass.id == id)?;
+        self.render_passes[index].as_ref().map(|(rp, _)| rp)
+    }
+
+    /// Get the size of the memory allocated for the transient resources.
+    pub fn memory_size(&self) -> u64 {
+        self.memory.iter().map(|&(_, size)| size).sum()
+    }
+
+    /// Record the frame, calling `record_pass` to record the commands of
+    /// every executed pass, after the barriers it needs. Graphics passes are
+    /// recorded inline, inside of their render pass.
+    ///
+    /// # Safety
+    ///
+    /// The command buffer has to be recording, outside of a render pass.
+    pub unsafe fn record<F>(&self, cmd_buffer: &mut B::CommandBuffer, mut record_pass: F)
+    where
+        F: FnMut(&CompiledPass, &mut B::CommandBuffer),
+    {
+        let mut tracker = Tracker::new();
+        let images = self
+            .images
+            .iter()
+            .enumerate()
+            .map(|(index, slot)| {
+                slot.as_ref().map(|_| {
+                    let info = &self.graph.images[index].info;
+                    tracker.add_image(
+                        info.levels,
+                        info.kind.num_layers(),
+                        self.image_states[index],
+                    )
+                })
+            })
+            .collect::<Vec<_>>();
+        let buffers = self
+            .buffers
+            .iter()
+            .enumerate()
+            .map(|(index, slot)| {
+                slot.as_ref()
+                    .map(|_| tracker.add_buffer(self.buffer_states[index]))
+            })
+            .collect::<Vec<_>>();
+
+        for (pass, render_pass) in self.graph.passes().iter().zip(&self.render_passes) {
+            let mut transitions = Transitions::<B>::new();
+            for &(id, state) in &pass.images {
+                tracker.use_image(
+                    images[id.0].unwrap(),
+                    self.image(id),
+                    full_range(self.graph.image_info(id)),
+                    state,
+                    &mut transitions,
+                );
+            }
+            for &(id, state) in &pass.buffers {
+                tracker.use_buffer(
+                    buffers[id.0].unwrap(),
+                    self.buffer(id),
+                    state,
+                    &mut transitions,
+                );
+            }
+            transitions.record(cmd_buffer);
+
+            match (render_pass, &pass.render_pass) {
+                (Some((rp, framebuffer)), Some(desc)) => {
+                    let clear_values = desc.images.iter().map(|&id| {
+                        self.graph.image_info(id).clear.unwrap_or(ClearValue {
+                            color: ClearColor { float32: [0.0; 4] },
+                        })
+                    });
+                    let area = Rect {
+                        x: 0,
+                        y: 0,
+                        w: desc.extent.width as i16,
+                        h: desc.extent.height as i16,
+                    };
+                    cmd_buffer.begin_render_pass(
+                        rp,
+                        framebuffer,
+                        area,
+                        clear_values,
+                        SubpassContents::Inline,
+                    );
+                    record_pass(pass, cmd_buffer);
+                    cmd_buffer.end_render_pass();
+                }
+                _ => record_pass(pass, cmd_buffer),
+            }
+        }
+
+        // Leave the imported images in their final layout.
+        let mut transitions = Transitions::<B>::new();
+        for (index, entry) in self.graph.images.iter().enumerate() {
+            if let (Some(id), Some(layouts)) = (images[index], entry.import.as_ref()) {
+                tracker.use_image(
+                    id,
+                    self.image(ImageId(index)),
+                    full_range(&entry.info),
+                    ImageUse {
+                        access: image::Access::empty(),
+                        layout: layouts.end,
+                        stages: PipelineStage::BOTTOM_OF_PIPE,
+                    },
+                    &mut transitions,
+                );
+            }
+        }
+        transitions.record(cmd_buffer);
+    }
+
+    /// Destroy the objects of the frame.
+    ///
+    /// # Safety
+    ///
+    /// The frame must not be in use by the device.
+    pub unsafe fn dispose(self, device: &B::Device) {
+        for (render_pass, framebuffer) in self.render_passes.into_iter().flatten() {
+            device.destroy_framebuffer(framebuffer);
+            device.destroy_render_pass(render_pass);
+        }
+        for view in self.views.into_iter().flatten() {
+            device.destroy_image_view(view);
+        }
+        for slot in self.images.into_iter().flatten() {
+            if let Slot::Owned(image) = slot {
+                device.destroy_image(image);
+            }
+        }
+        for slot in self.buffers.into_iter().flatten() {
+            if let Slot::Owned(buffer) = slot {
+                device.destroy_buffer(buffer);
+            }
+        }
+        for (memory, _) in self.memory {
+            device.free_memory(memory);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GraphBuilder, ImageInfo};
+    use hal::adapter::PhysicalDevice as _;
+    use hal::command as com;
+    use hal::pool::{self, CommandPool as _};
+
+    type Empty = gfx_backend_empty::Backend;
+
+    fn requirements(size: u64) -> memory::Requirements {
+        memory::Requirements {
+            size,
+            alignment: 256,
+            type_mask: !0,
+        }
+    }
+
+    #[test]
+    fn test_pack() {
+        let (offsets, size) = pack(&[
+            (0 .. 2, requirements(1000)),
+            (1 .. 3, requirements(500)),
+            (2 .. 4, requirements(1000)),
+            (3 .. 4, requirements(200)),
+        ]);
+        // the third resource takes the place of the first one, the last one
+        // takes the place of the second one
+        assert_eq!(offsets, vec![0, 1024, 0, 1024]);
+        assert_eq!(size, 1524);
+    }
+
+    #[test]
+    fn test_record() {
+        let instance = gfx_backend_empty::Instance::create("graph", 1).unwrap();
+        let (adapter, gpu) = gfx_backend_empty::test_utils::open(&instance);
+        let device = &gpu.device;
+        let memory_properties = adapter.physical_device.memory_properties();
+
+        let info = ImageInfo {
+            kind: image::Kind::D2(16, 16, 1, 1),
+            levels: 1,
+            format: format::Format::Rgba8Unorm,
+            clear: None,
+        };
+        let mut builder = GraphBuilder::new();
+        let output = builder.import_image(info, image::Layout::Undefined .. image::Layout::General);
+        let first = builder.create_image(info);
+        let second = builder.create_image(info);
+        let third = builder.create_image(info);
+        builder.pass("first").color(first).add();
+        builder
+            .pass("second")
+            .sampled(first, PipelineStage::FRAGMENT_SHADER)
+            .color(second)
+            .add();
+        builder
+            .pass("third")
+            .sampled(second, PipelineStage::FRAGMENT_SHADER)
+            .color(third)
+            .add();
+        builder
+            .pass("output")
+            .sampled(third, PipelineStage::FRAGMENT_SHADER)
+            .color(output)
+            .add();
+        let graph = builder.compile().unwrap();
+
+        unsafe {
+            let mut target = device
+                .create_image(
+                    info.kind,
+                    1,
+                    info.format,
+                    image::Tiling::Optimal,
+                    image::Usage::COLOR_ATTACHMENT,
+                    image::ViewCapabilities::empty(),
+                )
+                .unwrap();
+            let target_memory = device
+                .allocate_memory(MemoryTypeId(0), device.get_image_requirements(&target).size)
+                .unwrap();
+            device
+                .bind_image_memory(&target_memory, 0, &mut target)
+                .unwrap();
+            let imports = Imports::new().image(output, &target);
+            let frame = Frame::<Empty>::new(device, &memory_properties, graph, &imports).unwrap();
+            // the first and third images share their memory
+            assert_eq!(frame.memory_size(), 2 * 16 * 16 * 4);
+            assert!(frame.graph().passes()[0].render_pass.is_some());
+
+            let mut pool = device
+                .create_command_pool(
+                    gpu.queue_groups[0].family,
+                    pool::CommandPoolCreateFlags::empty(),
+                )
+                .unwrap();
+            let mut cmd_buffer = pool.allocate_one(com::Level::Primary);
+            cmd_buffer.begin_primary(com::CommandBufferFlags::ONE_TIME_SUBMIT);
+            let mut recorded = Vec::new();
+            frame.record(&mut cmd_buffer, |pass, _| recorded.push(pass.name.clone()));
+            cmd_buffer.finish();
+            assert_eq!(recorded, vec!["first", "second", "third", "output"]);
+
+            frame.dispose(device);
+            device.destroy_image(target);
+            device.free_memory(target_memory);
+        }
+    }
+}
@@ -0,0 +1,332 @@
+//! Render graph.
+//!
+//! A frame is described as a list of passes, each declaring the images and
+//! buffers it reads and writes. Compiling the graph culls the passes whose
+//! results are never used, computes the lifetime of the transient resources
+//! and derives the render pass attachments of the graphics passes: their
+//! load and store operations follow from the previous and next uses of the
+//! image. The compiled graph is pure data, it's turned into a `Frame` of
+//! device objects where transient resources with disjoint lifetimes share
+//! memory, and the barriers between passes are generated while recording.
+//!
+//! Passes use their attachments in declaration order: colors first, then
+//! the depth-stencil one.
+
+mod compile;
+mod frame;
+
+pub use crate::compile::{CompileError, CompiledGraph, CompiledPass, RenderPassDesc};
+pub use crate::frame::{Frame, FrameError, Imports};
+
+use hal::command::ClearValue;
+use hal::format::Format;
+use hal::pso::PipelineStage;
+use hal::{buffer, image};
+
+use std::ops::Range;
+
+/// Identifier of an image in a graph.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ImageId(usize);
+
+/// Identifier of a buffer in a graph.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BufferId(usize);
+
+/// Identifier of a pass in a graph.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PassId(usize);
+
+/// Description of an image of the graph.
+#[derive(Clone, Copy, Debug)]
+pub struct ImageInfo {
+    pub kind: image::Kind,
+    pub levels: image::Level,
+    pub format: Format,
+    /// Value to clear the image with when it's first used as an attachment.
+    pub clear: Option<ClearValue>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct ImageEntry {
+    pub info: ImageInfo,
+    /// Layouts of an image owned outside of the graph, at the start and
+    /// the end of the frame.
+    pub import: Option<Range<image::Layout>>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct BufferEntry {
+    pub size: u64,
+    pub usage: buffer::Usage,
+    pub imported: bool,
+}
+
+/// Way a pass uses an image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ImageRole {
+    Color,
+    DepthStencil,
+    DepthRead,
+    Sampled(PipelineStage),
+    Storage(PipelineStage),
+    TransferSrc,
+    TransferDst,
+}
+
+impl ImageRole {
+    pub fn is_attachment(self) -> bool {
+        matches!(
+            self,
+            ImageRole::Color | ImageRole::DepthStencil | ImageRole::DepthRead
+        )
+    }
+
+    /// Check if the pass depends on the previous contents of the image.
+    pub fn reads(self) -> bool {
+        self != ImageRole::TransferDst
+    }
+
+    pub fn writes(self) -> bool {
+        matches!(
+            self,
+            ImageRole::Color
+                | ImageRole::DepthStencil
+                | ImageRole::Storage(_)
+                | ImageRole::TransferDst
+        )
+    }
+
+    pub fn usage(self) -> image::Usage {
+        match self {
+            ImageRole::Color => image::Usage::COLOR_ATTACHMENT,
+            ImageRole::DepthStencil | ImageRole::DepthRead => {
+                image::Usage::DEPTH_STENCIL_ATTACHMENT
+            }
+            ImageRole::Sampled(_) => image::Usage::SAMPLED,
+            ImageRole::Storage(_) => image::Usage::STORAGE,
+            ImageRole::TransferSrc => image::Usage::TRANSFER_SRC,
+            ImageRole::TransferDst => image::Usage::TRANSFER_DST,
+        }
+    }
+
+    pub fn state(self) -> gfx_track::ImageUse {
+        let (access, layout, stages) = match self {
+            ImageRole::Color => (
+                image::Access::COLOR_ATTACHMENT_READ | image::Access::COLOR_ATTACHMENT_WRITE,
+                image::Layout::ColorAttachmentOptimal,
+                PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+            ),
+            ImageRole::DepthStencil => (
+                image::Access::DEPTH_STENCIL_ATTACHMENT_READ
+                    | image::Access::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                image::Layout::DepthStencilAttachmentOptimal,
+                PipelineStage::EARLY_FRAGMENT_TESTS | PipelineStage::LATE_FRAGMENT_TESTS,
+            ),
+            ImageRole::DepthRead => (
+                image::Access::DEPTH_STENCIL_ATTACHMENT_READ,
+                image::Layout::DepthStencilReadOnlyOptimal,
+                PipelineStage::EARLY_FRAGMENT_TESTS | PipelineStage::LATE_FRAGMENT_TESTS,
+            ),
+            ImageRole::Sampled(stages) => (
+                image::Access::SHADER_READ,
+                image::Layout::ShaderReadOnlyOptimal,
+                stages,
+            ),
+            ImageRole::Storage(stages) => (
+                image::Access::SHADER_READ | image::Access::SHADER_WRITE,
+                image::Layout::General,
+                stages,
+            ),
+            ImageRole::TransferSrc => (
+                image::Access::TRANSFER_READ,
+                image::Layout::TransferSrcOptimal,
+                PipelineStage::TRANSFER,
+            ),
+            ImageRole::TransferDst => (
+                image::Access::TRANSFER_WRITE,
+                image::Layout::TransferDstOptimal,
+                PipelineStage::TRANSFER,
+            ),
+        };
+        gfx_track::ImageUse {
+            access,
+            layout,
+            stages,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct PassEntry {
+    pub name: String,
+    pub images: Vec<(ImageId, ImageRole)>,
+    pub buffers: Vec<(BufferId, gfx_track::BufferUse)>,
+    pub side_effects: bool,
+}
+
+/// Builder of a render graph.
+#[derive(Debug, Default)]
+pub struct GraphBuilder {
+    images: Vec<ImageEntry>,
+    buffers: Vec<BufferEntry>,
+    passes: Vec<PassEntry>,
+}
+
+impl GraphBuilder {
+    /// Create an empty graph.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a transient image, created and owned by the frames.
+    pub fn create_image(&mut self, info: ImageInfo) -> ImageId {
+        self.images.push(ImageEntry { info, import: None });
+        ImageId(self.images.len() - 1)
+    }
+
+    /// Add an image owned outside of the graph, e.g. a swapchain image.
+    ///
+    /// The image is in the `layouts.start` layout when the frame starts and
+    /// is transitioned to `layouts.end` at the end. Imported images are the
+    /// outputs of the graph: the passes writing them are never culled.
+    pub fn import_image(&mut self, info: ImageInfo, layouts: Range<image::Layout>) -> ImageId {
+        self.images.push(ImageEntry {
+            info,
+            import: Some(layouts),
+        });
+        ImageId(self.images.len() - 1)
+    }
+
+    /// Add a transient buffer, created and owned by the frames.
+    pub fn create_buffer(&mut self, size: u64, usage: buffer::Usage) -> BufferId {
+        self.buffers.push(BufferEntry {
+            size,
+            usage,
+            imported: false,
+        });
+        BufferId(self.buffers.len() - 1)
+    }
+
+    /// Add a buffer owned outside of the graph.
+    pub fn import_buffer(&mut self) -> BufferId {
+        self.buffers.push(BufferEntry {
+            size: 0,
+            usage: buffer::Usage::empty(),
+            imported: true,
+        });
+        BufferId(self.buffers.len() - 1)
+    }
+
+    /// Start declaring a pass, executed after all the passes declared
+    /// before it.
+    pub fn pass<'g>(&'g mut self, name: &str) -> PassBuilder<'g> {
+        PassBuilder {
+            graph: self,
+            pass: PassEntry {
+                name: name.to_string(),
+                images: Vec::new(),
+                buffers: Vec::new(),
+                side_effects: false,
+            },
+        }
+    }
+
+    /// Compile the graph.
+    pub fn compile(&self) -> Result<CompiledGraph, CompileError> {
+        compile::compile(&self.images, &self.buffers, &self.passes)
+    }
+}
+
+/// Declaration of the resources used by a pass.
+///
+/// A pass with attachments is recorded inside of a render pass with a
+/// single subpass.
+#[derive(Debug)]
+pub struct PassBuilder<'g> {
+    graph: &'g mut GraphBuilder,
+    pass: PassEntry,
+}
+
+impl<'g> PassBuilder<'g> {
+    fn image(mut self, image: ImageId, role: ImageRole) -> Self {
+        self.pass.images.push((image, role));
+        self
+    }
+
+    /// Render to a color attachment, on top of its previous contents.
+    pub fn color(self, image: ImageId) -> Self {
+        self.image(image, ImageRole::Color)
+    }
+
+    /// Render with a depth-stencil attachment, testing and writing it.
+    pub fn depth_stencil(self, image: ImageId) -> Self {
+        self.image(image, ImageRole::DepthStencil)
+    }
+
+    /// Render with a read-only depth-stencil attachment.
+    pub fn depth_read(self, image: ImageId) -> Self {
+        self.image(image, ImageRole::DepthRead)
+    }
+
+    /// Sample an image in the shaders of `stages`.
+    pub fn sampled(self, image: ImageId, stages: PipelineStage) -> Self {
+        self.image(image, ImageRole::Sampled(stages))
+    }
+
+    /// Read and write a storage image in the shaders of `stages`.
+    pub fn storage(self, image: ImageId, stages: PipelineStage) -> Self {
+        self.image(image, ImageRole::Storage(stages))
+    }
+
+    /// Copy from an image.
+    pub fn transfer_src(self, image: ImageId) -> Self {
+        self.image(image, ImageRole::TransferSrc)
+    }
+
+    /// Copy to an image, overwriting its previous contents.
+    pub fn transfer_dst(self, image: ImageId) -> Self {
+        self.image(image, ImageRole::TransferDst)
+    }
+
+    fn buffer(mut self, buffer: BufferId, state: gfx_track::BufferUse) -> Self {
+        self.pass.buffers.push((buffer, state));
+        self
+    }
+
+    /// Read a buffer with `access` in `stages`.
+    pub fn read_buffer(
+        self,
+        buffer: BufferId,
+        access: buffer::Access,
+        stages: PipelineStage,
+    ) -> Self {
+        self.buffer(buffer, gfx_track::BufferUse { access, stages })
+    }
+
+    /// Write a buffer with `access` in `stages`.
+    ///
+    /// The access has to include the written bits, e.g. `TRANSFER_WRITE`.
+    pub fn write_buffer(
+        self,
+        buffer: BufferId,
+        access: buffer::Access,
+        stages: PipelineStage,
+    ) -> Self {
+        let state = gfx_track::BufferUse { access, stages };
+        debug_assert!(state.is_write(), "{:?} doesn't write the buffer", access);
+        self.buffer(buffer, state)
+    }
+
+    /// Never cull the pass, even if nothing uses its results.
+    pub fn side_effects(mut self) -> Self {
+        self.pass.side_effects = true;
+        self
+    }
+
+    /// Finish the declaration of the pass.
+    pub fn add(self) -> PassId {
+        self.graph.passes.push(self.pass);
+        PassId(self.graph.passes.len() - 1)
+    }
+}
@@ -0,0 +1,800 @@
+//! Device memory sub-allocator.
+//!
+//! Memory is allocated from the device in large blocks per memory type, which
+//! are split between the resources with a `RangeAllocator`. Linear and optimal
+//! resources are kept in separate blocks when the device reports a
+//! `buffer_image_granularity` above 1, so they never share a page. Large
+//! resources, or the ones asking for it, get a dedicated memory object.
+
+#[macro_use]
+extern crate log;
+
+use hal::adapter::{MemoryProperties, MemoryType};
+use hal::device::{self as d, Device as _};
+use hal::{memory, Backend, Limits, MemoryTypeId};
+use range_alloc::RangeAllocator;
+
+use std::ops::Range;
+use std::ptr::NonNull;
+
+/// Error of an allocation.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AllocationError {
+    /// None of the memory types allowed by the requirements has the
+    /// required properties.
+    NoSuitableMemoryType,
+    /// Out of either host or device memory.
+    OutOfMemory(d::OutOfMemory),
+    /// Cannot create any more memory objects.
+    TooManyObjects,
+    /// The memory can't be mapped.
+    MappingFailed(d::MapError),
+}
+
+impl From<d::OutOfMemory> for AllocationError {
+    fn from(error: d::OutOfMemory) -> Self {
+        AllocationError::OutOfMemory(error)
+    }
+}
+
+impl From<d::AllocationError> for AllocationError {
+    fn from(error: d::AllocationError) -> Self {
+        match error {
+            d::AllocationError::OutOfMemory(oom) => AllocationError::OutOfMemory(oom),
+            d::AllocationError::TooManyObjects => AllocationError::TooManyObjects,
+        }
+    }
+}
+
+impl From<d::MapError> for AllocationError {
+    fn from(error: d::MapError) -> Self {
+        AllocationError::MappingFailed(error)
+    }
+}
+
+/// Intended usage of the memory, choosing the properties of its type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Usage {
+    /// Only accessed by the device.
+    DeviceOnly,
+    /// Written by the host and read by the device, e.g. every frame.
+    Dynamic,
+    /// Written once by the host, to be copied into device-only memory.
+    Upload,
+    /// Written by the device, to be read back by the host.
+    Download,
+}
+
+impl Usage {
+    /// Properties the memory type must have.
+    pub fn required(self) -> memory::Properties {
+        match self {
+            Usage::DeviceOnly => memory::Properties::empty(),
+            Usage::Dynamic | Usage::Upload | Usage::Download => memory::Properties::CPU_VISIBLE,
+        }
+    }
+
+    /// Properties the memory type should have, if possible.
+    pub fn preferred(self) -> memory::Properties {
+        match self {
+            Usage::DeviceOnly => memory::Properties::DEVICE_LOCAL,
+            Usage::Dynamic => memory::Properties::DEVICE_LOCAL | memory::Properties::COHERENT,
+            Usage::Upload => memory::Properties::COHERENT,
+            Usage::Download => memory::Properties::COHERENT | memory::Properties::CPU_CACHED,
+        }
+    }
+}
+
+/// Layout of the resource bound to an allocation.
+///
+/// Linear resources (buffers and linearly tiled images) and optimal ones
+/// have to be `Limits::buffer_image_granularity` bytes apart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Linearity {
+    Linear,
+    Optimal,
+}
+
+/// Description of an allocation.
+#[derive(Clone, Copy, Debug)]
+pub struct Request {
+    /// Requirements of the resource bound to the allocation.
+    pub requirements: memory::Requirements,
+    /// Properties the memory type must have.
+    pub required: memory::Properties,
+    /// Properties the memory type should have, if possible.
+    pub preferred: memory::Properties,
+    pub linearity: Linearity,
+    /// Give the allocation its own memory object.
+    pub dedicated: bool,
+    /// Keep the memory mapped for the lifetime of the allocation.
+    pub mapped: bool,
+}
+
+impl Request {
+    /// Request memory for a linear resource, with the properties of `usage`.
+    ///
+    /// The memory is kept mapped unless the usage is `DeviceOnly`.
+    pub fn new(requirements: memory::Requirements, usage: Usage) -> Self {
+        Request {
+            requirements,
+            required: usage.required(),
+            preferred: usage.preferred(),
+            linearity: Linearity::Linear,
+            dedicated: false,
+            mapped: usage != Usage::DeviceOnly,
+        }
+    }
+}
+
+/// Configuration of an allocator.
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    /// Size of the blocks allocated from the device, limited to an eighth of
+    /// the heap of the memory type.
+    pub block_size: u64,
+    /// Size from which a resource gets a dedicated memory object.
+    pub dedicated_threshold: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            block_size: 64 << 20,
+            dedicated_threshold: 32 << 20,
+        }
+    }
+}
+
+/// Allocation statistics.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Number of memory objects allocated from the device.
+    pub memory_objects: usize,
+    /// Total size of the memory objects.
+    pub reserved: u64,
+    /// Number of live allocations.
+    pub allocations: usize,
+    /// Total size of the live allocations.
+    pub used: u64,
+}
+
+impl Stats {
+    fn add(&mut self, other: &Stats) {
+        self.memory_objects += other.memory_objects;
+        self.reserved += other.reserved;
+        self.allocations += other.allocations;
+        self.used += other.used;
+    }
+}
+
+/// Memory object shared by several allocations.
+#[derive(Debug)]
+struct Block<B: Backend> {
+    memory: B::Memory,
+    size: u64,
+    ranges: RangeAllocator<u64>,
+    ptr: Option<NonNull<u8>>,
+    allocations: usize,
+}
+
+#[derive(Debug)]
+enum Source<B: Backend> {
+    Dedicated(B::Memory),
+    Block {
+        pool: usize,
+        block: usize,
+        range: Range<u64>,
+    },
+}
+
+/// Range of a memory object given out by an `Allocator`.
+#[derive(Debug)]
+pub struct Allocation<B: Backend> {
+    memory_type: MemoryTypeId,
+    offset: u64,
+    size: u64,
+    ptr: Option<NonNull<u8>>,
+    source: Source<B>,
+}
+
+unsafe impl<B: Backend> Send for Allocation<B> {}
+unsafe impl<B: Backend> Sync for Allocation<B> {}
+
+impl<B: Backend> Allocation<B> {
+    /// Get the memory type of the allocation.
+    pub fn memory_type(&self) -> MemoryTypeId {
+        self.memory_type
+    }
+
+    /// Get the offset of the allocation in its memory object.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Get the size of the allocation.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Get the range of the allocation in its memory object.
+    pub fn range(&self) -> Range<u64> {
+        self.offset .. self.offset + self.size
+    }
+
+    /// Check if the allocation has its own memory object.
+    pub fn is_dedicated(&self) -> bool {
+        match self.source {
+            Source::Dedicated(_) => true,
+            Source::Block { .. } => false,
+        }
+    }
+
+    /// Get a host pointer to the start of the allocation, if it is mapped.
+    pub fn ptr(&self) -> Option<*mut u8> {
+        self.ptr.map(NonNull::as_ptr)
+    }
+}
+
+/// Sub-allocator of device memory.
+///
+/// All the allocations have to be freed with `free` before the allocator is
+/// released with `dispose`.
+#[derive(Debug)]
+pub struct Allocator<B: Backend> {
+    memory_types: Vec<MemoryType>,
+    heap_sizes: Vec<u64>,
+    granularity: u64,
+    atom_size: u64,
+    config: Config,
+    /// Blocks of each memory type and linearity, with free slots.
+    pools: Vec<Vec<Option<Block<B>>>>,
+    stats: Vec<Stats>,
+}
+
+unsafe impl<B: Backend> Send for Allocator<B> {}
+unsafe impl<B: Backend> Sync for Allocator<B> {}
+
+/// Round `value` up to a power of two `alignment`.
+fn align_up(value: u64, alignment: u64) -> u64 {
+    (value + alignment - 1) & !(alignment - 1)
+}
+
+impl<B: Backend> Allocator<B> {
+    /// Create an allocator for the memory of a physical device.
+    pub fn new(properties: MemoryProperties, limits: &Limits, config: Config) -> Self {
+        let type_count = properties.memory_types.len();
+        Allocator {
+            memory_types: properties.memory_types,
+            heap_sizes: properties.memory_heaps,
+            granularity: limits.buffer_image_granularity.max(1),
+            atom_size: (limits.non_coherent_atom_size as u64).max(1),
+            config,
+            pools: (0 .. type_count * 2).map(|_| Vec::new()).collect(),
+            stats: vec![Stats::default(); type_count],
+        }
+    }
+
+    /// List the memory types of `type_mask` that have the `required`
+    /// properties, from the best to the worst match of the `preferred` ones.
+    pub fn memory_types(
+        &self,
+        type_mask: u64,
+        required: memory::Properties,
+        preferred: memory::Properties,
+    ) -> Vec<MemoryTypeId> {
+        let mut candidates = self
+            .memory_types
+            .iter()
+            .enumerate()
+            .filter(|&(id, ty)| type_mask & (1 << id) != 0 && ty.properties.contains(required))
+            .map(|(id, ty)| {
+                let matched = (ty.properties & preferred).bits().count_ones();
+                let extra = (ty.properties - preferred - required).bits().count_ones();
+                (id, matched, extra)
+            })
+            .collect::<Vec<_>>();
+        // more preferred properties first, then fewer unneeded ones
+        candidates.sort_by_key(|&(_, matched, extra)| (!matched, extra));
+        candidates
+            .into_iter()
+            .map(|(id, _, _)| MemoryTypeId(id))
+            .collect()
+    }
+
+    /// Get the memory object of an allocation.
+    pub fn memory<'a>(&'a self, allocation: &'a Allocation<B>) -> &'a B::Memory {
+        match allocation.source {
+            Source::Dedicated(ref memory) => memory,
+            Source::Block { pool, block, .. } => &self.pools[pool][block].as_ref().unwrap().memory,
+        }
+    }
+
+    /// Get the statistics of all the memory types.
+    pub fn stats(&self) -> Stats {
+        let mut total = Stats::default();
+        for stats in &self.stats {
+            total.add(stats);
+        }
+        total
+    }
+
+    /// Get the statistics of a memory type.
+    pub fn type_stats(&self, memory_type: MemoryTypeId) -> Stats {
+        self.stats[memory_type.0]
+    }
+
+    fn block_size(&self, memory_type: MemoryTypeId) -> u64 {
+        let heap_size = self.heap_sizes[self.memory_types[memory_type.0].heap_index];
+        self.config.block_size.min(heap_size / 8)
+    }
+
+    fn pool_index(&self, memory_type: MemoryTypeId, linearity: Linearity) -> usize {
+        match linearity {
+            Linearity::Optimal if self.granularity > 1 => memory_type.0 * 2 + 1,
+            _ => memory_type.0 * 2,
+        }
+    }
+
+    /// Allocate memory for a resource, from the memory type that best
+    /// matches the request.
+    ///
+    /// # Safety
+    ///
+    /// `device` has to be the device of the memory properties given to
+    /// `new`, for all the calls to this allocator.
+    pub unsafe fn allocate(
+        &mut self,
+        device: &B::Device,
+        request: &Request,
+    ) -> Result<Allocation<B>, AllocationError> {
+        let mut required = request.required;
+        if request.mapped {
+            required |= memory::Properties::CPU_VISIBLE;
+        }
+        let candidates =
+            self.memory_types(request.requirements.type_mask, required, request.preferred);
+        let mut error = AllocationError::NoSuitableMemoryType;
+        for memory_type in candidates {
+            match self.allocate_from(device, memory_type, request) {
+                Ok(allocation) => return Ok(allocation),
+                Err(e) => {
+                    debug!("Unable to allocate from {:?}: {:?}", memory_type, e);
+                    error = e;
+                }
+            }
+        }
+        Err(error)
+    }
+
+    unsafe fn allocate_from(
+        &mut self,
+        device: &B::Device,
+        memory_type: MemoryTypeId,
+        request: &Request,
+    ) -> Result<Allocation<B>, AllocationError> {
+        let size = request.requirements.size.max(1);
+        let alignment = request.requirements.alignment.max(1);
+        let block_size = self.block_size(memory_type);
+        if request.dedicated || size >= self.config.dedicated_threshold || size > block_size / 2 {
+            return self.allocate_dedicated(device, memory_type, size, request.mapped);
+        }
+
+        let pool = self.pool_index(memory_type, request.linearity);
+        let mut found = None;
+        for (index, slot) in self.pools[pool].iter_mut().enumerate() {
+            if let Some(ref mut block) = *slot {
+                if let Ok(range) = block.ranges.allocate_range_aligned(size, alignment) {
+                    found = Some((index, range));
+                    break;
+                }
+            }
+        }
+        let (index, range) = match found {
+            Some(found) => found,
+            None => {
+                let index = self.add_block(device, memory_type, pool, block_size)?;
+                let block = self.pools[pool][index].as_mut().unwrap();
+                (
+                    index,
+                    block
+                        .ranges
+                        .allocate_range_aligned(size, alignment)
+                        .unwrap(),
+                )
+            }
+        };
+
+        let block = self.pools[pool][index].as_mut().unwrap();
+        if request.mapped && block.ptr.is_none() {
+            match device.map_memory(&block.memory, 0 .. block.size) {
+                Ok(ptr) => block.ptr = NonNull::new(ptr),
+                Err(e) => {
+                    block.ranges.free_range(range);
+                    return Err(e.into());
+                }
+            }
+        }
+        block.allocations += 1;
+        let offset = range.start;
+        let ptr = if request.mapped {
+            block
+                .ptr
+                .and_then(|ptr| NonNull::new(ptr.as_ptr().offset(offset as isize)))
+        } else {
+            None
+        };
+
+        let stats = &mut self.stats[memory_type.0];
+        stats.allocations += 1;
+        stats.used += size;
+        Ok(Allocation {
+            memory_type,
+            offset,
+            size,
+            ptr,
+            source: Source::Block {
+                pool,
+                block: index,
+                range,
+            },
+        })
+    }
+
+    unsafe fn add_block(
+        &mut self,
+        device: &B::Device,
+        memory_type: MemoryTypeId,
+        pool: usize,
+        size: u64,
+    ) -> Result<usize, AllocationError> {
+        let memory = device.allocate_memory(memory_type, size)?;
+        let block = Block {
+            memory,
+            size,
+            ranges: RangeAllocator::new(0 .. size),
+            ptr: None,
+            allocations: 0,
+        };
+        let stats = &mut self.stats[memory_type.0];
+        stats.memory_objects += 1;
+        stats.reserved += size;
+
+        let blocks = &mut self.pools[pool];
+        match blocks.iter().position(Option::is_none) {
+            Some(index) => {
+                blocks[index] = Some(block);
+                Ok(index)
+            }
+            None => {
+                blocks.push(Some(block));
+                Ok(blocks.len() - 1)
+            }
+        }
+    }
+
+    unsafe fn allocate_dedicated(
+        &mut self,
+        device: &B::Device,
+        memory_type: MemoryTypeId,
+        size: u64,
+        mapped: bool,
+    ) -> Result<Allocation<B>, AllocationError> {
+        let memory = device.allocate_memory(memory_type, size)?;
+        let ptr = if mapped {
+            match device.map_memory(&memory, 0 .. size) {
+                Ok(ptr) => NonNull::new(ptr),
+                Err(e) => {
+                    device.free_memory(memory);
+                    return Err(e.into());
+                }
+            }
+        } else {
+            None
+        };
+
+        let stats = &mut self.stats[memory_type.0];
+        stats.memory_objects += 1;
+        stats.reserved += size;
+        stats.allocations += 1;
+        stats.used += size;
+        Ok(Allocation {
+            memory_type,
+            offset: 0,
+            size,
+            ptr,
+            source: Source::Dedicated(memory),
+        })
+    }
+
+    /// Free an allocation of this allocator.
+    ///
+    /// Blocks left empty are released, except the last one of their pool.
+    ///
+    /// # Safety
+    ///
+    /// The allocation has to come from this allocator, and the device must
+    /// be done with the resources bound to it.
+    pub unsafe fn free(&mut self, device: &B::Device, allocation: Allocation<B>) {
+        let stats = &mut self.stats[allocation.memory_type.0];
+        stats.allocations -= 1;
+        stats.used -= allocation.size;
+        match allocation.source {
+            Source::Dedicated(memory) => {
+                if allocation.ptr.is_some() {
+                    device.unmap_memory(&memory);
+                }
+                device.free_memory(memory);
+                stats.memory_objects -= 1;
+                stats.reserved -= allocation.size;
+            }
+            Source::Block { pool, block, range } => {
+                let blocks = &mut self.pools[pool];
+                let live = blocks.iter().filter(|block| block.is_some()).count();
+                let empty = {
+                    let block = blocks[block].as_mut().unwrap();
+                    block.ranges.free_range(range);
+                    block.allocations -= 1;
+                    block.allocations == 0
+                };
+                if empty && live > 1 {
+                    let block = blocks[block].take().unwrap();
+                    stats.memory_objects -= 1;
+                    stats.reserved -= block.size;
+                    Self::release(device, block);
+                }
+            }
+        }
+    }
+
+    unsafe fn release(device: &B::Device, block: Block<B>) {
+        if block.ptr.is_some() {
+            device.unmap_memory(&block.memory);
+        }
+        device.free_memory(block.memory);
+    }
+
+    /// Get the range to flush or invalidate for an allocation, extended to the
+    /// non-coherent atoms, or `None` if its memory is coherent.
+    fn atom_range<'a>(
+        &'a self,
+        allocation: &'a Allocation<B>,
+    ) -> Option<(&'a B::Memory, Range<u64>)> {
+        let properties = self.memory_types[allocation.memory_type.0].properties;
+        if properties.contains(memory::Properties::COHERENT) {
+            return None;
+        }
+        let memory_size = match allocation.source {
+            Source::Dedicated(_) => allocation.size,
+            Source::Block { pool, block, .. } => self.pools[pool][block].as_ref().unwrap().size,
+        };
+        let start = allocation.offset / self.atom_size * self.atom_size;
+        let end = align_up(allocation.offset + allocation.size, self.atom_size).min(memory_size);
+        Some((self.memory(allocation), start .. end))
+    }
+
+    /// Make the host writes to mapped allocations visible to the device.
+    ///
+    /// Allocations of coherent memory are skipped.
+    ///
+    /// # Safety
+    ///
+    /// The allocations have to come from this allocator and be mapped.
+    pub unsafe fn flush<'a, I>(
+        &self,
+        device: &B::Device,
+        allocations: I,
+    ) -> Result<(), d::OutOfMemory>
+    where
+        I: IntoIterator<Item = &'a Allocation<B>>,
+        B: 'a,
+    {
+        let ranges = allocations
+            .into_iter()
+            .filter_map(|allocation| self.atom_range(allocation))
+            .collect::<Vec<_>>();
+        if ranges.is_empty() {
+            return Ok(());
+        }
+        device.flush_mapped_memory_ranges(ranges)
+    }
+
+    /// Make the device writes to mapped allocations visible to the host.
+    ///
+    /// Allocations of coherent memory are skipped.
+    ///
+    /// # Safety
+    ///
+    /// See `flush`.
+    pub unsafe fn invalidate<'a, I>(
+        &self,
+        device: &B::Device,
+        allocations: I,
+    ) -> Result<(), d::OutOfMemory>
+    where
+        I: IntoIterator<Item = &'a Allocation<B>>,
+        B: 'a,
+    {
+        let ranges = allocations
+            .into_iter()
+            .filter_map(|allocation| self.atom_range(allocation))
+            .collect::<Vec<_>>();
+        if ranges.is_empty() {
+            return Ok(());
+        }
+        device.invalidate_mapped_memory_ranges(ranges)
+    }
+
+    /// Release all the memory of the allocator.
+    ///
+    /// # Safety
+    ///
+    /// The device must be done with all the memory of the allocator.
+    pub unsafe fn dispose(self, device: &B::Device) {
+        let stats = self.stats();
+        if stats.allocations != 0 {
+            error!("{} allocations are leaked", stats.allocations);
+        }
+        for block in self.pools.into_iter().flatten().flatten() {
+            Self::release(device, block);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hal::adapter::PhysicalDevice as _;
+
+    use std::ptr;
+
+    type Empty = gfx_backend_empty::Backend;
+
+    const CONFIG: Config = Config {
+        block_size: 1 << 16,
+        dedicated_threshold: 1 << 14,
+    };
+
+    fn open(limits: Option<Limits>) -> (<Empty as Backend>::Device, Allocator<Empty>) {
+        let instance = gfx_backend_empty::Instance::create("memory", 1).unwrap();
+        let (adapter, gpu) = gfx_backend_empty::test_utils::open(&instance);
+        let limits = limits.unwrap_or_else(|| adapter.physical_device.limits());
+        let allocator =
+            Allocator::new(adapter.physical_device.memory_properties(), &limits, CONFIG);
+        (gpu.device, allocator)
+    }
+
+    fn requirements(size: u64, alignment: u64) -> memory::Requirements {
+        memory::Requirements {
+            size,
+            alignment,
+            type_mask: !0,
+        }
+    }
+
+    #[test]
+    fn test_memory_types() {
+        let (_, allocator) = open(None);
+        let best =
+            |usage: Usage| allocator.memory_types(!0, usage.required(), usage.preferred())[0];
+        // DEVICE_LOCAL, DEVICE_LOCAL | CPU_VISIBLE | COHERENT, CPU_VISIBLE | COHERENT | CPU_CACHED
+        assert_eq!(best(Usage::DeviceOnly), MemoryTypeId(0));
+        assert_eq!(best(Usage::Dynamic), MemoryTypeId(1));
+        assert_eq!(best(Usage::Download), MemoryTypeId(2));
+        assert_eq!(
+            allocator.memory_types(
+                0b100,
+                memory::Properties::empty(),
+                memory::Properties::DEVICE_LOCAL
+            ),
+            vec![MemoryTypeId(2)]
+        );
+        assert!(allocator
+            .memory_types(
+                0b001,
+                memory::Properties::CPU_VISIBLE,
+                memory::Properties::empty()
+            )
+            .is_empty());
+    }
+
+    #[test]
+    fn test_sub_allocation() {
+        let (device, mut allocator) = open(None);
+        let request = Request::new(requirements(1000, 256), Usage::DeviceOnly);
+        unsafe {
+            let first = allocator.allocate(&device, &request).unwrap();
+            let second = allocator.allocate(&device, &request).unwrap();
+            assert_eq!(first.memory_type(), MemoryTypeId(0));
+            assert!(!first.is_dedicated() && first.ptr().is_none());
+            assert_eq!(first.offset() % 256, 0);
+            assert_eq!(second.offset() % 256, 0);
+            assert!(first.range().end <= second.offset() || second.range().end <= first.offset());
+            assert_eq!(
+                allocator.stats(),
+                Stats {
+                    memory_objects: 1,
+                    reserved: CONFIG.block_size,
+                    allocations: 2,
+                    used: 2000,
+                }
+            );
+
+            allocator.free(&device, first);
+            allocator.free(&device, second);
+            let stats = allocator.type_stats(MemoryTypeId(0));
+            assert_eq!(
+                (stats.memory_objects, stats.allocations, stats.used),
+                (1, 0, 0)
+            );
+            allocator.dispose(&device);
+        }
+    }
+
+    #[test]
+    fn test_mapping() {
+        let (device, mut allocator) = open(None);
+        unsafe {
+            let small = allocator
+                .allocate(&device, &Request::new(requirements(64, 16), Usage::Upload))
+                .unwrap();
+            let other = allocator
+                .allocate(&device, &Request::new(requirements(64, 16), Usage::Upload))
+                .unwrap();
+            let large = allocator
+                .allocate(
+                    &device,
+                    &Request::new(requirements(1 << 15, 16), Usage::Download),
+                )
+                .unwrap();
+            assert!(!small.is_dedicated());
+            assert!(large.is_dedicated());
+            assert_eq!(large.memory_type(), MemoryTypeId(2));
+
+            // Allocations of a block point into its persistent mapping.
+            assert_eq!(other.memory_type(), small.memory_type());
+            assert_eq!(
+                other.ptr().unwrap() as isize - small.ptr().unwrap() as isize,
+                other.offset() as isize - small.offset() as isize,
+            );
+
+            let allocations = [&small, &other, &large];
+            for (i, allocation) in allocations.iter().enumerate() {
+                let data = [i as u8; 4];
+                ptr::copy_nonoverlapping(data.as_ptr(), allocation.ptr().unwrap(), data.len());
+            }
+            allocator.flush(&device, allocations.iter().cloned()).unwrap();
+            allocator.invalidate(&device, allocations.iter().cloned()).unwrap();
+            for (i, allocation) in allocations.iter().enumerate() {
+                let written = std::slice::from_raw_parts(allocation.ptr().unwrap(), 4);
+                assert_eq!(written, &[i as u8; 4]);
+            }
+
+            allocator.free(&device, small);
+            allocator.free(&device, other);
+            allocator.free(&device, large);
+            assert_eq!(allocator.stats().allocations, 0);
+            allocator.dispose(&device);
+        }
+    }
+
+    #[test]
+    fn test_buffer_image_granularity() {
+        let limits = Limits {
+            buffer_image_granularity: 1024,
+            non_coherent_atom_size: 1,
+            ..Limits::default()
+        };
+        let (device, mut allocator) = open(Some(limits));
+        let mut request = Request::new(requirements(100, 4), Usage::DeviceOnly);
+        unsafe {
+            let linear = allocator.allocate(&device, &request).unwrap();
+            request.linearity = Linearity::Optimal;
+            let optimal = allocator.allocate(&device, &request).unwrap();
+            assert_eq!(allocator.stats().memory_objects, 2);
+            allocator.free(&device, linear);
+            allocator.free(&device, optimal);
+            allocator.dispose(&device);
+        }
+    }
+}
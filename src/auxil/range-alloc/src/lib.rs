@@ -1,6 +1,21 @@
 use std::fmt::Debug;
 use std::iter::Sum;
-use std::ops::{Add, AddAssign, Range, Sub};
+use std::ops::{Add, AddAssign, Range, Rem, Sub};
+
+/// Strategy picking the free range of an allocation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum AllocationPolicy {
+    /// Take the first free range that fits, which is the fastest.
+    FirstFit,
+    /// Take the smallest free range that fits, to limit the fragmentation.
+    #[default]
+    BestFit,
+    /// Round the lengths up to powers of two and align the ranges to their
+    /// length. Unlike a buddy allocator there is no block tree: the blocks
+    /// are cut out of the free ranges, and freed ranges merge with any free
+    /// neighbour.
+    PowerOfTwo,
+}
 
 #[derive(Debug)]
 pub struct RangeAllocator<T> {
@@ -10,6 +25,7 @@ pub struct RangeAllocator<T> {
     /// Must be ordered with ascending range start to permit short circuiting allocation.
     /// No two ranges in this vec may overlap.
     free_ranges: Vec<Range<T>>,
+    policy: AllocationPolicy,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -17,51 +33,128 @@ pub struct RangeAllocationError<T> {
     pub fragmented_free_length: T,
 }
 
+/// Summary of the free space of an allocator.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FragmentationStats<T> {
+    /// Number of disjoint free ranges.
+    pub free_ranges: usize,
+    /// Total length of the free ranges.
+    pub total_free: T,
+    /// Length of the largest free range, which bounds the largest allocation.
+    pub largest_free: T,
+}
+
 impl<T> RangeAllocator<T>
 where
-    T: Clone + Copy + Add<Output = T> + AddAssign + Sub<Output = T> + Eq + PartialOrd + Debug,
+    T: Clone
+        + Copy
+        + Add<Output = T>
+        + AddAssign
+        + Sub<Output = T>
+        + Rem<Output = T>
+        + From<u8>
+        + Eq
+        + PartialOrd
+        + Debug,
 {
     pub fn new(range: Range<T>) -> Self {
+        Self::with_policy(range, AllocationPolicy::default())
+    }
+
+    pub fn with_policy(range: Range<T>, policy: AllocationPolicy) -> Self {
         RangeAllocator {
             initial_range: range.clone(),
             free_ranges: vec![range],
+            policy,
         }
     }
 
+    /// Returns the range this allocator covers.
+    pub fn initial_range(&self) -> &Range<T> {
+        &self.initial_range
+    }
+
+    pub fn policy(&self) -> AllocationPolicy {
+        self.policy
+    }
+
     pub fn allocate_range(&mut self, length: T) -> Result<Range<T>, RangeAllocationError<T>> {
-        assert_ne!(length + length, length);
-        let mut best_fit: Option<(usize, Range<T>)> = None;
-        let mut fragmented_free_length = length - length;
+        self.allocate_range_aligned(length, T::from(1))
+    }
+
+    /// Allocate a range whose start is a multiple of `alignment`.
+    ///
+    /// The free space skipped to align the start stays available.
+    pub fn allocate_range_aligned(
+        &mut self,
+        length: T,
+        alignment: T,
+    ) -> Result<Range<T>, RangeAllocationError<T>> {
+        let zero = T::from(0);
+        assert_ne!(length, zero);
+        assert_ne!(alignment, zero);
+        let (length, alignment) = match self.policy {
+            AllocationPolicy::PowerOfTwo => {
+                // A block has to fit in the whole range, which also stops the
+                // doubling before it overflows `T`.
+                let total = self.initial_range.end - self.initial_range.start;
+                let mut size = T::from(1);
+                while size < length {
+                    if size > total || total - size < size {
+                        return Err(RangeAllocationError {
+                            fragmented_free_length: self.fragmentation().total_free,
+                        });
+                    }
+                    size += size;
+                }
+                (size, if alignment < size { size } else { alignment })
+            }
+            AllocationPolicy::FirstFit | AllocationPolicy::BestFit => (length, alignment),
+        };
+
+        // Index, start and length left after the alignment of the best free range.
+        let mut best_fit: Option<(usize, T, T)> = None;
+        let mut fragmented_free_length = zero;
         for (index, range) in self.free_ranges.iter().cloned().enumerate() {
             let range_length = range.end - range.start;
             fragmented_free_length += range_length;
-            if range_length < length {
+            let misalignment = range.start % alignment;
+            let start = if misalignment == zero {
+                range.start
+            } else {
+                range.start + (alignment - misalignment)
+            };
+            if start > range.end || range.end - start < length {
                 continue;
-            } else if range_length == length {
-                // Found a perfect fit, so stop looking.
-                best_fit = Some((index, range));
+            }
+            let aligned_length = range.end - start;
+            if self.policy == AllocationPolicy::FirstFit || aligned_length == length {
+                // Found a perfect fit, or any fit is good enough, so stop looking.
+                best_fit = Some((index, start, aligned_length));
                 break;
             }
             best_fit = Some(match best_fit {
-                Some((best_index, best_range)) => {
-                    // Find best fit for this allocation to reduce memory fragmentation.
-                    if range_length < best_range.end - best_range.start {
-                        (index, range)
-                    } else {
-                        (best_index, best_range.clone())
-                    }
-                }
-                None => (index, range),
+                // Find best fit for this allocation to reduce memory fragmentation.
+                Some(best) if best.2 <= aligned_length => best,
+                _ => (index, start, aligned_length),
             });
         }
         match best_fit {
-            Some((index, range)) => {
-                if range.end - range.start == length {
-                    self.free_ranges.remove(index);
-                } else {
-                    self.free_ranges[index].start += length;
+            Some((index, start, _)) => {
+                let range = self.free_ranges[index].clone();
+                let end = start + length;
+                match (range.start < start, end < range.end) {
+                    (false, false) => {
+                        self.free_ranges.remove(index);
+                    }
+                    (false, true) => self.free_ranges[index].start = end,
+                    (true, false) => self.free_ranges[index].end = start,
+                    (true, true) => {
+                        self.free_ranges[index].end = start;
+                        self.free_ranges.insert(index + 1, end .. range.end);
+                    }
                 }
-                Ok(range.start .. (range.start + length))
+                Ok(start .. end)
             }
             None => Err(RangeAllocationError {
                 fragmented_free_length,
@@ -141,6 +234,78 @@ where
         first.into_iter().chain(mid).chain(last)
     }
 
+    /// Free several ranges at once, merging them with the free list in a
+    /// single pass.
+    pub fn free_ranges<I>(&mut self, ranges: I)
+    where
+        I: IntoIterator<Item = Range<T>>,
+    {
+        let mut freed = ranges.into_iter().collect::<Vec<_>>();
+        if freed.is_empty() {
+            return;
+        }
+        for range in &freed {
+            assert!(self.initial_range.start <= range.start && range.end <= self.initial_range.end);
+            assert!(range.start < range.end);
+        }
+        freed.sort_by(|a, b| a.start.partial_cmp(&b.start).unwrap());
+
+        let mut merged: Vec<Range<T>> = Vec::with_capacity(self.free_ranges.len() + freed.len());
+        let mut old = self.free_ranges.drain(..).peekable();
+        let mut new = freed.into_iter().peekable();
+        loop {
+            let range = match (old.peek(), new.peek()) {
+                (Some(a), Some(b)) if a.start < b.start => old.next().unwrap(),
+                (Some(_), Some(_)) | (None, Some(_)) => new.next().unwrap(),
+                (Some(_), None) => old.next().unwrap(),
+                (None, None) => break,
+            };
+            match merged.last_mut() {
+                Some(last) if last.end == range.start => last.end = range.end,
+                Some(last) => {
+                    // Freed ranges must not overlap the free ones.
+                    assert!(last.end < range.start);
+                    merged.push(range);
+                }
+                None => merged.push(range),
+            }
+        }
+        drop(old);
+        self.free_ranges = merged;
+    }
+
+    /// Extend the range this allocator covers up to `end`.
+    pub fn grow_to(&mut self, end: T) {
+        assert!(self.initial_range.end <= end);
+        if end == self.initial_range.end {
+            return;
+        }
+        match self.free_ranges.last_mut() {
+            Some(last) if last.end == self.initial_range.end => last.end = end,
+            _ => self.free_ranges.push(self.initial_range.end .. end),
+        }
+        self.initial_range.end = end;
+    }
+
+    /// Returns the statistics of the free space.
+    pub fn fragmentation(&self) -> FragmentationStats<T> {
+        let zero = T::from(0);
+        let mut stats = FragmentationStats {
+            free_ranges: self.free_ranges.len(),
+            total_free: zero,
+            largest_free: zero,
+        };
+        for range in &self.free_ranges {
+            let length = range.end - range.start;
+            stats.total_free += length;
+            if length > stats.largest_free {
+                stats.largest_free = length;
+            }
+        }
+        stats
+    }
+
+    /// Free all the allocations at once.
     pub fn reset(&mut self) {
         self.free_ranges.clear();
         self.free_ranges.push(self.initial_range.clone());
@@ -269,4 +434,176 @@ mod tests {
         alloc.free_range(3 .. 6);
         assert_eq!(alloc.free_ranges, vec![0 .. 9]);
     }
+
+    #[test]
+    fn test_aligned_allocation() {
+        let mut alloc = RangeAllocator::new(0 .. 64);
+        assert_eq!(alloc.allocate_range(3), Ok(0 .. 3));
+        assert_eq!(alloc.allocate_range_aligned(8, 16), Ok(16 .. 24));
+        // The space skipped for the alignment is still available.
+        assert_eq!(alloc.free_ranges, vec![3 .. 16, 24 .. 64]);
+        assert_eq!(alloc.allocate_range_aligned(4, 4), Ok(4 .. 8));
+        assert!(alloc.allocate_range_aligned(40, 32).is_err());
+        assert_eq!(alloc.allocate_range_aligned(32, 32), Ok(32 .. 64));
+
+        // Best fit compares the lengths left after the alignment.
+        let mut alloc = RangeAllocator::new(0 .. 32);
+        alloc.free_ranges = vec![1 .. 12, 16 .. 26];
+        assert_eq!(alloc.allocate_range_aligned(4, 8), Ok(8 .. 12));
+    }
+
+    #[test]
+    fn test_policies() {
+        let mut first = RangeAllocator::with_policy(0 .. 10, AllocationPolicy::FirstFit);
+        let mut best = RangeAllocator::with_policy(0 .. 10, AllocationPolicy::BestFit);
+        for alloc in &mut [&mut first, &mut best] {
+            assert_eq!(alloc.allocate_range(3), Ok(0 .. 3));
+            assert_eq!(alloc.allocate_range(3), Ok(3 .. 6));
+            assert_eq!(alloc.allocate_range(3), Ok(6 .. 9));
+            alloc.free_range(3 .. 6);
+        }
+        assert_eq!(first.allocate_range(1), Ok(3 .. 4));
+        assert_eq!(best.allocate_range(1), Ok(9 .. 10));
+
+        let mut pow2 = RangeAllocator::with_policy(0 .. 32, AllocationPolicy::PowerOfTwo);
+        assert_eq!(pow2.allocate_range(3), Ok(0 .. 4));
+        assert_eq!(pow2.allocate_range(5), Ok(8 .. 16));
+        assert_eq!(pow2.allocate_range(1), Ok(4 .. 5));
+        pow2.free_range(0 .. 4);
+        pow2.free_range(4 .. 5);
+        pow2.free_range(8 .. 16);
+        assert!(pow2.is_empty());
+
+        // Lengths above half of the largest value can't be rounded up.
+        let mut pow2 = RangeAllocator::with_policy(0 .. u32::MAX, AllocationPolicy::PowerOfTwo);
+        assert_eq!(pow2.allocate_range(1 << 31), Ok(0 .. 1 << 31));
+        assert_eq!(
+            pow2.allocate_range((1 << 31) + 1),
+            Err(RangeAllocationError {
+                fragmented_free_length: u32::MAX - (1 << 31),
+            })
+        );
+    }
+
+    #[test]
+    fn test_grow() {
+        let mut alloc = RangeAllocator::new(0 .. 10);
+        assert_eq!(alloc.allocate_range(10), Ok(0 .. 10));
+        alloc.grow_to(20);
+        assert_eq!(alloc.allocate_range(5), Ok(10 .. 15));
+        alloc.grow_to(30);
+        assert_eq!(alloc.free_ranges, vec![15 .. 30]);
+        alloc.reset();
+        assert_eq!(alloc.free_ranges, vec![0 .. 30]);
+    }
+
+    #[test]
+    fn test_free_ranges() {
+        let mut alloc = RangeAllocator::new(0 .. 100);
+        let ranges = (0 .. 10)
+            .map(|_| alloc.allocate_range(10).unwrap())
+            .collect::<Vec<_>>();
+        alloc.free_ranges(vec![
+            ranges[7].clone(),
+            ranges[1].clone(),
+            ranges[2].clone(),
+        ]);
+        assert_eq!(alloc.free_ranges, vec![10 .. 30, 70 .. 80]);
+        assert_eq!(
+            alloc.fragmentation(),
+            FragmentationStats {
+                free_ranges: 2,
+                total_free: 30,
+                largest_free: 20,
+            }
+        );
+        alloc.free_ranges(vec![
+            ranges[9].clone(),
+            ranges[8].clone(),
+            ranges[0].clone(),
+        ]);
+        assert_eq!(alloc.free_ranges, vec![0 .. 30, 70 .. 100]);
+    }
+
+    /// Check the invariants of the free list, and that it complements `live`.
+    fn check_invariants(alloc: &RangeAllocator<u32>, live: &[Range<u32>]) {
+        let initial = alloc.initial_range.clone();
+        for range in &alloc.free_ranges {
+            assert!(range.start < range.end, "empty free range {:?}", range);
+            assert!(initial.start <= range.start && range.end <= initial.end);
+        }
+        for pair in alloc.free_ranges.windows(2) {
+            // sorted, disjoint and merged
+            assert!(pair[0].end < pair[1].start, "{:?}", alloc.free_ranges);
+        }
+        let mut used = live.to_vec();
+        used.sort_by_key(|range| range.start);
+        for pair in used.windows(2) {
+            assert!(pair[0].end <= pair[1].start, "overlapping {:?}", pair);
+        }
+        let free = alloc.total_available();
+        let allocated = used
+            .iter()
+            .map(|range| range.end - range.start)
+            .sum::<u32>();
+        assert_eq!(free + allocated, initial.end - initial.start);
+        for range in &used {
+            assert!(alloc
+                .free_ranges
+                .iter()
+                .all(|free| free.end <= range.start || range.end <= free.start));
+        }
+    }
+
+    #[test]
+    fn test_fuzz_invariants() {
+        // xorshift, to stay deterministic without dependencies
+        let mut state = 0x2545_f491u32;
+        let mut random = move |bound: u32| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state % bound
+        };
+        let policies = [
+            AllocationPolicy::FirstFit,
+            AllocationPolicy::BestFit,
+            AllocationPolicy::PowerOfTwo,
+        ];
+        for &policy in &policies {
+            let mut alloc = RangeAllocator::with_policy(0 .. 1024, policy);
+            let mut live = Vec::new();
+            for _ in 0 .. 2000 {
+                match random(8) {
+                    0 ..= 3 => {
+                        let length = 1 + random(64);
+                        let alignment = 1 << random(5);
+                        let result = alloc.allocate_range_aligned(length, alignment);
+                        if let Ok(range) = result {
+                            assert_eq!(range.start % alignment, 0);
+                            assert!(range.end - range.start >= length);
+                            live.push(range);
+                        }
+                    }
+                    4 | 5 if !live.is_empty() => {
+                        let index = random(live.len() as u32) as usize;
+                        alloc.free_range(live.swap_remove(index));
+                    }
+                    6 if !live.is_empty() => {
+                        let count = 1 + random(live.len() as u32) as usize;
+                        let start = live.len() - count;
+                        alloc.free_ranges(live.drain(start ..));
+                    }
+                    7 if alloc.initial_range.end < 4096 => {
+                        let end = alloc.initial_range.end + random(256);
+                        alloc.grow_to(end);
+                    }
+                    _ => {}
+                }
+                check_invariants(&alloc, &live);
+            }
+            alloc.free_ranges(live.drain(..));
+            assert!(alloc.is_empty());
+        }
+    }
 }
@@ -0,0 +1,867 @@
+//! Staging ring.
+//!
+//! Data is uploaded to device-local resources, and read back from them,
+//! through a single host-visible buffer used as a ring. The copies are
+//! recorded in batches, each finished with a fence that the submission of
+//! the command buffer has to signal. Once the ring sees the fence signaled,
+//! the space of the batch is reused and its readbacks are completed.
+//!
+//! Copies from and to images are laid out with the offset and row pitch
+//! alignments of the device limits.
+
+use hal::adapter::MemoryProperties;
+use hal::command::{BufferCopy, BufferImageCopy, CommandBuffer as _};
+use hal::device::{self as d, Device as _};
+use hal::format::Format;
+use hal::memory::{Barrier, Dependencies, Properties};
+use hal::pso::PipelineStage;
+use hal::{buffer, image, Backend, Limits, MemoryTypeId};
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::ops::Range;
+use std::pin::Pin;
+use std::ptr::{self, NonNull};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::{fmt, iter, slice};
+
+/// Error of the staging ring.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StagingError {
+    /// None of the memory types of the ring buffer is host-visible.
+    NoSuitableMemoryType,
+    /// Out of either host or device memory.
+    OutOfMemory(d::OutOfMemory),
+    /// Cannot create any more memory objects.
+    TooManyObjects,
+    Buffer(buffer::CreationError),
+    Bind(d::BindError),
+    Map(d::MapError),
+    DeviceLost,
+    /// The copy is larger than the whole ring.
+    TooLarge {
+        size: u64,
+        capacity: u64,
+    },
+    /// The ring is full of batches still in flight, it has to be polled
+    /// again once some of them are done.
+    OutOfSpace,
+}
+
+impl From<d::OutOfMemory> for StagingError {
+    fn from(error: d::OutOfMemory) -> Self {
+        StagingError::OutOfMemory(error)
+    }
+}
+
+impl From<d::AllocationError> for StagingError {
+    fn from(error: d::AllocationError) -> Self {
+        match error {
+            d::AllocationError::OutOfMemory(oom) => StagingError::OutOfMemory(oom),
+            d::AllocationError::TooManyObjects => StagingError::TooManyObjects,
+        }
+    }
+}
+
+impl From<buffer::CreationError> for StagingError {
+    fn from(error: buffer::CreationError) -> Self {
+        StagingError::Buffer(error)
+    }
+}
+
+impl From<d::BindError> for StagingError {
+    fn from(error: d::BindError) -> Self {
+        StagingError::Bind(error)
+    }
+}
+
+impl From<d::MapError> for StagingError {
+    fn from(error: d::MapError) -> Self {
+        StagingError::Map(error)
+    }
+}
+
+impl From<d::DeviceLost> for StagingError {
+    fn from(_: d::DeviceLost) -> Self {
+        StagingError::DeviceLost
+    }
+}
+
+impl From<d::OomOrDeviceLost> for StagingError {
+    fn from(error: d::OomOrDeviceLost) -> Self {
+        match error {
+            d::OomOrDeviceLost::OutOfMemory(oom) => StagingError::OutOfMemory(oom),
+            d::OomOrDeviceLost::DeviceLost(_) => StagingError::DeviceLost,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ReadbackState {
+    data: Option<Vec<u8>>,
+    waker: Option<Waker>,
+}
+
+/// Data read back from the device, available once the batch of the copy
+/// is done.
+///
+/// The readback is also a future, woken up by `StagingRing::poll`.
+#[derive(Debug)]
+pub struct Readback {
+    state: Arc<Mutex<ReadbackState>>,
+}
+
+impl Readback {
+    /// Check if the data is available.
+    pub fn is_ready(&self) -> bool {
+        self.state.lock().unwrap().data.is_some()
+    }
+
+    /// Take the data, if it's available.
+    pub fn try_take(&self) -> Option<Vec<u8>> {
+        self.state.lock().unwrap().data.take()
+    }
+}
+
+impl Future for Readback {
+    type Output = Vec<u8>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Vec<u8>> {
+        let mut state = self.state.lock().unwrap();
+        match state.data.take() {
+            Some(data) => Poll::Ready(data),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Layout of image data in the ring.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Footprint {
+    /// Size of a row of texel blocks.
+    row_size: u64,
+    /// Distance between the rows in the ring.
+    row_pitch: u64,
+    /// Number of rows, in all the slices and layers.
+    rows: u64,
+    buffer_width: u32,
+    buffer_height: u32,
+}
+
+#[derive(Debug)]
+struct PendingReadback {
+    offset: u64,
+    footprint: Footprint,
+    state: Arc<Mutex<ReadbackState>>,
+}
+
+#[derive(Debug)]
+struct Batch<B: Backend> {
+    fence: B::Fence,
+    /// End of the space used by the batch.
+    end: u64,
+    readbacks: Vec<PendingReadback>,
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+    a
+}
+
+fn lcm(a: u64, b: u64) -> u64 {
+    let (a, b) = (a.max(1), b.max(1));
+    a / gcd(a, b) * b
+}
+
+fn align(value: u64, alignment: u64) -> u64 {
+    let alignment = alignment.max(1);
+    let mut aligned = value / alignment * alignment;
+    if aligned < value {
+        aligned += alignment;
+    }
+    aligned
+}
+
+/// Ring of host-visible memory to stage the copies through.
+///
+/// Positions in the ring grow monotonically, the offset in the buffer is
+/// the position modulo the capacity.
+pub struct StagingRing<B: Backend> {
+    buffer: B::Buffer,
+    memory: B::Memory,
+    ptr: NonNull<u8>,
+    coherent: bool,
+    capacity: u64,
+    /// Position of the next allocation.
+    head: u64,
+    /// Start of the oldest batch in flight.
+    tail: u64,
+    /// Start of the batch being recorded.
+    batch_start: u64,
+    offset_alignment: u64,
+    pitch_alignment: u64,
+    atom_size: u64,
+    readbacks: Vec<PendingReadback>,
+    batches: VecDeque<Batch<B>>,
+    free_fences: Vec<B::Fence>,
+}
+
+impl<B: Backend> fmt::Debug for StagingRing<B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StagingRing")
+            .field("capacity", &self.capacity)
+            .field("head", &self.head)
+            .field("tail", &self.tail)
+            .field("batches", &self.batches.len())
+            .finish()
+    }
+}
+
+unsafe impl<B: Backend> Send for StagingRing<B> {}
+unsafe impl<B: Backend> Sync for StagingRing<B> {}
+
+impl<B: Backend> StagingRing<B> {
+    /// Create a ring of `capacity` bytes.
+    ///
+    /// # Safety
+    ///
+    /// The properties and limits have to be the ones of the device.
+    pub unsafe fn new(
+        device: &B::Device,
+        memory_properties: &MemoryProperties,
+        limits: &Limits,
+        capacity: u64,
+    ) -> Result<Self, StagingError> {
+        let atom_size = (limits.non_coherent_atom_size as u64).max(1);
+        let capacity = align(capacity, atom_size);
+        let mut buffer = device.create_buffer(
+            capacity,
+            buffer::Usage::TRANSFER_SRC | buffer::Usage::TRANSFER_DST,
+        )?;
+        let requirements = device.get_buffer_requirements(&buffer);
+
+        let allowed = memory_properties
+            .memory_types
+            .iter()
+            .enumerate()
+            .filter(|&(id, ty)| {
+                requirements.type_mask & (1 << id) != 0
+                    && ty.properties.contains(Properties::CPU_VISIBLE)
+            });
+        let memory_type = allowed
+            .clone()
+            .find(|&(_, ty)| ty.properties.contains(Properties::COHERENT))
+            .or_else(|| allowed.clone().next());
+        let (memory_type, coherent) = match memory_type {
+            Some((id, ty)) => (id, ty.properties.contains(Properties::COHERENT)),
+            None => {
+                device.destroy_buffer(buffer);
+                return Err(StagingError::NoSuitableMemoryType);
+            }
+        };
+
+        let memory = match device.allocate_memory(MemoryTypeId(memory_type), requirements.size) {
+            Ok(memory) => memory,
+            Err(error) => {
+                device.destroy_buffer(buffer);
+                return Err(error.into());
+            }
+        };
+        let ptr = device
+            .bind_buffer_memory(&memory, 0, &mut buffer)
+            .map_err(StagingError::from)
+            .and_then(|()| Ok(device.map_memory(&memory, 0 .. capacity)?));
+        let ptr = match ptr {
+            Ok(ptr) => NonNull::new(ptr).expect("Mapped memory is null"),
+            Err(error) => {
+                device.destroy_buffer(buffer);
+                device.free_memory(memory);
+                return Err(error);
+            }
+        };
+
+        Ok(StagingRing {
+            buffer,
+            memory,
+            ptr,
+            coherent,
+            capacity,
+            head: 0,
+            tail: 0,
+            batch_start: 0,
+            offset_alignment: limits.optimal_buffer_copy_offset_alignment,
+            pitch_alignment: limits.optimal_buffer_copy_pitch_alignment,
+            atom_size,
+            readbacks: Vec::new(),
+            batches: VecDeque::new(),
+            free_fences: Vec::new(),
+        })
+    }
+
+    /// Get the size of the ring.
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    /// Get the number of bytes used by the batches in flight and the one
+    /// being recorded.
+    pub fn used(&self) -> u64 {
+        self.head - self.tail
+    }
+
+    /// Get the number of batches in flight.
+    pub fn batches_in_flight(&self) -> usize {
+        self.batches.len()
+    }
+
+    /// Reserve `size` bytes, returning their offset in the buffer.
+    fn allocate(&mut self, size: u64, alignment: u64) -> Result<u64, StagingError> {
+        if size > self.capacity {
+            return Err(StagingError::TooLarge {
+                size,
+                capacity: self.capacity,
+            });
+        }
+        let offset = self.head % self.capacity;
+        let aligned = align(offset, alignment);
+        let start = if aligned + size > self.capacity {
+            // wrap around to the start of the buffer
+            self.head - offset + self.capacity
+        } else {
+            self.head - offset + aligned
+        };
+        if start + size - self.tail > self.capacity {
+            return Err(StagingError::OutOfSpace);
+        }
+        self.head = start + size;
+        Ok(start % self.capacity)
+    }
+
+    fn footprint(&self, format: Format, extent: image::Extent, layers: image::Layer) -> Footprint {
+        let desc = format.surface_desc();
+        let (block_width, block_height) = (desc.dim.0 as u32, desc.dim.1 as u32);
+        let block_size = desc.bits as u64 / 8;
+        let blocks_x = extent.width.div_ceil(block_width);
+        let blocks_y = extent.height.div_ceil(block_height);
+        let row_size = blocks_x as u64 * block_size;
+        let row_pitch = align(row_size, lcm(self.pitch_alignment, block_size));
+        Footprint {
+            row_size,
+            row_pitch,
+            rows: blocks_y as u64 * extent.depth as u64 * layers as u64,
+            buffer_width: (row_pitch / block_size) as u32 * block_width,
+            buffer_height: blocks_y * block_height,
+        }
+    }
+
+    fn image_alignment(&self, format: Format) -> u64 {
+        let block_size = format.surface_desc().bits as u64 / 8;
+        lcm(lcm(self.offset_alignment, block_size), 4)
+    }
+
+    /// Record the upload of `data` into `dst` at `offset`.
+    ///
+    /// # Safety
+    ///
+    /// The command buffer has to be recording, outside of a render pass,
+    /// and the range of the buffer ready to be written by a transfer.
+    pub unsafe fn upload_buffer(
+        &mut self,
+        cmd_buffer: &mut B::CommandBuffer,
+        dst: &B::Buffer,
+        offset: buffer::Offset,
+        data: &[u8],
+    ) -> Result<(), StagingError> {
+        let size = data.len() as u64;
+        let src = self.allocate(size, lcm(self.offset_alignment, 4))?;
+        ptr::copy_nonoverlapping(
+            data.as_ptr(),
+            self.ptr.as_ptr().add(src as usize),
+            data.len(),
+        );
+        cmd_buffer.copy_buffer(
+            &self.buffer,
+            dst,
+            iter::once(BufferCopy {
+                src,
+                dst: offset,
+                size,
+            }),
+        );
+        Ok(())
+    }
+
+    /// Record the upload of a mip chain into `dst`, which is in `layout`.
+    ///
+    /// `levels[i]` holds the tightly packed data of the level `i`, for all
+    /// the `layers`. Only the images with a single aspect are supported.
+    ///
+    /// # Safety
+    ///
+    /// The command buffer has to be recording, outside of a render pass,
+    /// and the image created with `kind`, `format` and the levels.
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe fn upload_image(
+        &mut self,
+        cmd_buffer: &mut B::CommandBuffer,
+        dst: &B::Image,
+        layout: image::Layout,
+        kind: image::Kind,
+        format: Format,
+        layers: Range<image::Layer>,
+        levels: &[&[u8]],
+    ) -> Result<(), StagingError> {
+        let alignment = self.image_alignment(format);
+        let mut regions = Vec::with_capacity(levels.len());
+        for (level, data) in levels.iter().enumerate() {
+            let extent = kind.level_extent(level as image::Level);
+            let footprint = self.footprint(format, extent, layers.end - layers.start);
+            assert_eq!(
+                data.len() as u64,
+                footprint.row_size * footprint.rows,
+                "Level {} has the wrong size",
+                level
+            );
+            let offset = self.allocate(footprint.row_pitch * footprint.rows, alignment)?;
+            for row in 0 .. footprint.rows {
+                ptr::copy_nonoverlapping(
+                    data.as_ptr().add((row * footprint.row_size) as usize),
+                    self.ptr
+                        .as_ptr()
+                        .add((offset + row * footprint.row_pitch) as usize),
+                    footprint.row_size as usize,
+                );
+            }
+            regions.push(BufferImageCopy {
+                buffer_offset: offset,
+                buffer_width: footprint.buffer_width,
+                buffer_height: footprint.buffer_height,
+                image_layers: image::SubresourceLayers {
+                    aspects: format.surface_desc().aspects,
+                    level: level as image::Level,
+                    layers: layers.clone(),
+                },
+                image_offset: image::Offset::ZERO,
+                image_extent: extent,
+            });
+        }
+        cmd_buffer.copy_buffer_to_image(&self.buffer, dst, layout, regions);
+        Ok(())
+    }
+
+    fn read_barrier(&self, cmd_buffer: &mut B::CommandBuffer, range: Range<u64>) {
+        let barrier = Barrier::Buffer {
+            states: buffer::Access::TRANSFER_WRITE .. buffer::Access::HOST_READ,
+            target: &self.buffer,
+            families: None,
+            range: Some(range.start) .. Some(range.end),
+        };
+        unsafe {
+            cmd_buffer.pipeline_barrier(
+                PipelineStage::TRANSFER .. PipelineStage::HOST,
+                Dependencies::empty(),
+                iter::once(barrier),
+            );
+        }
+    }
+
+    fn pending(&mut self, offset: u64, footprint: Footprint) -> Readback {
+        let state = Arc::new(Mutex::new(ReadbackState::default()));
+        self.readbacks.push(PendingReadback {
+            offset,
+            footprint,
+            state: Arc::clone(&state),
+        });
+        Readback { state }
+    }
+
+    /// Record the readback of a `range` of `src`.
+    ///
+    /// # Safety
+    ///
+    /// The command buffer has to be recording, outside of a render pass,
+    /// and the range of the buffer ready to be read by a transfer.
+    pub unsafe fn read_buffer(
+        &mut self,
+        cmd_buffer: &mut B::CommandBuffer,
+        src: &B::Buffer,
+        range: Range<buffer::Offset>,
+    ) -> Result<Readback, StagingError> {
+        let size = range.end - range.start;
+        let dst = self.allocate(size, lcm(self.offset_alignment, 4))?;
+        cmd_buffer.copy_buffer(
+            src,
+            &self.buffer,
+            iter::once(BufferCopy {
+                src: range.start,
+                dst,
+                size,
+            }),
+        );
+        self.read_barrier(cmd_buffer, dst .. dst + size);
+        let footprint = Footprint {
+            row_size: size,
+            row_pitch: size,
+            rows: 1,
+            buffer_width: 0,
+            buffer_height: 0,
+        };
+        Ok(self.pending(dst, footprint))
+    }
+
+    /// Record the readback of a `level` of `src`, which is in `layout`.
+    ///
+    /// The data is tightly packed, in the same layout as the one of
+    /// `upload_image`.
+    ///
+    /// # Safety
+    ///
+    /// The command buffer has to be recording, outside of a render pass,
+    /// and the image created with `kind` and `format`.
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe fn read_image(
+        &mut self,
+        cmd_buffer: &mut B::CommandBuffer,
+        src: &B::Image,
+        layout: image::Layout,
+        kind: image::Kind,
+        format: Format,
+        level: image::Level,
+        layers: Range<image::Layer>,
+    ) -> Result<Readback, StagingError> {
+        let extent = kind.level_extent(level);
+        let footprint = self.footprint(format, extent, layers.end - layers.start);
+        let size = footprint.row_pitch * footprint.rows;
+        let offset = self.allocate(size, self.image_alignment(format))?;
+        cmd_buffer.copy_image_to_buffer(
+            src,
+            layout,
+            &self.buffer,
+            iter::once(BufferImageCopy {
+                buffer_offset: offset,
+                buffer_width: footprint.buffer_width,
+                buffer_height: footprint.buffer_height,
+                image_layers: image::SubresourceLayers {
+                    aspects: format.surface_desc().aspects,
+                    level,
+                    layers,
+                },
+                image_offset: image::Offset::ZERO,
+                image_extent: extent,
+            }),
+        );
+        self.read_barrier(cmd_buffer, offset .. offset + size);
+        Ok(self.pending(offset, footprint))
+    }
+
+    /// Physical ranges covering the positions `start .. end`, aligned to
+    /// the non-coherent atom size.
+    fn ranges(&self, start: u64, end: u64) -> Vec<Range<u64>> {
+        if start == end {
+            return Vec::new();
+        }
+        let first = start % self.capacity;
+        let last = (end - 1) % self.capacity + 1;
+        let atom = |range: Range<u64>| {
+            range.start / self.atom_size * self.atom_size
+                .. align(range.end, self.atom_size).min(self.capacity)
+        };
+        if first < last && end - start < self.capacity {
+            vec![atom(first .. last)]
+        } else {
+            vec![atom(first .. self.capacity), atom(0 .. last)]
+        }
+    }
+
+    /// Finish the batch of copies recorded since the last one, making the
+    /// uploaded data visible to the device.
+    ///
+    /// Returns the fence to signal with the submission of the command
+    /// buffer the copies were recorded into.
+    ///
+    /// # Safety
+    ///
+    /// The command buffer has to be submitted with the returned fence
+    /// before polling the ring.
+    pub unsafe fn finish(&mut self, device: &B::Device) -> Result<&B::Fence, StagingError> {
+        if !self.coherent {
+            let ranges = self.ranges(self.batch_start, self.head);
+            device.flush_mapped_memory_ranges(
+                ranges.into_iter().map(|range| (&self.memory, range)),
+            )?;
+        }
+        let fence = match self.free_fences.pop() {
+            Some(fence) => fence,
+            None => device.create_fence(false)?,
+        };
+        self.batches.push_back(Batch {
+            fence,
+            end: self.head,
+            readbacks: self.readbacks.drain(..).collect(),
+        });
+        self.batch_start = self.head;
+        Ok(&self.batches.back().unwrap().fence)
+    }
+
+    /// Reuse the space of the batches that are done, and complete their
+    /// readbacks.
+    ///
+    /// # Safety
+    ///
+    /// The fences of the finished batches have to be submitted.
+    pub unsafe fn poll(&mut self, device: &B::Device) -> Result<(), StagingError> {
+        while let Some(batch) = self.batches.front() {
+            if !device.get_fence_status(&batch.fence)? {
+                break;
+            }
+            let batch = self.batches.pop_front().unwrap();
+            self.complete(device, &batch)?;
+            self.tail = batch.end;
+            device.reset_fence(&batch.fence)?;
+            self.free_fences.push(batch.fence);
+        }
+        Ok(())
+    }
+
+    /// Wait for all the batches in flight, then poll the ring.
+    ///
+    /// # Safety
+    ///
+    /// The fences of the finished batches have to be submitted.
+    pub unsafe fn wait(&mut self, device: &B::Device) -> Result<(), StagingError> {
+        if !self.batches.is_empty() {
+            device.wait_for_fences(
+                self.batches.iter().map(|batch| &batch.fence),
+                d::WaitFor::All,
+                !0,
+            )?;
+        }
+        self.poll(device)
+    }
+
+    unsafe fn complete(&self, device: &B::Device, batch: &Batch<B>) -> Result<(), StagingError> {
+        for readback in &batch.readbacks {
+            let footprint = readback.footprint;
+            let size = footprint.row_pitch * footprint.rows;
+            if !self.coherent {
+                let ranges = self.ranges(readback.offset, readback.offset + size);
+                device.invalidate_mapped_memory_ranges(
+                    ranges.into_iter().map(|range| (&self.memory, range)),
+                )?;
+            }
+            let mut data = Vec::with_capacity((footprint.row_size * footprint.rows) as usize);
+            for row in 0 .. footprint.rows {
+                let start = self
+                    .ptr
+                    .as_ptr()
+                    .add((readback.offset + row * footprint.row_pitch) as usize);
+                data.extend_from_slice(slice::from_raw_parts(start, footprint.row_size as usize));
+            }
+            let mut state = readback.state.lock().unwrap();
+            state.data = Some(data);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        }
+        Ok(())
+    }
+
+    /// Destroy the ring.
+    ///
+    /// # Safety
+    ///
+    /// None of the batches may be in use by the device.
+    pub unsafe fn dispose(self, device: &B::Device) {
+        device.unmap_memory(&self.memory);
+        device.destroy_buffer(self.buffer);
+        device.free_memory(self.memory);
+        for batch in self.batches {
+            device.destroy_fence(batch.fence);
+        }
+        for fence in self.free_fences {
+            device.destroy_fence(fence);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hal::adapter::PhysicalDevice as _;
+    use hal::command as com;
+    use hal::pool::{self, CommandPool as _};
+    use hal::queue::{CommandQueue as _, Submission};
+    use hal::{format, memory};
+
+    type Empty = gfx_backend_empty::Backend;
+
+    #[test]
+    fn test_allocate() {
+        let limits = Limits {
+            optimal_buffer_copy_offset_alignment: 256,
+            optimal_buffer_copy_pitch_alignment: 128,
+            non_coherent_atom_size: 64,
+            ..Limits::default()
+        };
+        let instance = gfx_backend_empty::Instance::create("staging", 1).unwrap();
+        let (adapter, gpu) = gfx_backend_empty::test_utils::open(&instance);
+        let props = adapter.physical_device.memory_properties();
+        let mut ring =
+            unsafe { StagingRing::<Empty>::new(&gpu.device, &props, &limits, 1000).unwrap() };
+        assert_eq!(ring.capacity(), 1024);
+
+        // 3 bytes per texel: the pitch is a multiple of both
+        let footprint = ring.footprint(
+            format::Format::Rgb8Unorm,
+            image::Extent {
+                width: 10,
+                height: 4,
+                depth: 1,
+            },
+            2,
+        );
+        assert_eq!(footprint.row_size, 30);
+        assert_eq!(footprint.row_pitch, 384);
+        assert_eq!(footprint.rows, 8);
+        assert_eq!(footprint.buffer_width, 128);
+
+        assert_eq!(ring.allocate(300, 256), Ok(0));
+        assert_eq!(ring.allocate(300, 256), Ok(512));
+        // wraps around, but the first allocations are still in flight
+        assert_eq!(ring.allocate(300, 256), Err(StagingError::OutOfSpace));
+        ring.tail = 812;
+        assert_eq!(ring.allocate(300, 256), Ok(0));
+        assert_eq!(ring.used(), 1324 - 812);
+        assert_eq!(ring.ranges(812, 1324), vec![768 .. 1024, 0 .. 320]);
+        assert_eq!(
+            ring.allocate(2000, 1),
+            Err(StagingError::TooLarge {
+                size: 2000,
+                capacity: 1024
+            })
+        );
+
+        unsafe { ring.dispose(&gpu.device) };
+    }
+
+    #[test]
+    fn test_upload_readback() {
+        let instance = gfx_backend_empty::Instance::create("staging", 1).unwrap();
+        let (adapter, mut gpu) = gfx_backend_empty::test_utils::open(&instance);
+        let device = &gpu.device;
+        let props = adapter.physical_device.memory_properties();
+        let limits = adapter.physical_device.limits();
+
+        let buffer_data = (0 .. 100).collect::<Vec<u8>>();
+        let kind = image::Kind::D2(8, 8, 1, 1);
+        let level0 = (0 .. 64).map(|i| i as u8).collect::<Vec<_>>();
+        let level1 = (0 .. 16).map(|i| 100 + i as u8).collect::<Vec<_>>();
+
+        unsafe {
+            let mut ring = StagingRing::<Empty>::new(device, &props, &limits, 1 << 16).unwrap();
+            let mut buffer = device
+                .create_buffer(
+                    100,
+                    buffer::Usage::TRANSFER_SRC | buffer::Usage::TRANSFER_DST,
+                )
+                .unwrap();
+            let mut image = device
+                .create_image(
+                    kind,
+                    2,
+                    format::Format::R8Unorm,
+                    image::Tiling::Optimal,
+                    image::Usage::TRANSFER_SRC | image::Usage::TRANSFER_DST,
+                    image::ViewCapabilities::empty(),
+                )
+                .unwrap();
+            let buffer_size = device.get_buffer_requirements(&buffer).size;
+            let image_size = device.get_image_requirements(&image).size;
+            let memory = device
+                .allocate_memory(MemoryTypeId(0), buffer_size + image_size)
+                .unwrap();
+            device.bind_buffer_memory(&memory, 0, &mut buffer).unwrap();
+            device
+                .bind_image_memory(&memory, buffer_size, &mut image)
+                .unwrap();
+
+            let mut pool = device
+                .create_command_pool(
+                    gpu.queue_groups[0].family,
+                    pool::CommandPoolCreateFlags::empty(),
+                )
+                .unwrap();
+            let mut cmd_buffer = pool.allocate_one(com::Level::Primary);
+            cmd_buffer.begin_primary(com::CommandBufferFlags::ONE_TIME_SUBMIT);
+            ring.upload_buffer(&mut cmd_buffer, &buffer, 0, &buffer_data)
+                .unwrap();
+            ring.upload_image(
+                &mut cmd_buffer,
+                &image,
+                image::Layout::General,
+                kind,
+                format::Format::R8Unorm,
+                0 .. 1,
+                &[&level0, &level1],
+            )
+            .unwrap();
+            cmd_buffer.pipeline_barrier(
+                PipelineStage::TRANSFER .. PipelineStage::TRANSFER,
+                Dependencies::empty(),
+                iter::once(memory::Barrier::AllBuffers(
+                    buffer::Access::TRANSFER_WRITE .. buffer::Access::TRANSFER_READ,
+                )),
+            );
+            let buffer_readback = ring
+                .read_buffer(&mut cmd_buffer, &buffer, 10 .. 20)
+                .unwrap();
+            let image_readback = ring
+                .read_image(
+                    &mut cmd_buffer,
+                    &image,
+                    image::Layout::General,
+                    kind,
+                    format::Format::R8Unorm,
+                    1,
+                    0 .. 1,
+                )
+                .unwrap();
+            cmd_buffer.finish();
+
+            assert!(!buffer_readback.is_ready());
+            let fence = ring.finish(device).unwrap();
+            gpu.queue_groups[0].queues[0].submit(
+                Submission {
+                    command_buffers: iter::once(&cmd_buffer),
+                    wait_semaphores: iter::empty(),
+                    signal_semaphores: iter::empty::<&<Empty as Backend>::Semaphore>(),
+                },
+                Some(fence),
+            );
+            ring.wait(device).unwrap();
+
+            assert_eq!(ring.batches_in_flight(), 0);
+            assert_eq!(ring.used(), 0);
+            assert_eq!(
+                buffer_readback.try_take(),
+                Some(buffer_data[10 .. 20].to_vec())
+            );
+            assert_eq!(image_readback.try_take(), Some(level1));
+
+            pool.free(iter::once(cmd_buffer));
+            device.destroy_command_pool(pool);
+            device.destroy_buffer(buffer);
+            device.destroy_image(image);
+            device.free_memory(memory);
+            ring.dispose(device);
+        }
+    }
+}
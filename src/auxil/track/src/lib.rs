@@ -0,0 +1,734 @@
+//! Resource state tracking.
+//!
+//! The `Tracker` records the last known state of buffers, and of every
+//! subresource (mip level and array layer) of images. Declaring the next use
+//! of a resource adds the barriers it needs to a batch of `Transitions`,
+//! recorded as a single `pipeline_barrier`. Reads following reads in the same
+//! layout accumulate so the next write waits for all of them, and only need a
+//! barrier if the last write hasn't been made visible to their stages and
+//! accesses yet.
+//!
+//! Queue family ownership transfers are not tracked, and all the aspects of
+//! an image subresource share the same state.
+
+use hal::command::CommandBuffer as _;
+use hal::memory::{Barrier, Dependencies};
+use hal::pso::PipelineStage;
+use hal::{buffer, image, Backend};
+
+use std::ops::Range;
+
+fn buffer_writes() -> buffer::Access {
+    buffer::Access::SHADER_WRITE
+        | buffer::Access::TRANSFER_WRITE
+        | buffer::Access::HOST_WRITE
+        | buffer::Access::MEMORY_WRITE
+}
+
+fn image_writes() -> image::Access {
+    image::Access::SHADER_WRITE
+        | image::Access::COLOR_ATTACHMENT_WRITE
+        | image::Access::DEPTH_STENCIL_ATTACHMENT_WRITE
+        | image::Access::TRANSFER_WRITE
+        | image::Access::HOST_WRITE
+        | image::Access::MEMORY_WRITE
+}
+
+/// Use of a buffer by a set of commands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BufferUse {
+    pub access: buffer::Access,
+    /// Stages of the commands accessing the buffer.
+    pub stages: PipelineStage,
+}
+
+impl BufferUse {
+    /// State of a buffer that hasn't been used yet.
+    pub fn unused() -> Self {
+        BufferUse {
+            access: buffer::Access::empty(),
+            stages: PipelineStage::TOP_OF_PIPE,
+        }
+    }
+
+    /// Check if the buffer is written.
+    pub fn is_write(&self) -> bool {
+        self.access.intersects(buffer_writes())
+    }
+
+    /// Check if the buffer is read.
+    pub fn is_read(&self) -> bool {
+        !(self.access - buffer_writes()).is_empty()
+    }
+}
+
+/// Use of an image by a set of commands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImageUse {
+    pub access: image::Access,
+    pub layout: image::Layout,
+    /// Stages of the commands accessing the image.
+    pub stages: PipelineStage,
+}
+
+impl ImageUse {
+    /// State of an image that hasn't been used yet, in `layout`.
+    pub fn unused(layout: image::Layout) -> Self {
+        ImageUse {
+            access: image::Access::empty(),
+            layout,
+            stages: PipelineStage::TOP_OF_PIPE,
+        }
+    }
+
+    /// Check if the image is written.
+    pub fn is_write(&self) -> bool {
+        self.access.intersects(image_writes())
+    }
+}
+
+/// Last write of a resource, and the stages and accesses it was made visible to.
+#[derive(Clone, Copy, Debug)]
+struct WriteScope<A> {
+    access: A,
+    stages: PipelineStage,
+    visible_access: A,
+    visible_stages: PipelineStage,
+}
+
+impl WriteScope<buffer::Access> {
+    fn buffer(access: buffer::Access, stages: PipelineStage) -> Self {
+        WriteScope {
+            access,
+            stages,
+            visible_access: buffer::Access::empty(),
+            visible_stages: PipelineStage::empty(),
+        }
+    }
+
+    fn covers(&self, next: &BufferUse) -> bool {
+        self.visible_stages.contains(next.stages) && self.visible_access.contains(next.access)
+    }
+}
+
+impl WriteScope<image::Access> {
+    fn image(access: image::Access, stages: PipelineStage) -> Self {
+        WriteScope {
+            access,
+            stages,
+            visible_access: image::Access::empty(),
+            visible_stages: PipelineStage::empty(),
+        }
+    }
+
+    fn covers(&self, next: &ImageUse) -> bool {
+        self.visible_stages.contains(next.stages) && self.visible_access.contains(next.access)
+    }
+}
+
+#[derive(Debug)]
+struct BufferState {
+    /// Reads since the last write, or the last write itself.
+    current: BufferUse,
+    write: Option<WriteScope<buffer::Access>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct SubresourceState {
+    /// Reads since the last write or layout transition, or the last write itself.
+    current: ImageUse,
+    write: Option<WriteScope<image::Access>>,
+}
+
+/// Identifier of a buffer in a `Tracker`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BufferId(usize);
+
+/// Identifier of an image in a `Tracker`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ImageId(usize);
+
+#[derive(Debug)]
+struct ImageState {
+    levels: image::Level,
+    layers: image::Layer,
+    /// State of every subresource, by level then layer.
+    subresources: Vec<SubresourceState>,
+}
+
+impl ImageState {
+    fn index(&self, level: image::Level, layer: image::Layer) -> usize {
+        assert!(level < self.levels && layer < self.layers);
+        level as usize * self.layers as usize + layer as usize
+    }
+}
+
+/// Batch of barriers, to be recorded at once.
+#[derive(Debug)]
+pub struct Transitions<'a, B: Backend> {
+    stages: Range<PipelineStage>,
+    barriers: Vec<Barrier<'a, B>>,
+}
+
+impl<'a, B: Backend> Default for Transitions<'a, B> {
+    fn default() -> Self {
+        Transitions {
+            stages: PipelineStage::empty() .. PipelineStage::empty(),
+            barriers: Vec::new(),
+        }
+    }
+}
+
+impl<'a, B: Backend> Transitions<'a, B> {
+    /// Create an empty batch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check if no barrier is needed.
+    pub fn is_empty(&self) -> bool {
+        self.barriers.is_empty()
+    }
+
+    /// Get the stages to synchronize.
+    pub fn stages(&self) -> Range<PipelineStage> {
+        let src = if self.stages.start.is_empty() {
+            PipelineStage::TOP_OF_PIPE
+        } else {
+            self.stages.start
+        };
+        let dst = if self.stages.end.is_empty() {
+            PipelineStage::BOTTOM_OF_PIPE
+        } else {
+            self.stages.end
+        };
+        src .. dst
+    }
+
+    /// Get the barriers of the batch.
+    pub fn barriers(&self) -> &[Barrier<'a, B>] {
+        &self.barriers
+    }
+
+    fn add(&mut self, stages: Range<PipelineStage>, barrier: Barrier<'a, B>) {
+        self.stages.start |= stages.start;
+        self.stages.end |= stages.end;
+        self.barriers.push(barrier);
+    }
+
+    /// Extend the destination of the barrier of the batch transitioning `target`
+    /// into `layout`, and covering a given subresource, to another use.
+    /// Returns false if there is no such barrier.
+    fn widen_image(
+        &mut self,
+        target: &B::Image,
+        level: image::Level,
+        layer: image::Layer,
+        next: ImageUse,
+    ) -> bool {
+        for barrier in &mut self.barriers {
+            match *barrier {
+                Barrier::Image {
+                    ref mut states,
+                    target: t,
+                    ref range,
+                    ..
+                } if std::ptr::eq(t, target)
+                    && states.end.1 == next.layout
+                    && range.levels.start <= level
+                    && level < range.levels.end
+                    && range.layers.start <= layer
+                    && layer < range.layers.end =>
+                {
+                    states.end.0 |= next.access;
+                    self.stages.end |= next.stages;
+                    return true;
+                }
+                _ => {}
+            }
+        }
+        false
+    }
+
+    /// Extend the destination of the barrier of the batch on `target` to
+    /// another use. Returns false if there is no such barrier.
+    fn widen_buffer(&mut self, target: &B::Buffer, next: BufferUse) -> bool {
+        for barrier in &mut self.barriers {
+            match *barrier {
+                Barrier::Buffer {
+                    ref mut states,
+                    target: t,
+                    ..
+                } if std::ptr::eq(t, target) => {
+                    states.end |= next.access;
+                    self.stages.end |= next.stages;
+                    return true;
+                }
+                _ => {}
+            }
+        }
+        false
+    }
+
+    /// Record the barriers into a command buffer, if there are any.
+    ///
+    /// # Safety
+    ///
+    /// The command buffer has to be recording, outside of a render pass.
+    pub unsafe fn record(&self, cmd_buffer: &mut B::CommandBuffer) {
+        if !self.is_empty() {
+            cmd_buffer.pipeline_barrier(self.stages(), Dependencies::empty(), &self.barriers);
+        }
+    }
+}
+
+/// Tracker of the states of buffers and images.
+#[derive(Debug, Default)]
+pub struct Tracker {
+    buffers: Vec<Option<BufferState>>,
+    images: Vec<Option<ImageState>>,
+}
+
+fn insert<T>(slots: &mut Vec<Option<T>>, value: T) -> usize {
+    match slots.iter().position(Option::is_none) {
+        Some(index) => {
+            slots[index] = Some(value);
+            index
+        }
+        None => {
+            slots.push(Some(value));
+            slots.len() - 1
+        }
+    }
+}
+
+impl Tracker {
+    /// Create an empty tracker.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start tracking a buffer in `state`.
+    pub fn add_buffer(&mut self, state: BufferUse) -> BufferId {
+        let state = BufferState {
+            current: state,
+            write: if state.is_write() {
+                Some(WriteScope::buffer(state.access & buffer_writes(), state.stages))
+            } else {
+                None
+            },
+        };
+        BufferId(insert(&mut self.buffers, state))
+    }
+
+    /// Start tracking an image with all its subresources in `state`.
+    pub fn add_image(
+        &mut self,
+        levels: image::Level,
+        layers: image::Layer,
+        state: ImageUse,
+    ) -> ImageId {
+        let subresource = SubresourceState {
+            current: state,
+            write: if state.is_write() {
+                Some(WriteScope::image(state.access & image_writes(), state.stages))
+            } else {
+                None
+            },
+        };
+        let image = ImageState {
+            levels,
+            layers,
+            subresources: vec![subresource; levels as usize * layers as usize],
+        };
+        ImageId(insert(&mut self.images, image))
+    }
+
+    /// Stop tracking a buffer.
+    pub fn remove_buffer(&mut self, id: BufferId) {
+        self.buffers[id.0] = None;
+    }
+
+    /// Stop tracking an image.
+    pub fn remove_image(&mut self, id: ImageId) {
+        self.images[id.0] = None;
+    }
+
+    /// Get the last known state of a buffer.
+    pub fn buffer_state(&self, id: BufferId) -> BufferUse {
+        self.buffers[id.0]
+            .as_ref()
+            .expect("Buffer is not tracked")
+            .current
+    }
+
+    /// Get the last known state of an image subresource.
+    pub fn image_state(&self, id: ImageId, level: image::Level, layer: image::Layer) -> ImageUse {
+        let image = self.images[id.0].as_ref().expect("Image is not tracked");
+        image.subresources[image.index(level, layer)].current
+    }
+
+    /// Declare the next use of a buffer, adding the barrier it needs to
+    /// `transitions`.
+    pub fn use_buffer<'a, B: Backend>(
+        &mut self,
+        id: BufferId,
+        target: &'a B::Buffer,
+        next: BufferUse,
+        transitions: &mut Transitions<'a, B>,
+    ) {
+        let state = self.buffers[id.0].as_mut().expect("Buffer is not tracked");
+        let writes = buffer_writes();
+        if next.is_write() {
+            if !state.current.access.is_empty() {
+                transitions.add(
+                    state.current.stages .. next.stages,
+                    Barrier::whole_buffer(target, (state.current.access & writes) .. next.access),
+                );
+            }
+            state.current = next;
+            state.write = Some(WriteScope::buffer(next.access & writes, next.stages));
+            return;
+        }
+
+        if let Some(ref mut write) = state.write {
+            if !write.covers(&next) && !transitions.widen_buffer(target, next) {
+                transitions.add(
+                    write.stages .. next.stages,
+                    Barrier::whole_buffer(target, write.access .. next.access),
+                );
+            }
+            write.visible_access |= next.access;
+            write.visible_stages |= next.stages;
+        }
+        if state.current.is_write() || state.current.access.is_empty() {
+            state.current = next;
+        } else {
+            state.current.access |= next.access;
+            state.current.stages |= next.stages;
+        }
+    }
+
+    /// Declare the next use of a range of image subresources, adding the
+    /// barriers they need to `transitions`.
+    ///
+    /// Subresources in the same previous state share a barrier.
+    pub fn use_image<'a, B: Backend>(
+        &mut self,
+        id: ImageId,
+        target: &'a B::Image,
+        range: image::SubresourceRange,
+        next: ImageUse,
+        transitions: &mut Transitions<'a, B>,
+    ) {
+        let image = self.images[id.0].as_mut().expect("Image is not tracked");
+        let writes = image_writes();
+        // previous states to transition from, by level and run of layers
+        let mut runs: Vec<(image::State, Range<image::Level>, Range<image::Layer>)> = Vec::new();
+        let mut stages = PipelineStage::empty();
+
+        for level in range.levels.clone() {
+            let first_run = runs.len();
+            for layer in range.layers.clone() {
+                let index = image.index(level, layer);
+                let sub = &mut image.subresources[index];
+                let state = sub.current;
+                let src = if state.layout != next.layout || next.is_write() {
+                    if state.access.is_empty() && state.layout == next.layout {
+                        // nothing to wait for
+                        None
+                    } else {
+                        stages |= state.stages;
+                        Some((state.access & writes, state.layout))
+                    }
+                } else {
+                    match sub.write {
+                        Some(ref write)
+                            if !write.covers(&next)
+                                && !transitions.widen_image(target, level, layer, next) =>
+                        {
+                            // A barrier recorded earlier made the write visible to other
+                            // stages, chain with it.
+                            stages |= write.stages | write.visible_stages;
+                            Some((write.access, state.layout))
+                        }
+                        _ => None,
+                    }
+                };
+
+                if state.layout != next.layout || next.is_write() {
+                    sub.current = next;
+                    sub.write = Some(if next.is_write() {
+                        WriteScope::image(next.access & writes, next.stages)
+                    } else {
+                        // The layout transition is made visible to this use only.
+                        WriteScope {
+                            access: state.access & writes,
+                            stages: state.stages,
+                            visible_access: next.access,
+                            visible_stages: next.stages,
+                        }
+                    });
+                } else {
+                    if let Some(ref mut write) = sub.write {
+                        write.visible_access |= next.access;
+                        write.visible_stages |= next.stages;
+                    }
+                    if state.is_write() || state.access.is_empty() {
+                        sub.current = next;
+                    } else {
+                        sub.current.access |= next.access;
+                        sub.current.stages |= next.stages;
+                    }
+                }
+
+                let src = match src {
+                    Some(src) => src,
+                    None => continue,
+                };
+                match runs[first_run ..].last_mut() {
+                    Some(&mut (ref s, _, ref mut layers)) if *s == src && layers.end == layer => {
+                        layers.end += 1
+                    }
+                    _ => runs.push((src, level .. level + 1, layer .. layer + 1)),
+                }
+            }
+        }
+
+        // merge the runs of consecutive levels covering the same layers
+        let mut merged: Vec<(image::State, Range<image::Level>, Range<image::Layer>)> = Vec::new();
+        for (src, levels, layers) in runs {
+            let found = merged.iter_mut().find(|&&mut (ref s, ref l, ref r)| {
+                *s == src && l.end == levels.start && *r == layers
+            });
+            match found {
+                Some(&mut (_, ref mut l, _)) => l.end = levels.end,
+                None => merged.push((src, levels, layers)),
+            }
+        }
+
+        for (src, levels, layers) in merged {
+            transitions.add(
+                stages .. next.stages,
+                Barrier::Image {
+                    states: src .. (next.access, next.layout),
+                    target,
+                    families: None,
+                    range: image::SubresourceRange {
+                        aspects: range.aspects,
+                        levels,
+                        layers,
+                    },
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hal::command as com;
+    use hal::device::Device as _;
+    use hal::pool::{self, CommandPool as _};
+    use hal::queue::QueueFamily as _;
+    use hal::format;
+
+    type Empty = gfx_backend_empty::Backend;
+
+    fn open() -> (<Empty as Backend>::Device, hal::queue::QueueFamilyId) {
+        let instance = gfx_backend_empty::Instance::create("track", 1).unwrap();
+        let (adapter, gpu) = gfx_backend_empty::test_utils::open(&instance);
+        (gpu.device, adapter.queue_families[0].id())
+    }
+
+    #[test]
+    fn test_buffer_hazards() {
+        let (device, family) = open();
+        let buffer = unsafe {
+            device
+                .create_buffer(64, buffer::Usage::TRANSFER_DST | buffer::Usage::UNIFORM)
+                .unwrap()
+        };
+        let upload = BufferUse {
+            access: buffer::Access::TRANSFER_WRITE,
+            stages: PipelineStage::TRANSFER,
+        };
+        let vertex = BufferUse {
+            access: buffer::Access::UNIFORM_READ,
+            stages: PipelineStage::VERTEX_SHADER,
+        };
+        let fragment = BufferUse {
+            access: buffer::Access::UNIFORM_READ,
+            stages: PipelineStage::FRAGMENT_SHADER,
+        };
+        let mut tracker = Tracker::new();
+        let id = tracker.add_buffer(BufferUse::unused());
+
+        // the first write waits for nothing
+        let mut transitions = Transitions::<Empty>::new();
+        tracker.use_buffer(id, &buffer, upload, &mut transitions);
+        assert!(transitions.is_empty());
+
+        // read after write, the second reader widens the barrier
+        tracker.use_buffer(id, &buffer, vertex, &mut transitions);
+        tracker.use_buffer(id, &buffer, fragment, &mut transitions);
+        assert_eq!(transitions.barriers().len(), 1);
+        assert_eq!(
+            transitions.stages(),
+            PipelineStage::TRANSFER .. PipelineStage::VERTEX_SHADER | PipelineStage::FRAGMENT_SHADER
+        );
+        match transitions.barriers()[0] {
+            Barrier::Buffer { ref states, .. } => assert_eq!(
+                *states,
+                buffer::Access::TRANSFER_WRITE .. buffer::Access::UNIFORM_READ
+            ),
+            ref other => panic!("unexpected {:?}", other),
+        }
+        unsafe {
+            let mut pool = device
+                .create_command_pool(family, pool::CommandPoolCreateFlags::empty())
+                .unwrap();
+            let mut cmd_buffer = pool.allocate_one(com::Level::Primary);
+            cmd_buffer.begin_primary(com::CommandBufferFlags::ONE_TIME_SUBMIT);
+            transitions.record(&mut cmd_buffer);
+            cmd_buffer.finish();
+        }
+
+        // reading again in covered stages needs nothing
+        let mut transitions = Transitions::<Empty>::new();
+        tracker.use_buffer(id, &buffer, vertex, &mut transitions);
+        assert!(transitions.is_empty());
+
+        // a reader outside of the scope of the recorded barrier waits for the write
+        let compute = BufferUse {
+            access: buffer::Access::SHADER_READ,
+            stages: PipelineStage::COMPUTE_SHADER,
+        };
+        tracker.use_buffer(id, &buffer, compute, &mut transitions);
+        assert_eq!(
+            transitions.stages(),
+            PipelineStage::TRANSFER .. PipelineStage::COMPUTE_SHADER
+        );
+        match transitions.barriers()[0] {
+            Barrier::Buffer { ref states, .. } => assert_eq!(
+                *states,
+                buffer::Access::TRANSFER_WRITE .. buffer::Access::SHADER_READ
+            ),
+            ref other => panic!("unexpected {:?}", other),
+        }
+
+        // write after the reads waits for all the stages
+        let mut transitions = Transitions::<Empty>::new();
+        tracker.use_buffer(id, &buffer, upload, &mut transitions);
+        assert_eq!(
+            transitions.stages(),
+            PipelineStage::VERTEX_SHADER
+                | PipelineStage::FRAGMENT_SHADER
+                | PipelineStage::COMPUTE_SHADER
+                .. PipelineStage::TRANSFER
+        );
+        assert_eq!(tracker.buffer_state(id), upload);
+    }
+
+    #[test]
+    fn test_image_subresources() {
+        let (device, _) = open();
+        let image = unsafe {
+            device
+                .create_image(
+                    image::Kind::D2(16, 16, 2, 1),
+                    4,
+                    format::Format::Rgba8Unorm,
+                    image::Tiling::Optimal,
+                    image::Usage::TRANSFER_DST | image::Usage::SAMPLED,
+                    image::ViewCapabilities::empty(),
+                )
+                .unwrap()
+        };
+        let range =
+            |levels: Range<image::Level>, layers: Range<image::Layer>| image::SubresourceRange {
+                aspects: format::Aspects::COLOR,
+                levels,
+                layers,
+            };
+        let copy = ImageUse {
+            access: image::Access::TRANSFER_WRITE,
+            layout: image::Layout::TransferDstOptimal,
+            stages: PipelineStage::TRANSFER,
+        };
+        let sample = ImageUse {
+            access: image::Access::SHADER_READ,
+            layout: image::Layout::ShaderReadOnlyOptimal,
+            stages: PipelineStage::FRAGMENT_SHADER,
+        };
+        let mut tracker = Tracker::new();
+        let id = tracker.add_image(4, 2, ImageUse::unused(image::Layout::Undefined));
+
+        // layout transition of the first level only
+        let mut transitions = Transitions::<Empty>::new();
+        tracker.use_image(id, &image, range(0 .. 1, 0 .. 2), copy, &mut transitions);
+        assert_eq!(transitions.barriers().len(), 1);
+        assert_eq!(tracker.image_state(id, 0, 1), copy);
+        assert_eq!(
+            tracker.image_state(id, 1, 0),
+            ImageUse::unused(image::Layout::Undefined)
+        );
+
+        // sampling the whole image needs one barrier per previous state
+        let mut transitions = Transitions::<Empty>::new();
+        tracker.use_image(id, &image, range(0 .. 4, 0 .. 2), sample, &mut transitions);
+        let barriers = transitions
+            .barriers()
+            .iter()
+            .map(|barrier| match *barrier {
+                Barrier::Image {
+                    ref states,
+                    ref range,
+                    ..
+                } => (states.start, range.levels.clone(), range.layers.clone()),
+                ref other => panic!("unexpected {:?}", other),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            barriers,
+            vec![
+                (
+                    (
+                        image::Access::TRANSFER_WRITE,
+                        image::Layout::TransferDstOptimal
+                    ),
+                    0 .. 1,
+                    0 .. 2
+                ),
+                (
+                    (image::Access::empty(), image::Layout::Undefined),
+                    1 .. 4,
+                    0 .. 2
+                ),
+            ]
+        );
+
+        // sampling again needs nothing
+        let mut transitions = Transitions::<Empty>::new();
+        tracker.use_image(id, &image, range(0 .. 4, 0 .. 2), sample, &mut transitions);
+        assert!(transitions.is_empty());
+
+        // sampling from another stage chains with the layout transitions
+        let compute = ImageUse {
+            stages: PipelineStage::COMPUTE_SHADER,
+            ..sample
+        };
+        tracker.use_image(id, &image, range(0 .. 1, 0 .. 2), compute, &mut transitions);
+        assert_eq!(transitions.barriers().len(), 1);
+        assert_eq!(
+            transitions.stages(),
+            PipelineStage::TRANSFER | PipelineStage::FRAGMENT_SHADER
+                .. PipelineStage::COMPUTE_SHADER
+        );
+        assert_eq!(
+            tracker.image_state(id, 0, 0).stages,
+            PipelineStage::FRAGMENT_SHADER | PipelineStage::COMPUTE_SHADER
+        );
+    }
+}
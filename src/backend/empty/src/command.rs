@@ -0,0 +1,694 @@
+#![allow(missing_docs)]
+
+use hal::range::RangeArg;
+use hal::{buffer, command, image, memory, pso, query, IndexType};
+
+use crate::{native as n, Backend};
+
+use std::borrow::Borrow;
+use std::ops::Range;
+
+// Command buffer implementation details:
+//
+// Commands are recorded into a list owned by the command buffer, together
+// with shared references to the memory of the resources they access.
+// Everything is executed on the CPU by the queue at submission time,
+// including the state changes, which are tracked by the queue.
+
+#[derive(Clone, Debug)]
+pub enum Command {
+    CopyBuffer {
+        src: n::Binding,
+        dst: n::Binding,
+        regions: Vec<command::BufferCopy>,
+    },
+    FillBuffer {
+        dst: n::Binding,
+        range: Range<buffer::Offset>,
+        data: u32,
+    },
+    UpdateBuffer {
+        dst: n::Binding,
+        offset: buffer::Offset,
+        data: Vec<u8>,
+    },
+    CopyImage {
+        src: n::BoundImage,
+        dst: n::BoundImage,
+        regions: Vec<command::ImageCopy>,
+    },
+    CopyBufferToImage {
+        src: n::Binding,
+        dst: n::BoundImage,
+        regions: Vec<command::BufferImageCopy>,
+    },
+    CopyImageToBuffer {
+        src: n::BoundImage,
+        dst: n::Binding,
+        regions: Vec<command::BufferImageCopy>,
+    },
+    ClearImage {
+        image: n::BoundImage,
+        value: command::ClearValue,
+        ranges: Vec<image::SubresourceRange>,
+    },
+    ClearAttachments {
+        clears: Vec<command::AttachmentClear>,
+        rects: Vec<pso::ClearRect>,
+    },
+    BlitImage {
+        src: n::BoundImage,
+        dst: n::BoundImage,
+        filter: image::Filter,
+        regions: Vec<command::ImageBlit>,
+    },
+    BeginRenderPass {
+        render_pass: n::RenderPass,
+        framebuffer: n::Framebuffer,
+        area: pso::Rect,
+        clear_values: Vec<command::ClearValue>,
+    },
+    NextSubpass,
+    EndRenderPass,
+    BindGraphicsPipeline(n::GraphicsPipeline),
+    BindComputePipeline(n::ComputePipeline),
+    BindDescriptorSets {
+        compute: bool,
+        first: usize,
+        sets: Vec<n::DescriptorSet>,
+        offsets: Vec<command::DescriptorSetOffset>,
+    },
+    BindVertexBuffers {
+        first: pso::BufferIndex,
+        buffers: Vec<n::Binding>,
+    },
+    BindIndexBuffer(n::Binding, IndexType),
+    SetViewport(pso::Viewport),
+    SetScissor(pso::Rect),
+    SetStencilReference(pso::Face, pso::StencilValue),
+    SetStencilReadMask(pso::Face, pso::StencilValue),
+    SetStencilWriteMask(pso::Face, pso::StencilValue),
+    SetBlendConstants(pso::ColorValue),
+    SetDepthBounds(Range<f32>),
+    SetDepthBias(pso::DepthBias),
+    PushConstants {
+        offset: u32,
+        data: Vec<u32>,
+    },
+    Draw {
+        vertices: Range<hal::VertexCount>,
+        instances: Range<hal::InstanceCount>,
+    },
+    DrawIndexed {
+        indices: Range<hal::IndexCount>,
+        base_vertex: hal::VertexOffset,
+        instances: Range<hal::InstanceCount>,
+    },
+    DrawIndirect {
+        buffer: n::Binding,
+        count: hal::DrawCount,
+        stride: u32,
+        indexed: bool,
+    },
+    Dispatch(hal::WorkGroupCount),
+    DispatchIndirect(n::Binding),
+    SetEvent(n::Event, bool),
+    ResetQueryPool(n::QueryPool, Range<query::Id>),
+    BeginQuery(n::QueryPool, query::Id),
+    EndQuery(n::QueryPool, query::Id),
+    WriteTimestamp(n::QueryPool, query::Id),
+    CopyQueryPoolResults {
+        pool: n::QueryPool,
+        queries: Range<query::Id>,
+        dst: n::Binding,
+        stride: buffer::Offset,
+        flags: query::ResultFlags,
+    },
+}
+
+#[derive(Debug)]
+pub struct CommandBuffer {
+    pub(crate) commands: Vec<Command>,
+}
+
+impl CommandBuffer {
+    pub(crate) fn new() -> Self {
+        CommandBuffer {
+            commands: Vec::new(),
+        }
+    }
+}
+
+impl command::CommandBuffer<Backend> for CommandBuffer {
+    unsafe fn begin(
+        &mut self,
+        _flags: command::CommandBufferFlags,
+        _inheritance_info: command::CommandBufferInheritanceInfo<Backend>,
+    ) {
+        self.commands.clear();
+    }
+
+    unsafe fn finish(&mut self) {
+        // no-op
+    }
+
+    unsafe fn reset(&mut self, release_resources: bool) {
+        self.commands.clear();
+        if release_resources {
+            self.commands.shrink_to_fit();
+        }
+    }
+
+    unsafe fn pipeline_barrier<'a, T>(
+        &mut self,
+        _stages: Range<pso::PipelineStage>,
+        _dependencies: memory::Dependencies,
+        _barriers: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<memory::Barrier<'a, Backend>>,
+    {
+        // Commands are executed in order, so there is nothing to synchronize.
+    }
+
+    unsafe fn fill_buffer<R>(&mut self, buffer: &n::Buffer, range: R, data: u32)
+    where
+        R: RangeArg<buffer::Offset>,
+    {
+        let start = *range.start().unwrap_or(&0);
+        // Filling up to the end of the buffer rounds the size down to a multiple of 4.
+        let end = match range.end() {
+            Some(&end) => end,
+            None => start + (buffer.size - start) / 4 * 4,
+        };
+        self.commands.push(Command::FillBuffer {
+            dst: buffer.as_bound(),
+            range: start .. end,
+            data,
+        });
+    }
+
+    unsafe fn update_buffer(&mut self, buffer: &n::Buffer, offset: buffer::Offset, data: &[u8]) {
+        self.commands.push(Command::UpdateBuffer {
+            dst: buffer.as_bound(),
+            offset,
+            data: data.to_vec(),
+        });
+    }
+
+    unsafe fn clear_image<T>(
+        &mut self,
+        image: &n::Image,
+        _layout: image::Layout,
+        value: command::ClearValue,
+        subresource_ranges: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<image::SubresourceRange>,
+    {
+        self.commands.push(Command::ClearImage {
+            image: image.as_bound(),
+            value,
+            ranges: subresource_ranges
+                .into_iter()
+                .map(|r| r.borrow().clone())
+                .collect(),
+        });
+    }
+
+    unsafe fn clear_attachments<T, U>(&mut self, clears: T, rects: U)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<command::AttachmentClear>,
+        U: IntoIterator,
+        U::Item: Borrow<pso::ClearRect>,
+    {
+        self.commands.push(Command::ClearAttachments {
+            clears: clears.into_iter().map(|c| *c.borrow()).collect(),
+            rects: rects.into_iter().map(|r| r.borrow().clone()).collect(),
+        });
+    }
+
+    unsafe fn resolve_image<T>(
+        &mut self,
+        src: &n::Image,
+        _src_layout: image::Layout,
+        dst: &n::Image,
+        _dst_layout: image::Layout,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<command::ImageResolve>,
+    {
+        // Images have a single sample, so resolving is copying.
+        self.commands.push(Command::CopyImage {
+            src: src.as_bound(),
+            dst: dst.as_bound(),
+            regions: regions
+                .into_iter()
+                .map(|r| {
+                    let r = r.borrow();
+                    command::ImageCopy {
+                        src_subresource: r.src_subresource.clone(),
+                        src_offset: r.src_offset,
+                        dst_subresource: r.dst_subresource.clone(),
+                        dst_offset: r.dst_offset,
+                        extent: r.extent,
+                    }
+                })
+                .collect(),
+        });
+    }
+
+    unsafe fn blit_image<T>(
+        &mut self,
+        src: &n::Image,
+        _src_layout: image::Layout,
+        dst: &n::Image,
+        _dst_layout: image::Layout,
+        filter: image::Filter,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<command::ImageBlit>,
+    {
+        self.commands.push(Command::BlitImage {
+            src: src.as_bound(),
+            dst: dst.as_bound(),
+            filter,
+            regions: regions.into_iter().map(|r| r.borrow().clone()).collect(),
+        });
+    }
+
+    unsafe fn bind_index_buffer(&mut self, view: buffer::IndexBufferView<Backend>) {
+        let mut binding = view.buffer.as_bound();
+        binding.offset += view.offset;
+        self.commands
+            .push(Command::BindIndexBuffer(binding, view.index_type));
+    }
+
+    unsafe fn bind_vertex_buffers<I, T>(&mut self, first_binding: pso::BufferIndex, buffers: I)
+    where
+        I: IntoIterator<Item = (T, buffer::Offset)>,
+        T: Borrow<n::Buffer>,
+    {
+        self.commands.push(Command::BindVertexBuffers {
+            first: first_binding,
+            buffers: buffers
+                .into_iter()
+                .map(|(buffer, offset)| {
+                    let mut binding = buffer.borrow().as_bound();
+                    binding.offset += offset;
+                    binding
+                })
+                .collect(),
+        });
+    }
+
+    unsafe fn set_viewports<T>(&mut self, first_viewport: u32, viewports: T)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<pso::Viewport>,
+    {
+        // Only the first viewport is used, as there are no geometry shaders.
+        if first_viewport == 0 {
+            if let Some(viewport) = viewports.into_iter().next() {
+                self.commands
+                    .push(Command::SetViewport(viewport.borrow().clone()));
+            }
+        }
+    }
+
+    unsafe fn set_scissors<T>(&mut self, first_scissor: u32, scissors: T)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<pso::Rect>,
+    {
+        if first_scissor == 0 {
+            if let Some(scissor) = scissors.into_iter().next() {
+                self.commands.push(Command::SetScissor(*scissor.borrow()));
+            }
+        }
+    }
+
+    unsafe fn set_stencil_reference(&mut self, faces: pso::Face, value: pso::StencilValue) {
+        self.commands
+            .push(Command::SetStencilReference(faces, value));
+    }
+
+    unsafe fn set_stencil_read_mask(&mut self, faces: pso::Face, value: pso::StencilValue) {
+        self.commands
+            .push(Command::SetStencilReadMask(faces, value));
+    }
+
+    unsafe fn set_stencil_write_mask(&mut self, faces: pso::Face, value: pso::StencilValue) {
+        self.commands
+            .push(Command::SetStencilWriteMask(faces, value));
+    }
+
+    unsafe fn set_blend_constants(&mut self, color: pso::ColorValue) {
+        self.commands.push(Command::SetBlendConstants(color));
+    }
+
+    unsafe fn set_depth_bounds(&mut self, bounds: Range<f32>) {
+        self.commands.push(Command::SetDepthBounds(bounds));
+    }
+
+    unsafe fn set_line_width(&mut self, _width: f32) {
+        // Lines are always one pixel wide.
+    }
+
+    unsafe fn set_depth_bias(&mut self, depth_bias: pso::DepthBias) {
+        self.commands.push(Command::SetDepthBias(depth_bias));
+    }
+
+    unsafe fn begin_render_pass<T>(
+        &mut self,
+        render_pass: &n::RenderPass,
+        framebuffer: &n::Framebuffer,
+        render_area: pso::Rect,
+        clear_values: T,
+        _first_subpass: command::SubpassContents,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<command::ClearValue>,
+    {
+        self.commands.push(Command::BeginRenderPass {
+            render_pass: render_pass.clone(),
+            framebuffer: framebuffer.clone(),
+            area: render_area,
+            clear_values: clear_values.into_iter().map(|c| *c.borrow()).collect(),
+        });
+    }
+
+    unsafe fn next_subpass(&mut self, _contents: command::SubpassContents) {
+        self.commands.push(Command::NextSubpass);
+    }
+
+    unsafe fn end_render_pass(&mut self) {
+        self.commands.push(Command::EndRenderPass);
+    }
+
+    unsafe fn bind_graphics_pipeline(&mut self, pipeline: &n::GraphicsPipeline) {
+        self.commands
+            .push(Command::BindGraphicsPipeline(pipeline.clone()));
+    }
+
+    unsafe fn bind_graphics_descriptor_sets<I, J>(
+        &mut self,
+        _layout: &n::PipelineLayout,
+        first_set: usize,
+        sets: I,
+        offsets: J,
+    ) where
+        I: IntoIterator,
+        I::Item: Borrow<n::DescriptorSet>,
+        J: IntoIterator,
+        J::Item: Borrow<command::DescriptorSetOffset>,
+    {
+        self.commands.push(Command::BindDescriptorSets {
+            compute: false,
+            first: first_set,
+            sets: sets.into_iter().map(|s| s.borrow().clone()).collect(),
+            offsets: offsets.into_iter().map(|o| *o.borrow()).collect(),
+        });
+    }
+
+    unsafe fn bind_compute_pipeline(&mut self, pipeline: &n::ComputePipeline) {
+        self.commands
+            .push(Command::BindComputePipeline(pipeline.clone()));
+    }
+
+    unsafe fn bind_compute_descriptor_sets<I, J>(
+        &mut self,
+        _layout: &n::PipelineLayout,
+        first_set: usize,
+        sets: I,
+        offsets: J,
+    ) where
+        I: IntoIterator,
+        I::Item: Borrow<n::DescriptorSet>,
+        J: IntoIterator,
+        J::Item: Borrow<command::DescriptorSetOffset>,
+    {
+        self.commands.push(Command::BindDescriptorSets {
+            compute: true,
+            first: first_set,
+            sets: sets.into_iter().map(|s| s.borrow().clone()).collect(),
+            offsets: offsets.into_iter().map(|o| *o.borrow()).collect(),
+        });
+    }
+
+    unsafe fn dispatch(&mut self, count: hal::WorkGroupCount) {
+        self.commands.push(Command::Dispatch(count));
+    }
+
+    unsafe fn dispatch_indirect(&mut self, buffer: &n::Buffer, offset: buffer::Offset) {
+        let mut binding = buffer.as_bound();
+        binding.offset += offset;
+        self.commands.push(Command::DispatchIndirect(binding));
+    }
+
+    unsafe fn copy_buffer<T>(&mut self, src: &n::Buffer, dst: &n::Buffer, regions: T)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<command::BufferCopy>,
+    {
+        self.commands.push(Command::CopyBuffer {
+            src: src.as_bound(),
+            dst: dst.as_bound(),
+            regions: regions.into_iter().map(|r| *r.borrow()).collect(),
+        });
+    }
+
+    unsafe fn copy_image<T>(
+        &mut self,
+        src: &n::Image,
+        _src_layout: image::Layout,
+        dst: &n::Image,
+        _dst_layout: image::Layout,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<command::ImageCopy>,
+    {
+        self.commands.push(Command::CopyImage {
+            src: src.as_bound(),
+            dst: dst.as_bound(),
+            regions: regions.into_iter().map(|r| r.borrow().clone()).collect(),
+        });
+    }
+
+    unsafe fn copy_buffer_to_image<T>(
+        &mut self,
+        src: &n::Buffer,
+        dst: &n::Image,
+        _dst_layout: image::Layout,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<command::BufferImageCopy>,
+    {
+        self.commands.push(Command::CopyBufferToImage {
+            src: src.as_bound(),
+            dst: dst.as_bound(),
+            regions: regions.into_iter().map(|r| r.borrow().clone()).collect(),
+        });
+    }
+
+    unsafe fn copy_image_to_buffer<T>(
+        &mut self,
+        src: &n::Image,
+        _src_layout: image::Layout,
+        dst: &n::Buffer,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<command::BufferImageCopy>,
+    {
+        self.commands.push(Command::CopyImageToBuffer {
+            src: src.as_bound(),
+            dst: dst.as_bound(),
+            regions: regions.into_iter().map(|r| r.borrow().clone()).collect(),
+        });
+    }
+
+    unsafe fn draw(
+        &mut self,
+        vertices: Range<hal::VertexCount>,
+        instances: Range<hal::InstanceCount>,
+    ) {
+        self.commands.push(Command::Draw {
+            vertices,
+            instances,
+        });
+    }
+
+    unsafe fn draw_indexed(
+        &mut self,
+        indices: Range<hal::IndexCount>,
+        base_vertex: hal::VertexOffset,
+        instances: Range<hal::InstanceCount>,
+    ) {
+        self.commands.push(Command::DrawIndexed {
+            indices,
+            base_vertex,
+            instances,
+        });
+    }
+
+    unsafe fn draw_indirect(
+        &mut self,
+        buffer: &n::Buffer,
+        offset: buffer::Offset,
+        draw_count: hal::DrawCount,
+        stride: u32,
+    ) {
+        let mut binding = buffer.as_bound();
+        binding.offset += offset;
+        self.commands.push(Command::DrawIndirect {
+            buffer: binding,
+            count: draw_count,
+            stride,
+            indexed: false,
+        });
+    }
+
+    unsafe fn draw_indexed_indirect(
+        &mut self,
+        buffer: &n::Buffer,
+        offset: buffer::Offset,
+        draw_count: hal::DrawCount,
+        stride: u32,
+    ) {
+        let mut binding = buffer.as_bound();
+        binding.offset += offset;
+        self.commands.push(Command::DrawIndirect {
+            buffer: binding,
+            count: draw_count,
+            stride,
+            indexed: true,
+        });
+    }
+
+    unsafe fn set_event(&mut self, event: &n::Event, _stage_mask: pso::PipelineStage) {
+        self.commands.push(Command::SetEvent(event.clone(), true));
+    }
+
+    unsafe fn reset_event(&mut self, event: &n::Event, _stage_mask: pso::PipelineStage) {
+        self.commands.push(Command::SetEvent(event.clone(), false));
+    }
+
+    unsafe fn wait_events<'a, I, J>(
+        &mut self,
+        _events: I,
+        _stages: Range<pso::PipelineStage>,
+        _barriers: J,
+    ) where
+        I: IntoIterator,
+        I::Item: Borrow<n::Event>,
+        J: IntoIterator,
+        J::Item: Borrow<memory::Barrier<'a, Backend>>,
+    {
+        // Events can only be signaled by commands executed before,
+        // or from the host ahead of the submission.
+    }
+
+    unsafe fn begin_query(&mut self, query: query::Query<Backend>, _flags: query::ControlFlags) {
+        // Occlusion queries are always precise.
+        self.commands
+            .push(Command::BeginQuery(query.pool.clone(), query.id));
+    }
+
+    unsafe fn end_query(&mut self, query: query::Query<Backend>) {
+        self.commands
+            .push(Command::EndQuery(query.pool.clone(), query.id));
+    }
+
+    unsafe fn reset_query_pool(&mut self, pool: &n::QueryPool, queries: Range<query::Id>) {
+        self.commands
+            .push(Command::ResetQueryPool(pool.clone(), queries));
+    }
+
+    unsafe fn copy_query_pool_results(
+        &mut self,
+        pool: &n::QueryPool,
+        queries: Range<query::Id>,
+        buffer: &n::Buffer,
+        offset: buffer::Offset,
+        stride: buffer::Offset,
+        flags: query::ResultFlags,
+    ) {
+        let mut dst = buffer.as_bound();
+        dst.offset += offset;
+        self.commands.push(Command::CopyQueryPoolResults {
+            pool: pool.clone(),
+            queries,
+            dst,
+            stride,
+            flags,
+        });
+    }
+
+    unsafe fn write_timestamp(&mut self, _stage: pso::PipelineStage, query: query::Query<Backend>) {
+        self.commands
+            .push(Command::WriteTimestamp(query.pool.clone(), query.id));
+    }
+
+    unsafe fn push_graphics_constants(
+        &mut self,
+        _layout: &n::PipelineLayout,
+        _stages: pso::ShaderStageFlags,
+        offset: u32,
+        constants: &[u32],
+    ) {
+        // Graphics and compute pipelines share the same push constant storage.
+        self.commands.push(Command::PushConstants {
+            offset,
+            data: constants.to_vec(),
+        });
+    }
+
+    unsafe fn push_compute_constants(
+        &mut self,
+        _layout: &n::PipelineLayout,
+        offset: u32,
+        constants: &[u32],
+    ) {
+        self.commands.push(Command::PushConstants {
+            offset,
+            data: constants.to_vec(),
+        });
+    }
+
+    unsafe fn execute_commands<'a, T, I>(&mut self, buffers: I)
+    where
+        T: 'a + Borrow<CommandBuffer>,
+        I: IntoIterator<Item = &'a T>,
+    {
+        for buffer in buffers {
+            self.commands
+                .extend(buffer.borrow().commands.iter().cloned());
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CommandPool;
+
+impl hal::pool::CommandPool<Backend> for CommandPool {
+    unsafe fn reset(&mut self, _release_resources: bool) {
+        // Command buffers own their commands, and `begin` discards the old ones.
+    }
+
+    fn allocate_one(&mut self, _level: command::Level) -> CommandBuffer {
+        CommandBuffer::new()
+    }
+
+    unsafe fn free<I>(&mut self, _buffers: I)
+    where
+        I: IntoIterator<Item = CommandBuffer>,
+    {
+        // Dropping the command buffers releases their memory.
+    }
+}
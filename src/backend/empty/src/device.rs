@@ -0,0 +1,744 @@
+use hal::range::RangeArg;
+use hal::{buffer, device as d, format, image as i, memory, pass, pool, pso, query, queue, window};
+
+use crate::command::CommandPool;
+use crate::{native as n, Backend, Surface, Swapchain};
+#[cfg(feature = "software")]
+use crate::{raster, shader};
+
+use std::borrow::Borrow;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Alignment of buffers and images inside of a memory object.
+const RESOURCE_ALIGNMENT: u64 = 16;
+
+/// Device creating resources in host memory.
+#[derive(Debug)]
+pub struct Device {
+    pub(crate) memory_types: Vec<hal::adapter::MemoryType>,
+    /// Reference point for timestamp queries.
+    pub(crate) epoch: Instant,
+}
+
+impl Device {
+    // Returns the mask with all the memory types set, as every resource can live in any of them.
+    fn memory_type_mask(&self) -> u64 {
+        (1 << self.memory_types.len()) - 1
+    }
+}
+
+impl d::Device<Backend> for Device {
+    unsafe fn create_command_pool(
+        &self,
+        _family: queue::QueueFamilyId,
+        _flags: pool::CommandPoolCreateFlags,
+    ) -> Result<CommandPool, d::OutOfMemory> {
+        Ok(CommandPool)
+    }
+
+    unsafe fn destroy_command_pool(&self, _pool: CommandPool) {
+        // no-op
+    }
+
+    unsafe fn allocate_memory(
+        &self,
+        mem_type: hal::MemoryTypeId,
+        size: u64,
+    ) -> Result<n::Memory, d::AllocationError> {
+        let properties = self.memory_types[mem_type.0].properties;
+        let block = n::Block::new(size).ok_or(d::OutOfMemory::Device)?;
+        Ok(n::Memory {
+            properties,
+            block: Arc::new(block),
+        })
+    }
+
+    unsafe fn create_render_pass<'a, IA, IS, ID>(
+        &self,
+        attachments: IA,
+        subpasses: IS,
+        _dependencies: ID,
+    ) -> Result<n::RenderPass, d::OutOfMemory>
+    where
+        IA: IntoIterator,
+        IA::Item: Borrow<pass::Attachment>,
+        IS: IntoIterator,
+        IS::Item: Borrow<pass::SubpassDesc<'a>>,
+        ID: IntoIterator,
+        ID::Item: Borrow<pass::SubpassDependency>,
+    {
+        let ids = |refs: &[pass::AttachmentRef]| refs.iter().map(|&(id, _)| id).collect();
+        Ok(n::RenderPass {
+            attachments: attachments
+                .into_iter()
+                .map(|attachment| attachment.borrow().clone())
+                .collect(),
+            subpasses: subpasses
+                .into_iter()
+                .map(|subpass| {
+                    let subpass = subpass.borrow();
+                    n::Subpass {
+                        colors: ids(subpass.colors),
+                        depth_stencil: subpass.depth_stencil.map(|&(id, _)| id),
+                        resolves: ids(subpass.resolves),
+                    }
+                })
+                .collect(),
+        })
+    }
+
+    unsafe fn create_pipeline_layout<IS, IR>(
+        &self,
+        _set_layouts: IS,
+        _push_constant_ranges: IR,
+    ) -> Result<n::PipelineLayout, d::OutOfMemory>
+    where
+        IS: IntoIterator,
+        IS::Item: Borrow<n::DescriptorSetLayout>,
+        IR: IntoIterator,
+        IR::Item: Borrow<(pso::ShaderStageFlags, Range<u32>)>,
+    {
+        Ok(n::PipelineLayout)
+    }
+
+    unsafe fn create_pipeline_cache(
+        &self,
+        _data: Option<&[u8]>,
+    ) -> Result<n::PipelineCache, d::OutOfMemory> {
+        Ok(n::PipelineCache)
+    }
+
+    unsafe fn get_pipeline_cache_data(
+        &self,
+        _cache: &n::PipelineCache,
+    ) -> Result<Vec<u8>, d::OutOfMemory> {
+        Ok(Vec::new())
+    }
+
+    unsafe fn destroy_pipeline_cache(&self, _cache: n::PipelineCache) {
+        // no-op
+    }
+
+    unsafe fn merge_pipeline_caches<I>(
+        &self,
+        _target: &n::PipelineCache,
+        _sources: I,
+    ) -> Result<(), d::OutOfMemory>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<n::PipelineCache>,
+    {
+        Ok(())
+    }
+
+    #[cfg(feature = "software")]
+    unsafe fn create_graphics_pipeline<'a>(
+        &self,
+        desc: &pso::GraphicsPipelineDesc<'a, Backend>,
+        _cache: Option<&n::PipelineCache>,
+    ) -> Result<n::GraphicsPipeline, pso::CreationError> {
+        let shaders = &desc.shaders;
+        if shaders.hull.is_some() || shaders.domain.is_some() || shaders.geometry.is_some() {
+            return Err(pso::CreationError::Other);
+        }
+        let vertex = program(&shaders.vertex, pso::Stage::Vertex)?;
+        let fragment = match shaders.fragment {
+            Some(ref entry) => Some(program(entry, pso::Stage::Fragment)?),
+            None => None,
+        };
+        Ok(n::GraphicsPipeline {
+            pipeline: Arc::new(raster::Pipeline::new(desc, vertex, fragment)?),
+        })
+    }
+
+    #[cfg(not(feature = "software"))]
+    unsafe fn create_graphics_pipeline<'a>(
+        &self,
+        _desc: &pso::GraphicsPipelineDesc<'a, Backend>,
+        _cache: Option<&n::PipelineCache>,
+    ) -> Result<n::GraphicsPipeline, pso::CreationError> {
+        Ok(n::GraphicsPipeline {})
+    }
+
+    #[cfg(feature = "software")]
+    unsafe fn create_compute_pipeline<'a>(
+        &self,
+        desc: &pso::ComputePipelineDesc<'a, Backend>,
+        _cache: Option<&n::PipelineCache>,
+    ) -> Result<n::ComputePipeline, pso::CreationError> {
+        Ok(n::ComputePipeline {
+            program: Arc::new(program(&desc.shader, pso::Stage::Compute)?),
+        })
+    }
+
+    #[cfg(not(feature = "software"))]
+    unsafe fn create_compute_pipeline<'a>(
+        &self,
+        _desc: &pso::ComputePipelineDesc<'a, Backend>,
+        _cache: Option<&n::PipelineCache>,
+    ) -> Result<n::ComputePipeline, pso::CreationError> {
+        Ok(n::ComputePipeline {})
+    }
+
+    unsafe fn create_framebuffer<I>(
+        &self,
+        _render_pass: &n::RenderPass,
+        attachments: I,
+        _extent: i::Extent,
+    ) -> Result<n::Framebuffer, d::OutOfMemory>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<n::ImageView>,
+    {
+        Ok(n::Framebuffer {
+            views: attachments
+                .into_iter()
+                .map(|view| view.borrow().clone())
+                .collect(),
+        })
+    }
+
+    #[cfg(feature = "software")]
+    unsafe fn create_shader_module(
+        &self,
+        spirv: &[u32],
+    ) -> Result<n::ShaderModule, d::ShaderError> {
+        Ok(n::ShaderModule {
+            module: Arc::new(shader::Module::parse(spirv)?),
+        })
+    }
+
+    #[cfg(not(feature = "software"))]
+    unsafe fn create_shader_module(
+        &self,
+        _spirv: &[u32],
+    ) -> Result<n::ShaderModule, d::ShaderError> {
+        Ok(n::ShaderModule {})
+    }
+
+    unsafe fn create_sampler(
+        &self,
+        info: i::SamplerInfo,
+    ) -> Result<n::Sampler, d::AllocationError> {
+        Ok(n::Sampler { info })
+    }
+
+    unsafe fn create_buffer(
+        &self,
+        size: u64,
+        _usage: buffer::Usage,
+    ) -> Result<n::Buffer, buffer::CreationError> {
+        Ok(n::Buffer {
+            size,
+            binding: None,
+        })
+    }
+
+    unsafe fn get_buffer_requirements(&self, buffer: &n::Buffer) -> memory::Requirements {
+        memory::Requirements {
+            size: buffer.size,
+            alignment: RESOURCE_ALIGNMENT,
+            type_mask: self.memory_type_mask(),
+        }
+    }
+
+    unsafe fn bind_buffer_memory(
+        &self,
+        memory: &n::Memory,
+        offset: u64,
+        buffer: &mut n::Buffer,
+    ) -> Result<(), d::BindError> {
+        if offset + buffer.size > memory.block.size() {
+            return Err(d::BindError::OutOfBounds);
+        }
+        buffer.binding = Some(n::Binding {
+            block: Arc::clone(&memory.block),
+            offset,
+        });
+        Ok(())
+    }
+
+    unsafe fn create_buffer_view<R: RangeArg<u64>>(
+        &self,
+        buffer: &n::Buffer,
+        format: Option<format::Format>,
+        range: R,
+    ) -> Result<n::BufferView, buffer::ViewCreationError> {
+        let format = format.ok_or(buffer::ViewCreationError::UnsupportedFormat { format })?;
+        let start = *range.start().unwrap_or(&0);
+        let end = *range.end().unwrap_or(&buffer.size);
+        let binding = buffer.as_bound();
+        Ok(n::BufferView {
+            binding: n::Binding {
+                offset: binding.offset + start,
+                ..binding
+            },
+            format,
+            size: end - start,
+        })
+    }
+
+    unsafe fn create_image(
+        &self,
+        kind: i::Kind,
+        mip_levels: i::Level,
+        format: format::Format,
+        _tiling: i::Tiling,
+        _usage: i::Usage,
+        _view_caps: i::ViewCapabilities,
+    ) -> Result<n::Image, i::CreationError> {
+        if kind.num_samples() != 1 {
+            return Err(i::CreationError::Samples(kind.num_samples()));
+        }
+        Ok(n::Image {
+            desc: n::ImageDesc::new(kind, mip_levels, format),
+            binding: None,
+        })
+    }
+
+    unsafe fn get_image_requirements(&self, image: &n::Image) -> memory::Requirements {
+        memory::Requirements {
+            size: image.desc.size,
+            alignment: RESOURCE_ALIGNMENT,
+            type_mask: self.memory_type_mask(),
+        }
+    }
+
+    unsafe fn get_image_subresource_footprint(
+        &self,
+        image: &n::Image,
+        subresource: i::Subresource,
+    ) -> i::SubresourceFootprint {
+        image.desc.footprint(subresource.level, subresource.layer)
+    }
+
+    unsafe fn bind_image_memory(
+        &self,
+        memory: &n::Memory,
+        offset: u64,
+        image: &mut n::Image,
+    ) -> Result<(), d::BindError> {
+        if offset + image.desc.size > memory.block.size() {
+            return Err(d::BindError::OutOfBounds);
+        }
+        image.binding = Some(n::Binding {
+            block: Arc::clone(&memory.block),
+            offset,
+        });
+        Ok(())
+    }
+
+    unsafe fn create_image_view(
+        &self,
+        image: &n::Image,
+        kind: i::ViewKind,
+        format: format::Format,
+        swizzle: format::Swizzle,
+        range: i::SubresourceRange,
+    ) -> Result<n::ImageView, i::ViewError> {
+        if range.levels.end > image.desc.levels {
+            return Err(i::ViewError::Level(range.levels.start));
+        }
+        if range.layers.end > image.desc.kind.num_layers() {
+            return Err(i::ViewError::Layer(i::LayerError::OutOfBounds(
+                range.layers.clone(),
+            )));
+        }
+        Ok(n::ImageView {
+            image: image.as_bound(),
+            kind,
+            format,
+            swizzle,
+            range,
+        })
+    }
+
+    unsafe fn create_descriptor_pool<I>(
+        &self,
+        max_sets: usize,
+        descriptor_ranges: I,
+        _flags: pso::DescriptorPoolCreateFlags,
+    ) -> Result<n::DescriptorPool, d::OutOfMemory>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<pso::DescriptorRangeDesc>,
+    {
+        let mut ranges = Vec::<pso::DescriptorRangeDesc>::new();
+        for range in descriptor_ranges {
+            let range = range.borrow();
+            match ranges.iter_mut().find(|r| r.ty == range.ty) {
+                Some(r) => r.count += range.count,
+                None => ranges.push(pso::DescriptorRangeDesc {
+                    ty: range.ty,
+                    count: range.count,
+                }),
+            }
+        }
+        Ok(n::DescriptorPool::new(max_sets, ranges))
+    }
+
+    unsafe fn create_descriptor_set_layout<I, J>(
+        &self,
+        bindings: I,
+        immutable_samplers: J,
+    ) -> Result<n::DescriptorSetLayout, d::OutOfMemory>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<pso::DescriptorSetLayoutBinding>,
+        J: IntoIterator,
+        J::Item: Borrow<n::Sampler>,
+    {
+        let mut bindings = bindings
+            .into_iter()
+            .map(|binding| binding.borrow().clone())
+            .collect::<Vec<_>>();
+        bindings.sort_by_key(|binding| binding.binding);
+        Ok(n::DescriptorSetLayout {
+            bindings,
+            immutable_samplers: immutable_samplers
+                .into_iter()
+                .map(|sampler| sampler.borrow().clone())
+                .collect(),
+        })
+    }
+
+    unsafe fn write_descriptor_sets<'a, I, J>(&self, writes: I)
+    where
+        I: IntoIterator<Item = pso::DescriptorSetWrite<'a, Backend, J>>,
+        J: IntoIterator,
+        J::Item: Borrow<pso::Descriptor<'a, Backend>>,
+    {
+        for write in writes {
+            let mut descriptors = write.set.descriptors.lock().unwrap();
+            let mut binding = write.set.binding_index(write.binding);
+            let mut element = write.array_offset;
+            for descriptor in write.descriptors {
+                // Writes past the end of a binding spill over to the next one.
+                while element >= descriptors[binding].len() {
+                    element -= descriptors[binding].len();
+                    binding += 1;
+                }
+                let target = &mut descriptors[binding][element];
+                match *descriptor.borrow() {
+                    pso::Descriptor::Sampler(sampler) => target.sampler = Some(sampler.clone()),
+                    pso::Descriptor::Image(view, _) => target.image = Some(view.clone()),
+                    pso::Descriptor::CombinedImageSampler(view, _, sampler) => {
+                        target.image = Some(view.clone());
+                        // Immutable samplers are already filled in.
+                        if target.sampler.is_none() {
+                            target.sampler = Some(sampler.clone());
+                        }
+                    }
+                    pso::Descriptor::Buffer(buffer, ref range) => {
+                        let start = range.start.unwrap_or(0);
+                        let end = range.end.unwrap_or(buffer.size);
+                        target.buffer = Some((buffer.as_bound(), start .. end));
+                    }
+                    pso::Descriptor::UniformTexelBuffer(view)
+                    | pso::Descriptor::StorageTexelBuffer(view) => {
+                        target.texel_buffer = Some(view.clone())
+                    }
+                }
+                element += 1;
+            }
+        }
+    }
+
+    unsafe fn copy_descriptor_sets<'a, I>(&self, copies: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<pso::DescriptorSetCopy<'a, Backend>>,
+    {
+        for copy in copies {
+            let copy = copy.borrow();
+            let src = copy.src_set.descriptors.lock().unwrap().clone();
+            let mut dst = copy.dst_set.descriptors.lock().unwrap();
+            let mut src_binding = copy.src_set.binding_index(copy.src_binding);
+            let mut src_element = copy.src_array_offset;
+            let mut dst_binding = copy.dst_set.binding_index(copy.dst_binding);
+            let mut dst_element = copy.dst_array_offset;
+            for _ in 0 .. copy.count {
+                while src_element >= src[src_binding].len() {
+                    src_element -= src[src_binding].len();
+                    src_binding += 1;
+                }
+                while dst_element >= dst[dst_binding].len() {
+                    dst_element -= dst[dst_binding].len();
+                    dst_binding += 1;
+                }
+                dst[dst_binding][dst_element] = src[src_binding][src_element].clone();
+                src_element += 1;
+                dst_element += 1;
+            }
+        }
+    }
+
+    fn create_semaphore(&self) -> Result<n::Semaphore, d::OutOfMemory> {
+        Ok(n::Semaphore)
+    }
+
+    fn create_fence(&self, signaled: bool) -> Result<n::Fence, d::OutOfMemory> {
+        Ok(n::Fence::new(signaled))
+    }
+
+    unsafe fn reset_fence(&self, fence: &n::Fence) -> Result<(), d::OutOfMemory> {
+        *fence.signaled.lock().unwrap() = false;
+        Ok(())
+    }
+
+    unsafe fn wait_for_fence(
+        &self,
+        fence: &n::Fence,
+        timeout_ns: u64,
+    ) -> Result<bool, d::OomOrDeviceLost> {
+        let signaled = fence.signaled.lock().unwrap();
+        let (signaled, _) = fence
+            .condvar
+            .wait_timeout_while(signaled, Duration::from_nanos(timeout_ns), |signaled| {
+                !*signaled
+            })
+            .unwrap();
+        Ok(*signaled)
+    }
+
+    unsafe fn get_fence_status(&self, fence: &n::Fence) -> Result<bool, d::DeviceLost> {
+        Ok(*fence.signaled.lock().unwrap())
+    }
+
+    fn create_event(&self) -> Result<n::Event, d::OutOfMemory> {
+        Ok(n::Event(Arc::new(AtomicBool::new(false))))
+    }
+
+    unsafe fn get_event_status(&self, event: &n::Event) -> Result<bool, d::OomOrDeviceLost> {
+        Ok(event.0.load(Ordering::Acquire))
+    }
+
+    unsafe fn set_event(&self, event: &n::Event) -> Result<(), d::OutOfMemory> {
+        event.0.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    unsafe fn reset_event(&self, event: &n::Event) -> Result<(), d::OutOfMemory> {
+        event.0.store(false, Ordering::Release);
+        Ok(())
+    }
+
+    unsafe fn create_query_pool(
+        &self,
+        ty: query::Type,
+        count: query::Id,
+    ) -> Result<n::QueryPool, query::CreationError> {
+        Ok(n::QueryPool {
+            ty,
+            results: Arc::new(Mutex::new(vec![None; count as usize])),
+            epoch: self.epoch,
+        })
+    }
+
+    unsafe fn destroy_query_pool(&self, _pool: n::QueryPool) {
+        // no-op
+    }
+
+    unsafe fn get_query_pool_results(
+        &self,
+        pool: &n::QueryPool,
+        queries: Range<query::Id>,
+        data: &mut [u8],
+        stride: buffer::Offset,
+        flags: query::ResultFlags,
+    ) -> Result<bool, d::OomOrDeviceLost> {
+        Ok(pool.write_results(queries, data, stride, flags))
+    }
+
+    unsafe fn map_memory<R: RangeArg<u64>>(
+        &self,
+        memory: &n::Memory,
+        range: R,
+    ) -> Result<*mut u8, d::MapError> {
+        if !memory.properties.contains(memory::Properties::CPU_VISIBLE) {
+            return Err(d::MapError::MappingFailed);
+        }
+        let offset = *range.start().unwrap_or(&0);
+        let end = *range.end().unwrap_or(&memory.block.size());
+        if offset > end || end > memory.block.size() {
+            return Err(d::MapError::OutOfBounds);
+        }
+        Ok(memory.block.ptr().offset(offset as isize))
+    }
+
+    unsafe fn unmap_memory(&self, _memory: &n::Memory) {
+        // no-op
+    }
+
+    unsafe fn flush_mapped_memory_ranges<'a, I, R>(&self, _ranges: I) -> Result<(), d::OutOfMemory>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<(&'a n::Memory, R)>,
+        R: RangeArg<u64>,
+    {
+        // Memory is shared with the host, there are no caches to flush.
+        Ok(())
+    }
+
+    unsafe fn invalidate_mapped_memory_ranges<'a, I, R>(
+        &self,
+        _ranges: I,
+    ) -> Result<(), d::OutOfMemory>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<(&'a n::Memory, R)>,
+        R: RangeArg<u64>,
+    {
+        // Memory is shared with the host, there are no caches to invalidate.
+        Ok(())
+    }
+
+    unsafe fn free_memory(&self, _memory: n::Memory) {
+        // The block is released once all the bound resources are gone.
+    }
+
+    unsafe fn destroy_shader_module(&self, _module: n::ShaderModule) {
+        // no-op
+    }
+
+    unsafe fn destroy_render_pass(&self, _render_pass: n::RenderPass) {
+        // no-op
+    }
+
+    unsafe fn destroy_pipeline_layout(&self, _layout: n::PipelineLayout) {
+        // no-op
+    }
+
+    unsafe fn destroy_graphics_pipeline(&self, _pipeline: n::GraphicsPipeline) {
+        // no-op
+    }
+
+    unsafe fn destroy_compute_pipeline(&self, _pipeline: n::ComputePipeline) {
+        // no-op
+    }
+
+    unsafe fn destroy_framebuffer(&self, _framebuffer: n::Framebuffer) {
+        // no-op
+    }
+
+    unsafe fn destroy_buffer(&self, _buffer: n::Buffer) {
+        // no-op
+    }
+
+    unsafe fn destroy_buffer_view(&self, _view: n::BufferView) {
+        // no-op
+    }
+
+    unsafe fn destroy_image(&self, _image: n::Image) {
+        // no-op
+    }
+
+    unsafe fn destroy_image_view(&self, _view: n::ImageView) {
+        // no-op
+    }
+
+    unsafe fn destroy_sampler(&self, _sampler: n::Sampler) {
+        // no-op
+    }
+
+    unsafe fn destroy_descriptor_pool(&self, _pool: n::DescriptorPool) {
+        // no-op
+    }
+
+    unsafe fn destroy_descriptor_set_layout(&self, _layout: n::DescriptorSetLayout) {
+        // no-op
+    }
+
+    unsafe fn destroy_fence(&self, _fence: n::Fence) {
+        // no-op
+    }
+
+    unsafe fn destroy_semaphore(&self, _semaphore: n::Semaphore) {
+        // no-op
+    }
+
+    unsafe fn destroy_event(&self, _event: n::Event) {
+        // no-op
+    }
+
+    unsafe fn create_swapchain(
+        &self,
+        _surface: &mut Surface,
+        _config: window::SwapchainConfig,
+        _old_swapchain: Option<Swapchain>,
+    ) -> Result<(Swapchain, Vec<n::Image>), window::CreationError> {
+        // The surfaces can't be presented to.
+        Err(window::CreationError::SurfaceLost(d::SurfaceLost))
+    }
+
+    unsafe fn destroy_swapchain(&self, _swapchain: Swapchain) {
+        // no-op
+    }
+
+    fn wait_idle(&self) -> Result<(), d::OutOfMemory> {
+        // Submissions are executed synchronously.
+        Ok(())
+    }
+
+    unsafe fn set_image_name(&self, _image: &mut n::Image, _name: &str) {
+        // no-op
+    }
+
+    unsafe fn set_buffer_name(&self, _buffer: &mut n::Buffer, _name: &str) {
+        // no-op
+    }
+
+    unsafe fn set_command_buffer_name(
+        &self,
+        _command_buffer: &mut crate::command::CommandBuffer,
+        _name: &str,
+    ) {
+        // no-op
+    }
+
+    unsafe fn set_semaphore_name(&self, _semaphore: &mut n::Semaphore, _name: &str) {
+        // no-op
+    }
+
+    unsafe fn set_fence_name(&self, _fence: &mut n::Fence, _name: &str) {
+        // no-op
+    }
+
+    unsafe fn set_framebuffer_name(&self, _framebuffer: &mut n::Framebuffer, _name: &str) {
+        // no-op
+    }
+
+    unsafe fn set_render_pass_name(&self, _render_pass: &mut n::RenderPass, _name: &str) {
+        // no-op
+    }
+
+    unsafe fn set_descriptor_set_name(&self, _descriptor_set: &mut n::DescriptorSet, _name: &str) {
+        // no-op
+    }
+
+    unsafe fn set_descriptor_set_layout_name(
+        &self,
+        _descriptor_set_layout: &mut n::DescriptorSetLayout,
+        _name: &str,
+    ) {
+        // no-op
+    }
+}
+
+// Creates the program of a pipeline shader stage.
+#[cfg(feature = "software")]
+fn program(
+    entry: &pso::EntryPoint<Backend>,
+    stage: pso::Stage,
+) -> Result<shader::Program, pso::CreationError> {
+    shader::Program::new(
+        &entry.module.module,
+        entry.entry,
+        stage,
+        &entry.specialization,
+    )
+    .map_err(pso::CreationError::Shader)
+}
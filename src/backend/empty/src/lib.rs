@@ -1,30 +1,41 @@
-//! Dummy backend implementation to test the code for compile errors
-//! outside of the graphics development environment.
+//! CPU backend implementation, executing everything on the host.
+//!
+//! Memory objects are plain host allocations, images use a linear layout,
+//! and command buffers are executed on the submitting thread. This allows
+//! running the code outside of the graphics development environment,
+//! like on CI machines without a GPU. Transfer, clear, blit, event and
+//! query commands are always executed.
+//!
+//! With the `software` feature, SPIR-V shaders are interpreted as well:
+//! compute dispatches run the shader for every invocation, and draws are
+//! rasterized into the attachments following the rasterizer, blend and
+//! depth-stencil states of the pipeline. This is slow, and meant for
+//! reference tests rather than rendering.
 
 extern crate gfx_hal as hal;
 #[cfg(feature = "winit")]
 extern crate winit;
 
-use hal::range::RangeArg;
-use hal::{
-    adapter,
-    buffer,
-    command,
-    device,
-    format,
-    image,
-    memory,
-    pass,
-    pool,
-    pso,
-    query,
-    queue,
-    window,
-};
-use std::borrow::Borrow;
-use std::ops::Range;
-
-/// Dummy backend.
+use hal::{adapter, device as d, format, image, memory, queue as q, window};
+use hal::{Features, Limits};
+use std::time::Instant;
+
+mod command;
+mod device;
+mod native;
+mod queue;
+#[cfg(feature = "software")]
+mod raster;
+#[cfg(feature = "software")]
+mod shader;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+
+pub use crate::command::{CommandBuffer, CommandPool};
+pub use crate::device::Device;
+pub use crate::queue::CommandQueue;
+
+/// CPU backend.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Backend {}
 impl hal::Backend for Backend {
@@ -38,907 +49,265 @@ impl hal::Backend for Backend {
     type CommandQueue = CommandQueue;
     type CommandBuffer = CommandBuffer;
 
-    type Memory = ();
+    type Memory = native::Memory;
     type CommandPool = CommandPool;
 
-    type ShaderModule = ();
-    type RenderPass = ();
-    type Framebuffer = ();
-
-    type Buffer = ();
-    type BufferView = ();
-    type Image = ();
-    type ImageView = ();
-    type Sampler = ();
-
-    type ComputePipeline = ();
-    type GraphicsPipeline = ();
-    type PipelineCache = ();
-    type PipelineLayout = ();
-    type DescriptorSetLayout = ();
-    type DescriptorPool = DescriptorPool;
-    type DescriptorSet = ();
-
-    type Fence = ();
-    type Semaphore = ();
-    type Event = ();
-    type QueryPool = ();
+    type ShaderModule = native::ShaderModule;
+    type RenderPass = native::RenderPass;
+    type Framebuffer = native::Framebuffer;
+
+    type Buffer = native::Buffer;
+    type BufferView = native::BufferView;
+    type Image = native::Image;
+    type ImageView = native::ImageView;
+    type Sampler = native::Sampler;
+
+    type ComputePipeline = native::ComputePipeline;
+    type GraphicsPipeline = native::GraphicsPipeline;
+    type PipelineCache = native::PipelineCache;
+    type PipelineLayout = native::PipelineLayout;
+    type DescriptorSetLayout = native::DescriptorSetLayout;
+    type DescriptorPool = native::DescriptorPool;
+    type DescriptorSet = native::DescriptorSet;
+
+    type Fence = native::Fence;
+    type Semaphore = native::Semaphore;
+    type Event = native::Event;
+    type QueryPool = native::QueryPool;
 }
 
-/// Dummy physical device.
-#[derive(Debug)]
-pub struct PhysicalDevice;
-impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
-    unsafe fn open(
-        &self,
-        _: &[(&QueueFamily, &[queue::QueuePriority])],
-        _: hal::Features,
-    ) -> Result<adapter::Gpu<Backend>, device::CreationError> {
-        unimplemented!()
-    }
-
-    fn format_properties(&self, _: Option<format::Format>) -> format::Properties {
-        unimplemented!()
-    }
-
-    fn image_format_properties(
-        &self,
-        _: format::Format,
-        _dim: u8,
-        _: image::Tiling,
-        _: image::Usage,
-        _: image::ViewCapabilities,
-    ) -> Option<image::FormatProperties> {
-        unimplemented!()
-    }
-
-    fn memory_properties(&self) -> adapter::MemoryProperties {
-        unimplemented!()
-    }
-
-    fn features(&self) -> hal::Features {
-        unimplemented!()
-    }
-
-    fn limits(&self) -> hal::Limits {
-        unimplemented!()
-    }
-}
+/// Maximum number of queues that can be created.
+const MAX_QUEUES: usize = 4;
 
-/// Dummy command queue doing nothing.
+/// Physical device representing the host CPU.
 #[derive(Debug)]
-pub struct CommandQueue;
-impl queue::CommandQueue<Backend> for CommandQueue {
-    unsafe fn submit<'a, T, Ic, S, Iw, Is>(
-        &mut self,
-        _: queue::Submission<Ic, Iw, Is>,
-        _: Option<&()>,
-    ) where
-        T: 'a + Borrow<CommandBuffer>,
-        Ic: IntoIterator<Item = &'a T>,
-        S: 'a + Borrow<()>,
-        Iw: IntoIterator<Item = (&'a S, pso::PipelineStage)>,
-        Is: IntoIterator<Item = &'a S>,
-    {
-        unimplemented!()
-    }
-
-    unsafe fn present<'a, W, Is, S, Iw>(
-        &mut self,
-        _: Is,
-        _: Iw,
-    ) -> Result<Option<window::Suboptimal>, window::PresentError>
-    where
-        W: 'a + Borrow<Swapchain>,
-        Is: IntoIterator<Item = (&'a W, window::SwapImageIndex)>,
-        S: 'a + Borrow<()>,
-        Iw: IntoIterator<Item = &'a S>,
-    {
-        unimplemented!()
-    }
-
-    unsafe fn present_surface(
-        &mut self,
-        _surface: &mut Surface,
-        _image: (),
-        _wait_semaphore: Option<&()>,
-    ) -> Result<Option<window::Suboptimal>, window::PresentError> {
-        unimplemented!()
-    }
-
-    fn wait_idle(&self) -> Result<(), device::OutOfMemory> {
-        unimplemented!()
-    }
+pub struct PhysicalDevice {
+    memory_types: Vec<adapter::MemoryType>,
 }
 
-/// Dummy device doing nothing.
-#[derive(Debug)]
-pub struct Device;
-impl device::Device<Backend> for Device {
-    unsafe fn create_command_pool(
-        &self,
-        _: queue::QueueFamilyId,
-        _: pool::CommandPoolCreateFlags,
-    ) -> Result<CommandPool, device::OutOfMemory> {
-        unimplemented!()
-    }
-
-    unsafe fn destroy_command_pool(&self, _: CommandPool) {
-        unimplemented!()
-    }
-
-    unsafe fn allocate_memory(
-        &self,
-        _: hal::MemoryTypeId,
-        _: u64,
-    ) -> Result<(), device::AllocationError> {
-        unimplemented!()
-    }
-
-    unsafe fn create_render_pass<'a, IA, IS, ID>(
-        &self,
-        _: IA,
-        _: IS,
-        _: ID,
-    ) -> Result<(), device::OutOfMemory>
-    where
-        IA: IntoIterator,
-        IA::Item: Borrow<pass::Attachment>,
-        IS: IntoIterator,
-        IS::Item: Borrow<pass::SubpassDesc<'a>>,
-        ID: IntoIterator,
-        ID::Item: Borrow<pass::SubpassDependency>,
-    {
-        unimplemented!()
-    }
-
-    unsafe fn create_pipeline_layout<IS, IR>(&self, _: IS, _: IR) -> Result<(), device::OutOfMemory>
-    where
-        IS: IntoIterator,
-        IS::Item: Borrow<()>,
-        IR: IntoIterator,
-        IR::Item: Borrow<(pso::ShaderStageFlags, Range<u32>)>,
-    {
-        unimplemented!()
-    }
-
-    unsafe fn create_pipeline_cache(
-        &self,
-        _data: Option<&[u8]>,
-    ) -> Result<(), device::OutOfMemory> {
-        unimplemented!()
-    }
-
-    unsafe fn get_pipeline_cache_data(&self, _cache: &()) -> Result<Vec<u8>, device::OutOfMemory> {
-        unimplemented!()
-    }
-
-    unsafe fn destroy_pipeline_cache(&self, _: ()) {
-        unimplemented!()
-    }
-
-    unsafe fn create_graphics_pipeline<'a>(
-        &self,
-        _: &pso::GraphicsPipelineDesc<'a, Backend>,
-        _: Option<&()>,
-    ) -> Result<(), pso::CreationError> {
-        unimplemented!()
-    }
-
-    unsafe fn create_compute_pipeline<'a>(
-        &self,
-        _: &pso::ComputePipelineDesc<'a, Backend>,
-        _: Option<&()>,
-    ) -> Result<(), pso::CreationError> {
-        unimplemented!()
-    }
-
-    unsafe fn merge_pipeline_caches<I>(&self, _: &(), _: I) -> Result<(), device::OutOfMemory>
-    where
-        I: IntoIterator,
-        I::Item: Borrow<()>,
-    {
-        unimplemented!()
-    }
-
-    unsafe fn create_framebuffer<I>(
-        &self,
-        _: &(),
-        _: I,
-        _: image::Extent,
-    ) -> Result<(), device::OutOfMemory>
-    where
-        I: IntoIterator,
-        I::Item: Borrow<()>,
-    {
-        unimplemented!()
-    }
-
-    unsafe fn create_shader_module(&self, _: &[u32]) -> Result<(), device::ShaderError> {
-        unimplemented!()
-    }
-
-    unsafe fn create_sampler(&self, _: image::SamplerInfo) -> Result<(), device::AllocationError> {
-        unimplemented!()
-    }
-    unsafe fn create_buffer(&self, _: u64, _: buffer::Usage) -> Result<(), buffer::CreationError> {
-        unimplemented!()
-    }
-
-    unsafe fn get_buffer_requirements(&self, _: &()) -> memory::Requirements {
-        unimplemented!()
-    }
-
-    unsafe fn bind_buffer_memory(
-        &self,
-        _: &(),
-        _: u64,
-        _: &mut (),
-    ) -> Result<(), device::BindError> {
-        unimplemented!()
-    }
-
-    unsafe fn create_buffer_view<R: RangeArg<u64>>(
-        &self,
-        _: &(),
-        _: Option<format::Format>,
-        _: R,
-    ) -> Result<(), buffer::ViewCreationError> {
-        unimplemented!()
-    }
-
-    unsafe fn create_image(
-        &self,
-        _: image::Kind,
-        _: image::Level,
-        _: format::Format,
-        _: image::Tiling,
-        _: image::Usage,
-        _: image::ViewCapabilities,
-    ) -> Result<(), image::CreationError> {
-        unimplemented!()
-    }
-
-    unsafe fn get_image_requirements(&self, _: &()) -> memory::Requirements {
-        unimplemented!()
-    }
-
-    unsafe fn get_image_subresource_footprint(
-        &self,
-        _: &(),
-        _: image::Subresource,
-    ) -> image::SubresourceFootprint {
-        unimplemented!()
-    }
-
-    unsafe fn bind_image_memory(
-        &self,
-        _: &(),
-        _: u64,
-        _: &mut (),
-    ) -> Result<(), device::BindError> {
-        unimplemented!()
-    }
-
-    unsafe fn create_image_view(
-        &self,
-        _: &(),
-        _: image::ViewKind,
-        _: format::Format,
-        _: format::Swizzle,
-        _: image::SubresourceRange,
-    ) -> Result<(), image::ViewError> {
-        unimplemented!()
-    }
-
-    unsafe fn create_descriptor_pool<I>(
-        &self,
-        _: usize,
-        _: I,
-        _: pso::DescriptorPoolCreateFlags,
-    ) -> Result<DescriptorPool, device::OutOfMemory>
-    where
-        I: IntoIterator,
-        I::Item: Borrow<pso::DescriptorRangeDesc>,
-    {
-        unimplemented!()
-    }
-
-    unsafe fn create_descriptor_set_layout<I, J>(
-        &self,
-        _: I,
-        _: J,
-    ) -> Result<(), device::OutOfMemory>
-    where
-        I: IntoIterator,
-        I::Item: Borrow<pso::DescriptorSetLayoutBinding>,
-        J: IntoIterator,
-        J::Item: Borrow<()>,
-    {
-        unimplemented!()
-    }
-
-    unsafe fn write_descriptor_sets<'a, I, J>(&self, _: I)
-    where
-        I: IntoIterator<Item = pso::DescriptorSetWrite<'a, Backend, J>>,
-        J: IntoIterator,
-        J::Item: Borrow<pso::Descriptor<'a, Backend>>,
-    {
-        unimplemented!()
-    }
-
-    unsafe fn copy_descriptor_sets<'a, I>(&self, _: I)
-    where
-        I: IntoIterator,
-        I::Item: Borrow<pso::DescriptorSetCopy<'a, Backend>>,
-    {
-        unimplemented!()
-    }
-
-    fn create_semaphore(&self) -> Result<(), device::OutOfMemory> {
-        unimplemented!()
-    }
-
-    fn create_fence(&self, _: bool) -> Result<(), device::OutOfMemory> {
-        unimplemented!()
-    }
-
-    unsafe fn get_fence_status(&self, _: &()) -> Result<bool, device::DeviceLost> {
-        unimplemented!()
-    }
-
-    fn create_event(&self) -> Result<(), device::OutOfMemory> {
-        unimplemented!()
-    }
-
-    unsafe fn get_event_status(&self, _: &()) -> Result<bool, device::OomOrDeviceLost> {
-        unimplemented!()
-    }
-
-    unsafe fn set_event(&self, _: &()) -> Result<(), device::OutOfMemory> {
-        unimplemented!()
-    }
-
-    unsafe fn reset_event(&self, _: &()) -> Result<(), device::OutOfMemory> {
-        unimplemented!()
-    }
-
-    unsafe fn create_query_pool(&self, _: query::Type, _: u32) -> Result<(), query::CreationError> {
-        unimplemented!()
-    }
-
-    unsafe fn destroy_query_pool(&self, _: ()) {
-        unimplemented!()
-    }
-
-    unsafe fn get_query_pool_results(
-        &self,
-        _: &(),
-        _: Range<query::Id>,
-        _: &mut [u8],
-        _: buffer::Offset,
-        _: query::ResultFlags,
-    ) -> Result<bool, device::OomOrDeviceLost> {
-        unimplemented!()
-    }
-
-    unsafe fn map_memory<R: RangeArg<u64>>(&self, _: &(), _: R) -> Result<*mut u8, device::MapError> {
-        unimplemented!()
-    }
-
-    unsafe fn unmap_memory(&self, _: &()) {
-        unimplemented!()
-    }
-
-    unsafe fn flush_mapped_memory_ranges<'a, I, R>(&self, _: I) -> Result<(), device::OutOfMemory>
-    where
-        I: IntoIterator,
-        I::Item: Borrow<(&'a (), R)>,
-        R: RangeArg<u64>,
-    {
-        unimplemented!()
+impl PhysicalDevice {
+    fn new() -> Self {
+        // All the memory lives on the host, so the different types only
+        // differ in the properties they report to the user.
+        let memory_types = vec![
+            memory::Properties::DEVICE_LOCAL,
+            memory::Properties::DEVICE_LOCAL
+                | memory::Properties::CPU_VISIBLE
+                | memory::Properties::COHERENT,
+            memory::Properties::CPU_VISIBLE
+                | memory::Properties::COHERENT
+                | memory::Properties::CPU_CACHED,
+        ];
+        PhysicalDevice {
+            memory_types: memory_types
+                .into_iter()
+                .map(|properties| adapter::MemoryType {
+                    properties,
+                    heap_index: 0,
+                })
+                .collect(),
+        }
     }
+}
 
-    unsafe fn invalidate_mapped_memory_ranges<'a, I, R>(
+impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
+    unsafe fn open(
         &self,
-        _: I,
-    ) -> Result<(), device::OutOfMemory>
-    where
-        I: IntoIterator,
-        I::Item: Borrow<(&'a (), R)>,
-        R: RangeArg<u64>,
-    {
-        unimplemented!()
-    }
-
-    unsafe fn free_memory(&self, _: ()) {
-        unimplemented!()
-    }
-
-    unsafe fn destroy_shader_module(&self, _: ()) {
-        unimplemented!()
-    }
-
-    unsafe fn destroy_render_pass(&self, _: ()) {
-        unimplemented!()
-    }
-
-    unsafe fn destroy_pipeline_layout(&self, _: ()) {
-        unimplemented!()
-    }
-    unsafe fn destroy_graphics_pipeline(&self, _: ()) {
-        unimplemented!()
-    }
-    unsafe fn destroy_compute_pipeline(&self, _: ()) {
-        unimplemented!()
-    }
-    unsafe fn destroy_framebuffer(&self, _: ()) {
-        unimplemented!()
-    }
-
-    unsafe fn destroy_buffer(&self, _: ()) {
-        unimplemented!()
-    }
-    unsafe fn destroy_buffer_view(&self, _: ()) {
-        unimplemented!()
-    }
-    unsafe fn destroy_image(&self, _: ()) {
-        unimplemented!()
-    }
-    unsafe fn destroy_image_view(&self, _: ()) {
-        unimplemented!()
-    }
-    unsafe fn destroy_sampler(&self, _: ()) {
-        unimplemented!()
-    }
-
-    unsafe fn destroy_descriptor_pool(&self, _: DescriptorPool) {
-        unimplemented!()
-    }
-
-    unsafe fn destroy_descriptor_set_layout(&self, _: ()) {
-        unimplemented!()
-    }
-
-    unsafe fn destroy_fence(&self, _: ()) {
-        unimplemented!()
+        families: &[(&QueueFamily, &[q::QueuePriority])],
+        _requested_features: Features,
+    ) -> Result<adapter::Gpu<Backend>, d::CreationError> {
+        let queue_groups = families
+            .iter()
+            .map(|&(family, priorities)| {
+                let mut group = q::QueueGroup::new(q::QueueFamily::id(family));
+                for _ in priorities {
+                    group.add_queue(CommandQueue);
+                }
+                group
+            })
+            .collect();
+
+        Ok(adapter::Gpu {
+            device: Device {
+                memory_types: self.memory_types.clone(),
+                epoch: Instant::now(),
+            },
+            queue_groups,
+        })
+    }
+
+    fn format_properties(&self, format: Option<format::Format>) -> format::Properties {
+        let format = match format {
+            Some(format) if format::texel::is_supported(format) => format,
+            _ => return format::Properties::default(),
+        };
+        let mut features = format::ImageFeature::BLIT_SRC | format::ImageFeature::BLIT_DST;
+        let mut buffer_features = format::BufferFeature::empty();
+        if cfg!(feature = "software") {
+            features |= format::ImageFeature::SAMPLED;
+            if format.is_color() {
+                features |= format::ImageFeature::STORAGE | format::ImageFeature::COLOR_ATTACHMENT;
+                buffer_features |= format::BufferFeature::UNIFORM_TEXEL
+                    | format::BufferFeature::STORAGE_TEXEL
+                    | format::BufferFeature::VERTEX;
+            } else {
+                features |= format::ImageFeature::DEPTH_STENCIL_ATTACHMENT;
+            }
+            if format::texel::is_float(format) {
+                features |= format::ImageFeature::SAMPLED_LINEAR;
+                if format.is_color() {
+                    features |= format::ImageFeature::COLOR_ATTACHMENT_BLEND;
+                }
+            }
+        }
+        format::Properties {
+            linear_tiling: features,
+            optimal_tiling: features,
+            buffer_features,
+        }
     }
 
-    unsafe fn destroy_semaphore(&self, _: ()) {
-        unimplemented!()
-    }
-
-    unsafe fn destroy_event(&self, _: ()) {
-        unimplemented!()
-    }
-
-    unsafe fn create_swapchain(
+    fn image_format_properties(
         &self,
-        _: &mut Surface,
-        _: window::SwapchainConfig,
-        _: Option<Swapchain>,
-    ) -> Result<(Swapchain, Vec<()>), hal::window::CreationError> {
-        unimplemented!()
-    }
-
-    unsafe fn destroy_swapchain(&self, _: Swapchain) {
-        unimplemented!()
-    }
-
-    fn wait_idle(&self) -> Result<(), device::OutOfMemory> {
-        unimplemented!()
-    }
-
-    unsafe fn set_image_name(&self, _: &mut (), _: &str) {
-        unimplemented!()
-    }
-
-    unsafe fn set_buffer_name(&self, _: &mut (), _: &str) {
-        unimplemented!()
-    }
-
-    unsafe fn set_command_buffer_name(&self, _: &mut CommandBuffer, _: &str) {
-        unimplemented!()
-    }
-
-    unsafe fn set_semaphore_name(&self, _: &mut (), _: &str) {
-        unimplemented!()
-    }
-
-    unsafe fn set_fence_name(&self, _: &mut (), _: &str) {
-        unimplemented!()
-    }
-
-    unsafe fn set_framebuffer_name(&self, _: &mut (), _: &str) {
-        unimplemented!()
-    }
-
-    unsafe fn set_render_pass_name(&self, _: &mut (), _: &str) {
-        unimplemented!()
-    }
-
-    unsafe fn set_descriptor_set_name(&self, _: &mut (), _: &str) {
-        unimplemented!()
+        format: format::Format,
+        dimensions: u8,
+        _tiling: image::Tiling,
+        _usage: image::Usage,
+        _view_caps: image::ViewCapabilities,
+    ) -> Option<image::FormatProperties> {
+        let limits = self.limits();
+        let size = match dimensions {
+            1 => limits.max_image_1d_size,
+            2 => limits.max_image_2d_size,
+            _ => limits.max_image_3d_size,
+        };
+        Some(image::FormatProperties {
+            max_extent: image::Extent {
+                width: size,
+                height: if dimensions >= 2 { size } else { 1 },
+                depth: if dimensions >= 3 { size } else { 1 },
+            },
+            max_levels: 32 - size.leading_zeros() as image::Level,
+            max_layers: limits.max_image_array_layers,
+            sample_count_mask: 1,
+            max_resource_size: (format.surface_desc().bits as usize / 8) << 30,
+        })
     }
 
-    unsafe fn set_descriptor_set_layout_name(&self, _: &mut (), _: &str) {
-        unimplemented!()
+    fn memory_properties(&self) -> adapter::MemoryProperties {
+        adapter::MemoryProperties {
+            memory_types: self.memory_types.clone(),
+            memory_heaps: vec![!0],
+        }
+    }
+
+    fn features(&self) -> Features {
+        if cfg!(feature = "software") {
+            Features::INDEPENDENT_BLENDING
+                | Features::DUAL_SRC_BLENDING
+                | Features::LOGIC_OP
+                | Features::MULTI_DRAW_INDIRECT
+                | Features::DRAW_INDIRECT_FIRST_INSTANCE
+                | Features::DEPTH_CLAMP
+                | Features::DEPTH_BIAS_CLAMP
+                | Features::NON_FILL_POLYGON_MODE
+                | Features::DEPTH_BOUNDS
+                | Features::PRECISE_OCCLUSION_QUERY
+                | Features::PIPELINE_STATISTICS_QUERY
+                | Features::SEPARATE_STENCIL_REF_VALUES
+                | Features::INSTANCE_RATE
+                | Features::SAMPLER_MIP_LOD_BIAS
+        } else {
+            Features::empty()
+        }
+    }
+
+    fn limits(&self) -> Limits {
+        Limits {
+            max_image_1d_size: 1 << 14,
+            max_image_2d_size: 1 << 14,
+            max_image_3d_size: 1 << 11,
+            max_image_cube_size: 1 << 14,
+            max_image_array_layers: 1 << 11,
+            max_texel_elements: 1 << 27,
+            max_uniform_buffer_range: 1 << 16,
+            max_storage_buffer_range: 1 << 27,
+            max_push_constants_size: 128,
+            max_memory_allocation_count: 1 << 12,
+            max_sampler_allocation_count: 1 << 12,
+            max_bound_descriptor_sets: 8,
+            max_framebuffer_layers: 1 << 11,
+            max_per_stage_descriptor_samplers: 16,
+            max_per_stage_descriptor_uniform_buffers: 12,
+            max_per_stage_descriptor_storage_buffers: 8,
+            max_per_stage_descriptor_sampled_images: 16,
+            max_per_stage_descriptor_storage_images: 8,
+            max_per_stage_descriptor_input_attachments: 8,
+            max_per_stage_resources: 128,
+            max_descriptor_set_samplers: 96,
+            max_descriptor_set_uniform_buffers: 72,
+            max_descriptor_set_uniform_buffers_dynamic: 8,
+            max_descriptor_set_storage_buffers: 24,
+            max_descriptor_set_storage_buffers_dynamic: 4,
+            max_descriptor_set_sampled_images: 96,
+            max_descriptor_set_storage_images: 24,
+            max_descriptor_set_input_attachments: 8,
+            max_vertex_input_attributes: 16,
+            max_vertex_input_bindings: 16,
+            max_vertex_input_attribute_offset: 2047,
+            max_vertex_input_binding_stride: 2048,
+            max_vertex_output_components: 64,
+            max_fragment_input_components: 64,
+            max_fragment_output_attachments: 4,
+            max_fragment_combined_output_resources: 4,
+            max_compute_shared_memory_size: 1 << 14,
+            max_compute_work_group_count: [1 << 16; 3],
+            max_compute_work_group_invocations: 128,
+            max_compute_work_group_size: [128, 128, 64],
+            max_draw_indexed_index_value: !0,
+            max_draw_indirect_count: !0,
+            max_viewports: 1,
+            max_viewport_dimensions: [1 << 14; 2],
+            max_framebuffer_extent: image::Extent {
+                width: 1 << 14,
+                height: 1 << 14,
+                depth: 1 << 11,
+            },
+            min_memory_map_alignment: native::MEMORY_ALIGNMENT,
+            buffer_image_granularity: 1,
+            min_texel_buffer_offset_alignment: 16,
+            min_uniform_buffer_offset_alignment: 16,
+            min_storage_buffer_offset_alignment: 16,
+            framebuffer_color_sample_counts: 1,
+            framebuffer_depth_sample_counts: 1,
+            framebuffer_stencil_sample_counts: 1,
+            max_color_attachments: 4,
+            standard_sample_locations: true,
+            optimal_buffer_copy_offset_alignment: 1,
+            optimal_buffer_copy_pitch_alignment: 1,
+            non_coherent_atom_size: 1,
+            min_vertex_input_binding_stride_alignment: 1,
+            ..Limits::default()
+        }
     }
 }
 
+/// Single family of general purpose queues.
 #[derive(Debug)]
 pub struct QueueFamily;
-impl queue::QueueFamily for QueueFamily {
-    fn queue_type(&self) -> queue::QueueType {
-        unimplemented!()
+impl q::QueueFamily for QueueFamily {
+    fn queue_type(&self) -> q::QueueType {
+        q::QueueType::General
     }
     fn max_queues(&self) -> usize {
-        unimplemented!()
-    }
-    fn id(&self) -> queue::QueueFamilyId {
-        unimplemented!()
-    }
-}
-
-/// Dummy raw command pool.
-#[derive(Debug)]
-pub struct CommandPool;
-impl pool::CommandPool<Backend> for CommandPool {
-    unsafe fn reset(&mut self, _: bool) {
-        unimplemented!()
-    }
-
-    unsafe fn free<I>(&mut self, _: I)
-    where
-        I: IntoIterator<Item = CommandBuffer>,
-    {
-        unimplemented!()
-    }
-}
-
-/// Dummy command buffer, which ignores all the calls.
-#[derive(Debug)]
-pub struct CommandBuffer;
-impl command::CommandBuffer<Backend> for CommandBuffer {
-    unsafe fn begin(
-        &mut self,
-        _: command::CommandBufferFlags,
-        _: command::CommandBufferInheritanceInfo<Backend>,
-    ) {
-        unimplemented!()
-    }
-
-    unsafe fn finish(&mut self) {
-        unimplemented!()
-    }
-
-    unsafe fn reset(&mut self, _: bool) {
-        unimplemented!()
-    }
-
-    unsafe fn pipeline_barrier<'a, T>(
-        &mut self,
-        _: Range<pso::PipelineStage>,
-        _: memory::Dependencies,
-        _: T,
-    ) where
-        T: IntoIterator,
-        T::Item: Borrow<memory::Barrier<'a, Backend>>,
-    {
-        unimplemented!()
-    }
-
-    unsafe fn fill_buffer<R>(&mut self, _: &(), _: R, _: u32)
-    where
-        R: RangeArg<buffer::Offset>,
-    {
-        unimplemented!()
-    }
-
-    unsafe fn update_buffer(&mut self, _: &(), _: buffer::Offset, _: &[u8]) {
-        unimplemented!()
-    }
-
-    unsafe fn clear_image<T>(&mut self, _: &(), _: image::Layout, _: command::ClearValue, _: T)
-    where
-        T: IntoIterator,
-        T::Item: Borrow<image::SubresourceRange>,
-    {
-        unimplemented!()
-    }
-
-    unsafe fn clear_attachments<T, U>(&mut self, _: T, _: U)
-    where
-        T: IntoIterator,
-        T::Item: Borrow<command::AttachmentClear>,
-        U: IntoIterator,
-        U::Item: Borrow<pso::ClearRect>,
-    {
-        unimplemented!()
-    }
-
-    unsafe fn resolve_image<T>(&mut self, _: &(), _: image::Layout, _: &(), _: image::Layout, _: T)
-    where
-        T: IntoIterator,
-        T::Item: Borrow<command::ImageResolve>,
-    {
-        unimplemented!()
-    }
-
-    unsafe fn blit_image<T>(
-        &mut self,
-        _: &(),
-        _: image::Layout,
-        _: &(),
-        _: image::Layout,
-        _: image::Filter,
-        _: T,
-    ) where
-        T: IntoIterator,
-        T::Item: Borrow<command::ImageBlit>,
-    {
-        unimplemented!()
-    }
-
-    unsafe fn bind_index_buffer(&mut self, _: buffer::IndexBufferView<Backend>) {
-        unimplemented!()
-    }
-
-    unsafe fn bind_vertex_buffers<I, T>(&mut self, _: u32, _: I)
-    where
-        I: IntoIterator<Item = (T, buffer::Offset)>,
-        T: Borrow<()>,
-    {
-        unimplemented!()
-    }
-
-    unsafe fn set_viewports<T>(&mut self, _: u32, _: T)
-    where
-        T: IntoIterator,
-        T::Item: Borrow<pso::Viewport>,
-    {
-        unimplemented!()
-    }
-
-    unsafe fn set_scissors<T>(&mut self, _: u32, _: T)
-    where
-        T: IntoIterator,
-        T::Item: Borrow<pso::Rect>,
-    {
-        unimplemented!()
-    }
-
-    unsafe fn set_stencil_reference(&mut self, _: pso::Face, _: pso::StencilValue) {
-        unimplemented!()
-    }
-
-    unsafe fn set_stencil_read_mask(&mut self, _: pso::Face, _: pso::StencilValue) {
-        unimplemented!()
-    }
-
-    unsafe fn set_stencil_write_mask(&mut self, _: pso::Face, _: pso::StencilValue) {
-        unimplemented!()
-    }
-
-    unsafe fn set_blend_constants(&mut self, _: pso::ColorValue) {
-        unimplemented!()
+        MAX_QUEUES
     }
-
-    unsafe fn set_depth_bounds(&mut self, _: Range<f32>) {
-        unimplemented!()
-    }
-
-    unsafe fn set_line_width(&mut self, _: f32) {
-        unimplemented!()
-    }
-
-    unsafe fn set_depth_bias(&mut self, _: pso::DepthBias) {
-        unimplemented!()
-    }
-
-    unsafe fn begin_render_pass<T>(
-        &mut self,
-        _: &(),
-        _: &(),
-        _: pso::Rect,
-        _: T,
-        _: command::SubpassContents,
-    ) where
-        T: IntoIterator,
-        T::Item: Borrow<command::ClearValue>,
-    {
-        unimplemented!()
-    }
-
-    unsafe fn next_subpass(&mut self, _: command::SubpassContents) {
-        unimplemented!()
-    }
-
-    unsafe fn end_render_pass(&mut self) {
-        unimplemented!()
-    }
-
-    unsafe fn bind_graphics_pipeline(&mut self, _: &()) {
-        unimplemented!()
-    }
-
-    unsafe fn bind_graphics_descriptor_sets<I, J>(&mut self, _: &(), _: usize, _: I, _: J)
-    where
-        I: IntoIterator,
-        I::Item: Borrow<()>,
-        J: IntoIterator,
-        J::Item: Borrow<command::DescriptorSetOffset>,
-    {
-        unimplemented!()
-    }
-
-    unsafe fn bind_compute_pipeline(&mut self, _: &()) {
-        unimplemented!()
-    }
-
-    unsafe fn bind_compute_descriptor_sets<I, J>(&mut self, _: &(), _: usize, _: I, _: J)
-    where
-        I: IntoIterator,
-        I::Item: Borrow<()>,
-        J: IntoIterator,
-        J::Item: Borrow<command::DescriptorSetOffset>,
-    {
-        unimplemented!()
-    }
-
-    unsafe fn dispatch(&mut self, _: hal::WorkGroupCount) {
-        unimplemented!()
-    }
-
-    unsafe fn dispatch_indirect(&mut self, _: &(), _: buffer::Offset) {
-        unimplemented!()
-    }
-
-    unsafe fn copy_buffer<T>(&mut self, _: &(), _: &(), _: T)
-    where
-        T: IntoIterator,
-        T::Item: Borrow<command::BufferCopy>,
-    {
-        unimplemented!()
-    }
-
-    unsafe fn copy_image<T>(&mut self, _: &(), _: image::Layout, _: &(), _: image::Layout, _: T)
-    where
-        T: IntoIterator,
-        T::Item: Borrow<command::ImageCopy>,
-    {
-        unimplemented!()
-    }
-
-    unsafe fn copy_buffer_to_image<T>(&mut self, _: &(), _: &(), _: image::Layout, _: T)
-    where
-        T: IntoIterator,
-        T::Item: Borrow<command::BufferImageCopy>,
-    {
-        unimplemented!()
-    }
-
-    unsafe fn copy_image_to_buffer<T>(&mut self, _: &(), _: image::Layout, _: &(), _: T)
-    where
-        T: IntoIterator,
-        T::Item: Borrow<command::BufferImageCopy>,
-    {
-        unimplemented!()
-    }
-
-    unsafe fn draw(&mut self, _: Range<hal::VertexCount>, _: Range<hal::InstanceCount>) {
-        unimplemented!()
-    }
-
-    unsafe fn draw_indexed(
-        &mut self,
-        _: Range<hal::IndexCount>,
-        _: hal::VertexOffset,
-        _: Range<hal::InstanceCount>,
-    ) {
-        unimplemented!()
-    }
-
-    unsafe fn draw_indirect(&mut self, _: &(), _: buffer::Offset, _: hal::DrawCount, _: u32) {
-        unimplemented!()
-    }
-
-    unsafe fn draw_indexed_indirect(
-        &mut self,
-        _: &(),
-        _: buffer::Offset,
-        _: hal::DrawCount,
-        _: u32,
-    ) {
-        unimplemented!()
-    }
-
-    unsafe fn set_event(&mut self, _: &(), _: pso::PipelineStage) {
-        unimplemented!()
-    }
-
-    unsafe fn reset_event(&mut self, _: &(), _: pso::PipelineStage) {
-        unimplemented!()
-    }
-
-    unsafe fn wait_events<'a, I, J>(&mut self, _: I, _: Range<pso::PipelineStage>, _: J)
-    where
-        I: IntoIterator,
-        I::Item: Borrow<()>,
-        J: IntoIterator,
-        J::Item: Borrow<memory::Barrier<'a, Backend>>,
-    {
-        unimplemented!()
-    }
-
-    unsafe fn begin_query(&mut self, _: query::Query<Backend>, _: query::ControlFlags) {
-        unimplemented!()
-    }
-
-    unsafe fn end_query(&mut self, _: query::Query<Backend>) {
-        unimplemented!()
-    }
-
-    unsafe fn reset_query_pool(&mut self, _: &(), _: Range<query::Id>) {
-        unimplemented!()
-    }
-
-    unsafe fn copy_query_pool_results(
-        &mut self,
-        _: &(),
-        _: Range<query::Id>,
-        _: &(),
-        _: buffer::Offset,
-        _: buffer::Offset,
-        _: query::ResultFlags,
-    ) {
-        unimplemented!()
-    }
-
-    unsafe fn write_timestamp(&mut self, _: pso::PipelineStage, _: query::Query<Backend>) {
-        unimplemented!()
-    }
-
-    unsafe fn push_graphics_constants(
-        &mut self,
-        _: &(),
-        _: pso::ShaderStageFlags,
-        _: u32,
-        _: &[u32],
-    ) {
-        unimplemented!()
-    }
-
-    unsafe fn push_compute_constants(&mut self, _: &(), _: u32, _: &[u32]) {
-        unimplemented!()
-    }
-
-    unsafe fn execute_commands<'a, T, I>(&mut self, _: I)
-    where
-        T: 'a + Borrow<CommandBuffer>,
-        I: IntoIterator<Item = &'a T>,
-    {
-        unimplemented!()
-    }
-}
-
-// Dummy descriptor pool.
-#[derive(Debug)]
-pub struct DescriptorPool;
-impl pso::DescriptorPool<Backend> for DescriptorPool {
-    unsafe fn free_sets<I>(&mut self, _descriptor_sets: I)
-    where
-        I: IntoIterator<Item = ()>,
-    {
-        unimplemented!()
-    }
-
-    unsafe fn reset(&mut self) {
-        unimplemented!()
+    fn id(&self) -> q::QueueFamilyId {
+        q::QueueFamilyId(0)
     }
 }
 
-/// Dummy surface.
+/// Dummy surface, as there is no window system to present to.
 #[derive(Debug)]
 pub struct Surface;
 impl window::Surface<Backend> for Surface {
@@ -950,47 +319,60 @@ impl window::Surface<Backend> for Surface {
         Option<Vec<format::Format>>,
         Vec<window::PresentMode>,
     ) {
-        unimplemented!()
+        // Nothing can be presented, no format or present mode is supported.
+        let extent = window::Extent2D {
+            width: 0,
+            height: 0,
+        };
+        let capabilities = window::SurfaceCapabilities {
+            image_count: 1 ..= 1,
+            current_extent: None,
+            extents: extent ..= extent,
+            max_image_layers: 1,
+            usage: image::Usage::empty(),
+            composite_alpha: window::CompositeAlpha::empty(),
+        };
+        (capabilities, Some(Vec::new()), Vec::new())
     }
 
     fn supports_queue_family(&self, _: &QueueFamily) -> bool {
-        unimplemented!()
+        false
     }
 }
 impl window::PresentationSurface<Backend> for Surface {
-    type SwapchainImage = ();
+    type SwapchainImage = native::ImageView;
 
     unsafe fn configure_swapchain(
         &mut self,
         _: &Device,
         _: window::SwapchainConfig,
     ) -> Result<(), window::CreationError> {
-        unimplemented!()
+        Err(window::CreationError::SurfaceLost(d::SurfaceLost))
     }
 
     unsafe fn unconfigure_swapchain(&mut self, _: &Device) {
-        unimplemented!()
+        // Never configured
     }
 
     unsafe fn acquire_image(
         &mut self,
         _: u64,
-    ) -> Result<((), Option<window::Suboptimal>), window::AcquireError> {
-        unimplemented!()
+    ) -> Result<(native::ImageView, Option<window::Suboptimal>), window::AcquireError> {
+        Err(window::AcquireError::SurfaceLost(d::SurfaceLost))
     }
 }
 
-/// Dummy swapchain.
+/// Dummy swapchain, as there is no window system to present to.
 #[derive(Debug)]
 pub struct Swapchain;
 impl window::Swapchain<Backend> for Swapchain {
     unsafe fn acquire_image(
         &mut self,
         _: u64,
-        _: Option<&()>,
-        _: Option<&()>,
+        _: Option<&native::Semaphore>,
+        _: Option<&native::Fence>,
     ) -> Result<(window::SwapImageIndex, Option<window::Suboptimal>), window::AcquireError> {
-        unimplemented!()
+        Err(window::AcquireError::SurfaceLost(d::SurfaceLost))
     }
 }
 
@@ -1005,13 +387,23 @@ impl Instance {
 
     #[cfg(feature = "winit")]
     pub fn create_surface(&self, _: &winit::window::Window) -> Surface {
-        unimplemented!()
+        Surface
     }
 }
 
 impl hal::Instance for Instance {
     type Backend = Backend;
     fn enumerate_adapters(&self) -> Vec<adapter::Adapter<Backend>> {
-        vec![]
+        let info = adapter::AdapterInfo {
+            name: "CPU".to_owned(),
+            vendor: 0,
+            device: 0,
+            device_type: adapter::DeviceType::Cpu,
+        };
+        vec![adapter::Adapter {
+            info,
+            physical_device: PhysicalDevice::new(),
+            queue_families: vec![QueueFamily],
+        }]
     }
 }
@@ -0,0 +1,537 @@
+use hal::{buffer, format, image as i, memory, pass, pso, query};
+
+use crate::Backend;
+#[cfg(feature = "software")]
+use crate::{raster, shader};
+
+use std::alloc::{self, Layout};
+use std::fmt;
+use std::ops::Range;
+use std::slice;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Instant;
+
+/// Alignment of every memory allocation, and thus of the pointers
+/// returned by `map_memory`.
+pub(crate) const MEMORY_ALIGNMENT: usize = 64;
+
+/// Host allocation backing a `Memory` object.
+pub struct Block {
+    ptr: *mut u8,
+    layout: Layout,
+}
+
+// The block is only a chunk of bytes; synchronizing accesses to it
+// is the responsibility of the user, like with real device memory.
+unsafe impl Send for Block {}
+unsafe impl Sync for Block {}
+
+impl Block {
+    pub(crate) fn new(size: u64) -> Option<Self> {
+        // Zero-sized allocations are not allowed by the global allocator.
+        let layout = Layout::from_size_align(size.max(1) as usize, MEMORY_ALIGNMENT).ok()?;
+        let ptr = unsafe { alloc::alloc_zeroed(layout) };
+        if ptr.is_null() {
+            None
+        } else {
+            Some(Block { ptr, layout })
+        }
+    }
+
+    pub(crate) fn size(&self) -> u64 {
+        self.layout.size() as u64
+    }
+
+    pub(crate) fn ptr(&self) -> *mut u8 {
+        self.ptr
+    }
+}
+
+impl Drop for Block {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.ptr, self.layout) };
+    }
+}
+
+impl fmt::Debug for Block {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Block")
+            .field("ptr", &self.ptr)
+            .field("size", &self.layout.size())
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+pub struct Memory {
+    pub(crate) properties: memory::Properties,
+    pub(crate) block: Arc<Block>,
+}
+
+/// Location of a resource inside of a memory block.
+#[derive(Clone, Debug)]
+pub struct Binding {
+    pub(crate) block: Arc<Block>,
+    pub(crate) offset: u64,
+}
+
+impl Binding {
+    // Returns the host pointer to the byte at `offset` from the start of the resource.
+    pub(crate) fn ptr(&self, offset: u64) -> *mut u8 {
+        debug_assert!(self.offset + offset <= self.block.size());
+        unsafe { self.block.ptr().offset((self.offset + offset) as isize) }
+    }
+
+    // Returns the number of bytes between the start of the resource and the end of the memory.
+    #[cfg(feature = "software")]
+    pub(crate) fn remaining(&self) -> u64 {
+        self.block.size() - self.offset
+    }
+}
+
+#[derive(Debug)]
+pub struct Buffer {
+    pub(crate) size: buffer::Offset,
+    pub(crate) binding: Option<Binding>,
+}
+
+impl Buffer {
+    // Asserts that the buffer is bound and returns the memory location.
+    pub(crate) fn as_bound(&self) -> Binding {
+        self.binding.clone().expect("Expected bound buffer!")
+    }
+}
+
+#[cfg_attr(not(feature = "software"), allow(dead_code))]
+#[derive(Clone, Debug)]
+pub struct BufferView {
+    pub(crate) binding: Binding,
+    pub(crate) format: format::Format,
+    pub(crate) size: u64,
+}
+
+/// Linear layout of an image in memory.
+///
+/// Mip levels are laid out one after another, each of them containing
+/// all the array layers, which are in turn made of depth slices and rows.
+#[derive(Clone, Debug)]
+pub struct ImageDesc {
+    pub(crate) kind: i::Kind,
+    pub(crate) levels: i::Level,
+    pub(crate) format: format::Format,
+    /// Size of a texel (or a compressed block) in bytes.
+    pub(crate) block_size: u64,
+    /// Dimensions of a compressed block in texels.
+    pub(crate) block_dim: (u8, u8),
+    level_offsets: Vec<u64>,
+    pub(crate) size: u64,
+}
+
+impl ImageDesc {
+    pub(crate) fn new(kind: i::Kind, levels: i::Level, format: format::Format) -> Self {
+        let desc = format.surface_desc();
+        let mut image = ImageDesc {
+            kind,
+            levels,
+            format,
+            block_size: (desc.bits as u64).div_ceil(8),
+            block_dim: desc.dim,
+            level_offsets: Vec::with_capacity(levels as usize),
+            size: 0,
+        };
+        let layers = kind.num_layers() as u64;
+        for level in 0 .. levels {
+            image.level_offsets.push(image.size);
+            image.size += image.pitches(level).1 * layers;
+        }
+        image
+    }
+
+    // Returns the row, layer and depth slice pitches of a mip level.
+    fn pitches(&self, level: i::Level) -> (u64, u64, u64) {
+        let extent = self.kind.level_extent(level);
+        let (bw, bh) = (self.block_dim.0 as u64, self.block_dim.1 as u64);
+        let row_pitch = (extent.width as u64).div_ceil(bw) * self.block_size;
+        let depth_pitch = (extent.height as u64).div_ceil(bh) * row_pitch;
+        let array_pitch = depth_pitch * extent.depth as u64;
+        (row_pitch, array_pitch, depth_pitch)
+    }
+
+    pub(crate) fn footprint(&self, level: i::Level, layer: i::Layer) -> i::SubresourceFootprint {
+        let (row_pitch, array_pitch, depth_pitch) = self.pitches(level);
+        let start = self.level_offsets[level as usize] + layer as u64 * array_pitch;
+        i::SubresourceFootprint {
+            slice: start .. start + array_pitch,
+            row_pitch,
+            array_pitch,
+            depth_pitch,
+        }
+    }
+
+    // Returns the byte offset of the texel block containing `offset`.
+    pub(crate) fn texel_offset(&self, level: i::Level, layer: i::Layer, offset: i::Offset) -> u64 {
+        let footprint = self.footprint(level, layer);
+        footprint.slice.start
+            + offset.z as u64 * footprint.depth_pitch
+            + offset.y as u64 / self.block_dim.1 as u64 * footprint.row_pitch
+            + offset.x as u64 / self.block_dim.0 as u64 * self.block_size
+    }
+}
+
+#[derive(Debug)]
+pub struct Image {
+    pub(crate) desc: ImageDesc,
+    pub(crate) binding: Option<Binding>,
+}
+
+impl Image {
+    // Asserts that the image is bound and returns a self-contained description of it.
+    pub(crate) fn as_bound(&self) -> BoundImage {
+        BoundImage {
+            desc: self.desc.clone(),
+            binding: self.binding.clone().expect("Expected bound image!"),
+        }
+    }
+}
+
+/// Image reference that can outlive the `Image` object, stored in
+/// recorded commands, views and descriptors.
+#[derive(Clone, Debug)]
+pub struct BoundImage {
+    pub(crate) desc: ImageDesc,
+    pub(crate) binding: Binding,
+}
+
+impl BoundImage {
+    // Returns the bytes of the texel block containing `offset`.
+    //
+    // The bytes live in the memory the image is bound to, not in `self`,
+    // so the caller is responsible for keeping accesses to them exclusive.
+    pub(crate) unsafe fn texel<'a>(
+        &self,
+        level: i::Level,
+        layer: i::Layer,
+        offset: i::Offset,
+    ) -> &'a mut [u8] {
+        let ptr = self
+            .binding
+            .ptr(self.desc.texel_offset(level, layer, offset));
+        slice::from_raw_parts_mut(ptr, self.desc.block_size as usize)
+    }
+}
+
+#[cfg_attr(not(feature = "software"), allow(dead_code))]
+#[derive(Clone, Debug)]
+pub struct ImageView {
+    pub(crate) image: BoundImage,
+    pub(crate) kind: i::ViewKind,
+    pub(crate) format: format::Format,
+    pub(crate) swizzle: format::Swizzle,
+    pub(crate) range: i::SubresourceRange,
+}
+
+impl ImageView {
+    // Returns the extent of a mip level of the view, relative to its base level.
+    pub(crate) fn level_extent(&self, level: i::Level) -> i::Extent {
+        self.image
+            .desc
+            .kind
+            .level_extent(self.range.levels.start + level)
+    }
+
+    // Returns the bytes of a texel, relative to the base level and layer of the view.
+    pub(crate) unsafe fn texel<'a>(
+        &self,
+        level: i::Level,
+        layer: i::Layer,
+        offset: i::Offset,
+    ) -> &'a mut [u8] {
+        self.image.texel(
+            self.range.levels.start + level,
+            self.range.layers.start + layer,
+            offset,
+        )
+    }
+}
+
+#[cfg_attr(not(feature = "software"), allow(dead_code))]
+#[derive(Clone, Debug)]
+pub struct Sampler {
+    pub(crate) info: i::SamplerInfo,
+}
+
+#[derive(Debug)]
+pub struct ShaderModule {
+    #[cfg(feature = "software")]
+    pub(crate) module: Arc<shader::Module>,
+}
+
+/// Attachments used by a subpass, as indices into the attachments of the render pass.
+#[derive(Clone, Debug)]
+pub struct Subpass {
+    pub(crate) colors: Vec<pass::AttachmentId>,
+    pub(crate) depth_stencil: Option<pass::AttachmentId>,
+    pub(crate) resolves: Vec<pass::AttachmentId>,
+}
+
+#[derive(Clone, Debug)]
+pub struct RenderPass {
+    pub(crate) attachments: Vec<pass::Attachment>,
+    pub(crate) subpasses: Vec<Subpass>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Framebuffer {
+    pub(crate) views: Vec<ImageView>,
+}
+
+#[derive(Clone, Debug)]
+pub struct DescriptorSetLayout {
+    /// Bindings sorted by their binding number.
+    pub(crate) bindings: Vec<pso::DescriptorSetLayoutBinding>,
+    /// Immutable samplers of all the bindings, in binding order.
+    pub(crate) immutable_samplers: Vec<Sampler>,
+}
+
+impl DescriptorSetLayout {
+    // Returns the descriptors of a newly allocated set, with the immutable samplers filled in.
+    fn descriptors(&self) -> Vec<Vec<Descriptor>> {
+        let mut samplers = self.immutable_samplers.iter();
+        self.bindings
+            .iter()
+            .map(|binding| {
+                (0 .. binding.count)
+                    .map(|_| Descriptor {
+                        sampler: if binding.immutable_samplers {
+                            samplers.next().cloned()
+                        } else {
+                            None
+                        },
+                        ..Descriptor::default()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub struct PipelineLayout;
+
+#[derive(Debug)]
+pub struct PipelineCache;
+
+#[derive(Clone, Debug)]
+pub struct GraphicsPipeline {
+    #[cfg(feature = "software")]
+    pub(crate) pipeline: Arc<raster::Pipeline>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ComputePipeline {
+    #[cfg(feature = "software")]
+    pub(crate) program: Arc<shader::Program>,
+}
+
+/// Resources written to a single descriptor.
+///
+/// The fields that are used depend on the descriptor type.
+#[derive(Clone, Debug, Default)]
+pub struct Descriptor {
+    pub(crate) image: Option<ImageView>,
+    pub(crate) sampler: Option<Sampler>,
+    pub(crate) buffer: Option<(Binding, Range<u64>)>,
+    pub(crate) texel_buffer: Option<BufferView>,
+}
+
+#[derive(Clone, Debug)]
+pub struct DescriptorSet {
+    pub(crate) layout: DescriptorSetLayout,
+    /// Descriptors of every binding of the layout, in the same order.
+    pub(crate) descriptors: Arc<Mutex<Vec<Vec<Descriptor>>>>,
+}
+
+impl DescriptorSet {
+    // Returns the index of the layout binding with the given binding number.
+    pub(crate) fn binding_index(&self, binding: pso::DescriptorBinding) -> usize {
+        self.layout
+            .bindings
+            .iter()
+            .position(|b| b.binding == binding)
+            .expect("Unknown descriptor binding")
+    }
+}
+
+#[derive(Debug)]
+pub struct DescriptorPool {
+    pub(crate) max_sets: usize,
+    pub(crate) ranges: Vec<pso::DescriptorRangeDesc>,
+    pub(crate) allocated_sets: usize,
+    /// Number of allocated descriptors of each type in `ranges`.
+    pub(crate) allocated: Vec<usize>,
+}
+
+impl DescriptorPool {
+    pub(crate) fn new(max_sets: usize, ranges: Vec<pso::DescriptorRangeDesc>) -> Self {
+        DescriptorPool {
+            max_sets,
+            allocated_sets: 0,
+            allocated: vec![0; ranges.len()],
+            ranges,
+        }
+    }
+
+    fn range_index(&self, ty: pso::DescriptorType) -> Option<usize> {
+        self.ranges.iter().position(|range| range.ty == ty)
+    }
+}
+
+impl pso::DescriptorPool<Backend> for DescriptorPool {
+    unsafe fn allocate_set(
+        &mut self,
+        layout: &DescriptorSetLayout,
+    ) -> Result<DescriptorSet, pso::AllocationError> {
+        if self.allocated_sets == self.max_sets {
+            return Err(pso::AllocationError::OutOfPoolMemory);
+        }
+        let mut allocated = self.allocated.clone();
+        for binding in &layout.bindings {
+            let index = self
+                .range_index(binding.ty)
+                .ok_or(pso::AllocationError::IncompatibleLayout)?;
+            allocated[index] += binding.count;
+            if allocated[index] > self.ranges[index].count {
+                return Err(pso::AllocationError::OutOfPoolMemory);
+            }
+        }
+        self.allocated = allocated;
+        self.allocated_sets += 1;
+        Ok(DescriptorSet {
+            layout: layout.clone(),
+            descriptors: Arc::new(Mutex::new(layout.descriptors())),
+        })
+    }
+
+    unsafe fn free_sets<I>(&mut self, descriptor_sets: I)
+    where
+        I: IntoIterator<Item = DescriptorSet>,
+    {
+        for set in descriptor_sets {
+            for binding in &set.layout.bindings {
+                if let Some(index) = self.range_index(binding.ty) {
+                    self.allocated[index] -= binding.count;
+                }
+            }
+            self.allocated_sets -= 1;
+        }
+    }
+
+    unsafe fn reset(&mut self) {
+        self.allocated_sets = 0;
+        for count in &mut self.allocated {
+            *count = 0;
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Fence {
+    pub(crate) signaled: Mutex<bool>,
+    pub(crate) condvar: Condvar,
+}
+
+impl Fence {
+    pub(crate) fn new(signaled: bool) -> Self {
+        Fence {
+            signaled: Mutex::new(signaled),
+            condvar: Condvar::new(),
+        }
+    }
+
+    pub(crate) fn signal(&self) {
+        *self.signaled.lock().unwrap() = true;
+        self.condvar.notify_all();
+    }
+}
+
+#[derive(Debug)]
+pub struct Semaphore;
+
+#[derive(Clone, Debug)]
+pub struct Event(pub(crate) Arc<AtomicBool>);
+
+#[derive(Clone, Debug)]
+pub struct QueryPool {
+    pub(crate) ty: query::Type,
+    /// Values of every query, `None` until the query becomes available.
+    pub(crate) results: Arc<Mutex<Vec<Option<Vec<u64>>>>>,
+    /// Reference point of the timestamps, shared by all pools of a device.
+    pub(crate) epoch: Instant,
+}
+
+impl QueryPool {
+    // Returns the number of values produced by a single query.
+    pub(crate) fn value_count(&self) -> usize {
+        match self.ty {
+            query::Type::PipelineStatistics(stats) => stats.bits().count_ones() as usize,
+            query::Type::Occlusion | query::Type::Timestamp => 1,
+        }
+    }
+
+    // Returns the number of bytes written for a single query with the given flags.
+    pub(crate) fn result_size(&self, flags: query::ResultFlags) -> usize {
+        let value_size = if flags.contains(query::ResultFlags::BITS_64) {
+            8
+        } else {
+            4
+        };
+        let count =
+            self.value_count() + flags.contains(query::ResultFlags::WITH_AVAILABILITY) as usize;
+        count * value_size
+    }
+
+    // Writes query results into `data` with the layout of `vkGetQueryPoolResults`.
+    // Returns true if all of the queries were available.
+    pub(crate) fn write_results(
+        &self,
+        queries: Range<query::Id>,
+        data: &mut [u8],
+        stride: buffer::Offset,
+        flags: query::ResultFlags,
+    ) -> bool {
+        fn write_value(data: &mut [u8], index: usize, value: u64, flags: query::ResultFlags) {
+            if flags.contains(query::ResultFlags::BITS_64) {
+                data[index * 8 .. (index + 1) * 8].copy_from_slice(&value.to_ne_bytes());
+            } else {
+                data[index * 4 .. (index + 1) * 4].copy_from_slice(&(value as u32).to_ne_bytes());
+            }
+        }
+
+        let results = self.results.lock().unwrap();
+        let value_count = self.value_count();
+        let mut all_available = true;
+        for (i, id) in queries.enumerate() {
+            let data = &mut data[i * stride as usize ..];
+            let result = results[id as usize].as_ref();
+            match result {
+                Some(values) => {
+                    for (index, &value) in values.iter().enumerate() {
+                        write_value(data, index, value, flags);
+                    }
+                }
+                None if flags.contains(query::ResultFlags::PARTIAL) => {
+                    for index in 0 .. value_count {
+                        write_value(data, index, 0, flags);
+                    }
+                }
+                None => {}
+            }
+            if flags.contains(query::ResultFlags::WITH_AVAILABILITY) {
+                write_value(data, value_count, result.is_some() as u64, flags);
+            }
+            all_available &= result.is_some();
+        }
+        all_available
+    }
+}
@@ -0,0 +1,1463 @@
+use hal::format::{texel, Aspects, ChannelType};
+use hal::{command as com, image as i, pso, query, queue, window, IndexType};
+
+use crate::command::{Command, CommandBuffer};
+#[cfg(feature = "software")]
+use crate::raster;
+use crate::{native as n, Backend, Surface, Swapchain};
+
+use std::borrow::Borrow;
+use std::ops::Range;
+use std::ptr;
+use std::slice;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+/// Size of the push constant storage, matching `max_push_constants_size`.
+const PUSH_CONSTANTS_SIZE: usize = 128;
+
+/// Dynamic states of the graphics pipelines, set by the command buffers.
+#[derive(Clone, Debug)]
+pub(crate) struct Dynamic {
+    pub(crate) viewport: Option<pso::Viewport>,
+    pub(crate) scissor: Option<pso::Rect>,
+    pub(crate) blend_constants: pso::ColorValue,
+    pub(crate) stencil_reference: pso::Sided<pso::StencilValue>,
+    pub(crate) stencil_read_mask: pso::Sided<pso::StencilValue>,
+    pub(crate) stencil_write_mask: pso::Sided<pso::StencilValue>,
+    pub(crate) depth_bounds: Range<f32>,
+    pub(crate) depth_bias: pso::DepthBias,
+}
+
+impl Default for Dynamic {
+    fn default() -> Self {
+        Dynamic {
+            viewport: None,
+            scissor: None,
+            blend_constants: [0.0; 4],
+            stencil_reference: pso::Sided::new(0),
+            stencil_read_mask: pso::Sided::new(!0),
+            stencil_write_mask: pso::Sided::new(!0),
+            depth_bounds: 0.0 .. 1.0,
+            depth_bias: pso::DepthBias::default(),
+        }
+    }
+}
+
+/// Samples and pipeline statistics counted by the executed commands.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Counters {
+    pub(crate) samples: u64,
+    /// Statistics, indexed by the position of their flag.
+    statistics: [u64; 11],
+}
+
+impl Counters {
+    #[cfg_attr(not(feature = "software"), allow(dead_code))]
+    pub(crate) fn add(&mut self, statistic: query::PipelineStatistic, count: u64) {
+        self.statistics[statistic.bits().trailing_zeros() as usize] += count;
+    }
+
+    // Returns the values of a query, counted since `begin`.
+    fn query_values(&self, begin: &Counters, ty: query::Type) -> Vec<u64> {
+        match ty {
+            query::Type::Occlusion => vec![self.samples - begin.samples],
+            query::Type::PipelineStatistics(statistics) => (0 .. self.statistics.len())
+                .filter(|&i| statistics.bits() & (1 << i) != 0)
+                .map(|i| self.statistics[i] - begin.statistics[i])
+                .collect(),
+            query::Type::Timestamp => vec![0],
+        }
+    }
+}
+
+/// Render pass instance being executed.
+#[derive(Debug)]
+struct RenderPassState {
+    render_pass: n::RenderPass,
+    framebuffer: n::Framebuffer,
+    area: pso::Rect,
+    subpass: usize,
+}
+
+/// Descriptors of the bound sets, indexed by set, binding number and array element.
+type BoundSets = Vec<Vec<Vec<n::Descriptor>>>;
+
+/// State of the execution of a command buffer.
+#[derive(Debug)]
+struct State {
+    graphics_pipeline: Option<n::GraphicsPipeline>,
+    compute_pipeline: Option<n::ComputePipeline>,
+    graphics_sets: BoundSets,
+    compute_sets: BoundSets,
+    vertex_buffers: Vec<Option<n::Binding>>,
+    index_buffer: Option<(n::Binding, IndexType)>,
+    dynamic: Dynamic,
+    push_constants: Vec<u8>,
+    render_pass: Option<RenderPassState>,
+    counters: Counters,
+    /// Queries that began, with the counters at that time.
+    queries: Vec<(n::QueryPool, query::Id, Counters)>,
+}
+
+impl State {
+    fn new() -> Self {
+        State {
+            graphics_pipeline: None,
+            compute_pipeline: None,
+            graphics_sets: Vec::new(),
+            compute_sets: Vec::new(),
+            vertex_buffers: Vec::new(),
+            index_buffer: None,
+            dynamic: Dynamic::default(),
+            push_constants: vec![0; PUSH_CONSTANTS_SIZE],
+            render_pass: None,
+            counters: Counters::default(),
+            queries: Vec::new(),
+        }
+    }
+
+    // Returns the subpass being executed, with the attachments of the framebuffer.
+    fn subpass(&self) -> (&n::Subpass, &[n::ImageView]) {
+        let pass = self
+            .render_pass
+            .as_ref()
+            .expect("Expected an active render pass!");
+        (
+            &pass.render_pass.subpasses[pass.subpass],
+            &pass.framebuffer.views,
+        )
+    }
+}
+
+// Selects the faces of a sided state to update.
+fn set_sided(
+    sided: &mut pso::Sided<pso::StencilValue>,
+    faces: pso::Face,
+    value: pso::StencilValue,
+) {
+    if faces.contains(pso::Face::FRONT) {
+        sided.front = value;
+    }
+    if faces.contains(pso::Face::BACK) {
+        sided.back = value;
+    }
+}
+
+/// Value written by a clear operation.
+enum Clear {
+    Color(texel::Texel),
+    DepthStencil(Option<f32>, Option<u32>),
+}
+
+impl Clear {
+    // Returns the clear of the selected aspects of a format.
+    unsafe fn new(format: hal::format::Format, value: com::ClearValue, aspects: Aspects) -> Self {
+        if format.is_color() {
+            Clear::Color(Self::color(format, value.color))
+        } else {
+            let depth_stencil = value.depth_stencil;
+            Clear::DepthStencil(
+                Some(depth_stencil.depth)
+                    .filter(|_| format.is_depth() && aspects.contains(Aspects::DEPTH)),
+                Some(depth_stencil.stencil)
+                    .filter(|_| format.is_stencil() && aspects.contains(Aspects::STENCIL)),
+            )
+        }
+    }
+
+    // Converts a clear color to the texel representation of a format.
+    unsafe fn color(format: hal::format::Format, color: com::ClearColor) -> texel::Texel {
+        match format.base_format().1 {
+            ChannelType::Uint => color.uint32,
+            ChannelType::Sint => {
+                let c = color.sint32;
+                [c[0] as u32, c[1] as u32, c[2] as u32, c[3] as u32]
+            }
+            _ => {
+                let c = color.float32;
+                [
+                    c[0].to_bits(),
+                    c[1].to_bits(),
+                    c[2].to_bits(),
+                    c[3].to_bits(),
+                ]
+            }
+        }
+    }
+
+    // Clears a region of a single subresource of an image.
+    unsafe fn apply(
+        &self,
+        image: &n::BoundImage,
+        level: i::Level,
+        layer: i::Layer,
+        min: i::Offset,
+        max: i::Offset,
+    ) {
+        let format = image.desc.format;
+        let mut encoded = vec![0; image.desc.block_size as usize];
+        if let Clear::Color(texel) = *self {
+            texel::encode(format, &mut encoded, texel);
+        }
+        for z in min.z .. max.z {
+            for y in min.y .. max.y {
+                for x in min.x .. max.x {
+                    let data = image.texel(level, layer, i::Offset { x, y, z });
+                    match *self {
+                        Clear::Color(_) => data.copy_from_slice(&encoded),
+                        Clear::DepthStencil(depth, stencil) => {
+                            if let Some(depth) = depth {
+                                texel::encode_depth(format, data, depth);
+                            }
+                            if let Some(stencil) = stencil {
+                                texel::encode_stencil(format, data, stencil);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Clears a rectangle of the layers of a view, relative to its base layer.
+    unsafe fn apply_view(&self, view: &n::ImageView, rect: pso::Rect, layers: Range<i::Layer>) {
+        let extent = view.level_extent(0);
+        let min = i::Offset {
+            x: (rect.x as i32).max(0),
+            y: (rect.y as i32).max(0),
+            z: 0,
+        };
+        let max = i::Offset {
+            x: (rect.x as i32 + rect.w as i32).min(extent.width as i32),
+            y: (rect.y as i32 + rect.h as i32).min(extent.height as i32),
+            z: extent.depth as i32,
+        };
+        for layer in layers {
+            self.apply(
+                &view.image,
+                view.range.levels.start,
+                view.range.layers.start + layer,
+                min,
+                max,
+            );
+        }
+    }
+}
+
+/// Command queue executing the submitted commands on the calling thread.
+#[derive(Debug)]
+pub struct CommandQueue;
+
+impl CommandQueue {
+    // Copies the rows of a 3D region between two linear layouts, given in bytes.
+    unsafe fn copy_rows(
+        src: *const u8,
+        (src_row_pitch, src_depth_pitch): (u64, u64),
+        dst: *mut u8,
+        (dst_row_pitch, dst_depth_pitch): (u64, u64),
+        row_size: u64,
+        (rows, slices): (u64, u64),
+    ) {
+        for z in 0 .. slices {
+            for y in 0 .. rows {
+                ptr::copy(
+                    src.offset((z * src_depth_pitch + y * src_row_pitch) as isize),
+                    dst.offset((z * dst_depth_pitch + y * dst_row_pitch) as isize),
+                    row_size as usize,
+                );
+            }
+        }
+    }
+
+    // Copies texels between a buffer and an image, in either direction.
+    unsafe fn copy_buffer_image(
+        buffer: &n::Binding,
+        image: &n::BoundImage,
+        region: &com::BufferImageCopy,
+        to_image: bool,
+    ) {
+        let desc = &image.desc;
+        let (bw, bh) = (desc.block_dim.0 as u64, desc.block_dim.1 as u64);
+        let extent = region.image_extent;
+        // Zero buffer dimensions mean tightly packed data.
+        let buffer_width = match region.buffer_width {
+            0 => extent.width,
+            width => width,
+        } as u64;
+        let buffer_height = match region.buffer_height {
+            0 => extent.height,
+            height => height,
+        } as u64;
+        let buffer_row_pitch = buffer_width.div_ceil(bw) * desc.block_size;
+        let buffer_depth_pitch = buffer_height.div_ceil(bh) * buffer_row_pitch;
+        let buffer_array_pitch = buffer_depth_pitch * extent.depth as u64;
+        let row_size = (extent.width as u64).div_ceil(bw) * desc.block_size;
+        let rows = (extent.height as u64).div_ceil(bh);
+
+        let layers = &region.image_layers;
+        for (i, layer) in layers.layers.clone().enumerate() {
+            let footprint = desc.footprint(layers.level, layer);
+            let image_ptr =
+                image
+                    .binding
+                    .ptr(desc.texel_offset(layers.level, layer, region.image_offset));
+            let buffer_ptr = buffer.ptr(region.buffer_offset + i as u64 * buffer_array_pitch);
+            let image_pitches = (footprint.row_pitch, footprint.depth_pitch);
+            let buffer_pitches = (buffer_row_pitch, buffer_depth_pitch);
+            let size = (rows, extent.depth as u64);
+            if to_image {
+                Self::copy_rows(
+                    buffer_ptr,
+                    buffer_pitches,
+                    image_ptr,
+                    image_pitches,
+                    row_size,
+                    size,
+                );
+            } else {
+                Self::copy_rows(
+                    image_ptr,
+                    image_pitches,
+                    buffer_ptr,
+                    buffer_pitches,
+                    row_size,
+                    size,
+                );
+            }
+        }
+    }
+
+    // Copies the texels of a blit region, scaling them with the given filter.
+    unsafe fn blit(
+        src: &n::BoundImage,
+        dst: &n::BoundImage,
+        filter: i::Filter,
+        region: &com::ImageBlit,
+    ) {
+        let (s, d) = (&region.src_bounds, &region.dst_bounds);
+        let src_format = src.desc.format;
+        let dst_format = dst.desc.format;
+        let src_extent = src.desc.kind.level_extent(region.src_subresource.level);
+        let scale = |d0: i32, d1: i32, s0: i32, s1: i32, x: i32| {
+            s0 as f32 + (x as f32 + 0.5 - d0 as f32) * (s1 - s0) as f32 / (d1 - d0) as f32
+        };
+        // Depth and stencil values, and integer texels, are never filtered.
+        let raw = !src_format.is_color();
+        let linear = filter == i::Filter::Linear && !raw && texel::is_float(src_format);
+
+        let src_layers = region.src_subresource.layers.clone();
+        let dst_layers = region.dst_subresource.layers.clone();
+        for (src_layer, dst_layer) in src_layers.zip(dst_layers) {
+            let src_level = region.src_subresource.level;
+            let fetch = |x: i32, y: i32, z: i32| {
+                let offset = i::Offset {
+                    x: x.max(0).min(src_extent.width as i32 - 1),
+                    y: y.max(0).min(src_extent.height as i32 - 1),
+                    z: z.max(0).min(src_extent.depth as i32 - 1),
+                };
+                src.texel(src_level, src_layer, offset)
+            };
+            for z in d.start.z.min(d.end.z) .. d.start.z.max(d.end.z) {
+                let sz = scale(d.start.z, d.end.z, s.start.z, s.end.z, z).floor() as i32;
+                for y in d.start.y.min(d.end.y) .. d.start.y.max(d.end.y) {
+                    let sy = scale(d.start.y, d.end.y, s.start.y, s.end.y, y);
+                    for x in d.start.x.min(d.end.x) .. d.start.x.max(d.end.x) {
+                        let sx = scale(d.start.x, d.end.x, s.start.x, s.end.x, x);
+                        let target = dst.texel(
+                            region.dst_subresource.level,
+                            dst_layer,
+                            i::Offset { x, y, z },
+                        );
+                        if raw {
+                            target.copy_from_slice(fetch(sx.floor() as i32, sy.floor() as i32, sz));
+                            continue;
+                        }
+                        let texel = if linear {
+                            let (fx, fy) = (sx - 0.5, sy - 0.5);
+                            let (x0, y0) = (fx.floor() as i32, fy.floor() as i32);
+                            let (tx, ty) = (fx - fx.floor(), fy - fy.floor());
+                            let mut texel = [0.0f32; 4];
+                            let taps = [
+                                (x0, y0, (1.0 - tx) * (1.0 - ty)),
+                                (x0 + 1, y0, tx * (1.0 - ty)),
+                                (x0, y0 + 1, (1.0 - tx) * ty),
+                                (x0 + 1, y0 + 1, tx * ty),
+                            ];
+                            for &(tx, ty, weight) in &taps {
+                                let value = texel::decode(src_format, fetch(tx, ty, sz));
+                                for c in 0 .. 4 {
+                                    texel[c] += f32::from_bits(value[c]) * weight;
+                                }
+                            }
+                            [
+                                texel[0].to_bits(),
+                                texel[1].to_bits(),
+                                texel[2].to_bits(),
+                                texel[3].to_bits(),
+                            ]
+                        } else {
+                            texel::decode(
+                                src_format,
+                                fetch(sx.floor() as i32, sy.floor() as i32, sz),
+                            )
+                        };
+                        texel::encode(dst_format, target, texel);
+                    }
+                }
+            }
+        }
+    }
+
+    // Copies the color attachments of the current subpass into the resolve attachments.
+    unsafe fn resolve_subpass(state: &State) {
+        let pass = state.render_pass.as_ref().unwrap();
+        let (subpass, views) = state.subpass();
+        for (&color, &resolve) in subpass.colors.iter().zip(&subpass.resolves) {
+            let (src, dst) = (&views[color], &views[resolve]);
+            let area = pass.area;
+            let extent = src.level_extent(0);
+            let layers = src.range.layers.end - src.range.layers.start;
+            for layer in 0 .. layers {
+                for y in (area.y as i32).max(0)
+                    .. (area.y as i32 + area.h as i32).min(extent.height as i32)
+                {
+                    for x in (area.x as i32).max(0)
+                        .. (area.x as i32 + area.w as i32).min(extent.width as i32)
+                    {
+                        let offset = i::Offset { x, y, z: 0 };
+                        let texel = texel::decode(src.format, src.texel(0, layer, offset));
+                        texel::encode(dst.format, dst.texel(0, layer, offset), texel);
+                    }
+                }
+            }
+        }
+    }
+
+    // Snapshots the descriptors of the sets, applying the dynamic offsets.
+    fn bind_sets(
+        bound: &mut BoundSets,
+        first: usize,
+        sets: &[n::DescriptorSet],
+        offsets: &[com::DescriptorSetOffset],
+    ) {
+        let mut offsets = offsets.iter();
+        for (i, set) in sets.iter().enumerate() {
+            let descriptors = set.descriptors.lock().unwrap();
+            let mut bindings = Vec::new();
+            for (layout, descriptors) in set.layout.bindings.iter().zip(descriptors.iter()) {
+                let mut descriptors = descriptors.clone();
+                match layout.ty {
+                    pso::DescriptorType::UniformBufferDynamic
+                    | pso::DescriptorType::StorageBufferDynamic => {
+                        for descriptor in &mut descriptors {
+                            let offset = *offsets.next().expect("Missing dynamic offset") as u64;
+                            if let Some((_, ref mut range)) = descriptor.buffer {
+                                range.start += offset;
+                                range.end += offset;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                let binding = layout.binding as usize;
+                if bindings.len() <= binding {
+                    bindings.resize(binding + 1, Vec::new());
+                }
+                bindings[binding] = descriptors;
+            }
+            if bound.len() <= first + i {
+                bound.resize(first + i + 1, Vec::new());
+            }
+            bound[first + i] = bindings;
+        }
+    }
+
+    #[cfg(feature = "software")]
+    unsafe fn draw(
+        state: &mut State,
+        vertices: Range<u32>,
+        base_vertex: Option<i32>,
+        instances: Range<u32>,
+    ) {
+        let pipeline = state
+            .graphics_pipeline
+            .as_ref()
+            .expect("Expected a bound graphics pipeline!");
+        let (subpass, views) = state.subpass();
+        let draw = raster::Draw {
+            pipeline: &pipeline.pipeline,
+            resources: &state.graphics_sets,
+            push_constants: &state.push_constants,
+            vertex_buffers: &state.vertex_buffers,
+            index_buffer: state.index_buffer.as_ref(),
+            dynamic: &state.dynamic,
+            colors: subpass.colors.iter().map(|&id| Some(&views[id])).collect(),
+            depth_stencil: subpass.depth_stencil.map(|id| &views[id]),
+            area: state.render_pass.as_ref().unwrap().area,
+        };
+        let mut counters = state.counters.clone();
+        draw.draw(vertices, base_vertex, instances, &mut counters);
+        state.counters = counters;
+    }
+
+    #[cfg(not(feature = "software"))]
+    unsafe fn draw(
+        _state: &mut State,
+        _vertices: Range<u32>,
+        _base_vertex: Option<i32>,
+        _instances: Range<u32>,
+    ) {
+        panic!("Drawing requires the `software` feature of the empty backend");
+    }
+
+    #[cfg(feature = "software")]
+    unsafe fn dispatch(state: &mut State, groups: hal::WorkGroupCount) {
+        let pipeline = state
+            .compute_pipeline
+            .as_ref()
+            .expect("Expected a bound compute pipeline!");
+        let invocations =
+            pipeline
+                .program
+                .dispatch(&state.compute_sets, &state.push_constants, groups);
+        state.counters.add(
+            query::PipelineStatistic::COMPUTE_SHADER_INVOCATIONS,
+            invocations,
+        );
+    }
+
+    #[cfg(not(feature = "software"))]
+    unsafe fn dispatch(_state: &mut State, _groups: hal::WorkGroupCount) {
+        panic!("Dispatching requires the `software` feature of the empty backend");
+    }
+
+    unsafe fn execute(&self, command: &Command, state: &mut State) {
+        match *command {
+            Command::CopyBuffer {
+                ref src,
+                ref dst,
+                ref regions,
+            } => {
+                for region in regions {
+                    ptr::copy(
+                        src.ptr(region.src),
+                        dst.ptr(region.dst),
+                        region.size as usize,
+                    );
+                }
+            }
+            Command::FillBuffer {
+                ref dst,
+                ref range,
+                data,
+            } => {
+                let count = ((range.end - range.start) / 4) as usize;
+                let ptr = dst.ptr(range.start) as *mut u32;
+                for i in 0 .. count {
+                    ptr::write_unaligned(ptr.add(i), data);
+                }
+            }
+            Command::UpdateBuffer {
+                ref dst,
+                offset,
+                ref data,
+            } => {
+                ptr::copy(data.as_ptr(), dst.ptr(offset), data.len());
+            }
+            Command::CopyImage {
+                ref src,
+                ref dst,
+                ref regions,
+            } => {
+                let (bw, bh) = (src.desc.block_dim.0 as u64, src.desc.block_dim.1 as u64);
+                for region in regions {
+                    let row_size = (region.extent.width as u64).div_ceil(bw) * src.desc.block_size;
+                    let rows = (region.extent.height as u64).div_ceil(bh);
+                    let src_layers = region.src_subresource.layers.clone();
+                    let dst_layers = region.dst_subresource.layers.clone();
+                    for (src_layer, dst_layer) in src_layers.zip(dst_layers) {
+                        let src_level = region.src_subresource.level;
+                        let dst_level = region.dst_subresource.level;
+                        let src_footprint = src.desc.footprint(src_level, src_layer);
+                        let dst_footprint = dst.desc.footprint(dst_level, dst_layer);
+                        Self::copy_rows(
+                            src.binding.ptr(src.desc.texel_offset(
+                                src_level,
+                                src_layer,
+                                region.src_offset,
+                            )),
+                            (src_footprint.row_pitch, src_footprint.depth_pitch),
+                            dst.binding.ptr(dst.desc.texel_offset(
+                                dst_level,
+                                dst_layer,
+                                region.dst_offset,
+                            )),
+                            (dst_footprint.row_pitch, dst_footprint.depth_pitch),
+                            row_size,
+                            (rows, region.extent.depth as u64),
+                        );
+                    }
+                }
+            }
+            Command::CopyBufferToImage {
+                ref src,
+                ref dst,
+                ref regions,
+            } => {
+                for region in regions {
+                    Self::copy_buffer_image(src, dst, region, true);
+                }
+            }
+            Command::CopyImageToBuffer {
+                ref src,
+                ref dst,
+                ref regions,
+            } => {
+                for region in regions {
+                    Self::copy_buffer_image(dst, src, region, false);
+                }
+            }
+            Command::ClearImage {
+                ref image,
+                value,
+                ref ranges,
+            } => {
+                for range in ranges {
+                    let clear = Clear::new(image.desc.format, value, range.aspects);
+                    for level in range.levels.clone() {
+                        let extent = image.desc.kind.level_extent(level);
+                        let max = i::Offset {
+                            x: extent.width as i32,
+                            y: extent.height as i32,
+                            z: extent.depth as i32,
+                        };
+                        for layer in range.layers.clone() {
+                            clear.apply(image, level, layer, i::Offset::ZERO, max);
+                        }
+                    }
+                }
+            }
+            Command::ClearAttachments {
+                ref clears,
+                ref rects,
+            } => {
+                let (subpass, views) = state.subpass();
+                for clear in clears {
+                    let (view, value) = match *clear {
+                        com::AttachmentClear::Color { index, value } => {
+                            let view = &views[subpass.colors[index]];
+                            (view, Clear::Color(Clear::color(view.format, value)))
+                        }
+                        com::AttachmentClear::DepthStencil { depth, stencil } => {
+                            match subpass.depth_stencil {
+                                Some(id) => (&views[id], Clear::DepthStencil(depth, stencil)),
+                                None => continue,
+                            }
+                        }
+                    };
+                    for rect in rects {
+                        value.apply_view(view, rect.rect, rect.layers.clone());
+                    }
+                }
+            }
+            Command::BlitImage {
+                ref src,
+                ref dst,
+                filter,
+                ref regions,
+            } => {
+                for region in regions {
+                    Self::blit(src, dst, filter, region);
+                }
+            }
+            Command::BeginRenderPass {
+                ref render_pass,
+                ref framebuffer,
+                area,
+                ref clear_values,
+            } => {
+                // Attachments are cleared when the render pass begins, instead of at their first use.
+                for (id, attachment) in render_pass.attachments.iter().enumerate() {
+                    let view = &framebuffer.views[id];
+                    let mut aspects = Aspects::empty();
+                    if attachment.ops.load == hal::pass::AttachmentLoadOp::Clear {
+                        aspects |= Aspects::COLOR | Aspects::DEPTH;
+                    }
+                    if attachment.stencil_ops.load == hal::pass::AttachmentLoadOp::Clear {
+                        aspects |= Aspects::STENCIL;
+                    }
+                    if aspects.is_empty() {
+                        continue;
+                    }
+                    let clear = Clear::new(view.format, clear_values[id], aspects);
+                    let layers = view.range.layers.end - view.range.layers.start;
+                    clear.apply_view(view, area, 0 .. layers);
+                }
+                state.render_pass = Some(RenderPassState {
+                    render_pass: render_pass.clone(),
+                    framebuffer: framebuffer.clone(),
+                    area,
+                    subpass: 0,
+                });
+            }
+            Command::NextSubpass => {
+                Self::resolve_subpass(state);
+                state.render_pass.as_mut().unwrap().subpass += 1;
+            }
+            Command::EndRenderPass => {
+                Self::resolve_subpass(state);
+                state.render_pass = None;
+            }
+            Command::BindGraphicsPipeline(ref pipeline) => {
+                state.graphics_pipeline = Some(pipeline.clone());
+            }
+            Command::BindComputePipeline(ref pipeline) => {
+                state.compute_pipeline = Some(pipeline.clone());
+            }
+            Command::BindDescriptorSets {
+                compute,
+                first,
+                ref sets,
+                ref offsets,
+            } => {
+                let bound = if compute {
+                    &mut state.compute_sets
+                } else {
+                    &mut state.graphics_sets
+                };
+                Self::bind_sets(bound, first, sets, offsets);
+            }
+            Command::BindVertexBuffers { first, ref buffers } => {
+                let first = first as usize;
+                if state.vertex_buffers.len() < first + buffers.len() {
+                    state.vertex_buffers.resize(first + buffers.len(), None);
+                }
+                for (i, binding) in buffers.iter().enumerate() {
+                    state.vertex_buffers[first + i] = Some(binding.clone());
+                }
+            }
+            Command::BindIndexBuffer(ref binding, index_type) => {
+                state.index_buffer = Some((binding.clone(), index_type));
+            }
+            Command::SetViewport(ref viewport) => state.dynamic.viewport = Some(viewport.clone()),
+            Command::SetScissor(rect) => state.dynamic.scissor = Some(rect),
+            Command::SetStencilReference(faces, value) => {
+                set_sided(&mut state.dynamic.stencil_reference, faces, value)
+            }
+            Command::SetStencilReadMask(faces, value) => {
+                set_sided(&mut state.dynamic.stencil_read_mask, faces, value)
+            }
+            Command::SetStencilWriteMask(faces, value) => {
+                set_sided(&mut state.dynamic.stencil_write_mask, faces, value)
+            }
+            Command::SetBlendConstants(color) => state.dynamic.blend_constants = color,
+            Command::SetDepthBounds(ref bounds) => state.dynamic.depth_bounds = bounds.clone(),
+            Command::SetDepthBias(bias) => state.dynamic.depth_bias = bias,
+            Command::PushConstants { offset, ref data } => {
+                for (i, word) in data.iter().enumerate() {
+                    let start = offset as usize + i * 4;
+                    state.push_constants[start .. start + 4].copy_from_slice(&word.to_ne_bytes());
+                }
+            }
+            Command::Draw {
+                ref vertices,
+                ref instances,
+            } => Self::draw(state, vertices.clone(), None, instances.clone()),
+            Command::DrawIndexed {
+                ref indices,
+                base_vertex,
+                ref instances,
+            } => Self::draw(state, indices.clone(), Some(base_vertex), instances.clone()),
+            Command::DrawIndirect {
+                ref buffer,
+                count,
+                stride,
+                indexed,
+            } => {
+                for draw in 0 .. count {
+                    let ptr = buffer.ptr(draw as u64 * stride as u64) as *const u32;
+                    let arg = |i: usize| ptr.add(i).read_unaligned();
+                    if indexed {
+                        let indices = arg(2) .. arg(2) + arg(0);
+                        let instances = arg(4) .. arg(4) + arg(1);
+                        Self::draw(state, indices, Some(arg(3) as i32), instances);
+                    } else {
+                        let vertices = arg(2) .. arg(2) + arg(0);
+                        let instances = arg(3) .. arg(3) + arg(1);
+                        Self::draw(state, vertices, None, instances);
+                    }
+                }
+            }
+            Command::Dispatch(groups) => Self::dispatch(state, groups),
+            Command::DispatchIndirect(ref buffer) => {
+                let ptr = buffer.ptr(0) as *const u32;
+                let groups = [
+                    ptr.read_unaligned(),
+                    ptr.add(1).read_unaligned(),
+                    ptr.add(2).read_unaligned(),
+                ];
+                Self::dispatch(state, groups);
+            }
+            Command::SetEvent(ref event, value) => {
+                event.0.store(value, Ordering::Release);
+            }
+            Command::ResetQueryPool(ref pool, ref queries) => {
+                let mut results = pool.results.lock().unwrap();
+                for id in queries.clone() {
+                    results[id as usize] = None;
+                }
+            }
+            Command::BeginQuery(ref pool, id) => {
+                state
+                    .queries
+                    .push((pool.clone(), id, state.counters.clone()));
+            }
+            Command::EndQuery(ref pool, id) => {
+                let position = state
+                    .queries
+                    .iter()
+                    .position(|&(ref p, i, _)| Arc::ptr_eq(&p.results, &pool.results) && i == id);
+                let values = match position {
+                    Some(position) => {
+                        let (_, _, begin) = state.queries.remove(position);
+                        state.counters.query_values(&begin, pool.ty)
+                    }
+                    None => vec![0; pool.value_count()],
+                };
+                pool.results.lock().unwrap()[id as usize] = Some(values);
+            }
+            Command::WriteTimestamp(ref pool, id) => {
+                let elapsed = pool.epoch.elapsed();
+                let timestamp = elapsed.as_secs() * 1_000_000_000 + elapsed.subsec_nanos() as u64;
+                pool.results.lock().unwrap()[id as usize] = Some(vec![timestamp]);
+            }
+            Command::CopyQueryPoolResults {
+                ref pool,
+                ref queries,
+                ref dst,
+                stride,
+                flags,
+            } => {
+                let count = (queries.end - queries.start) as usize;
+                if count != 0 {
+                    let size = (count - 1) * stride as usize + pool.result_size(flags);
+                    let data = slice::from_raw_parts_mut(dst.ptr(0), size);
+                    pool.write_results(queries.clone(), data, stride, flags);
+                }
+            }
+        }
+    }
+}
+
+impl queue::CommandQueue<Backend> for CommandQueue {
+    unsafe fn submit<'a, T, Ic, S, Iw, Is>(
+        &mut self,
+        submission: queue::Submission<Ic, Iw, Is>,
+        fence: Option<&n::Fence>,
+    ) where
+        T: 'a + Borrow<CommandBuffer>,
+        Ic: IntoIterator<Item = &'a T>,
+        S: 'a + Borrow<n::Semaphore>,
+        Iw: IntoIterator<Item = (&'a S, pso::PipelineStage)>,
+        Is: IntoIterator<Item = &'a S>,
+    {
+        // Submissions complete before returning, so semaphores don't need to be tracked.
+        for buffer in submission.command_buffers {
+            let mut state = State::new();
+            for command in &buffer.borrow().commands {
+                self.execute(command, &mut state);
+            }
+        }
+
+        if let Some(fence) = fence {
+            fence.signal();
+        }
+    }
+
+    unsafe fn present<'a, W, Is, S, Iw>(
+        &mut self,
+        _swapchains: Is,
+        _wait_semaphores: Iw,
+    ) -> Result<Option<window::Suboptimal>, window::PresentError>
+    where
+        W: 'a + Borrow<Swapchain>,
+        Is: IntoIterator<Item = (&'a W, window::SwapImageIndex)>,
+        S: 'a + Borrow<n::Semaphore>,
+        Iw: IntoIterator<Item = &'a S>,
+    {
+        Err(window::PresentError::SurfaceLost(hal::device::SurfaceLost))
+    }
+
+    unsafe fn present_surface(
+        &mut self,
+        _surface: &mut Surface,
+        _image: n::ImageView,
+        _wait_semaphore: Option<&n::Semaphore>,
+    ) -> Result<Option<window::Suboptimal>, window::PresentError> {
+        Err(window::PresentError::SurfaceLost(hal::device::SurfaceLost))
+    }
+
+    fn wait_idle(&self) -> Result<(), hal::device::OutOfMemory> {
+        // Submissions are executed synchronously.
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_utils, Instance};
+    use hal::command::CommandBuffer as _;
+    use hal::device::Device as _;
+    use hal::pool::CommandPool as _;
+    use hal::queue::CommandQueue as _;
+    use hal::{buffer, command, format, image, memory, pso, query, MemoryTypeId};
+    use std::{ptr, slice};
+
+    // Records a command buffer and submits it, waiting for the fence it signals.
+    unsafe fn submit<F>(device: &crate::Device, queue: &mut crate::CommandQueue, record: F)
+    where
+        F: FnOnce(&mut crate::CommandBuffer),
+    {
+        let mut pool = device
+            .create_command_pool(
+                hal::queue::QueueFamilyId(0),
+                hal::pool::CommandPoolCreateFlags::empty(),
+            )
+            .unwrap();
+        let mut cmd_buffer = pool.allocate_one(command::Level::Primary);
+        cmd_buffer.begin_primary(command::CommandBufferFlags::ONE_TIME_SUBMIT);
+        record(&mut cmd_buffer);
+        cmd_buffer.finish();
+        let fence = device.create_fence(false).unwrap();
+        queue.submit_without_semaphores(Some(&cmd_buffer), Some(&fence));
+        assert!(device.wait_for_fence(&fence, 0).unwrap());
+    }
+
+    #[test]
+    fn test_buffer_regions() {
+        let (_, mut gpu) = test_utils::open(&Instance);
+        let data = (0 .. 64).collect::<Vec<u8>>();
+
+        let device = &gpu.device;
+        let queue = &mut gpu.queue_groups[0].queues[0];
+
+        unsafe {
+            let memory = device.allocate_memory(MemoryTypeId(1), 64).unwrap();
+            let mut src = device
+                .create_buffer(32, buffer::Usage::TRANSFER_SRC | buffer::Usage::TRANSFER_DST)
+                .unwrap();
+            let mut dst = device
+                .create_buffer(32, buffer::Usage::TRANSFER_DST)
+                .unwrap();
+            device.bind_buffer_memory(&memory, 0, &mut src).unwrap();
+            device.bind_buffer_memory(&memory, 32, &mut dst).unwrap();
+            let mapping = device.map_memory(&memory, ..).unwrap();
+            ptr::copy_nonoverlapping(data.as_ptr(), mapping, data.len());
+
+            // Commands run in order, so the copies see the fill and the update.
+            submit(device, queue, |cmd_buffer| {
+                cmd_buffer.fill_buffer(&src, 4 .. 12, 0xDDCC_BBAA);
+                cmd_buffer.update_buffer(&src, 14, &[100, 101, 102]);
+                cmd_buffer.copy_buffer(
+                    &src,
+                    &dst,
+                    &[
+                        command::BufferCopy {
+                            src: 8,
+                            dst: 0,
+                            size: 4,
+                        },
+                        command::BufferCopy {
+                            src: 12,
+                            dst: 20,
+                            size: 8,
+                        },
+                    ],
+                );
+            });
+
+            let src = slice::from_raw_parts(mapping, 32);
+            assert_eq!(&src[.. 4], &data[.. 4]);
+            assert_eq!(&src[4 .. 12], &[0xAA, 0xBB, 0xCC, 0xDD, 0xAA, 0xBB, 0xCC, 0xDD]);
+            assert_eq!(&src[12 .. 14], &data[12 .. 14]);
+            assert_eq!(&src[14 .. 17], &[100, 101, 102]);
+            assert_eq!(&src[17 ..], &data[17 .. 32]);
+
+            let dst = slice::from_raw_parts(mapping.offset(32), 32);
+            assert_eq!(&dst[.. 4], &[0xAA, 0xBB, 0xCC, 0xDD]);
+            assert_eq!(&dst[4 .. 20], &data[36 .. 52]);
+            assert_eq!(&dst[20 ..], &[12, 13, 100, 101, 102, 17, 18, 19, 60, 61, 62, 63][..]);
+            device.unmap_memory(&memory);
+        }
+    }
+
+    #[test]
+    fn test_query_results() {
+        let (_, mut gpu) = test_utils::open(&Instance);
+
+        let device = &gpu.device;
+        let queue = &mut gpu.queue_groups[0].queues[0];
+
+        unsafe {
+            let occlusion = device.create_query_pool(query::Type::Occlusion, 2).unwrap();
+            let timestamps = device.create_query_pool(query::Type::Timestamp, 2).unwrap();
+            let memory = device.allocate_memory(MemoryTypeId(1), 32).unwrap();
+            let mut results = device
+                .create_buffer(32, buffer::Usage::TRANSFER_DST)
+                .unwrap();
+            device.bind_buffer_memory(&memory, 0, &mut results).unwrap();
+            let mapping = device.map_memory(&memory, ..).unwrap();
+            ptr::write_bytes(mapping, 0xFF, 32);
+
+            submit(device, queue, |cmd_buffer| {
+                cmd_buffer.reset_query_pool(&occlusion, 0 .. 2);
+                cmd_buffer.reset_query_pool(&timestamps, 0 .. 2);
+                let query = |pool, id| query::Query { pool, id };
+                cmd_buffer.begin_query(query(&occlusion, 0), query::ControlFlags::empty());
+                cmd_buffer.end_query(query(&occlusion, 0));
+                cmd_buffer.write_timestamp(pso::PipelineStage::TOP_OF_PIPE, query(&timestamps, 0));
+                cmd_buffer
+                    .write_timestamp(pso::PipelineStage::BOTTOM_OF_PIPE, query(&timestamps, 1));
+                // The second occlusion query is reset but never ended.
+                cmd_buffer.copy_query_pool_results(
+                    &occlusion,
+                    0 .. 2,
+                    &results,
+                    0,
+                    16,
+                    query::ResultFlags::BITS_64 | query::ResultFlags::WITH_AVAILABILITY,
+                );
+            });
+
+            let words = slice::from_raw_parts(mapping as *const u64, 4);
+            assert_eq!(words, &[0, 1, !0, 0]);
+
+            // Unavailable queries are left untouched, unless partial results are requested.
+            let mut data = [0xFFu8; 8];
+            let flags = query::ResultFlags::empty();
+            assert!(!device
+                .get_query_pool_results(&occlusion, 0 .. 2, &mut data, 4, flags)
+                .unwrap());
+            assert_eq!(data, [0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF]);
+            let flags = query::ResultFlags::PARTIAL;
+            assert!(!device
+                .get_query_pool_results(&occlusion, 0 .. 2, &mut data, 4, flags)
+                .unwrap());
+            assert_eq!(data, [0; 8]);
+
+            let mut data = [0u8; 16];
+            let flags = query::ResultFlags::BITS_64 | query::ResultFlags::WAIT;
+            assert!(device
+                .get_query_pool_results(&timestamps, 0 .. 2, &mut data, 8, flags)
+                .unwrap());
+            let first = u64::from_ne_bytes([
+                data[0], data[1], data[2], data[3], data[4], data[5], data[6], data[7],
+            ]);
+            let second = u64::from_ne_bytes([
+                data[8], data[9], data[10], data[11], data[12], data[13], data[14], data[15],
+            ]);
+            assert!(first <= second);
+            device.unmap_memory(&memory);
+        }
+    }
+
+    #[test]
+    fn test_event_fence_state() {
+        let (_, mut gpu) = test_utils::open(&Instance);
+        let device = &gpu.device;
+        let queue = &mut gpu.queue_groups[0].queues[0];
+
+        unsafe {
+            let event = device.create_event().unwrap();
+            assert!(!device.get_event_status(&event).unwrap());
+            device.set_event(&event).unwrap();
+            assert!(device.get_event_status(&event).unwrap());
+            device.reset_event(&event).unwrap();
+            assert!(!device.get_event_status(&event).unwrap());
+
+            submit(device, queue, |cmd_buffer| {
+                cmd_buffer.set_event(&event, pso::PipelineStage::TRANSFER);
+            });
+            assert!(device.get_event_status(&event).unwrap());
+            submit(device, queue, |cmd_buffer| {
+                cmd_buffer.reset_event(&event, pso::PipelineStage::TRANSFER);
+            });
+            assert!(!device.get_event_status(&event).unwrap());
+
+            let fence = device.create_fence(true).unwrap();
+            assert!(device.get_fence_status(&fence).unwrap());
+            assert!(device.wait_for_fence(&fence, 0).unwrap());
+            device.reset_fence(&fence).unwrap();
+            assert!(!device.get_fence_status(&fence).unwrap());
+            assert!(!device.wait_for_fence(&fence, 1_000).unwrap());
+            // An empty submission still signals its fence.
+            queue.submit_without_semaphores(None::<&crate::CommandBuffer>, Some(&fence));
+            assert!(device.get_fence_status(&fence).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_buffer_image_round_trip() {
+        let (_, mut gpu) = test_utils::open(&Instance);
+        let device = &gpu.device;
+        let queue = &mut gpu.queue_groups[0].queues[0];
+        let data = (0 .. 64).collect::<Vec<u8>>();
+
+        unsafe {
+            // Host visible memory, big enough for two buffers and an image.
+            let memory = device.allocate_memory(MemoryTypeId(1), 256).unwrap();
+            let mut src = device
+                .create_buffer(64, buffer::Usage::TRANSFER_SRC)
+                .unwrap();
+            let mut dst = device
+                .create_buffer(64, buffer::Usage::TRANSFER_DST)
+                .unwrap();
+            let mut image = device
+                .create_image(
+                    image::Kind::D2(4, 4, 1, 1),
+                    1,
+                    format::Format::Rgba8Unorm,
+                    image::Tiling::Linear,
+                    image::Usage::TRANSFER_SRC | image::Usage::TRANSFER_DST,
+                    image::ViewCapabilities::empty(),
+                )
+                .unwrap();
+            device.bind_buffer_memory(&memory, 0, &mut src).unwrap();
+            device.bind_buffer_memory(&memory, 64, &mut dst).unwrap();
+            device.bind_image_memory(&memory, 128, &mut image).unwrap();
+
+            let mapping = device.map_memory(&memory, 0 .. 256).unwrap();
+            ptr::copy_nonoverlapping(data.as_ptr(), mapping, data.len());
+
+            // Copy the second half of the buffer into the image rows 1..3,
+            // then read the rows 1..2 back with a padded row pitch.
+            let mut pool = device
+                .create_command_pool(
+                    hal::queue::QueueFamilyId(0),
+                    hal::pool::CommandPoolCreateFlags::empty(),
+                )
+                .unwrap();
+            let mut cmd_buffer = pool.allocate_one(command::Level::Primary);
+            cmd_buffer.begin_primary(command::CommandBufferFlags::ONE_TIME_SUBMIT);
+            let layers = image::SubresourceLayers {
+                aspects: format::Aspects::COLOR,
+                level: 0,
+                layers: 0 .. 1,
+            };
+            cmd_buffer.copy_buffer_to_image(
+                &src,
+                &image,
+                image::Layout::TransferDstOptimal,
+                &[command::BufferImageCopy {
+                    buffer_offset: 32,
+                    buffer_width: 0,
+                    buffer_height: 0,
+                    image_layers: layers.clone(),
+                    image_offset: image::Offset { x: 0, y: 1, z: 0 },
+                    image_extent: image::Extent {
+                        width: 4,
+                        height: 2,
+                        depth: 1,
+                    },
+                }],
+            );
+            cmd_buffer.fill_buffer(&dst, .., 0xFFFF_FFFF);
+            cmd_buffer.copy_image_to_buffer(
+                &image,
+                image::Layout::TransferSrcOptimal,
+                &dst,
+                &[command::BufferImageCopy {
+                    buffer_offset: 0,
+                    buffer_width: 8,
+                    buffer_height: 0,
+                    image_layers: layers,
+                    image_offset: image::Offset { x: 1, y: 1, z: 0 },
+                    image_extent: image::Extent {
+                        width: 2,
+                        height: 2,
+                        depth: 1,
+                    },
+                }],
+            );
+            cmd_buffer.finish();
+
+            let fence = device.create_fence(false).unwrap();
+            queue.submit_without_semaphores(Some(&cmd_buffer), Some(&fence));
+            assert!(device.wait_for_fence(&fence, 0).unwrap());
+
+            let result = std::slice::from_raw_parts(mapping.offset(64), 64);
+            assert_eq!(&result[.. 8], &data[36 .. 44]);
+            assert_eq!(&result[8 .. 32], &[0xFF; 24][..]);
+            assert_eq!(&result[32 .. 40], &data[52 .. 60]);
+            assert_eq!(&result[40 ..], &[0xFF; 24][..]);
+
+            device.reset_fence(&fence).unwrap();
+            assert!(!device.get_fence_status(&fence).unwrap());
+            device.unmap_memory(&memory);
+        }
+    }
+
+    #[cfg(feature = "software")]
+    #[test]
+    fn test_draw_textured_quad() {
+        use hal::pso::DescriptorPool as _;
+        use hal::{pass, pso};
+
+        fn words(bytes: &[u8]) -> Vec<u32> {
+            bytes
+                .chunks(4)
+                .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+                .collect()
+        }
+
+        let (_, mut gpu) = test_utils::open(&Instance);
+        let device = &gpu.device;
+        let queue = &mut gpu.queue_groups[0].queues[0];
+        let format = format::Format::Rgba8Unorm;
+        let texels: [u8; 16] = [
+            255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 255, 255, 255, 255,
+        ];
+        // Positions and texture coordinates of two triangles covering the viewport.
+        let vertices: [f32; 24] = [
+            -1.0, 1.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, -1.0, 1.0, 0.0, -1.0, 1.0, 0.0, 1.0, 1.0,
+            -1.0, 1.0, 0.0, -1.0, -1.0, 0.0, 0.0,
+        ];
+
+        unsafe {
+            let memory = device.allocate_memory(MemoryTypeId(1), 256).unwrap();
+            let mapping = device.map_memory(&memory, ..).unwrap();
+            let create_image = |size: u32, offset: u64| {
+                let mut image = device
+                    .create_image(
+                        image::Kind::D2(size, size, 1, 1),
+                        1,
+                        format,
+                        image::Tiling::Linear,
+                        image::Usage::SAMPLED | image::Usage::COLOR_ATTACHMENT,
+                        image::ViewCapabilities::empty(),
+                    )
+                    .unwrap();
+                device
+                    .bind_image_memory(&memory, offset, &mut image)
+                    .unwrap();
+                let view = device
+                    .create_image_view(
+                        &image,
+                        image::ViewKind::D2,
+                        format,
+                        format::Swizzle::NO,
+                        image::SubresourceRange {
+                            aspects: format::Aspects::COLOR,
+                            levels: 0 .. 1,
+                            layers: 0 .. 1,
+                        },
+                    )
+                    .unwrap();
+                (image, view)
+            };
+            let (_texture, texture_view) = create_image(2, 0);
+            let (_target, target_view) = create_image(4, 16);
+            let mut vertex_buffer = device.create_buffer(96, buffer::Usage::VERTEX).unwrap();
+            device
+                .bind_buffer_memory(&memory, 96, &mut vertex_buffer)
+                .unwrap();
+            ptr::copy_nonoverlapping(texels.as_ptr(), mapping, 16);
+            ptr::copy_nonoverlapping(vertices.as_ptr() as *const u8, mapping.offset(96), 96);
+
+            let sampler = device
+                .create_sampler(image::SamplerInfo::new(
+                    image::Filter::Nearest,
+                    image::WrapMode::Clamp,
+                ))
+                .unwrap();
+            let bindings = [
+                pso::DescriptorType::SampledImage,
+                pso::DescriptorType::Sampler,
+            ];
+            let set_layout = device
+                .create_descriptor_set_layout(
+                    bindings.iter().enumerate().map(|(binding, &ty)| {
+                        pso::DescriptorSetLayoutBinding {
+                            binding: binding as u32,
+                            ty,
+                            count: 1,
+                            stage_flags: pso::ShaderStageFlags::FRAGMENT,
+                            immutable_samplers: false,
+                        }
+                    }),
+                    &[],
+                )
+                .unwrap();
+            let mut pool = device
+                .create_descriptor_pool(
+                    1,
+                    bindings
+                        .iter()
+                        .map(|&ty| pso::DescriptorRangeDesc { ty, count: 1 }),
+                    pso::DescriptorPoolCreateFlags::empty(),
+                )
+                .unwrap();
+            let set = pool.allocate_set(&set_layout).unwrap();
+            // The sampler spills over to the next binding.
+            device.write_descriptor_sets(Some(pso::DescriptorSetWrite {
+                set: &set,
+                binding: 0,
+                array_offset: 0,
+                descriptors: vec![
+                    pso::Descriptor::Image(&texture_view, image::Layout::General),
+                    pso::Descriptor::Sampler(&sampler),
+                ],
+            }));
+
+            let render_pass = device
+                .create_render_pass(
+                    Some(pass::Attachment {
+                        format: Some(format),
+                        samples: 1,
+                        ops: pass::AttachmentOps::new(
+                            pass::AttachmentLoadOp::Clear,
+                            pass::AttachmentStoreOp::Store,
+                        ),
+                        stencil_ops: pass::AttachmentOps::DONT_CARE,
+                        layouts: image::Layout::General .. image::Layout::General,
+                    }),
+                    Some(pass::SubpassDesc {
+                        colors: &[(0, image::Layout::General)],
+                        depth_stencil: None,
+                        inputs: &[],
+                        resolves: &[],
+                        preserves: &[],
+                    }),
+                    &[],
+                )
+                .unwrap();
+            let extent = image::Extent {
+                width: 4,
+                height: 4,
+                depth: 1,
+            };
+            let framebuffer = device
+                .create_framebuffer(&render_pass, Some(&target_view), extent)
+                .unwrap();
+            let layout = device
+                .create_pipeline_layout(Some(&set_layout), &[])
+                .unwrap();
+            let vs = device
+                .create_shader_module(&words(include_bytes!(
+                    "../../../../examples/quad/data/quad.vert.spv"
+                )))
+                .unwrap();
+            let fs = device
+                .create_shader_module(&words(include_bytes!(
+                    "../../../../examples/quad/data/quad.frag.spv"
+                )))
+                .unwrap();
+            let shaders = pso::GraphicsShaderSet {
+                vertex: pso::EntryPoint {
+                    entry: "main",
+                    module: &vs,
+                    specialization: hal::spec_const_list![1.0f32],
+                },
+                hull: None,
+                domain: None,
+                geometry: None,
+                fragment: Some(pso::EntryPoint {
+                    entry: "main",
+                    module: &fs,
+                    specialization: pso::Specialization::default(),
+                }),
+            };
+            let mut desc = pso::GraphicsPipelineDesc::new(
+                shaders,
+                hal::Primitive::TriangleList,
+                pso::Rasterizer::FILL,
+                &layout,
+                pass::Subpass {
+                    index: 0,
+                    main_pass: &render_pass,
+                },
+            );
+            desc.blender.targets.push(pso::ColorBlendDesc {
+                mask: pso::ColorMask::ALL,
+                blend: None,
+            });
+            desc.vertex_buffers.push(pso::VertexBufferDesc {
+                binding: 0,
+                stride: 16,
+                rate: pso::VertexInputRate::Vertex,
+            });
+            for &(location, offset) in &[(0, 0), (1, 8)] {
+                desc.attributes.push(pso::AttributeDesc {
+                    location,
+                    binding: 0,
+                    element: pso::Element {
+                        format: format::Format::Rg32Sfloat,
+                        offset,
+                    },
+                });
+            }
+            let pipeline = device.create_graphics_pipeline(&desc, None).unwrap();
+
+            let mut command_pool = device
+                .create_command_pool(
+                    hal::queue::QueueFamilyId(0),
+                    hal::pool::CommandPoolCreateFlags::empty(),
+                )
+                .unwrap();
+            let mut cmd_buffer = command_pool.allocate_one(command::Level::Primary);
+            let rect = pso::Rect {
+                x: 0,
+                y: 0,
+                w: 4,
+                h: 4,
+            };
+            cmd_buffer.begin_primary(command::CommandBufferFlags::ONE_TIME_SUBMIT);
+            cmd_buffer.begin_render_pass(
+                &render_pass,
+                &framebuffer,
+                rect,
+                Some(command::ClearValue {
+                    color: command::ClearColor { float32: [0.0; 4] },
+                }),
+                command::SubpassContents::Inline,
+            );
+            cmd_buffer.bind_graphics_pipeline(&pipeline);
+            cmd_buffer.bind_graphics_descriptor_sets(&layout, 0, Some(&set), &[]);
+            cmd_buffer.set_viewports(
+                0,
+                Some(pso::Viewport {
+                    rect,
+                    depth: 0.0 .. 1.0,
+                }),
+            );
+            cmd_buffer.set_scissors(0, Some(rect));
+            cmd_buffer.bind_vertex_buffers(0, Some((&vertex_buffer, 0)));
+            cmd_buffer.draw(0 .. 6, 0 .. 1);
+            cmd_buffer.end_render_pass();
+            cmd_buffer.finish();
+            queue.submit_without_semaphores(Some(&cmd_buffer), None);
+
+            // Every texel of the texture covers a quarter of the target.
+            let result = std::slice::from_raw_parts(mapping.offset(16), 64);
+            for (i, pixel) in result.chunks(4).enumerate() {
+                let (x, y) = (i % 4 / 2, i / 8);
+                assert_eq!(pixel, &texels[(y * 2 + x) * 4 .. (y * 2 + x + 1) * 4]);
+            }
+            device.unmap_memory(&memory);
+        }
+    }
+
+    #[test]
+    fn test_map_device_local() {
+        let (_, gpu) = test_utils::open(&Instance);
+        let memory_types = gpu.device.memory_types.clone();
+        assert!(!memory_types[0]
+            .properties
+            .contains(memory::Properties::CPU_VISIBLE));
+        unsafe {
+            let memory = gpu.device.allocate_memory(MemoryTypeId(0), 16).unwrap();
+            assert!(gpu.device.map_memory(&memory, ..).is_err());
+        }
+    }
+}
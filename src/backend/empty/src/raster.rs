@@ -0,0 +1,1155 @@
+//! Software rasterizer, drawing primitives with the shaders of a graphics pipeline.
+//!
+//! Primitives are clipped against the near and far planes, then rasterized one
+//! pixel at a time with edge functions, sampling at pixel centers and following
+//! the top-left fill convention. Multisampling is not supported, every pixel
+//! is a single sample.
+
+use hal::format::{texel, ChannelType};
+use hal::query::PipelineStatistic as Stat;
+use hal::{format, pso, IndexType, Primitive};
+
+use crate::native as n;
+use crate::queue::{Counters, Dynamic};
+use crate::shader::{self, builtin};
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+// Minimal `w` of the clipped vertices, avoiding divisions by zero.
+const W_EPSILON: f32 = 1.0e-6;
+
+/// Varying passed from a vertex shader output to a fragment shader input.
+#[derive(Debug)]
+struct Varying {
+    output: shader::Interface,
+    input: shader::Interface,
+    words: usize,
+}
+
+/// Graphics pipeline state, with the programs of every stage.
+#[derive(Debug)]
+pub struct Pipeline {
+    pub(crate) vertex: shader::Program,
+    pub(crate) fragment: Option<shader::Program>,
+    varyings: Vec<Varying>,
+    /// Flat interpolation of every varying word.
+    flat: Vec<bool>,
+    /// Screen-space interpolation of every varying word.
+    no_perspective: Vec<bool>,
+    pub(crate) rasterizer: pso::Rasterizer,
+    pub(crate) vertex_buffers: Vec<pso::VertexBufferDesc>,
+    pub(crate) attributes: Vec<pso::AttributeDesc>,
+    pub(crate) input_assembler: pso::InputAssemblerDesc,
+    pub(crate) blender: pso::BlendDesc,
+    pub(crate) depth_stencil: pso::DepthStencilDesc,
+    pub(crate) baked_states: pso::BakedStates,
+}
+
+impl Pipeline {
+    pub(crate) fn new(
+        desc: &pso::GraphicsPipelineDesc<crate::Backend>,
+        vertex: shader::Program,
+        fragment: Option<shader::Program>,
+    ) -> Result<Self, pso::CreationError> {
+        match desc.input_assembler.primitive {
+            Primitive::PointList
+            | Primitive::LineList
+            | Primitive::LineStrip
+            | Primitive::TriangleList
+            | Primitive::TriangleStrip => {}
+            _ => return Err(pso::CreationError::Other),
+        }
+
+        let mut varyings = Vec::new();
+        if let Some(ref fragment) = fragment {
+            for input in &fragment.inputs {
+                let output = vertex
+                    .outputs
+                    .iter()
+                    .find(|o| o.location == input.location && o.component == input.component);
+                if let Some(output) = output {
+                    let words = vertex
+                        .module
+                        .location_words(output.ty)
+                        .iter()
+                        .sum::<usize>()
+                        .min(fragment.module.location_words(input.ty).iter().sum());
+                    varyings.push(Varying {
+                        output: output.clone(),
+                        input: input.clone(),
+                        words,
+                    });
+                }
+            }
+        }
+        let mut flat = Vec::new();
+        let mut no_perspective = Vec::new();
+        for varying in &varyings {
+            let integer = match fragment {
+                Some(ref fs) => fs.module.is_integer(varying.input.ty),
+                None => false,
+            };
+            for _ in 0 .. varying.words {
+                flat.push(varying.input.flat || integer);
+                no_perspective.push(varying.input.no_perspective);
+            }
+        }
+
+        Ok(Pipeline {
+            vertex,
+            fragment,
+            varyings,
+            flat,
+            no_perspective,
+            rasterizer: desc.rasterizer,
+            vertex_buffers: desc.vertex_buffers.clone(),
+            attributes: desc.attributes.clone(),
+            input_assembler: desc.input_assembler.clone(),
+            blender: desc.blender.clone(),
+            depth_stencil: desc.depth_stencil,
+            baked_states: desc.baked_states.clone(),
+        })
+    }
+}
+
+/// Resources and state used by a draw call.
+pub(crate) struct Draw<'a> {
+    pub(crate) pipeline: &'a Pipeline,
+    pub(crate) resources: &'a shader::Resources,
+    pub(crate) push_constants: &'a [u8],
+    /// Vertex buffers by binding, with the binding offsets applied.
+    pub(crate) vertex_buffers: &'a [Option<n::Binding>],
+    pub(crate) index_buffer: Option<&'a (n::Binding, IndexType)>,
+    pub(crate) dynamic: &'a Dynamic,
+    /// Color attachments of the subpass, `None` for unused attachments.
+    pub(crate) colors: Vec<Option<&'a n::ImageView>>,
+    pub(crate) depth_stencil: Option<&'a n::ImageView>,
+    pub(crate) area: pso::Rect,
+}
+
+/// Vertex processed by the vertex shader, in clip coordinates.
+#[derive(Clone, Debug)]
+struct Vertex {
+    position: [f32; 4],
+    point_size: f32,
+    varyings: Vec<u32>,
+}
+
+/// Vertex in framebuffer coordinates.
+#[derive(Clone, Debug)]
+struct ScreenVertex {
+    x: f32,
+    y: f32,
+    z: f32,
+    inv_w: f32,
+    point_size: f32,
+    varyings: Vec<u32>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Assembled {
+    Point(usize),
+    Line(usize, usize),
+    Triangle(usize, usize, usize),
+}
+
+/// Fragment shader inputs and the values of the fixed function stages.
+struct Fragment<'v> {
+    x: i32,
+    y: i32,
+    z: f32,
+    inv_w: f32,
+    front_facing: bool,
+    point_coord: [f32; 2],
+    /// Vertices of the primitive with their linear and perspective-correct weights.
+    vertices: &'v [(&'v ScreenVertex, f32, f32)],
+    /// Vertex providing the flat varyings.
+    provoking: &'v ScreenVertex,
+}
+
+// Returns the per-attachment parameters used to resolve the state of a draw.
+fn sided(state: pso::State<pso::Sided<u32>>, dynamic: pso::Sided<u32>) -> pso::Sided<u32> {
+    match state {
+        pso::State::Static(value) => value,
+        pso::State::Dynamic => dynamic,
+    }
+}
+
+// Applies a stencil operation to a stored value.
+fn stencil_op(op: pso::StencilOp, value: u32, reference: u32, max: u32) -> u32 {
+    match op {
+        pso::StencilOp::Keep => value,
+        pso::StencilOp::Zero => 0,
+        pso::StencilOp::Replace => reference,
+        pso::StencilOp::IncrementClamp => (value + 1).min(max),
+        pso::StencilOp::DecrementClamp => value.saturating_sub(1),
+        pso::StencilOp::Invert => !value & max,
+        pso::StencilOp::IncrementWrap => (value + 1) & max,
+        pso::StencilOp::DecrementWrap => value.wrapping_sub(1) & max,
+    }
+}
+
+// Returns the minimal resolvable difference of a depth format, used by the depth bias.
+fn depth_resolution(format: format::Format) -> f32 {
+    match format {
+        format::Format::D16Unorm | format::Format::D16UnormS8Uint => 1.0 / 65535.0,
+        format::Format::D32Sfloat | format::Format::D32SfloatS8Uint => 2f32.powi(-23),
+        _ => 1.0 / 16_777_215.0,
+    }
+}
+
+fn lerp_vertex(a: &Vertex, b: &Vertex, t: f32) -> Vertex {
+    let mut position = [0.0; 4];
+    for (i, p) in position.iter_mut().enumerate() {
+        *p = a.position[i] + (b.position[i] - a.position[i]) * t;
+    }
+    Vertex {
+        position,
+        point_size: a.point_size,
+        // Flat words are interpolated too, but they are read from the provoking vertex.
+        varyings: a
+            .varyings
+            .iter()
+            .zip(&b.varyings)
+            .map(|(&a, &b)| {
+                let (a, b) = (f32::from_bits(a), f32::from_bits(b));
+                (a + (b - a) * t).to_bits()
+            })
+            .collect(),
+    }
+}
+
+impl Draw<'_> {
+    fn viewport(&self) -> pso::Viewport {
+        self.pipeline
+            .baked_states
+            .viewport
+            .clone()
+            .or_else(|| self.dynamic.viewport.clone())
+            .expect("No viewport set for the draw")
+    }
+
+    fn clip_planes(&self) -> Vec<fn(&[f32; 4]) -> f32> {
+        let mut planes: Vec<fn(&[f32; 4]) -> f32> = vec![|p| p[3] - W_EPSILON];
+        if !self.pipeline.rasterizer.depth_clamping {
+            planes.push(|p| p[2]);
+            planes.push(|p| p[3] - p[2]);
+        }
+        planes
+    }
+
+    /// Draws vertices of the vertex buffers, or of the index buffer if `base_vertex` is set.
+    pub(crate) unsafe fn draw(
+        &self,
+        vertices: Range<u32>,
+        base_vertex: Option<i32>,
+        instances: Range<u32>,
+        counters: &mut Counters,
+    ) {
+        let pipeline = self.pipeline;
+        let restart = match pipeline.input_assembler.primitive_restart {
+            pso::PrimitiveRestart::Disabled => None,
+            pso::PrimitiveRestart::U16 => Some(0xFFFF),
+            pso::PrimitiveRestart::U32 => Some(0xFFFF_FFFF),
+        };
+        // Vertex indices, with `None` restarting the strips.
+        let indices = match base_vertex {
+            Some(base_vertex) => {
+                let &(ref binding, index_type) = self.index_buffer.expect("No index buffer bound");
+                vertices
+                    .map(|i| {
+                        let index = match index_type {
+                            IndexType::U16 => {
+                                (binding.ptr(i as u64 * 2) as *const u16).read_unaligned() as u32
+                            }
+                            IndexType::U32 => {
+                                (binding.ptr(i as u64 * 4) as *const u32).read_unaligned()
+                            }
+                        };
+                        if Some(index) == restart {
+                            None
+                        } else {
+                            Some((index as i32 + base_vertex) as u32)
+                        }
+                    })
+                    .collect::<Vec<_>>()
+            }
+            None => vertices.map(Some).collect(),
+        };
+
+        let mut vs = shader::Invocation::new(&pipeline.vertex);
+        let mut fs = pipeline.fragment.as_ref().map(shader::Invocation::new);
+        let first_instance = instances.start;
+        for instance in instances {
+            // Vertices are shaded once per index, then assembled.
+            let mut cache = HashMap::new();
+            let mut shaded = Vec::new();
+            let mut slots = Vec::with_capacity(indices.len());
+            for index in &indices {
+                slots.push(index.map(|index| {
+                    *cache.entry(index).or_insert_with(|| {
+                        shaded.push(self.shade_vertex(&mut vs, index, instance, first_instance));
+                        shaded.len() - 1
+                    })
+                }));
+            }
+            counters.add(
+                Stat::INPUT_ASSEMBLY_VERTICES,
+                slots.iter().filter(|s| s.is_some()).count() as u64,
+            );
+            counters.add(Stat::VERTEX_SHADER_INVOCATIONS, shaded.len() as u64);
+
+            let primitives = self.assemble(&slots);
+            counters.add(Stat::INPUT_ASSEMBLY_PRIMITIVES, primitives.len() as u64);
+            counters.add(Stat::CLIPPING_INVOCATIONS, primitives.len() as u64);
+            for primitive in primitives {
+                self.rasterize(primitive, &shaded, &mut fs, counters);
+            }
+        }
+    }
+
+    unsafe fn shade_vertex(
+        &self,
+        vs: &mut shader::Invocation,
+        index: u32,
+        instance: u32,
+        first_instance: u32,
+    ) -> Vertex {
+        let program = &self.pipeline.vertex;
+        vs.reset(program);
+        vs.set_builtin(program, builtin::VERTEX_INDEX, &[index]);
+        vs.set_builtin(program, builtin::INSTANCE_INDEX, &[instance]);
+
+        for input in &program.inputs {
+            let mut offset = 0;
+            let location_words = program.module.location_words(input.ty);
+            for (i, &words) in location_words.iter().enumerate() {
+                let location = input.location + i as u32;
+                let attribute = match self
+                    .pipeline
+                    .attributes
+                    .iter()
+                    .find(|a| a.location == location)
+                {
+                    Some(attribute) => attribute,
+                    None => continue,
+                };
+                let desc = self
+                    .pipeline
+                    .vertex_buffers
+                    .iter()
+                    .find(|b| b.binding == attribute.binding)
+                    .expect("Missing vertex buffer description");
+                let element = match desc.rate {
+                    pso::VertexInputRate::Vertex => index,
+                    pso::VertexInputRate::Instance(0) => first_instance,
+                    pso::VertexInputRate::Instance(divisor) => {
+                        first_instance + (instance - first_instance) / divisor as u32
+                    }
+                };
+                let binding = self
+                    .vertex_buffers
+                    .get(attribute.binding as usize)
+                    .and_then(Option::as_ref)
+                    .expect("Missing vertex buffer");
+                let format = attribute.element.format;
+                let size = format.surface_desc().bits as usize / 8;
+                let start = element as u64 * desc.stride as u64 + attribute.element.offset as u64;
+                // Fetches past the end of the memory read zeros, as with robust buffer access.
+                let texel = if start + size as u64 <= binding.remaining() {
+                    let ptr = binding.ptr(start);
+                    texel::decode(format, std::slice::from_raw_parts(ptr, size))
+                } else {
+                    [0; 4]
+                };
+                let data = vs.interface_mut(program, input);
+                let count = words.min(4);
+                data[offset .. offset + count].copy_from_slice(&texel[.. count]);
+                offset += words;
+            }
+        }
+
+        let mut context = shader::Context {
+            resources: self.resources,
+            push_constants: self.push_constants,
+            workgroup: &mut [],
+        };
+        vs.run(program, &mut context);
+
+        let mut position = [0.0; 4];
+        if let Some(words) = vs.builtin(program, builtin::POSITION) {
+            for (p, &w) in position.iter_mut().zip(words) {
+                *p = f32::from_bits(w);
+            }
+        }
+        let point_size = vs
+            .builtin(program, builtin::POINT_SIZE)
+            .map_or(1.0, |words| f32::from_bits(words[0]));
+        let mut varyings = Vec::with_capacity(self.pipeline.flat.len());
+        for varying in &self.pipeline.varyings {
+            varyings.extend_from_slice(&vs.interface(program, &varying.output)[.. varying.words]);
+        }
+        Vertex {
+            position,
+            point_size,
+            varyings,
+        }
+    }
+
+    // Assembles the primitives, with the provoking vertex first.
+    fn assemble(&self, slots: &[Option<usize>]) -> Vec<Assembled> {
+        let mut primitives = Vec::new();
+        for strip in slots.split(Option::is_none) {
+            let v = strip.iter().map(|slot| slot.unwrap()).collect::<Vec<_>>();
+            match self.pipeline.input_assembler.primitive {
+                Primitive::PointList => primitives.extend(v.iter().map(|&a| Assembled::Point(a))),
+                Primitive::LineList => {
+                    primitives.extend(v.chunks_exact(2).map(|l| Assembled::Line(l[0], l[1])))
+                }
+                Primitive::LineStrip => {
+                    primitives.extend(v.windows(2).map(|l| Assembled::Line(l[0], l[1])))
+                }
+                Primitive::TriangleList => primitives.extend(
+                    v.chunks_exact(3)
+                        .map(|t| Assembled::Triangle(t[0], t[1], t[2])),
+                ),
+                Primitive::TriangleStrip => {
+                    // Odd triangles swap their last vertices to keep the winding order.
+                    primitives.extend(v.windows(3).enumerate().map(|(i, t)| {
+                        if i % 2 == 0 {
+                            Assembled::Triangle(t[0], t[1], t[2])
+                        } else {
+                            Assembled::Triangle(t[0], t[2], t[1])
+                        }
+                    }))
+                }
+                _ => unreachable!(),
+            }
+        }
+        primitives
+    }
+
+    // Returns the pixels that can be written, as a range on each axis.
+    fn bounds(&self) -> (Range<i32>, Range<i32>) {
+        let scissor = self
+            .pipeline
+            .baked_states
+            .scissor
+            .or(self.dynamic.scissor)
+            .unwrap_or(self.area);
+        let mut x = (scissor.x.max(self.area.x) as i32)
+            .. (scissor.x as i32 + scissor.w as i32).min(self.area.x as i32 + self.area.w as i32);
+        let mut y = (scissor.y.max(self.area.y) as i32)
+            .. (scissor.y as i32 + scissor.h as i32).min(self.area.y as i32 + self.area.h as i32);
+        let views = self
+            .colors
+            .iter()
+            .filter_map(|v| *v)
+            .chain(self.depth_stencil);
+        for view in views {
+            let extent = view.level_extent(0);
+            x.end = x.end.min(extent.width as i32);
+            y.end = y.end.min(extent.height as i32);
+        }
+        (x.start.max(0) .. x.end, y.start.max(0) .. y.end)
+    }
+
+    unsafe fn rasterize(
+        &self,
+        primitive: Assembled,
+        vertices: &[Vertex],
+        fs: &mut Option<shader::Invocation>,
+        counters: &mut Counters,
+    ) {
+        let planes = self.clip_planes();
+        let viewport = self.viewport();
+        let to_screen = |v: &Vertex| {
+            let [x, y, z, w] = v.position;
+            let rect = viewport.rect;
+            let (hw, hh) = (rect.w as f32 * 0.5, rect.h as f32 * 0.5);
+            let depth = &viewport.depth;
+            ScreenVertex {
+                x: rect.x as f32 + hw + x / w * hw,
+                y: rect.y as f32 + hh + y / w * hh,
+                z: depth.start + z / w * (depth.end - depth.start),
+                inv_w: 1.0 / w,
+                point_size: v.point_size,
+                varyings: v.varyings.clone(),
+            }
+        };
+        let mut raster = Raster {
+            draw: self,
+            bounds: self.bounds(),
+            fs,
+            counters,
+        };
+
+        match primitive {
+            Assembled::Point(a) => {
+                let vertex = &vertices[a];
+                if planes.iter().any(|plane| plane(&vertex.position) < 0.0) {
+                    return;
+                }
+                raster.counters.add(Stat::CLIPPING_PRIMITIVES, 1);
+                raster.point(&to_screen(vertex));
+            }
+            Assembled::Line(a, b) => {
+                let (mut a, mut b) = (vertices[a].clone(), vertices[b].clone());
+                let provoking = to_screen(&a);
+                for plane in &planes {
+                    let (da, db) = (plane(&a.position), plane(&b.position));
+                    if da < 0.0 && db < 0.0 {
+                        return;
+                    }
+                    if da < 0.0 {
+                        a = lerp_vertex(&a, &b, da / (da - db));
+                    } else if db < 0.0 {
+                        b = lerp_vertex(&a, &b, da / (da - db));
+                    }
+                }
+                raster.counters.add(Stat::CLIPPING_PRIMITIVES, 1);
+                raster.line(&to_screen(&a), &to_screen(&b), &provoking, true);
+            }
+            Assembled::Triangle(a, b, c) => {
+                let provoking = to_screen(&vertices[a]);
+                let mut polygon = vec![
+                    vertices[a].clone(),
+                    vertices[b].clone(),
+                    vertices[c].clone(),
+                ];
+                for plane in &planes {
+                    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+                    for (i, current) in polygon.iter().enumerate() {
+                        let next = &polygon[(i + 1) % polygon.len()];
+                        let (dc, dn) = (plane(&current.position), plane(&next.position));
+                        if dc >= 0.0 {
+                            clipped.push(current.clone());
+                        }
+                        if (dc >= 0.0) != (dn >= 0.0) {
+                            clipped.push(lerp_vertex(current, next, dc / (dc - dn)));
+                        }
+                    }
+                    polygon = clipped;
+                    if polygon.len() < 3 {
+                        return;
+                    }
+                }
+
+                let screen = polygon.iter().map(to_screen).collect::<Vec<_>>();
+                // The facing is given by the signed area of the polygon in framebuffer coordinates.
+                let area = -0.5
+                    * (0 .. screen.len())
+                        .map(|i| {
+                            let (p, q) = (&screen[i], &screen[(i + 1) % screen.len()]);
+                            p.x * q.y - q.x * p.y
+                        })
+                        .sum::<f32>();
+                let front_facing = match self.pipeline.rasterizer.front_face {
+                    pso::FrontFace::CounterClockwise => area > 0.0,
+                    pso::FrontFace::Clockwise => area < 0.0,
+                };
+                let face = if front_facing {
+                    pso::Face::FRONT
+                } else {
+                    pso::Face::BACK
+                };
+                if self.pipeline.rasterizer.cull_face.contains(face) {
+                    return;
+                }
+
+                raster.counters.add(Stat::CLIPPING_PRIMITIVES, 1);
+                match self.pipeline.rasterizer.polygon_mode {
+                    pso::PolygonMode::Fill => {
+                        for i in 1 .. screen.len() - 1 {
+                            raster.triangle(
+                                [&screen[0], &screen[i], &screen[i + 1]],
+                                &provoking,
+                                front_facing,
+                            );
+                        }
+                    }
+                    pso::PolygonMode::Line(_) => {
+                        for i in 0 .. screen.len() {
+                            raster.line(
+                                &screen[i],
+                                &screen[(i + 1) % screen.len()],
+                                &provoking,
+                                front_facing,
+                            );
+                        }
+                    }
+                    pso::PolygonMode::Point => {
+                        for vertex in &screen {
+                            raster.point(vertex);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Rasterization state of a single primitive.
+struct Raster<'a, 'd> {
+    draw: &'a Draw<'d>,
+    bounds: (Range<i32>, Range<i32>),
+    fs: &'a mut Option<shader::Invocation>,
+    counters: &'a mut Counters,
+}
+
+impl Raster<'_, '_> {
+    unsafe fn point(&mut self, vertex: &ScreenVertex) {
+        let half = vertex.point_size.max(1.0) * 0.5;
+        let (left, top) = (vertex.x - half, vertex.y - half);
+        let x0 = ((left - 0.5).ceil() as i32).max(self.bounds.0.start);
+        let x1 = (((vertex.x + half) - 0.5).ceil() as i32).min(self.bounds.0.end);
+        let y0 = ((top - 0.5).ceil() as i32).max(self.bounds.1.start);
+        let y1 = (((vertex.y + half) - 0.5).ceil() as i32).min(self.bounds.1.end);
+        let weights = [(vertex, 1.0, 1.0)];
+        for y in y0 .. y1 {
+            for x in x0 .. x1 {
+                let point_coord = [
+                    (x as f32 + 0.5 - left) / (2.0 * half),
+                    (y as f32 + 0.5 - top) / (2.0 * half),
+                ];
+                self.fragment(Fragment {
+                    x,
+                    y,
+                    z: vertex.z,
+                    inv_w: vertex.inv_w,
+                    front_facing: true,
+                    point_coord,
+                    vertices: &weights,
+                    provoking: vertex,
+                });
+            }
+        }
+    }
+
+    unsafe fn line(
+        &mut self,
+        a: &ScreenVertex,
+        b: &ScreenVertex,
+        provoking: &ScreenVertex,
+        front_facing: bool,
+    ) {
+        let (dx, dy) = (b.x - a.x, b.y - a.y);
+        if dx == 0.0 && dy == 0.0 {
+            return;
+        }
+        // Every pixel center on the major axis is covered once.
+        let x_major = dx.abs() >= dy.abs();
+        let (start, end, delta) = if x_major {
+            (a.x.min(b.x), a.x.max(b.x), dx)
+        } else {
+            (a.y.min(b.y), a.y.max(b.y), dy)
+        };
+        let origin = if x_major { a.x } else { a.y };
+        for major in ((start - 0.5).ceil() as i32) .. ((end - 0.5).ceil() as i32) {
+            let t = (major as f32 + 0.5 - origin) / delta;
+            let (x, y) = if x_major {
+                (major, (a.y + t * dy).floor() as i32)
+            } else {
+                ((a.x + t * dx).floor() as i32, major)
+            };
+            if !self.bounds.0.contains(&x) || !self.bounds.1.contains(&y) {
+                continue;
+            }
+            let (wa, wb) = ((1.0 - t) * a.inv_w, t * b.inv_w);
+            let inv_w = wa + wb;
+            let weights = [(a, 1.0 - t, wa / inv_w), (b, t, wb / inv_w)];
+            self.fragment(Fragment {
+                x,
+                y,
+                z: a.z + t * (b.z - a.z),
+                inv_w,
+                front_facing,
+                point_coord: [0.0; 2],
+                vertices: &weights,
+                provoking,
+            });
+        }
+    }
+
+    unsafe fn triangle(
+        &mut self,
+        mut v: [&ScreenVertex; 3],
+        provoking: &ScreenVertex,
+        front_facing: bool,
+    ) {
+        let edge = |a: &ScreenVertex, b: &ScreenVertex, x: f64, y: f64| {
+            (b.x as f64 - a.x as f64) * (y - a.y as f64)
+                - (b.y as f64 - a.y as f64) * (x - a.x as f64)
+        };
+        let mut area = edge(v[0], v[1], v[2].x as f64, v[2].y as f64);
+        if area == 0.0 {
+            return;
+        }
+        if area < 0.0 {
+            v.swap(1, 2);
+            area = -area;
+        }
+        // With this orientation, pixels exactly on an edge are covered by top and left edges only.
+        let top_left = |a: &ScreenVertex, b: &ScreenVertex| {
+            let (dx, dy) = (b.x - a.x, b.y - a.y);
+            (dy == 0.0 && dx > 0.0) || dy < 0.0
+        };
+        let edges = [(v[1], v[2]), (v[2], v[0]), (v[0], v[1])];
+        let inclusive = [
+            top_left(v[1], v[2]),
+            top_left(v[2], v[0]),
+            top_left(v[0], v[1]),
+        ];
+
+        let bias = self.depth_bias(&v);
+        let min_x = v.iter().map(|v| v.x).fold(f32::INFINITY, f32::min);
+        let max_x = v.iter().map(|v| v.x).fold(f32::NEG_INFINITY, f32::max);
+        let min_y = v.iter().map(|v| v.y).fold(f32::INFINITY, f32::min);
+        let max_y = v.iter().map(|v| v.y).fold(f32::NEG_INFINITY, f32::max);
+        let x0 = ((min_x - 0.5).floor() as i32).max(self.bounds.0.start);
+        let x1 = ((max_x + 0.5).ceil() as i32).min(self.bounds.0.end);
+        let y0 = ((min_y - 0.5).floor() as i32).max(self.bounds.1.start);
+        let y1 = ((max_y + 0.5).ceil() as i32).min(self.bounds.1.end);
+
+        for y in y0 .. y1 {
+            for x in x0 .. x1 {
+                let (px, py) = (x as f64 + 0.5, y as f64 + 0.5);
+                let mut b = [0.0f64; 3];
+                let mut inside = true;
+                for i in 0 .. 3 {
+                    let e = edge(edges[i].0, edges[i].1, px, py);
+                    inside &= e > 0.0 || (e == 0.0 && inclusive[i]);
+                    b[i] = e / area;
+                }
+                if !inside {
+                    continue;
+                }
+                let (b0, b1, b2) = (b[0] as f32, b[1] as f32, b[2] as f32);
+                let inv_w = b0 * v[0].inv_w + b1 * v[1].inv_w + b2 * v[2].inv_w;
+                let weights = [
+                    (v[0], b0, b0 * v[0].inv_w / inv_w),
+                    (v[1], b1, b1 * v[1].inv_w / inv_w),
+                    (v[2], b2, b2 * v[2].inv_w / inv_w),
+                ];
+                self.fragment(Fragment {
+                    x,
+                    y,
+                    z: b0 * v[0].z + b1 * v[1].z + b2 * v[2].z + bias,
+                    inv_w,
+                    front_facing,
+                    point_coord: [0.0; 2],
+                    vertices: &weights,
+                    provoking,
+                });
+            }
+        }
+    }
+
+    // Returns the depth offset of a triangle.
+    fn depth_bias(&self, v: &[&ScreenVertex; 3]) -> f32 {
+        let pipeline = self.draw.pipeline;
+        let bias = match pipeline.rasterizer.depth_bias {
+            Some(pso::State::Static(bias)) => bias,
+            Some(pso::State::Dynamic) => self.draw.dynamic.depth_bias,
+            None => return 0.0,
+        };
+        let resolution = self
+            .draw
+            .depth_stencil
+            .map_or(1.0 / 16_777_215.0, |view| depth_resolution(view.format));
+        // The slope is given by the plane equation of the depth.
+        let (x1, y1, z1) = (v[1].x - v[0].x, v[1].y - v[0].y, v[1].z - v[0].z);
+        let (x2, y2, z2) = (v[2].x - v[0].x, v[2].y - v[0].y, v[2].z - v[0].z);
+        let det = x1 * y2 - x2 * y1;
+        let slope = if det == 0.0 {
+            0.0
+        } else {
+            let dzdx = (z1 * y2 - z2 * y1) / det;
+            let dzdy = (x1 * z2 - x2 * z1) / det;
+            dzdx.abs().max(dzdy.abs())
+        };
+        let offset = bias.slope_factor * slope + bias.const_factor * resolution;
+        if bias.clamp > 0.0 {
+            offset.min(bias.clamp)
+        } else if bias.clamp < 0.0 {
+            offset.max(bias.clamp)
+        } else {
+            offset
+        }
+    }
+
+    // Runs the depth bounds, stencil and depth tests, updating the attachment.
+    unsafe fn depth_stencil_tests(&mut self, fragment: &Fragment, z: f32) -> bool {
+        let view = match self.draw.depth_stencil {
+            Some(view) => view,
+            None => return true,
+        };
+        let pipeline = self.draw.pipeline;
+        let desc = &pipeline.depth_stencil;
+        let dynamic = self.draw.dynamic;
+        let aspects = view.format.surface_desc().aspects;
+        let data = view.texel(
+            0,
+            0,
+            hal::image::Offset {
+                x: fragment.x,
+                y: fragment.y,
+                z: 0,
+            },
+        );
+        let has_depth = aspects.contains(format::Aspects::DEPTH);
+        let stored = if has_depth {
+            texel::decode_depth(view.format, data)
+        } else {
+            0.0
+        };
+
+        if desc.depth_bounds && has_depth {
+            let bounds = pipeline
+                .baked_states
+                .depth_bounds
+                .clone()
+                .unwrap_or_else(|| dynamic.depth_bounds.clone());
+            if stored < bounds.start || stored > bounds.end {
+                return false;
+            }
+        }
+
+        let depth_passed = match desc.depth {
+            Some(ref test) if has_depth => shader::compare(test.fun, z, stored),
+            _ => true,
+        };
+
+        let mut passed = depth_passed;
+        if let (Some(ref stencil), true) =
+            (desc.stencil, aspects.contains(format::Aspects::STENCIL))
+        {
+            let pick = |sided: pso::Sided<u32>| {
+                if fragment.front_facing {
+                    sided.front
+                } else {
+                    sided.back
+                }
+            };
+            let face = if fragment.front_facing {
+                stencil.faces.front
+            } else {
+                stencil.faces.back
+            };
+            let reference = pick(sided(stencil.reference_values, dynamic.stencil_reference));
+            let read_mask = pick(sided(stencil.read_masks, dynamic.stencil_read_mask));
+            let write_mask = pick(sided(stencil.write_masks, dynamic.stencil_write_mask));
+            let value = texel::decode_stencil(view.format, data);
+            let stencil_passed =
+                shader::compare(face.fun, reference & read_mask, value & read_mask);
+            let op = if !stencil_passed {
+                face.op_fail
+            } else if !depth_passed {
+                face.op_depth_fail
+            } else {
+                face.op_pass
+            };
+            let new = stencil_op(op, value, reference, 0xFF);
+            texel::encode_stencil(
+                view.format,
+                data,
+                (value & !write_mask) | (new & write_mask & 0xFF),
+            );
+            passed &= stencil_passed;
+        }
+
+        if passed {
+            if let Some(ref test) = desc.depth {
+                if test.write && has_depth {
+                    texel::encode_depth(view.format, data, z);
+                }
+            }
+        }
+        passed
+    }
+
+    unsafe fn fragment(&mut self, fragment: Fragment) {
+        let pipeline = self.draw.pipeline;
+        let viewport_depth = self.draw.viewport().depth;
+        let (depth_min, depth_max) = (
+            viewport_depth.start.min(viewport_depth.end),
+            viewport_depth.start.max(viewport_depth.end),
+        );
+        let clamp = |z: f32| {
+            if pipeline.rasterizer.depth_clamping {
+                z.max(depth_min).min(depth_max)
+            } else {
+                z
+            }
+        };
+        let early = match pipeline.fragment {
+            Some(ref fs) => fs.early_fragment_tests,
+            None => true,
+        };
+        let mut z = clamp(fragment.z);
+        if early && !self.depth_stencil_tests(&fragment, z) {
+            return;
+        }
+
+        let mut outputs = Vec::new();
+        if let (Some(program), Some(fs)) = (pipeline.fragment.as_ref(), self.fs.as_mut()) {
+            self.counters.add(Stat::FRAGMENT_SHADER_INVOCATIONS, 1);
+            fs.reset(program);
+            fs.fragment = [fragment.x, fragment.y];
+            let frag_coord = [
+                (fragment.x as f32 + 0.5).to_bits(),
+                (fragment.y as f32 + 0.5).to_bits(),
+                z.to_bits(),
+                fragment.inv_w.to_bits(),
+            ];
+            fs.set_builtin(program, builtin::FRAG_COORD, &frag_coord);
+            fs.set_builtin(
+                program,
+                builtin::FRONT_FACING,
+                &[fragment.front_facing as u32],
+            );
+            fs.set_builtin(
+                program,
+                builtin::POINT_COORD,
+                &[
+                    fragment.point_coord[0].to_bits(),
+                    fragment.point_coord[1].to_bits(),
+                ],
+            );
+
+            let mut word = 0;
+            for varying in &pipeline.varyings {
+                let data = fs.interface_mut(program, &varying.input);
+                for value in data.iter_mut().take(varying.words) {
+                    *value = if pipeline.flat[word] {
+                        fragment.provoking.varyings[word]
+                    } else {
+                        let perspective = !pipeline.no_perspective[word];
+                        fragment
+                            .vertices
+                            .iter()
+                            .map(|&(vertex, linear, correct)| {
+                                let weight = if perspective { correct } else { linear };
+                                f32::from_bits(vertex.varyings[word]) * weight
+                            })
+                            .sum::<f32>()
+                            .to_bits()
+                    };
+                    word += 1;
+                }
+            }
+
+            let mut context = shader::Context {
+                resources: self.draw.resources,
+                push_constants: self.draw.push_constants,
+                workgroup: &mut [],
+            };
+            if fs.run(program, &mut context) == shader::Status::Killed {
+                return;
+            }
+            if let Some(depth) = fs.builtin(program, builtin::FRAG_DEPTH) {
+                z = clamp(f32::from_bits(depth[0]));
+            }
+            // Color outputs by location, with the second source of dual-source blending.
+            let output = |location: usize, index: u32| {
+                program
+                    .outputs
+                    .iter()
+                    .find(|o| o.location == location as u32 && o.index == index)
+                    .map(|o| {
+                        let mut texel = [0, 0, 0, 1f32.to_bits()];
+                        for (t, &w) in texel.iter_mut().zip(fs.interface(program, o)) {
+                            *t = w;
+                        }
+                        texel
+                    })
+            };
+            outputs = (0 .. self.draw.colors.len())
+                .filter_map(|location| {
+                    output(location, 0).map(|color| (location, color, output(location, 1)))
+                })
+                .collect();
+        }
+
+        if !early && !self.depth_stencil_tests(&fragment, z) {
+            return;
+        }
+        self.counters.samples += 1;
+
+        for (location, color, color1) in outputs {
+            if let Some(view) = self.draw.colors[location] {
+                self.write_color(location, view, fragment.x, fragment.y, color, color1);
+            }
+        }
+    }
+
+    unsafe fn write_color(
+        &self,
+        location: usize,
+        view: &n::ImageView,
+        x: i32,
+        y: i32,
+        color: texel::Texel,
+        color1: Option<texel::Texel>,
+    ) {
+        let pipeline = self.draw.pipeline;
+        let target = pipeline
+            .blender
+            .targets
+            .get(location)
+            .cloned()
+            .unwrap_or(pso::ColorBlendDesc::EMPTY);
+        let data = view.texel(0, 0, hal::image::Offset { x, y, z: 0 });
+        let dst = texel::decode(view.format, data);
+
+        let mut result = if !texel::is_float(view.format) {
+            // Integer attachments are never blended, but support logical operations.
+            match pipeline.blender.logic_op {
+                Some(ref op) => {
+                    let mut result = [0; 4];
+                    for i in 0 .. 4 {
+                        result[i] = logic_op(op, color[i], dst[i]);
+                    }
+                    result
+                }
+                None => color,
+            }
+        } else {
+            let (low, high) = match view.format.base_format().1 {
+                ChannelType::Unorm | ChannelType::Srgb => (0.0, 1.0),
+                ChannelType::Snorm => (-1.0, 1.0),
+                _ => (f32::NEG_INFINITY, f32::INFINITY),
+            };
+            let floats = |texel: texel::Texel| {
+                let mut values = [0.0f32; 4];
+                for i in 0 .. 4 {
+                    values[i] = f32::from_bits(texel[i]).max(low).min(high);
+                }
+                values
+            };
+            let src = floats(color);
+            let src1 = floats(color1.unwrap_or([0; 4]));
+            let dst = floats(dst);
+            let constants = pipeline
+                .baked_states
+                .blend_color
+                .unwrap_or(self.draw.dynamic.blend_constants);
+            let values = match target.blend {
+                Some(ref state) => blend(state, src, src1, dst, constants),
+                None => src,
+            };
+            let mut result = [0; 4];
+            for i in 0 .. 4 {
+                result[i] = values[i].to_bits();
+            }
+            result
+        };
+
+        let channels = [
+            pso::ColorMask::RED,
+            pso::ColorMask::GREEN,
+            pso::ColorMask::BLUE,
+            pso::ColorMask::ALPHA,
+        ];
+        for (value, (&mask, &old)) in result.iter_mut().zip(channels.iter().zip(&dst)) {
+            if !target.mask.contains(mask) {
+                *value = old;
+            }
+        }
+        texel::encode(view.format, data, result);
+    }
+}
+
+fn logic_op(op: &pso::LogicOp, s: u32, d: u32) -> u32 {
+    use hal::pso::LogicOp as L;
+    match *op {
+        L::Clear => 0,
+        L::And => s & d,
+        L::AndReverse => s & !d,
+        L::Copy => s,
+        L::AndInverted => !s & d,
+        L::NoOp => d,
+        L::Xor => s ^ d,
+        L::Or => s | d,
+        L::Nor => !(s | d),
+        L::Equivalent => !(s ^ d),
+        L::Invert => !d,
+        L::OrReverse => s | !d,
+        L::CopyInverted => !s,
+        L::OrInverted => !s | d,
+        L::Nand => !(s & d),
+        L::Set => !0,
+    }
+}
+
+// Returns the value of a blend factor for the color channels and the alpha channel.
+fn factor(
+    factor: pso::Factor,
+    src: [f32; 4],
+    src1: [f32; 4],
+    dst: [f32; 4],
+    constants: [f32; 4],
+) -> ([f32; 3], f32) {
+    use hal::pso::Factor as F;
+    let rgb = |v: [f32; 4]| [v[0], v[1], v[2]];
+    let inv = |v: [f32; 3]| [1.0 - v[0], 1.0 - v[1], 1.0 - v[2]];
+    let splat = |v: f32| [v; 3];
+    match factor {
+        F::Zero => (splat(0.0), 0.0),
+        F::One => (splat(1.0), 1.0),
+        F::SrcColor => (rgb(src), src[3]),
+        F::OneMinusSrcColor => (inv(rgb(src)), 1.0 - src[3]),
+        F::DstColor => (rgb(dst), dst[3]),
+        F::OneMinusDstColor => (inv(rgb(dst)), 1.0 - dst[3]),
+        F::SrcAlpha => (splat(src[3]), src[3]),
+        F::OneMinusSrcAlpha => (splat(1.0 - src[3]), 1.0 - src[3]),
+        F::DstAlpha => (splat(dst[3]), dst[3]),
+        F::OneMinusDstAlpha => (splat(1.0 - dst[3]), 1.0 - dst[3]),
+        F::ConstColor => (rgb(constants), constants[3]),
+        F::OneMinusConstColor => (inv(rgb(constants)), 1.0 - constants[3]),
+        F::ConstAlpha => (splat(constants[3]), constants[3]),
+        F::OneMinusConstAlpha => (splat(1.0 - constants[3]), 1.0 - constants[3]),
+        F::SrcAlphaSaturate => (splat(src[3].min(1.0 - dst[3])), 1.0),
+        F::Src1Color => (rgb(src1), src1[3]),
+        F::OneMinusSrc1Color => (inv(rgb(src1)), 1.0 - src1[3]),
+        F::Src1Alpha => (splat(src1[3]), src1[3]),
+        F::OneMinusSrc1Alpha => (splat(1.0 - src1[3]), 1.0 - src1[3]),
+    }
+}
+
+fn blend(
+    state: &pso::BlendState,
+    src: [f32; 4],
+    src1: [f32; 4],
+    dst: [f32; 4],
+    constants: [f32; 4],
+) -> [f32; 4] {
+    // Applies an operation to the channels selected by `channels`.
+    let apply = |op: pso::BlendOp, channels: Range<usize>, result: &mut [f32; 4]| {
+        let alpha = channels.start == 3;
+        let pick = |(rgb, a): ([f32; 3], f32), i: usize| if alpha { a } else { rgb[i] };
+        for i in channels {
+            result[i] = match op {
+                pso::BlendOp::Add { src: s, dst: d } => {
+                    src[i] * pick(factor(s, src, src1, dst, constants), i)
+                        + dst[i] * pick(factor(d, src, src1, dst, constants), i)
+                }
+                pso::BlendOp::Sub { src: s, dst: d } => {
+                    src[i] * pick(factor(s, src, src1, dst, constants), i)
+                        - dst[i] * pick(factor(d, src, src1, dst, constants), i)
+                }
+                pso::BlendOp::RevSub { src: s, dst: d } => {
+                    dst[i] * pick(factor(d, src, src1, dst, constants), i)
+                        - src[i] * pick(factor(s, src, src1, dst, constants), i)
+                }
+                pso::BlendOp::Min => src[i].min(dst[i]),
+                pso::BlendOp::Max => src[i].max(dst[i]),
+            };
+        }
+    };
+    let mut result = [0.0; 4];
+    apply(state.color, 0 .. 3, &mut result);
+    apply(state.alpha, 3 .. 4, &mut result);
+    result
+}
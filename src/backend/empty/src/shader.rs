@@ -0,0 +1,3423 @@
+//! SPIR-V interpreter, running the shaders of the software pipelines on the host.
+//!
+//! Modules are parsed once when they are created, then specialized into a
+//! `Program` for every pipeline stage. Invocations are resumable, which lets
+//! the invocations of a compute workgroup run in lockstep across barriers.
+//!
+//! Values are flattened into 32-bit words, with matrices stored column by
+//! column. Buffer memory follows the explicit layout given by the `Offset`,
+//! `ArrayStride` and `MatrixStride` decorations. Derivatives are always zero,
+//! so implicit level of detail sampling always reads the base level.
+
+use hal::format::texel;
+use hal::{device as d, image as i, pso};
+
+use crate::native as n;
+
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::ptr;
+use std::sync::Arc;
+
+type Id = u32;
+
+// Marker of a decoration applying to a whole type or value, instead of a member.
+const NO_MEMBER: u32 = !0;
+
+#[allow(dead_code)]
+mod op {
+    pub const NOP: u32 = 0;
+    pub const UNDEF: u32 = 1;
+    pub const LINE: u32 = 8;
+    pub const EXT_INST_IMPORT: u32 = 11;
+    pub const EXT_INST: u32 = 12;
+    pub const ENTRY_POINT: u32 = 15;
+    pub const EXECUTION_MODE: u32 = 16;
+    pub const TYPE_VOID: u32 = 19;
+    pub const TYPE_BOOL: u32 = 20;
+    pub const TYPE_INT: u32 = 21;
+    pub const TYPE_FLOAT: u32 = 22;
+    pub const TYPE_VECTOR: u32 = 23;
+    pub const TYPE_MATRIX: u32 = 24;
+    pub const TYPE_IMAGE: u32 = 25;
+    pub const TYPE_SAMPLER: u32 = 26;
+    pub const TYPE_SAMPLED_IMAGE: u32 = 27;
+    pub const TYPE_ARRAY: u32 = 28;
+    pub const TYPE_RUNTIME_ARRAY: u32 = 29;
+    pub const TYPE_STRUCT: u32 = 30;
+    pub const TYPE_POINTER: u32 = 32;
+    pub const TYPE_FUNCTION: u32 = 33;
+    pub const CONSTANT_TRUE: u32 = 41;
+    pub const CONSTANT_FALSE: u32 = 42;
+    pub const CONSTANT: u32 = 43;
+    pub const CONSTANT_COMPOSITE: u32 = 44;
+    pub const CONSTANT_NULL: u32 = 46;
+    pub const SPEC_CONSTANT_TRUE: u32 = 48;
+    pub const SPEC_CONSTANT_FALSE: u32 = 49;
+    pub const SPEC_CONSTANT: u32 = 50;
+    pub const SPEC_CONSTANT_COMPOSITE: u32 = 51;
+    pub const SPEC_CONSTANT_OP: u32 = 52;
+    pub const FUNCTION: u32 = 54;
+    pub const FUNCTION_PARAMETER: u32 = 55;
+    pub const FUNCTION_END: u32 = 56;
+    pub const FUNCTION_CALL: u32 = 57;
+    pub const VARIABLE: u32 = 59;
+    pub const LOAD: u32 = 61;
+    pub const STORE: u32 = 62;
+    pub const COPY_MEMORY: u32 = 63;
+    pub const ACCESS_CHAIN: u32 = 65;
+    pub const IN_BOUNDS_ACCESS_CHAIN: u32 = 66;
+    pub const ARRAY_LENGTH: u32 = 68;
+    pub const DECORATE: u32 = 71;
+    pub const MEMBER_DECORATE: u32 = 72;
+    pub const GROUP_DECORATE: u32 = 74;
+    pub const GROUP_MEMBER_DECORATE: u32 = 75;
+    pub const VECTOR_EXTRACT_DYNAMIC: u32 = 77;
+    pub const VECTOR_INSERT_DYNAMIC: u32 = 78;
+    pub const VECTOR_SHUFFLE: u32 = 79;
+    pub const COMPOSITE_CONSTRUCT: u32 = 80;
+    pub const COMPOSITE_EXTRACT: u32 = 81;
+    pub const COMPOSITE_INSERT: u32 = 82;
+    pub const COPY_OBJECT: u32 = 83;
+    pub const TRANSPOSE: u32 = 84;
+    pub const SAMPLED_IMAGE: u32 = 86;
+    pub const IMAGE_SAMPLE_IMPLICIT_LOD: u32 = 87;
+    pub const IMAGE_SAMPLE_EXPLICIT_LOD: u32 = 88;
+    pub const IMAGE_SAMPLE_DREF_IMPLICIT_LOD: u32 = 89;
+    pub const IMAGE_SAMPLE_DREF_EXPLICIT_LOD: u32 = 90;
+    pub const IMAGE_SAMPLE_PROJ_IMPLICIT_LOD: u32 = 91;
+    pub const IMAGE_SAMPLE_PROJ_EXPLICIT_LOD: u32 = 92;
+    pub const IMAGE_SAMPLE_PROJ_DREF_IMPLICIT_LOD: u32 = 93;
+    pub const IMAGE_SAMPLE_PROJ_DREF_EXPLICIT_LOD: u32 = 94;
+    pub const IMAGE_FETCH: u32 = 95;
+    pub const IMAGE_GATHER: u32 = 96;
+    pub const IMAGE_DREF_GATHER: u32 = 97;
+    pub const IMAGE_READ: u32 = 98;
+    pub const IMAGE_WRITE: u32 = 99;
+    pub const IMAGE: u32 = 100;
+    pub const IMAGE_QUERY_SIZE_LOD: u32 = 103;
+    pub const IMAGE_QUERY_SIZE: u32 = 104;
+    pub const IMAGE_QUERY_LEVELS: u32 = 106;
+    pub const IMAGE_QUERY_SAMPLES: u32 = 107;
+    pub const CONVERT_F_TO_U: u32 = 109;
+    pub const CONVERT_F_TO_S: u32 = 110;
+    pub const CONVERT_S_TO_F: u32 = 111;
+    pub const CONVERT_U_TO_F: u32 = 112;
+    pub const U_CONVERT: u32 = 113;
+    pub const S_CONVERT: u32 = 114;
+    pub const F_CONVERT: u32 = 115;
+    pub const QUANTIZE_TO_F16: u32 = 116;
+    pub const BITCAST: u32 = 124;
+    pub const S_NEGATE: u32 = 126;
+    pub const F_NEGATE: u32 = 127;
+    pub const I_ADD: u32 = 128;
+    pub const F_ADD: u32 = 129;
+    pub const I_SUB: u32 = 130;
+    pub const F_SUB: u32 = 131;
+    pub const I_MUL: u32 = 132;
+    pub const F_MUL: u32 = 133;
+    pub const U_DIV: u32 = 134;
+    pub const S_DIV: u32 = 135;
+    pub const F_DIV: u32 = 136;
+    pub const U_MOD: u32 = 137;
+    pub const S_REM: u32 = 138;
+    pub const S_MOD: u32 = 139;
+    pub const F_REM: u32 = 140;
+    pub const F_MOD: u32 = 141;
+    pub const VECTOR_TIMES_SCALAR: u32 = 142;
+    pub const MATRIX_TIMES_SCALAR: u32 = 143;
+    pub const VECTOR_TIMES_MATRIX: u32 = 144;
+    pub const MATRIX_TIMES_VECTOR: u32 = 145;
+    pub const MATRIX_TIMES_MATRIX: u32 = 146;
+    pub const OUTER_PRODUCT: u32 = 147;
+    pub const DOT: u32 = 148;
+    pub const I_ADD_CARRY: u32 = 149;
+    pub const I_SUB_BORROW: u32 = 150;
+    pub const U_MUL_EXTENDED: u32 = 151;
+    pub const S_MUL_EXTENDED: u32 = 152;
+    pub const ANY: u32 = 154;
+    pub const ALL: u32 = 155;
+    pub const IS_NAN: u32 = 156;
+    pub const IS_INF: u32 = 157;
+    pub const IS_FINITE: u32 = 158;
+    pub const IS_NORMAL: u32 = 159;
+    pub const SIGN_BIT_SET: u32 = 160;
+    pub const LOGICAL_EQUAL: u32 = 164;
+    pub const LOGICAL_NOT_EQUAL: u32 = 165;
+    pub const LOGICAL_OR: u32 = 166;
+    pub const LOGICAL_AND: u32 = 167;
+    pub const LOGICAL_NOT: u32 = 168;
+    pub const SELECT: u32 = 169;
+    pub const I_EQUAL: u32 = 170;
+    pub const I_NOT_EQUAL: u32 = 171;
+    pub const U_GREATER_THAN: u32 = 172;
+    pub const S_GREATER_THAN: u32 = 173;
+    pub const U_GREATER_THAN_EQUAL: u32 = 174;
+    pub const S_GREATER_THAN_EQUAL: u32 = 175;
+    pub const U_LESS_THAN: u32 = 176;
+    pub const S_LESS_THAN: u32 = 177;
+    pub const U_LESS_THAN_EQUAL: u32 = 178;
+    pub const S_LESS_THAN_EQUAL: u32 = 179;
+    pub const F_ORD_EQUAL: u32 = 180;
+    pub const F_UNORD_EQUAL: u32 = 181;
+    pub const F_ORD_NOT_EQUAL: u32 = 182;
+    pub const F_UNORD_NOT_EQUAL: u32 = 183;
+    pub const F_ORD_LESS_THAN: u32 = 184;
+    pub const F_UNORD_LESS_THAN: u32 = 185;
+    pub const F_ORD_GREATER_THAN: u32 = 186;
+    pub const F_UNORD_GREATER_THAN: u32 = 187;
+    pub const F_ORD_LESS_THAN_EQUAL: u32 = 188;
+    pub const F_UNORD_LESS_THAN_EQUAL: u32 = 189;
+    pub const F_ORD_GREATER_THAN_EQUAL: u32 = 190;
+    pub const F_UNORD_GREATER_THAN_EQUAL: u32 = 191;
+    pub const SHIFT_RIGHT_LOGICAL: u32 = 194;
+    pub const SHIFT_RIGHT_ARITHMETIC: u32 = 195;
+    pub const SHIFT_LEFT_LOGICAL: u32 = 196;
+    pub const BITWISE_OR: u32 = 197;
+    pub const BITWISE_XOR: u32 = 198;
+    pub const BITWISE_AND: u32 = 199;
+    pub const NOT: u32 = 200;
+    pub const BIT_FIELD_INSERT: u32 = 201;
+    pub const BIT_FIELD_S_EXTRACT: u32 = 202;
+    pub const BIT_FIELD_U_EXTRACT: u32 = 203;
+    pub const BIT_REVERSE: u32 = 204;
+    pub const BIT_COUNT: u32 = 205;
+    pub const DPDX: u32 = 207;
+    pub const FWIDTH_COARSE: u32 = 215;
+    pub const CONTROL_BARRIER: u32 = 224;
+    pub const MEMORY_BARRIER: u32 = 225;
+    pub const ATOMIC_LOAD: u32 = 227;
+    pub const ATOMIC_STORE: u32 = 228;
+    pub const ATOMIC_EXCHANGE: u32 = 229;
+    pub const ATOMIC_COMPARE_EXCHANGE: u32 = 230;
+    pub const ATOMIC_COMPARE_EXCHANGE_WEAK: u32 = 231;
+    pub const ATOMIC_I_INCREMENT: u32 = 232;
+    pub const ATOMIC_I_DECREMENT: u32 = 233;
+    pub const ATOMIC_I_ADD: u32 = 234;
+    pub const ATOMIC_I_SUB: u32 = 235;
+    pub const ATOMIC_S_MIN: u32 = 236;
+    pub const ATOMIC_U_MIN: u32 = 237;
+    pub const ATOMIC_S_MAX: u32 = 238;
+    pub const ATOMIC_U_MAX: u32 = 239;
+    pub const ATOMIC_AND: u32 = 240;
+    pub const ATOMIC_OR: u32 = 241;
+    pub const ATOMIC_XOR: u32 = 242;
+    pub const PHI: u32 = 245;
+    pub const LOOP_MERGE: u32 = 246;
+    pub const SELECTION_MERGE: u32 = 247;
+    pub const LABEL: u32 = 248;
+    pub const BRANCH: u32 = 249;
+    pub const BRANCH_CONDITIONAL: u32 = 250;
+    pub const SWITCH: u32 = 251;
+    pub const KILL: u32 = 252;
+    pub const RETURN: u32 = 253;
+    pub const RETURN_VALUE: u32 = 254;
+    pub const UNREACHABLE: u32 = 255;
+    pub const LIFETIME_START: u32 = 256;
+    pub const LIFETIME_STOP: u32 = 257;
+    pub const NO_LINE: u32 = 317;
+    pub const EXECUTION_MODE_ID: u32 = 331;
+    pub const COPY_LOGICAL: u32 = 400;
+    pub const TERMINATE_INVOCATION: u32 = 4416;
+    pub const DEMOTE_TO_HELPER_INVOCATION: u32 = 5380;
+}
+
+mod decoration {
+    pub const SPEC_ID: u32 = 1;
+    pub const ROW_MAJOR: u32 = 4;
+    pub const ARRAY_STRIDE: u32 = 6;
+    pub const MATRIX_STRIDE: u32 = 7;
+    pub const BUILT_IN: u32 = 11;
+    pub const NO_PERSPECTIVE: u32 = 13;
+    pub const FLAT: u32 = 14;
+    pub const LOCATION: u32 = 30;
+    pub const COMPONENT: u32 = 31;
+    pub const INDEX: u32 = 32;
+    pub const BINDING: u32 = 33;
+    pub const DESCRIPTOR_SET: u32 = 34;
+    pub const OFFSET: u32 = 35;
+}
+
+/// Built-in variables used by the rasterizer and the compute dispatches.
+#[allow(dead_code)]
+pub(crate) mod builtin {
+    pub const POSITION: u32 = 0;
+    pub const POINT_SIZE: u32 = 1;
+    pub const FRAG_COORD: u32 = 15;
+    pub const POINT_COORD: u32 = 16;
+    pub const FRONT_FACING: u32 = 17;
+    pub const SAMPLE_ID: u32 = 18;
+    pub const FRAG_DEPTH: u32 = 22;
+    pub const HELPER_INVOCATION: u32 = 23;
+    pub const NUM_WORKGROUPS: u32 = 24;
+    pub const WORKGROUP_SIZE: u32 = 25;
+    pub const WORKGROUP_ID: u32 = 26;
+    pub const LOCAL_INVOCATION_ID: u32 = 27;
+    pub const GLOBAL_INVOCATION_ID: u32 = 28;
+    pub const LOCAL_INVOCATION_INDEX: u32 = 29;
+    pub const VERTEX_INDEX: u32 = 42;
+    pub const INSTANCE_INDEX: u32 = 43;
+}
+
+mod storage {
+    pub const UNIFORM_CONSTANT: u32 = 0;
+    pub const INPUT: u32 = 1;
+    pub const UNIFORM: u32 = 2;
+    pub const OUTPUT: u32 = 3;
+    pub const WORKGROUP: u32 = 4;
+    pub const PRIVATE: u32 = 6;
+    pub const FUNCTION: u32 = 7;
+    pub const PUSH_CONSTANT: u32 = 9;
+    pub const STORAGE_BUFFER: u32 = 12;
+}
+
+mod execution_mode {
+    pub const EARLY_FRAGMENT_TESTS: u32 = 9;
+    pub const LOCAL_SIZE: u32 = 17;
+    pub const LOCAL_SIZE_ID: u32 = 38;
+}
+
+// `Dim` of the image types read as subpass inputs.
+const DIM_SUBPASS_DATA: u32 = 6;
+
+// Image operand flags.
+const IMAGE_OPERAND_BIAS: u32 = 0x1;
+const IMAGE_OPERAND_LOD: u32 = 0x2;
+const IMAGE_OPERAND_GRAD: u32 = 0x4;
+const IMAGE_OPERAND_CONST_OFFSET: u32 = 0x8;
+const IMAGE_OPERAND_OFFSET: u32 = 0x10;
+const IMAGE_OPERAND_CONST_OFFSETS: u32 = 0x20;
+const IMAGE_OPERAND_SAMPLE: u32 = 0x40;
+const IMAGE_OPERAND_MIN_LOD: u32 = 0x80;
+
+// Returns true if the instruction starts with a result type and a result id.
+fn has_result_type(opcode: u32) -> bool {
+    matches!(
+        opcode,
+        op::UNDEF
+            | op::EXT_INST
+            | op::FUNCTION
+            | op::FUNCTION_PARAMETER
+            | op::FUNCTION_CALL
+            | op::VARIABLE
+            | op::PHI
+            | op::COPY_LOGICAL
+            | 41 ..= 52
+            | 60
+            | 61
+            | 65 ..= 70
+            | 77 ..= 84
+            | 86 ..= 98
+            | 100 ..= 107
+            | 109 ..= 124
+            | 126 ..= 205
+            | 207 ..= 215
+            | 227
+            | 229 ..= 242
+    )
+}
+
+fn is_terminator(opcode: u32) -> bool {
+    matches!(
+        opcode,
+        op::BRANCH
+            | op::BRANCH_CONDITIONAL
+            | op::SWITCH
+            | op::KILL
+            | op::RETURN
+            | op::RETURN_VALUE
+            | op::UNREACHABLE
+            | op::TERMINATE_INVOCATION
+    )
+}
+
+// Decodes a literal string, returning it with the number of words it takes.
+fn literal_string(words: &[u32]) -> (String, usize) {
+    let mut bytes = Vec::new();
+    for (i, word) in words.iter().enumerate() {
+        for &byte in &word.to_le_bytes() {
+            if byte == 0 {
+                return (String::from_utf8_lossy(&bytes).into_owned(), i + 1);
+            }
+            bytes.push(byte);
+        }
+    }
+    (String::from_utf8_lossy(&bytes).into_owned(), words.len())
+}
+
+#[derive(Clone, Debug)]
+enum Type {
+    Void,
+    Bool,
+    /// Integers, with their signedness given by the operations using them.
+    Int,
+    Float,
+    Vector {
+        component: Id,
+        count: u32,
+    },
+    Matrix {
+        column: Id,
+        count: u32,
+    },
+    Image {
+        dim: u32,
+    },
+    Sampler,
+    SampledImage,
+    Array {
+        element: Id,
+        length: u32,
+    },
+    RuntimeArray(Id),
+    Struct(Vec<Id>),
+    Pointer {
+        pointee: Id,
+    },
+    Function,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Instruction {
+    opcode: u32,
+    start: u32,
+    end: u32,
+}
+
+#[derive(Debug)]
+struct Block {
+    label: Id,
+    instructions: Vec<Instruction>,
+}
+
+#[derive(Debug, Default)]
+struct Function {
+    parameters: Vec<Id>,
+    blocks: Vec<Block>,
+    labels: HashMap<Id, usize>,
+}
+
+#[derive(Debug)]
+struct EntryPoint {
+    model: u32,
+    function: Id,
+    name: String,
+    interface: Vec<Id>,
+}
+
+/// Decorations of ids and struct members, as a list of decorations with their operands.
+type Decorations = HashMap<(Id, u32), Vec<(u32, Vec<u32>)>>;
+
+/// Parsed SPIR-V module.
+#[derive(Debug)]
+pub struct Module {
+    words: Vec<u32>,
+    types: Vec<Option<Type>>,
+    /// Number of words of the flattened values of every type.
+    type_words: Vec<usize>,
+    /// Result type of every value.
+    type_of: Vec<Id>,
+    decorations: Decorations,
+    /// Constants, undefined values and global variables, in declaration order.
+    globals: Vec<Instruction>,
+    functions: Vec<Function>,
+    function_index: HashMap<Id, usize>,
+    entry_points: Vec<EntryPoint>,
+    execution_modes: Vec<(Id, u32, Vec<u32>)>,
+    glsl_std: Option<Id>,
+}
+
+impl Module {
+    pub(crate) fn parse(words: &[u32]) -> Result<Self, d::ShaderError> {
+        let error = |message: &str| Err(d::ShaderError::CompilationFailed(message.to_string()));
+        if words.len() < 5 || words[0] != 0x0723_0203 {
+            return error("Invalid SPIR-V header");
+        }
+        let bound = words[3] as usize;
+        let mut module = Module {
+            words: words.to_vec(),
+            types: vec![None; bound],
+            type_words: vec![0; bound],
+            type_of: vec![0; bound],
+            decorations: HashMap::new(),
+            globals: Vec::new(),
+            functions: Vec::new(),
+            function_index: HashMap::new(),
+            entry_points: Vec::new(),
+            execution_modes: Vec::new(),
+            glsl_std: None,
+        };
+        // Values of the integer constants, used for the lengths of arrays.
+        let mut literals = HashMap::new();
+        let mut function: Option<(Id, Function)> = None;
+        let mut block: Option<Block> = None;
+
+        let mut offset = 5;
+        while offset < words.len() {
+            let count = (words[offset] >> 16) as usize;
+            let opcode = words[offset] & 0xFFFF;
+            if count == 0 || offset + count > words.len() {
+                return error("Invalid SPIR-V instruction size");
+            }
+            let instruction = Instruction {
+                opcode,
+                start: offset as u32 + 1,
+                end: (offset + count) as u32,
+            };
+            let ops = &words[offset + 1 .. offset + count];
+            offset += count;
+
+            if has_result_type(opcode) && ops.len() >= 2 {
+                if ops[1] as usize >= bound {
+                    return error("SPIR-V id out of bounds");
+                }
+                module.type_of[ops[1] as usize] = ops[0];
+            }
+            if let Some(ref mut current) = block {
+                current.instructions.push(instruction);
+                if is_terminator(opcode) {
+                    let current = block.take().unwrap();
+                    let function = &mut function.as_mut().unwrap().1;
+                    function.labels.insert(current.label, function.blocks.len());
+                    function.blocks.push(current);
+                }
+                continue;
+            }
+
+            match opcode {
+                op::EXT_INST_IMPORT if literal_string(&ops[1 ..]).0 == "GLSL.std.450" => {
+                    module.glsl_std = Some(ops[0]);
+                }
+                op::ENTRY_POINT => {
+                    let (name, length) = literal_string(&ops[2 ..]);
+                    module.entry_points.push(EntryPoint {
+                        model: ops[0],
+                        function: ops[1],
+                        name,
+                        interface: ops[2 + length ..].to_vec(),
+                    });
+                }
+                op::EXECUTION_MODE | op::EXECUTION_MODE_ID => {
+                    module
+                        .execution_modes
+                        .push((ops[0], ops[1], ops[2 ..].to_vec()));
+                }
+                op::DECORATE => {
+                    module
+                        .decorations
+                        .entry((ops[0], NO_MEMBER))
+                        .or_default()
+                        .push((ops[1], ops[2 ..].to_vec()));
+                }
+                op::MEMBER_DECORATE => {
+                    module
+                        .decorations
+                        .entry((ops[0], ops[1]))
+                        .or_default()
+                        .push((ops[2], ops[3 ..].to_vec()));
+                }
+                op::GROUP_DECORATE | op::GROUP_MEMBER_DECORATE => {
+                    let group = module
+                        .decorations
+                        .get(&(ops[0], NO_MEMBER))
+                        .cloned()
+                        .unwrap_or_default();
+                    let targets: Vec<(Id, u32)> = if opcode == op::GROUP_DECORATE {
+                        ops[1 ..].iter().map(|&id| (id, NO_MEMBER)).collect()
+                    } else {
+                        ops[1 ..].chunks(2).map(|pair| (pair[0], pair[1])).collect()
+                    };
+                    for target in targets {
+                        module
+                            .decorations
+                            .entry(target)
+                            .or_default()
+                            .extend(group.iter().cloned());
+                    }
+                }
+                op::TYPE_VOID ..= op::TYPE_FUNCTION => {
+                    let ty = match opcode {
+                        op::TYPE_VOID => Type::Void,
+                        op::TYPE_BOOL => Type::Bool,
+                        op::TYPE_INT | op::TYPE_FLOAT if ops[1] != 32 => {
+                            return error("Only 32-bit numbers are supported");
+                        }
+                        op::TYPE_INT => Type::Int,
+                        op::TYPE_FLOAT => Type::Float,
+                        op::TYPE_VECTOR => Type::Vector {
+                            component: ops[1],
+                            count: ops[2],
+                        },
+                        op::TYPE_MATRIX => Type::Matrix {
+                            column: ops[1],
+                            count: ops[2],
+                        },
+                        op::TYPE_IMAGE => Type::Image { dim: ops[2] },
+                        op::TYPE_SAMPLER => Type::Sampler,
+                        op::TYPE_SAMPLED_IMAGE => Type::SampledImage,
+                        op::TYPE_ARRAY => match literals.get(&ops[2]) {
+                            Some(&length) => Type::Array {
+                                element: ops[1],
+                                length,
+                            },
+                            None => return error("Unknown array length"),
+                        },
+                        op::TYPE_RUNTIME_ARRAY => Type::RuntimeArray(ops[1]),
+                        op::TYPE_STRUCT => Type::Struct(ops[1 ..].to_vec()),
+                        op::TYPE_POINTER => Type::Pointer { pointee: ops[2] },
+                        op::TYPE_FUNCTION => Type::Function,
+                        _ => continue,
+                    };
+                    if ops[0] as usize >= bound {
+                        return error("SPIR-V id out of bounds");
+                    }
+                    let words = match ty {
+                        Type::Bool | Type::Int | Type::Float => 1,
+                        Type::Vector { component, count }
+                        | Type::Matrix {
+                            column: component,
+                            count,
+                        } => module.type_words[component as usize] * count as usize,
+                        Type::Array { element, length } => {
+                            module.type_words[element as usize] * length as usize
+                        }
+                        Type::Struct(ref members) => members
+                            .iter()
+                            .map(|&member| module.type_words[member as usize])
+                            .sum(),
+                        _ => 0,
+                    };
+                    module.type_words[ops[0] as usize] = words;
+                    module.types[ops[0] as usize] = Some(ty);
+                }
+                op::CONSTANT_TRUE ..= op::SPEC_CONSTANT_OP => {
+                    if opcode == op::CONSTANT || opcode == op::SPEC_CONSTANT {
+                        literals.insert(ops[1], ops[2]);
+                    }
+                    module.globals.push(instruction);
+                }
+                op::UNDEF | op::VARIABLE => module.globals.push(instruction),
+                op::FUNCTION => function = Some((ops[1], Function::default())),
+                op::FUNCTION_PARAMETER => match function {
+                    Some((_, ref mut function)) => function.parameters.push(ops[1]),
+                    None => return error("Parameter outside of a function"),
+                },
+                op::LABEL => {
+                    if function.is_none() {
+                        return error("Label outside of a function");
+                    }
+                    block = Some(Block {
+                        label: ops[0],
+                        instructions: Vec::new(),
+                    });
+                }
+                op::FUNCTION_END => match function.take() {
+                    Some((id, function)) => {
+                        module.function_index.insert(id, module.functions.len());
+                        module.functions.push(function);
+                    }
+                    None => return error("Unexpected function end"),
+                },
+                // Capabilities, extensions and debug information.
+                _ => {}
+            }
+        }
+
+        if function.is_some() || block.is_some() {
+            return error("Unterminated function");
+        }
+        Ok(module)
+    }
+
+    fn operands(&self, instruction: Instruction) -> &[u32] {
+        &self.words[instruction.start as usize .. instruction.end as usize]
+    }
+
+    fn ty(&self, id: Id) -> &Type {
+        self.types[id as usize]
+            .as_ref()
+            .expect("Unknown SPIR-V type")
+    }
+
+    fn words(&self, ty: Id) -> usize {
+        self.type_words[ty as usize]
+    }
+
+    fn decoration(&self, id: Id, member: u32, decoration: u32) -> Option<&[u32]> {
+        self.decorations.get(&(id, member)).and_then(|decorations| {
+            decorations
+                .iter()
+                .find(|&&(d, _)| d == decoration)
+                .map(|(_, args)| &args[..])
+        })
+    }
+
+    fn decoration_value(&self, id: Id, member: u32, decoration: u32) -> Option<u32> {
+        self.decoration(id, member, decoration)
+            .and_then(|args| args.first().cloned())
+    }
+
+    // Returns the type of the value a pointer type points to.
+    fn pointee(&self, ty: Id) -> Id {
+        match *self.ty(ty) {
+            Type::Pointer { pointee } => pointee,
+            _ => panic!("Expected a pointer type"),
+        }
+    }
+
+    // Returns the scalar type at the bottom of a composite type.
+    fn scalar(&self, ty: Id) -> Id {
+        match *self.ty(ty) {
+            Type::Vector { component, .. } => self.scalar(component),
+            Type::Matrix { column, .. } => self.scalar(column),
+            Type::Array { element, .. } | Type::RuntimeArray(element) => self.scalar(element),
+            _ => ty,
+        }
+    }
+
+    pub(crate) fn is_integer(&self, ty: Id) -> bool {
+        matches!(*self.ty(self.scalar(ty)), Type::Int | Type::Bool)
+    }
+
+    // Returns the word offset and the type of a member of a flattened composite.
+    fn flat_member(&self, ty: Id, index: u32) -> (usize, Id) {
+        match *self.ty(ty) {
+            Type::Vector { component, .. } => (index as usize, component),
+            Type::Matrix { column, .. } => (index as usize * self.words(column), column),
+            Type::Array { element, .. } => (index as usize * self.words(element), element),
+            Type::Struct(ref members) => {
+                let offset = members[.. index as usize]
+                    .iter()
+                    .map(|&member| self.words(member))
+                    .sum();
+                (offset, members[index as usize])
+            }
+            ref other => panic!("Unexpected composite type {:?}", other),
+        }
+    }
+
+    // Returns the byte offset and the layout of a member of a block.
+    fn block_member(&self, ty: Id, index: u32) -> (usize, Layout) {
+        let offset = self
+            .decoration_value(ty, index, decoration::OFFSET)
+            .unwrap_or(0);
+        let layout = Layout {
+            matrix_stride: self
+                .decoration_value(ty, index, decoration::MATRIX_STRIDE)
+                .unwrap_or(16) as usize,
+            row_major: self.decoration(ty, index, decoration::ROW_MAJOR).is_some(),
+            component_stride: 4,
+        };
+        (offset as usize, layout)
+    }
+
+    fn array_stride(&self, ty: Id) -> usize {
+        self.decoration_value(ty, NO_MEMBER, decoration::ARRAY_STRIDE)
+            .unwrap_or(4) as usize
+    }
+
+    /// Returns the number of words in each location taken by an interface variable.
+    pub(crate) fn location_words(&self, ty: Id) -> Vec<usize> {
+        match *self.ty(ty) {
+            Type::Matrix { column, count } => vec![self.words(column); count as usize],
+            Type::Array { element, length } => {
+                let words = self.location_words(element);
+                (0 .. length).flat_map(|_| words.iter().cloned()).collect()
+            }
+            Type::Struct(ref members) => members
+                .iter()
+                .flat_map(|&member| self.location_words(member))
+                .collect(),
+            _ => vec![self.words(ty)],
+        }
+    }
+}
+
+/// Layout of a pointer into buffer memory, inherited from the enclosing block members.
+#[derive(Clone, Copy, Debug)]
+struct Layout {
+    matrix_stride: usize,
+    row_major: bool,
+    /// Stride between the components of a vector, which are spread
+    /// when accessing a column of a row-major matrix.
+    component_stride: usize,
+}
+
+const DEFAULT_LAYOUT: Layout = Layout {
+    matrix_stride: 16,
+    row_major: false,
+    component_stride: 4,
+};
+
+/// Descriptor referenced by a shader, as a set, binding and array element.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ResourceRef {
+    set: u32,
+    binding: u32,
+    index: u32,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Base {
+    /// Storage owned by the invocation.
+    Variable(usize),
+    /// Storage shared by the invocations of a workgroup.
+    Workgroup(usize),
+    /// Buffer memory with an explicit layout.
+    Memory {
+        address: *mut u8,
+        size: usize,
+    },
+    /// Descriptor, with a flag telling if an array element still needs to be selected.
+    Resource(ResourceRef, bool),
+    PushConstant,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Pointer {
+    base: Base,
+    /// Offset in words for flattened storage, in bytes for buffer memory.
+    offset: usize,
+    ty: Id,
+    layout: Layout,
+}
+
+#[derive(Clone, Debug)]
+enum Value {
+    Data(Vec<u32>),
+    Pointer(Pointer),
+    Image(ResourceRef),
+    Sampler(ResourceRef),
+    SampledImage(ResourceRef, ResourceRef),
+}
+
+impl Value {
+    fn data(&self) -> &[u32] {
+        match *self {
+            Value::Data(ref data) => data,
+            ref other => panic!("Expected data, got {:?}", other),
+        }
+    }
+}
+
+/// Descriptors bound to a pipeline, indexed by set, binding and array element.
+pub(crate) type Resources = Vec<Vec<Vec<n::Descriptor>>>;
+
+/// Interface variable with an explicit location.
+#[derive(Clone, Debug)]
+pub(crate) struct Interface {
+    pub(crate) slot: usize,
+    pub(crate) offset: usize,
+    pub(crate) ty: Id,
+    pub(crate) location: u32,
+    pub(crate) component: u32,
+    /// Index of fragment shader outputs, used for dual-source blending.
+    pub(crate) index: u32,
+    pub(crate) flat: bool,
+    pub(crate) no_perspective: bool,
+}
+
+#[derive(Clone, Debug)]
+struct BuiltinVariable {
+    builtin: u32,
+    slot: usize,
+    offset: usize,
+    words: usize,
+}
+
+/// Entry point of a module, specialized for a pipeline.
+#[derive(Debug)]
+pub struct Program {
+    pub(crate) module: Arc<Module>,
+    function: usize,
+    /// Values known before running the program: constants and pointers to global variables.
+    constants: Vec<Option<Value>>,
+    /// Size in words of the variables owned by every invocation.
+    variables: Vec<usize>,
+    /// Constant initializers of the variables.
+    initializers: Vec<Option<Id>>,
+    /// Size in words of the workgroup variables.
+    workgroup: Vec<usize>,
+    pub(crate) inputs: Vec<Interface>,
+    pub(crate) outputs: Vec<Interface>,
+    builtins: Vec<BuiltinVariable>,
+    pub(crate) local_size: [u32; 3],
+    pub(crate) early_fragment_tests: bool,
+}
+
+// The constants never point into buffer memory: such pointers are only
+// created while an invocation is running.
+unsafe impl Send for Program {}
+unsafe impl Sync for Program {}
+
+impl Program {
+    pub(crate) fn new(
+        module: &Arc<Module>,
+        entry: &str,
+        stage: pso::Stage,
+        specialization: &pso::Specialization,
+    ) -> Result<Self, d::ShaderError> {
+        let model = match stage {
+            pso::Stage::Vertex => 0,
+            pso::Stage::Fragment => 4,
+            pso::Stage::Compute => 5,
+            other => return Err(d::ShaderError::UnsupportedStage(other)),
+        };
+        let entry_point = module
+            .entry_points
+            .iter()
+            .find(|ep| ep.name == entry && ep.model == model)
+            .ok_or_else(|| d::ShaderError::MissingEntryPoint(entry.to_string()))?;
+
+        let mut program = Program {
+            module: Arc::clone(module),
+            function: module.function_index[&entry_point.function],
+            constants: vec![None; module.types.len()],
+            variables: Vec::new(),
+            initializers: Vec::new(),
+            workgroup: Vec::new(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            builtins: Vec::new(),
+            local_size: [1, 1, 1],
+            early_fragment_tests: false,
+        };
+
+        for &instruction in &module.globals {
+            let ops = module.operands(instruction);
+            let value = match instruction.opcode {
+                op::VARIABLE => program.global_variable(ops)?,
+                _ => program.constant(instruction.opcode, ops, specialization)?,
+            };
+            program.constants[ops[1] as usize] = Some(value);
+        }
+
+        // Variables of the functions have a fixed storage, as recursion is not allowed.
+        for function in &module.functions {
+            for block in &function.blocks {
+                for &instruction in &block.instructions {
+                    if instruction.opcode == op::VARIABLE {
+                        let ops = module.operands(instruction);
+                        let pointer = program.variable(module.pointee(ops[0]), ops.get(3));
+                        program.constants[ops[1] as usize] = Some(pointer);
+                    }
+                }
+            }
+        }
+
+        for &id in &entry_point.interface {
+            program.add_interface(id);
+        }
+
+        for &(function, mode, ref args) in &module.execution_modes {
+            if function != entry_point.function {
+                continue;
+            }
+            match mode {
+                execution_mode::EARLY_FRAGMENT_TESTS => program.early_fragment_tests = true,
+                execution_mode::LOCAL_SIZE => {
+                    program.local_size = [args[0], args[1], args[2]];
+                }
+                execution_mode::LOCAL_SIZE_ID => {
+                    for (i, &id) in args.iter().enumerate().take(3) {
+                        program.local_size[i] = program.constant_word(id);
+                    }
+                }
+                _ => {}
+            }
+        }
+        for (&(id, member), decorations) in &module.decorations {
+            let workgroup_size = decorations.iter().any(|&(d, ref args)| {
+                d == decoration::BUILT_IN && args[0] == builtin::WORKGROUP_SIZE
+            });
+            if member == NO_MEMBER && workgroup_size {
+                if let Some(Value::Data(ref size)) = program.constants[id as usize] {
+                    program.local_size = [size[0], size[1], size[2]];
+                }
+            }
+        }
+
+        Ok(program)
+    }
+
+    fn constant_word(&self, id: Id) -> u32 {
+        match self.constants[id as usize] {
+            Some(Value::Data(ref data)) => data[0],
+            _ => panic!("Expected a constant"),
+        }
+    }
+
+    fn constant(
+        &self,
+        opcode: u32,
+        ops: &[u32],
+        specialization: &pso::Specialization,
+    ) -> Result<Value, d::ShaderError> {
+        let module = &*self.module;
+        let specialized = || {
+            let id = module.decoration_value(ops[1], NO_MEMBER, decoration::SPEC_ID)?;
+            let constant = specialization.constants.iter().find(|c| c.id == id)?;
+            let data =
+                &specialization.data[constant.range.start as usize .. constant.range.end as usize];
+            let mut bytes = [0; 4];
+            let count = data.len().min(4);
+            bytes[.. count].copy_from_slice(&data[.. count]);
+            Some(u32::from_le_bytes(bytes))
+        };
+        let data = match opcode {
+            op::CONSTANT_TRUE => vec![1],
+            op::CONSTANT_FALSE => vec![0],
+            op::SPEC_CONSTANT_TRUE => vec![specialized().map_or(1, |value| (value != 0) as u32)],
+            op::SPEC_CONSTANT_FALSE => vec![specialized().map_or(0, |value| (value != 0) as u32)],
+            op::CONSTANT => ops[2 ..].to_vec(),
+            op::SPEC_CONSTANT => vec![specialized().unwrap_or(ops[2])],
+            op::CONSTANT_COMPOSITE | op::SPEC_CONSTANT_COMPOSITE => ops[2 ..]
+                .iter()
+                .flat_map(|&id| match self.constants[id as usize] {
+                    Some(Value::Data(ref data)) => data.clone(),
+                    _ => vec![0; module.words(module.type_of[id as usize])],
+                })
+                .collect(),
+            op::CONSTANT_NULL | op::UNDEF => vec![0; module.words(ops[0])],
+            op::SPEC_CONSTANT_OP => {
+                // The operation is evaluated like in a function, on the known constants.
+                let mut operands = vec![ops[0], ops[1]];
+                operands.extend_from_slice(&ops[3 ..]);
+                let get = |id: Id| {
+                    self.constants[id as usize]
+                        .as_ref()
+                        .expect("Unknown constant")
+                };
+                return evaluate(module, ops[2], &operands, &get).ok_or_else(|| {
+                    d::ShaderError::CompilationFailed(format!(
+                        "Unsupported specialization constant operation {}",
+                        ops[2]
+                    ))
+                });
+            }
+            _ => {
+                return Err(d::ShaderError::CompilationFailed(format!(
+                    "Unsupported constant instruction {}",
+                    opcode
+                )))
+            }
+        };
+        Ok(Value::Data(data))
+    }
+
+    // Allocates the storage of a variable owned by every invocation.
+    fn variable(&mut self, ty: Id, initializer: Option<&Id>) -> Value {
+        self.variables.push(self.module.words(ty));
+        self.initializers.push(initializer.cloned());
+        Value::Pointer(Pointer {
+            base: Base::Variable(self.variables.len() - 1),
+            offset: 0,
+            ty,
+            layout: DEFAULT_LAYOUT,
+        })
+    }
+
+    fn global_variable(&mut self, ops: &[u32]) -> Result<Value, d::ShaderError> {
+        let module = Arc::clone(&self.module);
+        let ty = module.pointee(ops[0]);
+        let pointer = |base| {
+            Value::Pointer(Pointer {
+                base,
+                offset: 0,
+                ty,
+                layout: DEFAULT_LAYOUT,
+            })
+        };
+        Ok(match ops[2] {
+            storage::INPUT | storage::OUTPUT | storage::PRIVATE | storage::FUNCTION => {
+                self.variable(ty, ops.get(3))
+            }
+            storage::WORKGROUP => {
+                self.workgroup.push(module.words(ty));
+                pointer(Base::Workgroup(self.workgroup.len() - 1))
+            }
+            storage::UNIFORM_CONSTANT | storage::UNIFORM | storage::STORAGE_BUFFER => {
+                let resource = ResourceRef {
+                    set: module
+                        .decoration_value(ops[1], NO_MEMBER, decoration::DESCRIPTOR_SET)
+                        .unwrap_or(0),
+                    binding: module
+                        .decoration_value(ops[1], NO_MEMBER, decoration::BINDING)
+                        .unwrap_or(0),
+                    index: 0,
+                };
+                // Arrays of blocks and images are arrays of descriptors.
+                let array = match *module.ty(ty) {
+                    Type::Array { element, .. } | Type::RuntimeArray(element) => matches!(
+                        *module.ty(element),
+                        Type::Struct(_) | Type::Image { .. } | Type::Sampler | Type::SampledImage
+                    ),
+                    _ => false,
+                };
+                pointer(Base::Resource(resource, array))
+            }
+            storage::PUSH_CONSTANT => pointer(Base::PushConstant),
+            other => {
+                return Err(d::ShaderError::CompilationFailed(format!(
+                    "Unsupported storage class {}",
+                    other
+                )))
+            }
+        })
+    }
+
+    // Registers an input or output variable of the entry point.
+    fn add_interface(&mut self, id: Id) {
+        let module = Arc::clone(&self.module);
+        let (slot, ty) = match self.constants[id as usize] {
+            Some(Value::Pointer(Pointer {
+                base: Base::Variable(slot),
+                ty,
+                ..
+            })) => (slot, ty),
+            _ => return,
+        };
+        let output = module
+            .globals
+            .iter()
+            .map(|&instruction| (instruction.opcode, module.operands(instruction)))
+            .any(|(opcode, ops)| {
+                opcode == op::VARIABLE && ops[1] == id && ops[2] == storage::OUTPUT
+            });
+        let list = if output {
+            &mut self.outputs
+        } else {
+            &mut self.inputs
+        };
+
+        if let Some(builtin) = module.decoration_value(id, NO_MEMBER, decoration::BUILT_IN) {
+            self.builtins.push(BuiltinVariable {
+                builtin,
+                slot,
+                offset: 0,
+                words: module.words(ty),
+            });
+        } else if let Some(location) = module.decoration_value(id, NO_MEMBER, decoration::LOCATION)
+        {
+            list.push(Interface {
+                slot,
+                offset: 0,
+                ty,
+                location,
+                component: module
+                    .decoration_value(id, NO_MEMBER, decoration::COMPONENT)
+                    .unwrap_or(0),
+                index: module
+                    .decoration_value(id, NO_MEMBER, decoration::INDEX)
+                    .unwrap_or(0),
+                flat: module.decoration(id, NO_MEMBER, decoration::FLAT).is_some(),
+                no_perspective: module
+                    .decoration(id, NO_MEMBER, decoration::NO_PERSPECTIVE)
+                    .is_some(),
+            });
+        } else if let Type::Struct(ref members) = *module.ty(ty) {
+            // Blocks of built-in variables, like `gl_PerVertex`, or of user variables.
+            for (index, &member) in members.iter().enumerate() {
+                let (offset, _) = module.flat_member(ty, index as u32);
+                let index = index as u32;
+                if let Some(builtin) = module.decoration_value(ty, index, decoration::BUILT_IN) {
+                    self.builtins.push(BuiltinVariable {
+                        builtin,
+                        slot,
+                        offset,
+                        words: module.words(member),
+                    });
+                } else if let Some(location) =
+                    module.decoration_value(ty, index, decoration::LOCATION)
+                {
+                    list.push(Interface {
+                        slot,
+                        offset,
+                        ty: member,
+                        location,
+                        component: module
+                            .decoration_value(ty, index, decoration::COMPONENT)
+                            .unwrap_or(0),
+                        index: 0,
+                        flat: module.decoration(ty, index, decoration::FLAT).is_some(),
+                        no_perspective: module
+                            .decoration(ty, index, decoration::NO_PERSPECTIVE)
+                            .is_some(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Runs all the invocations of a compute dispatch, returning their number.
+    pub(crate) fn dispatch(
+        &self,
+        resources: &Resources,
+        push_constants: &[u8],
+        groups: [u32; 3],
+    ) -> u64 {
+        let [sx, sy, sz] = self.local_size;
+        let size = (sx * sy * sz) as usize;
+        let mut invocations = (0 .. size)
+            .map(|_| Invocation::new(self))
+            .collect::<Vec<_>>();
+        let mut done = vec![false; size];
+        let words = |v: [u32; 3]| v.to_vec();
+
+        for gz in 0 .. groups[2] {
+            for gy in 0 .. groups[1] {
+                for gx in 0 .. groups[0] {
+                    let mut workgroup = self
+                        .workgroup
+                        .iter()
+                        .map(|&words| vec![0; words])
+                        .collect::<Vec<_>>();
+                    for (index, invocation) in invocations.iter_mut().enumerate() {
+                        let local = [
+                            index as u32 % sx,
+                            index as u32 / sx % sy,
+                            index as u32 / (sx * sy),
+                        ];
+                        let global = [gx * sx + local[0], gy * sy + local[1], gz * sz + local[2]];
+                        invocation.reset(self);
+                        invocation.set_builtin(self, builtin::NUM_WORKGROUPS, &words(groups));
+                        invocation.set_builtin(self, builtin::WORKGROUP_ID, &[gx, gy, gz]);
+                        invocation.set_builtin(self, builtin::LOCAL_INVOCATION_ID, &words(local));
+                        invocation.set_builtin(self, builtin::GLOBAL_INVOCATION_ID, &words(global));
+                        invocation.set_builtin(
+                            self,
+                            builtin::LOCAL_INVOCATION_INDEX,
+                            &[index as u32],
+                        );
+                        invocation.set_builtin(
+                            self,
+                            builtin::WORKGROUP_SIZE,
+                            &words(self.local_size),
+                        );
+                    }
+                    done.fill(false);
+
+                    // Every invocation runs until the next barrier, until all of them are done.
+                    while done.iter().any(|&done| !done) {
+                        for (invocation, done) in invocations.iter_mut().zip(&mut done) {
+                            if *done {
+                                continue;
+                            }
+                            let mut context = Context {
+                                resources,
+                                push_constants,
+                                workgroup: &mut workgroup,
+                            };
+                            match invocation.run(self, &mut context) {
+                                Status::Barrier => {}
+                                Status::Done | Status::Killed => *done = true,
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        groups.iter().map(|&count| count as u64).product::<u64>() * size as u64
+    }
+}
+
+/// State shared by the invocations of a draw or a dispatch.
+pub(crate) struct Context<'a> {
+    pub(crate) resources: &'a Resources,
+    pub(crate) push_constants: &'a [u8],
+    pub(crate) workgroup: &'a mut [Vec<u32>],
+}
+
+impl Context<'_> {
+    fn descriptor(&self, resource: ResourceRef) -> &n::Descriptor {
+        self.resources
+            .get(resource.set as usize)
+            .and_then(|set| set.get(resource.binding as usize))
+            .and_then(|binding| binding.get(resource.index as usize))
+            .unwrap_or_else(|| panic!("Missing descriptor {:?}", resource))
+    }
+
+    // Turns pointers to buffer descriptors and push constants into pointers to memory.
+    fn resolve(&self, module: &Module, pointer: Pointer) -> Pointer {
+        match pointer.base {
+            Base::PushConstant => Pointer {
+                base: Base::Memory {
+                    address: self.push_constants.as_ptr() as *mut u8,
+                    size: self.push_constants.len(),
+                },
+                ..pointer
+            },
+            Base::Resource(resource, false) => match *module.ty(pointer.ty) {
+                Type::Struct(_) => {
+                    let (ref binding, ref range) = *self
+                        .descriptor(resource)
+                        .buffer
+                        .as_ref()
+                        .expect("Missing buffer descriptor");
+                    Pointer {
+                        base: Base::Memory {
+                            address: binding.ptr(range.start),
+                            size: (range.end - range.start) as usize,
+                        },
+                        ..pointer
+                    }
+                }
+                _ => pointer,
+            },
+            _ => pointer,
+        }
+    }
+}
+
+/// Result of running an invocation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Status {
+    Done,
+    /// Reached a control barrier, the invocation can be resumed.
+    Barrier,
+    /// Discarded by the fragment shader.
+    Killed,
+}
+
+enum Flow {
+    Next,
+    Barrier,
+    Kill,
+    Done,
+}
+
+#[derive(Debug)]
+struct Frame {
+    function: usize,
+    block: usize,
+    ip: usize,
+    /// Id of the value receiving the result of the function.
+    result: Option<Id>,
+}
+
+/// Execution state of a single invocation.
+#[derive(Debug)]
+pub(crate) struct Invocation {
+    values: Vec<Option<Value>>,
+    variables: Vec<Vec<u32>>,
+    frames: Vec<Frame>,
+    /// Framebuffer coordinates of the fragment, used by subpass inputs.
+    pub(crate) fragment: [i32; 2],
+}
+
+impl Invocation {
+    pub(crate) fn new(program: &Program) -> Self {
+        Invocation {
+            values: vec![None; program.constants.len()],
+            variables: program
+                .variables
+                .iter()
+                .map(|&words| vec![0; words])
+                .collect(),
+            frames: Vec::new(),
+            fragment: [0; 2],
+        }
+    }
+
+    /// Prepares the invocation to run the entry point from the start.
+    pub(crate) fn reset(&mut self, program: &Program) {
+        for (variable, initializer) in self.variables.iter_mut().zip(&program.initializers) {
+            match initializer.and_then(|id| program.constants[id as usize].as_ref()) {
+                Some(value) => variable.copy_from_slice(value.data()),
+                None => {
+                    for word in variable.iter_mut() {
+                        *word = 0;
+                    }
+                }
+            }
+        }
+        self.frames.clear();
+        self.frames.push(Frame {
+            function: program.function,
+            block: 0,
+            ip: 0,
+            result: None,
+        });
+    }
+
+    /// Writes a built-in input variable, if the program uses it.
+    pub(crate) fn set_builtin(&mut self, program: &Program, builtin: u32, data: &[u32]) {
+        for variable in program.builtins.iter().filter(|b| b.builtin == builtin) {
+            let count = variable.words.min(data.len());
+            self.variables[variable.slot][variable.offset .. variable.offset + count]
+                .copy_from_slice(&data[.. count]);
+        }
+    }
+
+    /// Reads a built-in output variable, if the program uses it.
+    pub(crate) fn builtin(&self, program: &Program, builtin: u32) -> Option<&[u32]> {
+        program
+            .builtins
+            .iter()
+            .find(|b| b.builtin == builtin)
+            .map(|b| &self.variables[b.slot][b.offset .. b.offset + b.words])
+    }
+
+    /// Returns the words of an interface variable.
+    pub(crate) fn interface(&self, program: &Program, interface: &Interface) -> &[u32] {
+        let words = program.module.words(interface.ty);
+        &self.variables[interface.slot][interface.offset .. interface.offset + words]
+    }
+
+    pub(crate) fn interface_mut(&mut self, program: &Program, interface: &Interface) -> &mut [u32] {
+        let words = program.module.words(interface.ty);
+        &mut self.variables[interface.slot][interface.offset .. interface.offset + words]
+    }
+
+    /// Runs the invocation until it completes or reaches a barrier.
+    pub(crate) fn run(&mut self, program: &Program, context: &mut Context) -> Status {
+        let module = &*program.module;
+        loop {
+            let instruction = {
+                let frame = self.frames.last_mut().expect("Invocation is not running");
+                let block = &module.functions[frame.function].blocks[frame.block];
+                let instruction = block.instructions[frame.ip];
+                frame.ip += 1;
+                instruction
+            };
+            match unsafe { self.step(program, context, instruction) } {
+                Flow::Next => {}
+                Flow::Barrier => return Status::Barrier,
+                Flow::Kill => {
+                    self.frames.clear();
+                    return Status::Killed;
+                }
+                Flow::Done => return Status::Done,
+            }
+        }
+    }
+
+    fn value<'a>(&'a self, program: &'a Program, id: Id) -> &'a Value {
+        match program.constants[id as usize] {
+            Some(ref value) => value,
+            None => self.values[id as usize]
+                .as_ref()
+                .unwrap_or_else(|| panic!("Undefined SPIR-V value {}", id)),
+        }
+    }
+
+    fn pointer(&self, program: &Program, id: Id) -> Pointer {
+        match *self.value(program, id) {
+            Value::Pointer(pointer) => pointer,
+            ref other => panic!("Expected a pointer, got {:?}", other),
+        }
+    }
+
+    fn word(&self, program: &Program, id: Id) -> u32 {
+        self.value(program, id).data()[0]
+    }
+
+    unsafe fn step(
+        &mut self,
+        program: &Program,
+        context: &mut Context,
+        instruction: Instruction,
+    ) -> Flow {
+        let module = &*program.module;
+        let ops = module.operands(instruction);
+        let result = match instruction.opcode {
+            op::NOP
+            | op::LINE
+            | op::NO_LINE
+            | op::SELECTION_MERGE
+            | op::LOOP_MERGE
+            | op::MEMORY_BARRIER
+            | op::LIFETIME_START
+            | op::LIFETIME_STOP => return Flow::Next,
+            op::VARIABLE => {
+                // Function variables are initialized every time their function is entered.
+                let pointer = self.pointer(program, ops[1]);
+                if let Base::Variable(slot) = pointer.base {
+                    let initializer = ops
+                        .get(3)
+                        .map(|&id| self.value(program, id).data().to_vec());
+                    let variable = &mut self.variables[slot];
+                    match initializer {
+                        Some(data) => variable.copy_from_slice(&data),
+                        None => {
+                            for word in variable.iter_mut() {
+                                *word = 0;
+                            }
+                        }
+                    }
+                }
+                return Flow::Next;
+            }
+            op::LOAD => {
+                let pointer = self.pointer(program, ops[2]);
+                self.load(module, context, pointer)
+            }
+            op::STORE => {
+                let pointer = self.pointer(program, ops[0]);
+                let value = self.value(program, ops[1]).data().to_vec();
+                self.store(module, context, pointer, &value);
+                return Flow::Next;
+            }
+            op::COPY_MEMORY => {
+                let source = self.pointer(program, ops[1]);
+                let value = self.load(module, context, source);
+                let target = self.pointer(program, ops[0]);
+                self.store(module, context, target, value.data());
+                return Flow::Next;
+            }
+            op::ACCESS_CHAIN | op::IN_BOUNDS_ACCESS_CHAIN => {
+                let pointer = self.pointer(program, ops[2]);
+                Value::Pointer(self.access_chain(program, context, pointer, &ops[3 ..]))
+            }
+            op::ARRAY_LENGTH => {
+                let pointer = context.resolve(module, self.pointer(program, ops[2]));
+                let (offset, _) = module.block_member(pointer.ty, ops[3]);
+                let array = match *module.ty(pointer.ty) {
+                    Type::Struct(ref members) => members[ops[3] as usize],
+                    _ => panic!("Expected a block"),
+                };
+                let length = match pointer.base {
+                    Base::Memory { size, .. } => {
+                        size.saturating_sub(pointer.offset + offset) / module.array_stride(array)
+                    }
+                    _ => panic!("Expected a buffer"),
+                };
+                Value::Data(vec![length as u32])
+            }
+            op::FUNCTION_CALL => {
+                let function = module.function_index[&ops[2]];
+                for (&parameter, &argument) in
+                    module.functions[function].parameters.iter().zip(&ops[3 ..])
+                {
+                    let value = self.value(program, argument).clone();
+                    self.values[parameter as usize] = Some(value);
+                }
+                self.frames.push(Frame {
+                    function,
+                    block: 0,
+                    ip: 0,
+                    result: Some(ops[1]),
+                });
+                return Flow::Next;
+            }
+            op::BRANCH => {
+                self.branch(program, ops[0]);
+                return Flow::Next;
+            }
+            op::BRANCH_CONDITIONAL => {
+                let target = if self.word(program, ops[0]) != 0 {
+                    ops[1]
+                } else {
+                    ops[2]
+                };
+                self.branch(program, target);
+                return Flow::Next;
+            }
+            op::SWITCH => {
+                let selector = self.word(program, ops[0]);
+                let target = ops[2 ..]
+                    .chunks(2)
+                    .find(|case| case[0] == selector)
+                    .map_or(ops[1], |case| case[1]);
+                self.branch(program, target);
+                return Flow::Next;
+            }
+            op::RETURN => return self.return_value(None),
+            op::RETURN_VALUE => {
+                let value = self.value(program, ops[0]).clone();
+                return self.return_value(Some(value));
+            }
+            op::KILL | op::TERMINATE_INVOCATION | op::DEMOTE_TO_HELPER_INVOCATION => {
+                return Flow::Kill
+            }
+            op::UNREACHABLE => panic!("Reached an unreachable SPIR-V block"),
+            op::CONTROL_BARRIER => return Flow::Barrier,
+            op::SAMPLED_IMAGE => match (self.value(program, ops[2]), self.value(program, ops[3])) {
+                (&Value::Image(image), &Value::Sampler(sampler)) => {
+                    Value::SampledImage(image, sampler)
+                }
+                other => panic!("Invalid sampled image operands {:?}", other),
+            },
+            op::IMAGE => match *self.value(program, ops[2]) {
+                Value::SampledImage(image, _) => Value::Image(image),
+                ref other => panic!("Expected a sampled image, got {:?}", other),
+            },
+            op::IMAGE_SAMPLE_IMPLICIT_LOD ..= op::IMAGE_READ
+            | op::IMAGE_QUERY_SIZE_LOD
+            | op::IMAGE_QUERY_SIZE
+            | op::IMAGE_QUERY_LEVELS
+            | op::IMAGE_QUERY_SAMPLES => self.image(program, context, instruction.opcode, ops),
+            op::IMAGE_WRITE => {
+                let texel = self.value(program, ops[2]).data();
+                let mut words = [0, 0, 0, 0];
+                words[.. texel.len()].copy_from_slice(texel);
+                let (view, coordinate, layer) = self.image_texel(program, context, ops[0], ops[1]);
+                match view {
+                    ImageRef::View(view) => {
+                        if let Some(data) = texel_at(view, 0, layer, coordinate) {
+                            texel::encode(view.format, data, words);
+                        }
+                    }
+                    ImageRef::Buffer(view) => {
+                        if let Some(data) = buffer_texel(view, coordinate[0]) {
+                            texel::encode(view.format, data, words);
+                        }
+                    }
+                }
+                return Flow::Next;
+            }
+            op::ATOMIC_LOAD ..= op::ATOMIC_XOR => {
+                match self.atomic(program, context, instruction.opcode, ops) {
+                    Some(value) => Value::Data(vec![value]),
+                    None => return Flow::Next,
+                }
+            }
+            opcode => evaluate(module, opcode, ops, &|id| self.value(program, id))
+                .unwrap_or_else(|| panic!("Unsupported SPIR-V instruction {}", opcode)),
+        };
+        self.values[ops[1] as usize] = Some(result);
+        Flow::Next
+    }
+
+    fn branch(&mut self, program: &Program, label: Id) {
+        let module = &*program.module;
+        let (function, from) = {
+            let frame = self.frames.last().unwrap();
+            let function = &module.functions[frame.function];
+            (function, function.blocks[frame.block].label)
+        };
+        let block = function.labels[&label];
+
+        // Phi instructions select their values from the previous block all at once.
+        let mut phis = Vec::new();
+        for &instruction in &function.blocks[block].instructions {
+            if instruction.opcode != op::PHI {
+                break;
+            }
+            let ops = module.operands(instruction);
+            let value = ops[2 ..]
+                .chunks(2)
+                .find(|pair| pair[1] == from)
+                .map(|pair| self.value(program, pair[0]).clone())
+                .expect("Missing phi predecessor");
+            phis.push((ops[1], value));
+        }
+
+        let frame = self.frames.last_mut().unwrap();
+        frame.block = block;
+        frame.ip = phis.len();
+        for (id, value) in phis {
+            self.values[id as usize] = Some(value);
+        }
+    }
+
+    fn return_value(&mut self, value: Option<Value>) -> Flow {
+        let frame = self.frames.pop().unwrap();
+        if let (Some(result), Some(value)) = (frame.result, value) {
+            self.values[result as usize] = Some(value);
+        }
+        if self.frames.is_empty() {
+            Flow::Done
+        } else {
+            Flow::Next
+        }
+    }
+
+    fn access_chain(
+        &self,
+        program: &Program,
+        context: &Context,
+        mut pointer: Pointer,
+        indices: &[Id],
+    ) -> Pointer {
+        let module = &*program.module;
+        for &id in indices {
+            let index = self.word(program, id);
+            pointer = context.resolve(module, pointer);
+            pointer = match pointer.base {
+                Base::Resource(mut resource, true) => {
+                    resource.index = index;
+                    let element = match *module.ty(pointer.ty) {
+                        Type::Array { element, .. } | Type::RuntimeArray(element) => element,
+                        _ => unreachable!(),
+                    };
+                    Pointer {
+                        base: Base::Resource(resource, false),
+                        ty: element,
+                        ..pointer
+                    }
+                }
+                Base::Memory { .. } => {
+                    let index = index as usize;
+                    match *module.ty(pointer.ty) {
+                        Type::Struct(ref members) => {
+                            let (offset, layout) = module.block_member(pointer.ty, index as u32);
+                            Pointer {
+                                offset: pointer.offset + offset,
+                                ty: members[index],
+                                layout,
+                                ..pointer
+                            }
+                        }
+                        Type::Array { element, .. } | Type::RuntimeArray(element) => Pointer {
+                            offset: pointer.offset + index * module.array_stride(pointer.ty),
+                            ty: element,
+                            layout: Layout {
+                                component_stride: 4,
+                                ..pointer.layout
+                            },
+                            ..pointer
+                        },
+                        Type::Matrix { column, .. } => {
+                            let layout = pointer.layout;
+                            let (offset, component_stride) = if layout.row_major {
+                                (index * 4, layout.matrix_stride)
+                            } else {
+                                (index * layout.matrix_stride, 4)
+                            };
+                            Pointer {
+                                offset: pointer.offset + offset,
+                                ty: column,
+                                layout: Layout {
+                                    component_stride,
+                                    ..layout
+                                },
+                                ..pointer
+                            }
+                        }
+                        Type::Vector { component, .. } => Pointer {
+                            offset: pointer.offset + index * pointer.layout.component_stride,
+                            ty: component,
+                            ..pointer
+                        },
+                        ref other => panic!("Invalid access chain into {:?}", other),
+                    }
+                }
+                Base::Variable(_) | Base::Workgroup(_) => {
+                    let (offset, ty) = module.flat_member(pointer.ty, index);
+                    Pointer {
+                        offset: pointer.offset + offset,
+                        ty,
+                        ..pointer
+                    }
+                }
+                _ => panic!("Invalid access chain base {:?}", pointer),
+            };
+        }
+        pointer
+    }
+
+    unsafe fn load(&self, module: &Module, context: &Context, pointer: Pointer) -> Value {
+        let pointer = context.resolve(module, pointer);
+        let words = module.words(pointer.ty);
+        match pointer.base {
+            Base::Variable(slot) => {
+                Value::Data(self.variables[slot][pointer.offset .. pointer.offset + words].to_vec())
+            }
+            Base::Workgroup(slot) => Value::Data(
+                context.workgroup[slot][pointer.offset .. pointer.offset + words].to_vec(),
+            ),
+            Base::Memory { address, size } => {
+                let mut data = Vec::with_capacity(words);
+                let memory = Memory { address, size };
+                memory.load(
+                    module,
+                    pointer.offset,
+                    pointer.ty,
+                    pointer.layout,
+                    &mut data,
+                );
+                Value::Data(data)
+            }
+            Base::Resource(resource, _) => match *module.ty(pointer.ty) {
+                Type::Image { .. } => Value::Image(resource),
+                Type::Sampler => Value::Sampler(resource),
+                Type::SampledImage => Value::SampledImage(resource, resource),
+                ref other => panic!("Unexpected resource type {:?}", other),
+            },
+            Base::PushConstant => unreachable!(),
+        }
+    }
+
+    unsafe fn store(
+        &mut self,
+        module: &Module,
+        context: &mut Context,
+        pointer: Pointer,
+        data: &[u32],
+    ) {
+        let pointer = context.resolve(module, pointer);
+        let range = pointer.offset .. pointer.offset + data.len();
+        match pointer.base {
+            Base::Variable(slot) => self.variables[slot][range].copy_from_slice(data),
+            Base::Workgroup(slot) => context.workgroup[slot][range].copy_from_slice(data),
+            Base::Memory { address, size } => {
+                let memory = Memory { address, size };
+                memory.store(
+                    module,
+                    pointer.offset,
+                    pointer.ty,
+                    pointer.layout,
+                    &mut data.iter(),
+                );
+            }
+            _ => panic!("Invalid store to {:?}", pointer),
+        }
+    }
+
+    unsafe fn atomic(
+        &mut self,
+        program: &Program,
+        context: &mut Context,
+        opcode: u32,
+        ops: &[u32],
+    ) -> Option<u32> {
+        let module = &*program.module;
+        if opcode == op::ATOMIC_STORE {
+            let pointer = self.pointer(program, ops[0]);
+            let value = self.word(program, ops[3]);
+            self.store(module, context, pointer, &[value]);
+            return None;
+        }
+        let pointer = self.pointer(program, ops[2]);
+        let old = self.load(module, context, pointer).data()[0];
+        let value = || self.word(program, ops[5]);
+        let new = match opcode {
+            op::ATOMIC_LOAD => old,
+            op::ATOMIC_EXCHANGE => value(),
+            op::ATOMIC_COMPARE_EXCHANGE | op::ATOMIC_COMPARE_EXCHANGE_WEAK => {
+                if old == self.word(program, ops[7]) {
+                    self.word(program, ops[6])
+                } else {
+                    old
+                }
+            }
+            op::ATOMIC_I_INCREMENT => old.wrapping_add(1),
+            op::ATOMIC_I_DECREMENT => old.wrapping_sub(1),
+            op::ATOMIC_I_ADD => old.wrapping_add(value()),
+            op::ATOMIC_I_SUB => old.wrapping_sub(value()),
+            op::ATOMIC_S_MIN => (old as i32).min(value() as i32) as u32,
+            op::ATOMIC_U_MIN => old.min(value()),
+            op::ATOMIC_S_MAX => (old as i32).max(value() as i32) as u32,
+            op::ATOMIC_U_MAX => old.max(value()),
+            op::ATOMIC_AND => old & value(),
+            op::ATOMIC_OR => old | value(),
+            op::ATOMIC_XOR => old ^ value(),
+            _ => unreachable!(),
+        };
+        if new != old {
+            self.store(module, context, pointer, &[new]);
+        }
+        Some(old)
+    }
+
+    // Returns the image, the integer coordinates and the layer addressed by a read or a write.
+    fn image_texel<'a>(
+        &self,
+        program: &Program,
+        context: &'a Context,
+        image: Id,
+        coordinate: Id,
+    ) -> (ImageRef<'a>, [i32; 3], i::Layer) {
+        let module = &*program.module;
+        let resource = match *self.value(program, image) {
+            Value::Image(resource) | Value::SampledImage(resource, _) => resource,
+            ref other => panic!("Expected an image, got {:?}", other),
+        };
+        let descriptor = context.descriptor(resource);
+        let coords = self.value(program, coordinate).data();
+        let coord = |i: usize| coords.get(i).map_or(0, |&c| c as i32);
+        if let Some(ref view) = descriptor.texel_buffer {
+            return (ImageRef::Buffer(view), [coord(0), 0, 0], 0);
+        }
+        let view = descriptor.image.as_ref().expect("Missing image descriptor");
+        let subpass = match *module.ty(module.type_of[image as usize]) {
+            Type::Image { dim } => dim == DIM_SUBPASS_DATA,
+            _ => false,
+        };
+        if subpass {
+            // Subpass inputs are read at the location of the fragment.
+            let [x, y] = self.fragment;
+            return (ImageRef::View(view), [x + coord(0), y + coord(1), 0], 0);
+        }
+        let (coordinate, layer) = match view.kind {
+            i::ViewKind::D1 => ([coord(0), 0, 0], 0),
+            i::ViewKind::D1Array => ([coord(0), 0, 0], coord(1)),
+            i::ViewKind::D2 => ([coord(0), coord(1), 0], 0),
+            i::ViewKind::D2Array | i::ViewKind::Cube | i::ViewKind::CubeArray => {
+                ([coord(0), coord(1), 0], coord(2))
+            }
+            i::ViewKind::D3 => ([coord(0), coord(1), coord(2)], 0),
+        };
+        (ImageRef::View(view), coordinate, layer.max(0) as i::Layer)
+    }
+
+    unsafe fn image(
+        &self,
+        program: &Program,
+        context: &Context,
+        opcode: u32,
+        ops: &[u32],
+    ) -> Value {
+        let module = &*program.module;
+        match opcode {
+            op::IMAGE_FETCH | op::IMAGE_READ => {
+                let operands = ImageOperands::parse(ops.get(4 ..).unwrap_or(&[]), |id| {
+                    self.value(program, id).data()
+                });
+                let (image, coordinate, layer) = self.image_texel(program, context, ops[2], ops[3]);
+                let texel = match image {
+                    ImageRef::View(view) => {
+                        let level = operands.lod.map_or(0, |lod| lod[0]) as i::Level;
+                        let coordinate = [
+                            coordinate[0] + operands.offset[0],
+                            coordinate[1] + operands.offset[1],
+                            coordinate[2] + operands.offset[2],
+                        ];
+                        texel_at(view, level, layer, coordinate)
+                            .map(|data| swizzle(view, texel::decode(view.format, data)))
+                            .unwrap_or([0; 4])
+                    }
+                    ImageRef::Buffer(view) => buffer_texel(view, coordinate[0])
+                        .map(|data| texel::decode(view.format, data))
+                        .unwrap_or([0; 4]),
+                };
+                Value::Data(texel[.. module.words(ops[0]).min(4)].to_vec())
+            }
+            op::IMAGE_QUERY_SIZE_LOD | op::IMAGE_QUERY_SIZE => {
+                let level = if opcode == op::IMAGE_QUERY_SIZE_LOD {
+                    self.word(program, ops[3]) as i::Level
+                } else {
+                    0
+                };
+                let resource = match *self.value(program, ops[2]) {
+                    Value::Image(resource) | Value::SampledImage(resource, _) => resource,
+                    ref other => panic!("Expected an image, got {:?}", other),
+                };
+                let descriptor = context.descriptor(resource);
+                if let Some(ref view) = descriptor.texel_buffer {
+                    let texel_size = view.format.surface_desc().bits as u64 / 8;
+                    return Value::Data(vec![(view.size / texel_size) as u32]);
+                }
+                let view = descriptor.image.as_ref().expect("Missing image descriptor");
+                let extent = view.level_extent(level);
+                let layers = (view.range.layers.end - view.range.layers.start) as u32;
+                let size = match view.kind {
+                    i::ViewKind::D1 => vec![extent.width],
+                    i::ViewKind::D1Array => vec![extent.width, layers],
+                    i::ViewKind::D2 | i::ViewKind::Cube => vec![extent.width, extent.height],
+                    i::ViewKind::D2Array => vec![extent.width, extent.height, layers],
+                    i::ViewKind::CubeArray => vec![extent.width, extent.height, layers / 6],
+                    i::ViewKind::D3 => vec![extent.width, extent.height, extent.depth],
+                };
+                Value::Data(size)
+            }
+            op::IMAGE_QUERY_LEVELS => {
+                let view = match *self.value(program, ops[2]) {
+                    Value::Image(resource) | Value::SampledImage(resource, _) => {
+                        context.descriptor(resource).image.as_ref()
+                    }
+                    ref other => panic!("Expected an image, got {:?}", other),
+                };
+                let levels = view.map_or(0, |view| view.range.levels.end - view.range.levels.start);
+                Value::Data(vec![levels as u32])
+            }
+            op::IMAGE_QUERY_SAMPLES => Value::Data(vec![1]),
+            _ => {
+                // Sampling and gathering operations.
+                let (image, sampler) = match *self.value(program, ops[2]) {
+                    Value::SampledImage(image, sampler) => (image, sampler),
+                    ref other => panic!("Expected a sampled image, got {:?}", other),
+                };
+                let view = context
+                    .descriptor(image)
+                    .image
+                    .as_ref()
+                    .expect("Missing image descriptor");
+                let info = &context
+                    .descriptor(sampler)
+                    .sampler
+                    .as_ref()
+                    .expect("Missing sampler descriptor")
+                    .info;
+                let mut coords = self
+                    .value(program, ops[3])
+                    .data()
+                    .iter()
+                    .map(|&c| f32::from_bits(c))
+                    .collect::<Vec<_>>();
+                let (dref, gather, proj) = match opcode {
+                    op::IMAGE_SAMPLE_DREF_IMPLICIT_LOD
+                    | op::IMAGE_SAMPLE_DREF_EXPLICIT_LOD
+                    | op::IMAGE_SAMPLE_PROJ_DREF_IMPLICIT_LOD
+                    | op::IMAGE_SAMPLE_PROJ_DREF_EXPLICIT_LOD
+                    | op::IMAGE_DREF_GATHER => (
+                        Some(f32::from_bits(self.word(program, ops[4]))),
+                        None,
+                        (op::IMAGE_SAMPLE_PROJ_IMPLICIT_LOD
+                            ..= op::IMAGE_SAMPLE_PROJ_DREF_EXPLICIT_LOD)
+                            .contains(&opcode),
+                    ),
+                    op::IMAGE_GATHER => (None, Some(self.word(program, ops[4])), false),
+                    _ => (None, None, opcode >= op::IMAGE_SAMPLE_PROJ_IMPLICIT_LOD),
+                };
+                let first_operand = if dref.is_some() || gather.is_some() {
+                    5
+                } else {
+                    4
+                };
+                let operands =
+                    ImageOperands::parse(ops.get(first_operand ..).unwrap_or(&[]), |id| {
+                        self.value(program, id).data()
+                    });
+                if proj {
+                    // The last coordinate divides the others.
+                    let q = coords.pop().unwrap();
+                    for c in &mut coords {
+                        *c /= q;
+                    }
+                }
+                let lod = operands.lod.map_or(0.0, |lod| f32::from_bits(lod[0]))
+                    + operands.bias.map_or(0.0, f32::from_bits);
+                let sample = Sample {
+                    view,
+                    info,
+                    offset: operands.offset,
+                    dref,
+                    // Gathered comparisons return the results of the four comparisons.
+                    gather: if opcode == op::IMAGE_DREF_GATHER {
+                        Some(0)
+                    } else {
+                        gather
+                    },
+                };
+                let texel = sample.sample(&coords, lod);
+                let words = module.words(ops[0]).min(4);
+                Value::Data(texel[.. words].to_vec())
+            }
+        }
+    }
+}
+
+/// Buffer memory with an explicit layout.
+struct Memory {
+    address: *mut u8,
+    size: usize,
+}
+
+impl Memory {
+    // Out of bounds reads return zero, and out of bounds writes are discarded.
+    unsafe fn read(&self, offset: usize) -> u32 {
+        if offset + 4 <= self.size {
+            ptr::read_unaligned(self.address.add(offset) as *const u32)
+        } else {
+            0
+        }
+    }
+
+    unsafe fn write(&self, offset: usize, value: u32) {
+        if offset + 4 <= self.size {
+            ptr::write_unaligned(self.address.add(offset) as *mut u32, value);
+        }
+    }
+
+    unsafe fn load(
+        &self,
+        module: &Module,
+        offset: usize,
+        ty: Id,
+        layout: Layout,
+        data: &mut Vec<u32>,
+    ) {
+        match *module.ty(ty) {
+            Type::Bool | Type::Int | Type::Float => data.push(self.read(offset)),
+            Type::Vector { count, .. } => {
+                for i in 0 .. count as usize {
+                    data.push(self.read(offset + i * layout.component_stride));
+                }
+            }
+            Type::Matrix { column, count } => {
+                let rows = module.words(column);
+                for c in 0 .. count as usize {
+                    for r in 0 .. rows {
+                        data.push(self.read(offset + layout.element_offset(c, r)));
+                    }
+                }
+            }
+            Type::Array { element, length } => {
+                let stride = module.array_stride(ty);
+                for i in 0 .. length as usize {
+                    self.load(module, offset + i * stride, element, layout, data);
+                }
+            }
+            Type::Struct(ref members) => {
+                for (index, &member) in members.iter().enumerate() {
+                    let (member_offset, layout) = module.block_member(ty, index as u32);
+                    self.load(module, offset + member_offset, member, layout, data);
+                }
+            }
+            ref other => panic!("Unexpected type in buffer memory {:?}", other),
+        }
+    }
+
+    unsafe fn store<'a, I>(
+        &self,
+        module: &Module,
+        offset: usize,
+        ty: Id,
+        layout: Layout,
+        data: &mut I,
+    ) where
+        I: Iterator<Item = &'a u32>,
+    {
+        match *module.ty(ty) {
+            Type::Bool | Type::Int | Type::Float => self.write(offset, *data.next().unwrap()),
+            Type::Vector { count, .. } => {
+                for i in 0 .. count as usize {
+                    self.write(offset + i * layout.component_stride, *data.next().unwrap());
+                }
+            }
+            Type::Matrix { column, count } => {
+                let rows = module.words(column);
+                for c in 0 .. count as usize {
+                    for r in 0 .. rows {
+                        self.write(offset + layout.element_offset(c, r), *data.next().unwrap());
+                    }
+                }
+            }
+            Type::Array { element, length } => {
+                let stride = module.array_stride(ty);
+                for i in 0 .. length as usize {
+                    self.store(module, offset + i * stride, element, layout, data);
+                }
+            }
+            Type::Struct(ref members) => {
+                for (index, &member) in members.iter().enumerate() {
+                    let (member_offset, layout) = module.block_member(ty, index as u32);
+                    self.store(module, offset + member_offset, member, layout, data);
+                }
+            }
+            ref other => panic!("Unexpected type in buffer memory {:?}", other),
+        }
+    }
+}
+
+impl Layout {
+    // Returns the byte offset of an element of a matrix.
+    fn element_offset(&self, column: usize, row: usize) -> usize {
+        if self.row_major {
+            row * self.matrix_stride + column * 4
+        } else {
+            column * self.matrix_stride + row * 4
+        }
+    }
+}
+
+enum ImageRef<'a> {
+    View(&'a n::ImageView),
+    Buffer(&'a n::BufferView),
+}
+
+#[derive(Default)]
+struct ImageOperands<'a> {
+    bias: Option<u32>,
+    lod: Option<&'a [u32]>,
+    offset: [i32; 3],
+}
+
+impl<'a> ImageOperands<'a> {
+    fn parse<F>(ops: &[u32], get: F) -> Self
+    where
+        F: Fn(Id) -> &'a [u32],
+    {
+        let mut operands = ImageOperands::default();
+        let mask = match ops.first() {
+            Some(&mask) => mask,
+            None => return operands,
+        };
+        let mut args = ops[1 ..].iter();
+        let mut next = || get(*args.next().unwrap());
+        if mask & IMAGE_OPERAND_BIAS != 0 {
+            operands.bias = Some(next()[0]);
+        }
+        if mask & IMAGE_OPERAND_LOD != 0 {
+            operands.lod = Some(next());
+        }
+        if mask & IMAGE_OPERAND_GRAD != 0 {
+            // Without derivatives, the base level is always sampled.
+            next();
+            next();
+        }
+        if mask & (IMAGE_OPERAND_CONST_OFFSET | IMAGE_OPERAND_OFFSET) != 0 {
+            for (o, &c) in operands.offset.iter_mut().zip(next()) {
+                *o = c as i32;
+            }
+        }
+        if mask & IMAGE_OPERAND_CONST_OFFSETS != 0 {
+            next();
+        }
+        if mask & IMAGE_OPERAND_SAMPLE != 0 {
+            next();
+        }
+        if mask & IMAGE_OPERAND_MIN_LOD != 0 {
+            next();
+        }
+        operands
+    }
+}
+
+// Returns the bytes of a texel of a view, or `None` if it's out of bounds.
+unsafe fn texel_at<'a>(
+    view: &n::ImageView,
+    level: i::Level,
+    layer: i::Layer,
+    coordinate: [i32; 3],
+) -> Option<&'a mut [u8]> {
+    let levels = view.range.levels.end - view.range.levels.start;
+    let layers = view.range.layers.end - view.range.layers.start;
+    if level >= levels || layer >= layers {
+        return None;
+    }
+    let extent = view.level_extent(level);
+    let [x, y, z] = coordinate;
+    if x < 0
+        || y < 0
+        || z < 0
+        || x as u32 >= extent.width
+        || y as u32 >= extent.height
+        || z as u32 >= extent.depth
+    {
+        return None;
+    }
+    Some(view.texel(level, layer, i::Offset { x, y, z }))
+}
+
+unsafe fn buffer_texel<'a>(view: &n::BufferView, index: i32) -> Option<&'a mut [u8]> {
+    let size = view.format.surface_desc().bits as u64 / 8;
+    if index < 0 || (index as u64 + 1) * size > view.size {
+        return None;
+    }
+    let ptr = view.binding.ptr(index as u64 * size);
+    Some(std::slice::from_raw_parts_mut(ptr, size as usize))
+}
+
+// Applies the component mapping of a view to a texel.
+fn swizzle(view: &n::ImageView, texel: texel::Texel) -> texel::Texel {
+    use hal::format::Component as C;
+    let one = if texel::is_float(view.format) {
+        1f32.to_bits()
+    } else {
+        1
+    };
+    let component = |c| match c {
+        C::Zero => 0,
+        C::One => one,
+        C::R => texel[0],
+        C::G => texel[1],
+        C::B => texel[2],
+        C::A => texel[3],
+    };
+    let s = view.swizzle;
+    [
+        component(s.0),
+        component(s.1),
+        component(s.2),
+        component(s.3),
+    ]
+}
+
+/// Parameters of a sampling operation.
+struct Sample<'a> {
+    view: &'a n::ImageView,
+    info: &'a i::SamplerInfo,
+    offset: [i32; 3],
+    dref: Option<f32>,
+    /// Component returned by gather operations.
+    gather: Option<u32>,
+}
+
+impl Sample<'_> {
+    unsafe fn sample(&self, coords: &[f32], lod: f32) -> texel::Texel {
+        let c = |i: usize| coords.get(i).cloned().unwrap_or(0.0);
+        let layer = |c: f32| c.round().max(0.0) as i::Layer;
+        let (uvw, dims, layer) = match self.view.kind {
+            i::ViewKind::D1 => ([c(0), 0.0, 0.0], 1, 0),
+            i::ViewKind::D1Array => ([c(0), 0.0, 0.0], 1, layer(c(1))),
+            i::ViewKind::D2 => ([c(0), c(1), 0.0], 2, 0),
+            i::ViewKind::D2Array => ([c(0), c(1), 0.0], 2, layer(c(2))),
+            i::ViewKind::D3 => ([c(0), c(1), c(2)], 3, 0),
+            i::ViewKind::Cube | i::ViewKind::CubeArray => {
+                let (face, s, t) = cube_face(c(0), c(1), c(2));
+                let array = if self.view.kind == i::ViewKind::CubeArray {
+                    layer(c(3)) * 6
+                } else {
+                    0
+                };
+                ([s, t, 0.0], 2, array + face)
+            }
+        };
+
+        let levels = (self.view.range.levels.end - self.view.range.levels.start) as f32;
+        let min_lod: f32 = self.info.lod_range.start.into();
+        let max_lod: f32 = self.info.lod_range.end.into();
+        let bias: f32 = self.info.lod_bias.into();
+        let lod = (lod + bias)
+            .max(min_lod)
+            .min(max_lod)
+            .min(levels - 1.0)
+            .max(0.0);
+        let filter = if lod > 0.0 {
+            self.info.min_filter
+        } else {
+            self.info.mag_filter
+        };
+
+        if !texel::is_float(self.view.format) && self.gather.is_none() {
+            // Integer images are never filtered.
+            let level = lod.round() as i::Level;
+            return self.filter(i::Filter::Nearest, level, uvw, dims, layer);
+        }
+        match self.info.mip_filter {
+            i::Filter::Linear if lod.fract() != 0.0 => {
+                let low = self.filter(filter, lod.floor() as i::Level, uvw, dims, layer);
+                let high = self.filter(filter, lod.ceil() as i::Level, uvw, dims, layer);
+                let t = lod.fract();
+                let mut texel = [0; 4];
+                for i in 0 .. 4 {
+                    let (low, high) = (f32::from_bits(low[i]), f32::from_bits(high[i]));
+                    texel[i] = (low * (1.0 - t) + high * t).to_bits();
+                }
+                texel
+            }
+            _ => self.filter(filter, lod.round() as i::Level, uvw, dims, layer),
+        }
+    }
+
+    // Filters the texels of a single level.
+    unsafe fn filter(
+        &self,
+        filter: i::Filter,
+        level: i::Level,
+        uvw: [f32; 3],
+        dims: usize,
+        layer: i::Layer,
+    ) -> texel::Texel {
+        let extent = self.view.level_extent(level);
+        let size = [
+            extent.width as f32,
+            extent.height as f32,
+            extent.depth as f32,
+        ];
+        let wrap = [
+            self.info.wrap_mode.0,
+            self.info.wrap_mode.1,
+            self.info.wrap_mode.2,
+        ];
+        let scale = |axis: usize| {
+            if self.info.normalized {
+                uvw[axis] * size[axis]
+            } else {
+                uvw[axis]
+            }
+        };
+
+        if filter == i::Filter::Nearest && self.gather.is_none() {
+            let mut coordinate = [0; 3];
+            for (axis, c) in coordinate.iter_mut().enumerate().take(dims) {
+                *c = scale(axis).floor() as i32 + self.offset[axis];
+            }
+            return self.fetch(level, layer, coordinate, wrap);
+        }
+
+        // Linear filtering blends the 2x2(x2) texels around the coordinate.
+        let mut base = [0; 3];
+        let mut weights = [0.0; 3];
+        for axis in 0 .. dims {
+            let coordinate = scale(axis) - 0.5;
+            base[axis] = coordinate.floor() as i32 + self.offset[axis];
+            weights[axis] = coordinate - coordinate.floor();
+        }
+        let corners = 1 << dims;
+        let mut taps = Vec::with_capacity(corners);
+        for corner in 0 .. corners {
+            let mut coordinate = base;
+            let mut weight = 1.0;
+            for axis in 0 .. dims {
+                if corner & (1 << axis) != 0 {
+                    coordinate[axis] += 1;
+                    weight *= weights[axis];
+                } else {
+                    weight *= 1.0 - weights[axis];
+                }
+            }
+            taps.push((self.fetch(level, layer, coordinate, wrap), weight));
+        }
+
+        if let Some(component) = self.gather {
+            // Gathered texels are ordered as (i0, j1), (i1, j1), (i1, j0), (i0, j0).
+            let value = |tap: usize| taps[tap].0[component as usize];
+            return [value(2), value(3), value(1), value(0)];
+        }
+        let mut texel = [0.0f32; 4];
+        for &(tap, weight) in &taps {
+            for i in 0 .. 4 {
+                texel[i] += f32::from_bits(tap[i]) * weight;
+            }
+        }
+        [
+            texel[0].to_bits(),
+            texel[1].to_bits(),
+            texel[2].to_bits(),
+            texel[3].to_bits(),
+        ]
+    }
+
+    // Reads a single texel, applying the wrapping modes, the comparison and the swizzle.
+    unsafe fn fetch(
+        &self,
+        level: i::Level,
+        layer: i::Layer,
+        mut coordinate: [i32; 3],
+        wrap: [i::WrapMode; 3],
+    ) -> texel::Texel {
+        let extent = self.view.level_extent(level);
+        let size = [
+            extent.width as i32,
+            extent.height as i32,
+            extent.depth as i32,
+        ];
+        for axis in 0 .. 3 {
+            let (c, size) = (coordinate[axis], size[axis]);
+            coordinate[axis] = match wrap[axis] {
+                i::WrapMode::Tile => c.rem_euclid(size),
+                i::WrapMode::Mirror => {
+                    let c = c.rem_euclid(2 * size);
+                    if c >= size {
+                        2 * size - 1 - c
+                    } else {
+                        c
+                    }
+                }
+                i::WrapMode::Clamp => c.max(0).min(size - 1),
+                i::WrapMode::Border => c,
+            };
+        }
+        let texel = match texel_at(self.view, level, layer, coordinate) {
+            Some(data) => texel::decode(self.view.format, data),
+            None => {
+                let border: [f32; 4] = self.info.border.into();
+                [
+                    border[0].to_bits(),
+                    border[1].to_bits(),
+                    border[2].to_bits(),
+                    border[3].to_bits(),
+                ]
+            }
+        };
+        match self.dref {
+            Some(dref) => {
+                let comparison = self.info.comparison.unwrap_or(pso::Comparison::Always);
+                let passed = compare(comparison, dref, f32::from_bits(texel[0]));
+                let value = if passed { 1.0f32 } else { 0.0 };
+                [value.to_bits(), 0, 0, 1f32.to_bits()]
+            }
+            None => swizzle(self.view, texel),
+        }
+    }
+}
+
+/// Evaluates a comparison function, with the reference value on the left side.
+pub(crate) fn compare<T: PartialOrd>(comparison: pso::Comparison, reference: T, value: T) -> bool {
+    match comparison {
+        pso::Comparison::Never => false,
+        pso::Comparison::Less => reference < value,
+        pso::Comparison::Equal => reference == value,
+        pso::Comparison::LessEqual => reference <= value,
+        pso::Comparison::Greater => reference > value,
+        pso::Comparison::NotEqual => reference != value,
+        pso::Comparison::GreaterEqual => reference >= value,
+        pso::Comparison::Always => true,
+    }
+}
+
+// Selects the face of a cube map, returning it with the normalized coordinates on the face.
+fn cube_face(x: f32, y: f32, z: f32) -> (i::Layer, f32, f32) {
+    let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+    let (face, sc, tc, ma) = if ax >= ay && ax >= az {
+        if x >= 0.0 {
+            (0, -z, -y, ax)
+        } else {
+            (1, z, -y, ax)
+        }
+    } else if ay >= az {
+        if y >= 0.0 {
+            (2, x, z, ay)
+        } else {
+            (3, x, -z, ay)
+        }
+    } else if z >= 0.0 {
+        (4, x, -y, az)
+    } else {
+        (5, -x, -y, az)
+    };
+    (face, (sc / ma + 1.0) * 0.5, (tc / ma + 1.0) * 0.5)
+}
+
+fn f(word: u32) -> f32 {
+    f32::from_bits(word)
+}
+
+fn map<F: Fn(u32) -> u32>(a: &[u32], op: F) -> Value {
+    Value::Data(a.iter().map(|&a| op(a)).collect())
+}
+
+// Applies an operation component-wise, broadcasting scalar operands.
+fn map2<F: Fn(u32, u32) -> u32>(a: &[u32], b: &[u32], op: F) -> Value {
+    let count = a.len().max(b.len());
+    Value::Data(
+        (0 .. count)
+            .map(|i| op(a[i.min(a.len() - 1)], b[i.min(b.len() - 1)]))
+            .collect(),
+    )
+}
+
+fn map3<F: Fn(u32, u32, u32) -> u32>(a: &[u32], b: &[u32], c: &[u32], op: F) -> Value {
+    let count = a.len().max(b.len()).max(c.len());
+    Value::Data(
+        (0 .. count)
+            .map(|i| {
+                op(
+                    a[i.min(a.len() - 1)],
+                    b[i.min(b.len() - 1)],
+                    c[i.min(c.len() - 1)],
+                )
+            })
+            .collect(),
+    )
+}
+
+fn fmap<F: Fn(f32) -> f32>(a: &[u32], op: F) -> Value {
+    map(a, |a| op(f(a)).to_bits())
+}
+
+fn fmap2<F: Fn(f32, f32) -> f32>(a: &[u32], b: &[u32], op: F) -> Value {
+    map2(a, b, |a, b| op(f(a), f(b)).to_bits())
+}
+
+fn fmap3<F: Fn(f32, f32, f32) -> f32>(a: &[u32], b: &[u32], c: &[u32], op: F) -> Value {
+    map3(a, b, c, |a, b, c| op(f(a), f(b), f(c)).to_bits())
+}
+
+fn dot(a: &[u32], b: &[u32]) -> f32 {
+    a.iter().zip(b).map(|(&a, &b)| f(a) * f(b)).sum()
+}
+
+fn bits(value: u32, offset: u32, count: u32) -> u32 {
+    if count == 0 {
+        0
+    } else if count >= 32 {
+        value
+    } else {
+        (value >> offset) & ((1 << count) - 1)
+    }
+}
+
+fn mask(offset: u32, count: u32) -> u32 {
+    if count >= 32 {
+        !0
+    } else {
+        ((1u32 << count) - 1) << offset
+    }
+}
+
+// Evaluates an operation without side effects, returning `None` for other instructions.
+fn evaluate<'v>(
+    module: &Module,
+    opcode: u32,
+    ops: &[u32],
+    get: &dyn Fn(Id) -> &'v Value,
+) -> Option<Value> {
+    let arg = |index: usize| get(ops[2 + index]).data();
+    let boolean = |value: bool| value as u32;
+    Some(match opcode {
+        op::UNDEF => Value::Data(vec![0; module.words(ops[0])]),
+        op::COPY_OBJECT | op::COPY_LOGICAL => get(ops[2]).clone(),
+        op::CONVERT_F_TO_U => map(arg(0), |a| f(a) as u32),
+        op::CONVERT_F_TO_S => map(arg(0), |a| f(a) as i32 as u32),
+        op::CONVERT_S_TO_F => map(arg(0), |a| (a as i32 as f32).to_bits()),
+        op::CONVERT_U_TO_F => map(arg(0), |a| (a as f32).to_bits()),
+        // Only 32-bit types are supported, so these are all identities.
+        op::U_CONVERT | op::S_CONVERT | op::F_CONVERT | op::BITCAST => Value::Data(arg(0).to_vec()),
+        op::QUANTIZE_TO_F16 => fmap(arg(0), |a| texel::f16_to_f32(texel::f32_to_f16(a))),
+        op::S_NEGATE => map(arg(0), |a| (a as i32).wrapping_neg() as u32),
+        op::F_NEGATE => fmap(arg(0), |a| -a),
+        op::I_ADD => map2(arg(0), arg(1), u32::wrapping_add),
+        op::F_ADD => fmap2(arg(0), arg(1), |a, b| a + b),
+        op::I_SUB => map2(arg(0), arg(1), u32::wrapping_sub),
+        op::F_SUB => fmap2(arg(0), arg(1), |a, b| a - b),
+        op::I_MUL => map2(arg(0), arg(1), u32::wrapping_mul),
+        op::F_MUL | op::VECTOR_TIMES_SCALAR | op::MATRIX_TIMES_SCALAR => {
+            fmap2(arg(0), arg(1), |a, b| a * b)
+        }
+        op::U_DIV => map2(arg(0), arg(1), |a, b| a.checked_div(b).unwrap_or(0)),
+        op::S_DIV => map2(arg(0), arg(1), |a, b| {
+            (a as i32).checked_div(b as i32).unwrap_or(0) as u32
+        }),
+        op::F_DIV => fmap2(arg(0), arg(1), |a, b| a / b),
+        op::U_MOD => map2(arg(0), arg(1), |a, b| a.checked_rem(b).unwrap_or(0)),
+        op::S_REM => map2(arg(0), arg(1), |a, b| {
+            (a as i32).checked_rem(b as i32).unwrap_or(0) as u32
+        }),
+        op::S_MOD => map2(arg(0), arg(1), |a, b| {
+            let (a, b) = (a as i32, b as i32);
+            let r = a.checked_rem(b).unwrap_or(0);
+            if r != 0 && (r < 0) != (b < 0) {
+                (r + b) as u32
+            } else {
+                r as u32
+            }
+        }),
+        op::F_REM => fmap2(arg(0), arg(1), |a, b| a % b),
+        op::F_MOD => fmap2(arg(0), arg(1), |a, b| a - b * (a / b).floor()),
+        op::VECTOR_TIMES_MATRIX => {
+            let (v, m) = (arg(0), arg(1));
+            Value::Data(
+                m.chunks(v.len())
+                    .map(|column| dot(v, column).to_bits())
+                    .collect(),
+            )
+        }
+        op::MATRIX_TIMES_VECTOR => {
+            let (m, v) = (arg(0), arg(1));
+            let rows = m.len() / v.len();
+            Value::Data(
+                (0 .. rows)
+                    .map(|r| {
+                        let sum: f32 = v
+                            .iter()
+                            .enumerate()
+                            .map(|(c, &v)| f(m[c * rows + r]) * f(v))
+                            .sum();
+                        sum.to_bits()
+                    })
+                    .collect(),
+            )
+        }
+        op::MATRIX_TIMES_MATRIX => {
+            let (a, b) = (arg(0), arg(1));
+            let rows = match *module.ty(ops[0]) {
+                Type::Matrix { column, .. } => module.words(column),
+                _ => panic!("Expected a matrix type"),
+            };
+            let inner = a.len() / rows;
+            let mut result = Vec::with_capacity(b.len() / inner * rows);
+            for column in b.chunks(inner) {
+                for r in 0 .. rows {
+                    let sum: f32 = (0 .. inner)
+                        .map(|k| f(a[k * rows + r]) * f(column[k]))
+                        .sum();
+                    result.push(sum.to_bits());
+                }
+            }
+            Value::Data(result)
+        }
+        op::OUTER_PRODUCT => {
+            let (a, b) = (arg(0), arg(1));
+            Value::Data(
+                b.iter()
+                    .flat_map(|&b| a.iter().map(move |&a| (f(a) * f(b)).to_bits()))
+                    .collect(),
+            )
+        }
+        op::DOT => Value::Data(vec![dot(arg(0), arg(1)).to_bits()]),
+        op::TRANSPOSE => Value::Data(transpose(
+            arg(0),
+            module.words(module.type_of[ops[2] as usize])
+                / matrix_columns(module, module.type_of[ops[2] as usize]),
+        )),
+        op::I_ADD_CARRY | op::I_SUB_BORROW | op::U_MUL_EXTENDED | op::S_MUL_EXTENDED => {
+            let (a, b) = (arg(0), arg(1));
+            let pairs = a.iter().zip(b).map(|(&a, &b)| match opcode {
+                op::I_ADD_CARRY => {
+                    let (sum, carry) = a.overflowing_add(b);
+                    (sum, carry as u32)
+                }
+                op::I_SUB_BORROW => {
+                    let (difference, borrow) = a.overflowing_sub(b);
+                    (difference, borrow as u32)
+                }
+                op::U_MUL_EXTENDED => {
+                    let product = a as u64 * b as u64;
+                    (product as u32, (product >> 32) as u32)
+                }
+                _ => {
+                    let product = a as i32 as i64 * b as i32 as i64;
+                    (product as u32, (product >> 32) as u32)
+                }
+            });
+            let (low, high): (Vec<u32>, Vec<u32>) = pairs.unzip();
+            Value::Data(low.into_iter().chain(high).collect())
+        }
+        op::ANY => Value::Data(vec![boolean(arg(0).iter().any(|&a| a != 0))]),
+        op::ALL => Value::Data(vec![boolean(arg(0).iter().all(|&a| a != 0))]),
+        op::IS_NAN => map(arg(0), |a| boolean(f(a).is_nan())),
+        op::IS_INF => map(arg(0), |a| boolean(f(a).is_infinite())),
+        op::IS_FINITE => map(arg(0), |a| boolean(f(a).is_finite())),
+        op::IS_NORMAL => map(arg(0), |a| boolean(f(a).is_normal())),
+        op::SIGN_BIT_SET => map(arg(0), |a| a >> 31),
+        op::LOGICAL_EQUAL => map2(arg(0), arg(1), |a, b| boolean((a != 0) == (b != 0))),
+        op::LOGICAL_NOT_EQUAL => map2(arg(0), arg(1), |a, b| boolean((a != 0) != (b != 0))),
+        op::LOGICAL_OR => map2(arg(0), arg(1), |a, b| boolean(a != 0 || b != 0)),
+        op::LOGICAL_AND => map2(arg(0), arg(1), |a, b| boolean(a != 0 && b != 0)),
+        op::LOGICAL_NOT => map(arg(0), |a| boolean(a == 0)),
+        op::SELECT => {
+            let condition = arg(0);
+            if condition.len() == 1 {
+                // A scalar condition selects whole objects.
+                if condition[0] != 0 {
+                    get(ops[3]).clone()
+                } else {
+                    get(ops[4]).clone()
+                }
+            } else {
+                map3(
+                    condition,
+                    arg(1),
+                    arg(2),
+                    |c, a, b| if c != 0 { a } else { b },
+                )
+            }
+        }
+        op::I_EQUAL => map2(arg(0), arg(1), |a, b| boolean(a == b)),
+        op::I_NOT_EQUAL => map2(arg(0), arg(1), |a, b| boolean(a != b)),
+        op::U_GREATER_THAN => map2(arg(0), arg(1), |a, b| boolean(a > b)),
+        op::S_GREATER_THAN => map2(arg(0), arg(1), |a, b| boolean(a as i32 > b as i32)),
+        op::U_GREATER_THAN_EQUAL => map2(arg(0), arg(1), |a, b| boolean(a >= b)),
+        op::S_GREATER_THAN_EQUAL => map2(arg(0), arg(1), |a, b| boolean(a as i32 >= b as i32)),
+        op::U_LESS_THAN => map2(arg(0), arg(1), |a, b| boolean(a < b)),
+        op::S_LESS_THAN => map2(arg(0), arg(1), |a, b| boolean((a as i32) < b as i32)),
+        op::U_LESS_THAN_EQUAL => map2(arg(0), arg(1), |a, b| boolean(a <= b)),
+        op::S_LESS_THAN_EQUAL => map2(arg(0), arg(1), |a, b| boolean(a as i32 <= b as i32)),
+        op::F_ORD_EQUAL ..= op::F_UNORD_GREATER_THAN_EQUAL => {
+            let ordered = (opcode - op::F_ORD_EQUAL) & 1 == 0;
+            map2(arg(0), arg(1), |a, b| {
+                let (a, b) = (f(a), f(b));
+                if a.is_nan() || b.is_nan() {
+                    return boolean(!ordered);
+                }
+                boolean(match (opcode - op::F_ORD_EQUAL) / 2 {
+                    0 => a == b,
+                    1 => a != b,
+                    2 => a < b,
+                    3 => a > b,
+                    4 => a <= b,
+                    _ => a >= b,
+                })
+            })
+        }
+        op::SHIFT_RIGHT_LOGICAL => map2(arg(0), arg(1), |a, b| a.checked_shr(b).unwrap_or(0)),
+        op::SHIFT_RIGHT_ARITHMETIC => map2(arg(0), arg(1), |a, b| ((a as i32) >> b.min(31)) as u32),
+        op::SHIFT_LEFT_LOGICAL => map2(arg(0), arg(1), |a, b| a.checked_shl(b).unwrap_or(0)),
+        op::BITWISE_OR => map2(arg(0), arg(1), |a, b| a | b),
+        op::BITWISE_XOR => map2(arg(0), arg(1), |a, b| a ^ b),
+        op::BITWISE_AND => map2(arg(0), arg(1), |a, b| a & b),
+        op::NOT => map(arg(0), |a| !a),
+        op::BIT_FIELD_INSERT => {
+            let (offset, count) = (arg(2)[0], arg(3)[0]);
+            let mask = mask(offset, count);
+            map2(arg(0), arg(1), |base, insert| {
+                (base & !mask) | ((insert << offset.min(31)) & mask)
+            })
+        }
+        op::BIT_FIELD_S_EXTRACT => {
+            let (offset, count) = (arg(1)[0], arg(2)[0]);
+            map(arg(0), |a| {
+                let value = bits(a, offset, count);
+                if count == 0 || count >= 32 {
+                    value
+                } else {
+                    (((value << (32 - count)) as i32) >> (32 - count)) as u32
+                }
+            })
+        }
+        op::BIT_FIELD_U_EXTRACT => {
+            let (offset, count) = (arg(1)[0], arg(2)[0]);
+            map(arg(0), |a| bits(a, offset, count))
+        }
+        op::BIT_REVERSE => map(arg(0), u32::reverse_bits),
+        op::BIT_COUNT => map(arg(0), u32::count_ones),
+        // Derivatives are zero, as fragments are shaded one at a time.
+        op::DPDX ..= op::FWIDTH_COARSE => Value::Data(vec![0; arg(0).len()]),
+        op::VECTOR_EXTRACT_DYNAMIC => Value::Data(vec![arg(0)[arg(1)[0] as usize]]),
+        op::VECTOR_INSERT_DYNAMIC => {
+            let mut vector = arg(0).to_vec();
+            vector[arg(2)[0] as usize] = arg(1)[0];
+            Value::Data(vector)
+        }
+        op::VECTOR_SHUFFLE => {
+            let (a, b) = (arg(0), arg(1));
+            Value::Data(
+                ops[4 ..]
+                    .iter()
+                    .map(|&index| match index as usize {
+                        0xFFFF_FFFF => 0,
+                        index if index < a.len() => a[index],
+                        index => b[index - a.len()],
+                    })
+                    .collect(),
+            )
+        }
+        op::COMPOSITE_CONSTRUCT => Value::Data(
+            ops[2 ..]
+                .iter()
+                .flat_map(|&id| get(id).data().iter().cloned())
+                .collect(),
+        ),
+        op::COMPOSITE_EXTRACT => {
+            let mut ty = module.type_of[ops[2] as usize];
+            let mut offset = 0;
+            for &index in &ops[3 ..] {
+                let (member_offset, member) = module.flat_member(ty, index);
+                offset += member_offset;
+                ty = member;
+            }
+            Value::Data(arg(0)[offset .. offset + module.words(ty)].to_vec())
+        }
+        op::COMPOSITE_INSERT => {
+            let mut ty = module.type_of[ops[3] as usize];
+            let mut offset = 0;
+            for &index in &ops[4 ..] {
+                let (member_offset, member) = module.flat_member(ty, index);
+                offset += member_offset;
+                ty = member;
+            }
+            let mut composite = arg(1).to_vec();
+            composite[offset .. offset + module.words(ty)].copy_from_slice(arg(0));
+            Value::Data(composite)
+        }
+        op::EXT_INST if Some(ops[2]) == module.glsl_std => {
+            let args = ops[4 ..]
+                .iter()
+                .map(|&id| get(id).data())
+                .collect::<Vec<_>>();
+            glsl_std(ops[3], &args)
+                .unwrap_or_else(|| panic!("Unsupported GLSL.std.450 instruction {}", ops[3]))
+        }
+        _ => return None,
+    })
+}
+
+fn matrix_columns(module: &Module, ty: Id) -> usize {
+    match *module.ty(ty) {
+        Type::Matrix { count, .. } => count as usize,
+        _ => panic!("Expected a matrix type"),
+    }
+}
+
+// Transposes a column-major matrix with the given number of rows.
+fn transpose(m: &[u32], rows: usize) -> Vec<u32> {
+    let columns = m.len() / rows;
+    (0 .. rows)
+        .flat_map(|r| (0 .. columns).map(move |c| m[c * rows + r]))
+        .collect()
+}
+
+// Inverts a square matrix with Gauss-Jordan elimination, returning it with the determinant.
+fn invert(m: &[u32]) -> (Vec<f32>, f32) {
+    let size = (m.len() as f32).sqrt() as usize;
+    let mut a = m.iter().map(|&v| f(v)).collect::<Vec<_>>();
+    let mut inverse = vec![0.0; size * size];
+    for i in 0 .. size {
+        inverse[i * size + i] = 1.0;
+    }
+    let mut determinant = 1.0;
+    // Elements are accessed as `[column * size + row]`.
+    for column in 0 .. size {
+        let pivot = (column .. size)
+            .max_by(|&x, &y| {
+                a[column * size + x]
+                    .abs()
+                    .partial_cmp(&a[column * size + y].abs())
+                    .unwrap()
+            })
+            .unwrap();
+        if a[column * size + pivot] == 0.0 {
+            return (inverse, 0.0);
+        }
+        if pivot != column {
+            for c in 0 .. size {
+                a.swap(c * size + pivot, c * size + column);
+                inverse.swap(c * size + pivot, c * size + column);
+            }
+            determinant = -determinant;
+        }
+        let value = a[column * size + column];
+        determinant *= value;
+        for c in 0 .. size {
+            a[c * size + column] /= value;
+            inverse[c * size + column] /= value;
+        }
+        for row in 0 .. size {
+            if row == column {
+                continue;
+            }
+            let factor = a[column * size + row];
+            for c in 0 .. size {
+                a[c * size + row] -= factor * a[c * size + column];
+                inverse[c * size + row] -= factor * inverse[c * size + column];
+            }
+        }
+    }
+    (inverse, determinant)
+}
+
+fn round_even(x: f32) -> f32 {
+    let rounded = x.round();
+    if (x - x.trunc()).abs() == 0.5 && rounded % 2.0 != 0.0 {
+        rounded - x.signum()
+    } else {
+        rounded
+    }
+}
+
+// Evaluates an instruction of the GLSL.std.450 extended instruction set.
+fn glsl_std(instruction: u32, args: &[&[u32]]) -> Option<Value> {
+    let a = || args[0];
+    Some(match instruction {
+        1 => fmap(a(), f32::round),
+        2 => fmap(a(), round_even),
+        3 => fmap(a(), f32::trunc),
+        4 => fmap(a(), f32::abs),
+        5 => map(a(), |a| (a as i32).wrapping_abs() as u32),
+        6 => fmap(a(), |a| {
+            if a > 0.0 {
+                1.0
+            } else if a < 0.0 {
+                -1.0
+            } else {
+                0.0
+            }
+        }),
+        7 => map(a(), |a| (a as i32).signum() as u32),
+        8 => fmap(a(), f32::floor),
+        9 => fmap(a(), f32::ceil),
+        10 => fmap(a(), |a| a - a.floor()),
+        11 => fmap(a(), |a| a * PI / 180.0),
+        12 => fmap(a(), |a| a * 180.0 / PI),
+        13 => fmap(a(), f32::sin),
+        14 => fmap(a(), f32::cos),
+        15 => fmap(a(), f32::tan),
+        16 => fmap(a(), f32::asin),
+        17 => fmap(a(), f32::acos),
+        18 => fmap(a(), f32::atan),
+        19 => fmap(a(), f32::sinh),
+        20 => fmap(a(), f32::cosh),
+        21 => fmap(a(), f32::tanh),
+        22 => fmap(a(), f32::asinh),
+        23 => fmap(a(), f32::acosh),
+        24 => fmap(a(), f32::atanh),
+        25 => fmap2(a(), args[1], f32::atan2),
+        26 => fmap2(a(), args[1], f32::powf),
+        27 => fmap(a(), f32::exp),
+        28 => fmap(a(), f32::ln),
+        29 => fmap(a(), f32::exp2),
+        30 => fmap(a(), f32::log2),
+        31 => fmap(a(), f32::sqrt),
+        32 => fmap(a(), |a| 1.0 / a.sqrt()),
+        33 => Value::Data(vec![invert(a()).1.to_bits()]),
+        34 => Value::Data(invert(a()).0.iter().map(|v| v.to_bits()).collect()),
+        36 => {
+            // ModfStruct returns the fractional part, then the whole part.
+            let whole = a().iter().map(|&a| f(a).trunc());
+            let fract = a().iter().map(|&a| f(a) - f(a).trunc());
+            Value::Data(fract.chain(whole).map(f32::to_bits).collect())
+        }
+        37 | 79 => fmap2(a(), args[1], f32::min),
+        38 => map2(a(), args[1], u32::min),
+        39 => map2(a(), args[1], |a, b| (a as i32).min(b as i32) as u32),
+        40 | 80 => fmap2(a(), args[1], f32::max),
+        41 => map2(a(), args[1], u32::max),
+        42 => map2(a(), args[1], |a, b| (a as i32).max(b as i32) as u32),
+        43 | 81 => fmap3(a(), args[1], args[2], |x, lo, hi| x.max(lo).min(hi)),
+        44 => map3(a(), args[1], args[2], |x, lo, hi| x.max(lo).min(hi)),
+        45 => map3(a(), args[1], args[2], |x, lo, hi| {
+            (x as i32).max(lo as i32).min(hi as i32) as u32
+        }),
+        46 => fmap3(a(), args[1], args[2], |x, y, t| x * (1.0 - t) + y * t),
+        48 => fmap2(a(), args[1], |edge, x| if x < edge { 0.0 } else { 1.0 }),
+        49 => fmap3(a(), args[1], args[2], |e0, e1, x| {
+            let t = ((x - e0) / (e1 - e0)).clamp(0.0, 1.0);
+            t * t * (3.0 - 2.0 * t)
+        }),
+        50 => fmap3(a(), args[1], args[2], f32::mul_add),
+        52 => {
+            // FrexpStruct returns the significand, then the exponent.
+            let parts = a()
+                .iter()
+                .map(|&a| {
+                    let x = f(a);
+                    if x == 0.0 || !x.is_finite() {
+                        (x, 0)
+                    } else {
+                        let exponent = x.abs().log2().floor() as i32 + 1;
+                        (x / 2f32.powi(exponent), exponent)
+                    }
+                })
+                .collect::<Vec<_>>();
+            let significands = parts.iter().map(|&(s, _)| s.to_bits());
+            let exponents = parts.iter().map(|&(_, e)| e as u32);
+            Value::Data(significands.chain(exponents).collect())
+        }
+        53 => map2(a(), args[1], |x, e| (f(x) * 2f32.powi(e as i32)).to_bits()),
+        54 => pack(a(), 8, |c| {
+            (c.clamp(-1.0, 1.0) * 127.0).round() as i32 as u32
+        }),
+        55 => pack(a(), 8, |c| (c.clamp(0.0, 1.0) * 255.0).round() as u32),
+        56 => pack(a(), 16, |c| {
+            (c.clamp(-1.0, 1.0) * 32767.0).round() as i32 as u32
+        }),
+        57 => pack(a(), 16, |c| (c.clamp(0.0, 1.0) * 65535.0).round() as u32),
+        58 => pack(a(), 16, |c| texel::f32_to_f16(c) as u32),
+        60 => unpack(a()[0], 16, |v| {
+            ((v as u16 as i16) as f32 / 32767.0).max(-1.0)
+        }),
+        61 => unpack(a()[0], 16, |v| v as f32 / 65535.0),
+        62 => unpack(a()[0], 16, |v| texel::f16_to_f32(v as u16)),
+        63 => unpack(a()[0], 8, |v| ((v as u8 as i8) as f32 / 127.0).max(-1.0)),
+        64 => unpack(a()[0], 8, |v| v as f32 / 255.0),
+        66 => Value::Data(vec![dot(a(), a()).sqrt().to_bits()]),
+        67 => {
+            let distance: f32 = a()
+                .iter()
+                .zip(args[1])
+                .map(|(&x, &y)| (f(x) - f(y)).powi(2))
+                .sum();
+            Value::Data(vec![distance.sqrt().to_bits()])
+        }
+        68 => {
+            let (x, y) = (a(), args[1]);
+            let c = |i: usize, j: usize| f(x[i]) * f(y[j]) - f(x[j]) * f(y[i]);
+            Value::Data(vec![
+                c(1, 2).to_bits(),
+                c(2, 0).to_bits(),
+                c(0, 1).to_bits(),
+            ])
+        }
+        69 => {
+            let length = dot(a(), a()).sqrt();
+            fmap(a(), |x| x / length)
+        }
+        70 => {
+            // FaceForward(N, I, Nref)
+            if dot(args[2], args[1]) < 0.0 {
+                Value::Data(a().to_vec())
+            } else {
+                fmap(a(), |n| -n)
+            }
+        }
+        71 => {
+            // Reflect(I, N) = I - 2 * dot(N, I) * N
+            let d = dot(args[1], a());
+            fmap2(a(), args[1], |i, n| i - 2.0 * d * n)
+        }
+        72 => {
+            // Refract(I, N, eta)
+            let (incident, normal, eta) = (a(), args[1], f(args[2][0]));
+            let d = dot(normal, incident);
+            let k = 1.0 - eta * eta * (1.0 - d * d);
+            if k < 0.0 {
+                Value::Data(vec![0; incident.len()])
+            } else {
+                fmap2(incident, normal, |i, n| eta * i - (eta * d + k.sqrt()) * n)
+            }
+        }
+        73 => map(a(), |a| if a == 0 { !0 } else { a.trailing_zeros() }),
+        74 => map(a(), |a| {
+            let a = a as i32;
+            let value = if a < 0 { !a } else { a } as u32;
+            if value == 0 {
+                !0
+            } else {
+                31 - value.leading_zeros()
+            }
+        }),
+        75 => map(a(), |a| if a == 0 { !0 } else { 31 - a.leading_zeros() }),
+        _ => return None,
+    })
+}
+
+fn pack<F: Fn(f32) -> u32>(components: &[u32], bits: u32, op: F) -> Value {
+    let mask = (1u32 << bits) - 1;
+    let packed = components.iter().enumerate().fold(0, |packed, (i, &c)| {
+        packed | ((op(f(c)) & mask) << (i as u32 * bits))
+    });
+    Value::Data(vec![packed])
+}
+
+fn unpack<F: Fn(u32) -> f32>(packed: u32, bits: u32, op: F) -> Value {
+    let mask = (1u32 << bits) - 1;
+    Value::Data(
+        (0 .. 32 / bits)
+            .map(|i| op((packed >> (i * bits)) & mask).to_bits())
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_utils, Instance};
+    use hal::device::Device as _;
+    use hal::{format, image, MemoryTypeId};
+    use std::{ptr, slice};
+
+    /// Writes the words of a vertex shader `main`, which reads a vector of
+    /// four integers from the input 0 and writes one to the output 0.
+    struct Assembler {
+        words: Vec<u32>,
+        bound: Id,
+        void: Id,
+        bool: Id,
+        int: Id,
+        float: Id,
+        ivec2: Id,
+        ivec4: Id,
+        vec4: Id,
+        input: Id,
+        output: Id,
+        main: Id,
+        entry: Id,
+    }
+
+    impl Assembler {
+        fn new() -> Self {
+            let mut asm = Assembler {
+                words: vec![0x0723_0203, 0x0001_0000, 0, 0, 0],
+                bound: 1,
+                void: 0,
+                bool: 0,
+                int: 0,
+                float: 0,
+                ivec2: 0,
+                ivec4: 0,
+                vec4: 0,
+                input: 0,
+                output: 0,
+                main: 0,
+                entry: 0,
+            };
+            asm.void = asm.id();
+            asm.bool = asm.id();
+            asm.int = asm.id();
+            asm.float = asm.id();
+            asm.ivec2 = asm.id();
+            asm.ivec4 = asm.id();
+            asm.vec4 = asm.id();
+            asm.input = asm.id();
+            asm.output = asm.id();
+            asm.main = asm.id();
+            asm.entry = asm.id();
+
+            let mut entry_point = vec![0, asm.main, u32::from_le_bytes(*b"main"), 0];
+            entry_point.extend_from_slice(&[asm.input, asm.output]);
+            asm.emit(op::ENTRY_POINT, &entry_point);
+            asm.emit(op::DECORATE, &[asm.input, decoration::LOCATION, 0]);
+            asm.emit(op::DECORATE, &[asm.output, decoration::LOCATION, 0]);
+            asm.emit(op::TYPE_VOID, &[asm.void]);
+            asm.emit(op::TYPE_BOOL, &[asm.bool]);
+            asm.emit(op::TYPE_INT, &[asm.int, 32, 1]);
+            asm.emit(op::TYPE_FLOAT, &[asm.float, 32]);
+            asm.emit(op::TYPE_VECTOR, &[asm.ivec2, asm.int, 2]);
+            asm.emit(op::TYPE_VECTOR, &[asm.ivec4, asm.int, 4]);
+            asm.emit(op::TYPE_VECTOR, &[asm.vec4, asm.float, 4]);
+            let input = asm.pointer(storage::INPUT, asm.ivec4);
+            let output = asm.pointer(storage::OUTPUT, asm.ivec4);
+            asm.emit(op::VARIABLE, &[input, asm.input, storage::INPUT]);
+            asm.emit(op::VARIABLE, &[output, asm.output, storage::OUTPUT]);
+            asm
+        }
+
+        fn id(&mut self) -> Id {
+            self.bound += 1;
+            self.bound - 1
+        }
+
+        fn emit(&mut self, opcode: u32, operands: &[u32]) {
+            self.words.push((operands.len() as u32 + 1) << 16 | opcode);
+            self.words.extend_from_slice(operands);
+        }
+
+        // Emits an instruction with a result type, returning its result.
+        fn op(&mut self, opcode: u32, ty: Id, operands: &[u32]) -> Id {
+            let id = self.id();
+            let mut words = vec![ty, id];
+            words.extend_from_slice(operands);
+            self.emit(opcode, &words);
+            id
+        }
+
+        fn pointer(&mut self, storage: u32, ty: Id) -> Id {
+            let id = self.id();
+            self.emit(op::TYPE_POINTER, &[id, storage, ty]);
+            id
+        }
+
+        fn constant(&mut self, ty: Id, value: u32) -> Id {
+            self.op(op::CONSTANT, ty, &[value])
+        }
+
+        fn label(&mut self, label: Id) {
+            self.emit(op::LABEL, &[label]);
+        }
+
+        // Starts the body of `main`, returning the components of the input.
+        fn begin_main(&mut self) -> [Id; 4] {
+            let function = self.id();
+            self.emit(op::TYPE_FUNCTION, &[function, self.void]);
+            self.emit(op::FUNCTION, &[self.void, self.main, 0, function]);
+            self.label(self.entry);
+            let input = self.op(op::LOAD, self.ivec4, &[self.input]);
+            let mut components = [0; 4];
+            for (i, component) in components.iter_mut().enumerate() {
+                *component = self.op(op::COMPOSITE_EXTRACT, self.int, &[input, i as u32]);
+            }
+            components
+        }
+
+        // Writes `result` to the output and ends `main`.
+        fn finish(mut self, result: Id) -> Vec<u32> {
+            self.emit(op::STORE, &[self.output, result]);
+            self.emit(op::RETURN, &[]);
+            self.emit(op::FUNCTION_END, &[]);
+            self.words[3] = self.bound;
+            self.words
+        }
+    }
+
+    fn run(words: &[u32], resources: &Resources, input: [u32; 4]) -> Vec<u32> {
+        let module = Arc::new(Module::parse(words).unwrap());
+        let program = Program::new(
+            &module,
+            "main",
+            pso::Stage::Vertex,
+            &pso::Specialization::default(),
+        )
+        .unwrap();
+        let mut invocation = Invocation::new(&program);
+        invocation.reset(&program);
+        invocation
+            .interface_mut(&program, &program.inputs[0])
+            .copy_from_slice(&input);
+        let mut context = Context {
+            resources,
+            push_constants: &[],
+            workgroup: &mut [],
+        };
+        assert_eq!(invocation.run(&program, &mut context), Status::Done);
+        invocation.interface(&program, &program.outputs[0]).to_vec()
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let mut asm = Assembler::new();
+        let [a, b, c, d] = asm.begin_main();
+        let (int, float) = (asm.int, asm.float);
+        let product = asm.op(op::I_MUL, int, &[a, b]);
+        let sum = asm.op(op::I_ADD, int, &[product, a]);
+        let quotient = asm.op(op::S_DIV, int, &[a, b]);
+        let modulo = asm.op(op::S_MOD, int, &[a, b]);
+        let x = asm.op(op::BITCAST, float, &[c]);
+        let y = asm.op(op::BITCAST, float, &[d]);
+        let scaled = asm.op(op::F_MUL, float, &[x, y]);
+        let total = asm.op(op::F_ADD, float, &[scaled, x]);
+        let total = asm.op(op::BITCAST, int, &[total]);
+        let ivec4 = asm.ivec4;
+        let result = asm.op(op::COMPOSITE_CONSTRUCT, ivec4, &[sum, quotient, modulo, total]);
+        let words = asm.finish(result);
+
+        let input = |a: i32, b: i32, c: f32, d: f32| [a as u32, b as u32, c.to_bits(), d.to_bits()];
+        let output = |sum: i32, quotient: i32, modulo: i32, total: f32| {
+            vec![sum as u32, quotient as u32, modulo as u32, total.to_bits()]
+        };
+        // Division truncates, and the modulo takes the sign of the divisor.
+        assert_eq!(
+            run(&words, &Vec::new(), input(7, -3, 2.5, 0.5)),
+            output(-14, -2, -2, 3.75)
+        );
+        assert_eq!(
+            run(&words, &Vec::new(), input(-7, 3, -1.5, 4.0)),
+            output(-28, -2, 2, -7.5)
+        );
+    }
+
+    #[test]
+    fn test_control_flow() {
+        let mut asm = Assembler::new();
+        let (int, bool) = (asm.int, asm.bool);
+        let zero = asm.constant(int, 0);
+        let one = asm.constant(int, 1);
+        let cases = [asm.constant(int, 10), asm.constant(int, 20), asm.constant(int, 30)];
+
+        // int square(int x) { return x * x; }
+        let square = asm.id();
+        let function = asm.id();
+        let parameter = asm.id();
+        let label = asm.id();
+        asm.emit(op::TYPE_FUNCTION, &[function, int, int]);
+        asm.emit(op::FUNCTION, &[int, square, 0, function]);
+        asm.emit(op::FUNCTION_PARAMETER, &[int, parameter]);
+        asm.label(label);
+        let squared = asm.op(op::I_MUL, int, &[parameter, parameter]);
+        asm.emit(op::RETURN_VALUE, &[squared]);
+        asm.emit(op::FUNCTION_END, &[]);
+
+        // Sum of the odd numbers below `a`, counting the iterations.
+        let [a, b, _, _] = asm.begin_main();
+        let entry = asm.entry;
+        let (header, body, odd, join, next, merge) =
+            (asm.id(), asm.id(), asm.id(), asm.id(), asm.id(), asm.id());
+        let (i, sum, i_next, sum_next) = (asm.id(), asm.id(), asm.id(), asm.id());
+        asm.emit(op::BRANCH, &[header]);
+        asm.label(header);
+        asm.emit(op::PHI, &[int, i, zero, entry, i_next, next]);
+        asm.emit(op::PHI, &[int, sum, zero, entry, sum_next, next]);
+        asm.emit(op::LOOP_MERGE, &[merge, next, 0]);
+        let more = asm.op(op::S_LESS_THAN, bool, &[i, a]);
+        asm.emit(op::BRANCH_CONDITIONAL, &[more, body, merge]);
+        asm.label(body);
+        let bit = asm.op(op::BITWISE_AND, int, &[i, one]);
+        let is_odd = asm.op(op::I_NOT_EQUAL, bool, &[bit, zero]);
+        asm.emit(op::SELECTION_MERGE, &[join, 0]);
+        asm.emit(op::BRANCH_CONDITIONAL, &[is_odd, odd, join]);
+        asm.label(odd);
+        let added = asm.op(op::I_ADD, int, &[sum, i]);
+        asm.emit(op::BRANCH, &[join]);
+        asm.label(join);
+        let joined = asm.op(op::PHI, int, &[added, odd, sum, body]);
+        asm.emit(op::BRANCH, &[next]);
+        asm.label(next);
+        asm.emit(op::I_ADD, &[int, i_next, i, one]);
+        asm.emit(op::COPY_OBJECT, &[int, sum_next, joined]);
+        asm.emit(op::BRANCH, &[header]);
+        asm.label(merge);
+
+        // Constant picked by `b`, with 1 and 2 as cases.
+        let labels = [asm.id(), asm.id(), asm.id()];
+        let switched = asm.id();
+        asm.emit(op::SELECTION_MERGE, &[switched, 0]);
+        asm.emit(op::SWITCH, &[b, labels[2], 1, labels[0], 2, labels[1]]);
+        for &label in &labels {
+            asm.label(label);
+            asm.emit(op::BRANCH, &[switched]);
+        }
+        asm.label(switched);
+        let picked = asm.op(
+            op::PHI,
+            int,
+            &[cases[0], labels[0], cases[1], labels[1], cases[2], labels[2]],
+        );
+        let squared = asm.op(op::FUNCTION_CALL, int, &[square, a]);
+        let ivec4 = asm.ivec4;
+        let result = asm.op(op::COMPOSITE_CONSTRUCT, ivec4, &[sum, i, picked, squared]);
+        let words = asm.finish(result);
+
+        assert_eq!(run(&words, &Vec::new(), [5, 2, 0, 0]), vec![4, 5, 20, 25]);
+        assert_eq!(run(&words, &Vec::new(), [4, 1, 0, 0]), vec![4, 4, 10, 16]);
+        assert_eq!(run(&words, &Vec::new(), [0, 7, 0, 0]), vec![0, 0, 30, 0]);
+    }
+
+    #[test]
+    fn test_image_ops() {
+        let mut asm = Assembler::new();
+        let (int, float, ivec2, vec4) = (asm.int, asm.float, asm.ivec2, asm.vec4);
+        // Storage image of `Rgba8` texels, at the binding 0 of the set 0.
+        let image_type = asm.id();
+        asm.emit(op::TYPE_IMAGE, &[image_type, float, 1, 0, 0, 0, 2, 4]);
+        let pointer = asm.pointer(storage::UNIFORM_CONSTANT, image_type);
+        let image = asm.op(op::VARIABLE, pointer, &[storage::UNIFORM_CONSTANT]);
+        asm.emit(op::DECORATE, &[image, decoration::DESCRIPTOR_SET, 0]);
+        asm.emit(op::DECORATE, &[image, decoration::BINDING, 0]);
+        let half = asm.constant(float, 0.5f32.to_bits());
+
+        // Reads the texel at (x, y) and writes half of it at (y, x).
+        let [x, y, _, _] = asm.begin_main();
+        let loaded = asm.op(op::LOAD, image_type, &[image]);
+        let size = asm.op(op::IMAGE_QUERY_SIZE, ivec2, &[loaded]);
+        let coordinate = asm.op(op::COMPOSITE_CONSTRUCT, ivec2, &[x, y]);
+        let texel = asm.op(op::IMAGE_READ, vec4, &[loaded, coordinate]);
+        let scaled = asm.op(op::VECTOR_TIMES_SCALAR, vec4, &[texel, half]);
+        let swapped = asm.op(op::COMPOSITE_CONSTRUCT, ivec2, &[y, x]);
+        asm.emit(op::IMAGE_WRITE, &[loaded, swapped, scaled]);
+        let width = asm.op(op::COMPOSITE_EXTRACT, int, &[size, 0]);
+        let height = asm.op(op::COMPOSITE_EXTRACT, int, &[size, 1]);
+        let red = asm.op(op::COMPOSITE_EXTRACT, float, &[texel, 0]);
+        let alpha = asm.op(op::COMPOSITE_EXTRACT, float, &[texel, 3]);
+        let red = asm.op(op::BITCAST, int, &[red]);
+        let alpha = asm.op(op::BITCAST, int, &[alpha]);
+        let ivec4 = asm.ivec4;
+        let result = asm.op(op::COMPOSITE_CONSTRUCT, ivec4, &[width, height, red, alpha]);
+        let words = asm.finish(result);
+
+        let (_, gpu) = test_utils::open(&Instance);
+        let device = &gpu.device;
+        unsafe {
+            let format = format::Format::Rgba8Unorm;
+            let mut image = device
+                .create_image(
+                    image::Kind::D2(3, 2, 1, 1),
+                    1,
+                    format,
+                    image::Tiling::Linear,
+                    image::Usage::STORAGE,
+                    image::ViewCapabilities::empty(),
+                )
+                .unwrap();
+            let memory = device.allocate_memory(MemoryTypeId(1), 64).unwrap();
+            device.bind_image_memory(&memory, 0, &mut image).unwrap();
+            let row_pitch = device
+                .get_image_subresource_footprint(
+                    &image,
+                    image::Subresource {
+                        aspects: format::Aspects::COLOR,
+                        level: 0,
+                        layer: 0,
+                    },
+                )
+                .row_pitch as isize;
+            let mapping = device.map_memory(&memory, ..).unwrap();
+            ptr::write_bytes(mapping, 0, 64);
+            ptr::copy_nonoverlapping([200u8, 100, 50, 254].as_ptr(), mapping.offset(row_pitch), 4);
+            let view = device
+                .create_image_view(
+                    &image,
+                    image::ViewKind::D2,
+                    format,
+                    format::Swizzle::NO,
+                    image::SubresourceRange {
+                        aspects: format::Aspects::COLOR,
+                        levels: 0 .. 1,
+                        layers: 0 .. 1,
+                    },
+                )
+                .unwrap();
+            let resources = vec![vec![vec![n::Descriptor {
+                image: Some(view),
+                ..n::Descriptor::default()
+            }]]];
+
+            let output = run(&words, &resources, [0, 1, 0, 0]);
+            assert_eq!(&output[.. 2], &[3, 2]);
+            assert!((f(output[2]) - 200.0 / 255.0).abs() < 1e-6);
+            assert!((f(output[3]) - 254.0 / 255.0).abs() < 1e-6);
+            assert_eq!(slice::from_raw_parts(mapping.offset(4), 4), &[100, 50, 25, 127]);
+            // Writes out of the image are dropped, and reads return zero.
+            let output = run(&words, &resources, [2, 1, 0, 0]);
+            assert_eq!(&output[2 ..], &[0, 0]);
+            let end = 2 * row_pitch as usize;
+            assert_eq!(slice::from_raw_parts(mapping.offset(row_pitch), 4), &[200, 100, 50, 254]);
+            assert!(slice::from_raw_parts(mapping, 64)[end ..].iter().all(|&byte| byte == 0));
+            device.unmap_memory(&memory);
+        }
+    }
+}
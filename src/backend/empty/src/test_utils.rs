@@ -0,0 +1,17 @@
+//! Helpers for the tests of the crates built on top of a backend.
+
+use hal::adapter::{Adapter, Gpu, PhysicalDevice as _};
+
+/// Open the first adapter of `instance`, with one queue of its first family
+/// and no features. Returns the adapter along with the opened device.
+pub fn open<I: hal::Instance>(instance: &I) -> (Adapter<I::Backend>, Gpu<I::Backend>) {
+    let adapter = instance.enumerate_adapters().remove(0);
+    let family = &adapter.queue_families[0];
+    let gpu = unsafe {
+        adapter
+            .physical_device
+            .open(&[(family, &[1.0])], hal::Features::empty())
+            .unwrap()
+    };
+    (adapter, gpu)
+}
@@ -9,13 +9,21 @@ fn main() {
 
     println!("cargo:rerun-if-changed=build.rs");
 
+    if !target.contains("wasm32") {
+        // Entry points not exposed by `glow`.
+        let mut file = File::create(dest.join("gl_sys.rs")).unwrap();
+        Registry::new(Api::Gl, (4, 6), Profile::Core, Fallbacks::All, [])
+            .write_bindings(gl_generator::StructGenerator, &mut file)
+            .unwrap();
+    }
+
     if target.contains("windows") {
-        let mut file = File::create(&dest.join("wgl_sys.rs")).unwrap();
+        let mut file = File::create(dest.join("wgl_sys.rs")).unwrap();
         Registry::new(Api::Wgl, (1, 0), Profile::Core, Fallbacks::All, [])
             .write_bindings(gl_generator::StaticGenerator, &mut file)
             .unwrap();
 
-        let mut file = File::create(&dest.join("wgl_ext_sys.rs")).unwrap();
+        let mut file = File::create(dest.join("wgl_ext_sys.rs")).unwrap();
         Registry::new(
             Api::Wgl,
             (1, 0),
@@ -68,6 +68,23 @@ pub enum Command {
         base_vertex: hal::VertexOffset,
         instances: Range<hal::InstanceCount>,
     },
+    DrawIndirect {
+        primitive: u32,
+        buffer: n::RawBuffer,
+        offset: buffer::Offset,
+        draw_count: hal::DrawCount,
+        stride: u32,
+    },
+    DrawIndexedIndirect {
+        primitive: u32,
+        index_type: u32,
+        // Offset of the bound index buffer in its GL buffer.
+        index_buffer_offset: buffer::Offset,
+        buffer: n::RawBuffer,
+        offset: buffer::Offset,
+        draw_count: hal::DrawCount,
+        stride: u32,
+    },
     BindIndexBuffer(n::RawBuffer),
     //BindVertexBuffers(BufferSlice),
     BindUniform {
@@ -104,10 +121,18 @@ pub enum Command {
     DrawBuffers(BufferSlice),
 
     BindFrameBuffer(FrameBufferTarget, Option<n::RawFrameBuffer>),
+    /// Resolve an attachment of the source framebuffer into the same
+    /// attachment point of the destination framebuffer.
+    ResolveAttachment {
+        src: n::RawFrameBuffer,
+        dst: n::RawFrameBuffer,
+        attachment: AttachmentPoint,
+        rect: pso::Rect,
+    },
     BindTargetView(FrameBufferTarget, AttachmentPoint, n::ImageView),
     SetDrawColorBuffers(usize),
     SetPatchSize(i32),
-    BindProgram(<GlContext as glow::Context>::Program),
+    BindProgram(<GlContext as glow::HasContext>::Program),
     SetBlend(pso::ColorBlendDesc),
     SetBlendSlot(ColorSlot, pso::ColorBlendDesc),
     BindAttribute(n::AttributeDesc, n::RawBuffer, i32, u32),
@@ -148,6 +173,23 @@ pub enum Command {
     BindTexture(u32, n::Texture, n::TextureTarget),
     BindSampler(u32, n::Sampler),
     SetTextureSamplerSettings(u32, n::Texture, n::TextureTarget, image::SamplerInfo),
+
+    SetEvent(n::Event),
+    ResetEvent(n::Event),
+    WaitEvent(n::Event),
+    BeginQuery(n::Query, u32),
+    EndQuery(u32),
+    WriteTimestamp(n::Query),
+    /// Copy the results of queries into a buffer.
+    /// The buffer slice contains the GL queries, `values` per query.
+    CopyQueryPoolResults {
+        queries: BufferSlice,
+        values: u32,
+        buffer: n::RawBuffer,
+        offset: buffer::Offset,
+        stride: buffer::Offset,
+        flags: query::ResultFlags,
+    },
 }
 
 pub type FrameBufferTarget = u32;
@@ -165,6 +207,7 @@ struct AttachmentClear {
 pub struct RenderPassCache {
     render_pass: n::RenderPass,
     framebuffer: n::FrameBuffer,
+    render_area: pso::Rect,
     attachment_clears: Vec<Option<AttachmentClear>>,
 }
 
@@ -198,6 +241,10 @@ struct Cache {
     attributes: Vec<n::AttributeDesc>,
     // Active uniforms
     uniforms: Vec<n::UniformDesc>,
+    // Values of the push constants, in words.
+    push_constants: Vec<u32>,
+    // Target of the active occlusion query, which depends on its control flags.
+    occlusion_target: Option<u32>,
 }
 
 impl Cache {
@@ -216,6 +263,8 @@ impl Cache {
             vertex_buffer_descs: Vec::new(),
             attributes: Vec::new(),
             uniforms: Vec::new(),
+            push_constants: Vec::new(),
+            occlusion_target: None,
         }
     }
 }
@@ -550,6 +599,88 @@ impl CommandBuffer {
             self.push_cmd(cmd);
         }
     }
+
+    fn end_subpass(&mut self) {
+        let state = self.pass_cache.as_ref().unwrap();
+        let subpass = &state.render_pass.subpasses[self.cur_subpass];
+
+        // Resolve the color attachments with a blit from the subpass framebuffer
+        // into the framebuffer of the resolve attachments.
+        let resolve_cmds = match (
+            state.framebuffer.fbos[self.cur_subpass],
+            state.framebuffer.resolve_fbos[self.cur_subpass],
+        ) {
+            (Some(src), Some(dst)) => (0 .. subpass.resolve_attachments.len())
+                .map(|index| Command::ResolveAttachment {
+                    src,
+                    dst,
+                    attachment: glow::COLOR_ATTACHMENT0 + index as u32,
+                    rect: state.render_area,
+                })
+                .collect::<Vec<_>>(),
+            _ => Vec::new(),
+        };
+
+        for cmd in resolve_cmds {
+            self.push_cmd(cmd);
+        }
+    }
+
+    /// Push constants are emulated with the uniforms of the program: the values
+    /// are kept in the cache and written to the uniforms of the bound program,
+    /// as well as to the ones of every program bound later on.
+    fn push_constants(
+        &mut self,
+        layout: &n::PipelineLayout,
+        stages: pso::ShaderStageFlags,
+        offset: u32,
+        constants: &[u32],
+    ) {
+        let end = offset + constants.len() as u32 * 4;
+        let in_layout = (offset .. end).step_by(4).all(|word| {
+            layout
+                .push_constant_ranges
+                .iter()
+                .any(|&(range_stages, ref range)| {
+                    stages.contains(range_stages) && range.start <= word && word < range.end
+                })
+        });
+        if !in_layout {
+            error!(
+                "Push constants {:?} for {:?} are not in the ranges of the pipeline layout",
+                offset .. end,
+                stages
+            );
+            return;
+        }
+
+        let start = offset as usize / 4;
+        if self.cache.push_constants.len() < start + constants.len() {
+            self.cache.push_constants.resize(start + constants.len(), 0);
+        }
+        self.cache.push_constants[start .. start + constants.len()].copy_from_slice(constants);
+        self.update_push_constants(offset .. end);
+    }
+
+    /// Write the push constants of the cache to the uniforms of the bound program
+    /// overlapping the given range.
+    fn update_push_constants(&mut self, range: Range<u32>) {
+        let end = range.end.min(self.cache.push_constants.len() as u32 * 4);
+        for index in 0 .. self.cache.uniforms.len() {
+            let uniform = self.cache.uniforms[index];
+            if uniform.offset + uniform.size <= range.start || uniform.offset >= end {
+                continue;
+            }
+            let start = uniform.offset as usize / 4;
+            let end = start + uniform.size as usize / 4;
+            if self.cache.push_constants.len() < end {
+                self.cache.push_constants.resize(end, 0);
+            }
+            let data = self.cache.push_constants[start .. end].to_vec();
+            let buffer = self.add(&data);
+            self.push_cmd(Command::BindUniform { uniform, buffer });
+        }
+    }
 }
 
 impl command::CommandBuffer<Backend> for CommandBuffer {
@@ -625,7 +756,7 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         &mut self,
         render_pass: &n::RenderPass,
         framebuffer: &n::FrameBuffer,
-        _render_area: pso::Rect,
+        render_area: pso::Rect,
         clear_values: T,
         _first_subpass: command::SubpassContents,
     ) where
@@ -679,6 +810,7 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         self.pass_cache = Some(RenderPassCache {
             render_pass: render_pass.clone(),
             framebuffer: framebuffer.clone(),
+            render_area,
             attachment_clears,
         });
 
@@ -688,11 +820,13 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
     }
 
     unsafe fn next_subpass(&mut self, _contents: command::SubpassContents) {
-        unimplemented!()
+        self.end_subpass();
+        self.cur_subpass += 1;
+        self.begin_subpass();
     }
 
     unsafe fn end_render_pass(&mut self) {
-        // TODO
+        self.end_subpass();
     }
 
     unsafe fn clear_image<T>(
@@ -985,14 +1119,14 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         if self.cache.program != Some(program) {
             self.cache.program = Some(program);
             self.push_cmd(Command::BindProgram(program));
+            self.cache.uniforms = uniforms.clone();
+            self.update_push_constants(0 .. !0);
         }
 
         self.cache.attributes = attributes.clone();
 
         self.cache.vertex_buffer_descs = vertex_buffers.clone();
 
-        self.cache.uniforms = uniforms.clone();
-
         self.update_blend_targets(blend_targets);
 
         self.push_cmd(Command::BindRasterizer { rasterizer });
@@ -1104,11 +1238,16 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
     }
 
     unsafe fn bind_compute_pipeline(&mut self, pipeline: &n::ComputePipeline) {
-        let n::ComputePipeline { program } = *pipeline;
+        let n::ComputePipeline {
+            program,
+            ref uniforms,
+        } = *pipeline;
 
         if self.cache.program != Some(program) {
             self.cache.program = Some(program);
             self.push_cmd(Command::BindProgram(program));
+            self.cache.uniforms = uniforms.clone();
+            self.update_push_constants(0 .. !0);
         }
     }
 
@@ -1349,106 +1488,183 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
 
     unsafe fn draw_indirect(
         &mut self,
-        _buffer: &n::Buffer,
-        _offset: buffer::Offset,
-        _draw_count: hal::DrawCount,
-        _stride: u32,
+        buffer: &n::Buffer,
+        offset: buffer::Offset,
+        draw_count: hal::DrawCount,
+        stride: u32,
     ) {
-        unimplemented!()
+        self.bind_attributes();
+
+        let (raw_buffer, range) = buffer.as_bound();
+        match self.cache.primitive {
+            Some(primitive) => {
+                self.push_cmd(Command::DrawIndirect {
+                    primitive,
+                    buffer: raw_buffer,
+                    offset: range.start + offset,
+                    draw_count,
+                    stride,
+                });
+            }
+            None => {
+                warn!("No primitive bound. An active pipeline needs to be bound before calling `draw_indirect`.");
+                self.cache.error_state = true;
+            }
+        }
     }
 
     unsafe fn draw_indexed_indirect(
         &mut self,
-        _buffer: &n::Buffer,
-        _offset: buffer::Offset,
-        _draw_count: hal::DrawCount,
-        _stride: u32,
+        buffer: &n::Buffer,
+        offset: buffer::Offset,
+        draw_count: hal::DrawCount,
+        stride: u32,
     ) {
-        unimplemented!()
+        self.bind_attributes();
+
+        let (index_type, index_buffer_offset) = match self.cache.index_type_range {
+            Some((hal::IndexType::U16, ref buffer_range)) => {
+                (glow::UNSIGNED_SHORT, buffer_range.start)
+            }
+            Some((hal::IndexType::U32, ref buffer_range)) => {
+                (glow::UNSIGNED_INT, buffer_range.start)
+            }
+            None => {
+                warn!("No index type bound. An index buffer needs to be bound before calling `draw_indexed_indirect`.");
+                self.cache.error_state = true;
+                return;
+            }
+        };
+
+        let (raw_buffer, range) = buffer.as_bound();
+        match self.cache.primitive {
+            Some(primitive) => {
+                self.push_cmd(Command::DrawIndexedIndirect {
+                    primitive,
+                    index_type,
+                    index_buffer_offset,
+                    buffer: raw_buffer,
+                    offset: range.start + offset,
+                    draw_count,
+                    stride,
+                });
+            }
+            None => {
+                warn!("No primitive bound. An active pipeline needs to be bound before calling `draw_indexed_indirect`.");
+                self.cache.error_state = true;
+            }
+        }
     }
 
-    unsafe fn set_event(&mut self, _: &(), _: pso::PipelineStage) {
-        unimplemented!()
+    unsafe fn set_event(&mut self, event: &n::Event, _: pso::PipelineStage) {
+        self.push_cmd(Command::SetEvent(event.clone()));
     }
 
-    unsafe fn reset_event(&mut self, _: &(), _: pso::PipelineStage) {
-        unimplemented!()
+    unsafe fn reset_event(&mut self, event: &n::Event, _: pso::PipelineStage) {
+        self.push_cmd(Command::ResetEvent(event.clone()));
     }
 
-    unsafe fn wait_events<'a, I, J>(&mut self, _: I, _: Range<pso::PipelineStage>, _: J)
+    unsafe fn wait_events<'a, I, J>(&mut self, events: I, _: Range<pso::PipelineStage>, _: J)
     where
         I: IntoIterator,
-        I::Item: Borrow<()>,
+        I::Item: Borrow<n::Event>,
         J: IntoIterator,
         J::Item: Borrow<memory::Barrier<'a, Backend>>,
     {
-        unimplemented!()
+        // Barriers are ignored, as in `pipeline_barrier`.
+        for event in events {
+            self.push_cmd(Command::WaitEvent(event.borrow().clone()));
+        }
     }
 
-    unsafe fn begin_query(&mut self, _query: query::Query<Backend>, _flags: query::ControlFlags) {
-        unimplemented!()
+    unsafe fn begin_query(&mut self, query: query::Query<Backend>, flags: query::ControlFlags) {
+        let pool = query.pool;
+        if pool.ty == query::Type::Occlusion {
+            let target = if flags.contains(query::ControlFlags::PRECISE) {
+                glow::SAMPLES_PASSED
+            } else {
+                pool.targets[0]
+            };
+            self.cache.occlusion_target = Some(target);
+            self.push_cmd(Command::BeginQuery(
+                pool.queries(query.id .. query.id + 1)[0],
+                target,
+            ));
+        } else {
+            for (&gl_query, &target) in pool
+                .queries(query.id .. query.id + 1)
+                .iter()
+                .zip(&pool.targets)
+            {
+                self.push_cmd(Command::BeginQuery(gl_query, target));
+            }
+        }
     }
 
     unsafe fn copy_query_pool_results(
         &mut self,
-        _pool: &(),
-        _queries: Range<query::Id>,
-        _buffer: &n::Buffer,
-        _offset: buffer::Offset,
-        _stride: buffer::Offset,
-        _flags: query::ResultFlags,
+        pool: &n::QueryPool,
+        queries: Range<query::Id>,
+        buffer: &n::Buffer,
+        offset: buffer::Offset,
+        stride: buffer::Offset,
+        flags: query::ResultFlags,
     ) {
-        unimplemented!()
+        let (raw, range) = buffer.as_bound();
+        let queries = self.add(pool.queries(queries));
+        self.push_cmd(Command::CopyQueryPoolResults {
+            queries,
+            values: pool.targets.len() as u32,
+            buffer: raw,
+            offset: range.start + offset,
+            stride,
+            flags,
+        });
     }
 
-    unsafe fn end_query(&mut self, _query: query::Query<Backend>) {
-        unimplemented!()
+    unsafe fn end_query(&mut self, query: query::Query<Backend>) {
+        let pool = query.pool;
+        if pool.ty == query::Type::Occlusion {
+            match self.cache.occlusion_target.take() {
+                Some(target) => self.push_cmd(Command::EndQuery(target)),
+                None => {
+                    warn!("No occlusion query is active. `begin_query` needs to be called before `end_query`.");
+                    self.cache.error_state = true;
+                }
+            }
+        } else {
+            for &target in &pool.targets {
+                self.push_cmd(Command::EndQuery(target));
+            }
+        }
     }
 
-    unsafe fn reset_query_pool(&mut self, _pool: &(), _queries: Range<query::Id>) {
-        unimplemented!()
+    unsafe fn reset_query_pool(&mut self, _pool: &n::QueryPool, _queries: Range<query::Id>) {
+        // Nothing to do, GL queries are overwritten when they begin again.
     }
 
-    unsafe fn write_timestamp(&mut self, _: pso::PipelineStage, _: query::Query<Backend>) {
-        unimplemented!()
+    unsafe fn write_timestamp(&mut self, _: pso::PipelineStage, query: query::Query<Backend>) {
+        let gl_query = query.pool.queries(query.id .. query.id + 1)[0];
+        self.push_cmd(Command::WriteTimestamp(gl_query));
     }
 
     unsafe fn push_graphics_constants(
         &mut self,
-        _layout: &n::PipelineLayout,
-        _stages: pso::ShaderStageFlags,
+        layout: &n::PipelineLayout,
+        stages: pso::ShaderStageFlags,
         offset: u32,
         constants: &[u32],
     ) {
-        let buffer = self.add(constants);
-
-        let uniforms = &self.cache.uniforms;
-        if uniforms.is_empty() {
-            unimplemented!()
-        }
-
-        let uniform = if offset == 0 {
-            // If offset is zero, we can just return the first item
-            // in our uniform list
-            uniforms.get(0).unwrap()
-        } else {
-            match uniforms.binary_search_by(|uniform| uniform.offset.cmp(&offset as _)) {
-                Ok(index) => uniforms.get(index).unwrap(),
-                Err(_) => panic!("No uniform found at offset: {}", offset),
-            }
-        }
-        .clone();
-
-        self.push_cmd(Command::BindUniform { uniform, buffer });
+        self.push_constants(layout, stages, offset, constants);
     }
 
     unsafe fn push_compute_constants(
         &mut self,
-        _layout: &n::PipelineLayout,
-        _offset: u32,
-        _constants: &[u32],
+        layout: &n::PipelineLayout,
+        offset: u32,
+        constants: &[u32],
     ) {
-        unimplemented!()
+        self.push_constants(layout, pso::ShaderStageFlags::COMPUTE, offset, constants);
     }
 
     unsafe fn execute_commands<'a, T, I>(&mut self, _buffers: I)
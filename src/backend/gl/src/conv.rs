@@ -1,6 +1,6 @@
 use crate::native::VertexAttribFunction;
 use hal::format::Format;
-use hal::{image as i, Primitive};
+use hal::{image as i, query, Primitive};
 
 /*
 pub fn _image_kind_to_gl(kind: i::Kind) -> t::GLenum {
@@ -59,6 +59,48 @@ pub fn primitive_to_gl_primitive(primitive: Primitive) -> u32 {
     }
 }
 
+/// Returns the query targets of the pipeline statistics, ordered by flag bits
+/// like the results of the query.
+pub fn pipeline_statistics_to_gl(statistics: query::PipelineStatistic) -> Vec<u32> {
+    use hal::query::PipelineStatistic as Ps;
+
+    [
+        (Ps::INPUT_ASSEMBLY_VERTICES, glow::VERTICES_SUBMITTED),
+        (Ps::INPUT_ASSEMBLY_PRIMITIVES, glow::PRIMITIVES_SUBMITTED),
+        (
+            Ps::VERTEX_SHADER_INVOCATIONS,
+            glow::VERTEX_SHADER_INVOCATIONS,
+        ),
+        (
+            Ps::GEOMETRY_SHADER_INVOCATIONS,
+            glow::GEOMETRY_SHADER_INVOCATIONS,
+        ),
+        (
+            Ps::GEOMETRY_SHADER_PRIMITIVES,
+            glow::GEOMETRY_SHADER_PRIMITIVES_EMITTED,
+        ),
+        (Ps::CLIPPING_INVOCATIONS, glow::CLIPPING_INPUT_PRIMITIVES),
+        (Ps::CLIPPING_PRIMITIVES, glow::CLIPPING_OUTPUT_PRIMITIVES),
+        (
+            Ps::FRAGMENT_SHADER_INVOCATIONS,
+            glow::FRAGMENT_SHADER_INVOCATIONS,
+        ),
+        (Ps::HULL_SHADER_PATCHES, glow::TESS_CONTROL_SHADER_PATCHES),
+        (
+            Ps::DOMAIN_SHADER_INVOCATIONS,
+            glow::TESS_EVALUATION_SHADER_INVOCATIONS,
+        ),
+        (
+            Ps::COMPUTE_SHADER_INVOCATIONS,
+            glow::COMPUTE_SHADER_INVOCATIONS,
+        ),
+    ]
+    .iter()
+    .filter(|&&(statistic, _)| statistics.contains(statistic))
+    .map(|&(_, target)| target)
+    .collect()
+}
+
 pub struct FormatDescription {
     pub tex_internal: u32,
     pub tex_external: u32,
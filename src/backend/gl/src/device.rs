@@ -7,7 +7,7 @@ use std::ops::Range;
 use std::slice;
 use std::sync::Arc;
 
-use glow::Context as _;
+use glow::HasContext as _;
 
 use auxil::spirv_cross_specialize_ast;
 
@@ -54,7 +54,94 @@ fn gen_unexpected_error(err: SpirvErrorCode) -> d::ShaderError {
     d::ShaderError::CompilationFailed(msg)
 }
 
-fn create_fbo_internal(share: &Starc<Share>) -> Option<<GlContext as glow::Context>::Framebuffer> {
+/// Name of the members of push constant blocks, the uniforms backing them are
+/// found by name after linking.
+fn push_constant_member_name(index: u32) -> String {
+    format!("member{}", index)
+}
+
+/// Replaces the names of the push constant block members with names derived
+/// from their index, so that stripped modules and name collisions don't lose
+/// the uniforms backing them.
+fn name_push_constant_members(words: &[u32]) -> Vec<u32> {
+    const OP_MEMBER_NAME: u32 = 6;
+    const OP_TYPE_STRUCT: u32 = 30;
+    const OP_TYPE_POINTER: u32 = 32;
+    const OP_VARIABLE: u32 = 59;
+    const STORAGE_CLASS_PUSH_CONSTANT: u32 = 9;
+    // Instructions allowed before the debug names, in the logical layout of a module.
+    const PREAMBLE_OPS: &[u32] = &[2, 3, 4, 5, 6, 7, 10, 11, 14, 15, 16, 17, 330, 331];
+    const HEADER_LEN: usize = 5;
+
+    let instructions = || {
+        let mut offset = HEADER_LEN;
+        std::iter::from_fn(move || {
+            let word = *words.get(offset)?;
+            let len = ((word >> 16) as usize).max(1);
+            let inst = (offset, word & 0xFFFF, &words[offset + 1 .. (offset + len).min(words.len())]);
+            offset += len;
+            Some(inst)
+        })
+    };
+
+    let mut pointers = Vec::new();
+    for (_, op, operands) in instructions() {
+        if op == OP_VARIABLE && operands.get(2) == Some(&STORAGE_CLASS_PUSH_CONSTANT) {
+            pointers.push(operands[0]);
+        }
+    }
+    let mut blocks = Vec::new();
+    for (_, op, operands) in instructions() {
+        if op == OP_TYPE_POINTER && pointers.contains(&operands[0]) {
+            blocks.push(operands[2]);
+        }
+    }
+    let mut members = Vec::new();
+    for (_, op, operands) in instructions() {
+        if op == OP_TYPE_STRUCT && blocks.contains(&operands[0]) {
+            members.push((operands[0], operands.len() as u32 - 1));
+        }
+    }
+    if members.is_empty() {
+        return words.to_vec();
+    }
+
+    let mut names = Vec::new();
+    for &(id, count) in &members {
+        for index in 0 .. count {
+            let mut bytes = push_constant_member_name(index).into_bytes();
+            // Nul-terminated and padded to a whole number of words
+            bytes.resize((bytes.len() / 4 + 1) * 4, 0);
+            names.push(((3 + bytes.len() as u32 / 4) << 16) | OP_MEMBER_NAME);
+            names.push(id);
+            names.push(index);
+            names.extend(
+                bytes
+                    .chunks(4)
+                    .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]])),
+            );
+        }
+    }
+
+    let mut result = words[.. HEADER_LEN.min(words.len())].to_vec();
+    let mut inserted = false;
+    for (offset, op, operands) in instructions() {
+        if !inserted && !PREAMBLE_OPS.contains(&op) {
+            result.extend_from_slice(&names);
+            inserted = true;
+        }
+        if op == OP_MEMBER_NAME && members.iter().any(|&(id, _)| operands.first() == Some(&id)) {
+            continue;
+        }
+        result.extend_from_slice(&words[offset .. offset + 1 + operands.len()]);
+    }
+    if !inserted {
+        result.extend_from_slice(&names);
+    }
+    result
+}
+
+fn create_fbo_internal(share: &Starc<Share>) -> Option<<GlContext as glow::HasContext>::Framebuffer> {
     if share.private_caps.framebuffer {
         let gl = &share.context;
         let name = unsafe { gl.create_framebuffer() }.unwrap();
@@ -179,10 +266,38 @@ impl Device {
         })
     }
 
+    /// Push constant blocks are translated to plain uniform structs, whose members
+    /// are set with `glUniform*` by name. Collects the byte offset of each member,
+    /// keyed by the name of its uniform.
+    ///
+    /// The members must have been named with `name_push_constant_members`.
     fn set_push_const_layout(
         &self,
-        _ast: &mut spirv::Ast<glsl::Target>,
+        ast: &mut spirv::Ast<glsl::Target>,
+        stage: pso::Stage,
+        offsets: &mut FastHashMap<String, u32>,
     ) -> Result<(), d::ShaderError> {
+        let res = ast.get_shader_resources().map_err(gen_unexpected_error)?;
+        for pc in &res.push_constant_buffers {
+            // Each stage gets its own block name, as the blocks of different
+            // stages may declare different members.
+            let name = format!("GFX_HAL_PUSH_CONSTANTS_{:?}", stage).to_uppercase();
+            ast.set_name(pc.id, &name).map_err(gen_unexpected_error)?;
+
+            let ty = ast
+                .get_type(pc.base_type_id)
+                .map_err(gen_unexpected_error)?;
+            let member_count = match ty {
+                spirv::Type::Struct { member_types, .. } => member_types.len() as u32,
+                _ => continue,
+            };
+            for index in 0 .. member_count {
+                let offset = ast
+                    .get_member_decoration(pc.base_type_id, index, spirv::Decoration::Offset)
+                    .map_err(gen_unexpected_error)?;
+                offsets.insert(format!("{}.{}", name, push_constant_member_name(index)), offset);
+            }
+        }
         Ok(())
     }
 
@@ -248,6 +363,14 @@ impl Device {
             &res.sampled_images,
             n::BindingTypes::Images,
         );
+        // Subpass inputs are translated to textures read with `texelFetch`.
+        self.remap_binding(
+            ast,
+            desc_remap_data,
+            nb_map,
+            &res.subpass_inputs,
+            n::BindingTypes::Images,
+        );
         self.remap_binding(
             ast,
             desc_remap_data,
@@ -368,6 +491,7 @@ impl Device {
         stage: pso::Stage,
         desc_remap_data: &mut n::DescRemapData,
         name_binding_map: &mut FastHashMap<String, pso::DescriptorBinding>,
+        push_constant_offsets: &mut FastHashMap<String, u32>,
     ) -> n::Shader {
         assert_eq!(point.entry, "main");
         match *point.module {
@@ -376,7 +500,8 @@ impl Device {
                 raw
             }
             n::ShaderModule::Spirv(ref spirv) => {
-                let mut ast = self.parse_spirv(spirv).unwrap();
+                let spirv = name_push_constant_members(spirv);
+                let mut ast = self.parse_spirv(&spirv).unwrap();
 
                 spirv_cross_specialize_ast(&mut ast, &point.specialization).unwrap();
                 self.remap_bindings(&mut ast, desc_remap_data, name_binding_map);
@@ -385,7 +510,8 @@ impl Device {
                     desc_remap_data,
                     name_binding_map,
                 );
-                self.set_push_const_layout(&mut ast).unwrap();
+                self.set_push_const_layout(&mut ast, stage, push_constant_offsets)
+                    .unwrap();
 
                 let glsl = self.translate_spirv(&mut ast).unwrap();
                 debug!("SPIRV-Cross generated shader:\n{}", glsl);
@@ -398,6 +524,51 @@ impl Device {
             }
         }
     }
+
+    /// Returns the uniforms of a linked program backing the push constants,
+    /// sorted by their offset in the push constant range.
+    unsafe fn get_push_constant_uniforms(
+        &self,
+        program: n::Program,
+        push_constant_offsets: &FastHashMap<String, u32>,
+    ) -> Result<Vec<n::UniformDesc>, pso::CreationError> {
+        let gl = &self.share.context;
+        let mut uniforms = Vec::new();
+        for index in 0 .. gl.get_active_uniforms(program) {
+            let glow::ActiveUniform { size, utype, name } =
+                gl.get_active_uniform(program, index).unwrap();
+            let offset = match push_constant_offsets.get(&name) {
+                Some(&offset) => offset,
+                // Samplers and uniform blocks aren't push constants
+                None => continue,
+            };
+            if size != 1 {
+                warn!("Push constant arrays are not supported: {}", name);
+                continue;
+            }
+            let size = match n::UniformDesc::type_size(utype) {
+                Some(size) => size,
+                None => {
+                    return Err(pso::CreationError::Shader(
+                        d::ShaderError::InterfaceMismatch(format!(
+                            "Unsupported push constant type {:#x}: {}",
+                            utype, name
+                        )),
+                    ))
+                }
+            };
+            if let Some(location) = gl.get_uniform_location(program, &name) {
+                uniforms.push(n::UniformDesc {
+                    location,
+                    offset,
+                    size,
+                    utype,
+                });
+            }
+        }
+        uniforms.sort_by_key(|uniform| uniform.offset);
+        Ok(uniforms)
+    }
 }
 
 pub(crate) unsafe fn set_sampler_info<SetParamFloat, SetParamFloatVec, SetParamInt>(
@@ -621,10 +792,13 @@ impl d::Device<B> for Device {
                 let color_attachments = subpass.colors.iter().map(|&(index, _)| index).collect();
 
                 let depth_stencil = subpass.depth_stencil.map(|ds| ds.0);
+                let resolve_attachments =
+                    subpass.resolves.iter().map(|&(index, _)| index).collect();
 
                 n::SubpassDesc {
                     color_attachments,
                     depth_stencil,
+                    resolve_attachments,
                 }
             })
             .collect();
@@ -641,7 +815,7 @@ impl d::Device<B> for Device {
     unsafe fn create_pipeline_layout<IS, IR>(
         &self,
         layouts: IS,
-        _: IR,
+        push_constant_ranges: IR,
     ) -> Result<n::PipelineLayout, d::OutOfMemory>
     where
         IS: IntoIterator,
@@ -665,7 +839,8 @@ impl d::Device<B> for Device {
                 assert!(!binding.immutable_samplers); //TODO: Implement immutable_samplers
                 use crate::pso::DescriptorType::*;
                 match binding.ty {
-                    CombinedImageSampler => {
+                    CombinedImageSampler | InputAttachment => {
+                        // Input attachments are bound as textures
                         drd.insert_missing_binding_into_spare(
                             n::BindingTypes::Images,
                             set as _,
@@ -691,13 +866,17 @@ impl d::Device<B> for Device {
                         );
                     }
                     StorageImage | UniformTexelBuffer | UniformBufferDynamic
-                    | StorageTexelBuffer | StorageBufferDynamic | InputAttachment => unimplemented!(), // 6
+                    | StorageTexelBuffer | StorageBufferDynamic => unimplemented!(), // 5
                 }
             })
         });
 
         Ok(n::PipelineLayout {
             desc_remap_data: Arc::new(RwLock::new(drd)),
+            push_constant_ranges: push_constant_ranges
+                .into_iter()
+                .map(|range| range.borrow().clone())
+                .collect(),
         })
     }
 
@@ -739,6 +918,7 @@ impl d::Device<B> for Device {
             }
         };
 
+        let mut push_constant_offsets = FastHashMap::default();
         let program = {
             let name = gl.create_program().unwrap();
 
@@ -761,6 +941,7 @@ impl d::Device<B> for Device {
                             stage,
                             &mut desc.layout.desc_remap_data.write(),
                             &mut name_binding_map,
+                            &mut push_constant_offsets,
                         );
                         gl.attach_shader(name, shader_name);
                         shader_name
@@ -827,30 +1008,7 @@ impl d::Device<B> for Device {
             vertex_buffers[vb.binding as usize] = Some(*vb);
         }
 
-        let mut uniforms = Vec::new();
-        {
-            let gl = &self.share.context;
-            let count = gl.get_active_uniforms(program);
-
-            let mut offset = 0;
-
-            for uniform in 0 .. count {
-                let glow::ActiveUniform { size, utype, name } =
-                    gl.get_active_uniform(program, uniform).unwrap();
-
-                if let Some(location) = gl.get_uniform_location(program, &name) {
-                    // Sampler2D won't show up in UniformLocation and the only other uniforms
-                    // should be push constants
-                    uniforms.push(n::UniformDesc {
-                        location: location as _,
-                        offset,
-                        utype,
-                    });
-
-                    offset += size as u32;
-                }
-            }
-        }
+        let uniforms = self.get_push_constant_uniforms(program, &push_constant_offsets)?;
 
         Ok(n::GraphicsPipeline {
             program,
@@ -887,6 +1045,7 @@ impl d::Device<B> for Device {
         let gl = &self.share.context;
         let share = &self.share;
 
+        let mut push_constant_offsets = FastHashMap::default();
         let program = {
             let name = gl.create_program().unwrap();
 
@@ -896,6 +1055,7 @@ impl d::Device<B> for Device {
                 pso::Stage::Compute,
                 &mut desc.layout.desc_remap_data.write(),
                 &mut name_binding_map,
+                &mut push_constant_offsets,
             );
 
             gl.attach_shader(name, shader);
@@ -934,7 +1094,9 @@ impl d::Device<B> for Device {
             name
         };
 
-        Ok(n::ComputePipeline { program })
+        let uniforms = self.get_push_constant_uniforms(program, &push_constant_offsets)?;
+
+        Ok(n::ComputePipeline { program, uniforms })
     }
 
     unsafe fn create_framebuffer<I>(
@@ -960,7 +1122,7 @@ impl d::Device<B> for Device {
         let gl = &self.share.context;
         let target = glow::DRAW_FRAMEBUFFER;
 
-        let fbos = pass.subpasses.iter().map(|subpass| {
+        let fbos: Vec<_> = pass.subpasses.iter().map(|subpass| {
             let name = gl.create_framebuffer().unwrap();
             gl.bind_framebuffer(target, Some(name));
 
@@ -1009,9 +1171,40 @@ impl d::Device<B> for Device {
             Some(name)
         }).collect();
 
+        let mut resolve_fbos = Vec::with_capacity(pass.subpasses.len());
+        for subpass in &pass.subpasses {
+            if subpass.resolve_attachments.is_empty() {
+                resolve_fbos.push(None);
+                continue;
+            }
+            let name = gl.create_framebuffer().unwrap();
+            gl.bind_framebuffer(target, Some(name));
+
+            // Resolve attachments use the attachment points of their color attachments
+            for (index, &resolve) in subpass.resolve_attachments.iter().enumerate() {
+                let color_attachment = glow::COLOR_ATTACHMENT0 + index as u32;
+                if self.share.private_caps.framebuffer_texture {
+                    Self::bind_target(gl, target, color_attachment, &attachments[resolve]);
+                } else {
+                    Self::bind_target_compat(gl, target, color_attachment, &attachments[resolve]);
+                }
+            }
+
+            let status = gl.check_framebuffer_status(target);
+            resolve_fbos.push(Some(name));
+            if status != glow::FRAMEBUFFER_COMPLETE {
+                error!("Unexpected resolve framebuffer status code {}", status);
+                gl.bind_framebuffer(target, None);
+                for name in fbos.into_iter().chain(resolve_fbos).flatten() {
+                    gl.delete_framebuffer(name);
+                }
+                return Err(d::OutOfMemory::Host);
+            }
+        }
+
         gl.bind_framebuffer(target, None);
 
-        Ok(n::FrameBuffer { fbos })
+        Ok(n::FrameBuffer { fbos, resolve_fbos })
     }
 
     unsafe fn create_shader_module(
@@ -1266,6 +1459,7 @@ impl d::Device<B> for Device {
         let image = if num_levels > 1
             || usage.contains(i::Usage::STORAGE)
             || usage.contains(i::Usage::SAMPLED)
+            || usage.contains(i::Usage::INPUT_ATTACHMENT)
         {
             let name = gl.create_texture().unwrap();
             let target = match kind {
@@ -1358,6 +1552,17 @@ impl d::Device<B> for Device {
                     gl.bind_renderbuffer(glow::RENDERBUFFER, Some(name));
                     gl.renderbuffer_storage(glow::RENDERBUFFER, desc.tex_internal, w as _, h as _);
                 }
+                i::Kind::D2(w, h, 1, samples) => {
+                    // Multisampled render targets, resolved at the end of subpasses
+                    gl.bind_renderbuffer(glow::RENDERBUFFER, Some(name));
+                    gl.renderbuffer_storage_multisample(
+                        glow::RENDERBUFFER,
+                        samples as _,
+                        desc.tex_internal,
+                        w as _,
+                        h as _,
+                    );
+                }
                 _ => unimplemented!(),
             };
             n::ImageKind::Renderbuffer {
@@ -1696,20 +1901,32 @@ impl d::Device<B> for Device {
         })
     }
 
-    fn create_event(&self) -> Result<(), d::OutOfMemory> {
-        unimplemented!()
+    fn create_event(&self) -> Result<n::Event, d::OutOfMemory> {
+        Ok(n::Event::new(&self.share))
     }
 
-    unsafe fn get_event_status(&self, _event: &()) -> Result<bool, d::OomOrDeviceLost> {
-        unimplemented!()
+    unsafe fn get_event_status(&self, event: &n::Event) -> Result<bool, d::OomOrDeviceLost> {
+        Ok(match event.state() {
+            n::EventInner::Pending(sync) => {
+                let gl = &self.share.context;
+                let signaled = gl.get_sync_status(sync) == glow::SIGNALED;
+                if signaled {
+                    event.set_state(gl, n::EventInner::Idle { set: true });
+                }
+                signaled
+            }
+            n::EventInner::Idle { set } => set,
+        })
     }
 
-    unsafe fn set_event(&self, _event: &()) -> Result<(), d::OutOfMemory> {
-        unimplemented!()
+    unsafe fn set_event(&self, event: &n::Event) -> Result<(), d::OutOfMemory> {
+        event.set_state(&self.share.context, n::EventInner::Idle { set: true });
+        Ok(())
     }
 
-    unsafe fn reset_event(&self, _event: &()) -> Result<(), d::OutOfMemory> {
-        unimplemented!()
+    unsafe fn reset_event(&self, event: &n::Event) -> Result<(), d::OutOfMemory> {
+        event.set_state(&self.share.context, n::EventInner::Idle { set: false });
+        Ok(())
     }
 
     unsafe fn free_memory(&self, memory: n::Memory) {
@@ -1720,25 +1937,72 @@ impl d::Device<B> for Device {
 
     unsafe fn create_query_pool(
         &self,
-        _ty: query::Type,
-        _count: query::Id,
-    ) -> Result<(), query::CreationError> {
-        unimplemented!()
+        ty: query::Type,
+        count: query::Id,
+    ) -> Result<n::QueryPool, query::CreationError> {
+        let gl = &self.share.context;
+        let features = self.share.features;
+        let private_caps = &self.share.private_caps;
+
+        let targets = match ty {
+            query::Type::Occlusion if private_caps.occlusion_query => {
+                vec![glow::ANY_SAMPLES_PASSED]
+            }
+            query::Type::Occlusion if features.contains(hal::Features::PRECISE_OCCLUSION_QUERY) => {
+                vec![glow::SAMPLES_PASSED]
+            }
+            query::Type::PipelineStatistics(statistics)
+                if features.contains(hal::Features::PIPELINE_STATISTICS_QUERY) =>
+            {
+                conv::pipeline_statistics_to_gl(statistics)
+            }
+            query::Type::Timestamp if private_caps.timer_query => vec![glow::TIMESTAMP],
+            _ => return Err(query::CreationError::Unsupported(ty)),
+        };
+
+        let mut queries = Vec::with_capacity(count as usize * targets.len());
+        for _ in 0 .. queries.capacity() {
+            match gl.create_query() {
+                Ok(query) => queries.push(query),
+                Err(err) => {
+                    error!("Query creation failed: {:?}", err);
+                    for query in queries {
+                        gl.delete_query(query);
+                    }
+                    return Err(d::OutOfMemory::Host.into());
+                }
+            }
+        }
+
+        Ok(n::QueryPool {
+            ty,
+            targets,
+            queries,
+        })
     }
 
-    unsafe fn destroy_query_pool(&self, _: ()) {
-        unimplemented!()
+    unsafe fn destroy_query_pool(&self, pool: n::QueryPool) {
+        let gl = &self.share.context;
+        for query in pool.queries {
+            gl.delete_query(query);
+        }
     }
 
     unsafe fn get_query_pool_results(
         &self,
-        _pool: &(),
-        _queries: Range<query::Id>,
-        _data: &mut [u8],
-        _stride: buffer::Offset,
-        _flags: query::ResultFlags,
+        pool: &n::QueryPool,
+        queries: Range<query::Id>,
+        data: &mut [u8],
+        stride: buffer::Offset,
+        flags: query::ResultFlags,
     ) -> Result<bool, d::OomOrDeviceLost> {
-        unimplemented!()
+        let mut all_available = true;
+        for (i, queries) in pool.queries(queries).chunks(pool.targets.len()).enumerate() {
+            all_available &=
+                self.share
+                    .write_query_result(queries, &mut data[i * stride as usize ..], flags);
+        }
+        Ok(all_available)
     }
 
     unsafe fn destroy_shader_module(&self, _: n::ShaderModule) {
@@ -1763,7 +2027,11 @@ impl d::Device<B> for Device {
 
     unsafe fn destroy_framebuffer(&self, frame_buffer: n::FrameBuffer) {
         let gl = &self.share.context;
-        for f in frame_buffer.fbos {
+        for f in frame_buffer
+            .fbos
+            .into_iter()
+            .chain(frame_buffer.resolve_fbos)
+        {
             if let Some(f) = f {
                 gl.delete_framebuffer(f);
             }
@@ -1819,8 +2087,8 @@ impl d::Device<B> for Device {
         // Nothing to do
     }
 
-    unsafe fn destroy_event(&self, _event: ()) {
-        unimplemented!()
+    unsafe fn destroy_event(&self, _event: n::Event) {
+        // The sync object is deleted once the commands referencing the event are gone.
     }
 
     unsafe fn create_swapchain(
@@ -1911,8 +2179,8 @@ impl d::Device<B> for Device {
             extent: config.extent,
             context: {
                 surface.context().resize(glutin::dpi::PhysicalSize::new(
-                    config.extent.width as f64,
-                    config.extent.height as f64,
+                    config.extent.width,
+                    config.extent.height,
                 ));
                 surface.context.clone()
             },
@@ -1991,3 +2259,48 @@ impl d::Device<B> for Device {
         // TODO
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::name_push_constant_members;
+    use spirv_cross::{glsl, spirv};
+
+    /// Vertex shader with a push constant block `{ vec4; float; }`, without any debug names.
+    const STRIPPED_PUSH_CONSTANTS: &[u32] = &[
+        0x0723_0203, 0x0001_0000, 0, 10, 0,
+        2 << 16 | 17, 1, // OpCapability Shader
+        3 << 16 | 14, 0, 1, // OpMemoryModel Logical GLSL450
+        5 << 16 | 15, 0, 1, 0x6E69_616D, 0, // OpEntryPoint Vertex %1 "main"
+        5 << 16 | 72, 2, 0, 35, 0, // OpMemberDecorate %2 0 Offset 0
+        5 << 16 | 72, 2, 1, 35, 16, // OpMemberDecorate %2 1 Offset 16
+        3 << 16 | 71, 2, 2, // OpDecorate %2 Block
+        3 << 16 | 22, 3, 32, // %3 = OpTypeFloat 32
+        4 << 16 | 23, 4, 3, 4, // %4 = OpTypeVector %3 4
+        4 << 16 | 30, 2, 4, 3, // %2 = OpTypeStruct %4 %3
+        4 << 16 | 32, 5, 9, 2, // %5 = OpTypePointer PushConstant %2
+        4 << 16 | 59, 5, 6, 9, // %6 = OpVariable %5 PushConstant
+        2 << 16 | 19, 7, // %7 = OpTypeVoid
+        3 << 16 | 33, 8, 7, // %8 = OpTypeFunction %7
+        5 << 16 | 54, 7, 1, 0, 8, // %1 = OpFunction %7 None %8
+        2 << 16 | 248, 9, // %9 = OpLabel
+        1 << 16 | 253, // OpReturn
+        1 << 16 | 56, // OpFunctionEnd
+    ];
+
+    #[test]
+    fn test_name_push_constant_members() {
+        let words = name_push_constant_members(STRIPPED_PUSH_CONSTANTS);
+        let module = spirv::Module::from_words(&words);
+        let ast = spirv::Ast::<glsl::Target>::parse(&module).unwrap();
+        assert_eq!(ast.get_member_name(2, 0).unwrap(), "member0");
+        assert_eq!(ast.get_member_name(2, 1).unwrap(), "member1");
+        assert_eq!(
+            ast.get_member_decoration(2, 1, spirv::Decoration::Offset)
+                .unwrap(),
+            16
+        );
+
+        // Existing names are replaced
+        assert_eq!(name_push_constant_members(&words), words);
+    }
+}
@@ -3,7 +3,7 @@ use hal::{Features, Limits};
 use std::collections::HashSet;
 use std::{fmt, str};
 
-use glow::Context;
+use glow::HasContext;
 
 /// A version number for a specific component of an OpenGL implementation
 #[derive(Clone, Eq, Ord, PartialEq, PartialOrd)]
@@ -203,6 +203,10 @@ pub struct PrivateCaps {
     pub draw_buffers: bool,
     /// Whether or not glColorMaski / glBlendEquationi / glBlendFunci are available
     pub per_draw_buffer_blending: bool,
+    /// Whether boolean occlusion queries (`GL_ANY_SAMPLES_PASSED`) are supported
+    pub occlusion_query: bool,
+    /// Whether timestamp queries and 64-bit query results are supported
+    pub timer_query: bool,
 }
 
 /// OpenGL implementation information
@@ -415,11 +419,17 @@ pub(crate) fn query_all(gl: &GlContainer) -> (Info, Features, LegacyFeatures, Li
         features |= Features::SAMPLER_MIP_LOD_BIAS;
     }
 
-    // TODO
-    if false && info.is_supported(&[Core(4, 3), Es(3, 1)]) {
+    if info.is_supported(&[Core(4, 3), Es(3, 1)]) {
         // TODO: extension
         legacy |= LegacyFeatures::INDIRECT_EXECUTION;
     }
+    if info.is_supported(&[
+        Core(4, 3),
+        Ext("GL_ARB_multi_draw_indirect"),
+        Ext("GL_EXT_multi_draw_indirect"),
+    ]) {
+        features |= Features::MULTI_DRAW_INDIRECT;
+    }
     if info.is_supported(&[Core(3, 1), Es(3, 0), Ext("GL_ARB_draw_instanced")]) {
         legacy |= LegacyFeatures::DRAW_INSTANCED;
     }
@@ -484,6 +494,14 @@ pub(crate) fn query_all(gl: &GlContainer) -> (Info, Features, LegacyFeatures, Li
         features |= Features::INDEPENDENT_BLENDING;
     }
 
+    if info.is_supported(&[Core(1, 5)]) {
+        // `GL_SAMPLES_PASSED` isn't available in GLES
+        features |= Features::PRECISE_OCCLUSION_QUERY;
+    }
+    if info.is_supported(&[Core(4, 6), Ext("GL_ARB_pipeline_statistics_query")]) {
+        features |= Features::PIPELINE_STATISTICS_QUERY;
+    }
+
     let emulate_map = info.version.is_embedded;
 
     let private = PrivateCaps {
@@ -507,6 +525,16 @@ pub(crate) fn query_all(gl: &GlContainer) -> (Info, Features, LegacyFeatures, Li
         depth_range_f64_precision: !info.version.is_embedded, // TODO
         draw_buffers: info.is_supported(&[Core(2, 0), Es(3, 0)]),
         per_draw_buffer_blending,
+        // The query entry points aren't wired up on WebGL.
+        occlusion_query: !info.is_webgl()
+            && info.is_supported(&[
+                Core(3, 3),
+                Es(3, 0),
+                Ext("GL_ARB_occlusion_query2"),
+                Ext("GL_EXT_occlusion_query_boolean"),
+            ]),
+        timer_query: !info.is_webgl()
+            && info.is_supported(&[Core(3, 3), Ext("GL_ARB_timer_query")]),
     };
 
     (info, features, legacy, limits, private)
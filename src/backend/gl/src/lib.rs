@@ -17,7 +17,7 @@ use std::ops::Deref;
 use std::sync::{Arc, Weak};
 use std::thread::{self, ThreadId};
 
-use hal::{adapter, buffer, image, memory, pso, queue as q};
+use hal::{adapter, buffer, image, memory, pso, query, queue as q};
 
 pub use self::device::Device;
 pub use self::info::{Info, PlatformName, Version};
@@ -45,15 +45,24 @@ use window::wgl::{DeviceContext, Surface, Swapchain};
 #[cfg(not(any(target_arch = "wasm32", feature = "glutin", feature = "wgl")))]
 pub use window::dummy::{Surface, Swapchain};
 
-#[cfg(not(target_arch = "wasm32"))]
-pub use glow::native::Context as GlContext;
-#[cfg(target_arch = "wasm32")]
-pub use glow::web::Context as GlContext;
-use glow::Context;
+pub use glow::Context as GlContext;
+use glow::HasContext;
 
+#[cfg(not(target_arch = "wasm32"))]
+#[allow(non_camel_case_types, non_snake_case, non_upper_case_globals, dead_code, bare_trait_objects)]
+#[allow(clippy::all)]
+mod gl_sys {
+    include!(concat!(env!("OUT_DIR"), "/gl_sys.rs"));
+}
 
 pub(crate) struct GlContainer {
     context: GlContext,
+    /// Function pointers for the entry points `glow` doesn't expose.
+    #[cfg(not(target_arch = "wasm32"))]
+    raw: gl_sys::Gl,
+    /// The WebGL context, for the entry points `glow` doesn't expose.
+    #[cfg(target_arch = "wasm32")]
+    raw: web_sys::WebGl2RenderingContext,
 }
 
 impl GlContainer {
@@ -62,18 +71,27 @@ impl GlContainer {
         // Unimplemented
     }
 
-    #[cfg(not(target_arch = "wasm32"))]
-    fn from_fn_proc<F>(fn_proc: F) -> GlContainer
+    #[cfg(all(not(target_arch = "wasm32"), any(feature = "glutin", feature = "wgl")))]
+    fn from_fn_proc<F>(mut fn_proc: F) -> GlContainer
     where
         F: FnMut(&str) -> *const std::os::raw::c_void,
     {
-        let context = glow::native::Context::from_loader_function(fn_proc);
-        GlContainer { context }
+        let context = glow::Context::from_loader_function(&mut fn_proc);
+        // GLES exposes some of them with the `EXT` suffix only.
+        let raw = gl_sys::Gl::load_with(|name| {
+            let ptr = fn_proc(name);
+            if ptr.is_null() {
+                fn_proc(&format!("{}EXT", name))
+            } else {
+                ptr
+            }
+        });
+        GlContainer { context, raw }
     }
 
     #[cfg(target_arch = "wasm32")]
     fn from_new_canvas() -> GlContainer {
-        let context = {
+        let raw = {
             use wasm_bindgen::JsCast;
             let document = web_sys::window()
                 .and_then(|win| win.document())
@@ -107,9 +125,154 @@ impl GlContainer {
                 .expect("Cannot get document body")
                 .append_child(&canvas)
                 .expect("Cannot insert canvas into document body");
-            glow::web::Context::from_webgl2_context(webgl2_context)
+            webgl2_context
         };
-        GlContainer { context }
+        let context = glow::Context::from_webgl2_context(raw.clone());
+        GlContainer { context, raw }
+    }
+}
+
+/// Entry points missing from `glow`, called through the raw function pointers.
+#[cfg(not(target_arch = "wasm32"))]
+impl GlContainer {
+    unsafe fn create_query(&self) -> Result<native::Query, String> {
+        let mut query = 0;
+        self.raw.GenQueries(1, &mut query);
+        if query != 0 {
+            Ok(query)
+        } else {
+            Err("glGenQueries returned no name".to_string())
+        }
+    }
+
+    unsafe fn delete_query(&self, query: native::Query) {
+        self.raw.DeleteQueries(1, &query);
+    }
+
+    unsafe fn begin_query(&self, target: u32, query: native::Query) {
+        self.raw.BeginQuery(target, query);
+    }
+
+    unsafe fn end_query(&self, target: u32) {
+        self.raw.EndQuery(target);
+    }
+
+    unsafe fn query_counter(&self, query: native::Query, target: u32) {
+        self.raw.QueryCounter(query, target);
+    }
+
+    unsafe fn get_query_parameter_u32(&self, query: native::Query, parameter: u32) -> u32 {
+        let mut value = 0;
+        self.raw.GetQueryObjectuiv(query, parameter, &mut value);
+        value
+    }
+
+    unsafe fn get_query_parameter_u64(&self, query: native::Query, parameter: u32) -> u64 {
+        let mut value = 0;
+        self.raw.GetQueryObjectui64v(query, parameter, &mut value);
+        value
+    }
+
+    unsafe fn wait_sync(&self, sync: <GlContext as HasContext>::Fence, flags: u32, timeout: u64) {
+        self.raw.WaitSync(sync as gl_sys::types::GLsync, flags, timeout);
+    }
+
+    unsafe fn read_buffer(&self, src: u32) {
+        self.raw.ReadBuffer(src);
+    }
+
+    unsafe fn renderbuffer_storage_multisample(
+        &self,
+        target: u32,
+        samples: i32,
+        internal_format: u32,
+        width: i32,
+        height: i32,
+    ) {
+        self.raw
+            .RenderbufferStorageMultisample(target, samples, internal_format, width, height);
+    }
+
+    unsafe fn draw_arrays_indirect_offset(&self, mode: u32, offset: i32) {
+        self.raw.DrawArraysIndirect(mode, offset as usize as *const _);
+    }
+
+    unsafe fn draw_elements_indirect_offset(&self, mode: u32, element_type: u32, offset: i32) {
+        self.raw
+            .DrawElementsIndirect(mode, element_type, offset as usize as *const _);
+    }
+
+    unsafe fn multi_draw_arrays_indirect_offset(
+        &self,
+        mode: u32,
+        offset: i32,
+        draw_count: i32,
+        stride: i32,
+    ) {
+        self.raw
+            .MultiDrawArraysIndirect(mode, offset as usize as *const _, draw_count, stride);
+    }
+
+    unsafe fn multi_draw_elements_indirect_offset(
+        &self,
+        mode: u32,
+        element_type: u32,
+        offset: i32,
+        draw_count: i32,
+        stride: i32,
+    ) {
+        self.raw.MultiDrawElementsIndirect(
+            mode,
+            element_type,
+            offset as usize as *const _,
+            draw_count,
+            stride,
+        );
+    }
+}
+
+/// Entry points missing from `glow`. Queries and sync objects are never used on WebGL,
+/// the others are called on the WebGL context.
+#[cfg(target_arch = "wasm32")]
+impl GlContainer {
+    unsafe fn create_query(&self) -> Result<native::Query, String> {
+        Err("Queries are not supported on WebGL".to_string())
+    }
+
+    unsafe fn delete_query(&self, _: native::Query) {}
+
+    unsafe fn begin_query(&self, _: u32, _: native::Query) {}
+
+    unsafe fn end_query(&self, _: u32) {}
+
+    unsafe fn query_counter(&self, _: native::Query, _: u32) {}
+
+    unsafe fn get_query_parameter_u32(&self, _: native::Query, _: u32) -> u32 {
+        0
+    }
+
+    unsafe fn get_query_parameter_u64(&self, _: native::Query, _: u32) -> u64 {
+        0
+    }
+
+    unsafe fn wait_sync(&self, _: <GlContext as HasContext>::Fence, _: u32, _: u64) {
+        // Sync objects aren't used on WebGL, events are set once submitted.
+    }
+
+    unsafe fn read_buffer(&self, src: u32) {
+        self.raw.read_buffer(src);
+    }
+
+    unsafe fn renderbuffer_storage_multisample(
+        &self,
+        target: u32,
+        samples: i32,
+        internal_format: u32,
+        width: i32,
+        height: i32,
+    ) {
+        self.raw
+            .renderbuffer_storage_multisample(target, samples, internal_format, width, height);
     }
 }
 
@@ -158,8 +321,8 @@ impl hal::Backend for Backend {
 
     type Fence = native::Fence;
     type Semaphore = native::Semaphore;
-    type Event = ();
-    type QueryPool = ();
+    type Event = native::Event;
+    type QueryPool = native::QueryPool;
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
@@ -272,6 +435,49 @@ impl Share {
         Ok(())
     }
 
+    /// Writes the results of a query, backed by the given GL queries, into `data`
+    /// with the layout of `vkGetQueryPoolResults`.
+    /// Returns true if the results were available.
+    unsafe fn write_query_result(
+        &self,
+        queries: &[native::Query],
+        data: &mut [u8],
+        flags: query::ResultFlags,
+    ) -> bool {
+        let gl = &self.context;
+        let write_value = |data: &mut [u8], index: usize, value: u64| {
+            if flags.contains(query::ResultFlags::BITS_64) {
+                data[index * 8 .. (index + 1) * 8].copy_from_slice(&value.to_ne_bytes());
+            } else {
+                data[index * 4 .. (index + 1) * 4].copy_from_slice(&(value as u32).to_ne_bytes());
+            }
+        };
+
+        // Reading `GL_QUERY_RESULT` blocks until the result is available.
+        let available = flags.contains(query::ResultFlags::WAIT)
+            || queries
+                .iter()
+                .all(|&query| gl.get_query_parameter_u32(query, glow::QUERY_RESULT_AVAILABLE) != 0);
+        if available {
+            for (index, &query) in queries.iter().enumerate() {
+                let value = if self.private_caps.timer_query {
+                    gl.get_query_parameter_u64(query, glow::QUERY_RESULT)
+                } else {
+                    gl.get_query_parameter_u32(query, glow::QUERY_RESULT) as u64
+                };
+                write_value(data, index, value);
+            }
+        } else if flags.contains(query::ResultFlags::PARTIAL) {
+            for index in 0 .. queries.len() {
+                write_value(data, index, 0);
+            }
+        }
+        if flags.contains(query::ResultFlags::WITH_AVAILABILITY) {
+            write_value(data, queries.len(), available as u64);
+        }
+        available
+    }
+
     fn buffer_memory_type_mask(&self, usage: buffer::Usage) -> u64 {
         let mut type_mask = 0;
         for (type_index, &(_, kind)) in self.memory_types.iter().enumerate() {
@@ -382,6 +588,12 @@ pub struct Wstarc<T: ?Sized> {
     weak: Weak<T>,
     thread: ThreadId,
 }
+impl<T: ?Sized> fmt::Debug for Wstarc<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(fmt, "{:p}@{:?}", self.weak.as_ptr(), self.thread)
+    }
+}
+
 impl<T> Wstarc<T> {
     pub fn upgrade(&self) -> Option<Starc<T>> {
         let thread = self.thread;
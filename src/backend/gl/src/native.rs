@@ -5,30 +5,36 @@ use std::sync::Arc;
 
 use hal::backend::FastHashMap;
 use hal::memory::{Properties, Requirements};
-use hal::{buffer, format, image as i, pass, pso};
+use hal::{buffer, format, image as i, pass, pso, query};
 
-use crate::{Backend, GlContext};
+use crate::{Backend, GlContext, Share, Starc, Wstarc};
+
+use glow::HasContext as _;
 
 pub type TextureTarget = u32;
 pub type TextureFormat = u32;
 pub type DataType = u32;
 
 // TODO: Consider being generic over `glow::Context` instead
-pub type VertexArray = <GlContext as glow::Context>::VertexArray;
-pub type RawBuffer = <GlContext as glow::Context>::Buffer;
-pub type Shader = <GlContext as glow::Context>::Shader;
-pub type Program = <GlContext as glow::Context>::Program;
-pub type Renderbuffer = <GlContext as glow::Context>::Renderbuffer;
-pub type Texture = <GlContext as glow::Context>::Texture;
-pub type Sampler = <GlContext as glow::Context>::Sampler;
-pub type UniformLocation = <GlContext as glow::Context>::UniformLocation;
+pub type VertexArray = <GlContext as glow::HasContext>::VertexArray;
+pub type RawBuffer = <GlContext as glow::HasContext>::Buffer;
+pub type Shader = <GlContext as glow::HasContext>::Shader;
+pub type Program = <GlContext as glow::HasContext>::Program;
+pub type Renderbuffer = <GlContext as glow::HasContext>::Renderbuffer;
+pub type Texture = <GlContext as glow::HasContext>::Texture;
+pub type Sampler = <GlContext as glow::HasContext>::Sampler;
+pub type UniformLocation = <GlContext as glow::HasContext>::UniformLocation;
+// `glow` has no query objects, they are raw GL names.
+pub type Query = u32;
 pub type DescriptorSetLayout = Vec<pso::DescriptorSetLayoutBinding>;
 
-pub type RawFrameBuffer = <GlContext as glow::Context>::Framebuffer;
+pub type RawFrameBuffer = <GlContext as glow::HasContext>::Framebuffer;
 
 #[derive(Clone, Debug)]
 pub struct FrameBuffer {
     pub(crate) fbos: Vec<Option<RawFrameBuffer>>,
+    /// FBOs with the resolve attachments of each subpass, if it has any.
+    pub(crate) resolve_fbos: Vec<Option<RawFrameBuffer>>,
 }
 
 #[derive(Debug)]
@@ -59,7 +65,7 @@ pub struct BufferView;
 #[derive(Copy, Clone, Debug)]
 pub(crate) enum FenceInner {
     Idle { signaled: bool },
-    Pending(Option<<GlContext as glow::Context>::Fence>),
+    Pending(Option<<GlContext as glow::HasContext>::Fence>),
 }
 
 #[derive(Debug)]
@@ -67,6 +73,60 @@ pub struct Fence(pub(crate) Cell<FenceInner>);
 unsafe impl Send for Fence {}
 unsafe impl Sync for Fence {}
 
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum EventInner {
+    Idle {
+        set: bool,
+    },
+    /// Set by a command, once the GPU reaches the sync object.
+    Pending(<GlContext as glow::HasContext>::Fence),
+}
+// The sync object is a handle, only used with the context.
+unsafe impl Send for EventInner {}
+
+#[derive(Debug)]
+pub(crate) struct EventShared {
+    pub(crate) state: Mutex<EventInner>,
+    share: Wstarc<Share>,
+}
+
+impl Drop for EventShared {
+    fn drop(&mut self) {
+        // Recorded commands keep the event alive after `destroy_event`,
+        // the sync object goes away with the last of them.
+        if let EventInner::Pending(sync) = *self.state.get_mut() {
+            if let Some(share) = self.share.upgrade() {
+                unsafe { share.context.delete_sync(sync) };
+            }
+        }
+    }
+}
+
+/// Event, shared with the commands setting and waiting on it.
+#[derive(Clone, Debug)]
+pub struct Event(pub(crate) Arc<EventShared>);
+
+impl Event {
+    pub(crate) fn new(share: &Starc<Share>) -> Self {
+        Event(Arc::new(EventShared {
+            state: Mutex::new(EventInner::Idle { set: false }),
+            share: Starc::downgrade(share),
+        }))
+    }
+
+    pub(crate) fn state(&self) -> EventInner {
+        *self.0.state.lock()
+    }
+
+    /// Replace the state of the event, deleting the sync object it was pending on.
+    pub(crate) unsafe fn set_state(&self, gl: &GlContext, state: EventInner) {
+        let old = std::mem::replace(&mut *self.0.state.lock(), state);
+        if let EventInner::Pending(sync) = old {
+            gl.delete_sync(sync);
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
 pub enum BindingTypes {
     Images,
@@ -170,6 +230,7 @@ pub struct GraphicsPipeline {
 #[derive(Clone, Debug)]
 pub struct ComputePipeline {
     pub(crate) program: Program,
+    pub(crate) uniforms: Vec<UniformDesc>,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -286,6 +347,8 @@ pub struct RenderPass {
 pub struct SubpassDesc {
     pub(crate) color_attachments: Vec<usize>,
     pub(crate) depth_stencil: Option<usize>,
+    /// Either empty or one per color attachment.
+    pub(crate) resolve_attachments: Vec<usize>,
 }
 
 impl SubpassDesc {
@@ -305,12 +368,31 @@ impl SubpassDesc {
 #[derive(Debug)]
 pub struct PipelineLayout {
     pub(crate) desc_remap_data: Arc<RwLock<DescRemapData>>,
+    pub(crate) push_constant_ranges: Vec<(pso::ShaderStageFlags, Range<u32>)>,
 }
 
 #[derive(Debug)]
 // No inter-queue synchronization required for GL.
 pub struct Semaphore;
 
+#[derive(Debug)]
+pub struct QueryPool {
+    pub(crate) ty: query::Type,
+    /// Query targets written by each query of the pool, in the order of their results.
+    /// Pipeline statistics need one GL query per statistic.
+    pub(crate) targets: Vec<u32>,
+    /// GL queries, `targets.len()` per query of the pool.
+    pub(crate) queries: Vec<Query>,
+}
+
+impl QueryPool {
+    /// Returns the GL queries backing a range of queries of the pool.
+    pub(crate) fn queries(&self, range: Range<query::Id>) -> &[Query] {
+        let count = self.targets.len();
+        &self.queries[range.start as usize * count .. range.end as usize * count]
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct AttributeDesc {
     pub(crate) location: u32,
@@ -324,10 +406,31 @@ pub struct AttributeDesc {
 #[derive(Clone, Copy, Debug)]
 pub struct UniformDesc {
     pub(crate) location: UniformLocation,
+    /// Offset in bytes of the uniform in the push constant range.
     pub(crate) offset: u32,
+    /// Size in bytes of the uniform in the push constant range.
+    pub(crate) size: u32,
     pub(crate) utype: u32,
 }
 
+impl UniformDesc {
+    /// Size in bytes of a uniform of the given type in the push constant range,
+    /// using the std430 layout of push constant blocks. `None` for the types
+    /// push constants can't be backed by.
+    pub(crate) fn type_size(utype: u32) -> Option<u32> {
+        Some(match utype {
+            glow::FLOAT | glow::INT => 4,
+            glow::FLOAT_VEC2 | glow::INT_VEC2 => 8,
+            glow::FLOAT_VEC3 | glow::INT_VEC3 => 12,
+            glow::FLOAT_VEC4 | glow::INT_VEC4 | glow::FLOAT_MAT2 => 16,
+            // Columns are aligned to 16 bytes
+            glow::FLOAT_MAT3 => 44,
+            glow::FLOAT_MAT4 => 64,
+            _ => return None,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum VertexAttribFunction {
     Float,   // glVertexAttribPointer
@@ -1,7 +1,7 @@
 use std::borrow::Borrow;
-use std::{mem, slice};
+use std::{mem, ptr, slice};
 
-use glow::Context;
+use glow::HasContext;
 use smallvec::SmallVec;
 
 use crate::{
@@ -295,6 +295,74 @@ impl CommandQueue {
         }
     }
 
+    /// Read the arguments of indirect draws back from the indirect buffer,
+    /// for the devices without indirect execution. Each draw has `words`
+    /// arguments, the remaining ones are left to zero.
+    fn read_indirect_args(
+        &self,
+        buffer: native::RawBuffer,
+        offset: hal::buffer::Offset,
+        draw_count: hal::DrawCount,
+        stride: u32,
+        words: usize,
+    ) -> Vec<[u32; 5]> {
+        let gl = &self.share.context;
+        if draw_count == 0 {
+            return Vec::new();
+        }
+
+        let size = (draw_count - 1) as usize * stride as usize + words * 4;
+        let mut bytes = vec![0u8; size];
+        unsafe {
+            gl.bind_buffer(glow::COPY_READ_BUFFER, Some(buffer));
+            if self.share.info.is_webgl() {
+                gl.get_buffer_sub_data(glow::COPY_READ_BUFFER, offset as i32, &mut bytes);
+            } else {
+                // The indirect buffer itself may not be mappable (GLES, or immutable
+                // storage without `GL_MAP_READ_BIT`), read a copy of the arguments.
+                let staging = gl.create_buffer().unwrap();
+                gl.bind_buffer(glow::COPY_WRITE_BUFFER, Some(staging));
+                gl.buffer_data_size(glow::COPY_WRITE_BUFFER, size as i32, glow::STREAM_READ);
+                gl.copy_buffer_sub_data(
+                    glow::COPY_READ_BUFFER,
+                    glow::COPY_WRITE_BUFFER,
+                    offset as i32,
+                    0,
+                    size as i32,
+                );
+                let ptr = gl.map_buffer_range(
+                    glow::COPY_WRITE_BUFFER,
+                    0,
+                    size as i32,
+                    glow::MAP_READ_BIT,
+                );
+                if ptr.is_null() {
+                    error!("Failed to map the indirect arguments");
+                } else {
+                    ptr::copy_nonoverlapping(ptr, bytes.as_mut_ptr(), size);
+                    gl.unmap_buffer(glow::COPY_WRITE_BUFFER);
+                }
+                gl.bind_buffer(glow::COPY_WRITE_BUFFER, None);
+                gl.delete_buffer(staging);
+            }
+            gl.bind_buffer(glow::COPY_READ_BUFFER, None);
+        }
+
+        (0 .. draw_count as usize)
+            .map(|index| {
+                let mut args = [0u32; 5];
+                let start = index * stride as usize;
+                for (arg, chunk) in args
+                    .iter_mut()
+                    .zip(bytes[start .. start + words * 4].chunks(4))
+                {
+                    *arg = u32::from_ne_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+                }
+                args
+            })
+            .collect()
+    }
+
     fn process(&mut self, cmd: &com::Command, data_buf: &[u8]) {
         match *cmd {
             com::Command::BindIndexBuffer(buffer) => {
@@ -426,6 +494,126 @@ impl CommandQueue {
                     error!("Instanced indexed drawing is not supported");
                 }
             }
+            com::Command::DrawIndirect {
+                primitive,
+                buffer,
+                offset,
+                draw_count,
+                stride,
+            } => {
+                let legacy = &self.share.legacy_features;
+                // WebGL has no indirect draws, the arguments are always read back there.
+                if cfg!(not(target_arch = "wasm32"))
+                    && legacy.contains(LegacyFeatures::INDIRECT_EXECUTION)
+                {
+                    #[cfg(not(target_arch = "wasm32"))]
+                    unsafe {
+                        let gl = &self.share.context;
+                        gl.bind_buffer(glow::DRAW_INDIRECT_BUFFER, Some(buffer));
+                        if draw_count > 1
+                            && self
+                                .share
+                                .features
+                                .contains(hal::Features::MULTI_DRAW_INDIRECT)
+                        {
+                            gl.multi_draw_arrays_indirect_offset(
+                                primitive,
+                                offset as i32,
+                                draw_count as i32,
+                                stride as i32,
+                            );
+                        } else {
+                            for index in 0 .. draw_count {
+                                let offset = offset
+                                    + index as hal::buffer::Offset * stride as hal::buffer::Offset;
+                                gl.draw_arrays_indirect_offset(primitive, offset as i32);
+                            }
+                        }
+                    }
+                } else {
+                    // VkDrawIndirectCommand
+                    for args in self.read_indirect_args(buffer, offset, draw_count, stride, 4) {
+                        let [vertex_count, instance_count, first_vertex, first_instance, _] = args;
+                        self.process(
+                            &com::Command::Draw {
+                                primitive,
+                                vertices: first_vertex .. first_vertex + vertex_count,
+                                instances: first_instance .. first_instance + instance_count,
+                            },
+                            data_buf,
+                        );
+                    }
+                }
+            }
+            com::Command::DrawIndexedIndirect {
+                primitive,
+                index_type,
+                index_buffer_offset,
+                buffer,
+                offset,
+                draw_count,
+                stride,
+            } => {
+                let legacy = &self.share.legacy_features;
+                // `glDrawElementsIndirect` takes the first index relative to the start of
+                // the GL buffer, index buffers bound at an offset go through the fallback.
+                if cfg!(not(target_arch = "wasm32"))
+                    && legacy.contains(LegacyFeatures::INDIRECT_EXECUTION)
+                    && index_buffer_offset == 0
+                {
+                    #[cfg(not(target_arch = "wasm32"))]
+                    unsafe {
+                        let gl = &self.share.context;
+                        gl.bind_buffer(glow::DRAW_INDIRECT_BUFFER, Some(buffer));
+                        if draw_count > 1
+                            && self
+                                .share
+                                .features
+                                .contains(hal::Features::MULTI_DRAW_INDIRECT)
+                        {
+                            gl.multi_draw_elements_indirect_offset(
+                                primitive,
+                                index_type,
+                                offset as i32,
+                                draw_count as i32,
+                                stride as i32,
+                            );
+                        } else {
+                            for index in 0 .. draw_count {
+                                let offset = offset
+                                    + index as hal::buffer::Offset * stride as hal::buffer::Offset;
+                                gl.draw_elements_indirect_offset(
+                                    primitive,
+                                    index_type,
+                                    offset as i32,
+                                );
+                            }
+                        }
+                    }
+                } else {
+                    let index_size = match index_type {
+                        glow::UNSIGNED_SHORT => 2,
+                        _ => 4,
+                    };
+                    // VkDrawIndexedIndirectCommand
+                    for args in self.read_indirect_args(buffer, offset, draw_count, stride, 5) {
+                        let [index_count, instance_count, first_index, vertex_offset, first_instance] =
+                            args;
+                        self.process(
+                            &com::Command::DrawIndexed {
+                                primitive,
+                                index_type,
+                                index_count,
+                                index_buffer_offset: index_buffer_offset
+                                    + first_index as hal::buffer::Offset * index_size,
+                                base_vertex: vertex_offset as hal::VertexOffset,
+                                instances: first_instance .. first_instance + instance_count,
+                            },
+                            data_buf,
+                        );
+                    }
+                }
+            }
             com::Command::Dispatch(count) => {
                 // Capability support is given by which queue types will be exposed.
                 // If there is no compute support, this pattern should never be reached
@@ -565,6 +753,34 @@ impl CommandQueue {
                     error!("Tried to bind FBO without FBO support!");
                 }
             }
+            com::Command::ResolveAttachment {
+                src,
+                dst,
+                attachment,
+                rect,
+            } => unsafe {
+                let gl = &self.share.context;
+                gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(src));
+                gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, Some(dst));
+                gl.read_buffer(attachment);
+                gl.draw_buffers(&[attachment]);
+
+                let (x0, y0) = (rect.x as i32, rect.y as i32);
+                let (x1, y1) = (x0 + rect.w as i32, y0 + rect.h as i32);
+                gl.blit_framebuffer(
+                    x0,
+                    y0,
+                    x1,
+                    y1,
+                    x0,
+                    y0,
+                    x1,
+                    y1,
+                    glow::COLOR_BUFFER_BIT,
+                    glow::NEAREST,
+                );
+                self.state.fbo = Some(dst);
+            },
             com::Command::BindTargetView(point, attachment, view) => {
                 self.bind_target(point, attachment, &view)
             }
@@ -829,6 +1045,77 @@ impl CommandQueue {
                     |a, b| gl.tex_parameter_f32_slice(textype, a, &b),
                     |a, b| gl.tex_parameter_i32(textype, a, b),
                 );
+            },
+            com::Command::SetEvent(ref event) => unsafe {
+                let gl = &self.share.context;
+                let state = if self.share.private_caps.sync {
+                    native::EventInner::Pending(
+                        gl.fence_sync(glow::SYNC_GPU_COMMANDS_COMPLETE, 0).unwrap(),
+                    )
+                } else {
+                    // No sync capability, the event is set once the commands are submitted
+                    native::EventInner::Idle { set: true }
+                };
+                event.set_state(gl, state);
+            },
+            com::Command::ResetEvent(ref event) => unsafe {
+                event.set_state(&self.share.context, native::EventInner::Idle { set: false });
+            },
+            com::Command::WaitEvent(ref event) => match event.state() {
+                native::EventInner::Pending(sync) => unsafe {
+                    self.share.context.wait_sync(sync, 0, glow::TIMEOUT_IGNORED);
+                },
+                native::EventInner::Idle { set: true } => {}
+                native::EventInner::Idle { set: false } => {
+                    error!("Waiting on an event which isn't set, it would never complete");
+                }
+            },
+            com::Command::BeginQuery(query, target) => unsafe {
+                self.share.context.begin_query(target, query);
+            },
+            com::Command::EndQuery(target) => unsafe {
+                self.share.context.end_query(target);
+            },
+            com::Command::WriteTimestamp(query) => unsafe {
+                self.share.context.query_counter(query, glow::TIMESTAMP);
+            },
+            com::Command::CopyQueryPoolResults {
+                queries,
+                values,
+                buffer,
+                offset,
+                stride,
+                flags,
+            } => unsafe {
+                // TODO: write the results on the GPU with `GL_ARB_query_buffer_object`
+                // instead of waiting for them.
+                let gl = &self.share.context;
+                let queries = Self::get::<native::Query>(data_buf, queries);
+                let value_size = if flags.contains(hal::query::ResultFlags::BITS_64) {
+                    8
+                } else {
+                    4
+                };
+                let value_count = values as usize
+                    + flags.contains(hal::query::ResultFlags::WITH_AVAILABILITY) as usize;
+                let mut result = vec![0; value_count * value_size];
+
+                gl.bind_buffer(glow::COPY_WRITE_BUFFER, Some(buffer));
+                for (i, queries) in queries.chunks(values as usize).enumerate() {
+                    // Results of the previous commands become available in order,
+                    // waiting on them lets all of them be written.
+                    self.share.write_query_result(
+                        queries,
+                        &mut result,
+                        flags | hal::query::ResultFlags::WAIT,
+                    );
+                    gl.buffer_sub_data_u8_slice(
+                        glow::COPY_WRITE_BUFFER,
+                        (offset + i as hal::buffer::Offset * stride) as i32,
+                        &result,
+                    );
+                }
+                gl.bind_buffer(glow::COPY_WRITE_BUFFER, None);
             }, /*
             com::Command::BindConstantBuffer(pso::ConstantBufferParam(buffer, _, slot)) => unsafe {
             self.share.context.BindBufferBase(gl::UNIFORM_BUFFER, slot as gl::types::GLuint, buffer);
@@ -923,7 +1210,12 @@ impl CommandQueue {
                             gl.uniform_matrix_2_f32_slice(Some(uniform.location), false, &data);
                         }
                         glow::FLOAT_MAT3 => {
-                            let data = Self::get::<[f32; 9]>(data_buf, buffer)[0];
+                            // Columns are padded to 4 components in push constant blocks
+                            let padded = Self::get::<[f32; 11]>(data_buf, buffer)[0];
+                            let mut data = [0.0; 9];
+                            for (column, chunk) in data.chunks_mut(3).zip(padded.chunks(4)) {
+                                column.copy_from_slice(&chunk[.. 3]);
+                            }
                             gl.uniform_matrix_3_f32_slice(Some(uniform.location), false, &data);
                         }
                         glow::FLOAT_MAT4 => {
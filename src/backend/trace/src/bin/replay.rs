@@ -0,0 +1,95 @@
+#![cfg_attr(
+    not(any(
+        feature = "vulkan",
+        feature = "dx12",
+        feature = "dx11",
+        feature = "metal",
+        feature = "empty"
+    )),
+    allow(dead_code)
+)]
+
+extern crate gfx_hal as hal;
+
+use gfx_backend_trace::{ReplayError, Replayer};
+
+use std::path::Path;
+
+fn replay<I: hal::Instance>(instance: I, dir: &Path) -> Result<(), ReplayError> {
+    let adapter = instance
+        .enumerate_adapters()
+        .into_iter()
+        .next()
+        .ok_or_else(|| ReplayError::Device("no adapter found".into()))?;
+    println!("\tAdapter {}", adapter.info.name);
+    unsafe {
+        let mut replayer = Replayer::<I::Backend>::new(adapter, dir)?;
+        replayer.run()
+    }
+}
+
+fn report(result: Result<(), ReplayError>) {
+    match result {
+        Ok(()) => println!("\tDone"),
+        Err(e) => {
+            println!("\tFailed: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn main() {
+    #[cfg(feature = "env_logger")]
+    env_logger::init();
+
+    let dir = match std::env::args().nth(1) {
+        Some(dir) => dir,
+        None => {
+            println!("Call with the argument of the trace directory");
+            return;
+        }
+    };
+    let dir = Path::new(&dir);
+
+    #[cfg(feature = "vulkan")]
+    {
+        println!("Replaying on Vulkan:");
+        let instance = gfx_backend_vulkan::Instance::create("replay", 1).unwrap();
+        report(replay(instance, dir));
+    }
+    #[cfg(feature = "dx12")]
+    {
+        println!("Replaying on DX12:");
+        let instance = gfx_backend_dx12::Instance::create("replay", 1).unwrap();
+        report(replay(instance, dir));
+    }
+    #[cfg(feature = "dx11")]
+    {
+        println!("Replaying on DX11:");
+        let instance = gfx_backend_dx11::Instance::create("replay", 1).unwrap();
+        report(replay(instance, dir));
+    }
+    #[cfg(feature = "metal")]
+    {
+        println!("Replaying on Metal:");
+        let instance = gfx_backend_metal::Instance::create("replay", 1).unwrap();
+        report(replay(instance, dir));
+    }
+    #[cfg(feature = "empty")]
+    {
+        println!("Replaying on CPU:");
+        let instance = gfx_backend_empty::Instance::create("replay", 1).unwrap();
+        report(replay(instance, dir));
+    }
+    #[cfg(not(any(
+        feature = "vulkan",
+        feature = "dx12",
+        feature = "dx11",
+        feature = "metal",
+        feature = "empty"
+    )))]
+    {
+        let _ = dir;
+        println!("No backend selected, enable one of the features");
+    }
+}
@@ -0,0 +1,953 @@
+use crate::record::Tracer;
+use crate::trace::{self, Action, Command, Id};
+use crate::{Backend, Traced};
+
+use hal::command::{self as com, Level};
+use hal::image::{Filter, Layout, SubresourceRange};
+use hal::memory::{Barrier, Dependencies};
+use hal::range::RangeArg;
+use hal::{buffer, pass, pool, pso, query};
+use hal::{DrawCount, IndexCount, InstanceCount, VertexCount, VertexOffset, WorkGroupCount};
+
+use std::borrow::Borrow;
+use std::ops::Range;
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub struct CommandPool<B: hal::Backend> {
+    pub(crate) raw: B::CommandPool,
+    pub(crate) id: Id,
+    tracer: Arc<Tracer>,
+}
+
+impl<B: hal::Backend> CommandPool<B> {
+    pub(crate) fn new(raw: B::CommandPool, id: Id, tracer: Arc<Tracer>) -> Self {
+        CommandPool { raw, id, tracer }
+    }
+}
+
+impl<B: hal::Backend> pool::CommandPool<Backend<B>> for CommandPool<B> {
+    unsafe fn reset(&mut self, release_resources: bool) {
+        self.tracer.add(Action::ResetCommandPool {
+            pool: self.id,
+            release_resources,
+        });
+        self.raw.reset(release_resources)
+    }
+
+    fn allocate_vec(&mut self, num: usize, level: Level) -> Vec<CommandBuffer<B>> {
+        let cmd_buffers = self
+            .raw
+            .allocate_vec(num, level)
+            .into_iter()
+            .map(|raw| CommandBuffer {
+                raw,
+                id: self.tracer.id(),
+                tracer: Arc::clone(&self.tracer),
+                flags: com::CommandBufferFlags::empty(),
+                inheritance: None,
+                commands: Vec::new(),
+            })
+            .collect::<Vec<_>>();
+        self.tracer.add(Action::AllocateCommandBuffers {
+            pool: self.id,
+            level,
+            ids: cmd_buffers.iter().map(|cmd_buffer| cmd_buffer.id).collect(),
+        });
+        cmd_buffers
+    }
+
+    unsafe fn free<I>(&mut self, buffers: I)
+    where
+        I: IntoIterator<Item = CommandBuffer<B>>,
+    {
+        let buffers = buffers.into_iter().collect::<Vec<_>>();
+        self.tracer.add(Action::FreeCommandBuffers {
+            pool: self.id,
+            ids: buffers.iter().map(|cmd_buffer| cmd_buffer.id).collect(),
+        });
+        self.raw
+            .free(buffers.into_iter().map(|cmd_buffer| cmd_buffer.raw))
+    }
+}
+
+/// Command buffer, collecting the commands until the recording is finished.
+#[derive(Debug)]
+pub struct CommandBuffer<B: hal::Backend> {
+    pub(crate) raw: B::CommandBuffer,
+    pub(crate) id: Id,
+    tracer: Arc<Tracer>,
+    flags: com::CommandBufferFlags,
+    inheritance: Option<trace::InheritanceInfo>,
+    commands: Vec<Command>,
+}
+
+impl<B: hal::Backend> CommandBuffer<B> {
+    /// Get the identifier of the command buffer in the trace.
+    pub fn id(&self) -> Id {
+        self.id
+    }
+}
+
+fn convert_barriers<'a, B, T>(barriers: T) -> (Vec<Barrier<'a, B>>, Vec<trace::Barrier>)
+where
+    B: hal::Backend,
+    T: IntoIterator,
+    T::Item: Borrow<Barrier<'a, Backend<B>>>,
+{
+    barriers
+        .into_iter()
+        .map(|barrier| match *barrier.borrow() {
+            Barrier::AllBuffers(ref access) => (
+                Barrier::AllBuffers(access.clone()),
+                trace::Barrier::AllBuffers(access.clone()),
+            ),
+            Barrier::AllImages(ref access) => (
+                Barrier::AllImages(access.clone()),
+                trace::Barrier::AllImages(access.clone()),
+            ),
+            Barrier::Buffer {
+                ref states,
+                target,
+                ref families,
+                ref range,
+            } => (
+                Barrier::Buffer {
+                    states: states.clone(),
+                    target: &target.raw,
+                    families: families.clone(),
+                    range: range.clone(),
+                },
+                trace::Barrier::Buffer {
+                    states: states.clone(),
+                    target: target.id,
+                    families: families.clone(),
+                    range: range.clone(),
+                },
+            ),
+            Barrier::Image {
+                ref states,
+                target,
+                ref families,
+                ref range,
+            } => (
+                Barrier::Image {
+                    states: states.clone(),
+                    target: &target.raw,
+                    families: families.clone(),
+                    range: range.clone(),
+                },
+                trace::Barrier::Image {
+                    states: states.clone(),
+                    target: target.id,
+                    families: families.clone(),
+                    range: range.clone(),
+                },
+            ),
+        })
+        .unzip()
+}
+
+fn convert_query<'a, B: hal::Backend>(query: query::Query<'a, Backend<B>>) -> query::Query<'a, B> {
+    query::Query {
+        pool: &query.pool.raw,
+        id: query.id,
+    }
+}
+
+fn collect<T, I>(items: I) -> Vec<T>
+where
+    T: Clone,
+    I: IntoIterator,
+    I::Item: Borrow<T>,
+{
+    items
+        .into_iter()
+        .map(|item| item.borrow().clone())
+        .collect()
+}
+
+impl<B: hal::Backend> com::CommandBuffer<Backend<B>> for CommandBuffer<B> {
+    unsafe fn begin(
+        &mut self,
+        flags: com::CommandBufferFlags,
+        inheritance_info: com::CommandBufferInheritanceInfo<Backend<B>>,
+    ) {
+        self.flags = flags;
+        self.commands.clear();
+        self.inheritance = Some(trace::InheritanceInfo {
+            subpass: inheritance_info
+                .subpass
+                .as_ref()
+                .map(|subpass| (subpass.index, subpass.main_pass.id)),
+            framebuffer: inheritance_info.framebuffer.map(|fb| fb.id),
+            occlusion_query_enable: inheritance_info.occlusion_query_enable,
+            occlusion_query_flags: inheritance_info.occlusion_query_flags,
+            pipeline_statistics: inheritance_info.pipeline_statistics,
+        });
+        let inheritance_info = com::CommandBufferInheritanceInfo {
+            subpass: inheritance_info.subpass.map(|subpass| pass::Subpass {
+                index: subpass.index,
+                main_pass: &subpass.main_pass.raw,
+            }),
+            framebuffer: inheritance_info.framebuffer.map(|fb| &fb.raw),
+            occlusion_query_enable: inheritance_info.occlusion_query_enable,
+            occlusion_query_flags: inheritance_info.occlusion_query_flags,
+            pipeline_statistics: inheritance_info.pipeline_statistics,
+        };
+        self.raw.begin(flags, inheritance_info)
+    }
+
+    unsafe fn finish(&mut self) {
+        match self.inheritance.take() {
+            Some(inheritance) => self.tracer.add(Action::Record {
+                command_buffer: self.id,
+                flags: self.flags,
+                inheritance,
+                commands: self.commands.drain(..).collect(),
+            }),
+            None => error!("Command buffer {} is finished while not recording", self.id),
+        }
+        self.raw.finish()
+    }
+
+    unsafe fn reset(&mut self, release_resources: bool) {
+        self.inheritance = None;
+        self.commands.clear();
+        self.tracer.add(Action::ResetCommandBuffer {
+            command_buffer: self.id,
+            release_resources,
+        });
+        self.raw.reset(release_resources)
+    }
+
+    unsafe fn pipeline_barrier<'a, T>(
+        &mut self,
+        stages: Range<pso::PipelineStage>,
+        dependencies: Dependencies,
+        barriers: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<Barrier<'a, Backend<B>>>,
+    {
+        let (raw_barriers, barriers) = convert_barriers(barriers);
+        self.commands.push(Command::PipelineBarrier {
+            stages: stages.clone(),
+            dependencies,
+            barriers,
+        });
+        self.raw
+            .pipeline_barrier(stages, dependencies, raw_barriers)
+    }
+
+    unsafe fn fill_buffer<R>(&mut self, buffer: &Traced<B::Buffer>, range: R, data: u32)
+    where
+        R: RangeArg<buffer::Offset>,
+    {
+        self.commands.push(Command::FillBuffer {
+            buffer: buffer.id,
+            range: (range.start().cloned(), range.end().cloned()),
+            data,
+        });
+        self.raw.fill_buffer(&buffer.raw, range, data)
+    }
+
+    unsafe fn update_buffer(
+        &mut self,
+        buffer: &Traced<B::Buffer>,
+        offset: buffer::Offset,
+        data: &[u8],
+    ) {
+        self.commands.push(Command::UpdateBuffer {
+            buffer: buffer.id,
+            offset,
+            data: data.to_vec(),
+        });
+        self.raw.update_buffer(&buffer.raw, offset, data)
+    }
+
+    unsafe fn clear_image<T>(
+        &mut self,
+        image: &Traced<B::Image>,
+        layout: Layout,
+        value: com::ClearValue,
+        subresource_ranges: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<SubresourceRange>,
+    {
+        let ranges = collect(subresource_ranges);
+        self.commands.push(Command::ClearImage {
+            image: image.id,
+            layout,
+            value: value.into(),
+            ranges: ranges.clone(),
+        });
+        self.raw.clear_image(&image.raw, layout, value, ranges)
+    }
+
+    unsafe fn clear_attachments<T, U>(&mut self, clears: T, rects: U)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<com::AttachmentClear>,
+        U: IntoIterator,
+        U::Item: Borrow<pso::ClearRect>,
+    {
+        let clears = collect::<com::AttachmentClear, _>(clears);
+        let rects = collect(rects);
+        self.commands.push(Command::ClearAttachments {
+            clears: clears
+                .iter()
+                .map(|clear| match *clear {
+                    com::AttachmentClear::Color { index, value } => trace::AttachmentClear::Color {
+                        index,
+                        value: unsafe { value.uint32 },
+                    },
+                    com::AttachmentClear::DepthStencil { depth, stencil } => {
+                        trace::AttachmentClear::DepthStencil { depth, stencil }
+                    }
+                })
+                .collect(),
+            rects: rects.clone(),
+        });
+        self.raw.clear_attachments(clears, rects)
+    }
+
+    unsafe fn resolve_image<T>(
+        &mut self,
+        src: &Traced<B::Image>,
+        src_layout: Layout,
+        dst: &Traced<B::Image>,
+        dst_layout: Layout,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<com::ImageResolve>,
+    {
+        let regions = collect(regions);
+        self.commands.push(Command::ResolveImage {
+            src: src.id,
+            src_layout,
+            dst: dst.id,
+            dst_layout,
+            regions: regions.clone(),
+        });
+        self.raw
+            .resolve_image(&src.raw, src_layout, &dst.raw, dst_layout, regions)
+    }
+
+    unsafe fn blit_image<T>(
+        &mut self,
+        src: &Traced<B::Image>,
+        src_layout: Layout,
+        dst: &Traced<B::Image>,
+        dst_layout: Layout,
+        filter: Filter,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<com::ImageBlit>,
+    {
+        let regions = collect(regions);
+        self.commands.push(Command::BlitImage {
+            src: src.id,
+            src_layout,
+            dst: dst.id,
+            dst_layout,
+            filter,
+            regions: regions.clone(),
+        });
+        self.raw
+            .blit_image(&src.raw, src_layout, &dst.raw, dst_layout, filter, regions)
+    }
+
+    unsafe fn bind_index_buffer(&mut self, view: buffer::IndexBufferView<Backend<B>>) {
+        self.commands.push(Command::BindIndexBuffer {
+            buffer: view.buffer.id,
+            offset: view.offset,
+            index_type: view.index_type,
+        });
+        self.raw.bind_index_buffer(buffer::IndexBufferView {
+            buffer: &view.buffer.raw,
+            offset: view.offset,
+            index_type: view.index_type,
+        })
+    }
+
+    unsafe fn bind_vertex_buffers<I, T>(&mut self, first_binding: pso::BufferIndex, buffers: I)
+    where
+        I: IntoIterator<Item = (T, buffer::Offset)>,
+        T: Borrow<Traced<B::Buffer>>,
+    {
+        let buffers = buffers.into_iter().collect::<Vec<_>>();
+        self.commands.push(Command::BindVertexBuffers {
+            first_binding,
+            buffers: buffers
+                .iter()
+                .map(|&(ref buffer, offset)| (buffer.borrow().id, offset))
+                .collect(),
+        });
+        self.raw.bind_vertex_buffers(
+            first_binding,
+            buffers
+                .iter()
+                .map(|&(ref buffer, offset)| (&buffer.borrow().raw, offset)),
+        )
+    }
+
+    unsafe fn set_viewports<T>(&mut self, first_viewport: u32, viewports: T)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<pso::Viewport>,
+    {
+        let viewports = collect(viewports);
+        self.commands.push(Command::SetViewports {
+            first_viewport,
+            viewports: viewports.clone(),
+        });
+        self.raw.set_viewports(first_viewport, viewports)
+    }
+
+    unsafe fn set_scissors<T>(&mut self, first_scissor: u32, rects: T)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<pso::Rect>,
+    {
+        let rects = collect(rects);
+        self.commands.push(Command::SetScissors {
+            first_scissor,
+            rects: rects.clone(),
+        });
+        self.raw.set_scissors(first_scissor, rects)
+    }
+
+    unsafe fn set_stencil_reference(&mut self, faces: pso::Face, value: pso::StencilValue) {
+        self.commands
+            .push(Command::SetStencilReference { faces, value });
+        self.raw.set_stencil_reference(faces, value)
+    }
+
+    unsafe fn set_stencil_read_mask(&mut self, faces: pso::Face, value: pso::StencilValue) {
+        self.commands
+            .push(Command::SetStencilReadMask { faces, value });
+        self.raw.set_stencil_read_mask(faces, value)
+    }
+
+    unsafe fn set_stencil_write_mask(&mut self, faces: pso::Face, value: pso::StencilValue) {
+        self.commands
+            .push(Command::SetStencilWriteMask { faces, value });
+        self.raw.set_stencil_write_mask(faces, value)
+    }
+
+    unsafe fn set_blend_constants(&mut self, color: pso::ColorValue) {
+        self.commands.push(Command::SetBlendConstants(color));
+        self.raw.set_blend_constants(color)
+    }
+
+    unsafe fn set_depth_bounds(&mut self, bounds: Range<f32>) {
+        self.commands.push(Command::SetDepthBounds(bounds.clone()));
+        self.raw.set_depth_bounds(bounds)
+    }
+
+    unsafe fn set_line_width(&mut self, width: f32) {
+        self.commands.push(Command::SetLineWidth(width));
+        self.raw.set_line_width(width)
+    }
+
+    unsafe fn set_depth_bias(&mut self, depth_bias: pso::DepthBias) {
+        self.commands.push(Command::SetDepthBias(depth_bias));
+        self.raw.set_depth_bias(depth_bias)
+    }
+
+    unsafe fn begin_render_pass<T>(
+        &mut self,
+        render_pass: &Traced<B::RenderPass>,
+        framebuffer: &Traced<B::Framebuffer>,
+        render_area: pso::Rect,
+        clear_values: T,
+        first_subpass: com::SubpassContents,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<com::ClearValue>,
+    {
+        let clear_values = clear_values
+            .into_iter()
+            .map(|value| *value.borrow())
+            .collect::<Vec<_>>();
+        self.commands.push(Command::BeginRenderPass {
+            render_pass: render_pass.id,
+            framebuffer: framebuffer.id,
+            render_area,
+            clear_values: clear_values.iter().map(|&value| value.into()).collect(),
+            first_subpass,
+        });
+        self.raw.begin_render_pass(
+            &render_pass.raw,
+            &framebuffer.raw,
+            render_area,
+            clear_values,
+            first_subpass,
+        )
+    }
+
+    unsafe fn next_subpass(&mut self, contents: com::SubpassContents) {
+        self.commands.push(Command::NextSubpass(contents));
+        self.raw.next_subpass(contents)
+    }
+
+    unsafe fn end_render_pass(&mut self) {
+        self.commands.push(Command::EndRenderPass);
+        self.raw.end_render_pass()
+    }
+
+    unsafe fn bind_graphics_pipeline(&mut self, pipeline: &Traced<B::GraphicsPipeline>) {
+        self.commands
+            .push(Command::BindGraphicsPipeline(pipeline.id));
+        self.raw.bind_graphics_pipeline(&pipeline.raw)
+    }
+
+    unsafe fn bind_graphics_descriptor_sets<I, J>(
+        &mut self,
+        layout: &Traced<B::PipelineLayout>,
+        first_set: usize,
+        sets: I,
+        offsets: J,
+    ) where
+        I: IntoIterator,
+        I::Item: Borrow<Traced<B::DescriptorSet>>,
+        J: IntoIterator,
+        J::Item: Borrow<com::DescriptorSetOffset>,
+    {
+        let sets = sets.into_iter().collect::<Vec<_>>();
+        let offsets = collect(offsets);
+        self.commands.push(Command::BindGraphicsDescriptorSets {
+            layout: layout.id,
+            first_set,
+            sets: sets.iter().map(|set| set.borrow().id).collect(),
+            offsets: offsets.clone(),
+        });
+        self.raw.bind_graphics_descriptor_sets(
+            &layout.raw,
+            first_set,
+            sets.iter().map(|set| &set.borrow().raw),
+            offsets,
+        )
+    }
+
+    unsafe fn bind_compute_pipeline(&mut self, pipeline: &Traced<B::ComputePipeline>) {
+        self.commands
+            .push(Command::BindComputePipeline(pipeline.id));
+        self.raw.bind_compute_pipeline(&pipeline.raw)
+    }
+
+    unsafe fn bind_compute_descriptor_sets<I, J>(
+        &mut self,
+        layout: &Traced<B::PipelineLayout>,
+        first_set: usize,
+        sets: I,
+        offsets: J,
+    ) where
+        I: IntoIterator,
+        I::Item: Borrow<Traced<B::DescriptorSet>>,
+        J: IntoIterator,
+        J::Item: Borrow<com::DescriptorSetOffset>,
+    {
+        let sets = sets.into_iter().collect::<Vec<_>>();
+        let offsets = collect(offsets);
+        self.commands.push(Command::BindComputeDescriptorSets {
+            layout: layout.id,
+            first_set,
+            sets: sets.iter().map(|set| set.borrow().id).collect(),
+            offsets: offsets.clone(),
+        });
+        self.raw.bind_compute_descriptor_sets(
+            &layout.raw,
+            first_set,
+            sets.iter().map(|set| &set.borrow().raw),
+            offsets,
+        )
+    }
+
+    unsafe fn dispatch(&mut self, count: WorkGroupCount) {
+        self.commands.push(Command::Dispatch(count));
+        self.raw.dispatch(count)
+    }
+
+    unsafe fn dispatch_indirect(&mut self, buffer: &Traced<B::Buffer>, offset: buffer::Offset) {
+        self.commands.push(Command::DispatchIndirect {
+            buffer: buffer.id,
+            offset,
+        });
+        self.raw.dispatch_indirect(&buffer.raw, offset)
+    }
+
+    unsafe fn copy_buffer<T>(
+        &mut self,
+        src: &Traced<B::Buffer>,
+        dst: &Traced<B::Buffer>,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<com::BufferCopy>,
+    {
+        let regions = collect(regions);
+        self.commands.push(Command::CopyBuffer {
+            src: src.id,
+            dst: dst.id,
+            regions: regions.clone(),
+        });
+        self.raw.copy_buffer(&src.raw, &dst.raw, regions)
+    }
+
+    unsafe fn copy_image<T>(
+        &mut self,
+        src: &Traced<B::Image>,
+        src_layout: Layout,
+        dst: &Traced<B::Image>,
+        dst_layout: Layout,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<com::ImageCopy>,
+    {
+        let regions = collect(regions);
+        self.commands.push(Command::CopyImage {
+            src: src.id,
+            src_layout,
+            dst: dst.id,
+            dst_layout,
+            regions: regions.clone(),
+        });
+        self.raw
+            .copy_image(&src.raw, src_layout, &dst.raw, dst_layout, regions)
+    }
+
+    unsafe fn copy_buffer_to_image<T>(
+        &mut self,
+        src: &Traced<B::Buffer>,
+        dst: &Traced<B::Image>,
+        dst_layout: Layout,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<com::BufferImageCopy>,
+    {
+        let regions = collect(regions);
+        self.commands.push(Command::CopyBufferToImage {
+            src: src.id,
+            dst: dst.id,
+            dst_layout,
+            regions: regions.clone(),
+        });
+        self.raw
+            .copy_buffer_to_image(&src.raw, &dst.raw, dst_layout, regions)
+    }
+
+    unsafe fn copy_image_to_buffer<T>(
+        &mut self,
+        src: &Traced<B::Image>,
+        src_layout: Layout,
+        dst: &Traced<B::Buffer>,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<com::BufferImageCopy>,
+    {
+        let regions = collect(regions);
+        self.commands.push(Command::CopyImageToBuffer {
+            src: src.id,
+            src_layout,
+            dst: dst.id,
+            regions: regions.clone(),
+        });
+        self.raw
+            .copy_image_to_buffer(&src.raw, src_layout, &dst.raw, regions)
+    }
+
+    unsafe fn draw(&mut self, vertices: Range<VertexCount>, instances: Range<InstanceCount>) {
+        self.commands.push(Command::Draw {
+            vertices: vertices.clone(),
+            instances: instances.clone(),
+        });
+        self.raw.draw(vertices, instances)
+    }
+
+    unsafe fn draw_indexed(
+        &mut self,
+        indices: Range<IndexCount>,
+        base_vertex: VertexOffset,
+        instances: Range<InstanceCount>,
+    ) {
+        self.commands.push(Command::DrawIndexed {
+            indices: indices.clone(),
+            base_vertex,
+            instances: instances.clone(),
+        });
+        self.raw.draw_indexed(indices, base_vertex, instances)
+    }
+
+    unsafe fn draw_indirect(
+        &mut self,
+        buffer: &Traced<B::Buffer>,
+        offset: buffer::Offset,
+        draw_count: DrawCount,
+        stride: u32,
+    ) {
+        self.commands.push(Command::DrawIndirect {
+            buffer: buffer.id,
+            offset,
+            draw_count,
+            stride,
+        });
+        self.raw
+            .draw_indirect(&buffer.raw, offset, draw_count, stride)
+    }
+
+    unsafe fn draw_indexed_indirect(
+        &mut self,
+        buffer: &Traced<B::Buffer>,
+        offset: buffer::Offset,
+        draw_count: DrawCount,
+        stride: u32,
+    ) {
+        self.commands.push(Command::DrawIndexedIndirect {
+            buffer: buffer.id,
+            offset,
+            draw_count,
+            stride,
+        });
+        self.raw
+            .draw_indexed_indirect(&buffer.raw, offset, draw_count, stride)
+    }
+
+    unsafe fn set_event(&mut self, event: &Traced<B::Event>, stages: pso::PipelineStage) {
+        self.commands.push(Command::SetEvent {
+            event: event.id,
+            stages,
+        });
+        self.raw.set_event(&event.raw, stages)
+    }
+
+    unsafe fn reset_event(&mut self, event: &Traced<B::Event>, stages: pso::PipelineStage) {
+        self.commands.push(Command::ResetEvent {
+            event: event.id,
+            stages,
+        });
+        self.raw.reset_event(&event.raw, stages)
+    }
+
+    unsafe fn wait_events<'a, I, J>(
+        &mut self,
+        events: I,
+        stages: Range<pso::PipelineStage>,
+        barriers: J,
+    ) where
+        I: IntoIterator,
+        I::Item: Borrow<Traced<B::Event>>,
+        J: IntoIterator,
+        J::Item: Borrow<Barrier<'a, Backend<B>>>,
+    {
+        let events = events.into_iter().collect::<Vec<_>>();
+        let (raw_barriers, barriers) = convert_barriers(barriers);
+        self.commands.push(Command::WaitEvents {
+            events: events.iter().map(|event| event.borrow().id).collect(),
+            stages: stages.clone(),
+            barriers,
+        });
+        self.raw.wait_events(
+            events.iter().map(|event| &event.borrow().raw),
+            stages,
+            raw_barriers,
+        )
+    }
+
+    unsafe fn begin_query(&mut self, query: query::Query<Backend<B>>, flags: query::ControlFlags) {
+        self.commands.push(Command::BeginQuery {
+            pool: query.pool.id,
+            id: query.id,
+            flags,
+        });
+        self.raw.begin_query(convert_query(query), flags)
+    }
+
+    unsafe fn end_query(&mut self, query: query::Query<Backend<B>>) {
+        self.commands.push(Command::EndQuery {
+            pool: query.pool.id,
+            id: query.id,
+        });
+        self.raw.end_query(convert_query(query))
+    }
+
+    unsafe fn reset_query_pool(&mut self, pool: &Traced<B::QueryPool>, queries: Range<query::Id>) {
+        self.commands.push(Command::ResetQueryPool {
+            pool: pool.id,
+            queries: queries.clone(),
+        });
+        self.raw.reset_query_pool(&pool.raw, queries)
+    }
+
+    unsafe fn copy_query_pool_results(
+        &mut self,
+        pool: &Traced<B::QueryPool>,
+        queries: Range<query::Id>,
+        buffer: &Traced<B::Buffer>,
+        offset: buffer::Offset,
+        stride: buffer::Offset,
+        flags: query::ResultFlags,
+    ) {
+        self.commands.push(Command::CopyQueryPoolResults {
+            pool: pool.id,
+            queries: queries.clone(),
+            buffer: buffer.id,
+            offset,
+            stride,
+            flags,
+        });
+        self.raw
+            .copy_query_pool_results(&pool.raw, queries, &buffer.raw, offset, stride, flags)
+    }
+
+    unsafe fn write_timestamp(
+        &mut self,
+        stage: pso::PipelineStage,
+        query: query::Query<Backend<B>>,
+    ) {
+        self.commands.push(Command::WriteTimestamp {
+            stage,
+            pool: query.pool.id,
+            id: query.id,
+        });
+        self.raw.write_timestamp(stage, convert_query(query))
+    }
+
+    unsafe fn push_graphics_constants(
+        &mut self,
+        layout: &Traced<B::PipelineLayout>,
+        stages: pso::ShaderStageFlags,
+        offset: u32,
+        constants: &[u32],
+    ) {
+        self.commands.push(Command::PushGraphicsConstants {
+            layout: layout.id,
+            stages,
+            offset,
+            constants: constants.to_vec(),
+        });
+        self.raw
+            .push_graphics_constants(&layout.raw, stages, offset, constants)
+    }
+
+    unsafe fn push_compute_constants(
+        &mut self,
+        layout: &Traced<B::PipelineLayout>,
+        offset: u32,
+        constants: &[u32],
+    ) {
+        self.commands.push(Command::PushComputeConstants {
+            layout: layout.id,
+            offset,
+            constants: constants.to_vec(),
+        });
+        self.raw
+            .push_compute_constants(&layout.raw, offset, constants)
+    }
+
+    unsafe fn execute_commands<'a, T, I>(&mut self, cmd_buffers: I)
+    where
+        T: 'a + Borrow<CommandBuffer<B>>,
+        I: IntoIterator<Item = &'a T>,
+    {
+        let cmd_buffers = cmd_buffers
+            .into_iter()
+            .map(|cmd_buffer| cmd_buffer.borrow())
+            .collect::<Vec<_>>();
+        self.commands.push(Command::ExecuteCommands(
+            cmd_buffers.iter().map(|cmd_buffer| cmd_buffer.id).collect(),
+        ));
+        self.raw
+            .execute_commands(cmd_buffers.into_iter().map(|cmd_buffer| &cmd_buffer.raw))
+    }
+}
+
+#[derive(Debug)]
+pub struct DescriptorPool<B: hal::Backend> {
+    pub(crate) raw: B::DescriptorPool,
+    pub(crate) id: Id,
+    tracer: Arc<Tracer>,
+}
+
+impl<B: hal::Backend> DescriptorPool<B> {
+    pub(crate) fn new(raw: B::DescriptorPool, id: Id, tracer: Arc<Tracer>) -> Self {
+        DescriptorPool { raw, id, tracer }
+    }
+}
+
+impl<B: hal::Backend> pso::DescriptorPool<Backend<B>> for DescriptorPool<B> {
+    unsafe fn allocate_set(
+        &mut self,
+        layout: &Traced<B::DescriptorSetLayout>,
+    ) -> Result<Traced<B::DescriptorSet>, pso::AllocationError> {
+        let raw = self.raw.allocate_set(&layout.raw)?;
+        let set = Traced {
+            raw,
+            id: self.tracer.id(),
+        };
+        self.tracer.add(Action::AllocateDescriptorSets {
+            pool: self.id,
+            layouts: vec![layout.id],
+            ids: vec![set.id],
+        });
+        Ok(set)
+    }
+
+    unsafe fn allocate_sets<I>(
+        &mut self,
+        layouts: I,
+        sets: &mut Vec<Traced<B::DescriptorSet>>,
+    ) -> Result<(), pso::AllocationError>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Traced<B::DescriptorSetLayout>>,
+    {
+        let layouts = layouts.into_iter().collect::<Vec<_>>();
+        let mut raw_sets = Vec::with_capacity(layouts.len());
+        let result = self.raw.allocate_sets(
+            layouts.iter().map(|layout| &layout.borrow().raw),
+            &mut raw_sets,
+        );
+        // record the sets allocated before a failure as well
+        let first = sets.len();
+        sets.extend(raw_sets.into_iter().map(|raw| Traced {
+            raw,
+            id: self.tracer.id(),
+        }));
+        if sets.len() > first {
+            self.tracer.add(Action::AllocateDescriptorSets {
+                pool: self.id,
+                layouts: layouts[.. sets.len() - first]
+                    .iter()
+                    .map(|layout| layout.borrow().id)
+                    .collect(),
+                ids: sets[first ..].iter().map(|set| set.id).collect(),
+            });
+        }
+        result
+    }
+
+    unsafe fn free_sets<I>(&mut self, descriptor_sets: I)
+    where
+        I: IntoIterator<Item = Traced<B::DescriptorSet>>,
+    {
+        let sets = descriptor_sets.into_iter().collect::<Vec<_>>();
+        self.tracer.add(Action::FreeDescriptorSets {
+            pool: self.id,
+            ids: sets.iter().map(|set| set.id).collect(),
+        });
+        self.raw.free_sets(sets.into_iter().map(|set| set.raw))
+    }
+
+    unsafe fn reset(&mut self) {
+        self.tracer.add(Action::ResetDescriptorPool(self.id));
+        self.raw.reset()
+    }
+}
@@ -0,0 +1,1108 @@
+use crate::command::{CommandBuffer, CommandPool, DescriptorPool};
+use crate::record::Tracer;
+use crate::trace::{self, Action, Id, RangeArg as TraceRange};
+use crate::{Backend, ImageView, Surface, Swapchain, Traced};
+
+use hal::queue::QueueFamilyId;
+use hal::range::RangeArg;
+use hal::MemoryTypeId;
+use hal::{buffer, device as d, format, image, memory, pass, pool, pso, query, window};
+
+use std::borrow::Borrow;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Memory of the wrapped backend, with its identifier in the trace.
+#[derive(Debug)]
+pub struct Memory<B: hal::Backend> {
+    pub(crate) raw: B::Memory,
+    pub(crate) id: Id,
+    size: u64,
+}
+
+impl<B: hal::Backend> Memory<B> {
+    /// Get the identifier of the memory in the trace.
+    pub fn id(&self) -> Id {
+        self.id
+    }
+}
+
+/// Logical device, recording the calls before forwarding them.
+#[derive(Debug)]
+pub struct Device<B: hal::Backend> {
+    pub(crate) raw: B::Device,
+    memory_types: Vec<memory::Properties>,
+    tracer: Arc<Tracer>,
+}
+
+fn range_arg<R: RangeArg<u64>>(range: &R) -> TraceRange {
+    (range.start().cloned(), range.end().cloned())
+}
+
+fn entry_point<'a, B: hal::Backend>(
+    entry: &pso::EntryPoint<'a, Backend<B>>,
+) -> (pso::EntryPoint<'a, B>, trace::EntryPoint) {
+    let raw = pso::EntryPoint {
+        entry: entry.entry,
+        module: &entry.module.raw,
+        specialization: entry.specialization.clone(),
+    };
+    let traced = trace::EntryPoint {
+        entry: entry.entry.to_string(),
+        module: entry.module.id,
+        specialization: pso::Specialization {
+            constants: entry.specialization.constants.to_vec().into(),
+            data: entry.specialization.data.to_vec().into(),
+        },
+    };
+    (raw, traced)
+}
+
+fn base_pipeline<'a, P>(
+    parent: &pso::BasePipeline<'a, Traced<P>>,
+) -> (pso::BasePipeline<'a, P>, trace::BasePipeline) {
+    match *parent {
+        pso::BasePipeline::Pipeline(pipeline) => (
+            pso::BasePipeline::Pipeline(&pipeline.raw),
+            trace::BasePipeline::Pipeline(pipeline.id),
+        ),
+        pso::BasePipeline::Index(index) => (
+            pso::BasePipeline::Index(index),
+            trace::BasePipeline::Index(index),
+        ),
+        pso::BasePipeline::None => (pso::BasePipeline::None, trace::BasePipeline::None),
+    }
+}
+
+fn descriptor<'a, B: hal::Backend>(
+    descriptor: &pso::Descriptor<'a, Backend<B>>,
+) -> (pso::Descriptor<'a, B>, trace::Descriptor) {
+    match *descriptor {
+        pso::Descriptor::Sampler(sampler) => (
+            pso::Descriptor::Sampler(&sampler.raw),
+            trace::Descriptor::Sampler(sampler.id),
+        ),
+        pso::Descriptor::Image(view, layout) => (
+            pso::Descriptor::Image(view.raw(), layout),
+            trace::Descriptor::Image(view.id, layout),
+        ),
+        pso::Descriptor::CombinedImageSampler(view, layout, sampler) => (
+            pso::Descriptor::CombinedImageSampler(view.raw(), layout, &sampler.raw),
+            trace::Descriptor::CombinedImageSampler(view.id, layout, sampler.id),
+        ),
+        pso::Descriptor::Buffer(buffer, ref range) => (
+            pso::Descriptor::Buffer(&buffer.raw, range.clone()),
+            trace::Descriptor::Buffer(buffer.id, range.clone()),
+        ),
+        pso::Descriptor::UniformTexelBuffer(view) => (
+            pso::Descriptor::UniformTexelBuffer(&view.raw),
+            trace::Descriptor::UniformTexelBuffer(view.id),
+        ),
+        pso::Descriptor::StorageTexelBuffer(view) => (
+            pso::Descriptor::StorageTexelBuffer(&view.raw),
+            trace::Descriptor::StorageTexelBuffer(view.id),
+        ),
+    }
+}
+
+impl<B: hal::Backend> Device<B> {
+    pub(crate) fn new(
+        raw: B::Device,
+        memory_types: Vec<memory::Properties>,
+        tracer: Arc<Tracer>,
+    ) -> Self {
+        Device {
+            raw,
+            memory_types,
+            tracer,
+        }
+    }
+
+    /// Get the wrapped device.
+    pub fn raw(&self) -> &B::Device {
+        &self.raw
+    }
+
+    fn traced<T>(&self, raw: T) -> Traced<T> {
+        Traced {
+            raw,
+            id: self.tracer.id(),
+        }
+    }
+
+    fn set_name(&self, id: Id, name: &str) {
+        self.tracer.add(Action::SetName {
+            id,
+            name: name.to_string(),
+        });
+    }
+}
+
+impl<B: hal::Backend> d::Device<Backend<B>> for Device<B> {
+    unsafe fn allocate_memory(
+        &self,
+        memory_type: MemoryTypeId,
+        size: u64,
+    ) -> Result<Memory<B>, d::AllocationError> {
+        let raw = self.raw.allocate_memory(memory_type, size)?;
+        let id = self.tracer.id();
+        self.tracer.add(Action::AllocateMemory {
+            id,
+            memory_type,
+            properties: self
+                .memory_types
+                .get(memory_type.0)
+                .cloned()
+                .unwrap_or_else(memory::Properties::empty),
+            size,
+        });
+        Ok(Memory { raw, id, size })
+    }
+
+    unsafe fn free_memory(&self, memory: Memory<B>) {
+        self.tracer.unmap(memory.id);
+        self.tracer.add(Action::FreeMemory(memory.id));
+        self.raw.free_memory(memory.raw)
+    }
+
+    unsafe fn create_command_pool(
+        &self,
+        family: QueueFamilyId,
+        create_flags: pool::CommandPoolCreateFlags,
+    ) -> Result<CommandPool<B>, d::OutOfMemory> {
+        let raw = self.raw.create_command_pool(family, create_flags)?;
+        let id = self.tracer.id();
+        self.tracer.add(Action::CreateCommandPool {
+            id,
+            family,
+            flags: create_flags,
+        });
+        Ok(CommandPool::new(raw, id, Arc::clone(&self.tracer)))
+    }
+
+    unsafe fn destroy_command_pool(&self, pool: CommandPool<B>) {
+        self.tracer.add(Action::DestroyCommandPool(pool.id));
+        self.raw.destroy_command_pool(pool.raw)
+    }
+
+    unsafe fn create_render_pass<'a, IA, IS, ID>(
+        &self,
+        attachments: IA,
+        subpasses: IS,
+        dependencies: ID,
+    ) -> Result<Traced<B::RenderPass>, d::OutOfMemory>
+    where
+        IA: IntoIterator,
+        IA::Item: Borrow<pass::Attachment>,
+        IS: IntoIterator,
+        IS::Item: Borrow<pass::SubpassDesc<'a>>,
+        ID: IntoIterator,
+        ID::Item: Borrow<pass::SubpassDependency>,
+    {
+        let attachments = attachments
+            .into_iter()
+            .map(|attachment| attachment.borrow().clone())
+            .collect::<Vec<_>>();
+        let subpasses = subpasses
+            .into_iter()
+            .map(|subpass| {
+                let subpass = subpass.borrow();
+                trace::SubpassDesc {
+                    colors: subpass.colors.to_vec(),
+                    depth_stencil: subpass.depth_stencil.cloned(),
+                    inputs: subpass.inputs.to_vec(),
+                    resolves: subpass.resolves.to_vec(),
+                    preserves: subpass.preserves.to_vec(),
+                }
+            })
+            .collect::<Vec<_>>();
+        let dependencies = dependencies
+            .into_iter()
+            .map(|dependency| dependency.borrow().clone())
+            .collect::<Vec<_>>();
+
+        let raw = self.raw.create_render_pass(
+            &attachments,
+            subpasses.iter().map(|subpass| pass::SubpassDesc {
+                colors: &subpass.colors,
+                depth_stencil: subpass.depth_stencil.as_ref(),
+                inputs: &subpass.inputs,
+                resolves: &subpass.resolves,
+                preserves: &subpass.preserves,
+            }),
+            &dependencies,
+        )?;
+        let render_pass = self.traced(raw);
+        self.tracer.add(Action::CreateRenderPass {
+            id: render_pass.id,
+            attachments,
+            subpasses,
+            dependencies,
+        });
+        Ok(render_pass)
+    }
+
+    unsafe fn destroy_render_pass(&self, rp: Traced<B::RenderPass>) {
+        self.tracer.add(Action::DestroyRenderPass(rp.id));
+        self.raw.destroy_render_pass(rp.raw)
+    }
+
+    unsafe fn create_pipeline_layout<IS, IR>(
+        &self,
+        set_layouts: IS,
+        push_constant: IR,
+    ) -> Result<Traced<B::PipelineLayout>, d::OutOfMemory>
+    where
+        IS: IntoIterator,
+        IS::Item: Borrow<Traced<B::DescriptorSetLayout>>,
+        IR: IntoIterator,
+        IR::Item: Borrow<(pso::ShaderStageFlags, Range<u32>)>,
+    {
+        let set_layouts = set_layouts.into_iter().collect::<Vec<_>>();
+        let push_constants = push_constant
+            .into_iter()
+            .map(|constants| constants.borrow().clone())
+            .collect::<Vec<_>>();
+        let raw = self.raw.create_pipeline_layout(
+            set_layouts.iter().map(|layout| &layout.borrow().raw),
+            &push_constants,
+        )?;
+        let layout = self.traced(raw);
+        self.tracer.add(Action::CreatePipelineLayout {
+            id: layout.id,
+            set_layouts: set_layouts
+                .iter()
+                .map(|layout| layout.borrow().id)
+                .collect(),
+            push_constants,
+        });
+        Ok(layout)
+    }
+
+    unsafe fn destroy_pipeline_layout(&self, layout: Traced<B::PipelineLayout>) {
+        self.tracer.add(Action::DestroyPipelineLayout(layout.id));
+        self.raw.destroy_pipeline_layout(layout.raw)
+    }
+
+    unsafe fn create_pipeline_cache(
+        &self,
+        data: Option<&[u8]>,
+    ) -> Result<Traced<B::PipelineCache>, d::OutOfMemory> {
+        let raw = self.raw.create_pipeline_cache(data)?;
+        let cache = self.traced(raw);
+        self.tracer.add(Action::CreatePipelineCache {
+            id: cache.id,
+            data: data.map(|data| self.tracer.add_data("cache", data)),
+        });
+        Ok(cache)
+    }
+
+    unsafe fn get_pipeline_cache_data(
+        &self,
+        cache: &Traced<B::PipelineCache>,
+    ) -> Result<Vec<u8>, d::OutOfMemory> {
+        self.raw.get_pipeline_cache_data(&cache.raw)
+    }
+
+    unsafe fn merge_pipeline_caches<I>(
+        &self,
+        target: &Traced<B::PipelineCache>,
+        sources: I,
+    ) -> Result<(), d::OutOfMemory>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Traced<B::PipelineCache>>,
+    {
+        let sources = sources.into_iter().collect::<Vec<_>>();
+        self.tracer.add(Action::MergePipelineCaches {
+            target: target.id,
+            sources: sources.iter().map(|cache| cache.borrow().id).collect(),
+        });
+        self.raw
+            .merge_pipeline_caches(&target.raw, sources.iter().map(|cache| &cache.borrow().raw))
+    }
+
+    unsafe fn destroy_pipeline_cache(&self, cache: Traced<B::PipelineCache>) {
+        self.tracer.add(Action::DestroyPipelineCache(cache.id));
+        self.raw.destroy_pipeline_cache(cache.raw)
+    }
+
+    unsafe fn create_graphics_pipeline<'a>(
+        &self,
+        desc: &pso::GraphicsPipelineDesc<'a, Backend<B>>,
+        cache: Option<&Traced<B::PipelineCache>>,
+    ) -> Result<Traced<B::GraphicsPipeline>, pso::CreationError> {
+        let shaders = &desc.shaders;
+        let (vertex, traced_vertex) = entry_point(&shaders.vertex);
+        let hull = shaders.hull.as_ref().map(entry_point);
+        let domain = shaders.domain.as_ref().map(entry_point);
+        let geometry = shaders.geometry.as_ref().map(entry_point);
+        let fragment = shaders.fragment.as_ref().map(entry_point);
+        let (parent, traced_parent) = base_pipeline(&desc.parent);
+        let traced_desc = trace::GraphicsPipelineDesc {
+            vertex: traced_vertex,
+            hull: hull.as_ref().map(|(_, traced)| traced.clone()),
+            domain: domain.as_ref().map(|(_, traced)| traced.clone()),
+            geometry: geometry.as_ref().map(|(_, traced)| traced.clone()),
+            fragment: fragment.as_ref().map(|(_, traced)| traced.clone()),
+            rasterizer: desc.rasterizer,
+            vertex_buffers: desc.vertex_buffers.clone(),
+            attributes: desc.attributes.clone(),
+            input_assembler: desc.input_assembler.clone(),
+            blender: desc.blender.clone(),
+            depth_stencil: desc.depth_stencil,
+            multisampling: desc.multisampling.clone(),
+            baked_states: desc.baked_states.clone(),
+            layout: desc.layout.id,
+            subpass: (desc.subpass.index, desc.subpass.main_pass.id),
+            flags: desc.flags,
+            parent: traced_parent,
+        };
+        let raw_desc = pso::GraphicsPipelineDesc {
+            shaders: pso::GraphicsShaderSet {
+                vertex,
+                hull: hull.map(|(raw, _)| raw),
+                domain: domain.map(|(raw, _)| raw),
+                geometry: geometry.map(|(raw, _)| raw),
+                fragment: fragment.map(|(raw, _)| raw),
+            },
+            rasterizer: desc.rasterizer,
+            vertex_buffers: desc.vertex_buffers.clone(),
+            attributes: desc.attributes.clone(),
+            input_assembler: desc.input_assembler.clone(),
+            blender: desc.blender.clone(),
+            depth_stencil: desc.depth_stencil,
+            multisampling: desc.multisampling.clone(),
+            baked_states: desc.baked_states.clone(),
+            layout: &desc.layout.raw,
+            subpass: pass::Subpass {
+                index: desc.subpass.index,
+                main_pass: &desc.subpass.main_pass.raw,
+            },
+            flags: desc.flags,
+            parent,
+        };
+        let raw = self
+            .raw
+            .create_graphics_pipeline(&raw_desc, cache.map(|cache| &cache.raw))?;
+        let pipeline = self.traced(raw);
+        self.tracer.add(Action::CreateGraphicsPipeline {
+            id: pipeline.id,
+            desc: Box::new(traced_desc),
+            cache: cache.map(|cache| cache.id),
+        });
+        Ok(pipeline)
+    }
+
+    unsafe fn destroy_graphics_pipeline(&self, pipeline: Traced<B::GraphicsPipeline>) {
+        self.tracer
+            .add(Action::DestroyGraphicsPipeline(pipeline.id));
+        self.raw.destroy_graphics_pipeline(pipeline.raw)
+    }
+
+    unsafe fn create_compute_pipeline<'a>(
+        &self,
+        desc: &pso::ComputePipelineDesc<'a, Backend<B>>,
+        cache: Option<&Traced<B::PipelineCache>>,
+    ) -> Result<Traced<B::ComputePipeline>, pso::CreationError> {
+        let (shader, traced_shader) = entry_point(&desc.shader);
+        let (parent, traced_parent) = base_pipeline(&desc.parent);
+        let raw_desc = pso::ComputePipelineDesc {
+            shader,
+            layout: &desc.layout.raw,
+            flags: desc.flags,
+            parent,
+        };
+        let raw = self
+            .raw
+            .create_compute_pipeline(&raw_desc, cache.map(|cache| &cache.raw))?;
+        let pipeline = self.traced(raw);
+        self.tracer.add(Action::CreateComputePipeline {
+            id: pipeline.id,
+            desc: trace::ComputePipelineDesc {
+                shader: traced_shader,
+                layout: desc.layout.id,
+                flags: desc.flags,
+                parent: traced_parent,
+            },
+            cache: cache.map(|cache| cache.id),
+        });
+        Ok(pipeline)
+    }
+
+    unsafe fn destroy_compute_pipeline(&self, pipeline: Traced<B::ComputePipeline>) {
+        self.tracer.add(Action::DestroyComputePipeline(pipeline.id));
+        self.raw.destroy_compute_pipeline(pipeline.raw)
+    }
+
+    unsafe fn create_framebuffer<I>(
+        &self,
+        pass: &Traced<B::RenderPass>,
+        attachments: I,
+        extent: image::Extent,
+    ) -> Result<Traced<B::Framebuffer>, d::OutOfMemory>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<ImageView<B>>,
+    {
+        let attachments = attachments.into_iter().collect::<Vec<_>>();
+        let raw = self.raw.create_framebuffer(
+            &pass.raw,
+            attachments.iter().map(|view| view.borrow().raw()),
+            extent,
+        )?;
+        let framebuffer = self.traced(raw);
+        self.tracer.add(Action::CreateFramebuffer {
+            id: framebuffer.id,
+            render_pass: pass.id,
+            attachments: attachments.iter().map(|view| view.borrow().id).collect(),
+            extent,
+        });
+        Ok(framebuffer)
+    }
+
+    unsafe fn destroy_framebuffer(&self, buf: Traced<B::Framebuffer>) {
+        self.tracer.add(Action::DestroyFramebuffer(buf.id));
+        self.raw.destroy_framebuffer(buf.raw)
+    }
+
+    unsafe fn create_shader_module(
+        &self,
+        spirv_data: &[u32],
+    ) -> Result<Traced<B::ShaderModule>, d::ShaderError> {
+        let raw = self.raw.create_shader_module(spirv_data)?;
+        let module = self.traced(raw);
+        let bytes = spirv_data
+            .iter()
+            .flat_map(|word| word.to_le_bytes().to_vec())
+            .collect::<Vec<_>>();
+        self.tracer.add(Action::CreateShaderModule {
+            id: module.id,
+            data: self.tracer.add_data("spv", &bytes),
+        });
+        Ok(module)
+    }
+
+    unsafe fn destroy_shader_module(&self, shader: Traced<B::ShaderModule>) {
+        self.tracer.add(Action::DestroyShaderModule(shader.id));
+        self.raw.destroy_shader_module(shader.raw)
+    }
+
+    unsafe fn create_buffer(
+        &self,
+        size: u64,
+        usage: buffer::Usage,
+    ) -> Result<Traced<B::Buffer>, buffer::CreationError> {
+        let raw = self.raw.create_buffer(size, usage)?;
+        let buffer = self.traced(raw);
+        self.tracer.add(Action::CreateBuffer {
+            id: buffer.id,
+            size,
+            usage,
+        });
+        Ok(buffer)
+    }
+
+    unsafe fn get_buffer_requirements(&self, buf: &Traced<B::Buffer>) -> memory::Requirements {
+        self.raw.get_buffer_requirements(&buf.raw)
+    }
+
+    unsafe fn bind_buffer_memory(
+        &self,
+        memory: &Memory<B>,
+        offset: u64,
+        buf: &mut Traced<B::Buffer>,
+    ) -> Result<(), d::BindError> {
+        self.tracer.add(Action::BindBufferMemory {
+            memory: memory.id,
+            offset,
+            buffer: buf.id,
+        });
+        self.raw
+            .bind_buffer_memory(&memory.raw, offset, &mut buf.raw)
+    }
+
+    unsafe fn destroy_buffer(&self, buffer: Traced<B::Buffer>) {
+        self.tracer.add(Action::DestroyBuffer(buffer.id));
+        self.raw.destroy_buffer(buffer.raw)
+    }
+
+    unsafe fn create_buffer_view<R: RangeArg<u64>>(
+        &self,
+        buf: &Traced<B::Buffer>,
+        fmt: Option<format::Format>,
+        range: R,
+    ) -> Result<Traced<B::BufferView>, buffer::ViewCreationError> {
+        let traced_range = range_arg(&range);
+        let raw = self.raw.create_buffer_view(&buf.raw, fmt, range)?;
+        let view = self.traced(raw);
+        self.tracer.add(Action::CreateBufferView {
+            id: view.id,
+            buffer: buf.id,
+            format: fmt,
+            range: traced_range,
+        });
+        Ok(view)
+    }
+
+    unsafe fn destroy_buffer_view(&self, view: Traced<B::BufferView>) {
+        self.tracer.add(Action::DestroyBufferView(view.id));
+        self.raw.destroy_buffer_view(view.raw)
+    }
+
+    unsafe fn create_image(
+        &self,
+        kind: image::Kind,
+        mip_levels: image::Level,
+        format: format::Format,
+        tiling: image::Tiling,
+        usage: image::Usage,
+        view_caps: image::ViewCapabilities,
+    ) -> Result<Traced<B::Image>, image::CreationError> {
+        let raw = self
+            .raw
+            .create_image(kind, mip_levels, format, tiling, usage, view_caps)?;
+        let image = self.traced(raw);
+        self.tracer.add(Action::CreateImage {
+            id: image.id,
+            kind,
+            mip_levels,
+            format,
+            tiling,
+            usage,
+            view_caps,
+        });
+        Ok(image)
+    }
+
+    unsafe fn get_image_requirements(&self, image: &Traced<B::Image>) -> memory::Requirements {
+        self.raw.get_image_requirements(&image.raw)
+    }
+
+    unsafe fn get_image_subresource_footprint(
+        &self,
+        image: &Traced<B::Image>,
+        subresource: image::Subresource,
+    ) -> image::SubresourceFootprint {
+        self.raw
+            .get_image_subresource_footprint(&image.raw, subresource)
+    }
+
+    unsafe fn bind_image_memory(
+        &self,
+        memory: &Memory<B>,
+        offset: u64,
+        image: &mut Traced<B::Image>,
+    ) -> Result<(), d::BindError> {
+        self.tracer.add(Action::BindImageMemory {
+            memory: memory.id,
+            offset,
+            image: image.id,
+        });
+        self.raw
+            .bind_image_memory(&memory.raw, offset, &mut image.raw)
+    }
+
+    unsafe fn destroy_image(&self, image: Traced<B::Image>) {
+        self.tracer.add(Action::DestroyImage(image.id));
+        self.raw.destroy_image(image.raw)
+    }
+
+    unsafe fn create_image_view(
+        &self,
+        image: &Traced<B::Image>,
+        view_kind: image::ViewKind,
+        format: format::Format,
+        swizzle: format::Swizzle,
+        range: image::SubresourceRange,
+    ) -> Result<ImageView<B>, image::ViewError> {
+        let raw =
+            self.raw
+                .create_image_view(&image.raw, view_kind, format, swizzle, range.clone())?;
+        let view = ImageView::new(raw, self.tracer.id());
+        self.tracer.add(Action::CreateImageView {
+            id: view.id,
+            image: image.id,
+            view_kind,
+            format,
+            swizzle,
+            range,
+        });
+        Ok(view)
+    }
+
+    unsafe fn destroy_image_view(&self, view: ImageView<B>) {
+        self.tracer.add(Action::DestroyImageView(view.id));
+        if let Some(raw) = view.into_raw() {
+            self.raw.destroy_image_view(raw)
+        }
+    }
+
+    unsafe fn create_sampler(
+        &self,
+        info: image::SamplerInfo,
+    ) -> Result<Traced<B::Sampler>, d::AllocationError> {
+        let raw = self.raw.create_sampler(info.clone())?;
+        let sampler = self.traced(raw);
+        self.tracer.add(Action::CreateSampler {
+            id: sampler.id,
+            info,
+        });
+        Ok(sampler)
+    }
+
+    unsafe fn destroy_sampler(&self, sampler: Traced<B::Sampler>) {
+        self.tracer.add(Action::DestroySampler(sampler.id));
+        self.raw.destroy_sampler(sampler.raw)
+    }
+
+    unsafe fn create_descriptor_pool<I>(
+        &self,
+        max_sets: usize,
+        descriptor_ranges: I,
+        flags: pso::DescriptorPoolCreateFlags,
+    ) -> Result<DescriptorPool<B>, d::OutOfMemory>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<pso::DescriptorRangeDesc>,
+    {
+        let ranges = descriptor_ranges
+            .into_iter()
+            .map(|range| *range.borrow())
+            .collect::<Vec<_>>();
+        let raw = self.raw.create_descriptor_pool(max_sets, &ranges, flags)?;
+        let id = self.tracer.id();
+        self.tracer.add(Action::CreateDescriptorPool {
+            id,
+            max_sets,
+            ranges,
+            flags,
+        });
+        Ok(DescriptorPool::new(raw, id, Arc::clone(&self.tracer)))
+    }
+
+    unsafe fn destroy_descriptor_pool(&self, pool: DescriptorPool<B>) {
+        self.tracer.add(Action::DestroyDescriptorPool(pool.id));
+        self.raw.destroy_descriptor_pool(pool.raw)
+    }
+
+    unsafe fn create_descriptor_set_layout<I, J>(
+        &self,
+        bindings: I,
+        immutable_samplers: J,
+    ) -> Result<Traced<B::DescriptorSetLayout>, d::OutOfMemory>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<pso::DescriptorSetLayoutBinding>,
+        J: IntoIterator,
+        J::Item: Borrow<Traced<B::Sampler>>,
+    {
+        let bindings = bindings
+            .into_iter()
+            .map(|binding| binding.borrow().clone())
+            .collect::<Vec<_>>();
+        let immutable_samplers = immutable_samplers.into_iter().collect::<Vec<_>>();
+        let raw = self.raw.create_descriptor_set_layout(
+            &bindings,
+            immutable_samplers
+                .iter()
+                .map(|sampler| &sampler.borrow().raw),
+        )?;
+        let layout = self.traced(raw);
+        self.tracer.add(Action::CreateDescriptorSetLayout {
+            id: layout.id,
+            bindings,
+            immutable_samplers: immutable_samplers
+                .iter()
+                .map(|sampler| sampler.borrow().id)
+                .collect(),
+        });
+        Ok(layout)
+    }
+
+    unsafe fn destroy_descriptor_set_layout(&self, layout: Traced<B::DescriptorSetLayout>) {
+        self.tracer
+            .add(Action::DestroyDescriptorSetLayout(layout.id));
+        self.raw.destroy_descriptor_set_layout(layout.raw)
+    }
+
+    unsafe fn write_descriptor_sets<'a, I, J>(&self, write_iter: I)
+    where
+        I: IntoIterator<Item = pso::DescriptorSetWrite<'a, Backend<B>, J>>,
+        J: IntoIterator,
+        J::Item: Borrow<pso::Descriptor<'a, Backend<B>>>,
+    {
+        let mut traced_writes = Vec::new();
+        let writes = write_iter
+            .into_iter()
+            .map(|write| {
+                let (descriptors, traced_descriptors): (Vec<_>, Vec<_>) = write
+                    .descriptors
+                    .into_iter()
+                    .map(|desc| descriptor(desc.borrow()))
+                    .unzip();
+                traced_writes.push(trace::DescriptorSetWrite {
+                    set: write.set.id,
+                    binding: write.binding,
+                    array_offset: write.array_offset,
+                    descriptors: traced_descriptors,
+                });
+                pso::DescriptorSetWrite {
+                    set: &write.set.raw,
+                    binding: write.binding,
+                    array_offset: write.array_offset,
+                    descriptors,
+                }
+            })
+            .collect::<Vec<_>>();
+        self.tracer.add(Action::WriteDescriptorSets(traced_writes));
+        self.raw.write_descriptor_sets(writes)
+    }
+
+    unsafe fn copy_descriptor_sets<'a, I>(&self, copy_iter: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<pso::DescriptorSetCopy<'a, Backend<B>>>,
+    {
+        let copies = copy_iter.into_iter().collect::<Vec<_>>();
+        self.tracer.add(Action::CopyDescriptorSets(
+            copies
+                .iter()
+                .map(|copy| {
+                    let copy = copy.borrow();
+                    trace::DescriptorSetCopy {
+                        src_set: copy.src_set.id,
+                        src_binding: copy.src_binding,
+                        src_array_offset: copy.src_array_offset,
+                        dst_set: copy.dst_set.id,
+                        dst_binding: copy.dst_binding,
+                        dst_array_offset: copy.dst_array_offset,
+                        count: copy.count,
+                    }
+                })
+                .collect(),
+        ));
+        self.raw.copy_descriptor_sets(copies.iter().map(|copy| {
+            let copy = copy.borrow();
+            pso::DescriptorSetCopy {
+                src_set: &copy.src_set.raw,
+                src_binding: copy.src_binding,
+                src_array_offset: copy.src_array_offset,
+                dst_set: &copy.dst_set.raw,
+                dst_binding: copy.dst_binding,
+                dst_array_offset: copy.dst_array_offset,
+                count: copy.count,
+            }
+        }))
+    }
+
+    unsafe fn map_memory<R>(&self, memory: &Memory<B>, range: R) -> Result<*mut u8, d::MapError>
+    where
+        R: RangeArg<u64>,
+    {
+        let (start, end) = range_arg(&range);
+        let ptr = self.raw.map_memory(&memory.raw, (start, end))?;
+        self.tracer.add(Action::MapMemory {
+            memory: memory.id,
+            range: (start, end),
+        });
+        let start = start.unwrap_or(0);
+        self.tracer
+            .map(memory.id, ptr, start, end.unwrap_or(memory.size) - start);
+        Ok(ptr)
+    }
+
+    unsafe fn flush_mapped_memory_ranges<'a, I, R>(&self, ranges: I) -> Result<(), d::OutOfMemory>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<(&'a Memory<B>, R)>,
+        R: RangeArg<u64>,
+    {
+        let ranges = ranges
+            .into_iter()
+            .map(|item| {
+                let (memory, ref range) = *item.borrow();
+                (memory, range_arg(range))
+            })
+            .collect::<Vec<_>>();
+        for &(memory, _) in &ranges {
+            self.tracer.capture(memory.id);
+        }
+        self.tracer.add(Action::FlushMappedMemoryRanges(
+            ranges
+                .iter()
+                .map(|&(memory, range)| (memory.id, range))
+                .collect(),
+        ));
+        self.raw
+            .flush_mapped_memory_ranges(ranges.iter().map(|&(memory, range)| (&memory.raw, range)))
+    }
+
+    unsafe fn invalidate_mapped_memory_ranges<'a, I, R>(
+        &self,
+        ranges: I,
+    ) -> Result<(), d::OutOfMemory>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<(&'a Memory<B>, R)>,
+        R: RangeArg<u64>,
+    {
+        let ranges = ranges
+            .into_iter()
+            .map(|item| {
+                let (memory, ref range) = *item.borrow();
+                (memory, range_arg(range))
+            })
+            .collect::<Vec<_>>();
+        self.tracer.add(Action::InvalidateMappedMemoryRanges(
+            ranges
+                .iter()
+                .map(|&(memory, range)| (memory.id, range))
+                .collect(),
+        ));
+        self.raw.invalidate_mapped_memory_ranges(
+            ranges.iter().map(|&(memory, range)| (&memory.raw, range)),
+        )?;
+        // the contents now come from the device, not from the host
+        for &(memory, _) in &ranges {
+            self.tracer.refresh(memory.id);
+        }
+        Ok(())
+    }
+
+    unsafe fn unmap_memory(&self, memory: &Memory<B>) {
+        self.tracer.unmap(memory.id);
+        self.tracer.add(Action::UnmapMemory(memory.id));
+        self.raw.unmap_memory(&memory.raw)
+    }
+
+    fn create_semaphore(&self) -> Result<Traced<B::Semaphore>, d::OutOfMemory> {
+        let semaphore = self.traced(self.raw.create_semaphore()?);
+        self.tracer.add(Action::CreateSemaphore(semaphore.id));
+        Ok(semaphore)
+    }
+
+    unsafe fn destroy_semaphore(&self, semaphore: Traced<B::Semaphore>) {
+        self.tracer.add(Action::DestroySemaphore(semaphore.id));
+        self.raw.destroy_semaphore(semaphore.raw)
+    }
+
+    fn create_fence(&self, signaled: bool) -> Result<Traced<B::Fence>, d::OutOfMemory> {
+        let fence = self.traced(self.raw.create_fence(signaled)?);
+        self.tracer.add(Action::CreateFence {
+            id: fence.id,
+            signaled,
+        });
+        Ok(fence)
+    }
+
+    unsafe fn reset_fences<I>(&self, fences: I) -> Result<(), d::OutOfMemory>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Traced<B::Fence>>,
+    {
+        let fences = fences.into_iter().collect::<Vec<_>>();
+        self.tracer.add(Action::ResetFences(
+            fences.iter().map(|fence| fence.borrow().id).collect(),
+        ));
+        self.raw
+            .reset_fences(fences.iter().map(|fence| &fence.borrow().raw))
+    }
+
+    unsafe fn wait_for_fences<I>(
+        &self,
+        fences: I,
+        wait: d::WaitFor,
+        timeout_ns: u64,
+    ) -> Result<bool, d::OomOrDeviceLost>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Traced<B::Fence>>,
+    {
+        let fences = fences.into_iter().collect::<Vec<_>>();
+        self.tracer.add(Action::WaitForFences {
+            fences: fences.iter().map(|fence| fence.borrow().id).collect(),
+            wait: wait.clone(),
+            timeout_ns,
+        });
+        self.raw.wait_for_fences(
+            fences.iter().map(|fence| &fence.borrow().raw),
+            wait,
+            timeout_ns,
+        )
+    }
+
+    unsafe fn get_fence_status(&self, fence: &Traced<B::Fence>) -> Result<bool, d::DeviceLost> {
+        self.tracer.add(Action::GetFenceStatus(fence.id));
+        self.raw.get_fence_status(&fence.raw)
+    }
+
+    unsafe fn destroy_fence(&self, fence: Traced<B::Fence>) {
+        self.tracer.add(Action::DestroyFence(fence.id));
+        self.raw.destroy_fence(fence.raw)
+    }
+
+    fn create_event(&self) -> Result<Traced<B::Event>, d::OutOfMemory> {
+        let event = self.traced(self.raw.create_event()?);
+        self.tracer.add(Action::CreateEvent(event.id));
+        Ok(event)
+    }
+
+    unsafe fn destroy_event(&self, event: Traced<B::Event>) {
+        self.tracer.add(Action::DestroyEvent(event.id));
+        self.raw.destroy_event(event.raw)
+    }
+
+    unsafe fn get_event_status(
+        &self,
+        event: &Traced<B::Event>,
+    ) -> Result<bool, d::OomOrDeviceLost> {
+        self.tracer.add(Action::GetEventStatus(event.id));
+        self.raw.get_event_status(&event.raw)
+    }
+
+    unsafe fn set_event(&self, event: &Traced<B::Event>) -> Result<(), d::OutOfMemory> {
+        self.tracer.add(Action::SetEvent(event.id));
+        self.raw.set_event(&event.raw)
+    }
+
+    unsafe fn reset_event(&self, event: &Traced<B::Event>) -> Result<(), d::OutOfMemory> {
+        self.tracer.add(Action::ResetEvent(event.id));
+        self.raw.reset_event(&event.raw)
+    }
+
+    unsafe fn create_query_pool(
+        &self,
+        ty: query::Type,
+        count: query::Id,
+    ) -> Result<Traced<B::QueryPool>, query::CreationError> {
+        let pool = self.traced(self.raw.create_query_pool(ty, count)?);
+        self.tracer.add(Action::CreateQueryPool {
+            id: pool.id,
+            ty,
+            count,
+        });
+        Ok(pool)
+    }
+
+    unsafe fn destroy_query_pool(&self, pool: Traced<B::QueryPool>) {
+        self.tracer.add(Action::DestroyQueryPool(pool.id));
+        self.raw.destroy_query_pool(pool.raw)
+    }
+
+    unsafe fn get_query_pool_results(
+        &self,
+        pool: &Traced<B::QueryPool>,
+        queries: Range<query::Id>,
+        data: &mut [u8],
+        stride: buffer::Offset,
+        flags: query::ResultFlags,
+    ) -> Result<bool, d::OomOrDeviceLost> {
+        self.tracer.add(Action::GetQueryPoolResults {
+            pool: pool.id,
+            queries: queries.clone(),
+            size: data.len(),
+            stride,
+            flags,
+        });
+        self.raw
+            .get_query_pool_results(&pool.raw, queries, data, stride, flags)
+    }
+
+    unsafe fn create_swapchain(
+        &self,
+        surface: &mut Surface<B>,
+        config: window::SwapchainConfig,
+        old_swapchain: Option<Swapchain<B>>,
+    ) -> Result<(Swapchain<B>, Vec<Traced<B::Image>>), window::CreationError> {
+        let old_id = old_swapchain.as_ref().map(|swapchain| swapchain.id);
+        let (raw, images) = self.raw.create_swapchain(
+            &mut surface.raw,
+            config.clone(),
+            old_swapchain.map(|swapchain| swapchain.raw),
+        )?;
+        let swapchain = Swapchain {
+            raw,
+            id: self.tracer.id(),
+            tracer: Arc::clone(&self.tracer),
+        };
+        let images = images
+            .into_iter()
+            .map(|raw| self.traced(raw))
+            .collect::<Vec<_>>();
+        self.tracer.add(Action::CreateSwapchain {
+            id: swapchain.id,
+            surface: surface.id,
+            config,
+            old_swapchain: old_id,
+            images: images.iter().map(|image| image.id).collect(),
+        });
+        Ok((swapchain, images))
+    }
+
+    unsafe fn destroy_swapchain(&self, swapchain: Swapchain<B>) {
+        self.tracer.add(Action::DestroySwapchain(swapchain.id));
+        self.raw.destroy_swapchain(swapchain.raw)
+    }
+
+    fn wait_idle(&self) -> Result<(), d::OutOfMemory> {
+        self.tracer.add(Action::WaitIdle);
+        self.raw.wait_idle()
+    }
+
+    unsafe fn set_image_name(&self, image: &mut Traced<B::Image>, name: &str) {
+        self.set_name(image.id, name);
+        self.raw.set_image_name(&mut image.raw, name)
+    }
+
+    unsafe fn set_buffer_name(&self, buffer: &mut Traced<B::Buffer>, name: &str) {
+        self.set_name(buffer.id, name);
+        self.raw.set_buffer_name(&mut buffer.raw, name)
+    }
+
+    unsafe fn set_command_buffer_name(&self, command_buffer: &mut CommandBuffer<B>, name: &str) {
+        self.set_name(command_buffer.id, name);
+        self.raw
+            .set_command_buffer_name(&mut command_buffer.raw, name)
+    }
+
+    unsafe fn set_semaphore_name(&self, semaphore: &mut Traced<B::Semaphore>, name: &str) {
+        self.set_name(semaphore.id, name);
+        self.raw.set_semaphore_name(&mut semaphore.raw, name)
+    }
+
+    unsafe fn set_fence_name(&self, fence: &mut Traced<B::Fence>, name: &str) {
+        self.set_name(fence.id, name);
+        self.raw.set_fence_name(&mut fence.raw, name)
+    }
+
+    unsafe fn set_framebuffer_name(&self, framebuffer: &mut Traced<B::Framebuffer>, name: &str) {
+        self.set_name(framebuffer.id, name);
+        self.raw.set_framebuffer_name(&mut framebuffer.raw, name)
+    }
+
+    unsafe fn set_render_pass_name(&self, render_pass: &mut Traced<B::RenderPass>, name: &str) {
+        self.set_name(render_pass.id, name);
+        self.raw.set_render_pass_name(&mut render_pass.raw, name)
+    }
+
+    unsafe fn set_descriptor_set_name(
+        &self,
+        descriptor_set: &mut Traced<B::DescriptorSet>,
+        name: &str,
+    ) {
+        self.set_name(descriptor_set.id, name);
+        self.raw
+            .set_descriptor_set_name(&mut descriptor_set.raw, name)
+    }
+
+    unsafe fn set_descriptor_set_layout_name(
+        &self,
+        descriptor_set_layout: &mut Traced<B::DescriptorSetLayout>,
+        name: &str,
+    ) {
+        self.set_name(descriptor_set_layout.id, name);
+        self.raw
+            .set_descriptor_set_layout_name(&mut descriptor_set_layout.raw, name)
+    }
+}
@@ -0,0 +1,956 @@
+//! Trace backend, capturing the usage of the API on top of any other backend.
+//!
+//! Every call is recorded with its arguments to a trace directory before
+//! being forwarded to the wrapped backend, along with the shader modules and
+//! the data written by the host into mapped memory. Host writes are detected
+//! by comparing the mapped memory with a copy of it, on flushes, unmaps and
+//! submissions. The [`Replayer`](replay/struct.Replayer.html) plays a trace
+//! back on any backend, including the empty one.
+//!
+//! ```ignore
+//! let instance = gfx_backend_trace::Instance::new(raw_instance, "trace")?;
+//! // use `instance` like any other, the trace is complete once everything is dropped
+//! ```
+
+#[macro_use]
+extern crate log;
+#[macro_use]
+extern crate serde;
+extern crate gfx_hal as hal;
+
+use crate::record::Tracer;
+use crate::trace::{Action, Family, Id, QueueId};
+
+use hal::{adapter, device as d, format, image, queue as q, window};
+use hal::{Features, Limits};
+
+use std::borrow::Borrow;
+use std::io;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::Arc;
+
+mod command;
+mod device;
+mod record;
+pub mod replay;
+pub mod trace;
+
+pub use crate::command::{CommandBuffer, CommandPool, DescriptorPool};
+pub use crate::device::{Device, Memory};
+pub use crate::record::ACTIONS_FILE;
+pub use crate::replay::{ReplayError, Replayer};
+
+/// Trace backend, wrapping the backend `B`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Backend<B>(PhantomData<B>);
+
+impl<B: hal::Backend> hal::Backend for Backend<B> {
+    type PhysicalDevice = PhysicalDevice<B>;
+    type Device = Device<B>;
+
+    type Surface = Surface<B>;
+    type Swapchain = Swapchain<B>;
+
+    type QueueFamily = B::QueueFamily;
+    type CommandQueue = CommandQueue<B>;
+    type CommandBuffer = CommandBuffer<B>;
+
+    type Memory = Memory<B>;
+    type CommandPool = CommandPool<B>;
+
+    type ShaderModule = Traced<B::ShaderModule>;
+    type RenderPass = Traced<B::RenderPass>;
+    type Framebuffer = Traced<B::Framebuffer>;
+
+    type Buffer = Traced<B::Buffer>;
+    type BufferView = Traced<B::BufferView>;
+    type Image = Traced<B::Image>;
+    type ImageView = ImageView<B>;
+    type Sampler = Traced<B::Sampler>;
+
+    type ComputePipeline = Traced<B::ComputePipeline>;
+    type GraphicsPipeline = Traced<B::GraphicsPipeline>;
+    type PipelineCache = Traced<B::PipelineCache>;
+    type PipelineLayout = Traced<B::PipelineLayout>;
+    type DescriptorSetLayout = Traced<B::DescriptorSetLayout>;
+    type DescriptorPool = DescriptorPool<B>;
+    type DescriptorSet = Traced<B::DescriptorSet>;
+
+    type Fence = Traced<B::Fence>;
+    type Semaphore = Traced<B::Semaphore>;
+    type Event = Traced<B::Event>;
+    type QueryPool = Traced<B::QueryPool>;
+}
+
+/// Object of the wrapped backend, with its identifier in the trace.
+#[derive(Debug)]
+pub struct Traced<T> {
+    pub(crate) raw: T,
+    pub(crate) id: Id,
+}
+
+impl<T> Traced<T> {
+    /// Get the wrapped object.
+    pub fn raw(&self) -> &T {
+        &self.raw
+    }
+
+    /// Get the identifier of the object in the trace.
+    pub fn id(&self) -> Id {
+        self.id
+    }
+}
+
+#[derive(Debug)]
+enum ImageViewRaw<V> {
+    Owned(V),
+    /// View of an image acquired from a surface, owned by the boxed
+    /// swapchain image it was borrowed from.
+    Swapchain(*const V),
+}
+
+/// Image view of the wrapped backend, with its identifier in the trace.
+#[derive(Debug)]
+pub struct ImageView<B: hal::Backend> {
+    raw: ImageViewRaw<B::ImageView>,
+    id: Id,
+}
+
+// The view a swapchain image points to is only used while the image is alive.
+unsafe impl<B: hal::Backend> Send for ImageView<B> {}
+unsafe impl<B: hal::Backend> Sync for ImageView<B> {}
+
+impl<B: hal::Backend> ImageView<B> {
+    pub(crate) fn new(raw: B::ImageView, id: Id) -> Self {
+        ImageView {
+            raw: ImageViewRaw::Owned(raw),
+            id,
+        }
+    }
+
+    /// Get the wrapped image view.
+    pub fn raw(&self) -> &B::ImageView {
+        match self.raw {
+            ImageViewRaw::Owned(ref view) => view,
+            ImageViewRaw::Swapchain(view) => unsafe { &*view },
+        }
+    }
+
+    /// Get the identifier of the image view in the trace.
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    pub(crate) fn into_raw(self) -> Option<B::ImageView> {
+        match self.raw {
+            ImageViewRaw::Owned(view) => Some(view),
+            ImageViewRaw::Swapchain(_) => None,
+        }
+    }
+}
+
+type RawSwapchainImage<B> =
+    <<B as hal::Backend>::Surface as window::PresentationSurface<B>>::SwapchainImage;
+
+/// Image acquired from a surface, with a view traced under its own identifier.
+#[derive(Debug)]
+pub struct SwapchainImage<B: hal::Backend> {
+    raw: Box<RawSwapchainImage<B>>,
+    view: ImageView<B>,
+}
+
+impl<B: hal::Backend> Borrow<ImageView<B>> for SwapchainImage<B> {
+    fn borrow(&self) -> &ImageView<B> {
+        &self.view
+    }
+}
+
+/// Instance wrapping the instance of another backend.
+///
+/// The trace is written to a directory, and completed once the instance and
+/// all the objects created from it are dropped.
+#[derive(Debug)]
+pub struct Instance<I> {
+    raw: I,
+    tracer: Arc<Tracer>,
+}
+
+impl<I: hal::Instance> Instance<I> {
+    /// Wrap an instance, recording the trace into the directory `dir`.
+    pub fn new<P: AsRef<Path>>(raw: I, dir: P) -> io::Result<Self> {
+        let tracer = Arc::new(Tracer::new(dir.as_ref())?);
+        Ok(Instance { raw, tracer })
+    }
+
+    /// Get the wrapped instance, e.g. to create surfaces.
+    pub fn raw(&self) -> &I {
+        &self.raw
+    }
+
+    /// Wrap a surface created by the wrapped instance.
+    pub fn wrap_surface(&self, raw: <I::Backend as hal::Backend>::Surface) -> Surface<I::Backend> {
+        Surface {
+            raw,
+            id: self.tracer.id(),
+            tracer: Arc::clone(&self.tracer),
+        }
+    }
+}
+
+impl<I: hal::Instance> hal::Instance for Instance<I> {
+    type Backend = Backend<I::Backend>;
+    fn enumerate_adapters(&self) -> Vec<adapter::Adapter<Self::Backend>> {
+        self.raw
+            .enumerate_adapters()
+            .into_iter()
+            .map(|adapter| adapter::Adapter {
+                physical_device: PhysicalDevice {
+                    raw: adapter.physical_device,
+                    name: adapter.info.name.clone(),
+                    tracer: Arc::clone(&self.tracer),
+                },
+                info: adapter.info,
+                queue_families: adapter.queue_families,
+            })
+            .collect()
+    }
+}
+
+/// Physical device of the wrapped backend.
+#[derive(Debug)]
+pub struct PhysicalDevice<B: hal::Backend> {
+    raw: B::PhysicalDevice,
+    name: String,
+    tracer: Arc<Tracer>,
+}
+
+impl<B: hal::Backend> adapter::PhysicalDevice<Backend<B>> for PhysicalDevice<B> {
+    unsafe fn open(
+        &self,
+        families: &[(&B::QueueFamily, &[q::QueuePriority])],
+        requested_features: Features,
+    ) -> Result<adapter::Gpu<Backend<B>>, d::CreationError> {
+        use hal::queue::QueueFamily as _;
+
+        let gpu = self.raw.open(families, requested_features)?;
+        let memory_types = self
+            .raw
+            .memory_properties()
+            .memory_types
+            .iter()
+            .map(|ty| ty.properties)
+            .collect::<Vec<_>>();
+        self.tracer.add(Action::Init {
+            adapter: self.name.clone(),
+            families: families
+                .iter()
+                .map(|&(family, priorities)| Family {
+                    id: family.id(),
+                    ty: family.queue_type(),
+                    count: priorities.len(),
+                })
+                .collect(),
+            features: requested_features,
+            memory_types: memory_types.clone(),
+        });
+
+        let queue_groups = gpu
+            .queue_groups
+            .into_iter()
+            .map(|group| {
+                let mut new_group = q::QueueGroup::new(group.family);
+                for (index, queue) in group.queues.into_iter().enumerate() {
+                    new_group.add_queue(CommandQueue {
+                        raw: queue,
+                        id: (group.family, index),
+                        tracer: Arc::clone(&self.tracer),
+                    });
+                }
+                new_group
+            })
+            .collect();
+
+        Ok(adapter::Gpu {
+            device: Device::new(gpu.device, memory_types, Arc::clone(&self.tracer)),
+            queue_groups,
+        })
+    }
+
+    fn format_properties(&self, format: Option<format::Format>) -> format::Properties {
+        self.raw.format_properties(format)
+    }
+
+    fn image_format_properties(
+        &self,
+        format: format::Format,
+        dimensions: u8,
+        tiling: image::Tiling,
+        usage: image::Usage,
+        view_caps: image::ViewCapabilities,
+    ) -> Option<image::FormatProperties> {
+        self.raw
+            .image_format_properties(format, dimensions, tiling, usage, view_caps)
+    }
+
+    fn memory_properties(&self) -> adapter::MemoryProperties {
+        self.raw.memory_properties()
+    }
+
+    fn features(&self) -> Features {
+        self.raw.features()
+    }
+
+    fn limits(&self) -> Limits {
+        self.raw.limits()
+    }
+
+    fn is_valid_cache(&self, cache: &[u8]) -> bool {
+        self.raw.is_valid_cache(cache)
+    }
+}
+
+/// Command queue of the wrapped backend.
+#[derive(Debug)]
+pub struct CommandQueue<B: hal::Backend> {
+    raw: B::CommandQueue,
+    id: QueueId,
+    tracer: Arc<Tracer>,
+}
+
+impl<B: hal::Backend> q::CommandQueue<Backend<B>> for CommandQueue<B> {
+    unsafe fn submit<'a, T, Ic, S, Iw, Is>(
+        &mut self,
+        submission: q::Submission<Ic, Iw, Is>,
+        fence: Option<&Traced<B::Fence>>,
+    ) where
+        T: 'a + Borrow<CommandBuffer<B>>,
+        Ic: IntoIterator<Item = &'a T>,
+        S: 'a + Borrow<Traced<B::Semaphore>>,
+        Iw: IntoIterator<Item = (&'a S, hal::pso::PipelineStage)>,
+        Is: IntoIterator<Item = &'a S>,
+    {
+        let command_buffers = submission
+            .command_buffers
+            .into_iter()
+            .map(|cmd_buffer| cmd_buffer.borrow())
+            .collect::<Vec<_>>();
+        let wait_semaphores = submission
+            .wait_semaphores
+            .into_iter()
+            .map(|(semaphore, stage)| (semaphore.borrow(), stage))
+            .collect::<Vec<_>>();
+        let signal_semaphores = submission
+            .signal_semaphores
+            .into_iter()
+            .map(|semaphore| semaphore.borrow())
+            .collect::<Vec<_>>();
+
+        // the submission may read anything the host wrote so far
+        self.tracer.capture_all();
+        self.tracer.add(Action::Submit {
+            queue: self.id,
+            command_buffers: command_buffers.iter().map(|cmd| cmd.id).collect(),
+            wait_semaphores: wait_semaphores
+                .iter()
+                .map(|&(semaphore, stage)| (semaphore.id, stage))
+                .collect(),
+            signal_semaphores: signal_semaphores.iter().map(|sem| sem.id).collect(),
+            fence: fence.map(|fence| fence.id),
+        });
+
+        self.raw.submit(
+            q::Submission {
+                command_buffers: command_buffers.into_iter().map(|cmd| &cmd.raw),
+                wait_semaphores: wait_semaphores
+                    .into_iter()
+                    .map(|(semaphore, stage)| (&semaphore.raw, stage)),
+                signal_semaphores: signal_semaphores.into_iter().map(|sem| &sem.raw),
+            },
+            fence.map(|fence| &fence.raw),
+        )
+    }
+
+    unsafe fn present<'a, W, Is, S, Iw>(
+        &mut self,
+        swapchains: Is,
+        wait_semaphores: Iw,
+    ) -> Result<Option<window::Suboptimal>, window::PresentError>
+    where
+        W: 'a + Borrow<Swapchain<B>>,
+        Is: IntoIterator<Item = (&'a W, window::SwapImageIndex)>,
+        S: 'a + Borrow<Traced<B::Semaphore>>,
+        Iw: IntoIterator<Item = &'a S>,
+    {
+        let swapchains = swapchains
+            .into_iter()
+            .map(|(swapchain, index)| (swapchain.borrow(), index))
+            .collect::<Vec<_>>();
+        let wait_semaphores = wait_semaphores
+            .into_iter()
+            .map(|semaphore| semaphore.borrow())
+            .collect::<Vec<_>>();
+        self.tracer.add(Action::Present {
+            queue: self.id,
+            swapchains: swapchains
+                .iter()
+                .map(|&(swapchain, index)| (swapchain.id, index))
+                .collect(),
+            wait_semaphores: wait_semaphores.iter().map(|sem| sem.id).collect(),
+        });
+        self.raw.present(
+            swapchains
+                .into_iter()
+                .map(|(swapchain, index)| (&swapchain.raw, index)),
+            wait_semaphores.into_iter().map(|sem| &sem.raw),
+        )
+    }
+
+    unsafe fn present_surface(
+        &mut self,
+        surface: &mut Surface<B>,
+        image: SwapchainImage<B>,
+        wait_semaphore: Option<&Traced<B::Semaphore>>,
+    ) -> Result<Option<window::Suboptimal>, window::PresentError> {
+        self.tracer.add(Action::PresentSurface {
+            queue: self.id,
+            surface: surface.id,
+            view: image.view.id,
+            wait_semaphore: wait_semaphore.map(|semaphore| semaphore.id),
+        });
+        self.raw.present_surface(
+            &mut surface.raw,
+            *image.raw,
+            wait_semaphore.map(|semaphore| &semaphore.raw),
+        )
+    }
+
+    fn wait_idle(&self) -> Result<(), d::OutOfMemory> {
+        self.tracer.add(Action::QueueWaitIdle(self.id));
+        self.raw.wait_idle()
+    }
+}
+
+/// Surface of the wrapped backend, created with `Instance::wrap_surface`.
+///
+/// Surfaces are not recorded as such, but replayed as offscreen images.
+#[derive(Debug)]
+pub struct Surface<B: hal::Backend> {
+    raw: B::Surface,
+    id: Id,
+    tracer: Arc<Tracer>,
+}
+
+impl<B: hal::Backend> window::Surface<Backend<B>> for Surface<B> {
+    fn supports_queue_family(&self, family: &B::QueueFamily) -> bool {
+        self.raw.supports_queue_family(family)
+    }
+
+    fn compatibility(
+        &self,
+        physical_device: &PhysicalDevice<B>,
+    ) -> (
+        window::SurfaceCapabilities,
+        Option<Vec<format::Format>>,
+        Vec<window::PresentMode>,
+    ) {
+        self.raw.compatibility(&physical_device.raw)
+    }
+}
+
+impl<B: hal::Backend> window::PresentationSurface<Backend<B>> for Surface<B> {
+    type SwapchainImage = SwapchainImage<B>;
+
+    unsafe fn configure_swapchain(
+        &mut self,
+        device: &Device<B>,
+        config: window::SwapchainConfig,
+    ) -> Result<(), window::CreationError> {
+        self.tracer.add(Action::ConfigureSwapchain {
+            surface: self.id,
+            config: config.clone(),
+        });
+        self.raw.configure_swapchain(&device.raw, config)
+    }
+
+    unsafe fn unconfigure_swapchain(&mut self, device: &Device<B>) {
+        self.tracer.add(Action::UnconfigureSwapchain(self.id));
+        self.raw.unconfigure_swapchain(&device.raw)
+    }
+
+    unsafe fn acquire_image(
+        &mut self,
+        timeout_ns: u64,
+    ) -> Result<(Self::SwapchainImage, Option<window::Suboptimal>), window::AcquireError> {
+        let (raw, suboptimal) = self.raw.acquire_image(timeout_ns)?;
+        let raw = Box::new(raw);
+        let view = ImageView {
+            raw: ImageViewRaw::Swapchain((*raw).borrow() as *const _),
+            id: self.tracer.id(),
+        };
+        self.tracer.add(Action::AcquireSurfaceImage {
+            surface: self.id,
+            view: view.id,
+        });
+        Ok((SwapchainImage { raw, view }, suboptimal))
+    }
+}
+
+/// Swapchain of the wrapped backend.
+#[derive(Debug)]
+pub struct Swapchain<B: hal::Backend> {
+    raw: B::Swapchain,
+    id: Id,
+    tracer: Arc<Tracer>,
+}
+
+impl<B: hal::Backend> window::Swapchain<Backend<B>> for Swapchain<B> {
+    unsafe fn acquire_image(
+        &mut self,
+        timeout_ns: u64,
+        semaphore: Option<&Traced<B::Semaphore>>,
+        fence: Option<&Traced<B::Fence>>,
+    ) -> Result<(window::SwapImageIndex, Option<window::Suboptimal>), window::AcquireError> {
+        self.tracer.add(Action::AcquireImage {
+            swapchain: self.id,
+            semaphore: semaphore.map(|semaphore| semaphore.id),
+            fence: fence.map(|fence| fence.id),
+        });
+        self.raw.acquire_image(
+            timeout_ns,
+            semaphore.map(|semaphore| &semaphore.raw),
+            fence.map(|fence| &fence.raw),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hal::command::{self as com, CommandBuffer as _};
+    use hal::device::Device as _;
+    use hal::pool::{self, CommandPool as _};
+    use hal::queue::CommandQueue as _;
+    use hal::pso::DescriptorPool as _;
+    use hal::{buffer, format, image, pass, pso, Instance as _, MemoryTypeId};
+
+    use std::{env, fs, iter, ptr, slice};
+
+    type Empty = gfx_backend_empty::Backend;
+
+    const DATA: [u8; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+
+    const TEXELS: [u8; 16] = [
+        255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 255, 255, 255, 255,
+    ];
+
+    /// Host data written after the target of the draw.
+    const MARKER: [u8; 4] = [9, 8, 7, 6];
+
+    unsafe fn bound_buffer(
+        device: &Device<Empty>,
+        usage: buffer::Usage,
+    ) -> (Traced<<Empty as hal::Backend>::Buffer>, Memory<Empty>) {
+        let mut buffer = device.create_buffer(DATA.len() as u64, usage).unwrap();
+        let requirements = device.get_buffer_requirements(&buffer);
+        // CPU visible and coherent on the CPU backend
+        let memory = device
+            .allocate_memory(MemoryTypeId(1), requirements.size)
+            .unwrap();
+        device.bind_buffer_memory(&memory, 0, &mut buffer).unwrap();
+        (buffer, memory)
+    }
+
+    /// Record a copy of host data between two buffers, returning the
+    /// identifier of the memory of the destination.
+    unsafe fn record(dir: &Path) -> Id {
+        let instance = Instance::new(
+            gfx_backend_empty::Instance::create("trace", 1).unwrap(),
+            dir,
+        )
+        .unwrap();
+        let (_, mut gpu) = gfx_backend_empty::test_utils::open(&instance);
+        let device = &gpu.device;
+
+        let (src, src_memory) = bound_buffer(device, buffer::Usage::TRANSFER_SRC);
+        let (dst, dst_memory) = bound_buffer(device, buffer::Usage::TRANSFER_DST);
+        let ptr = device
+            .map_memory(&src_memory, 0 .. DATA.len() as u64)
+            .unwrap();
+        ptr::copy_nonoverlapping(DATA.as_ptr(), ptr, DATA.len());
+
+        let mut pool = device
+            .create_command_pool(
+                gpu.queue_groups[0].family,
+                pool::CommandPoolCreateFlags::empty(),
+            )
+            .unwrap();
+        let mut cmd_buffer = pool.allocate_one(com::Level::Primary);
+        cmd_buffer.begin_primary(com::CommandBufferFlags::ONE_TIME_SUBMIT);
+        cmd_buffer.copy_buffer(
+            &src,
+            &dst,
+            Some(com::BufferCopy {
+                src: 0,
+                dst: 0,
+                size: DATA.len() as u64,
+            }),
+        );
+        cmd_buffer.finish();
+
+        let fence = device.create_fence(false).unwrap();
+        gpu.queue_groups[0].queues[0].submit(
+            q::Submission {
+                command_buffers: iter::once(&cmd_buffer),
+                wait_semaphores: iter::empty(),
+                signal_semaphores: iter::empty::<&Traced<<Empty as hal::Backend>::Semaphore>>(),
+            },
+            Some(&fence),
+        );
+        device.wait_for_fence(&fence, !0).unwrap();
+
+        device.unmap_memory(&src_memory);
+        device.destroy_fence(fence);
+        pool.free(iter::once(cmd_buffer));
+        device.destroy_command_pool(pool);
+        device.destroy_buffer(src);
+        device.destroy_buffer(dst);
+        device.free_memory(src_memory);
+        dst_memory.id()
+    }
+
+    #[test]
+    fn test_record_replay() {
+        let dir = env::temp_dir().join(format!("gfx-trace-{}", std::process::id()));
+        let memory = unsafe { record(&dir) };
+
+        let actions = replay::load(&dir).unwrap();
+        match actions[0] {
+            Action::Init { ref families, .. } => assert_eq!(families.len(), 1),
+            ref other => panic!("the trace starts with {:?}", other),
+        }
+        let written = actions
+            .iter()
+            .filter_map(|action| match *action {
+                Action::WriteMemory { ref data, .. } => Some(data),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(written.len(), 1, "{:?}", written);
+        assert_eq!(fs::read(dir.join(written[0])).unwrap(), &DATA[..]);
+
+        let instance = gfx_backend_empty::Instance::create("replay", 1).unwrap();
+        let adapter = instance.enumerate_adapters().remove(0);
+        unsafe {
+            let mut replayer = Replayer::<Empty>::new(adapter, &dir).unwrap();
+            replayer.run().unwrap();
+            let device = replayer.device();
+            let memory = replayer.memory(memory).unwrap();
+            let ptr = device.map_memory(memory, 0 .. DATA.len() as u64).unwrap();
+            assert_eq!(slice::from_raw_parts(ptr, DATA.len()), &DATA[..]);
+            device.unmap_memory(memory);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn words(bytes: &[u8]) -> Vec<u32> {
+        bytes
+            .chunks(4)
+            .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect()
+    }
+
+    fn open(dir: &Path) -> hal::adapter::Gpu<Backend<Empty>> {
+        let instance = Instance::new(
+            gfx_backend_empty::Instance::create("trace", 1).unwrap(),
+            dir,
+        )
+        .unwrap();
+        gfx_backend_empty::test_utils::open(&instance).1
+    }
+
+    /// Record a textured quad drawn into a 4x4 image, reading the result back
+    /// through an invalidated mapping and writing `MARKER` after it. Returns
+    /// the identifier of the memory of the image and the drawn pixels.
+    unsafe fn record_draw(dir: &Path) -> (Id, Vec<u8>) {
+        let mut gpu = open(dir);
+        let device = &gpu.device;
+        let format = format::Format::Rgba8Unorm;
+        // Positions and texture coordinates of two triangles covering the viewport.
+        let vertices: [f32; 24] = [
+            -1.0, 1.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, -1.0, 1.0, 0.0, -1.0, 1.0, 0.0, 1.0, 1.0,
+            -1.0, 1.0, 0.0, -1.0, -1.0, 0.0, 0.0,
+        ];
+
+        let source_memory = device.allocate_memory(MemoryTypeId(1), 256).unwrap();
+        let target_memory = device.allocate_memory(MemoryTypeId(2), 128).unwrap();
+        let create_image = |size: u32, memory: &Memory<Empty>| {
+            let mut image = device
+                .create_image(
+                    image::Kind::D2(size, size, 1, 1),
+                    1,
+                    format,
+                    image::Tiling::Linear,
+                    image::Usage::SAMPLED | image::Usage::COLOR_ATTACHMENT,
+                    image::ViewCapabilities::empty(),
+                )
+                .unwrap();
+            device.bind_image_memory(memory, 0, &mut image).unwrap();
+            let view = device
+                .create_image_view(
+                    &image,
+                    image::ViewKind::D2,
+                    format,
+                    format::Swizzle::NO,
+                    image::SubresourceRange {
+                        aspects: format::Aspects::COLOR,
+                        levels: 0 .. 1,
+                        layers: 0 .. 1,
+                    },
+                )
+                .unwrap();
+            (image, view)
+        };
+        let (_texture, texture_view) = create_image(2, &source_memory);
+        let (_target, target_view) = create_image(4, &target_memory);
+        let mut vertex_buffer = device.create_buffer(96, buffer::Usage::VERTEX).unwrap();
+        device
+            .bind_buffer_memory(&source_memory, 96, &mut vertex_buffer)
+            .unwrap();
+        let source = device.map_memory(&source_memory, ..).unwrap();
+        ptr::copy_nonoverlapping(TEXELS.as_ptr(), source, TEXELS.len());
+        ptr::copy_nonoverlapping(vertices.as_ptr() as *const u8, source.offset(96), 96);
+        let target = device.map_memory(&target_memory, ..).unwrap();
+
+        let sampler = device
+            .create_sampler(image::SamplerInfo::new(
+                image::Filter::Nearest,
+                image::WrapMode::Clamp,
+            ))
+            .unwrap();
+        let bindings = [
+            pso::DescriptorType::SampledImage,
+            pso::DescriptorType::Sampler,
+        ];
+        let set_layout = device
+            .create_descriptor_set_layout(
+                bindings
+                    .iter()
+                    .enumerate()
+                    .map(|(binding, &ty)| pso::DescriptorSetLayoutBinding {
+                        binding: binding as u32,
+                        ty,
+                        count: 1,
+                        stage_flags: pso::ShaderStageFlags::FRAGMENT,
+                        immutable_samplers: false,
+                    }),
+                &[],
+            )
+            .unwrap();
+        let mut desc_pool = device
+            .create_descriptor_pool(
+                1,
+                bindings
+                    .iter()
+                    .map(|&ty| pso::DescriptorRangeDesc { ty, count: 1 }),
+                pso::DescriptorPoolCreateFlags::empty(),
+            )
+            .unwrap();
+        let set = desc_pool.allocate_set(&set_layout).unwrap();
+        device.write_descriptor_sets(Some(pso::DescriptorSetWrite {
+            set: &set,
+            binding: 0,
+            array_offset: 0,
+            descriptors: vec![
+                pso::Descriptor::Image(&texture_view, image::Layout::General),
+                pso::Descriptor::Sampler(&sampler),
+            ],
+        }));
+
+        let render_pass = device
+            .create_render_pass(
+                Some(pass::Attachment {
+                    format: Some(format),
+                    samples: 1,
+                    ops: pass::AttachmentOps::new(
+                        pass::AttachmentLoadOp::Clear,
+                        pass::AttachmentStoreOp::Store,
+                    ),
+                    stencil_ops: pass::AttachmentOps::DONT_CARE,
+                    layouts: image::Layout::General .. image::Layout::General,
+                }),
+                Some(pass::SubpassDesc {
+                    colors: &[(0, image::Layout::General)],
+                    depth_stencil: None,
+                    inputs: &[],
+                    resolves: &[],
+                    preserves: &[],
+                }),
+                &[],
+            )
+            .unwrap();
+        let framebuffer = device
+            .create_framebuffer(
+                &render_pass,
+                Some(&target_view),
+                image::Extent {
+                    width: 4,
+                    height: 4,
+                    depth: 1,
+                },
+            )
+            .unwrap();
+        let layout = device
+            .create_pipeline_layout(Some(&set_layout), &[])
+            .unwrap();
+        let vs = device
+            .create_shader_module(&words(include_bytes!(
+                "../../../../examples/quad/data/quad.vert.spv"
+            )))
+            .unwrap();
+        let fs = device
+            .create_shader_module(&words(include_bytes!(
+                "../../../../examples/quad/data/quad.frag.spv"
+            )))
+            .unwrap();
+        let shaders = pso::GraphicsShaderSet {
+            vertex: pso::EntryPoint {
+                entry: "main",
+                module: &vs,
+                specialization: hal::spec_const_list![1.0f32],
+            },
+            hull: None,
+            domain: None,
+            geometry: None,
+            fragment: Some(pso::EntryPoint {
+                entry: "main",
+                module: &fs,
+                specialization: pso::Specialization::default(),
+            }),
+        };
+        let mut desc = pso::GraphicsPipelineDesc::new(
+            shaders,
+            hal::Primitive::TriangleList,
+            pso::Rasterizer::FILL,
+            &layout,
+            pass::Subpass {
+                index: 0,
+                main_pass: &render_pass,
+            },
+        );
+        desc.blender.targets.push(pso::ColorBlendDesc {
+            mask: pso::ColorMask::ALL,
+            blend: None,
+        });
+        desc.vertex_buffers.push(pso::VertexBufferDesc {
+            binding: 0,
+            stride: 16,
+            rate: pso::VertexInputRate::Vertex,
+        });
+        for &(location, offset) in &[(0, 0), (1, 8)] {
+            desc.attributes.push(pso::AttributeDesc {
+                location,
+                binding: 0,
+                element: pso::Element {
+                    format: format::Format::Rg32Sfloat,
+                    offset,
+                },
+            });
+        }
+        let pipeline = device.create_graphics_pipeline(&desc, None).unwrap();
+
+        let mut pool = device
+            .create_command_pool(
+                gpu.queue_groups[0].family,
+                pool::CommandPoolCreateFlags::empty(),
+            )
+            .unwrap();
+        let mut cmd_buffer = pool.allocate_one(com::Level::Primary);
+        let rect = pso::Rect {
+            x: 0,
+            y: 0,
+            w: 4,
+            h: 4,
+        };
+        cmd_buffer.begin_primary(com::CommandBufferFlags::ONE_TIME_SUBMIT);
+        cmd_buffer.begin_render_pass(
+            &render_pass,
+            &framebuffer,
+            rect,
+            Some(com::ClearValue {
+                color: com::ClearColor { float32: [0.0; 4] },
+            }),
+            com::SubpassContents::Inline,
+        );
+        cmd_buffer.bind_graphics_pipeline(&pipeline);
+        cmd_buffer.bind_graphics_descriptor_sets(&layout, 0, Some(&set), &[]);
+        cmd_buffer.set_viewports(
+            0,
+            Some(pso::Viewport {
+                rect,
+                depth: 0.0 .. 1.0,
+            }),
+        );
+        cmd_buffer.set_scissors(0, Some(rect));
+        cmd_buffer.bind_vertex_buffers(0, Some((&vertex_buffer, 0)));
+        cmd_buffer.draw(0 .. 6, 0 .. 1);
+        cmd_buffer.end_render_pass();
+        cmd_buffer.finish();
+
+        let fence = device.create_fence(false).unwrap();
+        gpu.queue_groups[0].queues[0].submit_without_semaphores(Some(&cmd_buffer), Some(&fence));
+        device.wait_for_fence(&fence, !0).unwrap();
+
+        // The drawn pixels are not host writes, and are left out of the trace.
+        device
+            .invalidate_mapped_memory_ranges(Some((&target_memory, ..)))
+            .unwrap();
+        let pixels = slice::from_raw_parts(target, 64).to_vec();
+        ptr::copy_nonoverlapping(MARKER.as_ptr(), target.offset(64), MARKER.len());
+        device.unmap_memory(&target_memory);
+        device.unmap_memory(&source_memory);
+
+        (target_memory.id(), pixels)
+    }
+
+    #[test]
+    fn test_record_replay_draw() {
+        let dir = env::temp_dir().join(format!("gfx-trace-draw-{}", std::process::id()));
+        let (memory, pixels) = unsafe { record_draw(&dir) };
+        // Every texel of the texture covers a quarter of the target.
+        for (i, pixel) in pixels.chunks(4).enumerate() {
+            let (x, y) = (i % 4 / 2, i / 8);
+            assert_eq!(pixel, &TEXELS[(y * 2 + x) * 4 .. (y * 2 + x + 1) * 4]);
+        }
+
+        let actions = replay::load(&dir).unwrap();
+        let written = actions
+            .iter()
+            .filter_map(|action| match *action {
+                Action::WriteMemory {
+                    memory: id,
+                    offset,
+                    ref data,
+                } if id == memory => Some((offset, data)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(written.len(), 1, "{:?}", written);
+        assert_eq!(written[0].0, 64);
+        assert_eq!(fs::read(dir.join(written[0].1)).unwrap(), &MARKER[..]);
+
+        let instance = gfx_backend_empty::Instance::create("replay", 1).unwrap();
+        let adapter = instance.enumerate_adapters().remove(0);
+        unsafe {
+            let mut replayer = Replayer::<Empty>::new(adapter, &dir).unwrap();
+            replayer.run().unwrap();
+            let device = replayer.device();
+            let memory = replayer.memory(memory).unwrap();
+            let ptr = device.map_memory(memory, 0 .. 68).unwrap();
+            assert_eq!(slice::from_raw_parts(ptr, 64), &pixels[..]);
+            assert_eq!(slice::from_raw_parts(ptr.offset(64), 4), &MARKER[..]);
+            device.unmap_memory(memory);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,194 @@
+//! Writing of the trace files.
+
+use crate::trace::{Action, DataFile, Id};
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::{ptr, slice};
+
+/// Name of the list of actions in a trace directory.
+pub const ACTIONS_FILE: &str = "trace.ron";
+
+/// Host mapping of a memory object.
+///
+/// Writes of the host are detected by comparing the mapped memory with a
+/// copy of it, taken when it was mapped and updated on every capture.
+#[derive(Debug)]
+struct Mapping {
+    ptr: usize,
+    offset: u64,
+    shadow: Vec<u8>,
+}
+
+#[derive(Debug)]
+struct Recorder {
+    dir: PathBuf,
+    file: BufWriter<File>,
+    next_data: usize,
+    mappings: HashMap<Id, Mapping>,
+}
+
+impl Recorder {
+    fn write(&mut self, action: &Action) -> io::Result<()> {
+        let text = ron::ser::to_string_pretty(action, ron::ser::PrettyConfig::default())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        writeln!(self.file, "{},", text)
+    }
+
+    fn add(&mut self, action: Action) {
+        if let Err(e) = self.write(&action) {
+            error!("Unable to record {:?}: {}", action, e);
+        }
+        // keep the trace usable if the application crashes on the device
+        if let Action::Submit { .. } = action {
+            let _ = self.file.flush();
+        }
+    }
+
+    fn add_data(&mut self, extension: &str, data: &[u8]) -> DataFile {
+        let name = format!("data{}.{}", self.next_data, extension);
+        self.next_data += 1;
+        if let Err(e) = fs::write(self.dir.join(&name), data) {
+            error!("Unable to write {}: {}", name, e);
+        }
+        name
+    }
+
+    /// Record the bytes of a mapping that changed since the last capture.
+    fn capture(&mut self, memory: Id) {
+        let (offset, data) = {
+            let mapping = match self.mappings.get_mut(&memory) {
+                Some(mapping) => mapping,
+                None => return,
+            };
+            let current =
+                unsafe { slice::from_raw_parts(mapping.ptr as *const u8, mapping.shadow.len()) };
+            let first = match current
+                .iter()
+                .zip(&mapping.shadow)
+                .position(|(a, b)| a != b)
+            {
+                Some(first) => first,
+                None => return,
+            };
+            let last = current
+                .iter()
+                .zip(&mapping.shadow)
+                .rposition(|(a, b)| a != b)
+                .unwrap();
+            mapping.shadow[first ..= last].copy_from_slice(&current[first ..= last]);
+            (
+                mapping.offset + first as u64,
+                current[first ..= last].to_vec(),
+            )
+        };
+        let data = self.add_data("bin", &data);
+        self.add(Action::WriteMemory {
+            memory,
+            offset,
+            data,
+        });
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let _ = writeln!(self.file, "]").and_then(|_| self.file.flush());
+    }
+}
+
+/// Trace being recorded, shared by all the objects of a device.
+#[derive(Debug)]
+pub(crate) struct Tracer {
+    next_id: AtomicUsize,
+    recorder: Mutex<Recorder>,
+}
+
+impl Tracer {
+    pub(crate) fn new(dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let mut file = BufWriter::new(File::create(dir.join(ACTIONS_FILE))?);
+        writeln!(file, "[")?;
+        Ok(Tracer {
+            next_id: AtomicUsize::new(0),
+            recorder: Mutex::new(Recorder {
+                dir: dir.to_path_buf(),
+                file,
+                next_data: 0,
+                mappings: HashMap::new(),
+            }),
+        })
+    }
+
+    /// Allocate an identifier for a new object.
+    pub(crate) fn id(&self) -> Id {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    pub(crate) fn add(&self, action: Action) {
+        self.recorder.lock().unwrap().add(action)
+    }
+
+    /// Store binary data next to the actions, returning the name of the file.
+    pub(crate) fn add_data(&self, extension: &str, data: &[u8]) -> DataFile {
+        self.recorder.lock().unwrap().add_data(extension, data)
+    }
+
+    /// Start tracking the host writes to a mapped range of memory.
+    pub(crate) unsafe fn map(&self, memory: Id, ptr: *mut u8, offset: u64, size: u64) {
+        let mut shadow = vec![0; size as usize];
+        ptr::copy_nonoverlapping(ptr, shadow.as_mut_ptr(), shadow.len());
+        let mapping = Mapping {
+            ptr: ptr as usize,
+            offset,
+            shadow,
+        };
+        self.recorder
+            .lock()
+            .unwrap()
+            .mappings
+            .insert(memory, mapping);
+    }
+
+    /// Record the host writes to a memory object since the last capture.
+    pub(crate) fn capture(&self, memory: Id) {
+        self.recorder.lock().unwrap().capture(memory)
+    }
+
+    /// Record the host writes to all the mapped memory, e.g. before a
+    /// submission reads them.
+    pub(crate) fn capture_all(&self) {
+        let mut recorder = self.recorder.lock().unwrap();
+        let mut ids = recorder.mappings.keys().cloned().collect::<Vec<_>>();
+        ids.sort();
+        for id in ids {
+            recorder.capture(id);
+        }
+    }
+
+    /// Forget the current contents of a mapping, after the device wrote to it.
+    pub(crate) fn refresh(&self, memory: Id) {
+        let mut recorder = self.recorder.lock().unwrap();
+        if let Some(mapping) = recorder.mappings.get_mut(&memory) {
+            let len = mapping.shadow.len();
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    mapping.ptr as *const u8,
+                    mapping.shadow.as_mut_ptr(),
+                    len,
+                );
+            }
+        }
+    }
+
+    /// Record the last host writes to a memory object and stop tracking it.
+    pub(crate) fn unmap(&self, memory: Id) {
+        let mut recorder = self.recorder.lock().unwrap();
+        recorder.capture(memory);
+        recorder.mappings.remove(&memory);
+    }
+}
@@ -0,0 +1,1549 @@
+//! Playback of the traces on any backend.
+//!
+//! Queue families and memory types are matched by their capabilities with
+//! the ones of the replaying adapter, and surfaces and swapchains are
+//! replaced with offscreen images. Objects are bound at the offsets of the
+//! trace, so the memory requirements of the replaying device have to be
+//! compatible with the recorded ones.
+
+use crate::record::ACTIONS_FILE;
+use crate::trace::{self, Action, Command, Id, QueueId};
+
+use hal::adapter::{Adapter, PhysicalDevice as _};
+use hal::command::CommandBuffer as _;
+use hal::device::Device as _;
+use hal::pool::CommandPool as _;
+use hal::pso::DescriptorPool as _;
+use hal::queue::{CommandQueue as _, QueueFamily as _, QueueFamilyId, QueueGroup, QueueType};
+use hal::MemoryTypeId;
+use hal::{buffer, command as com, format, image, memory, pass, pso, query, window};
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::{error, fmt, fs, io, iter, ptr};
+
+/// Error of a replay.
+#[derive(Debug)]
+pub enum ReplayError {
+    /// A file of the trace can't be read.
+    Io(io::Error),
+    /// The trace is malformed.
+    Format(String),
+    /// An action refers to an object that doesn't exist at this point.
+    MissingObject(Id),
+    /// The replaying device failed to perform an action.
+    Device(String),
+}
+
+impl From<io::Error> for ReplayError {
+    fn from(error: io::Error) -> Self {
+        ReplayError::Io(error)
+    }
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReplayError::Io(ref e) => write!(f, "unable to read the trace: {}", e),
+            ReplayError::Format(ref e) => write!(f, "malformed trace: {}", e),
+            ReplayError::MissingObject(id) => write!(f, "object {} doesn't exist", id),
+            ReplayError::Device(ref e) => write!(f, "device error: {}", e),
+        }
+    }
+}
+
+impl error::Error for ReplayError {}
+
+fn device_error<E: fmt::Debug>(error: E) -> ReplayError {
+    ReplayError::Device(format!("{:?}", error))
+}
+
+fn get<T>(map: &HashMap<Id, T>, id: Id) -> Result<&T, ReplayError> {
+    map.get(&id).ok_or(ReplayError::MissingObject(id))
+}
+
+fn take<T>(map: &mut HashMap<Id, T>, id: Id) -> Result<T, ReplayError> {
+    map.remove(&id).ok_or(ReplayError::MissingObject(id))
+}
+
+fn get_all<'a, T>(map: &'a HashMap<Id, T>, ids: &[Id]) -> Result<Vec<&'a T>, ReplayError> {
+    ids.iter().map(|&id| get(map, id)).collect()
+}
+
+/// Load the actions of a trace directory.
+///
+/// The list may be unterminated, e.g. when the traced application crashed.
+pub fn load<P: AsRef<Path>>(dir: P) -> Result<Vec<Action>, ReplayError> {
+    let mut text = fs::read_to_string(dir.as_ref().join(ACTIONS_FILE))?;
+    if !text.trim_end().ends_with(']') {
+        text.push(']');
+    }
+    ron::de::from_str(&text).map_err(|e| ReplayError::Format(e.to_string()))
+}
+
+fn supports(family: QueueType, ty: QueueType) -> bool {
+    match ty {
+        QueueType::General => family.supports_graphics() && family.supports_compute(),
+        QueueType::Graphics => family.supports_graphics(),
+        QueueType::Compute => family.supports_compute(),
+        QueueType::Transfer => family.supports_transfer(),
+    }
+}
+
+/// Pick the memory type with the same properties, or the closest ones.
+fn match_memory_type(types: &[hal::adapter::MemoryType], properties: memory::Properties) -> usize {
+    let host = properties & (memory::Properties::CPU_VISIBLE | memory::Properties::COHERENT);
+    types
+        .iter()
+        .position(|ty| ty.properties == properties)
+        .or_else(|| {
+            types
+                .iter()
+                .position(|ty| ty.properties.contains(properties))
+        })
+        .or_else(|| types.iter().position(|ty| ty.properties.contains(host)))
+        .unwrap_or(0)
+}
+
+/// Offscreen image standing for the images of a surface.
+#[derive(Debug)]
+struct Offscreen<B: hal::Backend> {
+    config: window::SwapchainConfig,
+    image: B::Image,
+    memory: B::Memory,
+}
+
+/// Objects of the replaying device, by their identifier in the trace.
+#[derive(Debug)]
+struct Objects<B: hal::Backend> {
+    memories: HashMap<Id, B::Memory>,
+    command_pools: HashMap<Id, B::CommandPool>,
+    command_buffers: HashMap<Id, B::CommandBuffer>,
+    render_passes: HashMap<Id, B::RenderPass>,
+    pipeline_layouts: HashMap<Id, B::PipelineLayout>,
+    pipeline_caches: HashMap<Id, B::PipelineCache>,
+    graphics_pipelines: HashMap<Id, B::GraphicsPipeline>,
+    compute_pipelines: HashMap<Id, B::ComputePipeline>,
+    framebuffers: HashMap<Id, B::Framebuffer>,
+    shader_modules: HashMap<Id, B::ShaderModule>,
+    buffers: HashMap<Id, B::Buffer>,
+    buffer_views: HashMap<Id, B::BufferView>,
+    images: HashMap<Id, B::Image>,
+    image_views: HashMap<Id, B::ImageView>,
+    samplers: HashMap<Id, B::Sampler>,
+    descriptor_pools: HashMap<Id, B::DescriptorPool>,
+    descriptor_set_layouts: HashMap<Id, B::DescriptorSetLayout>,
+    descriptor_sets: HashMap<Id, B::DescriptorSet>,
+    semaphores: HashMap<Id, B::Semaphore>,
+    fences: HashMap<Id, B::Fence>,
+    events: HashMap<Id, B::Event>,
+    query_pools: HashMap<Id, B::QueryPool>,
+    /// Images of the swapchains, with the memory bound to them.
+    swapchains: HashMap<Id, Vec<(Id, B::Memory)>>,
+    surfaces: HashMap<Id, Offscreen<B>>,
+}
+
+impl<B: hal::Backend> Default for Objects<B> {
+    fn default() -> Self {
+        Objects {
+            memories: HashMap::new(),
+            command_pools: HashMap::new(),
+            command_buffers: HashMap::new(),
+            render_passes: HashMap::new(),
+            pipeline_layouts: HashMap::new(),
+            pipeline_caches: HashMap::new(),
+            graphics_pipelines: HashMap::new(),
+            compute_pipelines: HashMap::new(),
+            framebuffers: HashMap::new(),
+            shader_modules: HashMap::new(),
+            buffers: HashMap::new(),
+            buffer_views: HashMap::new(),
+            images: HashMap::new(),
+            image_views: HashMap::new(),
+            samplers: HashMap::new(),
+            descriptor_pools: HashMap::new(),
+            descriptor_set_layouts: HashMap::new(),
+            descriptor_sets: HashMap::new(),
+            semaphores: HashMap::new(),
+            fences: HashMap::new(),
+            events: HashMap::new(),
+            query_pools: HashMap::new(),
+            swapchains: HashMap::new(),
+            surfaces: HashMap::new(),
+        }
+    }
+}
+
+impl<B: hal::Backend> Objects<B> {
+    fn entry_point<'a>(
+        &'a self,
+        entry: &'a trace::EntryPoint,
+    ) -> Result<pso::EntryPoint<'a, B>, ReplayError> {
+        Ok(pso::EntryPoint {
+            entry: &entry.entry,
+            module: get(&self.shader_modules, entry.module)?,
+            specialization: entry.specialization.clone(),
+        })
+    }
+
+    fn optional_entry_point<'a>(
+        &'a self,
+        entry: &'a Option<trace::EntryPoint>,
+    ) -> Result<Option<pso::EntryPoint<'a, B>>, ReplayError> {
+        match *entry {
+            Some(ref entry) => self.entry_point(entry).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn base_pipeline<'a, P>(
+        map: &'a HashMap<Id, P>,
+        parent: &trace::BasePipeline,
+    ) -> Result<pso::BasePipeline<'a, P>, ReplayError> {
+        Ok(match *parent {
+            trace::BasePipeline::Pipeline(id) => pso::BasePipeline::Pipeline(get(map, id)?),
+            trace::BasePipeline::Index(index) => pso::BasePipeline::Index(index),
+            trace::BasePipeline::None => pso::BasePipeline::None,
+        })
+    }
+
+    fn descriptor<'a>(
+        &'a self,
+        descriptor: &trace::Descriptor,
+    ) -> Result<pso::Descriptor<'a, B>, ReplayError> {
+        Ok(match *descriptor {
+            trace::Descriptor::Sampler(id) => pso::Descriptor::Sampler(get(&self.samplers, id)?),
+            trace::Descriptor::Image(id, layout) => {
+                pso::Descriptor::Image(get(&self.image_views, id)?, layout)
+            }
+            trace::Descriptor::CombinedImageSampler(view, layout, sampler) => {
+                pso::Descriptor::CombinedImageSampler(
+                    get(&self.image_views, view)?,
+                    layout,
+                    get(&self.samplers, sampler)?,
+                )
+            }
+            trace::Descriptor::Buffer(id, ref range) => {
+                pso::Descriptor::Buffer(get(&self.buffers, id)?, range.clone())
+            }
+            trace::Descriptor::UniformTexelBuffer(id) => {
+                pso::Descriptor::UniformTexelBuffer(get(&self.buffer_views, id)?)
+            }
+            trace::Descriptor::StorageTexelBuffer(id) => {
+                pso::Descriptor::StorageTexelBuffer(get(&self.buffer_views, id)?)
+            }
+        })
+    }
+
+    fn barriers<'a>(
+        &'a self,
+        barriers: &[trace::Barrier],
+    ) -> Result<Vec<memory::Barrier<'a, B>>, ReplayError> {
+        barriers
+            .iter()
+            .map(|barrier| {
+                Ok(match *barrier {
+                    trace::Barrier::AllBuffers(ref access) => {
+                        memory::Barrier::AllBuffers(access.clone())
+                    }
+                    trace::Barrier::AllImages(ref access) => {
+                        memory::Barrier::AllImages(access.clone())
+                    }
+                    trace::Barrier::Buffer {
+                        ref states,
+                        target,
+                        ref families,
+                        ref range,
+                    } => memory::Barrier::Buffer {
+                        states: states.clone(),
+                        target: get(&self.buffers, target)?,
+                        families: families.clone(),
+                        range: range.clone(),
+                    },
+                    trace::Barrier::Image {
+                        ref states,
+                        target,
+                        ref families,
+                        ref range,
+                    } => memory::Barrier::Image {
+                        states: states.clone(),
+                        target: get(&self.images, target)?,
+                        families: families.clone(),
+                        range: range.clone(),
+                    },
+                })
+            })
+            .collect()
+    }
+
+    unsafe fn record(
+        &self,
+        cmd_buffer: &mut B::CommandBuffer,
+        flags: com::CommandBufferFlags,
+        inheritance: &trace::InheritanceInfo,
+        commands: &[Command],
+    ) -> Result<(), ReplayError> {
+        let subpass = match inheritance.subpass {
+            Some((index, main_pass)) => Some(pass::Subpass {
+                index,
+                main_pass: get(&self.render_passes, main_pass)?,
+            }),
+            None => None,
+        };
+        let framebuffer = match inheritance.framebuffer {
+            Some(id) => Some(get(&self.framebuffers, id)?),
+            None => None,
+        };
+        cmd_buffer.begin(
+            flags,
+            com::CommandBufferInheritanceInfo {
+                subpass,
+                framebuffer,
+                occlusion_query_enable: inheritance.occlusion_query_enable,
+                occlusion_query_flags: inheritance.occlusion_query_flags,
+                pipeline_statistics: inheritance.pipeline_statistics,
+            },
+        );
+        for command in commands {
+            self.command(cmd_buffer, command)?;
+        }
+        cmd_buffer.finish();
+        Ok(())
+    }
+
+    unsafe fn command(
+        &self,
+        cmd_buffer: &mut B::CommandBuffer,
+        command: &Command,
+    ) -> Result<(), ReplayError> {
+        match *command {
+            Command::PipelineBarrier {
+                ref stages,
+                dependencies,
+                ref barriers,
+            } => {
+                cmd_buffer.pipeline_barrier(stages.clone(), dependencies, self.barriers(barriers)?)
+            }
+            Command::FillBuffer {
+                buffer,
+                range,
+                data,
+            } => cmd_buffer.fill_buffer(get(&self.buffers, buffer)?, range, data),
+            Command::UpdateBuffer {
+                buffer,
+                offset,
+                ref data,
+            } => cmd_buffer.update_buffer(get(&self.buffers, buffer)?, offset, data),
+            Command::ClearImage {
+                image,
+                layout,
+                value,
+                ref ranges,
+            } => cmd_buffer.clear_image(get(&self.images, image)?, layout, value.into(), ranges),
+            Command::ClearAttachments {
+                ref clears,
+                ref rects,
+            } => cmd_buffer.clear_attachments(
+                clears.iter().map(|clear| match *clear {
+                    trace::AttachmentClear::Color { index, value } => com::AttachmentClear::Color {
+                        index,
+                        value: com::ClearColor { uint32: value },
+                    },
+                    trace::AttachmentClear::DepthStencil { depth, stencil } => {
+                        com::AttachmentClear::DepthStencil { depth, stencil }
+                    }
+                }),
+                rects,
+            ),
+            Command::ResolveImage {
+                src,
+                src_layout,
+                dst,
+                dst_layout,
+                ref regions,
+            } => cmd_buffer.resolve_image(
+                get(&self.images, src)?,
+                src_layout,
+                get(&self.images, dst)?,
+                dst_layout,
+                regions,
+            ),
+            Command::BlitImage {
+                src,
+                src_layout,
+                dst,
+                dst_layout,
+                filter,
+                ref regions,
+            } => cmd_buffer.blit_image(
+                get(&self.images, src)?,
+                src_layout,
+                get(&self.images, dst)?,
+                dst_layout,
+                filter,
+                regions,
+            ),
+            Command::BindIndexBuffer {
+                buffer,
+                offset,
+                index_type,
+            } => cmd_buffer.bind_index_buffer(buffer::IndexBufferView {
+                buffer: get(&self.buffers, buffer)?,
+                offset,
+                index_type,
+            }),
+            Command::BindVertexBuffers {
+                first_binding,
+                ref buffers,
+            } => {
+                let buffers = buffers
+                    .iter()
+                    .map(|&(id, offset)| Ok((get(&self.buffers, id)?, offset)))
+                    .collect::<Result<Vec<_>, ReplayError>>()?;
+                cmd_buffer.bind_vertex_buffers(first_binding, buffers)
+            }
+            Command::SetViewports {
+                first_viewport,
+                ref viewports,
+            } => cmd_buffer.set_viewports(first_viewport, viewports),
+            Command::SetScissors {
+                first_scissor,
+                ref rects,
+            } => cmd_buffer.set_scissors(first_scissor, rects),
+            Command::SetStencilReference { faces, value } => {
+                cmd_buffer.set_stencil_reference(faces, value)
+            }
+            Command::SetStencilReadMask { faces, value } => {
+                cmd_buffer.set_stencil_read_mask(faces, value)
+            }
+            Command::SetStencilWriteMask { faces, value } => {
+                cmd_buffer.set_stencil_write_mask(faces, value)
+            }
+            Command::SetBlendConstants(color) => cmd_buffer.set_blend_constants(color),
+            Command::SetDepthBounds(ref bounds) => cmd_buffer.set_depth_bounds(bounds.clone()),
+            Command::SetLineWidth(width) => cmd_buffer.set_line_width(width),
+            Command::SetDepthBias(bias) => cmd_buffer.set_depth_bias(bias),
+            Command::BeginRenderPass {
+                render_pass,
+                framebuffer,
+                render_area,
+                ref clear_values,
+                first_subpass,
+            } => cmd_buffer.begin_render_pass(
+                get(&self.render_passes, render_pass)?,
+                get(&self.framebuffers, framebuffer)?,
+                render_area,
+                clear_values
+                    .iter()
+                    .map(|&value| -> com::ClearValue { value.into() }),
+                first_subpass,
+            ),
+            Command::NextSubpass(contents) => cmd_buffer.next_subpass(contents),
+            Command::EndRenderPass => cmd_buffer.end_render_pass(),
+            Command::BindGraphicsPipeline(id) => {
+                cmd_buffer.bind_graphics_pipeline(get(&self.graphics_pipelines, id)?)
+            }
+            Command::BindGraphicsDescriptorSets {
+                layout,
+                first_set,
+                ref sets,
+                ref offsets,
+            } => cmd_buffer.bind_graphics_descriptor_sets(
+                get(&self.pipeline_layouts, layout)?,
+                first_set,
+                get_all(&self.descriptor_sets, sets)?,
+                offsets,
+            ),
+            Command::BindComputePipeline(id) => {
+                cmd_buffer.bind_compute_pipeline(get(&self.compute_pipelines, id)?)
+            }
+            Command::BindComputeDescriptorSets {
+                layout,
+                first_set,
+                ref sets,
+                ref offsets,
+            } => cmd_buffer.bind_compute_descriptor_sets(
+                get(&self.pipeline_layouts, layout)?,
+                first_set,
+                get_all(&self.descriptor_sets, sets)?,
+                offsets,
+            ),
+            Command::Dispatch(count) => cmd_buffer.dispatch(count),
+            Command::DispatchIndirect { buffer, offset } => {
+                cmd_buffer.dispatch_indirect(get(&self.buffers, buffer)?, offset)
+            }
+            Command::CopyBuffer {
+                src,
+                dst,
+                ref regions,
+            } => {
+                cmd_buffer.copy_buffer(get(&self.buffers, src)?, get(&self.buffers, dst)?, regions)
+            }
+            Command::CopyImage {
+                src,
+                src_layout,
+                dst,
+                dst_layout,
+                ref regions,
+            } => cmd_buffer.copy_image(
+                get(&self.images, src)?,
+                src_layout,
+                get(&self.images, dst)?,
+                dst_layout,
+                regions,
+            ),
+            Command::CopyBufferToImage {
+                src,
+                dst,
+                dst_layout,
+                ref regions,
+            } => cmd_buffer.copy_buffer_to_image(
+                get(&self.buffers, src)?,
+                get(&self.images, dst)?,
+                dst_layout,
+                regions,
+            ),
+            Command::CopyImageToBuffer {
+                src,
+                src_layout,
+                dst,
+                ref regions,
+            } => cmd_buffer.copy_image_to_buffer(
+                get(&self.images, src)?,
+                src_layout,
+                get(&self.buffers, dst)?,
+                regions,
+            ),
+            Command::Draw {
+                ref vertices,
+                ref instances,
+            } => cmd_buffer.draw(vertices.clone(), instances.clone()),
+            Command::DrawIndexed {
+                ref indices,
+                base_vertex,
+                ref instances,
+            } => cmd_buffer.draw_indexed(indices.clone(), base_vertex, instances.clone()),
+            Command::DrawIndirect {
+                buffer,
+                offset,
+                draw_count,
+                stride,
+            } => cmd_buffer.draw_indirect(get(&self.buffers, buffer)?, offset, draw_count, stride),
+            Command::DrawIndexedIndirect {
+                buffer,
+                offset,
+                draw_count,
+                stride,
+            } => cmd_buffer.draw_indexed_indirect(
+                get(&self.buffers, buffer)?,
+                offset,
+                draw_count,
+                stride,
+            ),
+            Command::SetEvent { event, stages } => {
+                cmd_buffer.set_event(get(&self.events, event)?, stages)
+            }
+            Command::ResetEvent { event, stages } => {
+                cmd_buffer.reset_event(get(&self.events, event)?, stages)
+            }
+            Command::WaitEvents {
+                ref events,
+                ref stages,
+                ref barriers,
+            } => cmd_buffer.wait_events(
+                get_all(&self.events, events)?,
+                stages.clone(),
+                self.barriers(barriers)?,
+            ),
+            Command::BeginQuery { pool, id, flags } => cmd_buffer.begin_query(
+                query::Query {
+                    pool: get(&self.query_pools, pool)?,
+                    id,
+                },
+                flags,
+            ),
+            Command::EndQuery { pool, id } => cmd_buffer.end_query(query::Query {
+                pool: get(&self.query_pools, pool)?,
+                id,
+            }),
+            Command::ResetQueryPool { pool, ref queries } => {
+                cmd_buffer.reset_query_pool(get(&self.query_pools, pool)?, queries.clone())
+            }
+            Command::CopyQueryPoolResults {
+                pool,
+                ref queries,
+                buffer,
+                offset,
+                stride,
+                flags,
+            } => cmd_buffer.copy_query_pool_results(
+                get(&self.query_pools, pool)?,
+                queries.clone(),
+                get(&self.buffers, buffer)?,
+                offset,
+                stride,
+                flags,
+            ),
+            Command::WriteTimestamp { stage, pool, id } => cmd_buffer.write_timestamp(
+                stage,
+                query::Query {
+                    pool: get(&self.query_pools, pool)?,
+                    id,
+                },
+            ),
+            Command::PushGraphicsConstants {
+                layout,
+                stages,
+                offset,
+                ref constants,
+            } => cmd_buffer.push_graphics_constants(
+                get(&self.pipeline_layouts, layout)?,
+                stages,
+                offset,
+                constants,
+            ),
+            Command::PushComputeConstants {
+                layout,
+                offset,
+                ref constants,
+            } => cmd_buffer.push_compute_constants(
+                get(&self.pipeline_layouts, layout)?,
+                offset,
+                constants,
+            ),
+            Command::ExecuteCommands(ref ids) => {
+                cmd_buffer.execute_commands(get_all(&self.command_buffers, ids)?)
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Device replaying a trace.
+#[derive(Debug)]
+pub struct Replayer<B: hal::Backend> {
+    dir: PathBuf,
+    actions: Vec<Action>,
+    position: usize,
+    physical_device: B::PhysicalDevice,
+    device: B::Device,
+    queue_groups: Vec<QueueGroup<B>>,
+    /// Replaying queue family of each recorded one.
+    families: HashMap<QueueFamilyId, QueueFamilyId>,
+    /// Replaying memory type of each recorded one.
+    memory_types: Vec<MemoryTypeId>,
+    objects: Objects<B>,
+    /// Mapped memory, with the start of the mapped range.
+    mappings: HashMap<Id, (*mut u8, u64)>,
+}
+
+// The mapped pointers are only used by the replayer itself.
+unsafe impl<B: hal::Backend> Send for Replayer<B> {}
+
+impl<B: hal::Backend> Replayer<B> {
+    /// Load the trace of a directory and open a device for it on `adapter`.
+    ///
+    /// # Safety
+    ///
+    /// Same requirements as opening the physical device of the adapter.
+    pub unsafe fn new<P: AsRef<Path>>(adapter: Adapter<B>, dir: P) -> Result<Self, ReplayError> {
+        let actions = load(&dir)?;
+        let (families, features, recorded_types) = match actions.first() {
+            Some(Action::Init {
+                adapter: ref name,
+                ref families,
+                features,
+                ref memory_types,
+            }) => {
+                info!("Replaying a trace recorded on {}", name);
+                (families.clone(), *features, memory_types.clone())
+            }
+            _ => {
+                return Err(ReplayError::Format(
+                    "the trace doesn't start with Init".into(),
+                ))
+            }
+        };
+
+        let Adapter {
+            physical_device,
+            queue_families,
+            ..
+        } = adapter;
+
+        // open every family able to run the recorded queues
+        let mut family_map = HashMap::new();
+        let mut open = Vec::<(usize, usize)>::new();
+        for family in &families {
+            let index = queue_families
+                .iter()
+                .position(|f| f.queue_type() == family.ty)
+                .or_else(|| {
+                    queue_families
+                        .iter()
+                        .position(|f| supports(f.queue_type(), family.ty))
+                })
+                .ok_or_else(|| {
+                    ReplayError::Device(format!("no queue family supports {:?}", family.ty))
+                })?;
+            let count = family.count.min(queue_families[index].max_queues()).max(1);
+            family_map.insert(family.id, queue_families[index].id());
+            match open.iter_mut().find(|&&mut (i, _)| i == index) {
+                Some(entry) => entry.1 = entry.1.max(count),
+                None => open.push((index, count)),
+            }
+        }
+        let priorities = open
+            .iter()
+            .map(|&(_, count)| vec![1.0; count])
+            .collect::<Vec<_>>();
+        let requests = open
+            .iter()
+            .zip(&priorities)
+            .map(|(&(index, _), priorities)| (&queue_families[index], &priorities[..]))
+            .collect::<Vec<_>>();
+
+        let available = physical_device.features();
+        if !available.contains(features) {
+            warn!("Missing features {:?}", features - available);
+        }
+        let gpu = physical_device
+            .open(&requests, features & available)
+            .map_err(device_error)?;
+
+        let types = physical_device.memory_properties().memory_types;
+        let memory_types = recorded_types
+            .iter()
+            .map(|&properties| MemoryTypeId(match_memory_type(&types, properties)))
+            .collect();
+
+        Ok(Replayer {
+            dir: dir.as_ref().to_path_buf(),
+            actions,
+            position: 1,
+            physical_device,
+            device: gpu.device,
+            queue_groups: gpu.queue_groups,
+            families: family_map,
+            memory_types,
+            objects: Objects::default(),
+            mappings: HashMap::new(),
+        })
+    }
+
+    /// Get the replaying device.
+    pub fn device(&self) -> &B::Device {
+        &self.device
+    }
+
+    /// Get a memory object of the trace.
+    pub fn memory(&self, id: Id) -> Option<&B::Memory> {
+        self.objects.memories.get(&id)
+    }
+
+    /// Get a buffer of the trace.
+    pub fn buffer(&self, id: Id) -> Option<&B::Buffer> {
+        self.objects.buffers.get(&id)
+    }
+
+    /// Get an image of the trace.
+    pub fn image(&self, id: Id) -> Option<&B::Image> {
+        self.objects.images.get(&id)
+    }
+
+    /// Play the next action, returning `false` once all of them are played.
+    ///
+    /// # Safety
+    ///
+    /// The trace has to be a valid usage of the API, as it is forwarded to
+    /// the device as is.
+    pub unsafe fn step(&mut self) -> Result<bool, ReplayError> {
+        let action = match self.actions.get(self.position) {
+            Some(action) => action.clone(),
+            None => return Ok(false),
+        };
+        self.position += 1;
+        self.play(&action)?;
+        Ok(true)
+    }
+
+    /// Play all the remaining actions and wait for the device to finish.
+    ///
+    /// # Safety
+    ///
+    /// See `step`.
+    pub unsafe fn run(&mut self) -> Result<(), ReplayError> {
+        while self.step()? {}
+        self.device.wait_idle().map_err(device_error)
+    }
+
+    /// Locate the replaying queue of a recorded one, as indices of the group
+    /// and of the queue within it.
+    fn queue_index(&self, (family, index): QueueId) -> Result<(usize, usize), ReplayError> {
+        let replay_family = self.families.get(&family).cloned();
+        let group = self
+            .queue_groups
+            .iter()
+            .position(|group| Some(group.family) == replay_family)
+            .ok_or_else(|| {
+                ReplayError::Format(format!("queue family {:?} is not opened", family))
+            })?;
+        Ok((group, index % self.queue_groups[group].queues.len()))
+    }
+
+    /// Wait for and signal semaphores and a fence, without doing any work.
+    ///
+    /// Stands for the synchronization of the presentation engine.
+    unsafe fn signal(
+        &mut self,
+        queue: Option<QueueId>,
+        wait: &[Id],
+        signal: &[Id],
+        fence: Option<Id>,
+    ) -> Result<(), ReplayError> {
+        if wait.is_empty() && signal.is_empty() && fence.is_none() {
+            return Ok(());
+        }
+        let (group, index) = match queue {
+            Some(queue) => self.queue_index(queue)?,
+            None => (0, 0),
+        };
+        let queue = &mut self.queue_groups[group].queues[index];
+        let semaphores = &self.objects.semaphores;
+        let fence = match fence {
+            Some(id) => Some(get(&self.objects.fences, id)?),
+            None => None,
+        };
+        queue.submit(
+            hal::queue::Submission {
+                command_buffers: iter::empty::<&B::CommandBuffer>(),
+                wait_semaphores: get_all(semaphores, wait)?
+                    .into_iter()
+                    .map(|semaphore| (semaphore, pso::PipelineStage::BOTTOM_OF_PIPE)),
+                signal_semaphores: get_all(semaphores, signal)?,
+            },
+            fence,
+        );
+        Ok(())
+    }
+
+    unsafe fn create_offscreen(
+        &self,
+        config: &window::SwapchainConfig,
+    ) -> Result<(B::Image, B::Memory), ReplayError> {
+        let kind = image::Kind::D2(config.extent.width, config.extent.height, 1, 1);
+        let mut image = self
+            .device
+            .create_image(
+                kind,
+                1,
+                config.format,
+                image::Tiling::Optimal,
+                config.image_usage,
+                image::ViewCapabilities::empty(),
+            )
+            .map_err(device_error)?;
+        let requirements = self.device.get_image_requirements(&image);
+        let types = self.physical_device.memory_properties().memory_types;
+        let type_id = (0 .. types.len())
+            .filter(|&i| requirements.type_mask & (1 << i) != 0)
+            .min_by_key(|&i| {
+                !types[i]
+                    .properties
+                    .contains(memory::Properties::DEVICE_LOCAL)
+            })
+            .unwrap_or(0);
+        let memory = self
+            .device
+            .allocate_memory(MemoryTypeId(type_id), requirements.size)
+            .map_err(device_error)?;
+        self.device
+            .bind_image_memory(&memory, 0, &mut image)
+            .map_err(device_error)?;
+        Ok((image, memory))
+    }
+
+    unsafe fn destroy_offscreen(&mut self, surface: Id) {
+        if let Some(offscreen) = self.objects.surfaces.remove(&surface) {
+            let _ = self.device.wait_idle();
+            self.device.destroy_image(offscreen.image);
+            self.device.free_memory(offscreen.memory);
+        }
+    }
+
+    fn set_name(&mut self, id: Id, name: &str) {
+        let device = &self.device;
+        let objects = &mut self.objects;
+        unsafe {
+            if let Some(image) = objects.images.get_mut(&id) {
+                device.set_image_name(image, name)
+            } else if let Some(buffer) = objects.buffers.get_mut(&id) {
+                device.set_buffer_name(buffer, name)
+            } else if let Some(cmd_buffer) = objects.command_buffers.get_mut(&id) {
+                device.set_command_buffer_name(cmd_buffer, name)
+            } else if let Some(semaphore) = objects.semaphores.get_mut(&id) {
+                device.set_semaphore_name(semaphore, name)
+            } else if let Some(fence) = objects.fences.get_mut(&id) {
+                device.set_fence_name(fence, name)
+            } else if let Some(framebuffer) = objects.framebuffers.get_mut(&id) {
+                device.set_framebuffer_name(framebuffer, name)
+            } else if let Some(render_pass) = objects.render_passes.get_mut(&id) {
+                device.set_render_pass_name(render_pass, name)
+            } else if let Some(set) = objects.descriptor_sets.get_mut(&id) {
+                device.set_descriptor_set_name(set, name)
+            } else if let Some(layout) = objects.descriptor_set_layouts.get_mut(&id) {
+                device.set_descriptor_set_layout_name(layout, name)
+            } else {
+                warn!("Object {} named {} doesn't exist", id, name);
+            }
+        }
+    }
+
+    unsafe fn play(&mut self, action: &Action) -> Result<(), ReplayError> {
+        let device = &self.device;
+        let objects = &mut self.objects;
+        match *action {
+            Action::Init { .. } => {
+                return Err(ReplayError::Format("the device is opened twice".into()));
+            }
+            Action::AllocateMemory {
+                id,
+                memory_type,
+                size,
+                ..
+            } => {
+                let type_id = self
+                    .memory_types
+                    .get(memory_type.0)
+                    .cloned()
+                    .unwrap_or(memory_type);
+                let memory = device
+                    .allocate_memory(type_id, size)
+                    .map_err(device_error)?;
+                objects.memories.insert(id, memory);
+            }
+            Action::FreeMemory(id) => {
+                self.mappings.remove(&id);
+                device.free_memory(take(&mut objects.memories, id)?);
+            }
+            Action::CreateCommandPool { id, family, flags } => {
+                let family = self.families.get(&family).cloned().unwrap_or(family);
+                let pool = device
+                    .create_command_pool(family, flags)
+                    .map_err(device_error)?;
+                objects.command_pools.insert(id, pool);
+            }
+            Action::DestroyCommandPool(id) => {
+                device.destroy_command_pool(take(&mut objects.command_pools, id)?)
+            }
+            Action::ResetCommandPool {
+                pool,
+                release_resources,
+            } => get_mut(&mut objects.command_pools, pool)?.reset(release_resources),
+            Action::AllocateCommandBuffers {
+                pool,
+                level,
+                ref ids,
+            } => {
+                let cmd_buffers =
+                    get_mut(&mut objects.command_pools, pool)?.allocate_vec(ids.len(), level);
+                objects
+                    .command_buffers
+                    .extend(ids.iter().cloned().zip(cmd_buffers));
+            }
+            Action::FreeCommandBuffers { pool, ref ids } => {
+                let cmd_buffers = ids
+                    .iter()
+                    .map(|&id| take(&mut objects.command_buffers, id))
+                    .collect::<Result<Vec<_>, _>>()?;
+                get_mut(&mut objects.command_pools, pool)?.free(cmd_buffers);
+            }
+            Action::Record {
+                command_buffer,
+                flags,
+                ref inheritance,
+                ref commands,
+            } => {
+                let mut cmd_buffer = take(&mut objects.command_buffers, command_buffer)?;
+                let result = objects.record(&mut cmd_buffer, flags, inheritance, commands);
+                objects.command_buffers.insert(command_buffer, cmd_buffer);
+                result?;
+            }
+            Action::ResetCommandBuffer {
+                command_buffer,
+                release_resources,
+            } => get_mut(&mut objects.command_buffers, command_buffer)?.reset(release_resources),
+            Action::CreateRenderPass {
+                id,
+                ref attachments,
+                ref subpasses,
+                ref dependencies,
+            } => {
+                let render_pass = device
+                    .create_render_pass(
+                        attachments,
+                        subpasses.iter().map(|subpass| pass::SubpassDesc {
+                            colors: &subpass.colors,
+                            depth_stencil: subpass.depth_stencil.as_ref(),
+                            inputs: &subpass.inputs,
+                            resolves: &subpass.resolves,
+                            preserves: &subpass.preserves,
+                        }),
+                        dependencies,
+                    )
+                    .map_err(device_error)?;
+                objects.render_passes.insert(id, render_pass);
+            }
+            Action::DestroyRenderPass(id) => {
+                device.destroy_render_pass(take(&mut objects.render_passes, id)?)
+            }
+            Action::CreatePipelineLayout {
+                id,
+                ref set_layouts,
+                ref push_constants,
+            } => {
+                let layout = device
+                    .create_pipeline_layout(
+                        get_all(&objects.descriptor_set_layouts, set_layouts)?,
+                        push_constants,
+                    )
+                    .map_err(device_error)?;
+                objects.pipeline_layouts.insert(id, layout);
+            }
+            Action::DestroyPipelineLayout(id) => {
+                device.destroy_pipeline_layout(take(&mut objects.pipeline_layouts, id)?)
+            }
+            Action::CreatePipelineCache { id, ref data } => {
+                let data = match *data {
+                    Some(ref file) => Some(fs::read(self.dir.join(file))?),
+                    None => None,
+                };
+                // caches of another device are dropped
+                let physical_device = &self.physical_device;
+                let data = data.filter(|data| physical_device.is_valid_cache(data));
+                let cache = device
+                    .create_pipeline_cache(data.as_ref().map(|data| &data[..]))
+                    .map_err(device_error)?;
+                objects.pipeline_caches.insert(id, cache);
+            }
+            Action::MergePipelineCaches {
+                target,
+                ref sources,
+            } => device
+                .merge_pipeline_caches(
+                    get(&objects.pipeline_caches, target)?,
+                    get_all(&objects.pipeline_caches, sources)?,
+                )
+                .map_err(device_error)?,
+            Action::DestroyPipelineCache(id) => {
+                device.destroy_pipeline_cache(take(&mut objects.pipeline_caches, id)?)
+            }
+            Action::CreateGraphicsPipeline {
+                id,
+                ref desc,
+                cache,
+            } => {
+                let raw_desc = pso::GraphicsPipelineDesc {
+                    shaders: pso::GraphicsShaderSet {
+                        vertex: objects.entry_point(&desc.vertex)?,
+                        hull: objects.optional_entry_point(&desc.hull)?,
+                        domain: objects.optional_entry_point(&desc.domain)?,
+                        geometry: objects.optional_entry_point(&desc.geometry)?,
+                        fragment: objects.optional_entry_point(&desc.fragment)?,
+                    },
+                    rasterizer: desc.rasterizer,
+                    vertex_buffers: desc.vertex_buffers.clone(),
+                    attributes: desc.attributes.clone(),
+                    input_assembler: desc.input_assembler.clone(),
+                    blender: desc.blender.clone(),
+                    depth_stencil: desc.depth_stencil,
+                    multisampling: desc.multisampling.clone(),
+                    baked_states: desc.baked_states.clone(),
+                    layout: get(&objects.pipeline_layouts, desc.layout)?,
+                    subpass: pass::Subpass {
+                        index: desc.subpass.0,
+                        main_pass: get(&objects.render_passes, desc.subpass.1)?,
+                    },
+                    flags: desc.flags,
+                    parent: Objects::<B>::base_pipeline(&objects.graphics_pipelines, &desc.parent)?,
+                };
+                let cache = match cache {
+                    Some(id) => Some(get(&objects.pipeline_caches, id)?),
+                    None => None,
+                };
+                let pipeline = device
+                    .create_graphics_pipeline(&raw_desc, cache)
+                    .map_err(device_error)?;
+                objects.graphics_pipelines.insert(id, pipeline);
+            }
+            Action::DestroyGraphicsPipeline(id) => {
+                device.destroy_graphics_pipeline(take(&mut objects.graphics_pipelines, id)?)
+            }
+            Action::CreateComputePipeline {
+                id,
+                ref desc,
+                cache,
+            } => {
+                let raw_desc = pso::ComputePipelineDesc {
+                    shader: objects.entry_point(&desc.shader)?,
+                    layout: get(&objects.pipeline_layouts, desc.layout)?,
+                    flags: desc.flags,
+                    parent: Objects::<B>::base_pipeline(&objects.compute_pipelines, &desc.parent)?,
+                };
+                let cache = match cache {
+                    Some(id) => Some(get(&objects.pipeline_caches, id)?),
+                    None => None,
+                };
+                let pipeline = device
+                    .create_compute_pipeline(&raw_desc, cache)
+                    .map_err(device_error)?;
+                objects.compute_pipelines.insert(id, pipeline);
+            }
+            Action::DestroyComputePipeline(id) => {
+                device.destroy_compute_pipeline(take(&mut objects.compute_pipelines, id)?)
+            }
+            Action::CreateFramebuffer {
+                id,
+                render_pass,
+                ref attachments,
+                extent,
+            } => {
+                let framebuffer = device
+                    .create_framebuffer(
+                        get(&objects.render_passes, render_pass)?,
+                        get_all(&objects.image_views, attachments)?,
+                        extent,
+                    )
+                    .map_err(device_error)?;
+                objects.framebuffers.insert(id, framebuffer);
+            }
+            Action::DestroyFramebuffer(id) => {
+                device.destroy_framebuffer(take(&mut objects.framebuffers, id)?)
+            }
+            Action::CreateShaderModule { id, ref data } => {
+                let bytes = fs::read(self.dir.join(data))?;
+                if bytes.len() % 4 != 0 {
+                    return Err(ReplayError::Format(format!("{} is not SPIR-V", data)));
+                }
+                let words = bytes
+                    .chunks(4)
+                    .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+                    .collect::<Vec<_>>();
+                let module = device.create_shader_module(&words).map_err(device_error)?;
+                objects.shader_modules.insert(id, module);
+            }
+            Action::DestroyShaderModule(id) => {
+                device.destroy_shader_module(take(&mut objects.shader_modules, id)?)
+            }
+            Action::CreateBuffer { id, size, usage } => {
+                let buffer = device.create_buffer(size, usage).map_err(device_error)?;
+                objects.buffers.insert(id, buffer);
+            }
+            Action::BindBufferMemory {
+                memory,
+                offset,
+                buffer,
+            } => device
+                .bind_buffer_memory(
+                    get(&objects.memories, memory)?,
+                    offset,
+                    get_mut(&mut objects.buffers, buffer)?,
+                )
+                .map_err(device_error)?,
+            Action::DestroyBuffer(id) => device.destroy_buffer(take(&mut objects.buffers, id)?),
+            Action::CreateBufferView {
+                id,
+                buffer,
+                format,
+                range,
+            } => {
+                let view = device
+                    .create_buffer_view(get(&objects.buffers, buffer)?, format, range)
+                    .map_err(device_error)?;
+                objects.buffer_views.insert(id, view);
+            }
+            Action::DestroyBufferView(id) => {
+                device.destroy_buffer_view(take(&mut objects.buffer_views, id)?)
+            }
+            Action::CreateImage {
+                id,
+                kind,
+                mip_levels,
+                format,
+                tiling,
+                usage,
+                view_caps,
+            } => {
+                let image = device
+                    .create_image(kind, mip_levels, format, tiling, usage, view_caps)
+                    .map_err(device_error)?;
+                objects.images.insert(id, image);
+            }
+            Action::BindImageMemory {
+                memory,
+                offset,
+                image,
+            } => device
+                .bind_image_memory(
+                    get(&objects.memories, memory)?,
+                    offset,
+                    get_mut(&mut objects.images, image)?,
+                )
+                .map_err(device_error)?,
+            Action::DestroyImage(id) => device.destroy_image(take(&mut objects.images, id)?),
+            Action::CreateImageView {
+                id,
+                image,
+                view_kind,
+                format,
+                swizzle,
+                ref range,
+            } => {
+                let view = device
+                    .create_image_view(
+                        get(&objects.images, image)?,
+                        view_kind,
+                        format,
+                        swizzle,
+                        range.clone(),
+                    )
+                    .map_err(device_error)?;
+                objects.image_views.insert(id, view);
+            }
+            Action::DestroyImageView(id) => {
+                device.destroy_image_view(take(&mut objects.image_views, id)?)
+            }
+            Action::CreateSampler { id, ref info } => {
+                let sampler = device.create_sampler(info.clone()).map_err(device_error)?;
+                objects.samplers.insert(id, sampler);
+            }
+            Action::DestroySampler(id) => device.destroy_sampler(take(&mut objects.samplers, id)?),
+            Action::CreateDescriptorPool {
+                id,
+                max_sets,
+                ref ranges,
+                flags,
+            } => {
+                let pool = device
+                    .create_descriptor_pool(max_sets, ranges, flags)
+                    .map_err(device_error)?;
+                objects.descriptor_pools.insert(id, pool);
+            }
+            Action::DestroyDescriptorPool(id) => {
+                device.destroy_descriptor_pool(take(&mut objects.descriptor_pools, id)?)
+            }
+            Action::AllocateDescriptorSets {
+                pool,
+                ref layouts,
+                ref ids,
+            } => {
+                let mut sets = Vec::with_capacity(ids.len());
+                get_mut(&mut objects.descriptor_pools, pool)?
+                    .allocate_sets(
+                        get_all(&objects.descriptor_set_layouts, layouts)?,
+                        &mut sets,
+                    )
+                    .map_err(device_error)?;
+                objects
+                    .descriptor_sets
+                    .extend(ids.iter().cloned().zip(sets));
+            }
+            Action::FreeDescriptorSets { pool, ref ids } => {
+                let sets = ids
+                    .iter()
+                    .map(|&id| take(&mut objects.descriptor_sets, id))
+                    .collect::<Result<Vec<_>, _>>()?;
+                get_mut(&mut objects.descriptor_pools, pool)?.free_sets(sets);
+            }
+            Action::ResetDescriptorPool(id) => {
+                get_mut(&mut objects.descriptor_pools, id)?.reset();
+            }
+            Action::CreateDescriptorSetLayout {
+                id,
+                ref bindings,
+                ref immutable_samplers,
+            } => {
+                let layout = device
+                    .create_descriptor_set_layout(
+                        bindings,
+                        get_all(&objects.samplers, immutable_samplers)?,
+                    )
+                    .map_err(device_error)?;
+                objects.descriptor_set_layouts.insert(id, layout);
+            }
+            Action::DestroyDescriptorSetLayout(id) => {
+                device.destroy_descriptor_set_layout(take(&mut objects.descriptor_set_layouts, id)?)
+            }
+            Action::WriteDescriptorSets(ref writes) => {
+                let writes = writes
+                    .iter()
+                    .map(|write| {
+                        Ok(pso::DescriptorSetWrite {
+                            set: get(&objects.descriptor_sets, write.set)?,
+                            binding: write.binding,
+                            array_offset: write.array_offset,
+                            descriptors: write
+                                .descriptors
+                                .iter()
+                                .map(|descriptor| objects.descriptor(descriptor))
+                                .collect::<Result<Vec<_>, _>>()?,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, ReplayError>>()?;
+                device.write_descriptor_sets(writes);
+            }
+            Action::CopyDescriptorSets(ref copies) => {
+                let copies = copies
+                    .iter()
+                    .map(|copy| {
+                        Ok(pso::DescriptorSetCopy {
+                            src_set: get(&objects.descriptor_sets, copy.src_set)?,
+                            src_binding: copy.src_binding,
+                            src_array_offset: copy.src_array_offset,
+                            dst_set: get(&objects.descriptor_sets, copy.dst_set)?,
+                            dst_binding: copy.dst_binding,
+                            dst_array_offset: copy.dst_array_offset,
+                            count: copy.count,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, ReplayError>>()?;
+                device.copy_descriptor_sets(copies);
+            }
+            Action::MapMemory { memory, range } => {
+                let ptr = device
+                    .map_memory(get(&objects.memories, memory)?, range)
+                    .map_err(device_error)?;
+                self.mappings.insert(memory, (ptr, range.0.unwrap_or(0)));
+            }
+            Action::WriteMemory {
+                memory,
+                offset,
+                ref data,
+            } => {
+                let &(ptr, start) = self.mappings.get(&memory).ok_or_else(|| {
+                    ReplayError::Format(format!("memory {} is written while not mapped", memory))
+                })?;
+                let bytes = fs::read(self.dir.join(data))?;
+                ptr::copy_nonoverlapping(
+                    bytes.as_ptr(),
+                    ptr.offset((offset - start) as isize),
+                    bytes.len(),
+                );
+            }
+            Action::FlushMappedMemoryRanges(ref ranges) => {
+                let ranges = ranges
+                    .iter()
+                    .map(|&(id, range)| Ok((get(&objects.memories, id)?, range)))
+                    .collect::<Result<Vec<_>, ReplayError>>()?;
+                device
+                    .flush_mapped_memory_ranges(ranges)
+                    .map_err(device_error)?;
+            }
+            Action::InvalidateMappedMemoryRanges(ref ranges) => {
+                let ranges = ranges
+                    .iter()
+                    .map(|&(id, range)| Ok((get(&objects.memories, id)?, range)))
+                    .collect::<Result<Vec<_>, ReplayError>>()?;
+                device
+                    .invalidate_mapped_memory_ranges(ranges)
+                    .map_err(device_error)?;
+            }
+            Action::UnmapMemory(id) => {
+                self.mappings.remove(&id);
+                device.unmap_memory(get(&objects.memories, id)?);
+            }
+            Action::CreateSemaphore(id) => {
+                let semaphore = device.create_semaphore().map_err(device_error)?;
+                objects.semaphores.insert(id, semaphore);
+            }
+            Action::DestroySemaphore(id) => {
+                device.destroy_semaphore(take(&mut objects.semaphores, id)?)
+            }
+            Action::CreateFence { id, signaled } => {
+                let fence = device.create_fence(signaled).map_err(device_error)?;
+                objects.fences.insert(id, fence);
+            }
+            Action::ResetFences(ref ids) => device
+                .reset_fences(get_all(&objects.fences, ids)?)
+                .map_err(device_error)?,
+            Action::WaitForFences {
+                ref fences,
+                ref wait,
+                timeout_ns,
+            } => {
+                device
+                    .wait_for_fences(get_all(&objects.fences, fences)?, wait.clone(), timeout_ns)
+                    .map_err(device_error)?;
+            }
+            Action::GetFenceStatus(id) => {
+                device
+                    .get_fence_status(get(&objects.fences, id)?)
+                    .map_err(device_error)?;
+            }
+            Action::DestroyFence(id) => device.destroy_fence(take(&mut objects.fences, id)?),
+            Action::CreateEvent(id) => {
+                let event = device.create_event().map_err(device_error)?;
+                objects.events.insert(id, event);
+            }
+            Action::DestroyEvent(id) => device.destroy_event(take(&mut objects.events, id)?),
+            Action::GetEventStatus(id) => {
+                device
+                    .get_event_status(get(&objects.events, id)?)
+                    .map_err(device_error)?;
+            }
+            Action::SetEvent(id) => device
+                .set_event(get(&objects.events, id)?)
+                .map_err(device_error)?,
+            Action::ResetEvent(id) => device
+                .reset_event(get(&objects.events, id)?)
+                .map_err(device_error)?,
+            Action::CreateQueryPool { id, ty, count } => {
+                let pool = device.create_query_pool(ty, count).map_err(device_error)?;
+                objects.query_pools.insert(id, pool);
+            }
+            Action::DestroyQueryPool(id) => {
+                device.destroy_query_pool(take(&mut objects.query_pools, id)?)
+            }
+            Action::GetQueryPoolResults {
+                pool,
+                ref queries,
+                size,
+                stride,
+                flags,
+            } => {
+                let mut data = vec![0; size];
+                device
+                    .get_query_pool_results(
+                        get(&objects.query_pools, pool)?,
+                        queries.clone(),
+                        &mut data,
+                        stride,
+                        flags,
+                    )
+                    .map_err(device_error)?;
+            }
+            Action::CreateSwapchain {
+                id,
+                ref config,
+                ref images,
+                ..
+            } => {
+                let mut swapchain_images = Vec::with_capacity(images.len());
+                for &image_id in images {
+                    let (image, memory) = self.create_offscreen(config)?;
+                    self.objects.images.insert(image_id, image);
+                    swapchain_images.push((image_id, memory));
+                }
+                self.objects.swapchains.insert(id, swapchain_images);
+            }
+            Action::DestroySwapchain(id) => {
+                let _ = device.wait_idle();
+                for (image_id, memory) in take(&mut objects.swapchains, id)? {
+                    if let Some(image) = objects.images.remove(&image_id) {
+                        device.destroy_image(image);
+                    }
+                    device.free_memory(memory);
+                }
+            }
+            Action::AcquireImage {
+                semaphore, fence, ..
+            } => {
+                let signal = semaphore.into_iter().collect::<Vec<_>>();
+                self.signal(None, &[], &signal, fence)?;
+            }
+            Action::ConfigureSwapchain {
+                surface,
+                ref config,
+            } => {
+                self.destroy_offscreen(surface);
+                let (image, memory) = self.create_offscreen(config)?;
+                self.objects.surfaces.insert(
+                    surface,
+                    Offscreen {
+                        config: config.clone(),
+                        image,
+                        memory,
+                    },
+                );
+            }
+            Action::UnconfigureSwapchain(surface) => self.destroy_offscreen(surface),
+            Action::AcquireSurfaceImage { surface, view } => {
+                let offscreen = get(&objects.surfaces, surface)?;
+                let image_view = device
+                    .create_image_view(
+                        &offscreen.image,
+                        image::ViewKind::D2,
+                        offscreen.config.format,
+                        format::Swizzle::NO,
+                        image::SubresourceRange {
+                            aspects: format::Aspects::COLOR,
+                            levels: 0 .. 1,
+                            layers: 0 .. 1,
+                        },
+                    )
+                    .map_err(device_error)?;
+                objects.image_views.insert(view, image_view);
+            }
+            Action::WaitIdle => device.wait_idle().map_err(device_error)?,
+            Action::SetName { id, ref name } => self.set_name(id, name),
+            Action::Submit {
+                queue,
+                ref command_buffers,
+                ref wait_semaphores,
+                ref signal_semaphores,
+                fence,
+            } => {
+                let (group, index) = self.queue_index(queue)?;
+                let queue = &mut self.queue_groups[group].queues[index];
+                let objects = &self.objects;
+                let wait_semaphores = wait_semaphores
+                    .iter()
+                    .map(|&(id, stage)| Ok((get(&objects.semaphores, id)?, stage)))
+                    .collect::<Result<Vec<_>, ReplayError>>()?;
+                let fence = match fence {
+                    Some(id) => Some(get(&objects.fences, id)?),
+                    None => None,
+                };
+                queue.submit(
+                    hal::queue::Submission {
+                        command_buffers: get_all(&objects.command_buffers, command_buffers)?,
+                        wait_semaphores,
+                        signal_semaphores: get_all(&objects.semaphores, signal_semaphores)?,
+                    },
+                    fence,
+                );
+            }
+            Action::Present {
+                queue,
+                ref wait_semaphores,
+                ..
+            } => self.signal(Some(queue), wait_semaphores, &[], None)?,
+            Action::PresentSurface {
+                queue,
+                view,
+                wait_semaphore,
+                ..
+            } => {
+                let wait = wait_semaphore.into_iter().collect::<Vec<_>>();
+                self.signal(Some(queue), &wait, &[], None)?;
+                let (group, index) = self.queue_index(queue)?;
+                self.queue_groups[group].queues[index]
+                    .wait_idle()
+                    .map_err(device_error)?;
+                let view = take(&mut self.objects.image_views, view)?;
+                self.device.destroy_image_view(view);
+            }
+            Action::QueueWaitIdle(queue) => {
+                let (group, index) = self.queue_index(queue)?;
+                self.queue_groups[group].queues[index]
+                    .wait_idle()
+                    .map_err(device_error)?
+            }
+        }
+        Ok(())
+    }
+}
+
+fn get_mut<T>(map: &mut HashMap<Id, T>, id: Id) -> Result<&mut T, ReplayError> {
+    map.get_mut(&id).ok_or(ReplayError::MissingObject(id))
+}
@@ -0,0 +1,634 @@
+//! Format of the traces.
+//!
+//! A trace is a directory with a `trace.ron` file, holding the list of
+//! actions in the order they were called, and binary files referenced by
+//! the actions, such as shader modules and the data written to mapped memory.
+//! Objects are referred to by identifiers that are unique within a trace.
+
+use hal::queue::{QueueFamilyId, QueueType};
+use hal::{buffer, command as com, format, image, memory, pass, pso, query, window};
+use hal::{DrawCount, IndexCount, IndexType, InstanceCount, VertexCount, VertexOffset};
+use hal::{Features, MemoryTypeId, WorkGroupCount};
+
+use std::ops::Range;
+
+/// Identifier of an object within a trace.
+pub type Id = usize;
+
+/// Name of a binary file of the trace directory.
+pub type DataFile = String;
+
+/// Queue, identified by its family and its index within the family.
+pub type QueueId = (QueueFamilyId, usize);
+
+/// Range of a buffer or memory object, where `None` means the start or the end.
+pub type RangeArg = (Option<u64>, Option<u64>);
+
+/// Queue family opened on the device.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Family {
+    pub id: QueueFamilyId,
+    pub ty: QueueType,
+    pub count: usize,
+}
+
+/// Clear value as raw bits, as it is a union in the API.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct ClearValue(pub [u32; 4]);
+
+impl From<com::ClearValue> for ClearValue {
+    fn from(value: com::ClearValue) -> Self {
+        ClearValue(unsafe { value.color.uint32 })
+    }
+}
+
+impl From<ClearValue> for com::ClearValue {
+    fn from(value: ClearValue) -> Self {
+        com::ClearValue {
+            color: com::ClearColor { uint32: value.0 },
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum AttachmentClear {
+    Color {
+        index: usize,
+        value: [u32; 4],
+    },
+    DepthStencil {
+        depth: Option<pso::DepthValue>,
+        stencil: Option<pso::StencilValue>,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SubpassDesc {
+    pub colors: Vec<pass::AttachmentRef>,
+    pub depth_stencil: Option<pass::AttachmentRef>,
+    pub inputs: Vec<pass::AttachmentRef>,
+    pub resolves: Vec<pass::AttachmentRef>,
+    pub preserves: Vec<pass::AttachmentId>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EntryPoint {
+    pub entry: String,
+    pub module: Id,
+    pub specialization: pso::Specialization<'static>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum BasePipeline {
+    Pipeline(Id),
+    Index(usize),
+    None,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GraphicsPipelineDesc {
+    pub vertex: EntryPoint,
+    pub hull: Option<EntryPoint>,
+    pub domain: Option<EntryPoint>,
+    pub geometry: Option<EntryPoint>,
+    pub fragment: Option<EntryPoint>,
+    pub rasterizer: pso::Rasterizer,
+    pub vertex_buffers: Vec<pso::VertexBufferDesc>,
+    pub attributes: Vec<pso::AttributeDesc>,
+    pub input_assembler: pso::InputAssemblerDesc,
+    pub blender: pso::BlendDesc,
+    pub depth_stencil: pso::DepthStencilDesc,
+    pub multisampling: Option<pso::Multisampling>,
+    pub baked_states: pso::BakedStates,
+    pub layout: Id,
+    pub subpass: (pass::SubpassId, Id),
+    pub flags: pso::PipelineCreationFlags,
+    pub parent: BasePipeline,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ComputePipelineDesc {
+    pub shader: EntryPoint,
+    pub layout: Id,
+    pub flags: pso::PipelineCreationFlags,
+    pub parent: BasePipeline,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Descriptor {
+    Sampler(Id),
+    Image(Id, image::Layout),
+    CombinedImageSampler(Id, image::Layout, Id),
+    Buffer(Id, Range<Option<buffer::Offset>>),
+    UniformTexelBuffer(Id),
+    StorageTexelBuffer(Id),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DescriptorSetWrite {
+    pub set: Id,
+    pub binding: pso::DescriptorBinding,
+    pub array_offset: pso::DescriptorArrayIndex,
+    pub descriptors: Vec<Descriptor>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DescriptorSetCopy {
+    pub src_set: Id,
+    pub src_binding: pso::DescriptorBinding,
+    pub src_array_offset: pso::DescriptorArrayIndex,
+    pub dst_set: Id,
+    pub dst_binding: pso::DescriptorBinding,
+    pub dst_array_offset: pso::DescriptorArrayIndex,
+    pub count: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Barrier {
+    AllBuffers(Range<buffer::Access>),
+    AllImages(Range<image::Access>),
+    Buffer {
+        states: Range<buffer::State>,
+        target: Id,
+        families: Option<Range<QueueFamilyId>>,
+        range: Range<Option<u64>>,
+    },
+    Image {
+        states: Range<image::State>,
+        target: Id,
+        families: Option<Range<QueueFamilyId>>,
+        range: image::SubresourceRange,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InheritanceInfo {
+    pub subpass: Option<(pass::SubpassId, Id)>,
+    pub framebuffer: Option<Id>,
+    pub occlusion_query_enable: bool,
+    pub occlusion_query_flags: query::ControlFlags,
+    pub pipeline_statistics: query::PipelineStatistic,
+}
+
+/// Command recorded into a command buffer.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Command {
+    PipelineBarrier {
+        stages: Range<pso::PipelineStage>,
+        dependencies: memory::Dependencies,
+        barriers: Vec<Barrier>,
+    },
+    FillBuffer {
+        buffer: Id,
+        range: RangeArg,
+        data: u32,
+    },
+    UpdateBuffer {
+        buffer: Id,
+        offset: buffer::Offset,
+        data: Vec<u8>,
+    },
+    ClearImage {
+        image: Id,
+        layout: image::Layout,
+        value: ClearValue,
+        ranges: Vec<image::SubresourceRange>,
+    },
+    ClearAttachments {
+        clears: Vec<AttachmentClear>,
+        rects: Vec<pso::ClearRect>,
+    },
+    ResolveImage {
+        src: Id,
+        src_layout: image::Layout,
+        dst: Id,
+        dst_layout: image::Layout,
+        regions: Vec<com::ImageResolve>,
+    },
+    BlitImage {
+        src: Id,
+        src_layout: image::Layout,
+        dst: Id,
+        dst_layout: image::Layout,
+        filter: image::Filter,
+        regions: Vec<com::ImageBlit>,
+    },
+    BindIndexBuffer {
+        buffer: Id,
+        offset: buffer::Offset,
+        index_type: IndexType,
+    },
+    BindVertexBuffers {
+        first_binding: pso::BufferIndex,
+        buffers: Vec<(Id, buffer::Offset)>,
+    },
+    SetViewports {
+        first_viewport: u32,
+        viewports: Vec<pso::Viewport>,
+    },
+    SetScissors {
+        first_scissor: u32,
+        rects: Vec<pso::Rect>,
+    },
+    SetStencilReference {
+        faces: pso::Face,
+        value: pso::StencilValue,
+    },
+    SetStencilReadMask {
+        faces: pso::Face,
+        value: pso::StencilValue,
+    },
+    SetStencilWriteMask {
+        faces: pso::Face,
+        value: pso::StencilValue,
+    },
+    SetBlendConstants(pso::ColorValue),
+    SetDepthBounds(Range<f32>),
+    SetLineWidth(f32),
+    SetDepthBias(pso::DepthBias),
+    BeginRenderPass {
+        render_pass: Id,
+        framebuffer: Id,
+        render_area: pso::Rect,
+        clear_values: Vec<ClearValue>,
+        first_subpass: com::SubpassContents,
+    },
+    NextSubpass(com::SubpassContents),
+    EndRenderPass,
+    BindGraphicsPipeline(Id),
+    BindGraphicsDescriptorSets {
+        layout: Id,
+        first_set: usize,
+        sets: Vec<Id>,
+        offsets: Vec<com::DescriptorSetOffset>,
+    },
+    BindComputePipeline(Id),
+    BindComputeDescriptorSets {
+        layout: Id,
+        first_set: usize,
+        sets: Vec<Id>,
+        offsets: Vec<com::DescriptorSetOffset>,
+    },
+    Dispatch(WorkGroupCount),
+    DispatchIndirect {
+        buffer: Id,
+        offset: buffer::Offset,
+    },
+    CopyBuffer {
+        src: Id,
+        dst: Id,
+        regions: Vec<com::BufferCopy>,
+    },
+    CopyImage {
+        src: Id,
+        src_layout: image::Layout,
+        dst: Id,
+        dst_layout: image::Layout,
+        regions: Vec<com::ImageCopy>,
+    },
+    CopyBufferToImage {
+        src: Id,
+        dst: Id,
+        dst_layout: image::Layout,
+        regions: Vec<com::BufferImageCopy>,
+    },
+    CopyImageToBuffer {
+        src: Id,
+        src_layout: image::Layout,
+        dst: Id,
+        regions: Vec<com::BufferImageCopy>,
+    },
+    Draw {
+        vertices: Range<VertexCount>,
+        instances: Range<InstanceCount>,
+    },
+    DrawIndexed {
+        indices: Range<IndexCount>,
+        base_vertex: VertexOffset,
+        instances: Range<InstanceCount>,
+    },
+    DrawIndirect {
+        buffer: Id,
+        offset: buffer::Offset,
+        draw_count: DrawCount,
+        stride: u32,
+    },
+    DrawIndexedIndirect {
+        buffer: Id,
+        offset: buffer::Offset,
+        draw_count: DrawCount,
+        stride: u32,
+    },
+    SetEvent {
+        event: Id,
+        stages: pso::PipelineStage,
+    },
+    ResetEvent {
+        event: Id,
+        stages: pso::PipelineStage,
+    },
+    WaitEvents {
+        events: Vec<Id>,
+        stages: Range<pso::PipelineStage>,
+        barriers: Vec<Barrier>,
+    },
+    BeginQuery {
+        pool: Id,
+        id: query::Id,
+        flags: query::ControlFlags,
+    },
+    EndQuery {
+        pool: Id,
+        id: query::Id,
+    },
+    ResetQueryPool {
+        pool: Id,
+        queries: Range<query::Id>,
+    },
+    CopyQueryPoolResults {
+        pool: Id,
+        queries: Range<query::Id>,
+        buffer: Id,
+        offset: buffer::Offset,
+        stride: buffer::Offset,
+        flags: query::ResultFlags,
+    },
+    WriteTimestamp {
+        stage: pso::PipelineStage,
+        pool: Id,
+        id: query::Id,
+    },
+    PushGraphicsConstants {
+        layout: Id,
+        stages: pso::ShaderStageFlags,
+        offset: u32,
+        constants: Vec<u32>,
+    },
+    PushComputeConstants {
+        layout: Id,
+        offset: u32,
+        constants: Vec<u32>,
+    },
+    ExecuteCommands(Vec<Id>),
+}
+
+/// Call made to the API, in the order of the trace.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Action {
+    /// Device opening, always the first action.
+    Init {
+        adapter: String,
+        families: Vec<Family>,
+        features: Features,
+        memory_types: Vec<memory::Properties>,
+    },
+    AllocateMemory {
+        id: Id,
+        memory_type: MemoryTypeId,
+        properties: memory::Properties,
+        size: u64,
+    },
+    FreeMemory(Id),
+    CreateCommandPool {
+        id: Id,
+        family: QueueFamilyId,
+        flags: hal::pool::CommandPoolCreateFlags,
+    },
+    DestroyCommandPool(Id),
+    ResetCommandPool {
+        pool: Id,
+        release_resources: bool,
+    },
+    AllocateCommandBuffers {
+        pool: Id,
+        level: com::Level,
+        ids: Vec<Id>,
+    },
+    FreeCommandBuffers {
+        pool: Id,
+        ids: Vec<Id>,
+    },
+    /// Complete recording of a command buffer, from `begin` to `finish`.
+    Record {
+        command_buffer: Id,
+        flags: com::CommandBufferFlags,
+        inheritance: InheritanceInfo,
+        commands: Vec<Command>,
+    },
+    ResetCommandBuffer {
+        command_buffer: Id,
+        release_resources: bool,
+    },
+    CreateRenderPass {
+        id: Id,
+        attachments: Vec<pass::Attachment>,
+        subpasses: Vec<SubpassDesc>,
+        dependencies: Vec<pass::SubpassDependency>,
+    },
+    DestroyRenderPass(Id),
+    CreatePipelineLayout {
+        id: Id,
+        set_layouts: Vec<Id>,
+        push_constants: Vec<(pso::ShaderStageFlags, Range<u32>)>,
+    },
+    DestroyPipelineLayout(Id),
+    CreatePipelineCache {
+        id: Id,
+        data: Option<DataFile>,
+    },
+    MergePipelineCaches {
+        target: Id,
+        sources: Vec<Id>,
+    },
+    DestroyPipelineCache(Id),
+    CreateGraphicsPipeline {
+        id: Id,
+        desc: Box<GraphicsPipelineDesc>,
+        cache: Option<Id>,
+    },
+    DestroyGraphicsPipeline(Id),
+    CreateComputePipeline {
+        id: Id,
+        desc: ComputePipelineDesc,
+        cache: Option<Id>,
+    },
+    DestroyComputePipeline(Id),
+    CreateFramebuffer {
+        id: Id,
+        render_pass: Id,
+        attachments: Vec<Id>,
+        extent: image::Extent,
+    },
+    DestroyFramebuffer(Id),
+    CreateShaderModule {
+        id: Id,
+        data: DataFile,
+    },
+    DestroyShaderModule(Id),
+    CreateBuffer {
+        id: Id,
+        size: u64,
+        usage: buffer::Usage,
+    },
+    BindBufferMemory {
+        memory: Id,
+        offset: u64,
+        buffer: Id,
+    },
+    DestroyBuffer(Id),
+    CreateBufferView {
+        id: Id,
+        buffer: Id,
+        format: Option<format::Format>,
+        range: RangeArg,
+    },
+    DestroyBufferView(Id),
+    CreateImage {
+        id: Id,
+        kind: image::Kind,
+        mip_levels: image::Level,
+        format: format::Format,
+        tiling: image::Tiling,
+        usage: image::Usage,
+        view_caps: image::ViewCapabilities,
+    },
+    BindImageMemory {
+        memory: Id,
+        offset: u64,
+        image: Id,
+    },
+    DestroyImage(Id),
+    CreateImageView {
+        id: Id,
+        image: Id,
+        view_kind: image::ViewKind,
+        format: format::Format,
+        swizzle: format::Swizzle,
+        range: image::SubresourceRange,
+    },
+    DestroyImageView(Id),
+    CreateSampler {
+        id: Id,
+        info: image::SamplerInfo,
+    },
+    DestroySampler(Id),
+    CreateDescriptorPool {
+        id: Id,
+        max_sets: usize,
+        ranges: Vec<pso::DescriptorRangeDesc>,
+        flags: pso::DescriptorPoolCreateFlags,
+    },
+    DestroyDescriptorPool(Id),
+    AllocateDescriptorSets {
+        pool: Id,
+        layouts: Vec<Id>,
+        ids: Vec<Id>,
+    },
+    FreeDescriptorSets {
+        pool: Id,
+        ids: Vec<Id>,
+    },
+    ResetDescriptorPool(Id),
+    CreateDescriptorSetLayout {
+        id: Id,
+        bindings: Vec<pso::DescriptorSetLayoutBinding>,
+        immutable_samplers: Vec<Id>,
+    },
+    DestroyDescriptorSetLayout(Id),
+    WriteDescriptorSets(Vec<DescriptorSetWrite>),
+    CopyDescriptorSets(Vec<DescriptorSetCopy>),
+    MapMemory {
+        memory: Id,
+        range: RangeArg,
+    },
+    /// Data written by the host into mapped memory, at an offset from the
+    /// start of the memory object.
+    WriteMemory {
+        memory: Id,
+        offset: u64,
+        data: DataFile,
+    },
+    FlushMappedMemoryRanges(Vec<(Id, RangeArg)>),
+    InvalidateMappedMemoryRanges(Vec<(Id, RangeArg)>),
+    UnmapMemory(Id),
+    CreateSemaphore(Id),
+    DestroySemaphore(Id),
+    CreateFence {
+        id: Id,
+        signaled: bool,
+    },
+    ResetFences(Vec<Id>),
+    WaitForFences {
+        fences: Vec<Id>,
+        wait: hal::device::WaitFor,
+        timeout_ns: u64,
+    },
+    GetFenceStatus(Id),
+    DestroyFence(Id),
+    CreateEvent(Id),
+    DestroyEvent(Id),
+    GetEventStatus(Id),
+    SetEvent(Id),
+    ResetEvent(Id),
+    CreateQueryPool {
+        id: Id,
+        ty: query::Type,
+        count: query::Id,
+    },
+    DestroyQueryPool(Id),
+    GetQueryPoolResults {
+        pool: Id,
+        queries: Range<query::Id>,
+        size: usize,
+        stride: buffer::Offset,
+        flags: query::ResultFlags,
+    },
+    CreateSwapchain {
+        id: Id,
+        surface: Id,
+        config: window::SwapchainConfig,
+        old_swapchain: Option<Id>,
+        images: Vec<Id>,
+    },
+    DestroySwapchain(Id),
+    AcquireImage {
+        swapchain: Id,
+        semaphore: Option<Id>,
+        fence: Option<Id>,
+    },
+    ConfigureSwapchain {
+        surface: Id,
+        config: window::SwapchainConfig,
+    },
+    UnconfigureSwapchain(Id),
+    /// Image acquired from a surface, with the identifier of its view.
+    AcquireSurfaceImage {
+        surface: Id,
+        view: Id,
+    },
+    WaitIdle,
+    /// Debug name given to any object.
+    SetName {
+        id: Id,
+        name: String,
+    },
+    Submit {
+        queue: QueueId,
+        command_buffers: Vec<Id>,
+        wait_semaphores: Vec<(Id, pso::PipelineStage)>,
+        signal_semaphores: Vec<Id>,
+        fence: Option<Id>,
+    },
+    Present {
+        queue: QueueId,
+        swapchains: Vec<(Id, window::SwapImageIndex)>,
+        wait_semaphores: Vec<Id>,
+    },
+    PresentSurface {
+        queue: QueueId,
+        surface: Id,
+        view: Id,
+        wait_semaphore: Option<Id>,
+    },
+    QueueWaitIdle(QueueId),
+}
@@ -0,0 +1,936 @@
+use crate::native::{describe, next_id, Buffer, Image};
+use crate::{Backend, Reporter};
+
+use hal::command::{self as com, Level};
+use hal::image::{Filter, Layout, SubresourceRange};
+use hal::memory::{Barrier, Dependencies};
+use hal::queue::QueueType;
+use hal::range::RangeArg;
+use hal::{buffer, image, pass, pool, pso, query};
+use hal::{DrawCount, IndexCount, InstanceCount, VertexCount, VertexOffset, WorkGroupCount};
+
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub struct CommandPool<B: hal::Backend> {
+    pub(crate) raw: B::CommandPool,
+    queue_type: QueueType,
+    reporter: Arc<Reporter>,
+}
+
+impl<B: hal::Backend> CommandPool<B> {
+    pub(crate) fn new(raw: B::CommandPool, queue_type: QueueType, reporter: Arc<Reporter>) -> Self {
+        CommandPool {
+            raw,
+            queue_type,
+            reporter,
+        }
+    }
+}
+
+impl<B: hal::Backend> pool::CommandPool<Backend<B>> for CommandPool<B> {
+    unsafe fn reset(&mut self, release_resources: bool) {
+        self.raw.reset(release_resources)
+    }
+
+    fn allocate_vec(&mut self, num: usize, level: Level) -> Vec<CommandBuffer<B>> {
+        self.raw
+            .allocate_vec(num, level)
+            .into_iter()
+            .map(|raw| CommandBuffer {
+                raw,
+                id: next_id(),
+                name: None,
+                level,
+                queue_type: self.queue_type,
+                reporter: Arc::clone(&self.reporter),
+                state: State::Initial,
+                in_render_pass: false,
+                layouts: HashMap::new(),
+            })
+            .collect()
+    }
+
+    unsafe fn free<I>(&mut self, buffers: I)
+    where
+        I: IntoIterator<Item = CommandBuffer<B>>,
+    {
+        self.raw
+            .free(buffers.into_iter().map(|cmd_buffer| cmd_buffer.raw))
+    }
+}
+
+/// Lifecycle of a command buffer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum State {
+    Initial,
+    Recording,
+    Executable,
+}
+
+/// Command buffer, tracking the recording state and the image layouts.
+///
+/// Layouts are only known after a barrier transitioned the image in the same
+/// command buffer, and are tracked for whole images.
+#[derive(Debug)]
+pub struct CommandBuffer<B: hal::Backend> {
+    pub(crate) raw: B::CommandBuffer,
+    id: usize,
+    pub(crate) name: Option<String>,
+    level: Level,
+    queue_type: QueueType,
+    reporter: Arc<Reporter>,
+    state: State,
+    in_render_pass: bool,
+    layouts: HashMap<usize, Layout>,
+}
+
+/// Where a command may be recorded with regard to render passes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Scope {
+    Inside,
+    Outside,
+    Both,
+}
+
+impl<B: hal::Backend> CommandBuffer<B> {
+    fn describe(&self) -> String {
+        describe("command buffer", self.id, &self.name)
+    }
+
+    fn report(&self, message: String) {
+        self.reporter
+            .report(format!("{}: {}", self.describe(), message));
+    }
+
+    /// Check that a command can be recorded in the current state.
+    fn command(&self, name: &str, scope: Scope) {
+        if self.state != State::Recording {
+            self.report(format!("{} is recorded while not recording", name));
+        }
+        match scope {
+            Scope::Inside if !self.in_render_pass => {
+                self.report(format!("{} is recorded outside of a render pass", name))
+            }
+            Scope::Outside if self.in_render_pass => {
+                self.report(format!("{} is recorded inside of a render pass", name))
+            }
+            _ => {}
+        }
+    }
+
+    fn require_graphics(&self, name: &str) {
+        if !self.queue_type.supports_graphics() {
+            self.report(format!(
+                "{} is recorded for a {:?} queue",
+                name, self.queue_type
+            ));
+        }
+    }
+
+    fn require_compute(&self, name: &str) {
+        if !self.queue_type.supports_compute() {
+            self.report(format!(
+                "{} is recorded for a {:?} queue",
+                name, self.queue_type
+            ));
+        }
+    }
+
+    fn check_buffer(&self, name: &str, buffer: &Buffer<B>, usage: buffer::Usage) {
+        if !buffer.usage.contains(usage) {
+            self.report(format!(
+                "{} uses {} without {:?} usage",
+                name,
+                buffer.describe(),
+                usage
+            ));
+        }
+        if !buffer.bound {
+            self.report(format!(
+                "{} uses {} before it is bound to memory",
+                name,
+                buffer.describe()
+            ));
+        }
+    }
+
+    fn check_buffer_range(&self, name: &str, buffer: &Buffer<B>, offset: u64, size: u64) {
+        if offset + size > buffer.size {
+            self.report(format!(
+                "{} accesses {} .. {} of {} of size {}",
+                name,
+                offset,
+                offset + size,
+                buffer.describe(),
+                buffer.size,
+            ));
+        }
+    }
+
+    fn check_image(
+        &self,
+        name: &str,
+        image: &Image<B>,
+        usage: image::Usage,
+        layout: Layout,
+        allowed: &[Layout],
+    ) {
+        if !image.usage.contains(usage) {
+            self.report(format!(
+                "{} uses {} without {:?} usage",
+                name,
+                image.describe(),
+                usage
+            ));
+        }
+        if !image.bound {
+            self.report(format!(
+                "{} uses {} before it is bound to memory",
+                name,
+                image.describe()
+            ));
+        }
+        if !allowed.contains(&layout) {
+            self.report(format!(
+                "{} uses {} in layout {:?}, expected one of {:?}",
+                name,
+                image.describe(),
+                layout,
+                allowed
+            ));
+        }
+        match self.layouts.get(&image.id) {
+            Some(&current) if current != layout => self.report(format!(
+                "{} uses {} in layout {:?}, but it was transitioned to {:?}",
+                name,
+                image.describe(),
+                layout,
+                current
+            )),
+            _ => {}
+        }
+    }
+
+    fn check_src_image(&self, name: &str, image: &Image<B>, layout: Layout) {
+        let allowed = [Layout::TransferSrcOptimal, Layout::General];
+        self.check_image(name, image, image::Usage::TRANSFER_SRC, layout, &allowed);
+    }
+
+    fn check_dst_image(&self, name: &str, image: &Image<B>, layout: Layout) {
+        let allowed = [Layout::TransferDstOptimal, Layout::General];
+        self.check_image(name, image, image::Usage::TRANSFER_DST, layout, &allowed);
+    }
+
+    fn check_image_region(
+        &self,
+        name: &str,
+        image: &Image<B>,
+        layers: &image::SubresourceLayers,
+        offset: image::Offset,
+        extent: image::Extent,
+    ) {
+        let level_extent = image.kind.level_extent(layers.level);
+        let fits = |start: i32, size: u32, max: u32| start >= 0 && start as u32 + size <= max;
+        if !fits(offset.x, extent.width, level_extent.width)
+            || !fits(offset.y, extent.height, level_extent.height)
+            || !fits(offset.z, extent.depth, level_extent.depth)
+            || layers.layers.end > image.kind.num_layers()
+        {
+            self.report(format!(
+                "{} accesses {:?} + {:?} of level {} and layers {:?} of {}, which is {:?} with {} layers",
+                name,
+                offset,
+                extent,
+                layers.level,
+                layers.layers,
+                image.describe(),
+                level_extent,
+                image.kind.num_layers(),
+            ));
+        }
+    }
+
+    /// Check the barriers, tracking the image layouts, and unwrap them.
+    fn convert_barriers<'a, T>(&mut self, name: &str, barriers: T) -> Vec<Barrier<'a, B>>
+    where
+        T: IntoIterator,
+        T::Item: Borrow<Barrier<'a, Backend<B>>>,
+    {
+        barriers
+            .into_iter()
+            .map(|barrier| match *barrier.borrow() {
+                Barrier::AllBuffers(ref access) => Barrier::AllBuffers(access.clone()),
+                Barrier::AllImages(ref access) => Barrier::AllImages(access.clone()),
+                Barrier::Buffer {
+                    ref states,
+                    target,
+                    ref families,
+                    ref range,
+                } => Barrier::Buffer {
+                    states: states.clone(),
+                    target: &target.raw,
+                    families: families.clone(),
+                    range: range.clone(),
+                },
+                Barrier::Image {
+                    ref states,
+                    target,
+                    ref families,
+                    ref range,
+                } => {
+                    let (old, new) = (states.start.1, states.end.1);
+                    match self.layouts.get(&target.id) {
+                        Some(&current) if old != Layout::Undefined && old != current => self
+                            .report(format!(
+                                "{} transitions {} from layout {:?}, but it is in {:?}",
+                                name,
+                                target.describe(),
+                                old,
+                                current
+                            )),
+                        _ => {}
+                    }
+                    self.layouts.insert(target.id, new);
+                    Barrier::Image {
+                        states: states.clone(),
+                        target: &target.raw,
+                        families: families.clone(),
+                        range: range.clone(),
+                    }
+                }
+            })
+            .collect()
+    }
+
+    pub(crate) fn check_submit(&self, reporter: &Reporter) {
+        if self.state != State::Executable {
+            reporter.report(format!(
+                "{} is submitted without being finished",
+                self.describe()
+            ));
+        }
+        if self.level != Level::Primary {
+            reporter.report(format!(
+                "{} is submitted, but it is not a primary command buffer",
+                self.describe()
+            ));
+        }
+    }
+}
+
+fn convert_query<'a, B: hal::Backend>(query: query::Query<'a, Backend<B>>) -> query::Query<'a, B> {
+    query::Query {
+        pool: query.pool,
+        id: query.id,
+    }
+}
+
+impl<B: hal::Backend> com::CommandBuffer<Backend<B>> for CommandBuffer<B> {
+    unsafe fn begin(
+        &mut self,
+        flags: com::CommandBufferFlags,
+        inheritance_info: com::CommandBufferInheritanceInfo<Backend<B>>,
+    ) {
+        if self.state == State::Recording {
+            self.report("begun while already recording".to_string());
+        }
+        self.state = State::Recording;
+        self.layouts.clear();
+        self.in_render_pass = self.level == Level::Secondary
+            && flags.contains(com::CommandBufferFlags::RENDER_PASS_CONTINUE);
+        let inheritance_info = com::CommandBufferInheritanceInfo {
+            subpass: inheritance_info.subpass.map(|subpass| pass::Subpass {
+                index: subpass.index,
+                main_pass: subpass.main_pass,
+            }),
+            framebuffer: inheritance_info.framebuffer,
+            occlusion_query_enable: inheritance_info.occlusion_query_enable,
+            occlusion_query_flags: inheritance_info.occlusion_query_flags,
+            pipeline_statistics: inheritance_info.pipeline_statistics,
+        };
+        self.raw.begin(flags, inheritance_info)
+    }
+
+    unsafe fn finish(&mut self) {
+        if self.state != State::Recording {
+            self.report("finished while not recording".to_string());
+        }
+        if self.in_render_pass && self.level == Level::Primary {
+            self.report("finished inside of a render pass".to_string());
+        }
+        self.state = State::Executable;
+        self.raw.finish()
+    }
+
+    unsafe fn reset(&mut self, release_resources: bool) {
+        self.state = State::Initial;
+        self.in_render_pass = false;
+        self.layouts.clear();
+        self.raw.reset(release_resources)
+    }
+
+    unsafe fn pipeline_barrier<'a, T>(
+        &mut self,
+        stages: Range<pso::PipelineStage>,
+        dependencies: Dependencies,
+        barriers: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<Barrier<'a, Backend<B>>>,
+    {
+        self.command("pipeline_barrier", Scope::Both);
+        let barriers = self.convert_barriers("pipeline_barrier", barriers);
+        self.raw.pipeline_barrier(stages, dependencies, barriers)
+    }
+
+    unsafe fn fill_buffer<R>(&mut self, buffer: &Buffer<B>, range: R, data: u32)
+    where
+        R: RangeArg<buffer::Offset>,
+    {
+        self.command("fill_buffer", Scope::Outside);
+        self.check_buffer("fill_buffer", buffer, buffer::Usage::TRANSFER_DST);
+        let start = range.start().cloned().unwrap_or(0);
+        let end = range.end().cloned().unwrap_or(buffer.size);
+        self.check_buffer_range("fill_buffer", buffer, start, end.saturating_sub(start));
+        self.raw.fill_buffer(&buffer.raw, range, data)
+    }
+
+    unsafe fn update_buffer(&mut self, buffer: &Buffer<B>, offset: buffer::Offset, data: &[u8]) {
+        self.command("update_buffer", Scope::Outside);
+        self.check_buffer("update_buffer", buffer, buffer::Usage::TRANSFER_DST);
+        self.check_buffer_range("update_buffer", buffer, offset, data.len() as u64);
+        self.raw.update_buffer(&buffer.raw, offset, data)
+    }
+
+    unsafe fn clear_image<T>(
+        &mut self,
+        image: &Image<B>,
+        layout: Layout,
+        value: com::ClearValue,
+        subresource_ranges: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<SubresourceRange>,
+    {
+        self.command("clear_image", Scope::Outside);
+        self.check_dst_image("clear_image", image, layout);
+        self.raw
+            .clear_image(&image.raw, layout, value, subresource_ranges)
+    }
+
+    unsafe fn clear_attachments<T, U>(&mut self, clears: T, rects: U)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<com::AttachmentClear>,
+        U: IntoIterator,
+        U::Item: Borrow<pso::ClearRect>,
+    {
+        self.command("clear_attachments", Scope::Inside);
+        self.raw.clear_attachments(clears, rects)
+    }
+
+    unsafe fn resolve_image<T>(
+        &mut self,
+        src: &Image<B>,
+        src_layout: Layout,
+        dst: &Image<B>,
+        dst_layout: Layout,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<com::ImageResolve>,
+    {
+        self.command("resolve_image", Scope::Outside);
+        self.check_src_image("resolve_image", src, src_layout);
+        self.check_dst_image("resolve_image", dst, dst_layout);
+        self.raw
+            .resolve_image(&src.raw, src_layout, &dst.raw, dst_layout, regions)
+    }
+
+    unsafe fn blit_image<T>(
+        &mut self,
+        src: &Image<B>,
+        src_layout: Layout,
+        dst: &Image<B>,
+        dst_layout: Layout,
+        filter: Filter,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<com::ImageBlit>,
+    {
+        self.command("blit_image", Scope::Outside);
+        self.require_graphics("blit_image");
+        self.check_src_image("blit_image", src, src_layout);
+        self.check_dst_image("blit_image", dst, dst_layout);
+        self.raw
+            .blit_image(&src.raw, src_layout, &dst.raw, dst_layout, filter, regions)
+    }
+
+    unsafe fn bind_index_buffer(&mut self, view: buffer::IndexBufferView<Backend<B>>) {
+        self.command("bind_index_buffer", Scope::Both);
+        self.require_graphics("bind_index_buffer");
+        self.check_buffer("bind_index_buffer", view.buffer, buffer::Usage::INDEX);
+        self.raw.bind_index_buffer(buffer::IndexBufferView {
+            buffer: &view.buffer.raw,
+            offset: view.offset,
+            index_type: view.index_type,
+        })
+    }
+
+    unsafe fn bind_vertex_buffers<I, T>(&mut self, first_binding: pso::BufferIndex, buffers: I)
+    where
+        I: IntoIterator<Item = (T, buffer::Offset)>,
+        T: Borrow<Buffer<B>>,
+    {
+        self.command("bind_vertex_buffers", Scope::Both);
+        self.require_graphics("bind_vertex_buffers");
+        let buffers = buffers.into_iter().collect::<Vec<_>>();
+        for (buffer, _) in &buffers {
+            self.check_buffer(
+                "bind_vertex_buffers",
+                buffer.borrow(),
+                buffer::Usage::VERTEX,
+            );
+        }
+        self.raw.bind_vertex_buffers(
+            first_binding,
+            buffers
+                .iter()
+                .map(|&(ref buffer, offset)| (&buffer.borrow().raw, offset)),
+        )
+    }
+
+    unsafe fn set_viewports<T>(&mut self, first_viewport: u32, viewports: T)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<pso::Viewport>,
+    {
+        self.command("set_viewports", Scope::Both);
+        self.raw.set_viewports(first_viewport, viewports)
+    }
+
+    unsafe fn set_scissors<T>(&mut self, first_scissor: u32, rects: T)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<pso::Rect>,
+    {
+        self.command("set_scissors", Scope::Both);
+        self.raw.set_scissors(first_scissor, rects)
+    }
+
+    unsafe fn set_stencil_reference(&mut self, faces: pso::Face, value: pso::StencilValue) {
+        self.command("set_stencil_reference", Scope::Both);
+        self.raw.set_stencil_reference(faces, value)
+    }
+
+    unsafe fn set_stencil_read_mask(&mut self, faces: pso::Face, value: pso::StencilValue) {
+        self.command("set_stencil_read_mask", Scope::Both);
+        self.raw.set_stencil_read_mask(faces, value)
+    }
+
+    unsafe fn set_stencil_write_mask(&mut self, faces: pso::Face, value: pso::StencilValue) {
+        self.command("set_stencil_write_mask", Scope::Both);
+        self.raw.set_stencil_write_mask(faces, value)
+    }
+
+    unsafe fn set_blend_constants(&mut self, color: pso::ColorValue) {
+        self.command("set_blend_constants", Scope::Both);
+        self.raw.set_blend_constants(color)
+    }
+
+    unsafe fn set_depth_bounds(&mut self, bounds: Range<f32>) {
+        self.command("set_depth_bounds", Scope::Both);
+        self.raw.set_depth_bounds(bounds)
+    }
+
+    unsafe fn set_line_width(&mut self, width: f32) {
+        self.command("set_line_width", Scope::Both);
+        self.raw.set_line_width(width)
+    }
+
+    unsafe fn set_depth_bias(&mut self, depth_bias: pso::DepthBias) {
+        self.command("set_depth_bias", Scope::Both);
+        self.raw.set_depth_bias(depth_bias)
+    }
+
+    unsafe fn begin_render_pass<T>(
+        &mut self,
+        render_pass: &B::RenderPass,
+        framebuffer: &B::Framebuffer,
+        render_area: pso::Rect,
+        clear_values: T,
+        first_subpass: com::SubpassContents,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<com::ClearValue>,
+    {
+        self.command("begin_render_pass", Scope::Outside);
+        self.require_graphics("begin_render_pass");
+        if self.level != Level::Primary {
+            self.report("begin_render_pass is recorded in a secondary command buffer".to_string());
+        }
+        self.in_render_pass = true;
+        self.raw.begin_render_pass(
+            render_pass,
+            framebuffer,
+            render_area,
+            clear_values,
+            first_subpass,
+        )
+    }
+
+    unsafe fn next_subpass(&mut self, contents: com::SubpassContents) {
+        self.command("next_subpass", Scope::Inside);
+        self.raw.next_subpass(contents)
+    }
+
+    unsafe fn end_render_pass(&mut self) {
+        self.command("end_render_pass", Scope::Inside);
+        self.in_render_pass = false;
+        self.raw.end_render_pass()
+    }
+
+    unsafe fn bind_graphics_pipeline(&mut self, pipeline: &B::GraphicsPipeline) {
+        self.command("bind_graphics_pipeline", Scope::Both);
+        self.require_graphics("bind_graphics_pipeline");
+        self.raw.bind_graphics_pipeline(pipeline)
+    }
+
+    unsafe fn bind_graphics_descriptor_sets<I, J>(
+        &mut self,
+        layout: &B::PipelineLayout,
+        first_set: usize,
+        sets: I,
+        offsets: J,
+    ) where
+        I: IntoIterator,
+        I::Item: Borrow<B::DescriptorSet>,
+        J: IntoIterator,
+        J::Item: Borrow<com::DescriptorSetOffset>,
+    {
+        self.command("bind_graphics_descriptor_sets", Scope::Both);
+        self.require_graphics("bind_graphics_descriptor_sets");
+        self.raw
+            .bind_graphics_descriptor_sets(layout, first_set, sets, offsets)
+    }
+
+    unsafe fn bind_compute_pipeline(&mut self, pipeline: &B::ComputePipeline) {
+        self.command("bind_compute_pipeline", Scope::Outside);
+        self.require_compute("bind_compute_pipeline");
+        self.raw.bind_compute_pipeline(pipeline)
+    }
+
+    unsafe fn bind_compute_descriptor_sets<I, J>(
+        &mut self,
+        layout: &B::PipelineLayout,
+        first_set: usize,
+        sets: I,
+        offsets: J,
+    ) where
+        I: IntoIterator,
+        I::Item: Borrow<B::DescriptorSet>,
+        J: IntoIterator,
+        J::Item: Borrow<com::DescriptorSetOffset>,
+    {
+        self.command("bind_compute_descriptor_sets", Scope::Outside);
+        self.require_compute("bind_compute_descriptor_sets");
+        self.raw
+            .bind_compute_descriptor_sets(layout, first_set, sets, offsets)
+    }
+
+    unsafe fn dispatch(&mut self, count: WorkGroupCount) {
+        self.command("dispatch", Scope::Outside);
+        self.require_compute("dispatch");
+        self.raw.dispatch(count)
+    }
+
+    unsafe fn dispatch_indirect(&mut self, buffer: &Buffer<B>, offset: buffer::Offset) {
+        self.command("dispatch_indirect", Scope::Outside);
+        self.require_compute("dispatch_indirect");
+        self.check_buffer("dispatch_indirect", buffer, buffer::Usage::INDIRECT);
+        self.check_buffer_range("dispatch_indirect", buffer, offset, 12);
+        self.raw.dispatch_indirect(&buffer.raw, offset)
+    }
+
+    unsafe fn copy_buffer<T>(&mut self, src: &Buffer<B>, dst: &Buffer<B>, regions: T)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<com::BufferCopy>,
+    {
+        self.command("copy_buffer", Scope::Outside);
+        self.check_buffer("copy_buffer", src, buffer::Usage::TRANSFER_SRC);
+        self.check_buffer("copy_buffer", dst, buffer::Usage::TRANSFER_DST);
+        let regions = regions
+            .into_iter()
+            .map(|region| *region.borrow())
+            .collect::<Vec<_>>();
+        for region in &regions {
+            self.check_buffer_range("copy_buffer", src, region.src, region.size);
+            self.check_buffer_range("copy_buffer", dst, region.dst, region.size);
+        }
+        self.raw.copy_buffer(&src.raw, &dst.raw, regions)
+    }
+
+    unsafe fn copy_image<T>(
+        &mut self,
+        src: &Image<B>,
+        src_layout: Layout,
+        dst: &Image<B>,
+        dst_layout: Layout,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<com::ImageCopy>,
+    {
+        self.command("copy_image", Scope::Outside);
+        self.check_src_image("copy_image", src, src_layout);
+        self.check_dst_image("copy_image", dst, dst_layout);
+        let regions = regions
+            .into_iter()
+            .map(|region| region.borrow().clone())
+            .collect::<Vec<_>>();
+        for r in &regions {
+            self.check_image_region(
+                "copy_image",
+                src,
+                &r.src_subresource,
+                r.src_offset,
+                r.extent,
+            );
+            self.check_image_region(
+                "copy_image",
+                dst,
+                &r.dst_subresource,
+                r.dst_offset,
+                r.extent,
+            );
+        }
+        self.raw
+            .copy_image(&src.raw, src_layout, &dst.raw, dst_layout, regions)
+    }
+
+    unsafe fn copy_buffer_to_image<T>(
+        &mut self,
+        src: &Buffer<B>,
+        dst: &Image<B>,
+        dst_layout: Layout,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<com::BufferImageCopy>,
+    {
+        self.command("copy_buffer_to_image", Scope::Outside);
+        self.check_buffer("copy_buffer_to_image", src, buffer::Usage::TRANSFER_SRC);
+        self.check_dst_image("copy_buffer_to_image", dst, dst_layout);
+        let regions = regions
+            .into_iter()
+            .map(|region| region.borrow().clone())
+            .collect::<Vec<_>>();
+        for r in &regions {
+            self.check_image_region(
+                "copy_buffer_to_image",
+                dst,
+                &r.image_layers,
+                r.image_offset,
+                r.image_extent,
+            );
+        }
+        self.raw
+            .copy_buffer_to_image(&src.raw, &dst.raw, dst_layout, regions)
+    }
+
+    unsafe fn copy_image_to_buffer<T>(
+        &mut self,
+        src: &Image<B>,
+        src_layout: Layout,
+        dst: &Buffer<B>,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<com::BufferImageCopy>,
+    {
+        self.command("copy_image_to_buffer", Scope::Outside);
+        self.check_src_image("copy_image_to_buffer", src, src_layout);
+        self.check_buffer("copy_image_to_buffer", dst, buffer::Usage::TRANSFER_DST);
+        let regions = regions
+            .into_iter()
+            .map(|region| region.borrow().clone())
+            .collect::<Vec<_>>();
+        for r in &regions {
+            self.check_image_region(
+                "copy_image_to_buffer",
+                src,
+                &r.image_layers,
+                r.image_offset,
+                r.image_extent,
+            );
+        }
+        self.raw
+            .copy_image_to_buffer(&src.raw, src_layout, &dst.raw, regions)
+    }
+
+    unsafe fn draw(&mut self, vertices: Range<VertexCount>, instances: Range<InstanceCount>) {
+        self.command("draw", Scope::Inside);
+        self.raw.draw(vertices, instances)
+    }
+
+    unsafe fn draw_indexed(
+        &mut self,
+        indices: Range<IndexCount>,
+        base_vertex: VertexOffset,
+        instances: Range<InstanceCount>,
+    ) {
+        self.command("draw_indexed", Scope::Inside);
+        self.raw.draw_indexed(indices, base_vertex, instances)
+    }
+
+    unsafe fn draw_indirect(
+        &mut self,
+        buffer: &Buffer<B>,
+        offset: buffer::Offset,
+        draw_count: DrawCount,
+        stride: u32,
+    ) {
+        self.command("draw_indirect", Scope::Inside);
+        self.check_buffer("draw_indirect", buffer, buffer::Usage::INDIRECT);
+        self.raw
+            .draw_indirect(&buffer.raw, offset, draw_count, stride)
+    }
+
+    unsafe fn draw_indexed_indirect(
+        &mut self,
+        buffer: &Buffer<B>,
+        offset: buffer::Offset,
+        draw_count: DrawCount,
+        stride: u32,
+    ) {
+        self.command("draw_indexed_indirect", Scope::Inside);
+        self.check_buffer("draw_indexed_indirect", buffer, buffer::Usage::INDIRECT);
+        self.raw
+            .draw_indexed_indirect(&buffer.raw, offset, draw_count, stride)
+    }
+
+    unsafe fn set_event(&mut self, event: &B::Event, stages: pso::PipelineStage) {
+        self.command("set_event", Scope::Outside);
+        self.raw.set_event(event, stages)
+    }
+
+    unsafe fn reset_event(&mut self, event: &B::Event, stages: pso::PipelineStage) {
+        self.command("reset_event", Scope::Outside);
+        self.raw.reset_event(event, stages)
+    }
+
+    unsafe fn wait_events<'a, I, J>(
+        &mut self,
+        events: I,
+        stages: Range<pso::PipelineStage>,
+        barriers: J,
+    ) where
+        I: IntoIterator,
+        I::Item: Borrow<B::Event>,
+        J: IntoIterator,
+        J::Item: Borrow<Barrier<'a, Backend<B>>>,
+    {
+        self.command("wait_events", Scope::Both);
+        let barriers = self.convert_barriers("wait_events", barriers);
+        self.raw.wait_events(events, stages, barriers)
+    }
+
+    unsafe fn begin_query(&mut self, query: query::Query<Backend<B>>, flags: query::ControlFlags) {
+        self.command("begin_query", Scope::Both);
+        self.raw.begin_query(convert_query(query), flags)
+    }
+
+    unsafe fn end_query(&mut self, query: query::Query<Backend<B>>) {
+        self.command("end_query", Scope::Both);
+        self.raw.end_query(convert_query(query))
+    }
+
+    unsafe fn reset_query_pool(&mut self, pool: &B::QueryPool, queries: Range<query::Id>) {
+        self.command("reset_query_pool", Scope::Outside);
+        self.raw.reset_query_pool(pool, queries)
+    }
+
+    unsafe fn copy_query_pool_results(
+        &mut self,
+        pool: &B::QueryPool,
+        queries: Range<query::Id>,
+        buffer: &Buffer<B>,
+        offset: buffer::Offset,
+        stride: buffer::Offset,
+        flags: query::ResultFlags,
+    ) {
+        self.command("copy_query_pool_results", Scope::Outside);
+        self.check_buffer(
+            "copy_query_pool_results",
+            buffer,
+            buffer::Usage::TRANSFER_DST,
+        );
+        self.raw
+            .copy_query_pool_results(pool, queries, &buffer.raw, offset, stride, flags)
+    }
+
+    unsafe fn write_timestamp(
+        &mut self,
+        stage: pso::PipelineStage,
+        query: query::Query<Backend<B>>,
+    ) {
+        self.command("write_timestamp", Scope::Both);
+        self.raw.write_timestamp(stage, convert_query(query))
+    }
+
+    unsafe fn push_graphics_constants(
+        &mut self,
+        layout: &B::PipelineLayout,
+        stages: pso::ShaderStageFlags,
+        offset: u32,
+        constants: &[u32],
+    ) {
+        self.command("push_graphics_constants", Scope::Both);
+        self.raw
+            .push_graphics_constants(layout, stages, offset, constants)
+    }
+
+    unsafe fn push_compute_constants(
+        &mut self,
+        layout: &B::PipelineLayout,
+        offset: u32,
+        constants: &[u32],
+    ) {
+        self.command("push_compute_constants", Scope::Both);
+        self.raw.push_compute_constants(layout, offset, constants)
+    }
+
+    unsafe fn execute_commands<'a, T, I>(&mut self, cmd_buffers: I)
+    where
+        T: 'a + Borrow<CommandBuffer<B>>,
+        I: IntoIterator<Item = &'a T>,
+    {
+        self.command("execute_commands", Scope::Both);
+        let cmd_buffers = cmd_buffers
+            .into_iter()
+            .map(|cmd_buffer| {
+                let cmd_buffer = cmd_buffer.borrow();
+                if cmd_buffer.level != Level::Secondary {
+                    self.report(format!(
+                        "execute_commands is given {}, which is not secondary",
+                        cmd_buffer.describe()
+                    ));
+                }
+                if cmd_buffer.state != State::Executable {
+                    self.report(format!(
+                        "execute_commands is given {}, which is not finished",
+                        cmd_buffer.describe()
+                    ));
+                }
+                &cmd_buffer.raw
+            })
+            .collect::<Vec<_>>();
+        self.raw.execute_commands(cmd_buffers)
+    }
+}
@@ -0,0 +1,856 @@
+use crate::command::{CommandBuffer, CommandPool};
+use crate::native::{next_id, Buffer, DescriptorPool, Image, Memory};
+use crate::{Backend, Reporter, Surface, Swapchain, Violation};
+
+use hal::queue::{QueueFamilyId, QueueType};
+use hal::range::RangeArg;
+use hal::MemoryTypeId;
+use hal::{adapter, buffer, device as d, format, image, memory, pass, pool, pso, query, window};
+
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+/// Range of mapped memory of the wrapped backend.
+type MappedRange<'a, B> = (&'a <B as hal::Backend>::Memory, (Option<u64>, Option<u64>));
+
+/// Logical device, checking the calls before forwarding them.
+#[derive(Debug)]
+pub struct Device<B: hal::Backend> {
+    pub(crate) raw: B::Device,
+    memory_types: Vec<adapter::MemoryType>,
+    queue_types: HashMap<QueueFamilyId, QueueType>,
+    reporter: Arc<Reporter>,
+}
+
+impl<B: hal::Backend> Device<B> {
+    pub(crate) fn new(
+        raw: B::Device,
+        memory_properties: adapter::MemoryProperties,
+        queue_types: HashMap<QueueFamilyId, QueueType>,
+        reporter: Arc<Reporter>,
+    ) -> Self {
+        Device {
+            raw,
+            memory_types: memory_properties.memory_types,
+            queue_types,
+            reporter,
+        }
+    }
+
+    /// Get the wrapped device.
+    pub fn raw(&self) -> &B::Device {
+        &self.raw
+    }
+
+    /// Take the violations reported so far by the device and the objects
+    /// created from it, including the queues.
+    pub fn take_violations(&self) -> Vec<Violation> {
+        self.reporter.take()
+    }
+
+    fn check_bind(
+        &self,
+        object: &str,
+        memory: &Memory<B>,
+        offset: u64,
+        requirements: memory::Requirements,
+    ) {
+        if requirements.type_mask & (1 << memory.type_id.0) == 0 {
+            self.reporter.report(format!(
+                "{} is bound to {}, which is not in its type mask {:#b}",
+                object,
+                memory.describe(),
+                requirements.type_mask,
+            ));
+        }
+        if offset & (requirements.alignment.max(1) - 1) != 0 {
+            self.reporter.report(format!(
+                "{} is bound at offset {}, which is not aligned to {}",
+                object, offset, requirements.alignment,
+            ));
+        }
+        if offset + requirements.size > memory.size {
+            self.reporter.report(format!(
+                "{} of size {} at offset {} doesn't fit in {} of size {}",
+                object,
+                requirements.size,
+                offset,
+                memory.describe(),
+                memory.size,
+            ));
+        }
+    }
+
+    fn check_mapped_ranges<'a, I, R>(&self, ranges: I) -> Vec<MappedRange<'a, B>>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<(&'a Memory<B>, R)>,
+        R: RangeArg<u64>,
+    {
+        ranges
+            .into_iter()
+            .map(|item| {
+                let (memory, ref range) = *item.borrow();
+                if !memory.mapped.load(Ordering::Acquire) {
+                    self.reporter.report(format!(
+                        "{} is flushed or invalidated while not mapped",
+                        memory.describe()
+                    ));
+                }
+                (&memory.raw, (range.start().cloned(), range.end().cloned()))
+            })
+            .collect()
+    }
+}
+
+fn convert_entry_point<'a, B: hal::Backend>(
+    entry: &pso::EntryPoint<'a, Backend<B>>,
+) -> pso::EntryPoint<'a, B> {
+    pso::EntryPoint {
+        entry: entry.entry,
+        module: entry.module,
+        specialization: entry.specialization.clone(),
+    }
+}
+
+// `BasePipeline` is neither `Clone` nor `Copy`, so it has to be rebuilt.
+#[allow(clippy::needless_match)]
+fn convert_base_pipeline<'a, P>(parent: &pso::BasePipeline<'a, P>) -> pso::BasePipeline<'a, P> {
+    match *parent {
+        pso::BasePipeline::Pipeline(pipeline) => pso::BasePipeline::Pipeline(pipeline),
+        pso::BasePipeline::Index(index) => pso::BasePipeline::Index(index),
+        pso::BasePipeline::None => pso::BasePipeline::None,
+    }
+}
+
+fn convert_descriptor<'a, B: hal::Backend>(
+    descriptor: &pso::Descriptor<'a, Backend<B>>,
+    reporter: &Reporter,
+) -> pso::Descriptor<'a, B> {
+    match *descriptor {
+        pso::Descriptor::Sampler(sampler) => pso::Descriptor::Sampler(sampler),
+        pso::Descriptor::Image(view, layout) => pso::Descriptor::Image(view, layout),
+        pso::Descriptor::CombinedImageSampler(view, layout, sampler) => {
+            pso::Descriptor::CombinedImageSampler(view, layout, sampler)
+        }
+        pso::Descriptor::Buffer(buffer, ref range) => {
+            if !buffer
+                .usage
+                .intersects(buffer::Usage::UNIFORM | buffer::Usage::STORAGE)
+            {
+                reporter.report(format!(
+                    "{} is written to a descriptor set without UNIFORM or STORAGE usage",
+                    buffer.describe()
+                ));
+            }
+            pso::Descriptor::Buffer(&buffer.raw, range.clone())
+        }
+        pso::Descriptor::UniformTexelBuffer(view) => pso::Descriptor::UniformTexelBuffer(view),
+        pso::Descriptor::StorageTexelBuffer(view) => pso::Descriptor::StorageTexelBuffer(view),
+    }
+}
+
+impl<B: hal::Backend> d::Device<Backend<B>> for Device<B> {
+    unsafe fn allocate_memory(
+        &self,
+        memory_type: MemoryTypeId,
+        size: u64,
+    ) -> Result<Memory<B>, d::AllocationError> {
+        let properties = match self.memory_types.get(memory_type.0) {
+            Some(ty) => ty.properties,
+            None => {
+                self.reporter.report(format!(
+                    "memory type {} is allocated, but only {} types exist",
+                    memory_type.0,
+                    self.memory_types.len(),
+                ));
+                memory::Properties::empty()
+            }
+        };
+        let raw = self.raw.allocate_memory(memory_type, size)?;
+        Ok(Memory {
+            raw,
+            id: next_id(),
+            type_id: memory_type,
+            properties,
+            size,
+            mapped: Default::default(),
+        })
+    }
+
+    unsafe fn free_memory(&self, memory: Memory<B>) {
+        self.raw.free_memory(memory.raw)
+    }
+
+    unsafe fn create_command_pool(
+        &self,
+        family: QueueFamilyId,
+        create_flags: pool::CommandPoolCreateFlags,
+    ) -> Result<CommandPool<B>, d::OutOfMemory> {
+        let queue_type = match self.queue_types.get(&family) {
+            Some(&ty) => ty,
+            None => {
+                self.reporter.report(format!(
+                    "command pool is created for queue family {:?}, which was not opened",
+                    family,
+                ));
+                QueueType::General
+            }
+        };
+        let raw = self.raw.create_command_pool(family, create_flags)?;
+        Ok(CommandPool::new(
+            raw,
+            queue_type,
+            Arc::clone(&self.reporter),
+        ))
+    }
+
+    unsafe fn destroy_command_pool(&self, pool: CommandPool<B>) {
+        self.raw.destroy_command_pool(pool.raw)
+    }
+
+    unsafe fn create_render_pass<'a, IA, IS, ID>(
+        &self,
+        attachments: IA,
+        subpasses: IS,
+        dependencies: ID,
+    ) -> Result<B::RenderPass, d::OutOfMemory>
+    where
+        IA: IntoIterator,
+        IA::Item: Borrow<pass::Attachment>,
+        IS: IntoIterator,
+        IS::Item: Borrow<pass::SubpassDesc<'a>>,
+        ID: IntoIterator,
+        ID::Item: Borrow<pass::SubpassDependency>,
+    {
+        self.raw
+            .create_render_pass(attachments, subpasses, dependencies)
+    }
+
+    unsafe fn destroy_render_pass(&self, rp: B::RenderPass) {
+        self.raw.destroy_render_pass(rp)
+    }
+
+    unsafe fn create_pipeline_layout<IS, IR>(
+        &self,
+        set_layouts: IS,
+        push_constant: IR,
+    ) -> Result<B::PipelineLayout, d::OutOfMemory>
+    where
+        IS: IntoIterator,
+        IS::Item: Borrow<B::DescriptorSetLayout>,
+        IR: IntoIterator,
+        IR::Item: Borrow<(pso::ShaderStageFlags, Range<u32>)>,
+    {
+        self.raw.create_pipeline_layout(set_layouts, push_constant)
+    }
+
+    unsafe fn destroy_pipeline_layout(&self, layout: B::PipelineLayout) {
+        self.raw.destroy_pipeline_layout(layout)
+    }
+
+    unsafe fn create_pipeline_cache(
+        &self,
+        data: Option<&[u8]>,
+    ) -> Result<B::PipelineCache, d::OutOfMemory> {
+        self.raw.create_pipeline_cache(data)
+    }
+
+    unsafe fn get_pipeline_cache_data(
+        &self,
+        cache: &B::PipelineCache,
+    ) -> Result<Vec<u8>, d::OutOfMemory> {
+        self.raw.get_pipeline_cache_data(cache)
+    }
+
+    unsafe fn merge_pipeline_caches<I>(
+        &self,
+        target: &B::PipelineCache,
+        sources: I,
+    ) -> Result<(), d::OutOfMemory>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<B::PipelineCache>,
+    {
+        self.raw.merge_pipeline_caches(target, sources)
+    }
+
+    unsafe fn destroy_pipeline_cache(&self, cache: B::PipelineCache) {
+        self.raw.destroy_pipeline_cache(cache)
+    }
+
+    unsafe fn create_graphics_pipeline<'a>(
+        &self,
+        desc: &pso::GraphicsPipelineDesc<'a, Backend<B>>,
+        cache: Option<&B::PipelineCache>,
+    ) -> Result<B::GraphicsPipeline, pso::CreationError> {
+        let shaders = &desc.shaders;
+        let raw_desc = pso::GraphicsPipelineDesc {
+            shaders: pso::GraphicsShaderSet {
+                vertex: convert_entry_point(&shaders.vertex),
+                hull: shaders.hull.as_ref().map(convert_entry_point),
+                domain: shaders.domain.as_ref().map(convert_entry_point),
+                geometry: shaders.geometry.as_ref().map(convert_entry_point),
+                fragment: shaders.fragment.as_ref().map(convert_entry_point),
+            },
+            rasterizer: desc.rasterizer,
+            vertex_buffers: desc.vertex_buffers.clone(),
+            attributes: desc.attributes.clone(),
+            input_assembler: desc.input_assembler.clone(),
+            blender: desc.blender.clone(),
+            depth_stencil: desc.depth_stencil,
+            multisampling: desc.multisampling.clone(),
+            baked_states: desc.baked_states.clone(),
+            layout: desc.layout,
+            subpass: pass::Subpass {
+                index: desc.subpass.index,
+                main_pass: desc.subpass.main_pass,
+            },
+            flags: desc.flags,
+            parent: convert_base_pipeline(&desc.parent),
+        };
+        self.raw.create_graphics_pipeline(&raw_desc, cache)
+    }
+
+    unsafe fn destroy_graphics_pipeline(&self, pipeline: B::GraphicsPipeline) {
+        self.raw.destroy_graphics_pipeline(pipeline)
+    }
+
+    unsafe fn create_compute_pipeline<'a>(
+        &self,
+        desc: &pso::ComputePipelineDesc<'a, Backend<B>>,
+        cache: Option<&B::PipelineCache>,
+    ) -> Result<B::ComputePipeline, pso::CreationError> {
+        let raw_desc = pso::ComputePipelineDesc {
+            shader: convert_entry_point(&desc.shader),
+            layout: desc.layout,
+            flags: desc.flags,
+            parent: convert_base_pipeline(&desc.parent),
+        };
+        self.raw.create_compute_pipeline(&raw_desc, cache)
+    }
+
+    unsafe fn destroy_compute_pipeline(&self, pipeline: B::ComputePipeline) {
+        self.raw.destroy_compute_pipeline(pipeline)
+    }
+
+    unsafe fn create_framebuffer<I>(
+        &self,
+        pass: &B::RenderPass,
+        attachments: I,
+        extent: image::Extent,
+    ) -> Result<B::Framebuffer, d::OutOfMemory>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<B::ImageView>,
+    {
+        self.raw.create_framebuffer(pass, attachments, extent)
+    }
+
+    unsafe fn destroy_framebuffer(&self, buf: B::Framebuffer) {
+        self.raw.destroy_framebuffer(buf)
+    }
+
+    unsafe fn create_shader_module(
+        &self,
+        spirv_data: &[u32],
+    ) -> Result<B::ShaderModule, d::ShaderError> {
+        self.raw.create_shader_module(spirv_data)
+    }
+
+    unsafe fn destroy_shader_module(&self, shader: B::ShaderModule) {
+        self.raw.destroy_shader_module(shader)
+    }
+
+    unsafe fn create_buffer(
+        &self,
+        size: u64,
+        usage: buffer::Usage,
+    ) -> Result<Buffer<B>, buffer::CreationError> {
+        if usage.is_empty() {
+            self.reporter
+                .report("buffer is created without any usage".to_string());
+        }
+        let raw = self.raw.create_buffer(size, usage)?;
+        Ok(Buffer {
+            raw,
+            id: next_id(),
+            size,
+            usage,
+            name: None,
+            bound: false,
+        })
+    }
+
+    unsafe fn get_buffer_requirements(&self, buf: &Buffer<B>) -> memory::Requirements {
+        self.raw.get_buffer_requirements(&buf.raw)
+    }
+
+    unsafe fn bind_buffer_memory(
+        &self,
+        memory: &Memory<B>,
+        offset: u64,
+        buf: &mut Buffer<B>,
+    ) -> Result<(), d::BindError> {
+        if buf.bound {
+            self.reporter
+                .report(format!("{} is bound to memory twice", buf.describe()));
+        }
+        let requirements = self.raw.get_buffer_requirements(&buf.raw);
+        self.check_bind(&buf.describe(), memory, offset, requirements);
+        self.raw
+            .bind_buffer_memory(&memory.raw, offset, &mut buf.raw)?;
+        buf.bound = true;
+        Ok(())
+    }
+
+    unsafe fn destroy_buffer(&self, buffer: Buffer<B>) {
+        self.raw.destroy_buffer(buffer.raw)
+    }
+
+    unsafe fn create_buffer_view<R: RangeArg<u64>>(
+        &self,
+        buf: &Buffer<B>,
+        fmt: Option<format::Format>,
+        range: R,
+    ) -> Result<B::BufferView, buffer::ViewCreationError> {
+        if !buf
+            .usage
+            .intersects(buffer::Usage::UNIFORM_TEXEL | buffer::Usage::STORAGE_TEXEL)
+        {
+            self.reporter.report(format!(
+                "{} is viewed without UNIFORM_TEXEL or STORAGE_TEXEL usage",
+                buf.describe()
+            ));
+        }
+        self.raw.create_buffer_view(&buf.raw, fmt, range)
+    }
+
+    unsafe fn destroy_buffer_view(&self, view: B::BufferView) {
+        self.raw.destroy_buffer_view(view)
+    }
+
+    unsafe fn create_image(
+        &self,
+        kind: image::Kind,
+        mip_levels: image::Level,
+        format: format::Format,
+        tiling: image::Tiling,
+        usage: image::Usage,
+        view_caps: image::ViewCapabilities,
+    ) -> Result<Image<B>, image::CreationError> {
+        if usage.is_empty() {
+            self.reporter
+                .report("image is created without any usage".to_string());
+        }
+        let raw = self
+            .raw
+            .create_image(kind, mip_levels, format, tiling, usage, view_caps)?;
+        Ok(Image {
+            raw,
+            id: next_id(),
+            kind,
+            format,
+            usage,
+            name: None,
+            bound: false,
+        })
+    }
+
+    unsafe fn get_image_requirements(&self, image: &Image<B>) -> memory::Requirements {
+        self.raw.get_image_requirements(&image.raw)
+    }
+
+    unsafe fn get_image_subresource_footprint(
+        &self,
+        image: &Image<B>,
+        subresource: image::Subresource,
+    ) -> image::SubresourceFootprint {
+        self.raw
+            .get_image_subresource_footprint(&image.raw, subresource)
+    }
+
+    unsafe fn bind_image_memory(
+        &self,
+        memory: &Memory<B>,
+        offset: u64,
+        image: &mut Image<B>,
+    ) -> Result<(), d::BindError> {
+        if image.bound {
+            self.reporter
+                .report(format!("{} is bound to memory twice", image.describe()));
+        }
+        let requirements = self.raw.get_image_requirements(&image.raw);
+        self.check_bind(&image.describe(), memory, offset, requirements);
+        self.raw
+            .bind_image_memory(&memory.raw, offset, &mut image.raw)?;
+        image.bound = true;
+        Ok(())
+    }
+
+    unsafe fn destroy_image(&self, image: Image<B>) {
+        self.raw.destroy_image(image.raw)
+    }
+
+    unsafe fn create_image_view(
+        &self,
+        image: &Image<B>,
+        view_kind: image::ViewKind,
+        format: format::Format,
+        swizzle: format::Swizzle,
+        range: image::SubresourceRange,
+    ) -> Result<B::ImageView, image::ViewError> {
+        if !image.bound {
+            self.reporter.report(format!(
+                "{} is viewed before being bound to memory",
+                image.describe()
+            ));
+        }
+        if !image.format.surface_desc().aspects.contains(range.aspects) {
+            self.reporter.report(format!(
+                "{} of format {:?} is viewed with aspects {:?}",
+                image.describe(),
+                image.format,
+                range.aspects,
+            ));
+        }
+        self.raw
+            .create_image_view(&image.raw, view_kind, format, swizzle, range)
+    }
+
+    unsafe fn destroy_image_view(&self, view: B::ImageView) {
+        self.raw.destroy_image_view(view)
+    }
+
+    unsafe fn create_sampler(
+        &self,
+        info: image::SamplerInfo,
+    ) -> Result<B::Sampler, d::AllocationError> {
+        self.raw.create_sampler(info)
+    }
+
+    unsafe fn destroy_sampler(&self, sampler: B::Sampler) {
+        self.raw.destroy_sampler(sampler)
+    }
+
+    unsafe fn create_descriptor_pool<I>(
+        &self,
+        max_sets: usize,
+        descriptor_ranges: I,
+        flags: pso::DescriptorPoolCreateFlags,
+    ) -> Result<DescriptorPool<B>, d::OutOfMemory>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<pso::DescriptorRangeDesc>,
+    {
+        let raw = self
+            .raw
+            .create_descriptor_pool(max_sets, descriptor_ranges, flags)?;
+        Ok(DescriptorPool { raw })
+    }
+
+    unsafe fn destroy_descriptor_pool(&self, pool: DescriptorPool<B>) {
+        self.raw.destroy_descriptor_pool(pool.raw)
+    }
+
+    unsafe fn create_descriptor_set_layout<I, J>(
+        &self,
+        bindings: I,
+        immutable_samplers: J,
+    ) -> Result<B::DescriptorSetLayout, d::OutOfMemory>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<pso::DescriptorSetLayoutBinding>,
+        J: IntoIterator,
+        J::Item: Borrow<B::Sampler>,
+    {
+        self.raw
+            .create_descriptor_set_layout(bindings, immutable_samplers)
+    }
+
+    unsafe fn destroy_descriptor_set_layout(&self, layout: B::DescriptorSetLayout) {
+        self.raw.destroy_descriptor_set_layout(layout)
+    }
+
+    unsafe fn write_descriptor_sets<'a, I, J>(&self, write_iter: I)
+    where
+        I: IntoIterator<Item = pso::DescriptorSetWrite<'a, Backend<B>, J>>,
+        J: IntoIterator,
+        J::Item: Borrow<pso::Descriptor<'a, Backend<B>>>,
+    {
+        let writes = write_iter
+            .into_iter()
+            .map(|write| pso::DescriptorSetWrite {
+                set: write.set,
+                binding: write.binding,
+                array_offset: write.array_offset,
+                descriptors: write
+                    .descriptors
+                    .into_iter()
+                    .map(|descriptor| convert_descriptor(descriptor.borrow(), &self.reporter))
+                    .collect::<Vec<_>>(),
+            })
+            .collect::<Vec<_>>();
+        self.raw.write_descriptor_sets(writes)
+    }
+
+    unsafe fn copy_descriptor_sets<'a, I>(&self, copy_iter: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<pso::DescriptorSetCopy<'a, Backend<B>>>,
+    {
+        let copies = copy_iter.into_iter().map(|copy| {
+            let copy = copy.borrow();
+            pso::DescriptorSetCopy {
+                src_set: copy.src_set,
+                src_binding: copy.src_binding,
+                src_array_offset: copy.src_array_offset,
+                dst_set: copy.dst_set,
+                dst_binding: copy.dst_binding,
+                dst_array_offset: copy.dst_array_offset,
+                count: copy.count,
+            }
+        });
+        self.raw.copy_descriptor_sets(copies)
+    }
+
+    unsafe fn map_memory<R>(&self, memory: &Memory<B>, range: R) -> Result<*mut u8, d::MapError>
+    where
+        R: RangeArg<u64>,
+    {
+        if !memory.properties.contains(memory::Properties::CPU_VISIBLE) {
+            self.reporter.report(format!(
+                "{} is mapped, but it is not CPU_VISIBLE",
+                memory.describe()
+            ));
+        }
+        let end = range.end().cloned().unwrap_or(memory.size);
+        if end > memory.size {
+            self.reporter.report(format!(
+                "{} of size {} is mapped up to {}",
+                memory.describe(),
+                memory.size,
+                end,
+            ));
+        }
+        if memory.mapped.swap(true, Ordering::AcqRel) {
+            self.reporter
+                .report(format!("{} is mapped twice", memory.describe()));
+        }
+        self.raw
+            .map_memory(&memory.raw, (range.start().cloned(), range.end().cloned()))
+    }
+
+    unsafe fn flush_mapped_memory_ranges<'a, I, R>(&self, ranges: I) -> Result<(), d::OutOfMemory>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<(&'a Memory<B>, R)>,
+        R: RangeArg<u64>,
+    {
+        let ranges = self.check_mapped_ranges(ranges);
+        self.raw.flush_mapped_memory_ranges(ranges)
+    }
+
+    unsafe fn invalidate_mapped_memory_ranges<'a, I, R>(
+        &self,
+        ranges: I,
+    ) -> Result<(), d::OutOfMemory>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<(&'a Memory<B>, R)>,
+        R: RangeArg<u64>,
+    {
+        let ranges = self.check_mapped_ranges(ranges);
+        self.raw.invalidate_mapped_memory_ranges(ranges)
+    }
+
+    unsafe fn unmap_memory(&self, memory: &Memory<B>) {
+        if !memory.mapped.swap(false, Ordering::AcqRel) {
+            self.reporter.report(format!(
+                "{} is unmapped while not mapped",
+                memory.describe()
+            ));
+        }
+        self.raw.unmap_memory(&memory.raw)
+    }
+
+    fn create_semaphore(&self) -> Result<B::Semaphore, d::OutOfMemory> {
+        self.raw.create_semaphore()
+    }
+
+    unsafe fn destroy_semaphore(&self, semaphore: B::Semaphore) {
+        self.raw.destroy_semaphore(semaphore)
+    }
+
+    fn create_fence(&self, signaled: bool) -> Result<B::Fence, d::OutOfMemory> {
+        self.raw.create_fence(signaled)
+    }
+
+    unsafe fn reset_fence(&self, fence: &B::Fence) -> Result<(), d::OutOfMemory> {
+        self.raw.reset_fence(fence)
+    }
+
+    unsafe fn reset_fences<I>(&self, fences: I) -> Result<(), d::OutOfMemory>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<B::Fence>,
+    {
+        self.raw.reset_fences(fences)
+    }
+
+    unsafe fn wait_for_fence(
+        &self,
+        fence: &B::Fence,
+        timeout_ns: u64,
+    ) -> Result<bool, d::OomOrDeviceLost> {
+        self.raw.wait_for_fence(fence, timeout_ns)
+    }
+
+    unsafe fn wait_for_fences<I>(
+        &self,
+        fences: I,
+        wait: d::WaitFor,
+        timeout_ns: u64,
+    ) -> Result<bool, d::OomOrDeviceLost>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<B::Fence>,
+    {
+        self.raw.wait_for_fences(fences, wait, timeout_ns)
+    }
+
+    unsafe fn get_fence_status(&self, fence: &B::Fence) -> Result<bool, d::DeviceLost> {
+        self.raw.get_fence_status(fence)
+    }
+
+    unsafe fn destroy_fence(&self, fence: B::Fence) {
+        self.raw.destroy_fence(fence)
+    }
+
+    fn create_event(&self) -> Result<B::Event, d::OutOfMemory> {
+        self.raw.create_event()
+    }
+
+    unsafe fn destroy_event(&self, event: B::Event) {
+        self.raw.destroy_event(event)
+    }
+
+    unsafe fn get_event_status(&self, event: &B::Event) -> Result<bool, d::OomOrDeviceLost> {
+        self.raw.get_event_status(event)
+    }
+
+    unsafe fn set_event(&self, event: &B::Event) -> Result<(), d::OutOfMemory> {
+        self.raw.set_event(event)
+    }
+
+    unsafe fn reset_event(&self, event: &B::Event) -> Result<(), d::OutOfMemory> {
+        self.raw.reset_event(event)
+    }
+
+    unsafe fn create_query_pool(
+        &self,
+        ty: query::Type,
+        count: query::Id,
+    ) -> Result<B::QueryPool, query::CreationError> {
+        self.raw.create_query_pool(ty, count)
+    }
+
+    unsafe fn destroy_query_pool(&self, pool: B::QueryPool) {
+        self.raw.destroy_query_pool(pool)
+    }
+
+    unsafe fn get_query_pool_results(
+        &self,
+        pool: &B::QueryPool,
+        queries: Range<query::Id>,
+        data: &mut [u8],
+        stride: buffer::Offset,
+        flags: query::ResultFlags,
+    ) -> Result<bool, d::OomOrDeviceLost> {
+        self.raw
+            .get_query_pool_results(pool, queries, data, stride, flags)
+    }
+
+    unsafe fn create_swapchain(
+        &self,
+        surface: &mut Surface<B>,
+        config: window::SwapchainConfig,
+        old_swapchain: Option<Swapchain<B>>,
+    ) -> Result<(Swapchain<B>, Vec<Image<B>>), window::CreationError> {
+        let (raw, images) = self.raw.create_swapchain(
+            &mut surface.raw,
+            config.clone(),
+            old_swapchain.map(|swapchain| swapchain.raw),
+        )?;
+        let kind = image::Kind::D2(config.extent.width, config.extent.height, 1, 1);
+        let images = images
+            .into_iter()
+            .map(|raw| Image {
+                raw,
+                id: next_id(),
+                kind,
+                format: config.format,
+                usage: config.image_usage,
+                name: None,
+                bound: true,
+            })
+            .collect();
+        Ok((Swapchain { raw }, images))
+    }
+
+    unsafe fn destroy_swapchain(&self, swapchain: Swapchain<B>) {
+        self.raw.destroy_swapchain(swapchain.raw)
+    }
+
+    fn wait_idle(&self) -> Result<(), d::OutOfMemory> {
+        self.raw.wait_idle()
+    }
+
+    unsafe fn set_image_name(&self, image: &mut Image<B>, name: &str) {
+        image.name = Some(name.to_string());
+        self.raw.set_image_name(&mut image.raw, name)
+    }
+
+    unsafe fn set_buffer_name(&self, buffer: &mut Buffer<B>, name: &str) {
+        buffer.name = Some(name.to_string());
+        self.raw.set_buffer_name(&mut buffer.raw, name)
+    }
+
+    unsafe fn set_command_buffer_name(&self, command_buffer: &mut CommandBuffer<B>, name: &str) {
+        command_buffer.name = Some(name.to_string());
+        self.raw
+            .set_command_buffer_name(&mut command_buffer.raw, name)
+    }
+
+    unsafe fn set_semaphore_name(&self, semaphore: &mut B::Semaphore, name: &str) {
+        self.raw.set_semaphore_name(semaphore, name)
+    }
+
+    unsafe fn set_fence_name(&self, fence: &mut B::Fence, name: &str) {
+        self.raw.set_fence_name(fence, name)
+    }
+
+    unsafe fn set_framebuffer_name(&self, framebuffer: &mut B::Framebuffer, name: &str) {
+        self.raw.set_framebuffer_name(framebuffer, name)
+    }
+
+    unsafe fn set_render_pass_name(&self, render_pass: &mut B::RenderPass, name: &str) {
+        self.raw.set_render_pass_name(render_pass, name)
+    }
+
+    unsafe fn set_descriptor_set_name(&self, descriptor_set: &mut B::DescriptorSet, name: &str) {
+        self.raw.set_descriptor_set_name(descriptor_set, name)
+    }
+
+    unsafe fn set_descriptor_set_layout_name(
+        &self,
+        descriptor_set_layout: &mut B::DescriptorSetLayout,
+        name: &str,
+    ) {
+        self.raw
+            .set_descriptor_set_layout_name(descriptor_set_layout, name)
+    }
+}
@@ -0,0 +1,568 @@
+//! Validation backend, checking the usage of the API on top of any other backend.
+//!
+//! Every call is forwarded to the wrapped backend after checking it against
+//! the rules that backends are allowed to assume are followed. Violations are
+//! logged and collected on the device, naming the objects involved with the
+//! names given through `Device::set_*_name`. Checking is done on the host and
+//! is necessarily partial; notably, image layouts are tracked per image within
+//! a single command buffer, without taking subresources into account.
+//!
+//! ```ignore
+//! let instance = gfx_backend_validation::Instance::new(raw_instance);
+//! // use `instance` like any other, then look at `device.take_violations()`
+//! ```
+
+#[macro_use]
+extern crate log;
+extern crate gfx_hal as hal;
+
+use hal::{adapter, device as d, format, image, queue as q, window};
+use hal::{Features, Limits};
+
+use std::borrow::Borrow;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+
+mod command;
+mod device;
+mod native;
+
+pub use crate::command::{CommandBuffer, CommandPool};
+pub use crate::device::Device;
+pub use crate::native::{Buffer, DescriptorPool, Image, Memory};
+
+/// Validation backend, wrapping the backend `B`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Backend<B>(PhantomData<B>);
+
+impl<B: hal::Backend> hal::Backend for Backend<B> {
+    type PhysicalDevice = PhysicalDevice<B>;
+    type Device = Device<B>;
+
+    type Surface = Surface<B>;
+    type Swapchain = Swapchain<B>;
+
+    type QueueFamily = B::QueueFamily;
+    type CommandQueue = CommandQueue<B>;
+    type CommandBuffer = CommandBuffer<B>;
+
+    type Memory = Memory<B>;
+    type CommandPool = CommandPool<B>;
+
+    type ShaderModule = B::ShaderModule;
+    type RenderPass = B::RenderPass;
+    type Framebuffer = B::Framebuffer;
+
+    type Buffer = Buffer<B>;
+    type BufferView = B::BufferView;
+    type Image = Image<B>;
+    type ImageView = B::ImageView;
+    type Sampler = B::Sampler;
+
+    type ComputePipeline = B::ComputePipeline;
+    type GraphicsPipeline = B::GraphicsPipeline;
+    type PipelineCache = B::PipelineCache;
+    type PipelineLayout = B::PipelineLayout;
+    type DescriptorSetLayout = B::DescriptorSetLayout;
+    type DescriptorPool = DescriptorPool<B>;
+    type DescriptorSet = B::DescriptorSet;
+
+    type Fence = B::Fence;
+    type Semaphore = B::Semaphore;
+    type Event = B::Event;
+    type QueryPool = B::QueryPool;
+}
+
+/// Misuse of the API detected by the validation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Collects the violations of a device and of everything created from it.
+#[derive(Debug, Default)]
+pub(crate) struct Reporter {
+    panic: bool,
+    violations: Mutex<Vec<Violation>>,
+}
+
+impl Reporter {
+    pub(crate) fn report(&self, message: String) {
+        if self.panic {
+            panic!("Validation error: {}", message);
+        }
+        error!("Validation error: {}", message);
+        self.violations.lock().unwrap().push(Violation { message });
+    }
+
+    pub(crate) fn take(&self) -> Vec<Violation> {
+        self.violations.lock().unwrap().drain(..).collect()
+    }
+}
+
+/// Instance wrapping the instance of another backend.
+#[derive(Debug)]
+pub struct Instance<I> {
+    raw: I,
+    panic: bool,
+}
+
+impl<I: hal::Instance> Instance<I> {
+    /// Wrap an instance, collecting the violations on the devices.
+    pub fn new(raw: I) -> Self {
+        Instance { raw, panic: false }
+    }
+
+    /// Wrap an instance, panicking on the first violation.
+    pub fn panicking(raw: I) -> Self {
+        Instance { raw, panic: true }
+    }
+
+    /// Get the wrapped instance, e.g. to create surfaces.
+    pub fn raw(&self) -> &I {
+        &self.raw
+    }
+}
+
+impl<I: hal::Instance> hal::Instance for Instance<I> {
+    type Backend = Backend<I::Backend>;
+    fn enumerate_adapters(&self) -> Vec<adapter::Adapter<Self::Backend>> {
+        self.raw
+            .enumerate_adapters()
+            .into_iter()
+            .map(|adapter| adapter::Adapter {
+                info: adapter.info,
+                physical_device: PhysicalDevice {
+                    raw: adapter.physical_device,
+                    panic: self.panic,
+                },
+                queue_families: adapter.queue_families,
+            })
+            .collect()
+    }
+}
+
+/// Physical device of the wrapped backend.
+#[derive(Debug)]
+pub struct PhysicalDevice<B: hal::Backend> {
+    raw: B::PhysicalDevice,
+    panic: bool,
+}
+
+impl<B: hal::Backend> adapter::PhysicalDevice<Backend<B>> for PhysicalDevice<B> {
+    unsafe fn open(
+        &self,
+        families: &[(&B::QueueFamily, &[q::QueuePriority])],
+        requested_features: Features,
+    ) -> Result<adapter::Gpu<Backend<B>>, d::CreationError> {
+        use hal::queue::QueueFamily as _;
+
+        let gpu = self.raw.open(families, requested_features)?;
+        let reporter = Arc::new(Reporter {
+            panic: self.panic,
+            violations: Mutex::new(Vec::new()),
+        });
+        let queue_types = families
+            .iter()
+            .map(|&(family, _)| (family.id(), family.queue_type()))
+            .collect();
+        let queue_groups = gpu
+            .queue_groups
+            .into_iter()
+            .map(|group| {
+                let mut new_group = q::QueueGroup::new(group.family);
+                for queue in group.queues {
+                    new_group.add_queue(CommandQueue {
+                        raw: queue,
+                        reporter: Arc::clone(&reporter),
+                    });
+                }
+                new_group
+            })
+            .collect();
+
+        Ok(adapter::Gpu {
+            device: Device::new(
+                gpu.device,
+                self.raw.memory_properties(),
+                queue_types,
+                reporter,
+            ),
+            queue_groups,
+        })
+    }
+
+    fn format_properties(&self, format: Option<format::Format>) -> format::Properties {
+        self.raw.format_properties(format)
+    }
+
+    fn image_format_properties(
+        &self,
+        format: format::Format,
+        dimensions: u8,
+        tiling: image::Tiling,
+        usage: image::Usage,
+        view_caps: image::ViewCapabilities,
+    ) -> Option<image::FormatProperties> {
+        self.raw
+            .image_format_properties(format, dimensions, tiling, usage, view_caps)
+    }
+
+    fn memory_properties(&self) -> adapter::MemoryProperties {
+        self.raw.memory_properties()
+    }
+
+    fn features(&self) -> Features {
+        self.raw.features()
+    }
+
+    fn limits(&self) -> Limits {
+        self.raw.limits()
+    }
+
+    fn is_valid_cache(&self, cache: &[u8]) -> bool {
+        self.raw.is_valid_cache(cache)
+    }
+}
+
+/// Command queue of the wrapped backend.
+#[derive(Debug)]
+pub struct CommandQueue<B: hal::Backend> {
+    raw: B::CommandQueue,
+    reporter: Arc<Reporter>,
+}
+
+impl<B: hal::Backend> q::CommandQueue<Backend<B>> for CommandQueue<B> {
+    unsafe fn submit<'a, T, Ic, S, Iw, Is>(
+        &mut self,
+        submission: q::Submission<Ic, Iw, Is>,
+        fence: Option<&B::Fence>,
+    ) where
+        T: 'a + Borrow<CommandBuffer<B>>,
+        Ic: IntoIterator<Item = &'a T>,
+        S: 'a + Borrow<B::Semaphore>,
+        Iw: IntoIterator<Item = (&'a S, hal::pso::PipelineStage)>,
+        Is: IntoIterator<Item = &'a S>,
+    {
+        let command_buffers = submission
+            .command_buffers
+            .into_iter()
+            .map(|cmd_buffer| {
+                let cmd_buffer = cmd_buffer.borrow();
+                cmd_buffer.check_submit(&self.reporter);
+                &cmd_buffer.raw
+            })
+            .collect::<Vec<_>>();
+        self.raw.submit(
+            q::Submission {
+                command_buffers,
+                wait_semaphores: submission.wait_semaphores,
+                signal_semaphores: submission.signal_semaphores,
+            },
+            fence,
+        )
+    }
+
+    unsafe fn present<'a, W, Is, S, Iw>(
+        &mut self,
+        swapchains: Is,
+        wait_semaphores: Iw,
+    ) -> Result<Option<window::Suboptimal>, window::PresentError>
+    where
+        W: 'a + Borrow<Swapchain<B>>,
+        Is: IntoIterator<Item = (&'a W, window::SwapImageIndex)>,
+        S: 'a + Borrow<B::Semaphore>,
+        Iw: IntoIterator<Item = &'a S>,
+    {
+        let swapchains = swapchains
+            .into_iter()
+            .map(|(swapchain, index)| (&swapchain.borrow().raw, index));
+        self.raw.present(swapchains, wait_semaphores)
+    }
+
+    unsafe fn present_surface(
+        &mut self,
+        surface: &mut Surface<B>,
+        image: <B::Surface as window::PresentationSurface<B>>::SwapchainImage,
+        wait_semaphore: Option<&B::Semaphore>,
+    ) -> Result<Option<window::Suboptimal>, window::PresentError> {
+        self.raw
+            .present_surface(&mut surface.raw, image, wait_semaphore)
+    }
+
+    fn wait_idle(&self) -> Result<(), d::OutOfMemory> {
+        self.raw.wait_idle()
+    }
+}
+
+/// Surface of the wrapped backend.
+#[derive(Debug)]
+pub struct Surface<B: hal::Backend> {
+    raw: B::Surface,
+}
+
+impl<B: hal::Backend> Surface<B> {
+    /// Wrap a surface created by the wrapped instance.
+    pub fn new(raw: B::Surface) -> Self {
+        Surface { raw }
+    }
+}
+
+impl<B: hal::Backend> window::Surface<Backend<B>> for Surface<B> {
+    fn supports_queue_family(&self, family: &B::QueueFamily) -> bool {
+        self.raw.supports_queue_family(family)
+    }
+
+    fn compatibility(
+        &self,
+        physical_device: &PhysicalDevice<B>,
+    ) -> (
+        window::SurfaceCapabilities,
+        Option<Vec<format::Format>>,
+        Vec<window::PresentMode>,
+    ) {
+        self.raw.compatibility(&physical_device.raw)
+    }
+}
+
+impl<B: hal::Backend> window::PresentationSurface<Backend<B>> for Surface<B> {
+    type SwapchainImage = <B::Surface as window::PresentationSurface<B>>::SwapchainImage;
+
+    unsafe fn configure_swapchain(
+        &mut self,
+        device: &Device<B>,
+        config: window::SwapchainConfig,
+    ) -> Result<(), window::CreationError> {
+        self.raw.configure_swapchain(&device.raw, config)
+    }
+
+    unsafe fn unconfigure_swapchain(&mut self, device: &Device<B>) {
+        self.raw.unconfigure_swapchain(&device.raw)
+    }
+
+    unsafe fn acquire_image(
+        &mut self,
+        timeout_ns: u64,
+    ) -> Result<(Self::SwapchainImage, Option<window::Suboptimal>), window::AcquireError> {
+        self.raw.acquire_image(timeout_ns)
+    }
+}
+
+/// Swapchain of the wrapped backend.
+#[derive(Debug)]
+pub struct Swapchain<B: hal::Backend> {
+    raw: B::Swapchain,
+}
+
+impl<B: hal::Backend> window::Swapchain<Backend<B>> for Swapchain<B> {
+    unsafe fn acquire_image(
+        &mut self,
+        timeout_ns: u64,
+        semaphore: Option<&B::Semaphore>,
+        fence: Option<&B::Fence>,
+    ) -> Result<(window::SwapImageIndex, Option<window::Suboptimal>), window::AcquireError> {
+        self.raw.acquire_image(timeout_ns, semaphore, fence)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hal::command::{self as com, CommandBuffer as _};
+    use hal::device::Device as _;
+    use hal::pool::{self, CommandPool as _};
+    use hal::queue::QueueFamily as _;
+    use hal::{buffer, memory, MemoryTypeId};
+
+    type Empty = gfx_backend_empty::Backend;
+
+    fn open() -> (Device<Empty>, q::QueueFamilyId) {
+        let instance = Instance::new(gfx_backend_empty::Instance::create("validation", 1).unwrap());
+        let (adapter, gpu) = gfx_backend_empty::test_utils::open(&instance);
+        (gpu.device, adapter.queue_families[0].id())
+    }
+
+    fn bound_buffer(
+        device: &Device<Empty>,
+        usage: buffer::Usage,
+        name: &str,
+    ) -> (Buffer<Empty>, Memory<Empty>) {
+        unsafe {
+            let mut buffer = device.create_buffer(256, usage).unwrap();
+            device.set_buffer_name(&mut buffer, name);
+            let requirements = device.get_buffer_requirements(&buffer);
+            let memory = device
+                .allocate_memory(MemoryTypeId(1), requirements.size)
+                .unwrap();
+            device.bind_buffer_memory(&memory, 0, &mut buffer).unwrap();
+            (buffer, memory)
+        }
+    }
+
+    fn recording(device: &Device<Empty>, family: q::QueueFamilyId) -> CommandBuffer<Empty> {
+        unsafe {
+            let mut pool = device
+                .create_command_pool(family, pool::CommandPoolCreateFlags::empty())
+                .unwrap();
+            let mut cmd_buffer = pool.allocate_one(com::Level::Primary);
+            device.set_command_buffer_name(&mut cmd_buffer, "main");
+            cmd_buffer.begin_primary(com::CommandBufferFlags::ONE_TIME_SUBMIT);
+            cmd_buffer
+        }
+    }
+
+    #[test]
+    fn test_buffer_usage() {
+        let (device, family) = open();
+        let usage = buffer::Usage::TRANSFER_SRC | buffer::Usage::TRANSFER_DST;
+        let (src, _src_memory) = bound_buffer(&device, usage, "src");
+        let (dst, _dst_memory) = bound_buffer(&device, buffer::Usage::UNIFORM, "uniforms");
+        let mut cmd_buffer = recording(&device, family);
+        let region = com::BufferCopy {
+            src: 0,
+            dst: 0,
+            size: 256,
+        };
+        unsafe {
+            cmd_buffer.copy_buffer(&src, &dst, Some(region));
+            cmd_buffer.copy_buffer(&src, &src, Some(region));
+        }
+
+        let violations = device.take_violations();
+        assert_eq!(violations.len(), 1, "{:?}", violations);
+        let message = &violations[0].message;
+        assert!(message.contains("command buffer 'main'"), "{}", message);
+        assert!(message.contains("buffer 'uniforms'"), "{}", message);
+        assert!(message.contains("TRANSFER_DST"), "{}", message);
+    }
+
+    #[test]
+    fn test_map_memory() {
+        let (device, _) = open();
+        unsafe {
+            // the first type of the CPU backend is only DEVICE_LOCAL
+            let memory = device.allocate_memory(MemoryTypeId(0), 256).unwrap();
+            let _ = device.map_memory(&memory, 0 .. 256);
+            device.unmap_memory(&memory);
+            device.unmap_memory(&memory);
+            let visible = device.allocate_memory(MemoryTypeId(1), 256).unwrap();
+            let _ = device.map_memory(&visible, 0 .. 256);
+            device.unmap_memory(&visible);
+        }
+
+        let violations = device.take_violations();
+        assert_eq!(violations.len(), 2, "{:?}", violations);
+        assert!(violations[0].message.contains("not CPU_VISIBLE"));
+        assert!(violations[1].message.contains("unmapped while not mapped"));
+    }
+
+    #[test]
+    fn test_render_pass_scope() {
+        let (device, family) = open();
+        let (dst, _memory) = bound_buffer(&device, buffer::Usage::TRANSFER_DST, "dst");
+        let mut cmd_buffer = recording(&device, family);
+        unsafe {
+            cmd_buffer.draw(0 .. 3, 0 .. 1);
+            cmd_buffer.fill_buffer(&dst, .., 0);
+            cmd_buffer.fill_buffer(&dst, 128 .. 512, 0);
+            cmd_buffer.finish();
+        }
+
+        let violations = device.take_violations();
+        assert_eq!(violations.len(), 2, "{:?}", violations);
+        assert!(violations[0]
+            .message
+            .contains("draw is recorded outside of a render pass"));
+        assert!(violations[1]
+            .message
+            .contains("accesses 128 .. 512 of buffer 'dst'"));
+    }
+
+    #[test]
+    fn test_image_layouts() {
+        let (device, family) = open();
+        let (dst, _dst_memory) = bound_buffer(&device, buffer::Usage::TRANSFER_DST, "readback");
+        let kind = image::Kind::D2(4, 4, 1, 1);
+        let (image, _memory) = unsafe {
+            let mut image = device
+                .create_image(
+                    kind,
+                    1,
+                    format::Format::Rgba8Unorm,
+                    image::Tiling::Optimal,
+                    image::Usage::TRANSFER_SRC,
+                    image::ViewCapabilities::empty(),
+                )
+                .unwrap();
+            device.set_image_name(&mut image, "color");
+            let requirements = device.get_image_requirements(&image);
+            let memory = device
+                .allocate_memory(MemoryTypeId(0), requirements.size)
+                .unwrap();
+            device.bind_image_memory(&memory, 0, &mut image).unwrap();
+            (image, memory)
+        };
+
+        let mut cmd_buffer = recording(&device, family);
+        let region = com::BufferImageCopy {
+            buffer_offset: 0,
+            buffer_width: 4,
+            buffer_height: 4,
+            image_layers: image::SubresourceLayers {
+                aspects: format::Aspects::COLOR,
+                level: 0,
+                layers: 0 .. 1,
+            },
+            image_offset: image::Offset::ZERO,
+            image_extent: kind.extent(),
+        };
+        unsafe {
+            cmd_buffer.pipeline_barrier(
+                hal::pso::PipelineStage::TOP_OF_PIPE .. hal::pso::PipelineStage::TRANSFER,
+                memory::Dependencies::empty(),
+                &[memory::Barrier::Image {
+                    states: (image::Access::empty(), image::Layout::Undefined)
+                        .. (
+                            image::Access::TRANSFER_READ,
+                            image::Layout::TransferSrcOptimal,
+                        ),
+                    target: &image,
+                    families: None,
+                    range: image::SubresourceRange {
+                        aspects: format::Aspects::COLOR,
+                        levels: 0 .. 1,
+                        layers: 0 .. 1,
+                    },
+                }],
+            );
+            cmd_buffer.copy_image_to_buffer(
+                &image,
+                image::Layout::TransferSrcOptimal,
+                &dst,
+                Some(&region),
+            );
+            cmd_buffer.copy_image_to_buffer(&image, image::Layout::General, &dst, &[region]);
+        }
+
+        let violations = device.take_violations();
+        assert_eq!(violations.len(), 1, "{:?}", violations);
+        let message = &violations[0].message;
+        assert!(
+            message.contains("image 'color' in layout General"),
+            "{}",
+            message
+        );
+        assert!(
+            message.contains("transitioned to TransferSrcOptimal"),
+            "{}",
+            message
+        );
+    }
+}
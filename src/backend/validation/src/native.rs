@@ -0,0 +1,106 @@
+use crate::Backend;
+use hal::{buffer, format, image, memory, pso, MemoryTypeId};
+
+use std::borrow::Borrow;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Identifiers of the wrapped objects, used to track them and to tell
+/// them apart in the messages when they have no name.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+pub(crate) fn next_id() -> usize {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+pub(crate) fn describe(kind: &str, id: usize, name: &Option<String>) -> String {
+    match *name {
+        Some(ref name) => format!("{} '{}'", kind, name),
+        None => format!("{} #{}", kind, id),
+    }
+}
+
+#[derive(Debug)]
+pub struct Memory<B: hal::Backend> {
+    pub(crate) raw: B::Memory,
+    pub(crate) id: usize,
+    pub(crate) type_id: MemoryTypeId,
+    pub(crate) properties: memory::Properties,
+    pub(crate) size: u64,
+    pub(crate) mapped: AtomicBool,
+}
+
+impl<B: hal::Backend> Memory<B> {
+    pub(crate) fn describe(&self) -> String {
+        format!("memory #{} of type {}", self.id, self.type_id.0)
+    }
+}
+
+#[derive(Debug)]
+pub struct Buffer<B: hal::Backend> {
+    pub(crate) raw: B::Buffer,
+    pub(crate) id: usize,
+    pub(crate) size: u64,
+    pub(crate) usage: buffer::Usage,
+    pub(crate) name: Option<String>,
+    pub(crate) bound: bool,
+}
+
+impl<B: hal::Backend> Buffer<B> {
+    pub(crate) fn describe(&self) -> String {
+        describe("buffer", self.id, &self.name)
+    }
+}
+
+#[derive(Debug)]
+pub struct Image<B: hal::Backend> {
+    pub(crate) raw: B::Image,
+    pub(crate) id: usize,
+    pub(crate) kind: image::Kind,
+    pub(crate) format: format::Format,
+    pub(crate) usage: image::Usage,
+    pub(crate) name: Option<String>,
+    pub(crate) bound: bool,
+}
+
+impl<B: hal::Backend> Image<B> {
+    pub(crate) fn describe(&self) -> String {
+        describe("image", self.id, &self.name)
+    }
+}
+
+#[derive(Debug)]
+pub struct DescriptorPool<B: hal::Backend> {
+    pub(crate) raw: B::DescriptorPool,
+}
+
+impl<B: hal::Backend> pso::DescriptorPool<Backend<B>> for DescriptorPool<B> {
+    unsafe fn allocate_set(
+        &mut self,
+        layout: &B::DescriptorSetLayout,
+    ) -> Result<B::DescriptorSet, pso::AllocationError> {
+        self.raw.allocate_set(layout)
+    }
+
+    unsafe fn allocate_sets<I>(
+        &mut self,
+        layouts: I,
+        sets: &mut Vec<B::DescriptorSet>,
+    ) -> Result<(), pso::AllocationError>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<B::DescriptorSetLayout>,
+    {
+        self.raw.allocate_sets(layouts, sets)
+    }
+
+    unsafe fn free_sets<I>(&mut self, descriptor_sets: I)
+    where
+        I: IntoIterator<Item = B::DescriptorSet>,
+    {
+        self.raw.free_sets(descriptor_sets)
+    }
+
+    unsafe fn reset(&mut self) {
+        self.raw.reset()
+    }
+}
@@ -0,0 +1,668 @@
+//! ASTC decoder for the LDR profile.
+
+use super::Bits;
+
+const ERROR_COLOR: [f32; 4] = [1.0, 0.0, 1.0, 1.0];
+
+/// Numbers of values of the integer sequence encodings, in increasing order.
+const LEVELS: [u32; 21] = [
+    2, 3, 4, 5, 6, 8, 10, 12, 16, 20, 24, 32, 40, 48, 64, 80, 96, 128, 160, 192, 256,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Encoding {
+    Bits,
+    Trits,
+    Quints,
+}
+
+/// Returns the encoding of values in `0 .. levels`, with the number of
+/// plain bits stored for each value.
+fn encoding(levels: u32) -> (Encoding, u32) {
+    if levels.is_multiple_of(3) {
+        (Encoding::Trits, (levels / 3).trailing_zeros())
+    } else if levels.is_multiple_of(5) {
+        (Encoding::Quints, (levels / 5).trailing_zeros())
+    } else {
+        (Encoding::Bits, levels.trailing_zeros())
+    }
+}
+
+/// Returns the number of bits of a sequence of `count` values.
+fn sequence_bits(count: u32, levels: u32) -> u32 {
+    match encoding(levels) {
+        (Encoding::Bits, bits) => count * bits,
+        (Encoding::Trits, bits) => count * bits + (8 * count).div_ceil(5),
+        (Encoding::Quints, bits) => count * bits + (7 * count).div_ceil(3),
+    }
+}
+
+fn decode_trits(packed: u32) -> [u32; 5] {
+    let bits = |high: u32, low: u32| (packed >> low) & ((1 << (high - low + 1)) - 1);
+    let (c, t4, t3) = if bits(4, 2) == 7 {
+        (bits(7, 5) << 2 | bits(1, 0), 2, 2)
+    } else if bits(6, 5) == 3 {
+        (bits(4, 0), 2, bits(7, 7))
+    } else {
+        (bits(4, 0), bits(7, 7), bits(6, 5))
+    };
+    let c_bit = |i: u32| (c >> i) & 1;
+    let (t2, t1, t0) = if c & 3 == 3 {
+        (2, c_bit(4), c_bit(3) << 1 | (c_bit(2) & !c_bit(3) & 1))
+    } else if (c >> 2) & 3 == 3 {
+        (2, 2, c & 3)
+    } else {
+        (
+            c_bit(4),
+            (c >> 2) & 3,
+            c_bit(1) << 1 | (c_bit(0) & !c_bit(1) & 1),
+        )
+    };
+    [t0, t1, t2, t3, t4]
+}
+
+fn decode_quints(packed: u32) -> [u32; 3] {
+    let bits = |high: u32, low: u32| (packed >> low) & ((1 << (high - low + 1)) - 1);
+    let bit = |i: u32| (packed >> i) & 1;
+    if bits(2, 1) == 3 && bits(6, 5) == 0 {
+        let q2 = bit(0) << 2 | (bit(4) & !bit(0) & 1) << 1 | (bit(3) & !bit(0) & 1);
+        return [4, 4, q2];
+    }
+    let (q2, c) = if bits(2, 1) == 3 {
+        (4, bits(4, 3) << 3 | (!bits(6, 5) & 3) << 1 | bit(0))
+    } else {
+        (bits(6, 5), bits(4, 0))
+    };
+    if c & 7 == 5 {
+        [(c >> 3) & 3, 4, q2]
+    } else {
+        [c & 7, (c >> 3) & 3, q2]
+    }
+}
+
+/// Decode a sequence of `values.len()` values in `0 .. levels`.
+fn decode_sequence(bits: &mut Bits, levels: u32, values: &mut [u32]) {
+    let (encoding, count) = encoding(levels);
+    // Bits of the packed trits or quints stored after each value of a group.
+    let packed_bits: &[u32] = match encoding {
+        Encoding::Bits => {
+            for value in values.iter_mut() {
+                *value = bits.read(count);
+            }
+            return;
+        }
+        Encoding::Trits => &[2, 2, 1, 2, 1],
+        Encoding::Quints => &[3, 2, 2],
+    };
+    for group in values.chunks_mut(packed_bits.len()) {
+        let (mut packed, mut position) = (0, 0);
+        for (value, &packed_count) in group.iter_mut().zip(packed_bits) {
+            *value = bits.read(count);
+            packed |= bits.read(packed_count) << position;
+            position += packed_count;
+        }
+        let digits = match encoding {
+            Encoding::Trits => decode_trits(packed),
+            _ => {
+                let [q0, q1, q2] = decode_quints(packed);
+                [q0, q1, q2, 0, 0]
+            }
+        };
+        for (value, &digit) in group.iter_mut().zip(&digits) {
+            *value |= digit << count;
+        }
+    }
+}
+
+/// Replicate the `bits` bits of `value` to fill `target` bits.
+fn replicate(value: u32, bits: u32, target: u32) -> u32 {
+    let mut result = 0;
+    let mut filled = 0;
+    while filled < target {
+        let shift = target - filled;
+        result |= if shift >= bits {
+            value << (shift - bits)
+        } else {
+            value >> (bits - shift)
+        };
+        filled += bits;
+    }
+    result
+}
+
+/// Unquantize a color endpoint value to 8 bits.
+fn unquantize_color(value: u32, levels: u32) -> u32 {
+    let (encoding, count) = encoding(levels);
+    if encoding == Encoding::Bits {
+        return replicate(value, count, 8);
+    }
+    let m = value & ((1 << count) - 1);
+    let bit = |i: u32| (m >> i) & 1;
+    let (b, c) = match (encoding, count) {
+        (Encoding::Trits, 1) => (0, 204),
+        (Encoding::Quints, 1) => (0, 113),
+        (Encoding::Trits, 2) => (bit(1) * 0x116, 93),
+        (Encoding::Quints, 2) => (bit(1) * 0x10C, 54),
+        (Encoding::Trits, 3) => (bit(2) * 0x10A + bit(1) * 0x85, 44),
+        (Encoding::Quints, 3) => (bit(2) * 0x105 + bit(1) * 0x82, 26),
+        (Encoding::Trits, 4) => (bit(3) * 0x104 + bit(2) * 0x82 + bit(1) * 0x41, 22),
+        (Encoding::Quints, 4) => (bit(3) * 0x102 + bit(2) * 0x81 + bit(1) * 0x40, 13),
+        (Encoding::Trits, 5) => (
+            bit(4) * 0x102 + bit(3) * 0x81 + bit(2) * 0x40 + bit(1) * 0x20,
+            11,
+        ),
+        (Encoding::Quints, 5) => (
+            bit(4) * 0x101 + bit(3) * 0x80 + bit(2) * 0x40 + bit(1) * 0x20,
+            6,
+        ),
+        _ => (
+            bit(5) * 0x101 + bit(4) * 0x80 + bit(3) * 0x40 + bit(2) * 0x20 + bit(1) * 0x10,
+            5,
+        ),
+    };
+    let a = if m & 1 != 0 { 0x1FF } else { 0 };
+    let t = ((value >> count) * c + b) ^ a;
+    (a & 0x80) | (t >> 2)
+}
+
+/// Unquantize a weight to `0 ..= 64`.
+fn unquantize_weight(value: u32, levels: u32) -> u32 {
+    let (encoding, count) = encoding(levels);
+    let m = value & ((1 << count) - 1);
+    let bit = |i: u32| (m >> i) & 1;
+    let a = if m & 1 != 0 { 0x7F } else { 0 };
+    let unquantized = match (encoding, count) {
+        (Encoding::Bits, _) => replicate(value, count, 6),
+        (Encoding::Trits, 0) => [0, 32, 63][value as usize],
+        (Encoding::Quints, 0) => [0, 16, 32, 47, 63][value as usize],
+        (encoding, _) => {
+            let (b, c) = match (encoding, count) {
+                (Encoding::Trits, 1) => (0, 50),
+                (Encoding::Quints, 1) => (0, 28),
+                (Encoding::Trits, 2) => (bit(1) * 0x45, 23),
+                (Encoding::Quints, 2) => (bit(1) * 0x42, 13),
+                _ => (bit(2) * 0x42 + bit(1) * 0x21, 11),
+            };
+            let t = ((value >> count) * c + b) ^ a;
+            (a & 0x20) | (t >> 2)
+        }
+    };
+    if unquantized > 32 {
+        unquantized + 1
+    } else {
+        unquantized
+    }
+}
+
+struct BlockMode {
+    width: u32,
+    height: u32,
+    dual_plane: bool,
+    /// Number of values of the weights.
+    levels: u32,
+}
+
+fn block_mode(mode: u32) -> Option<BlockMode> {
+    let bits = |low: u32, count: u32| (mode >> low) & ((1 << count) - 1);
+    let (a, b) = (bits(5, 2), bits(7, 2));
+    let (range, width, height) = if bits(0, 2) != 0 {
+        let (width, height) = match bits(2, 2) {
+            0 => (b + 4, a + 2),
+            1 => (b + 8, a + 2),
+            2 => (a + 2, b + 8),
+            _ if bits(8, 1) == 0 => (a + 2, bits(7, 1) + 6),
+            _ => (bits(7, 1) + 2, a + 2),
+        };
+        (bits(4, 1) | bits(0, 2) << 1, width, height)
+    } else {
+        if bits(2, 2) == 0 {
+            return None;
+        }
+        let (width, height) = match bits(7, 2) {
+            0 => (12, a + 2),
+            1 => (a + 2, 12),
+            2 => (a + 6, bits(9, 2) + 6),
+            _ => match a {
+                0 => (6, 10),
+                1 => (10, 6),
+                _ => return None,
+            },
+        };
+        (bits(4, 1) | bits(2, 2) << 1, width, height)
+    };
+    // The bits 9 and 10 are part of the grid height in the `A + 6, B + 6` mode.
+    let (high_precision, dual_plane) = if bits(0, 2) == 0 && bits(7, 2) == 2 {
+        (false, false)
+    } else {
+        (bits(9, 1) == 1, bits(10, 1) == 1)
+    };
+    let levels = match (high_precision, range) {
+        (false, 2) => 2,
+        (false, 3) => 3,
+        (false, 4) => 4,
+        (false, 5) => 5,
+        (false, 6) => 6,
+        (false, _) => 8,
+        (true, 2) => 10,
+        (true, 3) => 12,
+        (true, 4) => 16,
+        (true, 5) => 20,
+        (true, 6) => 24,
+        (true, _) => 32,
+    };
+    Some(BlockMode {
+        width,
+        height,
+        dual_plane,
+        levels,
+    })
+}
+
+fn hash52(mut p: u32) -> u32 {
+    p ^= p >> 15;
+    p = p.wrapping_sub(p << 17);
+    p = p.wrapping_add(p << 7);
+    p = p.wrapping_add(p << 4);
+    p ^= p >> 5;
+    p = p.wrapping_add(p << 16);
+    p ^= p >> 7;
+    p ^= p >> 3;
+    p ^= p << 6;
+    p ^= p >> 17;
+    p
+}
+
+/// Returns the partition of a texel, from the partition index of the block.
+fn select_partition(seed: u32, x: u32, y: u32, count: u32, small_block: bool) -> usize {
+    let (x, y) = if small_block {
+        (x << 1, y << 1)
+    } else {
+        (x, y)
+    };
+    let seed = seed + (count - 1) * 1024;
+    let rnum = hash52(seed);
+    let mut seeds = [0u32; 12];
+    for (i, value) in seeds[.. 8].iter_mut().enumerate() {
+        *value = (rnum >> (4 * i)) & 0xF;
+    }
+    seeds[8] = (rnum >> 18) & 0xF;
+    seeds[9] = (rnum >> 22) & 0xF;
+    seeds[10] = (rnum >> 26) & 0xF;
+    seeds[11] = rnum.rotate_left(2) & 0xF;
+    let odd_shift = if seed & 2 != 0 { 4 } else { 5 };
+    let count_shift = if count == 3 { 6 } else { 5 };
+    let (sh1, sh2) = if seed & 1 != 0 {
+        (odd_shift, count_shift)
+    } else {
+        (count_shift, odd_shift)
+    };
+    let sh3 = if seed & 0x10 != 0 { sh1 } else { sh2 };
+    for (i, value) in seeds.iter_mut().enumerate() {
+        let shift = match i {
+            0 | 2 | 4 | 6 => sh1,
+            1 | 3 | 5 | 7 => sh2,
+            _ => sh3,
+        };
+        *value = (*value * *value) >> shift;
+    }
+    // Texels are in a single slice, `z` is always 0.
+    let a = (seeds[0] * x + seeds[1] * y + (rnum >> 14)) & 0x3F;
+    let b = (seeds[2] * x + seeds[3] * y + (rnum >> 10)) & 0x3F;
+    let c = if count < 3 {
+        0
+    } else {
+        (seeds[4] * x + seeds[5] * y + (rnum >> 6)) & 0x3F
+    };
+    let d = if count < 4 {
+        0
+    } else {
+        (seeds[6] * x + seeds[7] * y + (rnum >> 2)) & 0x3F
+    };
+    if a >= b && a >= c && a >= d {
+        0
+    } else if b >= c && b >= d {
+        1
+    } else if c >= d {
+        2
+    } else {
+        3
+    }
+}
+
+fn bit_transfer_signed(a: i32, b: i32) -> (i32, i32) {
+    let b = (b >> 1) | (a & 0x80);
+    let a = (a >> 1) & 0x3F;
+    let a = if a & 0x20 != 0 { a - 0x40 } else { a };
+    (a, b)
+}
+
+fn blue_contract(r: i32, g: i32, b: i32, a: i32) -> [i32; 4] {
+    [(r + b) >> 1, (g + b) >> 1, b, a]
+}
+
+/// Decode the endpoints of a color endpoint mode from its values. Returns
+/// `None` for the HDR modes.
+fn decode_endpoints(mode: u32, v: &[i32]) -> Option<[[i32; 4]; 2]> {
+    let endpoints = match mode {
+        0 => [[v[0], v[0], v[0], 255], [v[1], v[1], v[1], 255]],
+        1 => {
+            let l0 = (v[0] >> 2) | (v[1] & 0xC0);
+            let l1 = (l0 + (v[1] & 0x3F)).min(255);
+            [[l0, l0, l0, 255], [l1, l1, l1, 255]]
+        }
+        4 => [[v[0], v[0], v[0], v[2]], [v[1], v[1], v[1], v[3]]],
+        5 => {
+            let (d0, l0) = bit_transfer_signed(v[1], v[0]);
+            let (d1, a0) = bit_transfer_signed(v[3], v[2]);
+            let l1 = l0 + d0;
+            [[l0, l0, l0, a0], [l1, l1, l1, a0 + d1]]
+        }
+        6 => [
+            [
+                (v[0] * v[3]) >> 8,
+                (v[1] * v[3]) >> 8,
+                (v[2] * v[3]) >> 8,
+                255,
+            ],
+            [v[0], v[1], v[2], 255],
+        ],
+        8 | 12 => {
+            let (a0, a1) = if mode == 12 { (v[6], v[7]) } else { (255, 255) };
+            if v[1] + v[3] + v[5] >= v[0] + v[2] + v[4] {
+                [[v[0], v[2], v[4], a0], [v[1], v[3], v[5], a1]]
+            } else {
+                [
+                    blue_contract(v[1], v[3], v[5], a1),
+                    blue_contract(v[0], v[2], v[4], a0),
+                ]
+            }
+        }
+        9 | 13 => {
+            let (dr, r) = bit_transfer_signed(v[1], v[0]);
+            let (dg, g) = bit_transfer_signed(v[3], v[2]);
+            let (db, b) = bit_transfer_signed(v[5], v[4]);
+            let (da, a) = if mode == 13 {
+                bit_transfer_signed(v[7], v[6])
+            } else {
+                (0, 255)
+            };
+            if dr + dg + db >= 0 {
+                [[r, g, b, a], [r + dr, g + dg, b + db, a + da]]
+            } else {
+                [
+                    blue_contract(r + dr, g + dg, b + db, a + da),
+                    blue_contract(r, g, b, a),
+                ]
+            }
+        }
+        10 => [
+            [
+                (v[0] * v[3]) >> 8,
+                (v[1] * v[3]) >> 8,
+                (v[2] * v[3]) >> 8,
+                v[4],
+            ],
+            [v[0], v[1], v[2], v[5]],
+        ],
+        _ => return None,
+    };
+    let mut clamped = endpoints;
+    for value in clamped.iter_mut().flat_map(|endpoint| endpoint.iter_mut()) {
+        *value = (*value).clamp(0, 255);
+    }
+    Some(clamped)
+}
+
+pub fn decode(block: &[u8], texels: &mut [[f32; 4]], width: u32, height: u32, srgb: bool) {
+    if decode_texels(block, texels, width, height, srgb).is_none() {
+        for texel in texels.iter_mut() {
+            *texel = ERROR_COLOR;
+        }
+    }
+}
+
+fn decode_texels(
+    block: &[u8],
+    texels: &mut [[f32; 4]],
+    width: u32,
+    height: u32,
+    srgb: bool,
+) -> Option<()> {
+    let value = Bits::new(block).value;
+    let bits = |low: u32, count: u32| (value >> low) as u32 & ((1u64 << count) - 1) as u32;
+
+    if bits(0, 9) == 0x1FC {
+        // Void-extent block of a constant color, HDR ones are errors.
+        if bits(9, 1) != 0 {
+            return None;
+        }
+        for texel in texels.iter_mut() {
+            for (c, value) in texel.iter_mut().enumerate() {
+                let unorm = bits(64 + 16 * c as u32, 16);
+                *value = if srgb {
+                    (unorm >> 8) as f32 / 255.0
+                } else {
+                    unorm as f32 / 65535.0
+                };
+            }
+        }
+        return Some(());
+    }
+
+    let mode = block_mode(bits(0, 11))?;
+    let planes = if mode.dual_plane { 2 } else { 1 };
+    let weight_count = mode.width * mode.height * planes;
+    let weight_bits = sequence_bits(weight_count, mode.levels);
+    if mode.width > width || mode.height > height || weight_count > 64 {
+        return None;
+    }
+    if !(24 ..= 96).contains(&weight_bits) {
+        return None;
+    }
+
+    let partitions = bits(11, 2) + 1;
+    if mode.dual_plane && partitions == 4 {
+        return None;
+    }
+    let mut below_weights = 128 - weight_bits;
+    let mut endpoint_modes = [0; 4];
+    let (partition_index, color_start) = if partitions == 1 {
+        endpoint_modes[0] = bits(13, 4);
+        (0, 17)
+    } else {
+        let selector = bits(23, 6);
+        if selector & 3 == 0 {
+            for endpoint_mode in endpoint_modes.iter_mut() {
+                *endpoint_mode = selector >> 2;
+            }
+        } else {
+            let extra_bits = 3 * partitions - 4;
+            below_weights -= extra_bits;
+            let selector = selector | bits(below_weights, extra_bits) << 6;
+            let class = (selector & 3) - 1;
+            for (i, endpoint_mode) in endpoint_modes[.. partitions as usize]
+                .iter_mut()
+                .enumerate()
+            {
+                let i = i as u32;
+                let offset = (selector >> (2 + i)) & 1;
+                let m = (selector >> (2 + partitions + 2 * i)) & 3;
+                *endpoint_mode = (class + offset) << 2 | m;
+            }
+        }
+        (bits(13, 10), 29)
+    };
+    let plane_component = if mode.dual_plane {
+        below_weights -= 2;
+        Some(bits(below_weights, 2) as usize)
+    } else {
+        None
+    };
+
+    let color_count: u32 = endpoint_modes[.. partitions as usize]
+        .iter()
+        .map(|mode| ((mode >> 2) + 1) * 2)
+        .sum();
+    if color_count > 18 || below_weights < color_start {
+        return None;
+    }
+    let color_levels = *LEVELS
+        .iter()
+        .rev()
+        .find(|&&levels| sequence_bits(color_count, levels) <= below_weights - color_start)?;
+    if color_levels < 6 {
+        return None;
+    }
+    let mut colors = [0u32; 18];
+    let mut reader = Bits {
+        value,
+        position: color_start,
+    };
+    decode_sequence(
+        &mut reader,
+        color_levels,
+        &mut colors[.. color_count as usize],
+    );
+
+    let mut endpoints = [[[0; 4]; 2]; 4];
+    let mut offset = 0;
+    for (endpoint, &endpoint_mode) in endpoints
+        .iter_mut()
+        .zip(&endpoint_modes[.. partitions as usize])
+    {
+        let count = (((endpoint_mode >> 2) + 1) * 2) as usize;
+        let mut values = [0i32; 8];
+        for (value, &color) in values.iter_mut().zip(&colors[offset .. offset + count]) {
+            *value = unquantize_color(color, color_levels) as i32;
+        }
+        *endpoint = decode_endpoints(endpoint_mode, &values)?;
+        offset += count;
+    }
+
+    let mut weights = [0u32; 64];
+    let mut reader = Bits {
+        value: value.reverse_bits(),
+        position: 0,
+    };
+    decode_sequence(
+        &mut reader,
+        mode.levels,
+        &mut weights[.. weight_count as usize],
+    );
+    for weight in weights[.. weight_count as usize].iter_mut() {
+        *weight = unquantize_weight(*weight, mode.levels);
+    }
+
+    let ds = (1024 + width / 2) / (width - 1);
+    let dt = (1024 + height / 2) / (height - 1);
+    let small_block = width * height < 31;
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let (x, y) = (i as u32 % width, i as u32 / width);
+        let partition = if partitions == 1 {
+            0
+        } else {
+            select_partition(partition_index, x, y, partitions, small_block)
+        };
+
+        // Bilinear infill of the weight grid.
+        let gs = (ds * x * (mode.width - 1) + 32) >> 6;
+        let gt = (dt * y * (mode.height - 1) + 32) >> 6;
+        let (js, fs) = (gs >> 4, gs & 0xF);
+        let (jt, ft) = (gt >> 4, gt & 0xF);
+        let w11 = (fs * ft + 8) >> 4;
+        let factors = [16 + w11 - fs - ft, fs - w11, ft - w11, w11];
+        let base = js + jt * mode.width;
+        let grid = [base, base + 1, base + mode.width, base + mode.width + 1];
+        let mut plane_weights = [0; 2];
+        for (plane, weight) in plane_weights[.. planes as usize].iter_mut().enumerate() {
+            let sum: u32 = grid
+                .iter()
+                .zip(&factors)
+                .filter(|&(_, &factor)| factor != 0)
+                .map(|(&index, &factor)| weights[(index * planes) as usize + plane] * factor)
+                .sum();
+            *weight = (sum + 8) >> 4;
+        }
+
+        let [e0, e1] = endpoints[partition];
+        for (c, value) in texel.iter_mut().enumerate() {
+            let w = if plane_component == Some(c) {
+                plane_weights[1]
+            } else {
+                plane_weights[0]
+            } as i32;
+            let (c0, c1) = if srgb {
+                (e0[c] << 8 | 0x80, e1[c] << 8 | 0x80)
+            } else {
+                (e0[c] << 8 | e0[c], e1[c] << 8 | e1[c])
+            };
+            let interpolated = (c0 * (64 - w) + c1 * w + 32) >> 6;
+            *value = if srgb {
+                (interpolated >> 8) as f32 / 255.0
+            } else {
+                interpolated as f32 / 65535.0
+            };
+        }
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_integer_sequence() {
+        // Every combination of digits has an encoding.
+        let mut trits = [false; 243];
+        for packed in 0 .. 256 {
+            let t = decode_trits(packed);
+            trits[(t[0] + 3 * t[1] + 9 * t[2] + 27 * t[3] + 81 * t[4]) as usize] = true;
+        }
+        assert!(trits.iter().all(|&found| found));
+        let mut quints = [false; 125];
+        for packed in 0 .. 128 {
+            let q = decode_quints(packed);
+            quints[(q[0] + 5 * q[1] + 25 * q[2]) as usize] = true;
+        }
+        assert!(quints.iter().all(|&found| found));
+
+        let unquantized: Vec<_> = (0 .. 6).map(|v| unquantize_color(v, 6)).collect();
+        assert_eq!(unquantized, [0, 255, 51, 204, 102, 153]);
+        let unquantized: Vec<_> = (0 .. 6).map(|v| unquantize_weight(v, 6)).collect();
+        assert_eq!(unquantized, [0, 64, 12, 52, 25, 39]);
+    }
+
+    #[test]
+    fn test_void_extent() {
+        let mut texels = [[0.0; 4]; 16];
+        let value = 0xFFFF_8000_0000_FFFF_FFFF_FFFF_FFFF_FDFCu128;
+        decode(&value.to_le_bytes(), &mut texels, 4, 4, false);
+        assert_eq!(texels[5], [1.0, 0.0, 32768.0 / 65535.0, 1.0]);
+    }
+
+    #[test]
+    fn test_decode() {
+        let mut texels = [[0.0; 4]; 36];
+        // 4x4 grid of 2-bit weights, a single partition of RGB endpoints
+        // from black to white, only the first weight is set.
+        let mut value = 66 | 8 << 13;
+        for (i, &color) in [0u128, 255, 0, 255, 0, 255].iter().enumerate() {
+            value |= color << (17 + 8 * i);
+        }
+        value |= 3 << 126;
+        decode(&value.to_le_bytes(), &mut texels, 4, 4, false);
+        assert_eq!(texels[0], [1.0; 4]);
+        assert_eq!(texels[1], [0.0, 0.0, 0.0, 1.0]);
+
+        // The same block interpolated over 6x6 texels.
+        decode(&value.to_le_bytes(), &mut texels, 6, 6, false);
+        assert_eq!(texels[0], [1.0; 4]);
+        assert!(texels[1][0] > 0.0 && texels[1][0] < 1.0);
+        assert_eq!(texels[35], [0.0, 0.0, 0.0, 1.0]);
+
+        // Reserved block mode.
+        decode(&[0; 16], &mut texels[.. 16], 4, 4, false);
+        assert_eq!(texels[0], ERROR_COLOR);
+    }
+}
@@ -0,0 +1,1046 @@
+//! BC1 to BC7 decoders.
+
+use super::{expand, Bits};
+use crate::format::texel::f16_to_f32;
+
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+fn weight(bits: u32, index: u32) -> u32 {
+    match bits {
+        2 => WEIGHTS_2[index as usize],
+        3 => WEIGHTS_3[index as usize],
+        _ => WEIGHTS_4[index as usize],
+    }
+}
+
+/// Subsets of the texels for the 2-subset partitions, one bit per texel.
+/// BC6H uses the first 32 of them.
+const PARTITIONS_2: [u16; 64] = [
+    0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80, 0xC800, 0xFFEC, 0xFE80, 0xE800,
+    0xFFE8, 0xFF00, 0xFFF0, 0xF000, 0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE,
+    0x088C, 0x3110, 0x6666, 0x366C, 0x17E8, 0x0FF0, 0x718E, 0x399C, 0xAAAA, 0xF0F0, 0x5A5A, 0x33CC,
+    0x3C3C, 0x55AA, 0x9696, 0xA55A, 0x73CE, 0x13C8, 0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660,
+    0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C, 0x9336, 0x9CC6, 0x817E, 0xE718,
+    0xCCF0, 0x0FCC, 0x7744, 0xEE22,
+];
+
+/// Subsets of the texels for the 3-subset partitions.
+const PARTITIONS_3: [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+    [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2],
+    [0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+    [0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0],
+    [0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0],
+    [0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+    [0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+    [0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2],
+    [0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0],
+    [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+    [0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0],
+    [0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1],
+    [0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1],
+    [0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+    [0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2],
+    [0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2],
+    [0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+    [0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+    [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1],
+    [0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+/// Anchor texel of the second subset of the 2-subset partitions.
+const ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, //
+    15, 2, 8, 2, 2, 8, 8, 15, 2, 8, 2, 2, 8, 8, 2, 2, //
+    15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, //
+    6, 2, 6, 8, 15, 15, 2, 2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// Anchor texels of the second and third subsets of the 3-subset partitions.
+const ANCHORS_3: [[u8; 64]; 2] = [
+    [
+        3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3, //
+        3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5, 15, 15, //
+        8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15, //
+        3, 15, 5, 5, 5, 8, 5, 10, 5, 10, 8, 13, 15, 12, 3, 3,
+    ],
+    [
+        15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8, //
+        15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6, 10, 15, 15, 10, 8, //
+        15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8, //
+        15, 3, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+    ],
+];
+
+fn subset(subsets: u32, partition: u32, texel: usize) -> usize {
+    match subsets {
+        1 => 0,
+        2 => (PARTITIONS_2[partition as usize] >> texel) as usize & 1,
+        _ => PARTITIONS_3[partition as usize][texel] as usize,
+    }
+}
+
+fn is_anchor(subsets: u32, partition: u32, texel: usize) -> bool {
+    let partition = partition as usize;
+    texel == 0
+        || match subsets {
+            1 => false,
+            2 => ANCHORS_2[partition] as usize == texel,
+            _ => ANCHORS_3
+                .iter()
+                .any(|anchors| anchors[partition] as usize == texel),
+        }
+}
+
+fn rgb565(color: u16) -> [f32; 4] {
+    [
+        (color >> 11) as f32 / 31.0,
+        ((color >> 5) & 0x3F) as f32 / 63.0,
+        (color & 0x1F) as f32 / 31.0,
+        1.0,
+    ]
+}
+
+fn mix(a: [f32; 4], b: [f32; 4], wa: f32, wb: f32, total: f32) -> [f32; 4] {
+    let mut value = a;
+    for (value, &b) in value.iter_mut().zip(&b) {
+        *value = (*value * wa + b * wb) / total;
+    }
+    value
+}
+
+/// Decode the color part of BC1 to BC3 blocks. `punch_through` is set for
+/// BC1 blocks, where the order of the endpoints selects 3 colors and a
+/// black texel, transparent if `punch_through` is `Some(true)`.
+fn decode_colors(block: &[u8], texels: &mut [[f32; 4]], punch_through: Option<bool>) {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (e0, e1) = (rgb565(c0), rgb565(c1));
+    let palette = match punch_through {
+        Some(alpha) if c0 <= c1 => [
+            e0,
+            e1,
+            mix(e0, e1, 1.0, 1.0, 2.0),
+            [0.0, 0.0, 0.0, if alpha { 0.0 } else { 1.0 }],
+        ],
+        _ => [
+            e0,
+            e1,
+            mix(e0, e1, 2.0, 1.0, 3.0),
+            mix(e0, e1, 1.0, 2.0, 3.0),
+        ],
+    };
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    for (i, texel) in texels.iter_mut().enumerate() {
+        *texel = palette[(indices >> (2 * i)) as usize & 3];
+    }
+}
+
+/// Decode a single channel block of BC3, BC4 and BC5.
+fn decode_channel(block: &[u8], signed: bool) -> [f32; 16] {
+    let (v0, v1) = if signed {
+        (block[0] as i8 as i32, block[1] as i8 as i32)
+    } else {
+        (block[0] as i32, block[1] as i32)
+    };
+    let (min, max, scale) = if signed {
+        (-1.0, 1.0, 127.0)
+    } else {
+        (0.0, 1.0, 255.0)
+    };
+    let e0 = (v0 as f32 / scale).max(min);
+    let e1 = (v1 as f32 / scale).max(min);
+    let mut palette = [e0, e1, 0.0, 0.0, 0.0, 0.0, min, max];
+    if v0 > v1 {
+        for (i, value) in palette[2 ..].iter_mut().enumerate() {
+            let w = i as f32 + 1.0;
+            *value = (e0 * (7.0 - w) + e1 * w) / 7.0;
+        }
+    } else {
+        for (i, value) in palette[2 .. 6].iter_mut().enumerate() {
+            let w = i as f32 + 1.0;
+            *value = (e0 * (5.0 - w) + e1 * w) / 5.0;
+        }
+    }
+    let mut bytes = [0; 8];
+    bytes[.. 6].copy_from_slice(&block[2 .. 8]);
+    let indices = u64::from_le_bytes(bytes);
+    let mut values = [0.0; 16];
+    for (i, value) in values.iter_mut().enumerate() {
+        *value = palette[(indices >> (3 * i)) as usize & 7];
+    }
+    values
+}
+
+pub fn decode_bc1(block: &[u8], texels: &mut [[f32; 4]], alpha: bool) {
+    decode_colors(block, texels, Some(alpha));
+}
+
+pub fn decode_bc2(block: &[u8], texels: &mut [[f32; 4]]) {
+    decode_colors(&block[8 ..], texels, None);
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&block[.. 8]);
+    let alpha = u64::from_le_bytes(bytes);
+    for (i, texel) in texels.iter_mut().enumerate() {
+        texel[3] = ((alpha >> (4 * i)) & 0xF) as f32 / 15.0;
+    }
+}
+
+pub fn decode_bc3(block: &[u8], texels: &mut [[f32; 4]]) {
+    decode_colors(&block[8 ..], texels, None);
+    let alpha = decode_channel(block, false);
+    for (texel, &alpha) in texels.iter_mut().zip(&alpha) {
+        texel[3] = alpha;
+    }
+}
+
+pub fn decode_bc4(block: &[u8], texels: &mut [[f32; 4]], signed: bool) {
+    let red = decode_channel(block, signed);
+    for (texel, &red) in texels.iter_mut().zip(&red) {
+        *texel = [red, 0.0, 0.0, 1.0];
+    }
+}
+
+pub fn decode_bc5(block: &[u8], texels: &mut [[f32; 4]], signed: bool) {
+    let red = decode_channel(block, signed);
+    let green = decode_channel(&block[8 ..], signed);
+    for (texel, (&red, &green)) in texels.iter_mut().zip(red.iter().zip(&green)) {
+        *texel = [red, green, 0.0, 1.0];
+    }
+}
+
+// Endpoint components of BC6H blocks: red, green and blue of the endpoints
+// `w` and `x` of the first region, then `y` and `z` of the second one.
+const RW: u8 = 0;
+const GW: u8 = 1;
+const BW: u8 = 2;
+const RX: u8 = 3;
+const GX: u8 = 4;
+const BX: u8 = 5;
+const RY: u8 = 6;
+const GY: u8 = 7;
+const BY: u8 = 8;
+const RZ: u8 = 9;
+const GZ: u8 = 10;
+const BZ: u8 = 11;
+
+struct Bc6Mode {
+    /// Value of the mode bits.
+    value: u32,
+    transformed: bool,
+    endpoint_bits: u32,
+    delta_bits: [u32; 3],
+    /// Bits of the endpoints stored after the mode, as `(component, first, last)`.
+    /// Some ranges are stored from their highest bit to their lowest.
+    layout: &'static [(u8, u8, u8)],
+}
+
+impl Bc6Mode {
+    fn regions(&self) -> usize {
+        if self.value & 3 == 3 {
+            1
+        } else {
+            2
+        }
+    }
+}
+
+const BC6_MODES: [Bc6Mode; 14] = [
+    Bc6Mode {
+        value: 0,
+        transformed: true,
+        endpoint_bits: 10,
+        delta_bits: [5, 5, 5],
+        layout: &[
+            (GY, 4, 4),
+            (BY, 4, 4),
+            (BZ, 4, 4),
+            (RW, 0, 9),
+            (GW, 0, 9),
+            (BW, 0, 9),
+            (RX, 0, 4),
+            (GZ, 4, 4),
+            (GY, 0, 3),
+            (GX, 0, 4),
+            (BZ, 0, 0),
+            (GZ, 0, 3),
+            (BX, 0, 4),
+            (BZ, 1, 1),
+            (BY, 0, 3),
+            (RY, 0, 4),
+            (BZ, 2, 2),
+            (RZ, 0, 4),
+            (BZ, 3, 3),
+        ],
+    },
+    Bc6Mode {
+        value: 1,
+        transformed: true,
+        endpoint_bits: 7,
+        delta_bits: [6, 6, 6],
+        layout: &[
+            (GY, 5, 5),
+            (GZ, 4, 5),
+            (RW, 0, 6),
+            (BZ, 0, 1),
+            (BY, 4, 4),
+            (GW, 0, 6),
+            (BY, 5, 5),
+            (BZ, 2, 2),
+            (GY, 4, 4),
+            (BW, 0, 6),
+            (BZ, 3, 3),
+            (BZ, 5, 5),
+            (BZ, 4, 4),
+            (RX, 0, 5),
+            (GY, 0, 3),
+            (GX, 0, 5),
+            (GZ, 0, 3),
+            (BX, 0, 5),
+            (BY, 0, 3),
+            (RY, 0, 5),
+            (RZ, 0, 5),
+        ],
+    },
+    Bc6Mode {
+        value: 2,
+        transformed: true,
+        endpoint_bits: 11,
+        delta_bits: [5, 4, 4],
+        layout: &[
+            (RW, 0, 9),
+            (GW, 0, 9),
+            (BW, 0, 9),
+            (RX, 0, 4),
+            (RW, 10, 10),
+            (GY, 0, 3),
+            (GX, 0, 3),
+            (GW, 10, 10),
+            (BZ, 0, 0),
+            (GZ, 0, 3),
+            (BX, 0, 3),
+            (BW, 10, 10),
+            (BZ, 1, 1),
+            (BY, 0, 3),
+            (RY, 0, 4),
+            (BZ, 2, 2),
+            (RZ, 0, 4),
+            (BZ, 3, 3),
+        ],
+    },
+    Bc6Mode {
+        value: 6,
+        transformed: true,
+        endpoint_bits: 11,
+        delta_bits: [4, 5, 4],
+        layout: &[
+            (RW, 0, 9),
+            (GW, 0, 9),
+            (BW, 0, 9),
+            (RX, 0, 3),
+            (RW, 10, 10),
+            (GZ, 4, 4),
+            (GY, 0, 3),
+            (GX, 0, 4),
+            (GW, 10, 10),
+            (GZ, 0, 3),
+            (BX, 0, 3),
+            (BW, 10, 10),
+            (BZ, 1, 1),
+            (BY, 0, 3),
+            (RY, 0, 3),
+            (BZ, 0, 0),
+            (BZ, 2, 2),
+            (RZ, 0, 3),
+            (GY, 4, 4),
+            (BZ, 3, 3),
+        ],
+    },
+    Bc6Mode {
+        value: 10,
+        transformed: true,
+        endpoint_bits: 11,
+        delta_bits: [4, 4, 5],
+        layout: &[
+            (RW, 0, 9),
+            (GW, 0, 9),
+            (BW, 0, 9),
+            (RX, 0, 3),
+            (RW, 10, 10),
+            (BY, 4, 4),
+            (GY, 0, 3),
+            (GX, 0, 3),
+            (GW, 10, 10),
+            (BZ, 0, 0),
+            (GZ, 0, 3),
+            (BX, 0, 4),
+            (BW, 10, 10),
+            (BY, 0, 3),
+            (RY, 0, 3),
+            (BZ, 1, 2),
+            (RZ, 0, 3),
+            (BZ, 4, 4),
+            (BZ, 3, 3),
+        ],
+    },
+    Bc6Mode {
+        value: 14,
+        transformed: true,
+        endpoint_bits: 9,
+        delta_bits: [5, 5, 5],
+        layout: &[
+            (RW, 0, 8),
+            (BY, 4, 4),
+            (GW, 0, 8),
+            (GY, 4, 4),
+            (BW, 0, 8),
+            (BZ, 4, 4),
+            (RX, 0, 4),
+            (GZ, 4, 4),
+            (GY, 0, 3),
+            (GX, 0, 4),
+            (BZ, 0, 0),
+            (GZ, 0, 3),
+            (BX, 0, 4),
+            (BZ, 1, 1),
+            (BY, 0, 3),
+            (RY, 0, 4),
+            (BZ, 2, 2),
+            (RZ, 0, 4),
+            (BZ, 3, 3),
+        ],
+    },
+    Bc6Mode {
+        value: 18,
+        transformed: true,
+        endpoint_bits: 8,
+        delta_bits: [6, 5, 5],
+        layout: &[
+            (RW, 0, 7),
+            (GZ, 4, 4),
+            (BY, 4, 4),
+            (GW, 0, 7),
+            (BZ, 2, 2),
+            (GY, 4, 4),
+            (BW, 0, 7),
+            (BZ, 3, 4),
+            (RX, 0, 5),
+            (GY, 0, 3),
+            (GX, 0, 4),
+            (BZ, 0, 0),
+            (GZ, 0, 3),
+            (BX, 0, 4),
+            (BZ, 1, 1),
+            (BY, 0, 3),
+            (RY, 0, 5),
+            (RZ, 0, 5),
+        ],
+    },
+    Bc6Mode {
+        value: 22,
+        transformed: true,
+        endpoint_bits: 8,
+        delta_bits: [5, 6, 5],
+        layout: &[
+            (RW, 0, 7),
+            (BZ, 0, 0),
+            (BY, 4, 4),
+            (GW, 0, 7),
+            (GY, 5, 5),
+            (GY, 4, 4),
+            (BW, 0, 7),
+            (GZ, 5, 5),
+            (BZ, 4, 4),
+            (RX, 0, 4),
+            (GZ, 4, 4),
+            (GY, 0, 3),
+            (GX, 0, 5),
+            (GZ, 0, 3),
+            (BX, 0, 4),
+            (BZ, 1, 1),
+            (BY, 0, 3),
+            (RY, 0, 4),
+            (BZ, 2, 2),
+            (RZ, 0, 4),
+            (BZ, 3, 3),
+        ],
+    },
+    Bc6Mode {
+        value: 26,
+        transformed: true,
+        endpoint_bits: 8,
+        delta_bits: [5, 5, 6],
+        layout: &[
+            (RW, 0, 7),
+            (BZ, 1, 1),
+            (BY, 4, 4),
+            (GW, 0, 7),
+            (BY, 5, 5),
+            (GY, 4, 4),
+            (BW, 0, 7),
+            (BZ, 5, 5),
+            (BZ, 4, 4),
+            (RX, 0, 4),
+            (GZ, 4, 4),
+            (GY, 0, 3),
+            (GX, 0, 4),
+            (BZ, 0, 0),
+            (GZ, 0, 3),
+            (BX, 0, 5),
+            (BY, 0, 3),
+            (RY, 0, 4),
+            (BZ, 2, 2),
+            (RZ, 0, 4),
+            (BZ, 3, 3),
+        ],
+    },
+    Bc6Mode {
+        value: 30,
+        transformed: false,
+        endpoint_bits: 6,
+        delta_bits: [6, 6, 6],
+        layout: &[
+            (RW, 0, 5),
+            (GZ, 4, 4),
+            (BZ, 0, 1),
+            (BY, 4, 4),
+            (GW, 0, 5),
+            (GY, 5, 5),
+            (BY, 5, 5),
+            (BZ, 2, 2),
+            (GY, 4, 4),
+            (BW, 0, 5),
+            (GZ, 5, 5),
+            (BZ, 3, 3),
+            (BZ, 5, 5),
+            (BZ, 4, 4),
+            (RX, 0, 5),
+            (GY, 0, 3),
+            (GX, 0, 5),
+            (GZ, 0, 3),
+            (BX, 0, 5),
+            (BY, 0, 3),
+            (RY, 0, 5),
+            (RZ, 0, 5),
+        ],
+    },
+    Bc6Mode {
+        value: 3,
+        transformed: false,
+        endpoint_bits: 10,
+        delta_bits: [10, 10, 10],
+        layout: &[
+            (RW, 0, 9),
+            (GW, 0, 9),
+            (BW, 0, 9),
+            (RX, 0, 9),
+            (GX, 0, 9),
+            (BX, 0, 9),
+        ],
+    },
+    Bc6Mode {
+        value: 7,
+        transformed: true,
+        endpoint_bits: 11,
+        delta_bits: [9, 9, 9],
+        layout: &[
+            (RW, 0, 9),
+            (GW, 0, 9),
+            (BW, 0, 9),
+            (RX, 0, 8),
+            (RW, 10, 10),
+            (GX, 0, 8),
+            (GW, 10, 10),
+            (BX, 0, 8),
+            (BW, 10, 10),
+        ],
+    },
+    Bc6Mode {
+        value: 11,
+        transformed: true,
+        endpoint_bits: 12,
+        delta_bits: [8, 8, 8],
+        layout: &[
+            (RW, 0, 9),
+            (GW, 0, 9),
+            (BW, 0, 9),
+            (RX, 0, 7),
+            (RW, 11, 10),
+            (GX, 0, 7),
+            (GW, 11, 10),
+            (BX, 0, 7),
+            (BW, 11, 10),
+        ],
+    },
+    Bc6Mode {
+        value: 15,
+        transformed: true,
+        endpoint_bits: 16,
+        delta_bits: [4, 4, 4],
+        layout: &[
+            (RW, 0, 9),
+            (GW, 0, 9),
+            (BW, 0, 9),
+            (RX, 0, 3),
+            (RW, 15, 10),
+            (GX, 0, 3),
+            (GW, 15, 10),
+            (BX, 0, 3),
+            (BW, 15, 10),
+        ],
+    },
+];
+
+fn sign_extend(value: i32, bits: u32) -> i32 {
+    (value << (32 - bits)) >> (32 - bits)
+}
+
+fn unquantize_bc6(value: i32, bits: u32, signed: bool) -> i32 {
+    if signed {
+        if bits >= 16 {
+            return value;
+        }
+        let magnitude = value.abs();
+        let unquantized = if magnitude == 0 {
+            0
+        } else if magnitude >= (1 << (bits - 1)) - 1 {
+            0x7FFF
+        } else {
+            ((magnitude << 15) + 0x4000) >> (bits - 1)
+        };
+        if value < 0 {
+            -unquantized
+        } else {
+            unquantized
+        }
+    } else if bits >= 15 {
+        value
+    } else if value == 0 {
+        0
+    } else if value == (1 << bits) - 1 {
+        0xFFFF
+    } else {
+        ((value << 16) + 0x8000) >> bits
+    }
+}
+
+/// Scale an interpolated value to the range of half floats.
+fn finish_bc6(value: i32, signed: bool) -> f32 {
+    let half = if !signed {
+        (value * 31) >> 6
+    } else if value < 0 {
+        0x8000 | ((-value * 31) >> 5)
+    } else {
+        (value * 31) >> 5
+    };
+    f16_to_f32(half as u16)
+}
+
+pub fn decode_bc6(block: &[u8], texels: &mut [[f32; 4]], signed: bool) {
+    let mut bits = Bits::new(block);
+    let mut value = bits.read(2);
+    if value > 1 {
+        value |= bits.read(3) << 2;
+    }
+    let mode = match BC6_MODES.iter().find(|mode| mode.value == value) {
+        Some(mode) => mode,
+        // Reserved modes decode to black.
+        None => {
+            for texel in texels.iter_mut() {
+                *texel = [0.0, 0.0, 0.0, 1.0];
+            }
+            return;
+        }
+    };
+
+    let mut endpoints = [0i32; 12];
+    for &(component, first, last) in mode.layout {
+        let count = first.max(last) - first.min(last) + 1;
+        for i in 0 .. count {
+            let bit = if first <= last { first + i } else { first - i };
+            endpoints[component as usize] |= (bits.read(1) as i32) << bit;
+        }
+    }
+    let regions = mode.regions();
+    let partition = if regions == 2 { bits.read(5) } else { 0 };
+
+    let epb = mode.endpoint_bits;
+    if signed {
+        for value in endpoints[.. 3].iter_mut() {
+            *value = sign_extend(*value, epb);
+        }
+    }
+    for i in 3 .. regions * 6 {
+        let base = endpoints[i % 3];
+        let value = &mut endpoints[i];
+        if mode.transformed {
+            let delta = sign_extend(*value, mode.delta_bits[i % 3]);
+            *value = (base + delta) & ((1 << epb) - 1);
+        }
+        if signed {
+            *value = sign_extend(*value, epb);
+        }
+    }
+    for value in endpoints[.. regions * 6].iter_mut() {
+        *value = unquantize_bc6(*value, epb, signed);
+    }
+
+    let index_bits = if regions == 2 { 3 } else { 4 };
+    let subsets = regions as u32;
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let anchor = is_anchor(subsets, partition, i) as u32;
+        let w = weight(index_bits, bits.read(index_bits - anchor)) as i32;
+        let e = &endpoints[subset(subsets, partition, i) * 6 ..];
+        for c in 0 .. 3 {
+            let value = ((64 - w) * e[c] + w * e[3 + c] + 32) >> 6;
+            texel[c] = finish_bc6(value, signed);
+        }
+        texel[3] = 1.0;
+    }
+}
+
+struct Bc7Mode {
+    subsets: u32,
+    partition_bits: u32,
+    rotation_bits: u32,
+    selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    index_bits: u32,
+    index_bits2: u32,
+}
+
+const BC7_MODES: [Bc7Mode; 8] = [
+    Bc7Mode {
+        subsets: 3,
+        partition_bits: 4,
+        rotation_bits: 0,
+        selection_bits: 0,
+        color_bits: 4,
+        alpha_bits: 0,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 3,
+        index_bits2: 0,
+    },
+    Bc7Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        selection_bits: 0,
+        color_bits: 6,
+        alpha_bits: 0,
+        endpoint_pbits: false,
+        shared_pbits: true,
+        index_bits: 3,
+        index_bits2: 0,
+    },
+    Bc7Mode {
+        subsets: 3,
+        partition_bits: 6,
+        rotation_bits: 0,
+        selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 0,
+        endpoint_pbits: false,
+        shared_pbits: false,
+        index_bits: 2,
+        index_bits2: 0,
+    },
+    Bc7Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 0,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 2,
+        index_bits2: 0,
+    },
+    Bc7Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        selection_bits: 1,
+        color_bits: 5,
+        alpha_bits: 6,
+        endpoint_pbits: false,
+        shared_pbits: false,
+        index_bits: 2,
+        index_bits2: 3,
+    },
+    Bc7Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 8,
+        endpoint_pbits: false,
+        shared_pbits: false,
+        index_bits: 2,
+        index_bits2: 2,
+    },
+    Bc7Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 0,
+        selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 7,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 4,
+        index_bits2: 0,
+    },
+    Bc7Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 5,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 2,
+        index_bits2: 0,
+    },
+];
+
+pub fn decode_bc7(block: &[u8], texels: &mut [[f32; 4]]) {
+    let mode_index = block[0].trailing_zeros();
+    let mode = match BC7_MODES.get(mode_index as usize) {
+        Some(mode) => mode,
+        // Reserved modes decode to transparent black.
+        None => {
+            for texel in texels.iter_mut() {
+                *texel = [0.0; 4];
+            }
+            return;
+        }
+    };
+    let mut bits = Bits::new(block);
+    bits.read(mode_index + 1);
+    let partition = bits.read(mode.partition_bits);
+    let rotation = bits.read(mode.rotation_bits);
+    let selection = bits.read(mode.selection_bits);
+
+    let count = mode.subsets as usize * 2;
+    let mut endpoints = [[0u32; 4]; 6];
+    for c in 0 .. 4 {
+        let channel_bits = if c < 3 {
+            mode.color_bits
+        } else {
+            mode.alpha_bits
+        };
+        for endpoint in endpoints[.. count].iter_mut() {
+            endpoint[c] = bits.read(channel_bits);
+        }
+    }
+    let pbit = (mode.endpoint_pbits || mode.shared_pbits) as u32;
+    if mode.endpoint_pbits {
+        for endpoint in endpoints[.. count].iter_mut() {
+            let p = bits.read(1);
+            for value in endpoint.iter_mut() {
+                *value = *value << 1 | p;
+            }
+        }
+    }
+    if mode.shared_pbits {
+        for pair in endpoints[.. count].chunks_mut(2) {
+            let p = bits.read(1);
+            for endpoint in pair {
+                for value in endpoint.iter_mut() {
+                    *value = *value << 1 | p;
+                }
+            }
+        }
+    }
+    for endpoint in endpoints[.. count].iter_mut() {
+        for value in endpoint[.. 3].iter_mut() {
+            *value = expand(*value as i32, mode.color_bits + pbit) as u32;
+        }
+        endpoint[3] = if mode.alpha_bits == 0 {
+            255
+        } else {
+            expand(endpoint[3] as i32, mode.alpha_bits + pbit) as u32
+        };
+    }
+
+    let mut indices = [[0u32; 2]; 16];
+    for (i, index) in indices.iter_mut().enumerate() {
+        let anchor = is_anchor(mode.subsets, partition, i) as u32;
+        index[0] = bits.read(mode.index_bits - anchor);
+    }
+    if mode.index_bits2 != 0 {
+        for (i, index) in indices.iter_mut().enumerate() {
+            index[1] = bits.read(mode.index_bits2 - (i == 0) as u32);
+        }
+    }
+
+    for (i, (texel, index)) in texels.iter_mut().zip(&indices).enumerate() {
+        let s = subset(mode.subsets, partition, i);
+        let (e0, e1) = (endpoints[s * 2], endpoints[s * 2 + 1]);
+        let (color, alpha) = if mode.index_bits2 == 0 {
+            let w = weight(mode.index_bits, index[0]);
+            (w, w)
+        } else if selection == 0 {
+            (
+                weight(mode.index_bits, index[0]),
+                weight(mode.index_bits2, index[1]),
+            )
+        } else {
+            (
+                weight(mode.index_bits2, index[1]),
+                weight(mode.index_bits, index[0]),
+            )
+        };
+        for c in 0 .. 4 {
+            let w = if c < 3 { color } else { alpha };
+            let value = ((64 - w) * e0[c] + w * e1[c] + 32) >> 6;
+            texel[c] = value as f32 / 255.0;
+        }
+        if rotation != 0 {
+            texel.swap(rotation as usize - 1, 3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pack fields of the given number of bits, from the lowest bit of the block.
+    fn pack(fields: &[(u32, u32)]) -> [u8; 16] {
+        let (mut value, mut position) = (0u128, 0);
+        for &(field, bits) in fields {
+            value |= (field as u128) << position;
+            position += bits;
+        }
+        value.to_le_bytes()
+    }
+
+    #[test]
+    fn test_partitions() {
+        for partition in 0 .. 64 {
+            let anchor = ANCHORS_2[partition] as usize;
+            assert_eq!(subset(2, partition as u32, anchor), 1, "{}", partition);
+            for (s, anchors) in ANCHORS_3.iter().enumerate() {
+                let anchor = anchors[partition] as usize;
+                assert_eq!(subset(3, partition as u32, anchor), s + 1, "{}", partition);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bc4() {
+        let mut texels = [[0.0; 4]; 16];
+        let indices = 0o76543210u64.to_le_bytes();
+        let block = [255, 0, indices[0], indices[1], indices[2], 0, 0, 0];
+        decode_bc4(&block, &mut texels, false);
+        assert_eq!(texels[0], [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(texels[1][0], 0.0);
+        assert_eq!(texels[2][0], 6.0 / 7.0);
+        // Signed endpoints with the second one greater, -128 reads as -1.
+        let block = [0x80, 0x7F, indices[0], indices[1], indices[2], 0, 0, 0];
+        decode_bc4(&block, &mut texels, true);
+        assert_eq!(texels[0][0], -1.0);
+        assert_eq!(texels[1][0], 1.0);
+        assert_eq!(texels[6][0], -1.0);
+        assert_eq!(texels[7][0], 1.0);
+    }
+
+    #[test]
+    fn test_bc6() {
+        let mut texels = [[0.0; 4]; 16];
+        // Mode 11 with a single region of 10-bit endpoints.
+        let block = pack(&[
+            (3, 5),
+            (512, 10),
+            (0, 10),
+            (1023, 10),
+            (0, 10),
+            (0, 10),
+            (1023, 10),
+            (0, 3),
+            (0xF, 4),
+        ]);
+        decode_bc6(&block, &mut texels, false);
+        let half = f16_to_f32((((((512 << 16) + 0x8000) >> 10) * 31) >> 6) as u16);
+        assert_eq!(texels[0], [half, 0.0, 65504.0, 1.0]);
+        assert_eq!(texels[1], [0.0, 0.0, 65504.0, 1.0]);
+    }
+
+    #[test]
+    fn test_bc7() {
+        let mut texels = [[0.0; 4]; 16];
+        // Mode 6 with equal endpoints, all texels have the same color.
+        let block = pack(&[
+            (1 << 6, 7),
+            (127, 7),
+            (127, 7),
+            (0, 7),
+            (0, 7),
+            (64, 7),
+            (64, 7),
+            (127, 7),
+            (127, 7),
+            (1, 1),
+            (1, 1),
+            (0x5, 3),
+        ]);
+        decode_bc7(&block, &mut texels);
+        let color = [1.0, 1.0 / 255.0, 129.0 / 255.0, 1.0];
+        assert!(texels.iter().all(|&texel| texel == color), "{:?}", texels);
+    }
+}
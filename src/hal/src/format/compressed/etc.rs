@@ -0,0 +1,319 @@
+//! ETC2 and EAC decoders.
+//!
+//! The blocks are stored as big-endian 64-bit integers, their texel indices
+//! in column-major order.
+
+use super::expand;
+
+const MODIFIERS: [[i32; 2]; 8] = [
+    [2, 8],
+    [5, 17],
+    [9, 29],
+    [13, 42],
+    [18, 60],
+    [24, 80],
+    [33, 106],
+    [47, 183],
+];
+
+const DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+const EAC_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+fn read_block(block: &[u8]) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&block[.. 8]);
+    u64::from_be_bytes(bytes)
+}
+
+fn bits(value: u64, low: u32, count: u32) -> i32 {
+    ((value >> low) & ((1 << count) - 1)) as i32
+}
+
+fn expand_rgb(rgb: [i32; 3], count: u32) -> [i32; 3] {
+    [
+        expand(rgb[0], count),
+        expand(rgb[1], count),
+        expand(rgb[2], count),
+    ]
+}
+
+fn offset(rgb: [i32; 3], offset: i32) -> [i32; 3] {
+    [
+        (rgb[0] + offset).clamp(0, 255),
+        (rgb[1] + offset).clamp(0, 255),
+        (rgb[2] + offset).clamp(0, 255),
+    ]
+}
+
+/// Color of a texel, `None` for transparent texels.
+type Color = Option<[i32; 3]>;
+
+pub fn decode_etc2(block: &[u8], texels: &mut [[f32; 4]], punch_through: bool) {
+    let value = read_block(block);
+    let diff = bits(value, 33, 1) == 1;
+    let flip = bits(value, 32, 1) == 1;
+    // With punch-through alpha, the `diff` bit tells if the block is opaque
+    // and the individual mode isn't available.
+    let opaque = !punch_through || diff;
+    let index = |x: usize, y: usize| {
+        let p = x * 4 + y;
+        ((value >> (16 + p)) & 1) << 1 | ((value >> p) & 1)
+    };
+
+    let mut colors: [[Color; 4]; 4] = [[None; 4]; 4];
+    let mut paint = |palette: [Color; 4]| {
+        for (y, row) in colors.iter_mut().enumerate() {
+            for (x, color) in row.iter_mut().enumerate() {
+                let i = index(x, y) as usize;
+                *color = if !opaque && i == 2 { None } else { palette[i] };
+            }
+        }
+    };
+
+    let (r, g, b) = (bits(value, 59, 5), bits(value, 51, 5), bits(value, 43, 5));
+    let sign_extend = |delta: i32| (delta << 29) >> 29;
+    let (dr, dg, db) = (
+        sign_extend(bits(value, 56, 3)),
+        sign_extend(bits(value, 48, 3)),
+        sign_extend(bits(value, 40, 3)),
+    );
+    let overflows = |base: i32, delta: i32| !(0 .. 32).contains(&(base + delta));
+    if !diff && !punch_through {
+        // Individual mode, two 4-bit base colors.
+        let base = [
+            expand_rgb(
+                [bits(value, 60, 4), bits(value, 52, 4), bits(value, 44, 4)],
+                4,
+            ),
+            expand_rgb(
+                [bits(value, 56, 4), bits(value, 48, 4), bits(value, 40, 4)],
+                4,
+            ),
+        ];
+        decode_subblocks(value, flip, base, opaque, &mut colors);
+    } else if overflows(r, dr) {
+        // T mode.
+        let c1 = expand_rgb(
+            [
+                bits(value, 59, 2) << 2 | bits(value, 56, 2),
+                bits(value, 52, 4),
+                bits(value, 48, 4),
+            ],
+            4,
+        );
+        let c2 = expand_rgb(
+            [bits(value, 44, 4), bits(value, 40, 4), bits(value, 36, 4)],
+            4,
+        );
+        let d = DISTANCES[(bits(value, 34, 2) << 1 | bits(value, 32, 1)) as usize];
+        paint([
+            Some(c1),
+            Some(offset(c2, d)),
+            Some(c2),
+            Some(offset(c2, -d)),
+        ]);
+    } else if overflows(g, dg) {
+        // H mode.
+        let c1 = [
+            bits(value, 59, 4),
+            bits(value, 56, 3) << 1 | bits(value, 52, 1),
+            bits(value, 51, 1) << 3 | bits(value, 47, 3),
+        ];
+        let c2 = [bits(value, 43, 4), bits(value, 39, 4), bits(value, 35, 4)];
+        let key = |c: [i32; 3]| c[0] << 8 | c[1] << 4 | c[2];
+        let order = (key(c1) >= key(c2)) as i32;
+        let d = DISTANCES[(bits(value, 34, 1) << 2 | bits(value, 32, 1) << 1 | order) as usize];
+        let (c1, c2) = (expand_rgb(c1, 4), expand_rgb(c2, 4));
+        paint([
+            Some(offset(c1, d)),
+            Some(offset(c1, -d)),
+            Some(offset(c2, d)),
+            Some(offset(c2, -d)),
+        ]);
+    } else if overflows(b, db) {
+        // Planar mode, always opaque.
+        let o = [
+            expand(bits(value, 57, 6), 6),
+            expand(bits(value, 56, 1) << 6 | bits(value, 49, 6), 7),
+            expand(
+                bits(value, 48, 1) << 5 | bits(value, 43, 2) << 3 | bits(value, 39, 3),
+                6,
+            ),
+        ];
+        let h = [
+            expand(bits(value, 34, 5) << 1 | bits(value, 32, 1), 6),
+            expand(bits(value, 25, 7), 7),
+            expand(bits(value, 19, 6), 6),
+        ];
+        let v = [
+            expand(bits(value, 13, 6), 6),
+            expand(bits(value, 6, 7), 7),
+            expand(bits(value, 0, 6), 6),
+        ];
+        for (y, row) in colors.iter_mut().enumerate() {
+            for (x, color) in row.iter_mut().enumerate() {
+                let mut rgb = [0; 3];
+                for c in 0 .. 3 {
+                    let value =
+                        (x as i32 * (h[c] - o[c]) + y as i32 * (v[c] - o[c]) + 4 * o[c] + 2) >> 2;
+                    rgb[c] = value.clamp(0, 255);
+                }
+                *color = Some(rgb);
+            }
+        }
+    } else {
+        // Differential mode, a 5-bit base color and a 3-bit signed offset.
+        let base = [
+            expand_rgb([r, g, b], 5),
+            expand_rgb([r + dr, g + dg, b + db], 5),
+        ];
+        decode_subblocks(value, flip, base, opaque, &mut colors);
+    }
+
+    for (y, row) in colors.iter().enumerate() {
+        for (x, color) in row.iter().enumerate() {
+            texels[y * 4 + x] = match *color {
+                Some([r, g, b]) => [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0],
+                None => [0.0; 4],
+            };
+        }
+    }
+}
+
+/// Decode the texels of the individual and differential modes, where each
+/// half of the block has a base color and a table of modifiers.
+fn decode_subblocks(
+    value: u64,
+    flip: bool,
+    base: [[i32; 3]; 2],
+    opaque: bool,
+    colors: &mut [[Color; 4]; 4],
+) {
+    let tables = [bits(value, 37, 3) as usize, bits(value, 34, 3) as usize];
+    for (y, row) in colors.iter_mut().enumerate() {
+        for (x, color) in row.iter_mut().enumerate() {
+            let subblock = if flip { y / 2 } else { x / 2 };
+            let p = x * 4 + y;
+            let index = ((value >> (16 + p)) & 1) << 1 | ((value >> p) & 1);
+            let [a, b] = MODIFIERS[tables[subblock]];
+            // Non-opaque punch-through blocks use index 2 for transparent
+            // texels and drop the smaller modifier.
+            let modifier = match (index, opaque) {
+                (0, true) => a,
+                (0, false) => 0,
+                (1, _) => b,
+                (2, true) => -a,
+                (2, false) => {
+                    *color = None;
+                    continue;
+                }
+                _ => -b,
+            };
+            *color = Some(offset(base[subblock], modifier));
+        }
+    }
+}
+
+/// Decode an EAC block into 11-bit values.
+fn decode_eac(block: &[u8], signed: bool) -> [f32; 16] {
+    let value = read_block(block);
+    let multiplier = bits(value, 52, 4);
+    let table = &EAC_MODIFIERS[bits(value, 48, 4) as usize];
+    let mut values = [0.0; 16];
+    for (i, texel) in values.iter_mut().enumerate() {
+        let (x, y) = (i % 4, i / 4);
+        let modifier = table[bits(value, 45 - 3 * (x * 4 + y) as u32, 3) as usize];
+        let modifier = if multiplier == 0 {
+            modifier
+        } else {
+            modifier * multiplier * 8
+        };
+        *texel = if signed {
+            let base = (bits(value, 56, 8) as i8 as i32).max(-127);
+            (base * 8 + modifier).clamp(-1023, 1023) as f32 / 1023.0
+        } else {
+            (bits(value, 56, 8) * 8 + 4 + modifier).clamp(0, 2047) as f32 / 2047.0
+        };
+    }
+    values
+}
+
+pub fn decode_etc2_eac(block: &[u8], texels: &mut [[f32; 4]]) {
+    decode_etc2(&block[8 ..], texels, false);
+    let value = read_block(block);
+    let base = bits(value, 56, 8);
+    let multiplier = bits(value, 52, 4);
+    let table = &EAC_MODIFIERS[bits(value, 48, 4) as usize];
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let (x, y) = (i % 4, i / 4);
+        let modifier = table[bits(value, 45 - 3 * (x * 4 + y) as u32, 3) as usize];
+        texel[3] = (base + modifier * multiplier).clamp(0, 255) as f32 / 255.0;
+    }
+}
+
+pub fn decode_eac_r11(block: &[u8], texels: &mut [[f32; 4]], signed: bool) {
+    let red = decode_eac(block, signed);
+    for (texel, &red) in texels.iter_mut().zip(&red) {
+        *texel = [red, 0.0, 0.0, 1.0];
+    }
+}
+
+pub fn decode_eac_r11_g11(block: &[u8], texels: &mut [[f32; 4]], signed: bool) {
+    let red = decode_eac(block, signed);
+    let green = decode_eac(&block[8 ..], signed);
+    for (texel, (&red, &green)) in texels.iter_mut().zip(red.iter().zip(&green)) {
+        *texel = [red, green, 0.0, 1.0];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_etc2() {
+        let mut texels = [[0.0; 4]; 16];
+        // Differential mode with red 16 and table 0, the texel at (1, 0)
+        // uses the index 1 and the one at (0, 1) the index 2.
+        let value: u64 = 16 << 59 | 1 << 33 | 1 << 4 | 1 << 17;
+        decode_etc2(&value.to_be_bytes(), &mut texels, false);
+        let unorm = |value: i32| value as f32 / 255.0;
+        assert_eq!(texels[0], [unorm(134), unorm(2), unorm(2), 1.0]);
+        assert_eq!(texels[1], [unorm(140), unorm(8), unorm(8), 1.0]);
+        assert_eq!(texels[4], [unorm(130), 0.0, 0.0, 1.0]);
+        // Without the opaque bit, the index 2 is transparent.
+        let value = value & !(1 << 33);
+        decode_etc2(&value.to_be_bytes(), &mut texels, true);
+        assert_eq!(texels[0], [unorm(132), 0.0, 0.0, 1.0]);
+        assert_eq!(texels[4], [0.0; 4]);
+    }
+
+    #[test]
+    fn test_eac() {
+        let mut texels = [[0.0; 4]; 16];
+        // Base 128, multiplier 2 and table 13, the texel at (0, 1) uses
+        // the index 7.
+        let value: u64 = 128 << 56 | 2 << 52 | 13 << 48 | 7 << 42;
+        decode_eac_r11(&value.to_be_bytes(), &mut texels, false);
+        assert_eq!(texels[0][0], (128 * 8 + 4 - 16) as f32 / 2047.0);
+        assert_eq!(texels[4][0], (128 * 8 + 4 + 9 * 16) as f32 / 2047.0);
+    }
+}
@@ -0,0 +1,208 @@
+//! Decompression of block-compressed formats on the CPU.
+//!
+//! Meant as a fallback for the devices without `Features::FORMAT_BC`,
+//! `FORMAT_ETC2` or `FORMAT_ASTC_LDR`: the texels are decoded to floats,
+//! with the same conventions as `texel::decode_f32`, and can be written
+//! into any uncompressed format supported by the `texel` module.
+//!
+//! ASTC blocks are decoded with the LDR profile, blocks using HDR endpoint
+//! modes decode to the error color (opaque magenta).
+
+mod astc;
+mod bc;
+mod etc;
+
+use super::{texel, BaseFormat, ChannelType, Format, SurfaceType};
+use crate::image::Extent;
+
+/// Maximum number of texels in a block, for 12x12 ASTC blocks.
+const MAX_BLOCK_TEXELS: usize = 144;
+
+/// Expand a value of `count` bits to 8 bits by replicating its highest bits.
+fn expand(value: i32, count: u32) -> i32 {
+    let value = value << (8 - count);
+    value | value >> count
+}
+
+/// Little-endian bit reader over a block of at most 128 bits.
+struct Bits {
+    value: u128,
+    position: u32,
+}
+
+impl Bits {
+    fn new(block: &[u8]) -> Self {
+        let mut bytes = [0; 16];
+        bytes[.. block.len()].copy_from_slice(block);
+        Bits {
+            value: u128::from_le_bytes(bytes),
+            position: 0,
+        }
+    }
+
+    /// Read the next `count` bits, at most 32. Bits past the end read as 0.
+    fn read(&mut self, count: u32) -> u32 {
+        let value = self.value.checked_shr(self.position).unwrap_or(0) as u32;
+        let value = value & ((1u64 << count) - 1) as u32;
+        self.position += count;
+        value
+    }
+}
+
+/// Returns true if the format is block-compressed, all of which can be decompressed.
+pub fn is_supported(format: Format) -> bool {
+    format.surface_desc().is_compressed()
+}
+
+/// Returns the format to decompress a block-compressed format into, keeping
+/// its range and most of its precision.
+///
+/// This is `Rgba8Srgb` for sRGB formats, `Rgba8Unorm` for 8-bit normalized
+/// ones and `Rgba16Sfloat` for BC6H, the signed formats and the 11-bit EAC
+/// formats. Returns `None` if the format isn't compressed.
+pub fn decompressed_format(format: Format) -> Option<Format> {
+    if !is_supported(format) {
+        return None;
+    }
+    let BaseFormat(surface, ty) = format.base_format();
+    Some(match (surface, ty) {
+        (SurfaceType::EAC_R11, _) | (SurfaceType::EAC_R11_G11, _) => Format::Rgba16Sfloat,
+        (_, ChannelType::Srgb) => Format::Rgba8Srgb,
+        (_, ChannelType::Unorm) => Format::Rgba8Unorm,
+        _ => Format::Rgba16Sfloat,
+    })
+}
+
+/// Decode a single block into `texels`, in row-major order.
+///
+/// `texels` has to hold at least as many values as there are texels in a
+/// block of the format. sRGB values are converted to linear.
+pub fn decode_block(format: Format, block: &[u8], texels: &mut [[f32; 4]]) {
+    use self::SurfaceType as S;
+    let BaseFormat(surface, ty) = format.base_format();
+    let (width, height) = format.surface_desc().dim;
+    let signed = ty == ChannelType::Snorm || ty == ChannelType::Sfloat;
+    let texels = &mut texels[.. width as usize * height as usize];
+    match surface {
+        S::BC1_RGB => bc::decode_bc1(block, texels, false),
+        S::BC1_RGBA => bc::decode_bc1(block, texels, true),
+        S::BC2 => bc::decode_bc2(block, texels),
+        S::BC3 => bc::decode_bc3(block, texels),
+        S::BC4 => bc::decode_bc4(block, texels, signed),
+        S::BC5 => bc::decode_bc5(block, texels, signed),
+        S::BC6 => bc::decode_bc6(block, texels, signed),
+        S::BC7 => bc::decode_bc7(block, texels),
+        S::ETC2_R8_G8_B8 => etc::decode_etc2(block, texels, false),
+        S::ETC2_R8_G8_B8_A1 => etc::decode_etc2(block, texels, true),
+        S::ETC2_R8_G8_B8_A8 => etc::decode_etc2_eac(block, texels),
+        S::EAC_R11 => etc::decode_eac_r11(block, texels, signed),
+        S::EAC_R11_G11 => etc::decode_eac_r11_g11(block, texels, signed),
+        S::ASTC_4x4
+        | S::ASTC_5x4
+        | S::ASTC_5x5
+        | S::ASTC_6x5
+        | S::ASTC_6x6
+        | S::ASTC_8x5
+        | S::ASTC_8x6
+        | S::ASTC_8x8
+        | S::ASTC_10x5
+        | S::ASTC_10x6
+        | S::ASTC_10x8
+        | S::ASTC_10x10
+        | S::ASTC_12x10
+        | S::ASTC_12x12 => astc::decode(
+            block,
+            texels,
+            width as u32,
+            height as u32,
+            ty == ChannelType::Srgb,
+        ),
+        _ => panic!("Unsupported compressed format {:?}", format),
+    }
+    if ty == ChannelType::Srgb {
+        for texel in texels.iter_mut() {
+            for value in texel[.. 3].iter_mut() {
+                *value = texel::srgb_to_linear(*value);
+            }
+        }
+    }
+}
+
+/// Decompress the blocks of an image of the given extent, in texels, into
+/// the `target` format.
+///
+/// The blocks are tightly packed in `data`, row by row and slice by slice,
+/// and so are the texels written to `output`. The blocks on the right and
+/// bottom edges may cover texels past the extent, which are discarded.
+pub fn decompress(format: Format, extent: Extent, data: &[u8], target: Format, output: &mut [u8]) {
+    let desc = format.surface_desc();
+    let (block_width, block_height) = (desc.dim.0 as u32, desc.dim.1 as u32);
+    let block_size = desc.bits as usize / 8;
+    let texel_size = target.surface_desc().bits as usize / 8;
+    let blocks_x = extent.width.div_ceil(block_width);
+    let blocks_y = extent.height.div_ceil(block_height);
+    let mut texels = [[0.0; 4]; MAX_BLOCK_TEXELS];
+
+    for z in 0 .. extent.depth {
+        for by in 0 .. blocks_y {
+            for bx in 0 .. blocks_x {
+                let index = ((z * blocks_y + by) * blocks_x + bx) as usize;
+                decode_block(
+                    format,
+                    &data[index * block_size ..][.. block_size],
+                    &mut texels,
+                );
+                for y in 0 .. block_height.min(extent.height - by * block_height) {
+                    for x in 0 .. block_width.min(extent.width - bx * block_width) {
+                        let (tx, ty) = (bx * block_width + x, by * block_height + y);
+                        let offset = ((z * extent.height + ty) * extent.width + tx) as usize;
+                        texel::encode_f32(
+                            target,
+                            &mut output[offset * texel_size ..],
+                            texels[(y * block_width + x) as usize],
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decompress() {
+        // BC1 blocks of red texels, with black ones in the top-left and
+        // bottom-right corners.
+        let mut block = [0u8; 8];
+        block[.. 4].copy_from_slice(&[0x00, 0xF8, 0x00, 0x00]);
+        block[4 ..].copy_from_slice(&0x4000_0001u32.to_le_bytes());
+        let data = [block, block].concat();
+        let extent = Extent {
+            width: 6,
+            height: 3,
+            depth: 1,
+        };
+        let mut output = [0u8; 6 * 3 * 4];
+        decompress(
+            Format::Bc1RgbUnorm,
+            extent,
+            &data,
+            Format::Rgba8Unorm,
+            &mut output,
+        );
+        let texel = |x: usize, y: usize| &output[(y * 6 + x) * 4 .. (y * 6 + x + 1) * 4];
+        assert_eq!(texel(0, 0), &[0, 0, 0, 255]);
+        assert_eq!(texel(1, 0), &[255, 0, 0, 255]);
+        assert_eq!(texel(4, 0), &[0, 0, 0, 255]);
+        assert_eq!(texel(5, 0), &[255, 0, 0, 255]);
+        assert_eq!(texel(5, 2), &[255, 0, 0, 255]);
+        assert_eq!(
+            decompressed_format(Format::Bc6hUfloat),
+            Some(Format::Rgba16Sfloat)
+        );
+        assert_eq!(decompressed_format(Format::Rgba8Unorm), None);
+    }
+}
@@ -9,6 +9,9 @@
 //! for instance `R32_G32_B32_A32`.  The `ChannelType` specifies how the
 //! components are interpreted, for instance `Sfloat` or `Sint`.
 
+pub mod compressed;
+pub mod texel;
+
 bitflags!(
     /// Bitflags which describe what properties of an image
     /// a format specifies or does not specify.  For example,
@@ -0,0 +1,681 @@
+//! Conversions between texels in memory and their values.
+//!
+//! Values are passed around as 4 raw words, the same way as `ClearColor`
+//! does: the bits of `f32` values for the normalized, scaled and floating
+//! point formats, and plain integers for the integer ones. The typed
+//! functions convert the words to the requested type. Missing components
+//! read as `0`, except alpha which reads as `1`.
+//!
+//! 64-bit channels are converted to and from 32-bit values. Block-compressed
+//! formats are not supported.
+
+use super::{BaseFormat, ChannelType, Format, SurfaceType};
+
+/// Raw words of a texel value, see the module documentation.
+pub type Texel = [u32; 4];
+
+// Placement of a channel inside of a packed texel, as `(channel, shift, bits)`.
+type PackedChannel = (usize, u32, u32);
+
+enum Layout {
+    /// Channels of the given number of bits stored one after another,
+    /// with the given order of the RGBA components.
+    Plain { bits: u32, order: &'static [usize] },
+    /// Channels packed into a single little-endian integer of the given size in bytes.
+    Packed {
+        bytes: usize,
+        channels: &'static [PackedChannel],
+    },
+    /// Unsigned 11-bit red and green and 10-bit blue floats.
+    Float11_11_10,
+    /// Unsigned 9-bit mantissas sharing a 5-bit exponent.
+    SharedExponent,
+}
+
+fn layout(surface: SurfaceType) -> Option<Layout> {
+    use self::SurfaceType as S;
+    const R: &[usize] = &[0];
+    const RG: &[usize] = &[0, 1];
+    const RGB: &[usize] = &[0, 1, 2];
+    const BGR: &[usize] = &[2, 1, 0];
+    const RGBA: &[usize] = &[0, 1, 2, 3];
+    const BGRA: &[usize] = &[2, 1, 0, 3];
+    let plain = |bits, order| Layout::Plain { bits, order };
+    Some(match surface {
+        S::R8 => plain(8, R),
+        S::R8_G8 => plain(8, RG),
+        S::R8_G8_B8 => plain(8, RGB),
+        S::B8_G8_R8 => plain(8, BGR),
+        // `A8_B8_G8_R8` is packed into 32 bits with red in the low byte,
+        // so its bytes are in RGBA order in little-endian memory.
+        S::R8_G8_B8_A8 | S::A8_B8_G8_R8 => plain(8, RGBA),
+        S::B8_G8_R8_A8 => plain(8, BGRA),
+        S::R16 => plain(16, R),
+        S::R16_G16 => plain(16, RG),
+        S::R16_G16_B16 => plain(16, RGB),
+        S::R16_G16_B16_A16 => plain(16, RGBA),
+        S::R32 => plain(32, R),
+        S::R32_G32 => plain(32, RG),
+        S::R32_G32_B32 => plain(32, RGB),
+        S::R32_G32_B32_A32 => plain(32, RGBA),
+        S::R64 => plain(64, R),
+        S::R64_G64 => plain(64, RG),
+        S::R64_G64_B64 => plain(64, RGB),
+        S::R64_G64_B64_A64 => plain(64, RGBA),
+        S::R4_G4 => Layout::Packed {
+            bytes: 1,
+            channels: &[(0, 4, 4), (1, 0, 4)],
+        },
+        S::R4_G4_B4_A4 => Layout::Packed {
+            bytes: 2,
+            channels: &[(0, 12, 4), (1, 8, 4), (2, 4, 4), (3, 0, 4)],
+        },
+        S::B4_G4_R4_A4 => Layout::Packed {
+            bytes: 2,
+            channels: &[(2, 12, 4), (1, 8, 4), (0, 4, 4), (3, 0, 4)],
+        },
+        S::R5_G6_B5 => Layout::Packed {
+            bytes: 2,
+            channels: &[(0, 11, 5), (1, 5, 6), (2, 0, 5)],
+        },
+        S::B5_G6_R5 => Layout::Packed {
+            bytes: 2,
+            channels: &[(2, 11, 5), (1, 5, 6), (0, 0, 5)],
+        },
+        S::R5_G5_B5_A1 => Layout::Packed {
+            bytes: 2,
+            channels: &[(0, 11, 5), (1, 6, 5), (2, 1, 5), (3, 0, 1)],
+        },
+        S::B5_G5_R5_A1 => Layout::Packed {
+            bytes: 2,
+            channels: &[(2, 11, 5), (1, 6, 5), (0, 1, 5), (3, 0, 1)],
+        },
+        S::A1_R5_G5_B5 => Layout::Packed {
+            bytes: 2,
+            channels: &[(3, 15, 1), (0, 10, 5), (1, 5, 5), (2, 0, 5)],
+        },
+        S::A2_R10_G10_B10 => Layout::Packed {
+            bytes: 4,
+            channels: &[(3, 30, 2), (0, 20, 10), (1, 10, 10), (2, 0, 10)],
+        },
+        S::A2_B10_G10_R10 => Layout::Packed {
+            bytes: 4,
+            channels: &[(3, 30, 2), (2, 20, 10), (1, 10, 10), (0, 0, 10)],
+        },
+        S::B10_G11_R11 => Layout::Float11_11_10,
+        S::E5_B9_G9_R9 => Layout::SharedExponent,
+        _ => return None,
+    })
+}
+
+/// Returns true if the values of the format are stored as floats in a `Texel`.
+pub fn is_float(format: Format) -> bool {
+    !matches!(
+        format.base_format().1,
+        ChannelType::Uint | ChannelType::Sint
+    )
+}
+
+/// Returns true if the format can be read and written by `decode` and `encode`.
+pub fn is_supported(format: Format) -> bool {
+    let BaseFormat(surface, _) = format.base_format();
+    layout(surface).is_some() || format.is_depth() || format.is_stencil()
+}
+
+/// Default value of a missing component, `0` for colors and `1` for alpha.
+fn default_component(channel: usize, float: bool) -> u32 {
+    match (channel, float) {
+        (3, true) => 1f32.to_bits(),
+        (3, false) => 1,
+        _ => 0,
+    }
+}
+
+fn max_value(bits: u32) -> u64 {
+    if bits == 64 {
+        !0
+    } else {
+        (1 << bits) - 1
+    }
+}
+
+fn decode_channel(raw: u64, bits: u32, ty: ChannelType, channel: usize) -> u32 {
+    let max = max_value(bits);
+    // Sign-extends the value to 64 bits.
+    let signed = || ((raw << (64 - bits)) as i64) >> (64 - bits);
+    let unorm = || (raw as f64 / max as f64) as f32;
+    match ty {
+        ChannelType::Unorm => unorm().to_bits(),
+        ChannelType::Srgb if channel < 3 => srgb_to_linear(unorm()).to_bits(),
+        ChannelType::Srgb => unorm().to_bits(),
+        ChannelType::Snorm => {
+            let max = (max >> 1) as f64;
+            ((signed() as f64 / max).max(-1.0) as f32).to_bits()
+        }
+        ChannelType::Uint => raw.min(u32::MAX as u64) as u32,
+        ChannelType::Sint => signed().clamp(i32::MIN as i64, i32::MAX as i64) as i32 as u32,
+        ChannelType::Uscaled => (raw as f32).to_bits(),
+        ChannelType::Sscaled => (signed() as f32).to_bits(),
+        ChannelType::Sfloat | ChannelType::Ufloat => match bits {
+            16 => f16_to_f32(raw as u16).to_bits(),
+            64 => (f64::from_bits(raw) as f32).to_bits(),
+            _ => raw as u32,
+        },
+    }
+}
+
+fn encode_channel(value: u32, bits: u32, ty: ChannelType, channel: usize) -> u64 {
+    let max = max_value(bits);
+    let float = f32::from_bits(value) as f64;
+    let unorm = |value: f64| (value.clamp(0.0, 1.0) * max as f64).round() as u64;
+    match ty {
+        ChannelType::Unorm => unorm(float),
+        ChannelType::Srgb if channel < 3 => unorm(linear_to_srgb(float as f32) as f64),
+        ChannelType::Srgb => unorm(float),
+        ChannelType::Snorm => {
+            let half = (max >> 1) as f64;
+            (float.clamp(-1.0, 1.0) * half).round() as i64 as u64 & max
+        }
+        ChannelType::Uint => value as u64 & max,
+        ChannelType::Sint => value as i32 as i64 as u64 & max,
+        ChannelType::Uscaled => float.round().clamp(0.0, max as f64) as u64,
+        ChannelType::Sscaled => {
+            let half = (max >> 1) as f64;
+            float.round().clamp(-half - 1.0, half) as i64 as u64 & max
+        }
+        ChannelType::Sfloat | ChannelType::Ufloat => match bits {
+            16 => f32_to_f16(float as f32) as u64,
+            64 => float.to_bits(),
+            _ => value as u64,
+        },
+    }
+}
+
+/// Decode the texel stored at the start of `data` into raw words.
+///
+/// Depth formats decode their depth into the first word, stencil formats
+/// their stencil.
+pub fn decode(format: Format, data: &[u8]) -> Texel {
+    let BaseFormat(surface, ty) = format.base_format();
+    let float = is_float(format);
+    let mut texel = [
+        default_component(0, float),
+        default_component(1, float),
+        default_component(2, float),
+        default_component(3, float),
+    ];
+    match layout(surface) {
+        Some(Layout::Plain { bits, order }) => {
+            let bytes = bits as usize / 8;
+            for (i, &channel) in order.iter().enumerate() {
+                let raw = read_bytes(&data[i * bytes ..], bytes);
+                texel[channel] = decode_channel(raw, bits, ty, channel);
+            }
+        }
+        Some(Layout::Packed { bytes, channels }) => {
+            let raw = read_bytes(data, bytes);
+            for &(channel, shift, bits) in channels {
+                let value = (raw >> shift) & max_value(bits);
+                texel[channel] = decode_channel(value, bits, ty, channel);
+            }
+        }
+        Some(Layout::Float11_11_10) => {
+            let raw = read_bytes(data, 4) as u32;
+            texel[0] = ufloat_to_f32(raw & 0x7FF, 6).to_bits();
+            texel[1] = ufloat_to_f32((raw >> 11) & 0x7FF, 6).to_bits();
+            texel[2] = ufloat_to_f32(raw >> 22, 5).to_bits();
+        }
+        Some(Layout::SharedExponent) => {
+            let raw = read_bytes(data, 4) as u32;
+            let scale = 2f32.powi((raw >> 27) as i32 - 15 - 9);
+            for (channel, value) in texel[.. 3].iter_mut().enumerate() {
+                let mantissa = (raw >> (9 * channel)) & 0x1FF;
+                *value = (mantissa as f32 * scale).to_bits();
+            }
+        }
+        None if format.is_depth() => {
+            texel[0] = decode_depth(format, data).to_bits();
+        }
+        None if format.is_stencil() => {
+            texel[0] = decode_stencil(format, data);
+        }
+        None => panic!("Unsupported texel format {:?}", format),
+    }
+    texel
+}
+
+/// Encode raw words into the texel at the start of `data`.
+///
+/// Depth formats encode the depth in the first word, stencil formats the
+/// stencil, preserving the other aspect.
+pub fn encode(format: Format, data: &mut [u8], texel: Texel) {
+    let BaseFormat(surface, ty) = format.base_format();
+    match layout(surface) {
+        Some(Layout::Plain { bits, order }) => {
+            let bytes = bits as usize / 8;
+            for (i, &channel) in order.iter().enumerate() {
+                let raw = encode_channel(texel[channel], bits, ty, channel);
+                write_bytes(&mut data[i * bytes ..], bytes, raw);
+            }
+        }
+        Some(Layout::Packed { bytes, channels }) => {
+            let mut raw = 0;
+            for &(channel, shift, bits) in channels {
+                raw |= encode_channel(texel[channel], bits, ty, channel) << shift;
+            }
+            write_bytes(data, bytes, raw);
+        }
+        Some(Layout::Float11_11_10) => {
+            let value = |channel: usize| f32::from_bits(texel[channel]);
+            let raw = f32_to_ufloat(value(0), 6)
+                | f32_to_ufloat(value(1), 6) << 11
+                | f32_to_ufloat(value(2), 5) << 22;
+            write_bytes(data, 4, raw as u64);
+        }
+        Some(Layout::SharedExponent) => {
+            let rgb = [
+                f32::from_bits(texel[0]),
+                f32::from_bits(texel[1]),
+                f32::from_bits(texel[2]),
+            ];
+            write_bytes(data, 4, encode_shared_exponent(rgb) as u64);
+        }
+        None if format.is_depth() => {
+            encode_depth(format, data, f32::from_bits(texel[0]));
+        }
+        None if format.is_stencil() => {
+            encode_stencil(format, data, texel[0]);
+        }
+        None => panic!("Unsupported texel format {:?}", format),
+    }
+}
+
+/// Decode the texel stored at the start of `data` into floats.
+///
+/// Integer values are converted to floats.
+pub fn decode_f32(format: Format, data: &[u8]) -> [f32; 4] {
+    let texel = decode(format, data);
+    let mut value = [0.0; 4];
+    for (value, &word) in value.iter_mut().zip(&texel) {
+        *value = match format.base_format().1 {
+            ChannelType::Uint => word as f32,
+            ChannelType::Sint => word as i32 as f32,
+            _ => f32::from_bits(word),
+        };
+    }
+    value
+}
+
+/// Decode the texel stored at the start of `data` into unsigned integers.
+///
+/// Float values are converted to integers, saturating, and signed integers
+/// keep their two's complement bits.
+pub fn decode_u32(format: Format, data: &[u8]) -> [u32; 4] {
+    let mut texel = decode(format, data);
+    if is_float(format) {
+        for word in texel.iter_mut() {
+            *word = f32::from_bits(*word) as u32;
+        }
+    }
+    texel
+}
+
+/// Decode the texel stored at the start of `data` into signed integers.
+///
+/// Float values are converted to integers, saturating, and unsigned
+/// integers keep their bits.
+pub fn decode_i32(format: Format, data: &[u8]) -> [i32; 4] {
+    let texel = decode(format, data);
+    let float = is_float(format);
+    let mut value = [0; 4];
+    for (value, &word) in value.iter_mut().zip(&texel) {
+        *value = if float {
+            f32::from_bits(word) as i32
+        } else {
+            word as i32
+        };
+    }
+    value
+}
+
+/// Encode floats into the texel at the start of `data`.
+///
+/// Floats are rounded to the nearest value of integer formats.
+pub fn encode_f32(format: Format, data: &mut [u8], value: [f32; 4]) {
+    let mut texel = [0; 4];
+    for (word, &value) in texel.iter_mut().zip(&value) {
+        *word = match format.base_format().1 {
+            ChannelType::Uint => value.round() as u32,
+            ChannelType::Sint => value.round() as i32 as u32,
+            _ => value.to_bits(),
+        };
+    }
+    encode(format, data, texel)
+}
+
+/// Encode unsigned integers into the texel at the start of `data`.
+pub fn encode_u32(format: Format, data: &mut [u8], value: [u32; 4]) {
+    let mut texel = value;
+    if is_float(format) {
+        for word in texel.iter_mut() {
+            *word = (*word as f32).to_bits();
+        }
+    }
+    encode(format, data, texel)
+}
+
+/// Encode signed integers into the texel at the start of `data`.
+pub fn encode_i32(format: Format, data: &mut [u8], value: [i32; 4]) {
+    let float = is_float(format);
+    let mut texel = [0; 4];
+    for (word, &value) in texel.iter_mut().zip(&value) {
+        *word = if float {
+            (value as f32).to_bits()
+        } else {
+            value as u32
+        };
+    }
+    encode(format, data, texel)
+}
+
+/// Decode the depth value of a depth (and stencil) texel.
+pub fn decode_depth(format: Format, data: &[u8]) -> f32 {
+    use self::SurfaceType as S;
+    match format.base_format().0 {
+        S::D16 | S::D16_S8 => read_bytes(data, 2) as f32 / 65535.0,
+        S::X8D24 | S::D24_S8 => (read_bytes(data, 4) & 0xFF_FFFF) as f32 / 16_777_215.0,
+        S::D32 | S::D32_S8 => f32::from_bits(read_bytes(data, 4) as u32),
+        _ => panic!("Unsupported depth format {:?}", format),
+    }
+}
+
+/// Encode the depth value of a depth (and stencil) texel, preserving the
+/// stencil value.
+pub fn encode_depth(format: Format, data: &mut [u8], depth: f32) {
+    use self::SurfaceType as S;
+    let unorm = |max: f32| (depth.clamp(0.0, 1.0) * max).round() as u64;
+    match format.base_format().0 {
+        S::D16 | S::D16_S8 => write_bytes(data, 2, unorm(65535.0)),
+        S::X8D24 | S::D24_S8 => {
+            let raw = read_bytes(data, 4) & 0xFF00_0000 | unorm(16_777_215.0);
+            write_bytes(data, 4, raw);
+        }
+        S::D32 | S::D32_S8 => write_bytes(data, 4, depth.to_bits() as u64),
+        _ => panic!("Unsupported depth format {:?}", format),
+    }
+}
+
+fn stencil_index(format: Format) -> usize {
+    use self::SurfaceType as S;
+    match format.base_format().0 {
+        S::S8 => 0,
+        S::D16_S8 => 2,
+        S::D24_S8 => 3,
+        S::D32_S8 => 4,
+        _ => panic!("Unsupported stencil format {:?}", format),
+    }
+}
+
+/// Decode the stencil value of a stencil (and depth) texel.
+pub fn decode_stencil(format: Format, data: &[u8]) -> u32 {
+    data[stencil_index(format)] as u32
+}
+
+/// Encode the stencil value of a stencil (and depth) texel, preserving the
+/// depth value.
+pub fn encode_stencil(format: Format, data: &mut [u8], stencil: u32) {
+    data[stencil_index(format)] = stencil as u8;
+}
+
+fn read_bytes(data: &[u8], count: usize) -> u64 {
+    let mut bytes = [0; 8];
+    bytes[.. count].copy_from_slice(&data[.. count]);
+    u64::from_le_bytes(bytes)
+}
+
+fn write_bytes(data: &mut [u8], count: usize, value: u64) {
+    data[.. count].copy_from_slice(&value.to_le_bytes()[.. count]);
+}
+
+/// Convert an sRGB encoded value to linear.
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Convert a linear value to sRGB encoding.
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Convert a half precision float to single precision.
+pub fn f16_to_f32(half: u16) -> f32 {
+    let sign = ((half & 0x8000) as u32) << 16;
+    let exponent = ((half >> 10) & 0x1F) as u32;
+    let mantissa = (half & 0x3FF) as u32;
+    let bits = match exponent {
+        0 if mantissa == 0 => sign,
+        // Subnormal halves are normal floats.
+        0 => {
+            let shift = mantissa.leading_zeros() - 21;
+            let mantissa = (mantissa << shift) & 0x3FF;
+            sign | ((113 - shift) << 23) | (mantissa << 13)
+        }
+        0x1F => sign | 0x7F80_0000 | (mantissa << 13),
+        _ => sign | ((exponent + 112) << 23) | (mantissa << 13),
+    };
+    f32::from_bits(bits)
+}
+
+/// Convert a single precision float to half precision, rounding to the
+/// nearest even value.
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xFF) as i32;
+    let mantissa = bits & 0x7F_FFFF;
+    if exponent == 0xFF {
+        // Infinity stays infinity, NaN stays NaN.
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7C00 | nan;
+    }
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1F {
+        return sign | 0x7C00;
+    }
+    let (mantissa, shift) = if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        (mantissa | 0x80_0000, (14 - exponent) as u32)
+    } else {
+        (mantissa, 13)
+    };
+    let half = (mantissa >> shift) as u16;
+    let rest = mantissa & ((1 << shift) - 1);
+    let halfway = 1 << (shift - 1);
+    let base = if exponent > 0 {
+        sign | ((exponent as u16) << 10) | half
+    } else {
+        sign | half
+    };
+    // Rounding up carries into the exponent when needed.
+    if rest > halfway || (rest == halfway && half & 1 != 0) {
+        base + 1
+    } else {
+        base
+    }
+}
+
+/// Convert an unsigned float with a 5-bit exponent and the given number of
+/// mantissa bits, as used by `B10_G11_R11`.
+fn ufloat_to_f32(raw: u32, mantissa_bits: u32) -> f32 {
+    let exponent = raw >> mantissa_bits;
+    let mantissa = raw & ((1 << mantissa_bits) - 1);
+    let fraction = mantissa as f32 / (1 << mantissa_bits) as f32;
+    match exponent {
+        0 => fraction * 2f32.powi(-14),
+        0x1F if mantissa == 0 => f32::INFINITY,
+        0x1F => f32::NAN,
+        _ => (1.0 + fraction) * 2f32.powi(exponent as i32 - 15),
+    }
+}
+
+/// Convert a float to an unsigned float with a 5-bit exponent, rounding to
+/// the nearest value. Negative values become zero.
+fn f32_to_ufloat(value: f32, mantissa_bits: u32) -> u32 {
+    let infinity = 0x1F << mantissa_bits;
+    if value.is_nan() {
+        return infinity | 1;
+    }
+    if value <= 0.0 {
+        return 0;
+    }
+    let bits = value.to_bits();
+    let exponent = ((bits >> 23) & 0xFF) as i32 - 127 + 15;
+    if exponent >= 0x1F {
+        return infinity;
+    }
+    let (mantissa, exponent, shift) = if exponent <= 0 {
+        (
+            (bits & 0x7F_FFFF) | 0x80_0000,
+            0,
+            23 - mantissa_bits + (1 - exponent) as u32,
+        )
+    } else {
+        (bits & 0x7F_FFFF, exponent as u32, 23 - mantissa_bits)
+    };
+    if shift >= 32 {
+        return 0;
+    }
+    // Rounding up carries into the exponent when needed.
+    let round = (mantissa >> (shift - 1)) & 1;
+    ((exponent << mantissa_bits) + (mantissa >> shift) + round).min(infinity)
+}
+
+/// Encode RGB floats into 9-bit mantissas sharing a 5-bit exponent.
+fn encode_shared_exponent(rgb: [f32; 3]) -> u32 {
+    const MANTISSA_BITS: i32 = 9;
+    const BIAS: i32 = 15;
+    let max_value = 511.0 / 512.0 * 2f32.powi(31 - BIAS);
+    let mut clamped = [0f32; 3];
+    for (c, &value) in clamped.iter_mut().zip(&rgb) {
+        *c = if value.is_nan() {
+            0.0
+        } else {
+            value.clamp(0.0, max_value)
+        };
+    }
+    let max = clamped[0].max(clamped[1]).max(clamped[2]);
+    let floor_log2 = if max > 0.0 {
+        max.log2().floor() as i32
+    } else {
+        -BIAS - 1
+    };
+    let mut exponent = floor_log2.max(-BIAS - 1) + 1 + BIAS;
+    let max_mantissa = (max / 2f32.powi(exponent - BIAS - MANTISSA_BITS) + 0.5).floor();
+    if max_mantissa as i32 == 1 << MANTISSA_BITS {
+        exponent += 1;
+    }
+    let scale = 2f32.powi(exponent - BIAS - MANTISSA_BITS);
+    let mut raw = (exponent as u32) << 27;
+    for (channel, &c) in clamped.iter().enumerate() {
+        raw |= ((c / scale + 0.5).floor() as u32) << (9 * channel);
+    }
+    raw
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut data = [0u8; 32];
+        let color = [0.2f32, 0.4, 0.6, 1.0];
+        encode_f32(Format::Bgra8Unorm, &mut data, color);
+        assert_eq!(&data[.. 4], &[153, 102, 51, 255]);
+        assert_eq!(decode_f32(Format::Bgra8Unorm, &data), color);
+
+        encode_f32(Format::Rgba16Sfloat, &mut data, color);
+        assert_eq!(decode_f32(Format::Rgba16Sfloat, &data)[3], 1.0);
+        encode_i32(Format::Rg32Sint, &mut data, [-3, 7, 0, 0]);
+        assert_eq!(decode_i32(Format::Rg32Sint, &data), [-3, 7, 0, 1]);
+        encode_f32(Format::Rgba64Sfloat, &mut data, color);
+        assert_eq!(decode_f32(Format::Rgba64Sfloat, &data), color);
+        encode_u32(Format::R64Uint, &mut data, [1 << 31, 0, 0, 0]);
+        assert_eq!(decode_u32(Format::R64Uint, &data)[0], 1 << 31);
+
+        // sRGB values are stored encoded and decoded to linear
+        encode_f32(Format::Rgba8Srgb, &mut data, [0.5, 0.5, 0.5, 0.5]);
+        assert_eq!(&data[.. 4], &[188, 188, 188, 128]);
+        let decoded = decode_f32(Format::Rgba8Srgb, &data);
+        assert!((decoded[0] - 0.5).abs() < 0.005, "{:?}", decoded);
+
+        encode_f32(Format::Rgba8Snorm, &mut data, [-1.0, -0.5, 0.5, 1.0]);
+        assert_eq!(&data[.. 4], &[0x81, 0xC0, 0x40, 0x7F]);
+        encode_f32(Format::Rg8Sscaled, &mut data, [-200.0, 3.4, 0.0, 0.0]);
+        assert_eq!(
+            decode_f32(Format::Rg8Sscaled, &data),
+            [-128.0, 3.0, 0.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn test_packed() {
+        let mut data = [0u8; 4];
+        encode_f32(Format::A2b10g10r10Unorm, &mut data, [1.0, 0.0, 1.0, 1.0]);
+        assert_eq!(u32::from_le_bytes(data), 0xC000_0000 | 0x3FF << 20 | 0x3FF);
+        encode_u32(Format::A2b10g10r10Uint, &mut data, [5, 6, 7, 2]);
+        assert_eq!(decode_u32(Format::A2b10g10r10Uint, &data), [5, 6, 7, 2]);
+
+        let color = [1.0f32, 0.5, 64512.0, 1.0];
+        encode_f32(Format::B10g11r11Ufloat, &mut data, color);
+        assert_eq!(u32::from_le_bytes(data) & 0x7FF, 15 << 6);
+        assert_eq!(decode_f32(Format::B10g11r11Ufloat, &data), color);
+        encode_f32(Format::B10g11r11Ufloat, &mut data, [-1.0, 1.0e9, 0.0, 1.0]);
+        assert_eq!(
+            decode_f32(Format::B10g11r11Ufloat, &data),
+            [0.0, f32::INFINITY, 0.0, 1.0]
+        );
+
+        let color = [1.0f32, 0.25, 3.0, 1.0];
+        encode_f32(Format::E5b9g9r9Ufloat, &mut data, color);
+        assert_eq!(u32::from_le_bytes(data) >> 27, 17);
+        assert_eq!(decode_f32(Format::E5b9g9r9Ufloat, &data), color);
+    }
+
+    #[test]
+    fn test_depth_stencil() {
+        let mut data = [0u8; 4];
+        encode_stencil(Format::D24UnormS8Uint, &mut data, 0x5A);
+        encode_depth(Format::D24UnormS8Uint, &mut data, 1.0);
+        assert_eq!(decode_depth(Format::D24UnormS8Uint, &data), 1.0);
+        assert_eq!(decode_stencil(Format::D24UnormS8Uint, &data), 0x5A);
+        assert_eq!(decode_f32(Format::D24UnormS8Uint, &data)[0], 1.0);
+        assert!(is_supported(Format::S8Uint));
+        assert!(!is_supported(Format::Bc1RgbUnorm));
+    }
+
+    #[test]
+    fn test_f16() {
+        for &value in &[
+            0.0f32,
+            1.0,
+            -2.5,
+            65504.0,
+            0.000_061_035_156,
+            0.000_000_059_604_645,
+        ] {
+            assert_eq!(f16_to_f32(f32_to_f16(value)), value);
+        }
+        assert_eq!(f32_to_f16(1.0e6), 0x7C00);
+    }
+}
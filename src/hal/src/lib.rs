@@ -440,8 +440,8 @@ pub enum IndexType {
 /// // complicated for some backends).
 /// let instance = backend::Instance;
 /// // We can get a list of the available adapters, which are either physical graphics
-/// // devices, or virtual adapters. Because we are using the dummy `empty` backend,
-/// // there will be nothing in this list.
+/// // devices, or virtual adapters. Because we are using the `empty` backend,
+/// // there will be a single CPU adapter in this list.
 /// for (idx, adapter) in hal::Instance::enumerate_adapters(&instance).iter().enumerate() {
 ///     println!("Adapter {}: {:?}", idx, adapter.info);
 /// }
@@ -242,6 +242,7 @@ pub struct DescriptorSetCopy<'a, B: Backend> {
 
 bitflags! {
     /// Descriptor pool creation flags.
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
     pub struct DescriptorPoolCreateFlags: u32 {
         /// Specifies that descriptor sets are allowed to be freed from the pool
         /// individually.
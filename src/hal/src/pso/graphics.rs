@@ -273,6 +273,7 @@ pub type SampleMask = u64;
 
 ///
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Multisampling {
     ///
     pub rasterization_samples: image::NumSamples,
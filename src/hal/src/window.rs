@@ -256,6 +256,7 @@ pub type SwapImageIndex = u32;
 /// Specifies the mode regulating how a swapchain presents frames.
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum PresentMode {
     /// Don't ever wait for v-sync.
     Immediate = 0,
@@ -314,6 +315,7 @@ bitflags!(
 /// # }
 /// ```
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SwapchainConfig {
     /// Presentation mode.
     pub present_mode: PresentMode,
@@ -4,7 +4,8 @@
         feature = "dx12",
         feature = "dx11",
         feature = "metal",
-        feature = "gl"
+        feature = "gl",
+        feature = "empty"
     )),
     allow(dead_code)
 )]
@@ -18,6 +19,7 @@ use std::fs::File;
 use std::path::PathBuf;
 
 use ron::de;
+use warden::stats::{self, Baseline, Stats};
 
 #[derive(Debug, Deserialize)]
 enum Expectation {
@@ -45,6 +47,10 @@ struct Disabilities {}
 struct Harness {
     base_path: PathBuf,
     suite: Vec<TestGroup>,
+    /// Runs of every test that are not measured.
+    warmup: usize,
+    /// Measured runs of every test.
+    runs: usize,
 }
 
 impl Harness {
@@ -68,10 +74,21 @@ impl Harness {
             })
             .collect();
 
-        Harness { base_path, suite }
+        Harness {
+            base_path,
+            suite,
+            warmup: 2,
+            runs: 10,
+        }
     }
 
-    fn run<I: hal::Instance>(&self, instance: I, _disabilities: Disabilities) {
+    fn run<I: hal::Instance>(
+        &self,
+        backend: &str,
+        instance: I,
+        _disabilities: Disabilities,
+        results: &mut Baseline,
+    ) {
         use hal::adapter::PhysicalDevice as _;
 
         for tg in &self.suite {
@@ -108,74 +125,120 @@ impl Harness {
                         "\tskipped (features missing: {:?})",
                         test.features - features
                     );
+                    continue;
                 }
-                let mut max_compute_work_groups = [0; 3];
-                for job_name in &test.jobs {
-                    if let warden::raw::Job::Compute { dispatch, .. } = tg.scene.jobs[job_name] {
-                        for (max, count) in max_compute_work_groups.iter_mut().zip(dispatch.iter())
-                        {
-                            *max = (*max).max(*count);
-                        }
-                    }
-                }
-                if max_compute_work_groups[0] > limits.max_compute_work_group_size[0]
-                    || max_compute_work_groups[1] > limits.max_compute_work_group_size[1]
-                    || max_compute_work_groups[2] > limits.max_compute_work_group_size[2]
+                if let Err(reason) =
+                    tg.scene
+                        .check_limits(&test.jobs, &self.base_path.join("data"), &limits)
                 {
-                    println!("\tskipped (compute {:?})", max_compute_work_groups);
+                    println!("\tskipped ({})", reason);
                     continue;
                 }
 
-                scene.run(test.jobs.iter());
-                let time = scene.measure_time();
-                println!(" {} mcs", time / 1000);
+                for _ in 0 .. self.warmup {
+                    scene.run(test.jobs.iter());
+                }
+                let samples = (0 .. self.runs)
+                    .map(|_| {
+                        scene.run(test.jobs.iter());
+                        scene.measure_time()
+                    })
+                    .collect::<Vec<_>>();
+                let stats = Stats::from_samples(&samples);
+                println!(
+                    " min {:.1} / median {:.1} / p95 {:.1} mcs",
+                    micros(stats.min),
+                    micros(stats.median),
+                    micros(stats.p95),
+                );
+                results.insert(backend, format!("{}/{}", tg.name, test_name), stats);
             }
         }
     }
 }
 
+fn micros(time: u64) -> f64 {
+    time as f64 / 1000.0
+}
+
 fn main() {
-    use std::env;
+    use std::{env, process};
 
     #[cfg(feature = "env_logger")]
     env_logger::init();
 
-    let suite_name = match env::args().nth(1) {
+    let mut args = env::args().skip(1);
+    let mut suite_name = None;
+    let mut runs = None;
+    let mut warmup = None;
+    let mut save_path = None;
+    let mut compare_path = None;
+    let mut threshold = 10.0;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--runs" => runs = args.next().and_then(|n| n.parse().ok()),
+            "--warmup" => warmup = args.next().and_then(|n| n.parse().ok()),
+            "--save" => save_path = args.next().map(PathBuf::from),
+            "--compare" => compare_path = args.next().map(PathBuf::from),
+            "--threshold" => {
+                threshold = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .expect("--threshold needs a percentage")
+            }
+            _ => suite_name = Some(arg),
+        }
+    }
+    let suite_name = match suite_name {
         Some(name) => name,
         None => {
             println!("Call with the argument of the bench suite name");
+            println!("Optionally, add `--runs <n>` and `--warmup <n>` to choose the repetitions,");
+            println!("`--save <path>` to write the results as a JSON baseline, and");
+            println!("`--compare <path>` to check them against a baseline, failing on medians");
+            println!("slower by more than `--threshold <percent>` (10 by default)");
             return;
         }
     };
 
-    let harness = Harness::new(&suite_name);
+    let mut harness = Harness::new(&suite_name);
+    if let Some(runs) = runs {
+        assert_ne!(runs, 0, "At least one run is needed");
+        harness.runs = runs;
+    }
+    if let Some(warmup) = warmup {
+        harness.warmup = warmup;
+    }
+    let mut results = Baseline::default();
     #[cfg(feature = "vulkan")]
     {
         println!("Benching Vulkan:");
         let instance = gfx_backend_vulkan::Instance::create("warden", 1).unwrap();
-        harness.run(instance, Disabilities::default());
+        harness.run("vulkan", instance, Disabilities::default(), &mut results);
     }
     #[cfg(feature = "dx12")]
     {
         println!("Benching DX12:");
         let instance = gfx_backend_dx12::Instance::create("warden", 1).unwrap();
-        harness.run(instance, Disabilities::default());
+        harness.run("dx12", instance, Disabilities::default(), &mut results);
     }
     #[cfg(feature = "dx11")]
     {
         println!("Benching DX11:");
         let instance = gfx_backend_dx11::Instance::create("warden", 1).unwrap();
-        harness.run(instance, Disabilities::default());
+        harness.run("dx11", instance, Disabilities::default(), &mut results);
     }
     #[cfg(feature = "metal")]
     {
         println!("Benching Metal:");
         let instance = gfx_backend_metal::Instance::create("warden", 1).unwrap();
         harness.run(
+            "metal",
             instance,
             Disabilities {
                 ..Disabilities::default()
             },
+            &mut results,
         );
     }
     #[cfg(feature = "gl")]
@@ -194,7 +257,7 @@ fn main() {
                 .split()
         };
         let instance = gfx_backend_gl::Surface::from_context(context);
-        harness.run(instance, Disabilities::default());
+        harness.run("gl", instance, Disabilities::default(), &mut results);
     }
     #[cfg(feature = "gl-headless")]
     {
@@ -206,17 +269,49 @@ fn main() {
             .unwrap();
         let context = unsafe { context.make_current() }.expect("Unable to make context current");
         let instance = gfx_backend_gl::Headless::from_context(context);
-        harness.run(instance, Disabilities::default());
+        harness.run("gl-headless", instance, Disabilities::default(), &mut results);
+    }
+    #[cfg(feature = "empty")]
+    {
+        println!("Benching CPU:");
+        let instance = gfx_backend_empty::Instance::create("warden", 1).unwrap();
+        harness.run("empty", instance, Disabilities::default(), &mut results);
     }
     #[cfg(not(any(
         feature = "vulkan",
         feature = "dx12",
         feature = "dx11",
         feature = "metal",
-        feature = "gl"
+        feature = "gl",
+        feature = "empty"
     )))]
     {
         println!("No backend selected!");
         let _ = harness;
     }
+
+    if let Some(path) = save_path {
+        results
+            .save(&path)
+            .expect(&format!("failed to write the baseline {:?}", path));
+    }
+    if let Some(path) = compare_path {
+        let baseline =
+            Baseline::load(&path).expect(&format!("failed to read the baseline {:?}", path));
+        let regressions = stats::find_regressions(&baseline, &results, threshold / 100.0);
+        for r in &regressions {
+            println!(
+                "Regression on {}: {} median {:.1} -> {:.1} mcs (+{:.1}%)",
+                r.backend,
+                r.bench,
+                micros(r.baseline),
+                micros(r.current),
+                r.change * 100.0,
+            );
+        }
+        if !regressions.is_empty() {
+            process::exit(1);
+        }
+        println!("No regressions above {}% against {:?}", threshold, path);
+    }
 }
@@ -4,7 +4,8 @@
         feature = "dx12",
         feature = "dx11",
         feature = "metal",
-        feature = "gl"
+        feature = "gl",
+        feature = "empty"
     )),
     allow(dead_code)
 )]
@@ -14,15 +15,35 @@ extern crate gfx_warden as warden;
 extern crate serde;
 
 use std::collections::HashMap;
-use std::fs::File;
+use std::fs::{self, File};
+use std::ops::Range;
 use std::path::PathBuf;
+use std::time::Instant;
 
+use hal::format::Format;
 use ron::de;
+use warden::compare::{self, Image, Tolerance};
+use warden::report::{Record, Report, Status};
 
 #[derive(Debug, Deserialize)]
 enum Expectation {
     Buffer(String, Vec<u8>),
     ImageRow(String, usize, Vec<u8>),
+    /// Whole image compared against a reference PNG in the data directory.
+    Image(String, String, Tolerance),
+    /// Results of a range of queries in a query pool.
+    QueryResults(String, Range<hal::query::Id>, QueryCheck),
+}
+
+/// Condition on the values of the queries, as 64-bit integers.
+#[derive(Debug, Deserialize)]
+enum QueryCheck {
+    /// Values are exactly the given ones.
+    Equal(Vec<u64>),
+    /// Values are not lower than the given ones.
+    AtLeast(Vec<u64>),
+    /// Values are monotonically increasing, as written timestamps should be.
+    Increasing,
 }
 
 #[derive(Debug, Deserialize)]
@@ -30,6 +51,9 @@ struct Test {
     features: hal::Features,
     jobs: Vec<String>,
     expect: Expectation,
+    /// Backends known to fail the test, reporting XFAIL instead of FAIL.
+    #[serde(default)]
+    xfail: Vec<String>,
 }
 
 type Suite = HashMap<String, HashMap<String, Test>>;
@@ -45,13 +69,44 @@ struct TestResults {
     pass: usize,
     skip: usize,
     fail: usize,
+    xfail: usize,
+    xpass: usize,
+}
+
+enum Failure {
+    Bytes { expected: Vec<u8>, actual: Vec<u8> },
+    Message(String),
+}
+
+/// Known shortcomings of a backend.
+struct Disabilities {
+    /// Features that are reported by the adapter but don't work well enough,
+    /// so the tests requiring them are skipped.
+    features: hal::Features,
+}
+
+impl Default for Disabilities {
+    fn default() -> Self {
+        Disabilities {
+            features: hal::Features::empty(),
+        }
+    }
 }
 
-#[derive(Default)]
-struct Disabilities {}
+impl Disabilities {
+    /// Add the features the adapter doesn't report at all.
+    fn with_adapter_features(&self, features: hal::Features) -> Self {
+        Disabilities {
+            features: self.features | (hal::Features::all() - features),
+        }
+    }
+}
 
 struct Harness {
     base_path: PathBuf,
+    output_path: PathBuf,
+    filters: Vec<String>,
+    suite_name: String,
     suite: Vec<TestGroup>,
 }
 
@@ -60,7 +115,10 @@ impl Harness {
         let base_path = PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/../../work"));
         println!("Parsing test suite '{}'...", suite_name);
 
-        let suite_path = base_path.join("reftests").join(suite_name).with_extension("ron");
+        let suite_path = base_path
+            .join("reftests")
+            .join(suite_name)
+            .with_extension("ron");
         let suite = File::open(&suite_path)
             .map_err(de::Error::from)
             .and_then(de::from_reader::<_, Suite>)
@@ -76,21 +134,44 @@ impl Harness {
             })
             .collect();
 
-        Harness { base_path, suite }
+        Harness {
+            output_path: base_path.join("output"),
+            filters: Vec::new(),
+            base_path,
+            suite_name: suite_name.to_string(),
+            suite,
+        }
     }
 
-    fn run<I: hal::Instance>(&self, instance: I, _disabilities: Disabilities) -> usize {
+    fn run<I: hal::Instance>(
+        &self,
+        backend: &str,
+        instance: I,
+        disabilities: Disabilities,
+        report: &mut Report,
+    ) -> usize {
         use hal::adapter::PhysicalDevice as _;
 
         let mut results = TestResults {
             pass: 0,
             skip: 0,
             fail: 0,
+            xfail: 0,
+            xpass: 0,
         };
         for tg in &self.suite {
+            let tests = tg
+                .tests
+                .iter()
+                .filter(|&(test_name, _)| self.is_selected(&tg.name, test_name))
+                .collect::<Vec<_>>();
+            if tests.is_empty() {
+                continue;
+            }
+
             let mut adapters = instance.enumerate_adapters();
             let adapter = adapters.remove(0);
-            let features = adapter.physical_device.features();
+            let disabled = disabilities.with_adapter_features(adapter.physical_device.features());
             let limits = adapter.physical_device.limits();
             //println!("\t{:?}", adapter.info);
             println!("\tScene '{}':", tg.name);
@@ -102,8 +183,17 @@ impl Harness {
                     _ => true,
                 });
                 if !all_spirv {
-                    println!("\t\tskipped {} tests (GLSL shaders)", tg.tests.len());
-                    results.skip += tg.tests.len();
+                    println!("\t\tskipped {} tests (GLSL shaders)", tests.len());
+                    results.skip += tests.len();
+                    for (test_name, _) in tests {
+                        report.records.push(Record::skip(
+                            &self.suite_name,
+                            &tg.name,
+                            test_name,
+                            backend,
+                            "GLSL shaders".to_string(),
+                        ));
+                    }
                     continue;
                 }
             }
@@ -115,58 +205,261 @@ impl Harness {
             )
             .unwrap();
 
-            for (test_name, test) in &tg.tests {
+            for (test_name, test) in tests {
                 print!("\t\tTest '{}' ...", test_name);
-                if !features.contains(test.features) {
-                    println!(
-                        "\tskipped (features missing: {:?})",
-                        test.features - features
-                    );
+                if test.features.intersects(disabled.features) {
+                    let missing = test.features & disabled.features;
+                    let reason = format!("features missing: {:?}", missing);
+                    println!("\tskipped ({})", reason);
                     results.skip += 1;
+                    report.records.push(Record::skip(
+                        &self.suite_name,
+                        &tg.name,
+                        test_name,
+                        backend,
+                        reason,
+                    ));
+                    continue;
                 }
-                let mut max_compute_work_groups = [0; 3];
-                for job_name in &test.jobs {
-                    if let warden::raw::Job::Compute { dispatch, .. } = tg.scene.jobs[job_name] {
-                        for (max, count) in max_compute_work_groups.iter_mut().zip(dispatch.iter())
-                        {
-                            *max = (*max).max(*count);
-                        }
-                    }
-                }
-                if max_compute_work_groups[0] > limits.max_compute_work_group_size[0]
-                    || max_compute_work_groups[1] > limits.max_compute_work_group_size[1]
-                    || max_compute_work_groups[2] > limits.max_compute_work_group_size[2]
+                if let Err(reason) =
+                    tg.scene
+                        .check_limits(&test.jobs, &self.base_path.join("data"), &limits)
                 {
-                    println!("\tskipped (compute {:?})", max_compute_work_groups);
+                    println!("\tskipped ({})", reason);
                     results.skip += 1;
+                    report.records.push(Record::skip(
+                        &self.suite_name,
+                        &tg.name,
+                        test_name,
+                        backend,
+                        reason,
+                    ));
                     continue;
                 }
 
+                let start = Instant::now();
                 scene.run(test.jobs.iter());
 
                 print!("\tran: ");
-                let (guard, row, data) = match test.expect {
+                let failure = match test.expect {
                     Expectation::Buffer(ref buffer, ref data) => {
-                        (scene.fetch_buffer(buffer), 0, data)
+                        check_bytes(&scene.fetch_buffer(buffer), 0, data)
                     }
                     Expectation::ImageRow(ref image, row, ref data) => {
-                        (scene.fetch_image(image), row, data)
+                        check_bytes(&scene.fetch_image(image), row, data)
+                    }
+                    Expectation::Image(ref image, ref reference, ref tolerance) => {
+                        let output_name =
+                            format!("{}-{}-{}-{}", backend, self.suite_name, tg.name, test_name);
+                        self.check_image(tg, &mut scene, image, reference, tolerance, &output_name)
+                            .err()
+                            .map(Failure::Message)
+                    }
+                    Expectation::QueryResults(ref pool, ref queries, ref check) => {
+                        let values = scene.fetch_query_results(pool, queries.clone());
+                        check_queries(&values, check)
                     }
                 };
+                let elapsed = start.elapsed();
+                let known_failure = test.xfail.iter().any(|name| name == backend);
+                let verdict = if known_failure { "XFAIL" } else { "FAIL" };
 
-                if data.as_slice() == guard.row(row) {
-                    println!("PASS");
-                    results.pass += 1;
-                } else {
-                    println!("FAIL {:?}", guard.row(row));
-                    results.fail += 1;
+                let mut record = match failure {
+                    None if known_failure => {
+                        println!("XPASS (expected to fail on '{}')", backend);
+                        results.xpass += 1;
+                        Record {
+                            status: Status::XPass,
+                            ..Record::pass(&self.suite_name, &tg.name, test_name, backend, elapsed)
+                        }
+                    }
+                    None => {
+                        println!("PASS");
+                        results.pass += 1;
+                        Record::pass(&self.suite_name, &tg.name, test_name, backend, elapsed)
+                    }
+                    Some(Failure::Bytes { expected, actual }) => {
+                        println!("{} {:?}", verdict, actual);
+                        Record::fail(
+                            &self.suite_name,
+                            &tg.name,
+                            test_name,
+                            backend,
+                            elapsed,
+                            &expected,
+                            &actual,
+                        )
+                    }
+                    Some(Failure::Message(message)) => {
+                        println!("{} {}", verdict, message);
+                        Record::mismatch(
+                            &self.suite_name,
+                            &tg.name,
+                            test_name,
+                            backend,
+                            elapsed,
+                            message,
+                        )
+                    }
+                };
+                if record.status == Status::Fail {
+                    if known_failure {
+                        results.xfail += 1;
+                        record.status = Status::XFail;
+                    } else {
+                        results.fail += 1;
+                    }
                 }
+                report.records.push(record);
             }
         }
 
         println!("\t{:?}", results);
+        if results.skip != 0 && results.pass + results.fail + results.xfail + results.xpass == 0 {
+            // Nothing was actually tested, which shouldn't pass silently.
+            println!("\tall tests were skipped");
+            return 1;
+        }
         results.fail
     }
+
+    /// Check if a test passes the command line filters.
+    fn is_selected(&self, scene_name: &str, test_name: &str) -> bool {
+        let path = format!("{}/{}", scene_name, test_name);
+        self.filters.is_empty() || self.filters.iter().any(|filter| glob_match(filter, &path))
+    }
+
+    /// Compare a whole image with its reference PNG.
+    ///
+    /// On failure, the actual and the diff images are written into the output
+    /// directory, and the returned message describes the difference.
+    fn check_image<B: hal::Backend>(
+        &self,
+        tg: &TestGroup,
+        scene: &mut warden::gpu::Scene<B>,
+        image: &str,
+        reference: &str,
+        tolerance: &Tolerance,
+        output_name: &str,
+    ) -> Result<(), String> {
+        let swizzle = match tg.scene.resources.get(image) {
+            Some(&warden::raw::Resource::Image { format, .. }) => match format {
+                Format::Rgba8Unorm | Format::Rgba8Srgb => false,
+                Format::Bgra8Unorm | Format::Bgra8Srgb => true,
+                other => panic!("Unsupported format for image comparison: {:?}", other),
+            },
+            _ => panic!("Unable to find image to compare: {}", image),
+        };
+
+        let actual = {
+            let guard = scene.fetch_image(image);
+            let mut data = Vec::new();
+            for row in 0 .. guard.num_rows() {
+                data.extend_from_slice(guard.row(row));
+            }
+            if swizzle {
+                for pixel in data.chunks_mut(4) {
+                    pixel.swap(0, 2);
+                }
+            }
+            Image {
+                width: (guard.row(0).len() / 4) as u32,
+                height: guard.num_rows() as u32,
+                data,
+            }
+        };
+
+        let reference_path = self.base_path.join("data").join(reference);
+        let reference = Image::load_png(&reference_path)
+            .map_err(|e| format!("unable to load {:?}: {}", reference_path, e))?;
+        if (actual.width, actual.height) != (reference.width, reference.height) {
+            return Err(format!(
+                "size {}x{} doesn't match the reference {}x{}",
+                actual.width, actual.height, reference.width, reference.height
+            ));
+        }
+
+        let comparison = compare::compare(&actual, &reference, tolerance);
+        if comparison.passes(tolerance) {
+            return Ok(());
+        }
+
+        let diff_path = self.output_path.join(format!("{}.diff.png", output_name));
+        fs::create_dir_all(&self.output_path)
+            .and_then(|_| actual.save_png(&self.output_path.join(format!("{}.png", output_name))))
+            .and_then(|_| comparison.diff.save_png(&diff_path))
+            .map_err(|e| format!("unable to write {:?}: {}", diff_path, e))?;
+
+        Err(format!(
+            "{} pixels differ by up to {}, PSNR {:.2} dB, SSIM {:.4}, diff in {:?}",
+            comparison.differing_pixels,
+            comparison.max_channel_difference,
+            comparison.psnr,
+            comparison.ssim,
+            diff_path,
+        ))
+    }
+}
+
+fn check_bytes<B: hal::Backend>(
+    guard: &warden::gpu::FetchGuard<B>,
+    row: usize,
+    expected: &[u8],
+) -> Option<Failure> {
+    let actual = guard.row(row);
+    if actual == expected {
+        None
+    } else {
+        Some(Failure::Bytes {
+            expected: expected.to_vec(),
+            actual: actual.to_vec(),
+        })
+    }
+}
+
+fn check_queries(values: &[u64], check: &QueryCheck) -> Option<Failure> {
+    let passes = match *check {
+        QueryCheck::Equal(ref expected) => values == &expected[..],
+        QueryCheck::AtLeast(ref minimum) => {
+            values.len() == minimum.len() && values.iter().zip(minimum).all(|(v, m)| v >= m)
+        }
+        QueryCheck::Increasing => values.windows(2).all(|pair| pair[0] <= pair[1]),
+    };
+    if passes {
+        None
+    } else {
+        Some(Failure::Message(format!(
+            "query results {:?} don't satisfy {:?}",
+            values, check
+        )))
+    }
+}
+
+/// Match a text against a pattern, where `*` stands for any sequence
+/// of characters and `?` for any single character.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    // Position of the last `*` in the pattern, and of the text it was matched at.
+    let mut star = None;
+    let (mut p, mut t) = (0, 0);
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // Let the last `*` swallow one more character.
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p ..].iter().all(|&c| c == '*')
 }
 
 fn main() {
@@ -176,42 +469,67 @@ fn main() {
     env_logger::init();
     let mut num_failures = 0;
 
-    let suite_name = match env::args().nth(1) {
+    let mut args = env::args().skip(1);
+    let mut suite_name = None;
+    let mut json_path = None;
+    let mut junit_path = None;
+    let mut output_path = None;
+    let mut filters = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--filter" => filters.extend(args.next()),
+            "--json" => json_path = args.next().map(PathBuf::from),
+            "--junit" => junit_path = args.next().map(PathBuf::from),
+            "--output" => output_path = args.next().map(PathBuf::from),
+            _ => suite_name = Some(arg),
+        }
+    }
+    let suite_name = match suite_name {
         Some(name) => name,
         None => {
             println!("Call with the argument of the reftest suite name");
+            println!("Optionally, add `--json <path>` and/or `--junit <path>` to write reports");
+            println!("and `--output <dir>` to choose where images of failed tests are written");
+            println!("Tests can be selected with `--filter <scene/test>` globs, like `basic/*`");
             return;
         }
     };
 
-    let harness = Harness::new(&suite_name);
+    let mut harness = Harness::new(&suite_name);
+    if let Some(path) = output_path {
+        harness.output_path = path;
+    }
+    harness.filters = filters;
+    let mut report = Report::default();
     #[cfg(feature = "vulkan")]
     {
         println!("Testing Vulkan:");
         let instance = gfx_backend_vulkan::Instance::create("warden", 1).unwrap();
-        num_failures += harness.run(instance, Disabilities::default());
+        num_failures += harness.run("vulkan", instance, Disabilities::default(), &mut report);
     }
     #[cfg(feature = "dx12")]
     {
         println!("Testing DX12:");
         let instance = gfx_backend_dx12::Instance::create("warden", 1).unwrap();
-        num_failures += harness.run(instance, Disabilities::default());
+        num_failures += harness.run("dx12", instance, Disabilities::default(), &mut report);
     }
     #[cfg(feature = "dx11")]
     {
         println!("Testing DX11:");
         let instance = gfx_backend_dx11::Instance::create("warden", 1).unwrap();
-        num_failures += harness.run(instance, Disabilities::default());
+        num_failures += harness.run("dx11", instance, Disabilities::default(), &mut report);
     }
     #[cfg(feature = "metal")]
     {
         println!("Testing Metal:");
         let instance = gfx_backend_metal::Instance::create("warden", 1).unwrap();
         num_failures += harness.run(
+            "metal",
             instance,
             Disabilities {
                 ..Disabilities::default()
             },
+            &mut report,
         );
     }
     #[cfg(feature = "gl")]
@@ -230,7 +548,7 @@ fn main() {
                 .split()
         };
         let instance = gfx_backend_gl::Surface::from_context(context);
-        num_failures += harness.run(instance, Disabilities::default());
+        num_failures += harness.run("gl", instance, Disabilities::default(), &mut report);
     }
     #[cfg(feature = "gl-headless")]
     {
@@ -242,9 +560,47 @@ fn main() {
             .unwrap();
         let context = unsafe { context.make_current() }.expect("Unable to make context current");
         let instance = gfx_backend_gl::Headless::from_context(context);
-        num_failures += harness.run(instance, Disabilities::default());
+        num_failures += harness.run(
+            "gl-headless",
+            instance,
+            Disabilities::default(),
+            &mut report,
+        );
+    }
+    #[cfg(feature = "empty")]
+    {
+        // Runs on the CPU, without any window system or driver.
+        println!("Testing CPU:");
+        let instance = gfx_backend_empty::Instance::create("warden", 1).unwrap();
+        num_failures += harness.run("empty", instance, Disabilities::default(), &mut report);
     }
     let _ = harness;
     num_failures += 0; // mark as mutated
+
+    if let Some(path) = json_path {
+        let file = File::create(&path).expect(&format!("failed to create {:?}", path));
+        report.write_json(file).unwrap();
+    }
+    if let Some(path) = junit_path {
+        let file = File::create(&path).expect(&format!("failed to create {:?}", path));
+        report.write_junit(file).unwrap();
+    }
     process::exit(num_failures as _);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::glob_match;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("basic/pass-through", "basic/pass-through"));
+        assert!(glob_match("basic/*", "basic/pass-through"));
+        assert!(glob_match("*/copy-*", "transfer/copy-buf-cut"));
+        assert!(glob_match("*-b?f*", "transfer/copy-buf-cut"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("basic/*", "compute/fill"));
+        assert!(!glob_match("*/copy-?", "transfer/copy-buf"));
+        assert!(!glob_match("basic", "basic/pass-through"));
+    }
+}
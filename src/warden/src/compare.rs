@@ -0,0 +1,244 @@
+//! Whole-image comparison against reference PNG files.
+
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+/// RGBA8 image in host memory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    /// Tightly packed rows of RGBA8 pixels.
+    pub data: Vec<u8>,
+}
+
+impl Image {
+    /// Load a PNG file, converting it to RGBA8.
+    pub fn load_png(path: &Path) -> io::Result<Self> {
+        let mut decoder = png::Decoder::new(File::open(path)?);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let (info, mut reader) = decoder.read_info().map_err(to_io_error)?;
+        let mut raw = vec![0; info.buffer_size()];
+        reader.next_frame(&mut raw).map_err(to_io_error)?;
+
+        let data = match info.color_type {
+            png::ColorType::RGBA => raw,
+            png::ColorType::RGB => raw
+                .chunks(3)
+                .flat_map(|p| vec![p[0], p[1], p[2], 0xFF])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => raw
+                .chunks(2)
+                .flat_map(|p| vec![p[0], p[0], p[0], p[1]])
+                .collect(),
+            png::ColorType::Grayscale => raw.iter().flat_map(|&v| vec![v, v, v, 0xFF]).collect(),
+            png::ColorType::Indexed => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "indexed PNG was not expanded",
+                ));
+            }
+        };
+
+        Ok(Image {
+            width: info.width,
+            height: info.height,
+            data,
+        })
+    }
+
+    /// Save the image as an RGBA8 PNG file.
+    pub fn save_png(&self, path: &Path) -> io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::RGBA);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&self.data))
+            .map_err(to_io_error)
+    }
+
+    fn pixel(&self, x: u32, y: u32) -> &[u8] {
+        let offset = (y * self.width + x) as usize * 4;
+        &self.data[offset .. offset + 4]
+    }
+}
+
+fn to_io_error<E: std::error::Error + Send + Sync + 'static>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// Acceptable deviation of an image from its reference.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Tolerance {
+    /// Maximum absolute difference of a channel for a pixel to count as equal.
+    #[serde(default)]
+    pub channel: u8,
+    /// Maximum number of pixels that are allowed to differ.
+    #[serde(default)]
+    pub pixels: usize,
+    /// Minimum peak signal-to-noise ratio, in decibels.
+    #[serde(default)]
+    pub psnr: Option<f32>,
+    /// Minimum structural similarity index of the luminance, up to 1.0.
+    #[serde(default)]
+    pub ssim: Option<f32>,
+}
+
+/// Result of comparing two images.
+#[derive(Clone, Debug)]
+pub struct Comparison {
+    /// Number of pixels with a channel differing by more than the tolerance.
+    pub differing_pixels: usize,
+    /// Largest difference of any channel.
+    pub max_channel_difference: u8,
+    /// Peak signal-to-noise ratio, in decibels, infinite for equal images.
+    pub psnr: f32,
+    /// Mean structural similarity index of the luminance over 8x8 windows.
+    pub ssim: f32,
+    /// Image highlighting the differing pixels in red.
+    pub diff: Image,
+}
+
+impl Comparison {
+    /// Check the comparison against the tolerance.
+    pub fn passes(&self, tolerance: &Tolerance) -> bool {
+        self.differing_pixels <= tolerance.pixels
+            && !matches!(tolerance.psnr, Some(psnr) if self.psnr < psnr)
+            && !matches!(tolerance.ssim, Some(ssim) if self.ssim < ssim)
+    }
+}
+
+/// Compare an image with a reference of the same size.
+pub fn compare(actual: &Image, reference: &Image, tolerance: &Tolerance) -> Comparison {
+    assert_eq!(
+        (actual.width, actual.height),
+        (reference.width, reference.height),
+        "Image size doesn't match the reference"
+    );
+
+    let mut differing_pixels = 0;
+    let mut max_channel_difference = 0;
+    let mut squared_error = 0u64;
+    let mut diff = Vec::with_capacity(actual.data.len());
+    for (a, r) in actual.data.chunks(4).zip(reference.data.chunks(4)) {
+        let mut pixel_difference = 0;
+        for (&a, &r) in a.iter().zip(r) {
+            let difference = a.max(r) - a.min(r);
+            pixel_difference = pixel_difference.max(difference);
+            squared_error += difference as u64 * difference as u64;
+        }
+        max_channel_difference = max_channel_difference.max(pixel_difference);
+        if pixel_difference > tolerance.channel {
+            differing_pixels += 1;
+            diff.extend_from_slice(&[0xFF, 0, 0, 0xFF]);
+        } else {
+            // Matching pixels are kept as a faded copy of the reference.
+            let gray = (luma(r) / 4.0) as u8;
+            diff.extend_from_slice(&[gray, gray, gray, 0xFF]);
+        }
+    }
+
+    let psnr = if squared_error == 0 {
+        f32::INFINITY
+    } else {
+        let mse = squared_error as f64 / actual.data.len() as f64;
+        (10.0 * (255.0 * 255.0 / mse).log10()) as f32
+    };
+
+    Comparison {
+        differing_pixels,
+        max_channel_difference,
+        psnr,
+        ssim: ssim(actual, reference),
+        diff: Image {
+            width: actual.width,
+            height: actual.height,
+            data: diff,
+        },
+    }
+}
+
+fn luma(pixel: &[u8]) -> f64 {
+    0.299 * pixel[0] as f64 + 0.587 * pixel[1] as f64 + 0.114 * pixel[2] as f64
+}
+
+fn ssim(actual: &Image, reference: &Image) -> f32 {
+    const WINDOW: u32 = 8;
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+    let mut total = 0.0;
+    let mut windows = 0;
+    for y0 in (0 .. actual.height).step_by(WINDOW as usize) {
+        for x0 in (0 .. actual.width).step_by(WINDOW as usize) {
+            let (mut sum_a, mut sum_r) = (0.0, 0.0);
+            let (mut sum_aa, mut sum_rr, mut sum_ar) = (0.0, 0.0, 0.0);
+            let mut count = 0.0;
+            for y in y0 .. (y0 + WINDOW).min(actual.height) {
+                for x in x0 .. (x0 + WINDOW).min(actual.width) {
+                    let a = luma(actual.pixel(x, y));
+                    let r = luma(reference.pixel(x, y));
+                    sum_a += a;
+                    sum_r += r;
+                    sum_aa += a * a;
+                    sum_rr += r * r;
+                    sum_ar += a * r;
+                    count += 1.0;
+                }
+            }
+            let (mean_a, mean_r) = (sum_a / count, sum_r / count);
+            let var_a = sum_aa / count - mean_a * mean_a;
+            let var_r = sum_rr / count - mean_r * mean_r;
+            let covar = sum_ar / count - mean_a * mean_r;
+            total += ((2.0 * mean_a * mean_r + C1) * (2.0 * covar + C2))
+                / ((mean_a * mean_a + mean_r * mean_r + C1) * (var_a + var_r + C2));
+            windows += 1;
+        }
+    }
+
+    if windows == 0 {
+        1.0
+    } else {
+        (total / windows as f64) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, pixel: [u8; 4]) -> Image {
+        Image {
+            width,
+            height,
+            data: (0 .. width * height).flat_map(|_| pixel.to_vec()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_compare_tolerance() {
+        let reference = solid(16, 16, [10, 20, 30, 255]);
+        let mut actual = reference.clone();
+        actual.data[0] = 12;
+        actual.data[4 * 17 + 1] = 40;
+
+        let exact = Tolerance::default();
+        let comparison = compare(&actual, &reference, &exact);
+        assert_eq!(comparison.differing_pixels, 2);
+        assert_eq!(comparison.max_channel_difference, 20);
+        assert!(!comparison.passes(&exact));
+        assert_eq!(comparison.diff.pixel(1, 1), &[0xFF, 0, 0, 0xFF]);
+
+        let loose = Tolerance {
+            channel: 2,
+            pixels: 1,
+            psnr: Some(30.0),
+            ssim: Some(0.9),
+        };
+        assert!(compare(&actual, &reference, &loose).passes(&loose));
+        assert!(compare(&reference, &reference, &exact).passes(&exact));
+    }
+}
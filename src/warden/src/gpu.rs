@@ -37,6 +37,7 @@ pub struct FetchGuard<'a, B: hal::Backend> {
     mapping: *const u8,
     row_pitch: usize,
     width: usize,
+    num_rows: usize,
 }
 
 impl<'a, B: hal::Backend> FetchGuard<'a, B> {
@@ -44,6 +45,10 @@ impl<'a, B: hal::Backend> FetchGuard<'a, B> {
         let offset = (i * self.row_pitch) as isize;
         unsafe { slice::from_raw_parts(self.mapping.offset(offset), self.width) }
     }
+
+    pub fn num_rows(&self) -> usize {
+        self.num_rows
+    }
 }
 
 impl<'a, B: hal::Backend> Drop for FetchGuard<'a, B> {
@@ -66,7 +71,7 @@ pub struct Buffer<B: hal::Backend> {
 }
 
 impl<B: hal::Backend> Buffer<B> {
-    fn _barrier_to(&self, access: b::Access) -> memory::Barrier<B> {
+    fn barrier_to(&self, access: b::Access) -> memory::Barrier<B> {
         memory::Barrier::whole_buffer(&self.handle, self.stable_state .. access)
     }
     fn barrier_from(&self, access: b::Access) -> memory::Barrier<B> {
@@ -162,10 +167,172 @@ pub struct Resources<B: hal::Backend> {
     pub pipeline_layouts: HashMap<String, B::PipelineLayout>,
     pub graphics_pipelines: HashMap<String, B::GraphicsPipeline>,
     pub compute_pipelines: HashMap<String, (String, B::ComputePipeline)>,
+    pub query_pools: HashMap<String, QueryPool<B>>,
+    pub events: HashMap<String, B::Event>,
+    pub semaphores: HashMap<String, B::Semaphore>,
+    /// Family and index of every named queue.
+    pub queues: HashMap<String, (queue::QueueFamilyId, usize)>,
+}
+
+impl<B: hal::Backend> Resources<B> {
+    fn queue_families(
+        &self,
+        queues: &Option<Range<String>>,
+    ) -> Option<Range<queue::QueueFamilyId>> {
+        queues.as_ref().map(|queues| {
+            let family = |name: &String| {
+                self.queues
+                    .get(name)
+                    .expect(&format!("Missing queue: {}", name))
+                    .0
+            };
+            family(&queues.start) .. family(&queues.end)
+        })
+    }
+
+    fn barrier(&self, barrier: &raw::Barrier) -> memory::Barrier<B> {
+        match *barrier {
+            raw::Barrier::AllBuffers(ref states) => memory::Barrier::AllBuffers(states.clone()),
+            raw::Barrier::AllImages(ref states) => memory::Barrier::AllImages(states.clone()),
+            raw::Barrier::Buffer { ref buffer, ref states, ref queues } => {
+                let buffer = self
+                    .buffers
+                    .get(buffer)
+                    .expect(&format!("Missing buffer: {}", buffer));
+                memory::Barrier::Buffer {
+                    states: states.clone(),
+                    target: &buffer.handle,
+                    families: self.queue_families(queues),
+                    range: None .. None,
+                }
+            }
+            raw::Barrier::Image { ref image, ref states, ref queues } => {
+                let image = self
+                    .images
+                    .get(image)
+                    .expect(&format!("Missing image: {}", image));
+                memory::Barrier::Image {
+                    states: states.clone(),
+                    target: &image.handle,
+                    families: self.queue_families(queues),
+                    range: image.range.clone(),
+                }
+            }
+        }
+    }
+
+    fn query_pool(&self, name: &str) -> &B::QueryPool {
+        &self
+            .query_pools
+            .get(name)
+            .expect(&format!("Missing query pool: {}", name))
+            .handle
+    }
+
+    fn event(&self, name: &str) -> &B::Event {
+        self.events
+            .get(name)
+            .expect(&format!("Missing event: {}", name))
+    }
+
+    unsafe fn record_sync(&self, command_buf: &mut B::CommandBuffer, command: &raw::SyncCommand) {
+        use crate::raw::SyncCommand as Sc;
+        match *command {
+            Sc::PipelineBarrier {
+                ref stages,
+                dependencies,
+                ref barriers,
+            } => {
+                command_buf.pipeline_barrier(
+                    stages.clone(),
+                    dependencies,
+                    barriers.iter().map(|barrier| self.barrier(barrier)),
+                );
+            }
+            Sc::SetEvent { ref event, stages } => {
+                command_buf.set_event(self.event(event), stages);
+            }
+            Sc::ResetEvent { ref event, stages } => {
+                command_buf.reset_event(self.event(event), stages);
+            }
+            Sc::WaitEvents {
+                ref events,
+                ref stages,
+                ref barriers,
+            } => {
+                command_buf.wait_events(
+                    events.iter().map(|name| self.event(name)),
+                    stages.clone(),
+                    barriers.iter().map(|barrier| self.barrier(barrier)),
+                );
+            }
+            Sc::ResetQueryPool {
+                ref pool,
+                ref queries,
+            } => {
+                command_buf.reset_query_pool(self.query_pool(pool), queries.clone());
+            }
+            Sc::BeginQuery {
+                ref pool,
+                id,
+                precise,
+            } => {
+                let flags = if precise {
+                    query::ControlFlags::PRECISE
+                } else {
+                    query::ControlFlags::empty()
+                };
+                command_buf.begin_query(
+                    query::Query {
+                        pool: self.query_pool(pool),
+                        id,
+                    },
+                    flags,
+                );
+            }
+            Sc::EndQuery { ref pool, id } => {
+                command_buf.end_query(query::Query {
+                    pool: self.query_pool(pool),
+                    id,
+                });
+            }
+            Sc::WriteTimestamp {
+                ref pool,
+                id,
+                stage,
+            } => {
+                command_buf.write_timestamp(
+                    stage,
+                    query::Query {
+                        pool: self.query_pool(pool),
+                        id,
+                    },
+                );
+            }
+        }
+    }
+}
+
+pub struct QueryPool<B: hal::Backend> {
+    handle: B::QueryPool,
+    ty: query::Type,
 }
 
 pub struct Job<B: hal::Backend> {
     submission: B::CommandBuffer,
+    family: queue::QueueFamilyId,
+}
+
+/// Pick the queue family for a requested queue type, preferring the families
+/// of exactly that type, since those are usually backed by dedicated hardware.
+fn pick_queue_family<F: queue::QueueFamily>(families: &[F], ty: queue::QueueType) -> Option<&F> {
+    families.iter().find(|family| family.queue_type() == ty).or_else(|| {
+        families.iter().find(|family| {
+            let other = family.queue_type();
+            (!ty.supports_graphics() || other.supports_graphics()) &&
+                (!ty.supports_compute() || other.supports_compute())
+        })
+    })
 }
 
 pub struct Scene<B: hal::Backend> {
@@ -175,7 +342,10 @@ pub struct Scene<B: hal::Backend> {
     finish_submit: B::CommandBuffer,
     device: B::Device,
     queue_group: queue::QueueGroup<B>,
+    other_queue_groups: Vec<queue::QueueGroup<B>>,
     command_pool: Option<B::CommandPool>,
+    other_command_pools: HashMap<queue::QueueFamilyId, B::CommandPool>,
+    submissions: HashMap<String, raw::Submission>,
     query_pool: Option<B::QueryPool>,
     upload_buffers: HashMap<String, (B::Buffer, B::Memory)>,
     download_type: hal::MemoryTypeId,
@@ -200,15 +370,77 @@ impl<B: hal::Backend> Scene<B> {
         let memory_types = adapter.physical_device.memory_properties().memory_types;
         let limits = adapter.physical_device.limits();
 
+        // pick a family for every named queue, sharing queues when the family runs out
+        let main_family = &adapter.queue_families[0];
+        let mut family_queues = vec![(main_family, 1)];
+        let mut queues = HashMap::new();
+        queues.insert(raw::MAIN_QUEUE.to_string(), (main_family.id(), 0));
+        let mut queue_names = raw.queues.keys().collect::<Vec<_>>();
+        queue_names.sort();
+        for name in queue_names {
+            assert_ne!(name, raw::MAIN_QUEUE, "The main queue can't be redefined");
+            let family = pick_queue_family(&adapter.queue_families, raw.queues[name])
+                .expect(&format!("No queue family for queue: {}", name));
+            let index = match family_queues.iter_mut().find(|&&mut (f, _)| f.id() == family.id()) {
+                Some(&mut (f, ref mut count)) => {
+                    if *count < f.max_queues() {
+                        *count += 1;
+                    }
+                    *count - 1
+                }
+                None => {
+                    family_queues.push((family, 1));
+                    0
+                }
+            };
+            queues.insert(name.clone(), (family.id(), index));
+        }
+        let family_types = family_queues
+            .iter()
+            .map(|&(family, _)| (family.id(), family.queue_type()))
+            .collect::<HashMap<_, _>>();
+
         // initialize graphics
+        let priorities = family_queues
+            .iter()
+            .map(|&(_, count)| vec![1.0; count])
+            .collect::<Vec<_>>();
+        let families = family_queues
+            .iter()
+            .zip(&priorities)
+            .map(|(&(family, _), priorities)| (family, &priorities[..]))
+            .collect::<Vec<_>>();
         let mut gpu = unsafe {
             adapter.physical_device.open(
-                &[(&adapter.queue_families[0], &[1.0])],
+                &families,
                 hal::Features::empty(),
             ).unwrap()
         };
         let device = gpu.device;
-        let queue_group = gpu.queue_groups.pop().unwrap();
+        let main_group = gpu.queue_groups
+            .iter()
+            .position(|group| group.family == main_family.id())
+            .unwrap();
+        let queue_group = gpu.queue_groups.swap_remove(main_group);
+        let other_queue_groups = gpu.queue_groups;
+
+        // jobs are recorded for the family of the submissions they are part of
+        let mut job_families = HashMap::new();
+        for (name, submission) in &raw.submissions {
+            assert!(!raw.jobs.contains_key(name), "Submission {} shadows a job", name);
+            let family = queues
+                .get(&submission.queue)
+                .expect(&format!("Missing queue: {}", submission.queue))
+                .0;
+            for job in &submission.jobs {
+                let previous = job_families.insert(job.as_str(), family);
+                assert!(
+                    previous.is_none() || previous == Some(family),
+                    "Job {} is submitted to different queue families",
+                    job,
+                );
+            }
+        }
 
         let upload_type: hal::MemoryTypeId = memory_types
             .iter()
@@ -259,6 +491,10 @@ impl<B: hal::Backend> Scene<B> {
             pipeline_layouts: HashMap::new(),
             graphics_pipelines: HashMap::new(),
             compute_pipelines: HashMap::new(),
+            query_pools: HashMap::new(),
+            events: HashMap::new(),
+            semaphores: HashMap::new(),
+            queues,
         };
         let mut upload_buffers = HashMap::new();
         let mut finish_cmd = command_pool.allocate_one(c::Level::Primary);
@@ -274,10 +510,6 @@ impl<B: hal::Backend> Scene<B> {
         unsafe {
             init_cmd.begin_primary(c::CommandBufferFlags::empty());
             init_cmd.reset_query_pool(&query_pool, 0 .. 2);
-            init_cmd.write_timestamp(
-                pso::PipelineStage::TOP_OF_PIPE,
-                query::Query { pool: &query_pool, id: 0 },
-            );
         }
         // Pass[1]: images, samplers, buffers, passes, descriptor set layouts/pools
         for (name, resource) in &raw.resources {
@@ -701,6 +933,29 @@ impl<B: hal::Backend> Scene<B> {
                     .expect("Descriptor pool creation failure!");
                     resources.desc_pools.insert(name.clone(), pool);
                 }
+                raw::Resource::QueryPool { ty, count } => {
+                    let pool = unsafe { device.create_query_pool(ty, count) }
+                        .expect("Query pool creation failure!");
+                    // queries start from scratch for every run
+                    unsafe {
+                        init_cmd.reset_query_pool(&pool, 0 .. count);
+                    }
+                    resources.query_pools.insert(name.clone(), QueryPool {
+                        handle: pool,
+                        ty,
+                    });
+                }
+                raw::Resource::Semaphore => {
+                    let semaphore = device.create_semaphore().unwrap();
+                    resources.semaphores.insert(name.clone(), semaphore);
+                }
+                raw::Resource::Event => {
+                    let event = device.create_event().unwrap();
+                    unsafe {
+                        init_cmd.reset_event(&event, pso::PipelineStage::TOP_OF_PIPE);
+                    }
+                    resources.events.insert(name.clone(), event);
+                }
                 _ => {}
             }
         }
@@ -934,14 +1189,38 @@ impl<B: hal::Backend> Scene<B> {
         }
 
         unsafe {
+            // measure the jobs only, not the uploads
+            init_cmd.write_timestamp(
+                pso::PipelineStage::BOTTOM_OF_PIPE,
+                query::Query { pool: &query_pool, id: 0 },
+            );
             init_cmd.finish();
         }
 
         // fill up command buffers
         let mut jobs = HashMap::new();
+        let mut other_command_pools = HashMap::new();
         for (name, job) in &raw.jobs {
             use crate::raw::TransferCommand as Tc;
-            let mut command_buf = command_pool.allocate_one(c::Level::Primary);
+            let family = job_families
+                .get(name.as_str())
+                .cloned()
+                .unwrap_or(queue_group.family);
+            let mut command_buf = if family == queue_group.family {
+                command_pool.allocate_one(c::Level::Primary)
+            } else {
+                other_command_pools
+                    .entry(family)
+                    .or_insert_with(|| unsafe {
+                        device.create_command_pool(
+                            family,
+                            hal::pool::CommandPoolCreateFlags::empty(),
+                        ).unwrap()
+                    })
+                    .allocate_one(c::Level::Primary)
+            };
+            // only graphics queues can wait for the graphics stages
+            let graphics = family_types[&family].supports_graphics();
             unsafe {
                 command_buf.begin_primary(c::CommandBufferFlags::SIMULTANEOUS_USE);
             }
@@ -949,8 +1228,11 @@ impl<B: hal::Backend> Scene<B> {
                 raw::Job::Transfer { ref commands } => {
                     let mut buffers = HashMap::new();
                     let mut images = HashMap::new();
-                    let src_stage = pso::PipelineStage::TRANSFER |
-                        pso::PipelineStage::COLOR_ATTACHMENT_OUTPUT;
+                    let src_stage = if graphics {
+                        pso::PipelineStage::TRANSFER | pso::PipelineStage::COLOR_ATTACHMENT_OUTPUT
+                    } else {
+                        pso::PipelineStage::TRANSFER
+                    };
                     for command in commands {
                         match *command {
                             Tc::CopyBuffer {
@@ -1158,6 +1440,9 @@ impl<B: hal::Backend> Scene<B> {
                                 );
                                 command_buf.fill_buffer(&buf.handle, (start, end), data);
                             },
+                            Tc::Sync(ref sync) => unsafe {
+                                resources.record_sync(&mut command_buf, sync);
+                            },
                         }
                     }
 
@@ -1171,8 +1456,12 @@ impl<B: hal::Backend> Scene<B> {
                         .map(|(name, (access, layout))| {
                             resources.images.get(name).unwrap().barrier_from(access, layout)
                         });
-                    let dst_stages = pso::PipelineStage::FRAGMENT_SHADER |
-                        pso::PipelineStage::COLOR_ATTACHMENT_OUTPUT;
+                    let dst_stages = if graphics {
+                        pso::PipelineStage::FRAGMENT_SHADER |
+                            pso::PipelineStage::COLOR_ATTACHMENT_OUTPUT
+                    } else {
+                        pso::PipelineStage::BOTTOM_OF_PIPE
+                    };
                     unsafe {
                         command_buf.pipeline_barrier(
                             pso::PipelineStage::TRANSFER .. dst_stages,
@@ -1186,20 +1475,30 @@ impl<B: hal::Backend> Scene<B> {
                     ref pass,
                     ref clear_values,
                 } => unsafe {
-                    // collect all used image descriptors
+                    // collect all used image descriptors and indirect buffers
                     let mut all_images = Vec::new();
+                    let mut indirect_buffers = Vec::new();
                     for subpass in pass.1.iter() {
                         for com in subpass.1.commands.iter() {
-                            if let raw::DrawCommand::BindDescriptorSets { ref sets, .. } = *com {
-                                for set in sets {
-                                    for pair in resources.desc_sets[set].views.iter() {
-                                        let view = &resources.image_views[&pair.0];
-                                        all_images.push((view.image.clone(), pair.1));
+                            match *com {
+                                raw::DrawCommand::BindDescriptorSets { ref sets, .. } => {
+                                    for set in sets {
+                                        for pair in resources.desc_sets[set].views.iter() {
+                                            let view = &resources.image_views[&pair.0];
+                                            all_images.push((view.image.clone(), pair.1));
+                                        }
                                     }
                                 }
+                                raw::DrawCommand::DrawIndirect { ref buffer, .. } |
+                                raw::DrawCommand::DrawIndexedIndirect { ref buffer, .. } => {
+                                    indirect_buffers.push(buffer);
+                                }
+                                _ => {}
                             }
                         }
                     }
+                    indirect_buffers.sort();
+                    indirect_buffers.dedup();
 
                     let fb = resources.framebuffers
                         .get(framebuffer)
@@ -1232,6 +1531,16 @@ impl<B: hal::Backend> Scene<B> {
                                 .barrier_to(i::Access::SHADER_READ, layout)
                         }),
                     );
+                    command_buf.pipeline_barrier(
+                        pso::PipelineStage::TOP_OF_PIPE .. pso::PipelineStage::DRAW_INDIRECT,
+                        memory::Dependencies::empty(),
+                        indirect_buffers.iter().map(|name| {
+                            resources.buffers
+                                .get(*name)
+                                .expect(&format!("Missing indirect buffer: {}", name))
+                                .barrier_to(b::Access::INDIRECT_COMMAND_READ)
+                        }),
+                    );
                     command_buf.begin_render_pass(
                         &rp.handle,
                         &fb.handle,
@@ -1326,17 +1635,70 @@ impl<B: hal::Backend> Scene<B> {
                                         instances.clone(),
                                     );
                                 }
+                                Dc::DrawIndirect {
+                                    ref buffer,
+                                    offset,
+                                    draw_count,
+                                    stride,
+                                } => {
+                                    command_buf.draw_indirect(
+                                        &resources.buffers[buffer].handle,
+                                        offset,
+                                        draw_count,
+                                        stride,
+                                    );
+                                }
+                                Dc::DrawIndexedIndirect {
+                                    ref buffer,
+                                    offset,
+                                    draw_count,
+                                    stride,
+                                } => {
+                                    command_buf.draw_indexed_indirect(
+                                        &resources.buffers[buffer].handle,
+                                        offset,
+                                        draw_count,
+                                        stride,
+                                    );
+                                }
+                                Dc::PushConstants {
+                                    ref layout,
+                                    stages,
+                                    offset,
+                                    ref data,
+                                } => {
+                                    command_buf.push_graphics_constants(
+                                        resources.pipeline_layouts.get(layout).expect(&format!(
+                                            "Missing pipeline layout: {}",
+                                            layout
+                                        )),
+                                        stages,
+                                        offset,
+                                        data,
+                                    );
+                                }
                                 Dc::SetViewports(ref viewports) => {
                                     command_buf.set_viewports(0, viewports);
                                 }
                                 Dc::SetScissors(ref scissors) => {
                                     command_buf.set_scissors(0, scissors);
                                 }
+                                Dc::Sync(ref sync) => {
+                                    resources.record_sync(&mut command_buf, sync);
+                                }
                             }
                         }
                     }
 
                     command_buf.end_render_pass();
+                    command_buf.pipeline_barrier(
+                        pso::PipelineStage::DRAW_INDIRECT .. pso::PipelineStage::BOTTOM_OF_PIPE,
+                        memory::Dependencies::empty(),
+                        indirect_buffers.iter().map(|name| {
+                            resources.buffers[*name]
+                                .barrier_from(b::Access::INDIRECT_COMMAND_READ)
+                        }),
+                    );
                     command_buf.pipeline_barrier(
                         pso::PipelineStage::VERTEX_SHADER | pso::PipelineStage::FRAGMENT_SHADER ..
                         pso::PipelineStage::COLOR_ATTACHMENT_OUTPUT,
@@ -1362,7 +1724,10 @@ impl<B: hal::Backend> Scene<B> {
                     ref descriptor_sets,
                     dispatch,
                 } => unsafe {
-                    let (ref layout, ref pso) = resources.compute_pipelines[pipeline];
+                    let (ref layout, ref pso) = resources
+                        .compute_pipelines
+                        .get(pipeline)
+                        .expect(&format!("Missing compute pipeline: {}", pipeline));
                     command_buf.bind_compute_pipeline(pso);
                     command_buf.bind_compute_descriptor_sets(
                         resources
@@ -1381,6 +1746,94 @@ impl<B: hal::Backend> Scene<B> {
                     );
                     command_buf.dispatch(dispatch);
                 },
+                raw::Job::ComputeCommands { ref commands } => unsafe {
+                    use crate::raw::ComputeCommand as Cc;
+                    let mut indirect_buffers = commands
+                        .iter()
+                        .filter_map(|command| match *command {
+                            Cc::DispatchIndirect { ref buffer, .. } => Some(buffer),
+                            _ => None,
+                        })
+                        .collect::<Vec<_>>();
+                    indirect_buffers.sort();
+                    indirect_buffers.dedup();
+                    command_buf.pipeline_barrier(
+                        pso::PipelineStage::TOP_OF_PIPE .. pso::PipelineStage::DRAW_INDIRECT,
+                        memory::Dependencies::empty(),
+                        indirect_buffers.iter().map(|name| {
+                            resources.buffers
+                                .get(*name)
+                                .expect(&format!("Missing indirect buffer: {}", name))
+                                .barrier_to(b::Access::INDIRECT_COMMAND_READ)
+                        }),
+                    );
+
+                    for command in commands {
+                        match *command {
+                            Cc::BindPipeline(ref name) => {
+                                let (_, ref pso) = resources
+                                    .compute_pipelines
+                                    .get(name)
+                                    .expect(&format!("Missing compute pipeline: {}", name));
+                                command_buf.bind_compute_pipeline(pso);
+                            }
+                            Cc::BindDescriptorSets {
+                                ref layout,
+                                first,
+                                ref sets,
+                            } => {
+                                command_buf.bind_compute_descriptor_sets(
+                                    resources
+                                        .pipeline_layouts
+                                        .get(layout)
+                                        .expect(&format!("Missing pipeline layout: {}", layout)),
+                                    first,
+                                    sets.iter().map(|name| {
+                                        &resources
+                                            .desc_sets
+                                            .get(name)
+                                            .expect(&format!("Missing descriptor set: {}", name))
+                                            .handle
+                                    }),
+                                    &[],
+                                );
+                            }
+                            Cc::PushConstants {
+                                ref layout,
+                                offset,
+                                ref data,
+                            } => {
+                                command_buf.push_compute_constants(
+                                    resources
+                                        .pipeline_layouts
+                                        .get(layout)
+                                        .expect(&format!("Missing pipeline layout: {}", layout)),
+                                    offset,
+                                    data,
+                                );
+                            }
+                            Cc::Dispatch(count) => {
+                                command_buf.dispatch(count);
+                            }
+                            Cc::DispatchIndirect { ref buffer, offset } => {
+                                command_buf
+                                    .dispatch_indirect(&resources.buffers[buffer].handle, offset);
+                            }
+                            Cc::Sync(ref sync) => {
+                                resources.record_sync(&mut command_buf, sync);
+                            }
+                        }
+                    }
+
+                    command_buf.pipeline_barrier(
+                        pso::PipelineStage::DRAW_INDIRECT .. pso::PipelineStage::BOTTOM_OF_PIPE,
+                        memory::Dependencies::empty(),
+                        indirect_buffers.iter().map(|name| {
+                            resources.buffers[*name]
+                                .barrier_from(b::Access::INDIRECT_COMMAND_READ)
+                        }),
+                    );
+                },
             }
 
             unsafe {
@@ -1388,6 +1841,7 @@ impl<B: hal::Backend> Scene<B> {
             }
             jobs.insert(name.clone(), Job {
                 submission: command_buf,
+                family,
             });
         }
 
@@ -1399,7 +1853,10 @@ impl<B: hal::Backend> Scene<B> {
             finish_submit: finish_cmd,
             device,
             queue_group,
+            other_queue_groups,
             command_pool: Some(command_pool),
+            other_command_pools,
+            submissions: raw.submissions.clone(),
             query_pool: Some(query_pool),
             upload_buffers,
             download_type,
@@ -1414,19 +1871,97 @@ impl<B: hal::Backend> Scene<B> {
         I: Iterator,
         I::Item: AsRef<str>,
     {
+        let names = job_names.collect::<Vec<_>>();
         let jobs = &self.jobs;
-        let submits = job_names.map(|name| {
+        let job = |name: &str| {
             &jobs
-                .get(name.as_ref())
-                .expect(&format!("Missing job: {}", name.as_ref()))
+                .get(name)
+                .expect(&format!("Missing job: {}", name))
                 .submission
-        });
+        };
+        let main_family = self.queue_group.family;
+        for name in &names {
+            if let Some(job) = jobs.get(name.as_ref()) {
+                assert_eq!(
+                    job.family, main_family,
+                    "Job {} is recorded for another queue family",
+                    name.as_ref()
+                );
+            }
+        }
 
-        let command_buffers = iter::once(&self.init_submit)
-            .chain(submits)
-            .chain(iter::once(&self.finish_submit));
+        if names.iter().all(|name| jobs.contains_key(name.as_ref())) {
+            let command_buffers = iter::once(&self.init_submit)
+                .chain(names.iter().map(|name| job(name.as_ref())))
+                .chain(iter::once(&self.finish_submit));
+            unsafe {
+                self.queue_group.queues[0].submit_without_semaphores(command_buffers, None);
+            }
+            return;
+        }
+
+        // Other queues don't synchronize with the initialization, so it has to finish first.
         unsafe {
-            self.queue_group.queues[0].submit_without_semaphores(command_buffers, None);
+            self.queue_group.queues[0]
+                .submit_without_semaphores(iter::once(&self.init_submit), None);
+            self.queue_group.queues[0].wait_idle().unwrap();
+        }
+
+        // Plain jobs are batched on the main queue until the next submission.
+        let mut pending = Vec::new();
+        for name in &names {
+            let name = name.as_ref();
+            if jobs.contains_key(name) {
+                pending.push(job(name));
+                continue;
+            }
+            let submission = self
+                .submissions
+                .get(name)
+                .expect(&format!("Missing job or submission: {}", name));
+            if !pending.is_empty() {
+                unsafe {
+                    self.queue_group.queues[0].submit_without_semaphores(pending.drain(..), None);
+                }
+            }
+
+            let (family, index) = self.resources.queues[&submission.queue];
+            let queue = if family == self.queue_group.family {
+                &mut self.queue_group.queues[index]
+            } else {
+                let group = self
+                    .other_queue_groups
+                    .iter_mut()
+                    .find(|group| group.family == family)
+                    .unwrap();
+                &mut group.queues[index]
+            };
+            let semaphores = &self.resources.semaphores;
+            let semaphore = |name: &String| {
+                semaphores
+                    .get(name)
+                    .expect(&format!("Missing semaphore: {}", name))
+            };
+            unsafe {
+                queue.submit(
+                    queue::Submission {
+                        command_buffers: submission.jobs.iter().map(|name| job(name)),
+                        wait_semaphores: submission
+                            .wait
+                            .iter()
+                            .map(|&(ref name, stages)| (semaphore(name), stages)),
+                        signal_semaphores: submission.signal.iter().map(semaphore),
+                    },
+                    None,
+                );
+            }
+        }
+
+        pending.push(&self.finish_submit);
+        unsafe {
+            self.queue_group.queues[0].submit_without_semaphores(pending, None);
+            // results are fetched on the main queue, which knows nothing about the others
+            self.device.wait_idle().unwrap();
         }
     }
 
@@ -1519,6 +2054,7 @@ impl<B: hal::Backend> Scene<B> {
             mapping,
             row_pitch: down_size as _,
             width: buffer.size,
+            num_rows: 1,
         }
     }
 
@@ -1647,27 +2183,60 @@ impl<B: hal::Backend> Scene<B> {
             mapping,
             row_pitch: row_pitch as _,
             width: width_bytes as _,
+            num_rows: (height * depth as u64 / block_height as u64) as _,
         }
     }
 
-    pub fn measure_time(&self) -> u32 {
-        let mut results = vec![0u32; 2];
+    /// Read back the results of the given queries, waiting for them to be available.
+    ///
+    /// Every query contributes as many values as its type produces.
+    pub fn fetch_query_results(&self, name: &str, queries: Range<query::Id>) -> Vec<u64> {
+        let pool = self
+            .resources
+            .query_pools
+            .get(name)
+            .expect(&format!("Unable to find query pool to fetch: {}", name));
+        let values_per_query = match pool.ty {
+            query::Type::Occlusion | query::Type::Timestamp => 1,
+            query::Type::PipelineStatistics(statistics) => statistics.bits().count_ones() as usize,
+        };
+        let mut results = vec![0u64; (queries.end - queries.start) as usize * values_per_query];
         unsafe {
             self.device.wait_idle().unwrap();
             let raw_data = slice::from_raw_parts_mut(
                 results.as_mut_ptr() as *mut u8,
-                4 * 2,
+                results.len() * 8,
+            );
+            self.device.get_query_pool_results(
+                &pool.handle,
+                queries,
+                raw_data,
+                values_per_query as b::Offset * 8,
+                query::ResultFlags::BITS_64 | query::ResultFlags::WAIT,
+            ).unwrap();
+        }
+        results
+    }
+
+    /// Time spent running the jobs of the last run, in timestamp units.
+    pub fn measure_time(&self) -> u64 {
+        let mut results = vec![0u64; 2];
+        unsafe {
+            self.device.wait_idle().unwrap();
+            let raw_data = slice::from_raw_parts_mut(
+                results.as_mut_ptr() as *mut u8,
+                8 * 2,
             );
             self.device.get_query_pool_results(
                 self.query_pool.as_ref().unwrap(),
                 0 .. 2,
                 raw_data,
-                4,
-                query::ResultFlags::empty(),
+                8,
+                query::ResultFlags::BITS_64 | query::ResultFlags::WAIT,
             ).unwrap();
         }
 
-        results[1] - results[0]
+        results[1].wrapping_sub(results[0])
     }
 }
 
@@ -1682,8 +2251,20 @@ impl<B: hal::Backend> Drop for Scene<B> {
             let _ = &self.queue_group;
             self.device
                 .destroy_command_pool(self.command_pool.take().unwrap());
+            for (_, pool) in self.other_command_pools.drain() {
+                self.device.destroy_command_pool(pool);
+            }
             self.device
                 .destroy_query_pool(self.query_pool.take().unwrap());
+            for (_, pool) in self.resources.query_pools.drain() {
+                self.device.destroy_query_pool(pool.handle);
+            }
+            for (_, event) in self.resources.events.drain() {
+                self.device.destroy_event(event);
+            }
+            for (_, semaphore) in self.resources.semaphores.drain() {
+                self.device.destroy_semaphore(semaphore);
+            }
         }
     }
 }
@@ -6,5 +6,8 @@ extern crate log;
 #[macro_use]
 extern crate serde;
 
+pub mod compare;
 pub mod gpu;
 pub mod raw;
+pub mod report;
+pub mod stats;
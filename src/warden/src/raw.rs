@@ -1,5 +1,8 @@
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
 use std::ops::Range;
+use std::path::Path;
 
 use hal;
 
@@ -152,6 +155,12 @@ pub enum Resource {
         views: HashMap<String, String>,
         extent: hal::image::Extent,
     },
+    QueryPool {
+        ty: hal::query::Type,
+        count: hal::query::Id,
+    },
+    Event,
+    Semaphore,
 }
 
 #[derive(Debug, Deserialize)]
@@ -193,6 +202,80 @@ pub enum TransferCommand {
         end: Option<hal::buffer::Offset>,
         data: u32,
     },
+    Sync(SyncCommand),
+}
+
+/// Memory barrier on a named resource, or on all of them.
+///
+/// Resource barriers can transfer the ownership between the families
+/// of two named queues of the scene.
+#[derive(Debug, Deserialize)]
+pub enum Barrier {
+    AllBuffers(Range<hal::buffer::Access>),
+    AllImages(Range<hal::image::Access>),
+    Buffer {
+        buffer: String,
+        states: Range<hal::buffer::State>,
+        #[serde(default)]
+        queues: Option<Range<String>>,
+    },
+    Image {
+        image: String,
+        states: Range<hal::image::State>,
+        #[serde(default)]
+        queues: Option<Range<String>>,
+    },
+}
+
+fn default_dependencies() -> hal::memory::Dependencies {
+    hal::memory::Dependencies::empty()
+}
+
+/// Synchronization and query commands, shared by all the job types.
+///
+/// Explicit barriers are recorded on top of the ones inserted by the scene,
+/// so they have to bring the resources back to their stable states.
+#[derive(Debug, Deserialize)]
+pub enum SyncCommand {
+    PipelineBarrier {
+        stages: Range<hal::pso::PipelineStage>,
+        #[serde(default = "default_dependencies")]
+        dependencies: hal::memory::Dependencies,
+        barriers: Vec<Barrier>,
+    },
+    SetEvent {
+        event: String,
+        stages: hal::pso::PipelineStage,
+    },
+    ResetEvent {
+        event: String,
+        stages: hal::pso::PipelineStage,
+    },
+    WaitEvents {
+        events: Vec<String>,
+        stages: Range<hal::pso::PipelineStage>,
+        #[serde(default)]
+        barriers: Vec<Barrier>,
+    },
+    ResetQueryPool {
+        pool: String,
+        queries: Range<hal::query::Id>,
+    },
+    BeginQuery {
+        pool: String,
+        id: hal::query::Id,
+        #[serde(default)]
+        precise: bool,
+    },
+    EndQuery {
+        pool: String,
+        id: hal::query::Id,
+    },
+    WriteTimestamp {
+        pool: String,
+        id: hal::query::Id,
+        stage: hal::pso::PipelineStage,
+    },
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -230,8 +313,48 @@ pub enum DrawCommand {
         base_vertex: hal::VertexOffset,
         instances: Range<hal::InstanceCount>,
     },
+    DrawIndirect {
+        buffer: String,
+        offset: hal::buffer::Offset,
+        draw_count: hal::DrawCount,
+        stride: u32,
+    },
+    DrawIndexedIndirect {
+        buffer: String,
+        offset: hal::buffer::Offset,
+        draw_count: hal::DrawCount,
+        stride: u32,
+    },
+    PushConstants {
+        layout: String,
+        stages: hal::pso::ShaderStageFlags,
+        offset: u32,
+        data: Vec<u32>,
+    },
     SetViewports(Vec<hal::pso::Viewport>),
     SetScissors(Vec<hal::pso::Rect>),
+    Sync(SyncCommand),
+}
+
+#[derive(Debug, Deserialize)]
+pub enum ComputeCommand {
+    BindPipeline(String),
+    BindDescriptorSets {
+        layout: String,
+        first: usize,
+        sets: Vec<String>,
+    },
+    PushConstants {
+        layout: String,
+        offset: u32,
+        data: Vec<u32>,
+    },
+    Dispatch(hal::WorkGroupCount),
+    DispatchIndirect {
+        buffer: String,
+        offset: hal::buffer::Offset,
+    },
+    Sync(SyncCommand),
 }
 
 #[derive(Debug, Deserialize)]
@@ -249,15 +372,152 @@ pub enum Job {
         clear_values: Vec<ClearValue>,
         pass: (String, HashMap<String, DrawPass>),
     },
+    /// Single dispatch, with the descriptor sets bound to the layout of the pipeline.
     Compute {
         pipeline: String,
         descriptor_sets: Vec<String>,
         dispatch: hal::WorkGroupCount,
     },
+    ComputeCommands {
+        commands: Vec<ComputeCommand>,
+    },
+}
+
+/// Name of the queue that is always available to a scene.
+pub const MAIN_QUEUE: &str = "main";
+
+/// Jobs submitted together to a named queue.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Submission {
+    pub queue: String,
+    pub jobs: Vec<String>,
+    /// Semaphores to wait for, with the stages that wait on them.
+    #[serde(default)]
+    pub wait: Vec<(String, hal::pso::PipelineStage)>,
+    /// Semaphores to signal once the jobs are done.
+    #[serde(default)]
+    pub signal: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Scene {
     pub resources: HashMap<String, Resource>,
     pub jobs: HashMap<String, Job>,
+    /// Queues requested in addition to the main one, by their type.
+    #[serde(default)]
+    pub queues: HashMap<String, hal::queue::QueueType>,
+    /// Submissions that tests can run next to the plain jobs, which
+    /// always go to the main queue.
+    #[serde(default)]
+    pub submissions: HashMap<String, Submission>,
+}
+
+impl Scene {
+    /// Jobs run by a name in a test, which is either a job or a submission.
+    pub fn jobs_of<'a>(&'a self, name: &str) -> Vec<&'a Job> {
+        match self.submissions.get(name) {
+            Some(submission) => submission.jobs.iter().map(|job| &self.jobs[job]).collect(),
+            None => vec![&self.jobs[name]],
+        }
+    }
+
+    /// Largest number of work groups dispatched by the jobs run by `names`,
+    /// in each dimension. Indirect dispatches are counted with the initial data
+    /// of their buffer, found in `data_path`.
+    pub fn max_work_group_count(&self, names: &[String], data_path: &Path) -> hal::WorkGroupCount {
+        let mut max = [0; 3];
+        for job in names.iter().flat_map(|name| self.jobs_of(name)) {
+            let counts = match *job {
+                Job::Compute { dispatch, .. } => vec![dispatch],
+                Job::ComputeCommands { ref commands } => commands
+                    .iter()
+                    .filter_map(|command| match *command {
+                        ComputeCommand::Dispatch(count) => Some(count),
+                        ComputeCommand::DispatchIndirect { ref buffer, offset } => {
+                            self.read_dispatch(buffer, offset, data_path)
+                        }
+                        _ => None,
+                    })
+                    .collect(),
+                _ => continue,
+            };
+            for count in counts {
+                for (max, count) in max.iter_mut().zip(count.iter()) {
+                    *max = (*max).max(*count);
+                }
+            }
+        }
+        max
+    }
+
+    /// Read the arguments of an indirect dispatch from the initial data of a buffer.
+    fn read_dispatch(
+        &self,
+        buffer: &str,
+        offset: hal::buffer::Offset,
+        data_path: &Path,
+    ) -> Option<hal::WorkGroupCount> {
+        let data = match self.resources.get(buffer) {
+            Some(Resource::Buffer { ref data, .. }) if !data.is_empty() => data,
+            _ => return None,
+        };
+        let mut file = File::open(data_path.join(data)).ok()?;
+        file.seek(SeekFrom::Start(offset)).ok()?;
+        let mut bytes = [0u8; 12];
+        file.read_exact(&mut bytes).ok()?;
+        let mut count = [0; 3];
+        for (count, chunk) in count.iter_mut().zip(bytes.chunks(4)) {
+            *count = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        Some(count)
+    }
+
+    /// Check that the jobs run by `names` fit in the limits of a device,
+    /// returning the reason to skip them otherwise.
+    pub fn check_limits(
+        &self,
+        names: &[String],
+        data_path: &Path,
+        limits: &hal::Limits,
+    ) -> Result<(), String> {
+        let count = self.max_work_group_count(names, data_path);
+        let max = limits.max_compute_work_group_count;
+        if count.iter().zip(max.iter()).any(|(count, max)| count > max) {
+            Err(format!("compute {:?} exceeds {:?}", count, max))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_max_work_group_count() {
+        let work = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../work");
+        let file = File::open(work.join("scenes").join("compute.ron")).unwrap();
+        let scene: Scene = ron::de::from_reader(file).unwrap();
+        let data = work.join("data");
+        let jobs = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(scene.max_work_group_count(&jobs(&["fill-multi"]), &data), [2, 1, 1]);
+        // indirect dispatches read the initial data of their buffer
+        assert_eq!(
+            scene.max_work_group_count(&jobs(&["fill", "fill-indirect"]), &data),
+            [4, 1, 1]
+        );
+
+        let mut limits = hal::Limits::default();
+        limits.max_compute_work_group_count = [4, 1, 1];
+        assert!(scene
+            .check_limits(&jobs(&["fill-indirect"]), &data, &limits)
+            .is_ok());
+        limits.max_compute_work_group_count = [3, 65535, 65535];
+        assert!(scene
+            .check_limits(&jobs(&["fill-indirect"]), &data, &limits)
+            .is_err());
+    }
 }
@@ -0,0 +1,297 @@
+//! Machine-readable reports of reftest runs.
+//!
+//! Every executed (or skipped) test produces a `Record`, and the whole
+//! `Report` can be written out as JSON or as JUnit XML for CI dashboards.
+
+use std::io::{self, Write};
+use std::time::Duration;
+
+/// Outcome of a single test.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pass,
+    Skip,
+    Fail,
+    /// Known failure of the backend.
+    XFail,
+    /// Known failure of the backend that passed.
+    XPass,
+}
+
+/// Record of a single test on a single backend.
+#[derive(Clone, Debug, Serialize)]
+pub struct Record {
+    pub suite: String,
+    pub scene: String,
+    pub test: String,
+    pub backend: String,
+    pub status: Status,
+    /// Why the test was skipped, if it was.
+    pub skip_reason: Option<String>,
+    /// Time spent running the jobs and fetching the result, in seconds.
+    pub elapsed: f64,
+    /// Expected bytes, only provided on failure.
+    pub expected: Option<Vec<u8>>,
+    /// Actual bytes, only provided on failure.
+    pub actual: Option<Vec<u8>>,
+    /// Description of the failure, if the bytes alone don't tell.
+    pub message: Option<String>,
+}
+
+impl Record {
+    /// Create a record of a passed test.
+    pub fn pass(suite: &str, scene: &str, test: &str, backend: &str, elapsed: Duration) -> Self {
+        Record {
+            suite: suite.to_string(),
+            scene: scene.to_string(),
+            test: test.to_string(),
+            backend: backend.to_string(),
+            status: Status::Pass,
+            skip_reason: None,
+            elapsed: seconds(elapsed),
+            expected: None,
+            actual: None,
+            message: None,
+        }
+    }
+
+    /// Create a record of a skipped test.
+    pub fn skip(suite: &str, scene: &str, test: &str, backend: &str, reason: String) -> Self {
+        Record {
+            status: Status::Skip,
+            skip_reason: Some(reason),
+            ..Record::pass(suite, scene, test, backend, Duration::from_secs(0))
+        }
+    }
+
+    /// Create a record of a failed test.
+    pub fn fail(
+        suite: &str,
+        scene: &str,
+        test: &str,
+        backend: &str,
+        elapsed: Duration,
+        expected: &[u8],
+        actual: &[u8],
+    ) -> Self {
+        Record {
+            status: Status::Fail,
+            expected: Some(expected.to_vec()),
+            actual: Some(actual.to_vec()),
+            ..Record::pass(suite, scene, test, backend, elapsed)
+        }
+    }
+
+    /// Create a record of a failed test, described by a message instead of the bytes.
+    pub fn mismatch(
+        suite: &str,
+        scene: &str,
+        test: &str,
+        backend: &str,
+        elapsed: Duration,
+        message: String,
+    ) -> Self {
+        Record {
+            status: Status::Fail,
+            message: Some(message),
+            ..Record::pass(suite, scene, test, backend, elapsed)
+        }
+    }
+}
+
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 * 1e-9
+}
+
+/// Collection of test records, in the order they were produced.
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    pub records: Vec<Record>,
+}
+
+impl Report {
+    /// Write the report as a JSON document.
+    pub fn write_json<W: Write>(&self, writer: W) -> io::Result<()> {
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    /// Write the report as JUnit XML.
+    ///
+    /// Each (backend, suite, scene) triple becomes a `<testsuite>`, named
+    /// `backend.suite.scene`, with a `<testcase>` for every test in it.
+    pub fn write_junit<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut groups = Vec::<(String, Vec<&Record>)>::new();
+        for record in &self.records {
+            let name = format!("{}.{}.{}", record.backend, record.suite, record.scene);
+            match groups.iter_mut().find(|&&mut (ref n, _)| *n == name) {
+                Some(&mut (_, ref mut records)) => records.push(record),
+                None => groups.push((name, vec![record])),
+            }
+        }
+
+        // JUnit has no notion of expected failures, so they are reported as skipped.
+        let failed =
+            |records: &[&Record]| records.iter().filter(|r| r.status == Status::Fail).count();
+        let skipped = |records: &[&Record]| {
+            records
+                .iter()
+                .filter(|r| r.status == Status::Skip || r.status == Status::XFail)
+                .count()
+        };
+        let all = self.records.iter().collect::<Vec<_>>();
+
+        writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            writer,
+            r#"<testsuites tests="{}" failures="{}" skipped="{}">"#,
+            all.len(),
+            failed(&all),
+            skipped(&all),
+        )?;
+        for (name, records) in &groups {
+            writeln!(
+                writer,
+                r#"  <testsuite name="{}" tests="{}" failures="{}" skipped="{}" time="{:.6}">"#,
+                escape(name),
+                records.len(),
+                failed(records),
+                skipped(records),
+                records.iter().map(|r| r.elapsed).sum::<f64>(),
+            )?;
+            for record in records {
+                write!(
+                    writer,
+                    r#"    <testcase classname="{}" name="{}" time="{:.6}""#,
+                    escape(name),
+                    escape(&record.test),
+                    record.elapsed,
+                )?;
+                match record.status {
+                    Status::Pass | Status::XPass => writeln!(writer, "/>")?,
+                    Status::Skip => {
+                        writeln!(writer, ">")?;
+                        writeln!(
+                            writer,
+                            r#"      <skipped message="{}"/>"#,
+                            escape(record.skip_reason.as_ref().map_or("", String::as_str)),
+                        )?;
+                        writeln!(writer, "    </testcase>")?;
+                    }
+                    Status::XFail => {
+                        writeln!(writer, ">")?;
+                        writeln!(writer, r#"      <skipped message="known failure"/>"#)?;
+                        writeln!(writer, "    </testcase>")?;
+                    }
+                    Status::Fail => {
+                        writeln!(writer, ">")?;
+                        match record.message {
+                            Some(ref message) => writeln!(
+                                writer,
+                                r#"      <failure message="{}"/>"#,
+                                escape(message),
+                            )?,
+                            None => {
+                                writeln!(
+                                    writer,
+                                    r#"      <failure message="data mismatch">expected: {:?}"#,
+                                    record.expected.as_ref().map_or(&[][..], Vec::as_slice),
+                                )?;
+                                writeln!(
+                                    writer,
+                                    "actual: {:?}</failure>",
+                                    record.actual.as_ref().map_or(&[][..], Vec::as_slice),
+                                )?;
+                            }
+                        }
+                        writeln!(writer, "    </testcase>")?;
+                    }
+                }
+            }
+            writeln!(writer, "  </testsuite>")?;
+        }
+        writeln!(writer, "</testsuites>")
+    }
+}
+
+/// Escape the XML special characters of an attribute value.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAME: &str = r#"fill <&"x">"#;
+
+    fn report() -> Report {
+        let elapsed = Duration::from_millis(250);
+        let xfail = Record {
+            status: Status::XFail,
+            ..Record::fail("ci", "a&b", "xfail", "empty", elapsed, &[1], &[2])
+        };
+        Report {
+            records: vec![
+                Record::pass("ci", "a&b", "pass", "empty", elapsed),
+                Record::mismatch(
+                    "ci",
+                    "a&b",
+                    NAME,
+                    "empty",
+                    Duration::from_millis(1500),
+                    r#"expected <1> & "2""#.to_string(),
+                ),
+                xfail,
+                Record::skip("ci", "a&b", "skip", "empty", r#"needs <&"feature">"#.to_string()),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_write_json() {
+        let mut json = Vec::new();
+        report().write_json(&mut json).unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        let records = value["records"].as_array().unwrap();
+        let statuses = records
+            .iter()
+            .map(|record| record["status"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(statuses, ["pass", "fail", "xfail", "skip"]);
+        assert_eq!(records[1]["test"], NAME);
+        assert_eq!(records[1]["message"], r#"expected <1> & "2""#);
+        assert_eq!(records[3]["skip_reason"], r#"needs <&"feature">"#);
+    }
+
+    #[test]
+    fn test_write_junit() {
+        let mut xml = Vec::new();
+        report().write_junit(&mut xml).unwrap();
+        let xml = String::from_utf8(xml).unwrap();
+
+        // Expected failures count as skipped.
+        assert!(xml.contains(r#"<testsuites tests="4" failures="1" skipped="2">"#));
+        assert!(xml.contains(
+            r#"<testsuite name="empty.ci.a&amp;b" tests="4" failures="1" skipped="2" time="2.0"#
+        ));
+        assert!(xml.contains(r#"name="fill &lt;&amp;&quot;x&quot;&gt;" time="1.500000">"#));
+        assert!(xml.contains(r#"<failure message="expected &lt;1&gt; &amp; &quot;2&quot;"/>"#));
+        assert!(xml.contains(r#"<skipped message="known failure"/>"#));
+        assert!(xml.contains(r#"<skipped message="needs &lt;&amp;&quot;feature&quot;&gt;"/>"#));
+        assert!(!xml.contains(r#"<&""#));
+        assert_eq!(xml.matches("<testcase ").count(), 4);
+    }
+}
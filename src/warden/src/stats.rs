@@ -0,0 +1,171 @@
+//! Timing statistics of benchmarks, and baselines to compare them against.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+/// Summary of the timings of repeated runs, in timestamp units
+/// (nanoseconds on most backends).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    pub runs: usize,
+    pub min: u64,
+    pub median: u64,
+    pub p95: u64,
+    pub max: u64,
+}
+
+impl Stats {
+    /// Summarize a non-empty set of samples.
+    pub fn from_samples(samples: &[u64]) -> Self {
+        assert!(!samples.is_empty(), "No samples to summarize");
+        let mut sorted = samples.to_vec();
+        sorted.sort();
+        Stats {
+            runs: sorted.len(),
+            min: sorted[0],
+            median: percentile(&sorted, 50),
+            p95: percentile(&sorted, 95),
+            max: sorted[sorted.len() - 1],
+        }
+    }
+}
+
+/// Nearest-rank percentile of sorted samples.
+fn percentile(sorted: &[u64], percent: usize) -> u64 {
+    // rounded up, and at least 1 for non-zero percents
+    let rank = (percent * sorted.len() - 1) / 100 + 1;
+    sorted[rank - 1]
+}
+
+/// Statistics of every benchmark, keyed by backend and then by `scene/test`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Baseline {
+    pub backends: BTreeMap<String, BTreeMap<String, Stats>>,
+}
+
+impl Baseline {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    pub fn insert(&mut self, backend: &str, bench: String, stats: Stats) {
+        self.backends
+            .entry(backend.to_string())
+            .or_default()
+            .insert(bench, stats);
+    }
+
+    pub fn get(&self, backend: &str, bench: &str) -> Option<&Stats> {
+        self.backends
+            .get(backend)
+            .and_then(|benches| benches.get(bench))
+    }
+}
+
+/// Slowdown of a benchmark relative to its baseline.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Regression {
+    pub backend: String,
+    pub bench: String,
+    pub baseline: u64,
+    pub current: u64,
+    /// Relative change of the median, e.g. 0.25 for 25% slower.
+    pub change: f64,
+}
+
+/// Find the benchmarks whose median got slower than the baseline by more
+/// than the threshold, as a fraction. Benchmarks missing from the baseline
+/// are not regressions.
+pub fn find_regressions(
+    baseline: &Baseline,
+    current: &Baseline,
+    threshold: f64,
+) -> Vec<Regression> {
+    let mut regressions = Vec::new();
+    for (backend, benches) in &current.backends {
+        for (bench, stats) in benches {
+            let old = match baseline.get(backend, bench) {
+                Some(old) => old,
+                None => continue,
+            };
+            let change = relative_change(old.median, stats.median);
+            if change > threshold {
+                regressions.push(Regression {
+                    backend: backend.clone(),
+                    bench: bench.clone(),
+                    baseline: old.median,
+                    current: stats.median,
+                    change,
+                });
+            }
+        }
+    }
+    regressions
+}
+
+/// Relative change from one timing to another.
+pub fn relative_change(from: u64, to: u64) -> f64 {
+    if from == 0 {
+        if to == 0 {
+            0.0
+        } else {
+            f64::INFINITY
+        }
+    } else {
+        (to as f64 - from as f64) / from as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let samples = (1 ..= 20).rev().collect::<Vec<u64>>();
+        let stats = Stats::from_samples(&samples);
+        assert_eq!(
+            stats,
+            Stats {
+                runs: 20,
+                min: 1,
+                median: 10,
+                p95: 19,
+                max: 20,
+            }
+        );
+        assert_eq!(Stats::from_samples(&[7]).p95, 7);
+    }
+
+    #[test]
+    fn test_find_regressions() {
+        let stats = |median| Stats {
+            runs: 1,
+            min: median,
+            median,
+            p95: median,
+            max: median,
+        };
+        let mut baseline = Baseline::default();
+        baseline.insert("empty", "large/copy".to_string(), stats(100));
+        baseline.insert("empty", "large/blit".to_string(), stats(100));
+        let mut current = Baseline::default();
+        current.insert("empty", "large/copy".to_string(), stats(105));
+        current.insert("empty", "large/blit".to_string(), stats(150));
+        current.insert("empty", "large/new".to_string(), stats(1000));
+
+        let regressions = find_regressions(&baseline, &current, 0.1);
+        assert_eq!(regressions.len(), 1);
+        assert_eq!(regressions[0].bench, "large/blit");
+        assert_eq!(regressions[0].change, 0.5);
+    }
+}